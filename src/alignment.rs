@@ -0,0 +1,120 @@
+use std::f64::consts::TAU;
+use crate::body::CelestialBody;
+use crate::spaceship::wrap_angle;
+
+// Bodies count as lined up with their orbital longitudes this close (2
+// degrees)
+const TOLERANCE: f64 = 0.035;
+// Conjunctions of the fastest pair looked through before giving up
+const MAX_CONJUNCTIONS: usize = 100_000;
+// Slower than this relative to each other (rad/s), two bodies keep pace
+const SAME_PACE: f64 = 1e-6;
+
+// When the chosen bodies next line up on the same side of what they orbit
+pub struct Alignment {
+    pub bodies: Vec<usize>,
+    // Simulated seconds from now
+    pub wait: f32,
+}
+
+// Direction from the orbit's center to the body in the XZ plane, on
+// average: for flat orbits it grows at exactly orbital_speed, which is
+// what makes the synodic periods below exact. Eccentric bodies are only as
+// far off it as their true anomaly is from the mean one
+fn mean_longitude(body: &CelestialBody) -> f64 {
+    let offset = body.orbit_offset(body.orbital_angle);
+    (offset.z.atan2(offset.x) - wrap_angle(body.orbital_angle) + body.mean_anomaly()) as f64
+}
+
+// Bodies that don't orbit anything are centers, in line with everything
+// and left out. Works on the kinematic orbits; n-body positions can't be
+// told ahead like this
+pub fn next_alignment(bodies: &[CelestialBody], chosen: &[usize]) -> Result<Alignment, String> {
+    let orbiting: Vec<usize> = chosen.iter().copied().filter(|&i| bodies[i].orbital_radius > 0.0).collect();
+    let [first, ref rest @ ..] = orbiting[..] else {
+        return Err("Nothing chosen orbits anything".to_string());
+    };
+    if rest.is_empty() {
+        return Err("Choose two bodies that orbit to line them up".to_string());
+    }
+
+    // Each of the others against the first: how far ahead it is and how
+    // fast it's gaining
+    let reference = &bodies[first];
+    let relative: Vec<(usize, f64, f64)> = rest.iter()
+        .map(|&i| {
+            let phase = wrap_angle((mean_longitude(&bodies[i]) - mean_longitude(reference)) as f32) as f64;
+            (i, phase, bodies[i].orbital_speed as f64 - reference.orbital_speed as f64)
+        })
+        .collect();
+    let (moving, pacing): (Vec<_>, Vec<_>) = relative.into_iter().partition(|&(_, _, rate)| rate.abs() > SAME_PACE);
+    if let Some(&(i, _, _)) = pacing.iter().find(|&&(_, phase, _)| phase.abs() > TOLERANCE) {
+        return Err(format!("{} and {} keep the same pace and never line up", reference.name, bodies[i].name));
+    }
+    let Some(&(_, phase, rate)) = moving.iter().max_by(|a, b| a.2.abs().total_cmp(&b.2.abs())) else {
+        return Err("These keep the same pace, already lined up".to_string());
+    };
+
+    // The fastest pair meets once every synodic period; the first time
+    // everything else is within the tolerance then is the answer
+    let synodic = TAU / rate.abs();
+    let mut wait = (-phase / rate).rem_euclid(synodic);
+    // A meeting going on right now doesn't count
+    if wait < synodic * 1e-3 {
+        wait += synodic;
+    }
+    for _ in 0..MAX_CONJUNCTIONS {
+        let lined_up = moving.iter().all(|&(_, phase, rate)| {
+            let apart = (phase + rate * wait).rem_euclid(TAU);
+            apart.min(TAU - apart) <= TOLERANCE
+        });
+        if lined_up {
+            return Ok(Alignment { bodies: orbiting, wait: wait as f32 });
+        }
+        wait += synodic;
+    }
+    Err(format!("They don't line up within {} conjunctions", MAX_CONJUNCTIONS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec3;
+
+    fn planet(longitude: f32, speed: f32) -> CelestialBody {
+        let mut body = CelestialBody::new_planet(format!("P{}", speed), Vec::new(), Vec3::zeros(), 10.0, speed, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+        body.orbital_angle = longitude;
+        body
+    }
+
+    #[test]
+    fn alignments_follow_the_synodic_periods() {
+        let sun = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 1.0, Color::from_hex(0xFFFFFF));
+        let mut bodies = vec![sun, planet(0.0, 0.5), planet(1.0, 0.3), planet(2.0, 0.1), planet(1.0, 0.5)];
+
+        // B starts a radian ahead and falls back at 0.2 rad/s: 5 s
+        let pair = next_alignment(&bodies, &[1, 2]).unwrap();
+        assert_eq!(pair.bodies, vec![1, 2]);
+        assert!((pair.wait - 5.0).abs() < 1e-4);
+        // C meets A every 2 pi / 0.4 s, first at 5 s as well, so all three
+        // then; the sun doesn't orbit and is left out
+        let all = next_alignment(&bodies, &[0, 1, 2, 3]).unwrap();
+        assert_eq!(all.bodies, vec![1, 2, 3]);
+        assert!((all.wait - 5.0).abs() < 1e-4);
+        // Having just met, A and B wait a whole synodic period for the next
+        for body in &mut bodies {
+            body.fast_forward(all.wait);
+        }
+        for i in [2, 3] {
+            assert!(wrap_angle((mean_longitude(&bodies[i]) - mean_longitude(&bodies[1])) as f32).abs() < 1e-4);
+        }
+        let again = next_alignment(&bodies, &[1, 2]).unwrap().wait;
+        assert!((again - std::f32::consts::TAU / 0.2).abs() < 1e-3, "{}", again);
+
+        // Equal speeds never meet; one orbiting body has nothing to meet
+        assert!(next_alignment(&bodies, &[1, 4]).is_err_and(|err| err.contains("same pace")));
+        assert!(next_alignment(&bodies, &[0, 1]).is_err());
+    }
+}
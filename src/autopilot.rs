@@ -0,0 +1,138 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+
+// Flight limits, in world units per second (and per second squared)
+const MAX_SPEED: f32 = 600.0;
+const MAX_ACCELERATION: f32 = 400.0;
+// Braking uses a bit less than the full acceleration so the ship doesn't
+// overshoot the arrival point
+const BRAKING_FRACTION: f32 = 0.8;
+
+// Close enough to the arrival point, and moving with the target
+const ARRIVAL_DISTANCE: f32 = 2.0;
+const ARRIVAL_SPEED: f32 = 10.0;
+// Gives up on a perfect match and hands off anyway after this long
+const MAX_FLIGHT_TIME: f32 = 12.0;
+
+// Bodies start pushing the ship away inside this many collision radii
+const AVOID_FACTOR: f32 = 2.0;
+
+// Flies the camera from wherever it is into orbit around a body: accelerate
+// toward where the arrival point is going to be, brake on approach and end
+// up moving with the body
+pub struct Autopilot {
+    pub target: usize,
+    pub position: Vec3,
+    velocity: Vec3,
+    // Distance from the target's center where the flight ends
+    orbit_radius: f32,
+    last_target_position: Vec3,
+    last_target_velocity: Option<Vec3>,
+    elapsed: f32,
+}
+
+impl Autopilot {
+    pub fn new(target: usize, orbit_radius: f32, position: Vec3, bodies: &[CelestialBody]) -> Self {
+        Self {
+            target,
+            position,
+            velocity: Vec3::zeros(),
+            orbit_radius,
+            last_target_position: bodies[target].position,
+            last_target_velocity: None,
+            elapsed: 0.0,
+        }
+    }
+
+    // Advances the flight by dt (real seconds), returns true once the ship
+    // has arrived
+    pub fn fly(&mut self, bodies: &[CelestialBody], dt: f32, collision_radius: impl Fn(&CelestialBody) -> f32) -> bool {
+        let target_position = bodies[self.target].position;
+        let target_velocity = (target_position - self.last_target_position) / dt;
+        let target_acceleration = self.last_target_velocity
+            .map_or(0.0, |last| (target_velocity - last).magnitude() / dt);
+        self.last_target_position = target_position;
+        self.last_target_velocity = Some(target_velocity);
+        self.elapsed += dt;
+
+        // Arrive on the side of the target the ship is coming from, so the
+        // final approach never crosses the body itself
+        let from_target = self.position - target_position;
+        let outward = if from_target.magnitude() > f32::EPSILON { from_target.normalize() } else { Vec3::y() };
+        let arrival = target_position + outward * self.orbit_radius;
+
+        let to_arrival = arrival - self.position;
+        let distance = to_arrival.magnitude();
+        let mut desired = if distance > f32::EPSILON {
+            // Fastest speed that can still brake to a stop (relative to the
+            // target) over the remaining distance
+            let closing_speed = (2.0 * MAX_ACCELERATION * BRAKING_FRACTION * distance).sqrt().min(MAX_SPEED);
+            let time_to_go = distance / closing_speed;
+            // Aim where the arrival point will be by then
+            let intercept = arrival + target_velocity * time_to_go;
+            (intercept - self.position) / time_to_go
+        } else {
+            target_velocity
+        };
+
+        // Nudge away from other bodies en route. The push fades out on final
+        // approach, where moons sharing the target's neighbourhood would
+        // otherwise keep the ship from ever settling
+        let en_route = (distance / (2.0 * self.orbit_radius)).min(1.0);
+        for (i, body) in bodies.iter().enumerate() {
+            if i == self.target || body.is_virtual() || body.is_portal() {
+                continue;
+            }
+            let radius = collision_radius(body);
+            let away = self.position - body.position;
+            let distance = away.magnitude();
+            if distance < radius * AVOID_FACTOR && distance > f32::EPSILON {
+                let strength = (1.0 - (distance - radius) / (radius * (AVOID_FACTOR - 1.0))).min(1.0);
+                desired += away / distance * (MAX_SPEED * strength * en_route);
+            }
+        }
+
+        // The target's own acceleration is allowed on top, so the ship can
+        // keep up with a body going round a tight orbit
+        let max_change = (MAX_ACCELERATION + target_acceleration) * dt;
+        let change = desired - self.velocity;
+        self.velocity += if change.magnitude() > max_change { change.normalize() * max_change } else { change };
+        self.position += self.velocity * dt;
+
+        let matched = (self.velocity - target_velocity).magnitude() < ARRIVAL_SPEED;
+        (distance < ARRIVAL_DISTANCE && matched) || self.elapsed >= MAX_FLIGHT_TIME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::ShaderType;
+
+    fn place(planet: &mut CelestialBody, dt: f32) {
+        planet.update(dt);
+        planet.position = planet.local_position;
+    }
+
+    #[test]
+    fn arrives_next_to_a_moving_body_at_its_speed() {
+        let mut planet = CelestialBody::new_planet(
+            "Planet".to_string(), Vec::new(), Vec3::zeros(), 400.0, 0.3, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+        );
+        let dt = 0.016;
+        place(&mut planet, 0.0);
+        let mut bodies = vec![planet];
+        let mut pilot = Autopilot::new(0, 50.0, Vec3::new(-600.0, 200.0, 0.0), &bodies);
+
+        let mut arrived = false;
+        while !arrived {
+            place(&mut bodies[0], dt);
+            arrived = pilot.fly(&bodies, dt, |_| 20.0);
+        }
+        assert!(pilot.elapsed < MAX_FLIGHT_TIME);
+        assert!(((pilot.position - bodies[0].position).magnitude() - 50.0).abs() < ARRIVAL_DISTANCE + 1.0);
+        // Orbital speed is 0.3 rad/s on a 400 unit orbit
+        assert!((pilot.velocity.magnitude() - 120.0).abs() < ARRIVAL_SPEED + 1.0);
+    }
+}
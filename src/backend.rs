@@ -0,0 +1,111 @@
+use minifb::{KeyRepeat, Scale, Window, WindowOptions};
+use crate::input::{InputBindings, InputFrame};
+use crate::terminal::TerminalBackend;
+
+pub const WINDOW_TITLE: &str = "Enhanced Solar System - Multi-Model 3D Renderer";
+
+// Where finished frames are shown and the keyboard is read from. The main
+// loop only talks to this, so a window and a terminal are interchangeable
+pub trait Backend {
+    // False once the user has closed it
+    fn is_open(&self) -> bool;
+    // Shows a finished frame of 0RGB pixels
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), String>;
+    // What the keyboard did since the last frame, as actions
+    fn poll_input(&mut self, bindings: &InputBindings) -> InputFrame;
+    // Frames per second to hold to, 0 for as fast as possible
+    fn set_target_fps(&mut self, fps: usize);
+    fn set_title(&mut self, _title: &str) {}
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), String>;
+}
+
+// --backend
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum BackendKind {
+    #[default]
+    Window,
+    // Colored half blocks in the terminal, for running over SSH
+    Terminal,
+}
+
+impl BackendKind {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "window" => Ok(BackendKind::Window),
+            "terminal" => Ok(BackendKind::Terminal),
+            _ => Err(format!("Unknown backend {}, expected window or terminal", name)),
+        }
+    }
+
+    pub fn open(self, width: usize, height: usize) -> Result<Box<dyn Backend>, String> {
+        match self {
+            BackendKind::Window => Ok(Box::new(WindowBackend::new(width, height)?)),
+            BackendKind::Terminal => Ok(Box::new(TerminalBackend::new()?)),
+        }
+    }
+}
+
+// A minifb window
+pub struct WindowBackend {
+    window: Window,
+    width: usize,
+    height: usize,
+    target_fps: usize,
+}
+
+impl WindowBackend {
+    pub fn new(width: usize, height: usize) -> Result<Self, String> {
+        Ok(Self { window: open_window(width, height, false)?, width, height, target_fps: 0 })
+    }
+}
+
+impl Backend for WindowBackend {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), String> {
+        self.window.update_with_buffer(buffer, width, height).map_err(|err| err.to_string())
+    }
+
+    fn poll_input(&mut self, bindings: &InputBindings) -> InputFrame {
+        let window = &self.window;
+        bindings.sample(|key| window.is_key_down(key), |key| window.is_key_pressed(key, KeyRepeat::No))
+    }
+
+    fn set_target_fps(&mut self, fps: usize) {
+        self.target_fps = fps;
+        self.window.set_target_fps(fps);
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    // The window is opened again with the other style, and told the frame
+    // rate again
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), String> {
+        self.window = open_window(self.width, self.height, fullscreen)?;
+        self.window.set_target_fps(self.target_fps);
+        Ok(())
+    }
+}
+
+// minifb has no real fullscreen mode; the closest is a borderless window
+// scaled up to fit the screen
+fn open_window(width: usize, height: usize, fullscreen: bool) -> Result<Window, String> {
+    let options = if fullscreen {
+        WindowOptions { borderless: true, scale: Scale::FitScreen, ..WindowOptions::default() }
+    } else {
+        WindowOptions::default()
+    };
+    let mut window = Window::new(WINDOW_TITLE, width, height, options)
+        .map_err(|err| err.to_string())?;
+    if fullscreen {
+        window.set_position(0, 0);
+    } else {
+        window.set_position(500, 500);
+    }
+    window.update();
+    Ok(window)
+}
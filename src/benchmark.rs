@@ -0,0 +1,193 @@
+use std::f32::consts::PI;
+use std::fmt::Write as _;
+use std::time::Instant;
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::ShaderType;
+use crate::sky::SkyMode;
+
+// Frames in a --benchmark run, split evenly between the legs of the path.
+// Changing this or the path makes new numbers incomparable with old ones
+pub const BENCHMARK_FRAMES: usize = 1200;
+const LEGS: usize = 4;
+const LEG_FRAMES: usize = BENCHMARK_FRAMES / LEGS;
+
+// Overview: half a circle around the star this far out, this many radians
+// above the orbit plane, with the dwarf planet belt in view
+const OVERVIEW_DISTANCE: f32 = 3200.0;
+const OVERVIEW_ELEVATION: f32 = 0.5;
+// Close pass: from PASS_FAR gas giant radii away down to PASS_NEAR and back
+// out, swinging half around it
+const PASS_FAR: f32 = 10.0;
+const PASS_NEAR: f32 = 1.3;
+const PASS_ELEVATION: f32 = 0.2;
+// Belt: along this many radians of it in the orbit plane, looking
+// BELT_LOOKAHEAD radians further along
+const BELT_ARC: f32 = 2.0;
+const BELT_LOOKAHEAD: f32 = 0.3;
+// Star: circling slowly this many star radii out, so it fills most of the view
+const STAR_DISTANCE: f32 = 2.5;
+const STAR_ARC: f32 = 0.5;
+
+// Parts of a frame timed separately, in the order the main loop runs them
+#[derive(Clone, Copy)]
+pub enum Stage {
+    // Input, simulation and the camera
+    Update,
+    Scene,
+    // Exposure, motion blur and the other full-frame passes
+    Effects,
+    // Everything drawn over the scene, HUD included
+    Overlays,
+    Present,
+}
+
+const STAGE_NAMES: [&str; 5] = ["update", "scene", "effects", "overlays", "present"];
+
+// --benchmark: flies a fixed path through the default scene and times every
+// frame and every stage of it
+pub struct Benchmark {
+    // Printed with the results, runs with different skies don't compare
+    sky: SkyMode,
+    frame_times: Vec<f32>,
+    stage_totals: [f32; 5],
+    frame_start: Instant,
+    lap_start: Instant,
+}
+
+impl Benchmark {
+    pub fn new(sky: SkyMode) -> Self {
+        let now = Instant::now();
+        Self { sky, frame_times: Vec::with_capacity(BENCHMARK_FRAMES), stage_totals: [0.0; 5], frame_start: now, lap_start: now }
+    }
+
+    // Camera target and position for the frame about to be drawn
+    pub fn camera(&self, bodies: &[CelestialBody]) -> (Vec3, Vec3) {
+        camera_on_path(self.frame_times.len(), bodies)
+    }
+
+    pub fn start_frame(&mut self) {
+        self.frame_start = Instant::now();
+        self.lap_start = self.frame_start;
+    }
+
+    // Adds the time since the last lap to `stage`
+    pub fn lap(&mut self, stage: Stage) {
+        let now = Instant::now();
+        self.stage_totals[stage as usize] += (now - self.lap_start).as_secs_f32();
+        self.lap_start = now;
+    }
+
+    // True once every frame of the path has been run
+    pub fn end_frame(&mut self) -> bool {
+        self.frame_times.push(self.frame_start.elapsed().as_secs_f32());
+        self.frame_times.len() >= BENCHMARK_FRAMES
+    }
+
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        let frames = self.frame_times.len();
+        let Some(stats) = FrameStats::of(&self.frame_times) else {
+            return "Benchmark stopped before the first frame\n".to_string();
+        };
+        if frames < BENCHMARK_FRAMES {
+            let _ = writeln!(out, "Benchmark stopped after {} of {} frames, not comparable", frames, BENCHMARK_FRAMES);
+        } else {
+            let _ = writeln!(out, "Benchmark: {} frames, {} sky", frames, self.sky.label());
+        }
+        let _ = writeln!(out, "  average  {:8.2} ms  {:7.1} fps", stats.average * 1000.0, 1.0 / stats.average);
+        let _ = writeln!(out, "  99th     {:8.2} ms", stats.percentile_99 * 1000.0);
+        let _ = writeln!(out, "  1% low   {:8.2} ms  {:7.1} fps", stats.one_percent_low * 1000.0, 1.0 / stats.one_percent_low);
+        let _ = writeln!(out, "Stages (total, per frame):");
+        for (name, total) in STAGE_NAMES.iter().zip(self.stage_totals) {
+            let _ = writeln!(out, "  {:<9}{:8.1} ms {:8.2} ms", name, total * 1000.0, total * 1000.0 / frames as f32);
+        }
+        out
+    }
+}
+
+// Seconds per frame: the mean, the 99th percentile and the mean of the
+// slowest 1% of frames (the "1% low")
+struct FrameStats {
+    average: f32,
+    percentile_99: f32,
+    one_percent_low: f32,
+}
+
+impl FrameStats {
+    fn of(frame_times: &[f32]) -> Option<Self> {
+        if frame_times.is_empty() {
+            return None;
+        }
+        let mut sorted = frame_times.to_vec();
+        sorted.sort_by(|a, b| b.total_cmp(a));
+        let slowest = sorted.len().div_ceil(100);
+        Some(Self {
+            average: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            percentile_99: sorted[slowest - 1],
+            one_percent_low: sorted[..slowest].iter().sum::<f32>() / slowest as f32,
+        })
+    }
+}
+
+// Where the camera is on the path at `frame`: an overview of the whole
+// system, a close pass over the gas giant, a run through the belt and a
+// long look at the star. Those are the worst cases for point bodies, big
+// meshes on screen, many bodies at once and the star shader
+fn camera_on_path(frame: usize, bodies: &[CelestialBody]) -> (Vec3, Vec3) {
+    let leg = (frame / LEG_FRAMES).min(LEGS - 1);
+    let t = (frame - leg * LEG_FRAMES) as f32 / LEG_FRAMES as f32;
+    let star = &bodies[0];
+    let around = |angle: f32, elevation: f32| Vec3::new(angle.cos() * elevation.cos(), elevation.sin(), angle.sin() * elevation.cos());
+    match leg {
+        0 => (star.position, star.position + around(PI * t, OVERVIEW_ELEVATION) * OVERVIEW_DISTANCE),
+        1 => {
+            let giant = bodies.iter().find(|body| matches!(body.shader_type, ShaderType::GasGiant)).unwrap_or(star);
+            let distance = giant.bounding_radius() * (PASS_NEAR + (PASS_FAR - PASS_NEAR) * (2.0 * t - 1.0).powi(2));
+            (giant.position, giant.position + around(PI * t, PASS_ELEVATION) * distance)
+        }
+        2 => {
+            let radius = belt_radius(bodies);
+            let angle = BELT_ARC * t;
+            (star.position + around(angle + BELT_LOOKAHEAD, 0.0) * radius, star.position + around(angle, 0.0) * radius)
+        }
+        _ => (star.position, star.position + around(STAR_ARC * t, PASS_ELEVATION) * star.bounding_radius() * STAR_DISTANCE),
+    }
+}
+
+// Average orbit of the point-drawn dwarf planets, or the outermost orbit in
+// a scene without them
+fn belt_radius(bodies: &[CelestialBody]) -> f32 {
+    let belt: Vec<f32> = bodies.iter().filter(|body| body.draw_as_point).map(|body| body.orbital_radius).collect();
+    if belt.is_empty() {
+        bodies.iter().map(|body| body.orbital_radius).fold(0.0, f32::max)
+    } else {
+        belt.iter().sum::<f32>() / belt.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{Scene, DEFAULT_SCENE};
+
+    #[test]
+    fn one_percent_low_averages_the_slowest_frames() {
+        let mut frame_times = vec![0.01; 198];
+        frame_times.extend([0.05, 0.03]);
+        let stats = FrameStats::of(&frame_times).unwrap();
+        assert!((stats.average - 0.0103).abs() < 1e-5);
+        assert_eq!(stats.percentile_99, 0.03);
+        assert!((stats.one_percent_low - 0.04).abs() < 1e-6);
+        assert!(FrameStats::of(&[]).is_none());
+    }
+
+    #[test]
+    fn path_passes_close_to_the_gas_giant_without_entering_it() {
+        let scene = Scene::load(DEFAULT_SCENE, None).unwrap();
+        let giant = scene.bodies.iter().find(|body| matches!(body.shader_type, ShaderType::GasGiant)).unwrap();
+        let clearance = |frame: usize| (camera_on_path(frame, &scene.bodies).1 - giant.position).magnitude() / giant.bounding_radius();
+        let closest = (LEG_FRAMES..2 * LEG_FRAMES).map(clearance).fold(f32::MAX, f32::min);
+        assert!((PASS_NEAR..1.5).contains(&closest), "closest {}", closest);
+    }
+}
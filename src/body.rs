@@ -0,0 +1,722 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use std::f32::consts::PI;
+use crate::vertex::Vertex;
+use crate::color::Color;
+use crate::ShaderType;
+use crate::texture::SurfaceTexture;
+use crate::shader_params::ShaderParams;
+use crate::eruptions::EruptionSettings;
+use crate::light::LightKind;
+use crate::ephemeris::true_anomaly;
+use crate::spaceship::wrap_angle;
+
+// Roughly Earth's, for bodies whose scene doesn't give one
+const DEFAULT_ALBEDO: f32 = 0.3;
+// Brightness (HSV value) of orbit rings derived from the body's color
+const PLANET_ORBIT_VALUE: f32 = 0.8;
+const MOON_ORBIT_VALUE: f32 = 0.45;
+
+// How a body's orbit ring is drawn
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitStyle {
+    pub color: u32,
+    // Pixels across
+    pub width: usize,
+    // Dashes around the whole ring, with equal gaps; None is a solid ring
+    pub dashes: Option<usize>,
+    pub visible: bool,
+}
+
+impl OrbitStyle {
+    // A shade of the body's own color, dimmer and thinner for moons so
+    // the planets' rings stand out in the overview
+    pub fn derived(color: Color, moon: bool) -> Self {
+        let (value, width) = if moon { (MOON_ORBIT_VALUE, 1) } else { (PLANET_ORBIT_VALUE, 2) };
+        Self { color: color.with_value(value).to_pixel(), width, dashes: None, visible: true }
+    }
+}
+
+// Enhanced celestial body struct for multiple models
+pub struct CelestialBody {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    // World position, written by the scene graph every frame
+    pub position: Vec3,
+    // Position relative to the parent (world space for root bodies)
+    pub local_position: Vec3,
+    pub rotation: Vec3,
+    pub scale: f32,
+    pub color: Color,
+    pub shader_type: ShaderType,  // New field for shader selection
+    // Orbital properties
+    pub orbital_center: Option<Vec3>,
+    pub orbital_radius: f32,
+    pub orbital_speed: f32,
+    pub orbital_angle: f32,
+    // Orbit shape: 0 is a circle, otherwise an ellipse with the parent at a
+    // focus and orbital_radius as the semi-major axis
+    pub eccentricity: f32,
+    // Tilt of the orbit plane (radians) about its line of nodes, which is
+    // turned by ascending_node around the vertical axis
+    pub inclination: f32,
+    pub ascending_node: f32,
+    // Self rotation
+    pub rotation_speed: f32,
+    // Parent for moons
+    pub parent_index: Option<usize>,
+    // Gravitational parameter (G * M) used when other bodies orbit this one
+    pub mass: f32,
+    // When set, orbital_speed is computed from orbital_radius (Kepler's third law)
+    pub derive_speed: bool,
+    // Linear velocity, only integrated in n-body mode
+    pub velocity: Vec3,
+    // Keeps the same hemisphere facing the parent instead of spinning freely
+    pub tidally_locked: bool,
+    // Extra rotation (radians) applied on top of the lock
+    pub lock_offset: f32,
+    // Light emitted by stars, ignored for every other body
+    pub light_color: u32,
+    pub luminosity: f32,
+    pub light_kind: LightKind,
+    // Share of starlight reflected, used for the temperature view
+    pub albedo: f32,
+    // Farthest vertex from the model origin, before scaling
+    pub mesh_radius: f32,
+    // Typical distance of the surface from the origin, before scaling: the
+    // globe without its rings or panels, which is what casts a shadow
+    pub core_radius: f32,
+    // Drawn as a shaded dot until the camera gets close (distant dwarf planets)
+    pub draw_as_point: bool,
+    // World matrix of the mesh, cached by the scene graph
+    pub model_matrix: Mat4,
+    // Centers of impact craters, as unit directions in model space so they
+    // turn with the surface; darkened by the rocky shader
+    pub craters: Vec<Vec3>,
+    // Ash fields left by eruptions, the same kind of directions as craters
+    pub ash: Vec<Vec3>,
+    // Vents erupting right now, in model space, with how brightly the lava
+    // around each one glows (0 to 1). Rewritten by the eruptions every frame
+    pub vents: Vec<(Vec3, f32)>,
+    // Rocky bodies only: how often and how hard the body erupts
+    pub eruptions: Option<EruptionSettings>,
+    // Lighting-only relief from the terrain, see the rocky shader's bump
+    // mapping; 0 leaves triangles flat shaded
+    pub bump: f32,
+    // Painted on instead of the shader's colors, see texture.rs
+    pub texture: Option<SurfaceTexture>,
+    // Colors and patterns of the star, rocky or gas giant shader
+    pub shader_params: ShaderParams,
+    // Stations only: where a ship docks, in model space so it turns and
+    // orbits with the station
+    pub docking_port: Option<Vec3>,
+    // Portals only: the body at the other end of the wormhole
+    pub portal_partner: Option<usize>,
+    pub orbit_style: OrbitStyle,
+}
+
+impl CelestialBody {
+    pub fn new_sun(name: String, vertices: Vec<Vertex>, position: Vec3, scale: f32, color: Color) -> Self {
+        let mesh_radius = mesh_radius(&vertices);
+        let core_radius = core_radius(&vertices);
+        Self {
+            name,
+            vertices,
+            position,
+            local_position: position,
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale,
+            color,
+            shader_type: ShaderType::Star,  // Sun uses Star shader
+            orbital_center: None,
+            orbital_radius: 0.0,
+            orbital_speed: 0.0,
+            orbital_angle: 0.0,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            rotation_speed: 0.1,
+            parent_index: None,
+            mass: 0.0,
+            derive_speed: false,
+            velocity: Vec3::new(0.0, 0.0, 0.0),
+            tidally_locked: false,
+            lock_offset: 0.0,
+            light_color: 0xFFFFFF,
+            luminosity: 1.0,
+            light_kind: LightKind::default(),
+            albedo: DEFAULT_ALBEDO,
+            mesh_radius,
+            core_radius,
+            draw_as_point: false,
+            model_matrix: Mat4::identity(),
+            craters: Vec::new(),
+            ash: Vec::new(),
+            vents: Vec::new(),
+            eruptions: None,
+            bump: 0.0,
+            texture: None,
+            shader_params: ShaderParams::defaults(ShaderType::Star),
+            docking_port: None,
+            portal_partner: None,
+            orbit_style: OrbitStyle::derived(color, false),
+        }
+    }
+
+    pub fn new_planet(
+        name: String,
+        vertices: Vec<Vertex>,
+        orbital_center: Vec3,
+        orbital_radius: f32,
+        orbital_speed: f32,
+        scale: f32,
+        color: Color,
+        shader_type: ShaderType,
+    ) -> Self {
+        let mesh_radius = mesh_radius(&vertices);
+        let core_radius = core_radius(&vertices);
+        Self {
+            name,
+            vertices,
+            position: Vec3::new(0.0, 0.0, 0.0), // Will be calculated
+            local_position: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale,
+            color,
+            shader_type,
+            orbital_center: Some(orbital_center),
+            orbital_radius,
+            orbital_speed,
+            orbital_angle: 0.0,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            rotation_speed: 0.3,
+            parent_index: None,
+            mass: 0.0,
+            derive_speed: false,
+            velocity: Vec3::new(0.0, 0.0, 0.0),
+            tidally_locked: false,
+            lock_offset: 0.0,
+            light_color: 0xFFFFFF,
+            luminosity: 1.0,
+            light_kind: LightKind::default(),
+            albedo: DEFAULT_ALBEDO,
+            mesh_radius,
+            core_radius,
+            draw_as_point: false,
+            model_matrix: Mat4::identity(),
+            craters: Vec::new(),
+            ash: Vec::new(),
+            vents: Vec::new(),
+            eruptions: None,
+            bump: 0.0,
+            texture: None,
+            shader_params: ShaderParams::defaults(shader_type),
+            docking_port: None,
+            portal_partner: None,
+            orbit_style: OrbitStyle::derived(color, false),
+        }
+    }
+
+    pub fn new_moon(
+        name: String,
+        vertices: Vec<Vertex>,
+        parent_index: usize,
+        orbital_radius: f32,
+        orbital_speed: f32,
+        scale: f32,
+        color: Color,
+        shader_type: ShaderType,
+    ) -> Self {
+        let mesh_radius = mesh_radius(&vertices);
+        let core_radius = core_radius(&vertices);
+        Self {
+            name,
+            vertices,
+            position: Vec3::new(0.0, 0.0, 0.0), // Will be calculated
+            local_position: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale,
+            color,
+            shader_type,
+            orbital_center: None, // Will use parent position
+            orbital_radius,
+            orbital_speed,
+            orbital_angle: 0.0,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            rotation_speed: 0.5,
+            parent_index: Some(parent_index),
+            mass: 0.0,
+            derive_speed: false,
+            velocity: Vec3::new(0.0, 0.0, 0.0),
+            tidally_locked: false,
+            lock_offset: 0.0,
+            light_color: 0xFFFFFF,
+            luminosity: 1.0,
+            light_kind: LightKind::default(),
+            albedo: DEFAULT_ALBEDO,
+            mesh_radius,
+            core_radius,
+            draw_as_point: false,
+            model_matrix: Mat4::identity(),
+            craters: Vec::new(),
+            ash: Vec::new(),
+            vents: Vec::new(),
+            eruptions: None,
+            bump: 0.0,
+            texture: None,
+            shader_params: ShaderParams::defaults(shader_type),
+            docking_port: None,
+            portal_partner: None,
+            orbit_style: OrbitStyle::derived(color, true),
+        }
+    }
+
+    // Moves the body along its orbit; the world position follows once the
+    // scene graph runs
+    pub fn update(&mut self, delta_time: f32) {
+        // Update orbital angle
+        self.orbital_angle += self.angular_rate() * delta_time;
+        
+        // Update rotation
+        self.spin(delta_time);
+
+        // Bodies with a parent orbit the origin of the parent's frame, a fixed
+        // orbital center is already in world space
+        let center = if self.parent_index.is_some() { Some(Vec3::zeros()) } else { self.orbital_center };
+        if let Some(center) = center {
+            let offset = self.orbit_offset(self.orbital_angle);
+            self.local_position = center + offset;
+
+            if self.tidally_locked {
+                self.apply_tidal_lock(offset);
+            }
+        }
+    }
+
+    // Takes everything the scene file sets from the same body loaded again,
+    // keeping where it has got to along its orbit and spin, its velocity,
+    // craters and ash. Bodies that don't orbit move to their new position,
+    // and portals, which don't spin, turn to face their new way
+    pub fn take_settings(&mut self, loaded: CelestialBody) {
+        let orbits = self.parent_index.is_some() || self.orbital_center.is_some();
+        *self = CelestialBody {
+            position: self.position,
+            local_position: if orbits { self.local_position } else { loaded.local_position },
+            orbital_angle: self.orbital_angle,
+            rotation: if loaded.is_portal() { loaded.rotation } else { Vec3::new(self.rotation.x, self.rotation.y, loaded.rotation.z) },
+            velocity: self.velocity,
+            model_matrix: self.model_matrix,
+            craters: std::mem::take(&mut self.craters),
+            ash: std::mem::take(&mut self.ash),
+            ..loaded
+        };
+    }
+
+    // Self rotation only; in n-body mode the position comes from the integrator
+    pub fn spin(&mut self, delta_time: f32) {
+        if !self.tidally_locked {
+            self.rotation.y += self.rotation_speed * delta_time;
+        }
+    }
+
+    // How fast the body turns about its own axis, rad/s; tidally locked
+    // bodies turn once an orbit (see apply_tidal_lock)
+    pub fn spin_rate(&self) -> f32 {
+        if self.tidally_locked { -self.angular_rate() } else { self.rotation_speed }
+    }
+
+    // Spin angle that points the body's local +X hemisphere at its parent.
+    // Only the spin (rotation.y) is replaced, so an axial tilt in rotation.z
+    // still tilts the locked body's spin axis.
+    // `offset` goes from the parent to the body.
+    pub fn apply_tidal_lock(&mut self, offset: Vec3) {
+        if offset.x == 0.0 && offset.z == 0.0 {
+            return;
+        }
+        // Ry(angle) maps local +X to (cos(angle), 0, -sin(angle)), which has to
+        // match the direction toward the parent, -offset
+        let orbit_angle = offset.z.atan2(offset.x);
+        self.rotation.y = PI - orbit_angle + self.lock_offset;
+    }
+
+    // Offset from the orbit center at a given angle along the orbit (the true
+    // anomaly, measured from periapsis). Flat circular orbits reduce to
+    // (r cos, 0, r sin) in the XZ plane.
+    pub fn orbit_offset(&self, angle: f32) -> Vec3 {
+        let e = self.eccentricity;
+        let radius = self.orbital_radius * (1.0 - e * e) / (1.0 + e * angle.cos());
+        let (x, z) = (radius * angle.cos(), radius * angle.sin());
+
+        // Tilt about the X axis, then turn the line of nodes around Y
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        let (y, z) = (-z * sin_i, z * cos_i);
+        let (sin_node, cos_node) = self.ascending_node.sin_cos();
+        Vec3::new(x * cos_node + z * sin_node, y, -x * sin_node + z * cos_node)
+    }
+
+    // How fast orbital_angle changes right now. orbital_speed is the mean
+    // angular speed; eccentric orbits go faster near periapsis and slower
+    // near apoapsis (Kepler's second law).
+    pub fn angular_rate(&self) -> f32 {
+        let e = self.eccentricity;
+        if e <= 0.0 {
+            return self.orbital_speed;
+        }
+        let swept = 1.0 + e * self.orbital_angle.cos();
+        self.orbital_speed * swept * swept / (1.0 - e * e).powf(1.5)
+    }
+
+    // Where along its orbit the body is on average, in (-PI, PI]: unlike
+    // orbital_angle it grows at a steady orbital_speed. Circular orbits
+    // have the two the same
+    pub fn mean_anomaly(&self) -> f32 {
+        let (e, angle) = (self.eccentricity, wrap_angle(self.orbital_angle));
+        if e <= 0.0 {
+            return angle;
+        }
+        let half = angle / 2.0;
+        let eccentric = 2.0 * ((1.0 - e).sqrt() * half.sin()).atan2((1.0 + e).sqrt() * half.cos());
+        eccentric - e * eccentric.sin()
+    }
+
+    // Jumps `seconds` of simulated time ahead in one step, however many
+    // orbits that is: along the orbit by Kepler's equation, which update()
+    // only follows as closely as its frame steps, and the spin at its rate
+    pub fn fast_forward(&mut self, seconds: f32) {
+        let e = self.eccentricity;
+        if e <= 0.0 {
+            self.orbital_angle += self.orbital_speed * seconds;
+        } else {
+            let start = wrap_angle(self.orbital_angle);
+            let mean = self.mean_anomaly() + self.orbital_speed * seconds;
+            let turns = (mean / (2.0 * PI)).round();
+            let angle = true_anomaly((mean - turns * 2.0 * PI) as f64, e as f64) as f32;
+            self.orbital_angle += turns * 2.0 * PI + angle - start;
+        }
+        self.spin(seconds);
+        self.update(0.0);
+    }
+
+    // +1 for counter-clockwise (seen from above), -1 for retrograde orbits
+    pub fn orbit_direction(&self) -> f32 {
+        if self.orbital_speed < 0.0 { -1.0 } else { 1.0 }
+    }
+
+    // Center of the orbit this frame, None for bodies that don't orbit
+    pub fn orbit_center(&self, bodies: &[CelestialBody]) -> Option<Vec3> {
+        self.parent_index
+            .and_then(|parent_idx| bodies.get(parent_idx))
+            .map(|parent| parent.position)
+            .or(self.orbital_center)
+    }
+
+    // Orbits something that itself orbits, like a planet's moon
+    pub fn is_moon(&self, bodies: &[CelestialBody]) -> bool {
+        self.parent_index
+            .and_then(|parent| bodies.get(parent))
+            .is_some_and(|parent| parent.parent_index.is_some())
+    }
+
+    // Stars are drawn at full brightness and light everything else
+    pub fn is_star(&self) -> bool {
+        matches!(self.shader_type, ShaderType::Star)
+    }
+
+    // Barycenters and other mesh-less nodes only exist to be orbited
+    pub fn is_virtual(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    // Portals are flown through, nothing collides with them
+    pub fn is_portal(&self) -> bool {
+        self.portal_partner.is_some()
+    }
+
+    // World position of the docking port, for stations that have one
+    pub fn docking_port_position(&self) -> Option<Vec3> {
+        self.docking_port.map(|port| (self.model_matrix * Vec4::new(port.x, port.y, port.z, 1.0)).xyz())
+    }
+
+    // Radius of a sphere around the body that contains its whole mesh; used
+    // for collision and level of detail instead of assuming a unit sphere
+    pub fn bounding_radius(&self) -> f32 {
+        self.mesh_radius * self.scale
+    }
+
+    // Sphere that stands in for the body in eclipses
+    pub fn shadow_radius(&self) -> f32 {
+        self.core_radius * self.scale
+    }
+
+    // Time for one full orbit, None for bodies that don't orbit
+    pub fn orbital_period(&self) -> Option<f32> {
+        if self.orbital_speed.abs() > f32::EPSILON {
+            Some(2.0 * PI / self.orbital_speed.abs())
+        } else {
+            None
+        }
+    }
+
+    pub fn get_model_matrix(&self) -> Mat4 {
+        self.model_matrix
+    }
+}
+
+// Distance from the model origin to its farthest vertex, so rings, solar
+// panels and other non-spherical parts are covered
+pub fn mesh_radius(vertices: &[Vertex]) -> f32 {
+    vertices.iter().map(|vertex| vertex.position.magnitude()).fold(0.0, f32::max)
+}
+
+// Median distance of the vertices from the model origin. Rings and other
+// thin parts have far fewer vertices than the globe they surround
+pub fn core_radius(vertices: &[Vertex]) -> f32 {
+    let mut distances: Vec<f32> = vertices.iter().map(|vertex| vertex.position.magnitude()).collect();
+    distances.sort_by(f32::total_cmp);
+    distances.get(distances.len() / 2).copied().unwrap_or(0.0)
+}
+
+// Drawn body whose surface (bounding sphere) is closest to a point, with the
+// altitude above it; negative altitudes are inside the sphere
+pub fn nearest_body(bodies: &[CelestialBody], point: Vec3) -> Option<(usize, f32)> {
+    bodies.iter()
+        .enumerate()
+        .filter(|(_, body)| !body.is_virtual())
+        .map(|(i, body)| (i, (point - body.position).magnitude() - body.bounding_radius()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+// Kepler's third law for a circular orbit: T^2 = 4 * PI^2 * r^3 / (G * M),
+// which gives an angular speed of sqrt(G * M / r^3)
+pub fn kepler_angular_speed(central_mass: f32, orbital_radius: f32) -> f32 {
+    if central_mass <= 0.0 || orbital_radius <= 0.0 {
+        return 0.0;
+    }
+    (central_mass / orbital_radius.powi(3)).sqrt()
+}
+
+// Body indices ordered by depth in the orbit hierarchy, so every parent comes
+// before its children (star -> planet -> moon -> station ...)
+pub fn hierarchy_order(bodies: &[CelestialBody]) -> Result<Vec<usize>, String> {
+    let mut depths = Vec::with_capacity(bodies.len());
+    for (i, body) in bodies.iter().enumerate() {
+        let mut depth = 0;
+        let mut current = body.parent_index;
+        while let Some(parent_idx) = current {
+            depth += 1;
+            if depth > bodies.len() {
+                return Err(format!("{} is part of a parent cycle", bodies[i].name));
+            }
+            current = bodies.get(parent_idx).and_then(|parent| parent.parent_index);
+        }
+        depths.push(depth);
+    }
+
+    let mut order: Vec<usize> = (0..bodies.len()).collect();
+    order.sort_by_key(|&i| depths[i]);
+    Ok(order)
+}
+
+// Recompute the orbital speed of every body flagged with derive_speed from its
+// radius and the mass of whatever it orbits. Planets placed with a fixed
+// orbital_center orbit the scene's central mass.
+pub fn apply_kepler_speeds(bodies: &mut [CelestialBody], central_mass: f32) {
+    for i in 0..bodies.len() {
+        if !bodies[i].derive_speed {
+            continue;
+        }
+        let parent_mass = match bodies[i].parent_index {
+            Some(parent_idx) if parent_idx < bodies.len() => bodies[parent_idx].mass,
+            _ => central_mass,
+        };
+        // Keep the direction of travel, only the magnitude comes from Kepler
+        let direction = bodies[i].orbit_direction();
+        bodies[i].orbital_speed = direction * kepler_angular_speed(parent_mass, bodies[i].orbital_radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbody::init_circular_velocities;
+    use crate::scene_graph::{SceneGraph, Transform};
+
+    fn orbiting_pair(orbital_speed: f32) -> Vec<CelestialBody> {
+        let mut sun = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 1.0, Color::from_hex(0xFFFFFF));
+        sun.mass = 1000.0;
+        let mut planet = CelestialBody::new_planet(
+            "Planet".to_string(),
+            Vec::new(),
+            Vec3::zeros(),
+            10.0,
+            orbital_speed,
+            1.0,
+            Color::from_hex(0xFFFFFF),
+            ShaderType::RockyPlanet,
+        );
+        planet.parent_index = Some(0);
+        vec![sun, planet]
+    }
+
+    #[test]
+    fn retrograde_orbit_moves_clockwise() {
+        let mut bodies = orbiting_pair(-0.5);
+        bodies[1].update(0.0);
+        bodies[1].position = bodies[1].local_position;
+        assert!((bodies[1].position - Vec3::new(10.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+        bodies[1].update(0.1);
+        assert!(bodies[1].orbital_angle < 0.0);
+        // Counter-clockwise orbits move toward +z first, retrograde ones toward -z
+        assert!(bodies[1].local_position.z < 0.0);
+        assert_eq!(bodies[1].orbit_direction(), -1.0);
+    }
+
+    #[test]
+    fn children_follow_parent_in_the_same_frame() {
+        let mut bodies = orbiting_pair(0.5);
+        // Declared child-first on purpose: the graph must still place the
+        // planet before its moon
+        let moon = CelestialBody::new_moon("Moon".to_string(), Vec::new(), 2, 2.0, 3.0, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+        bodies.insert(0, moon);
+        bodies[2].parent_index = Some(1);
+        assert_eq!(hierarchy_order(&bodies).unwrap(), vec![1, 2, 0]);
+        let mut graph = SceneGraph::from_bodies(&bodies).unwrap();
+
+        for _ in 0..10 {
+            for body in bodies.iter_mut() {
+                body.update(0.25);
+            }
+            graph.update(&mut bodies);
+            let offset = bodies[0].position - bodies[2].position;
+            assert!((offset.magnitude() - 2.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn eccentric_orbit_speeds_up_near_periapsis() {
+        let mut bodies = orbiting_pair(0.5);
+        bodies[1].eccentricity = 0.5;
+        bodies[1].inclination = 0.4;
+        let periapsis = bodies[1].orbit_offset(0.0).magnitude();
+        let apoapsis = bodies[1].orbit_offset(PI).magnitude();
+        assert!((periapsis - 5.0).abs() < 1e-4);
+        assert!((apoapsis - 15.0).abs() < 1e-4);
+
+        let near = bodies[1].angular_rate();
+        bodies[1].orbital_angle = PI;
+        let far = bodies[1].angular_rate();
+        // Equal areas in equal times: angular speed goes with 1 / r^2
+        assert!((near / far - 9.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn nearest_body_goes_by_surface_not_center() {
+        let vertex = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::y(), nalgebra_glm::Vec2::zeros());
+        let big = CelestialBody::new_sun("Big".to_string(), vec![vertex.clone()], Vec3::zeros(), 50.0, Color::from_hex(0xFFFFFF));
+        let small = CelestialBody::new_sun("Small".to_string(), vec![vertex], Vec3::new(100.0, 0.0, 0.0), 1.0, Color::from_hex(0xFFFFFF));
+        let barycenter = CelestialBody::new_sun("Barycenter".to_string(), Vec::new(), Vec3::new(70.0, 0.0, 0.0), 1.0, Color::from_hex(0xFFFFFF));
+        let bodies = vec![big, small, barycenter];
+
+        // 70 from Big's center but only 20 above its surface, 29 above Small
+        let (index, altitude) = nearest_body(&bodies, Vec3::new(70.0, 0.0, 0.0)).unwrap();
+        assert_eq!(index, 0);
+        assert!((altitude - 20.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn parent_cycles_are_rejected() {
+        let mut bodies = orbiting_pair(0.5);
+        bodies[0].parent_index = Some(1);
+        assert!(hierarchy_order(&bodies).is_err());
+    }
+
+    #[test]
+    fn reloaded_settings_keep_the_body_where_it_is() {
+        let mut bodies = orbiting_pair(0.5);
+        bodies[1].update(2.0);
+        bodies[1].craters.push(Vec3::x());
+        let (angle, spin) = (bodies[1].orbital_angle, bodies[1].rotation.y);
+
+        let mut loaded = orbiting_pair(0.8).remove(1);
+        loaded.color = Color::from_hex(0xFF0000);
+        loaded.rotation.z = 0.4;
+        bodies[1].take_settings(loaded);
+        assert_eq!(bodies[1].orbital_speed, 0.8);
+        assert_eq!(bodies[1].color.to_pixel(), 0xFF0000);
+        assert_eq!((bodies[1].orbital_angle, bodies[1].rotation.y, bodies[1].rotation.z), (angle, spin, 0.4));
+        assert_eq!(bodies[1].craters, vec![Vec3::x()]);
+    }
+
+    #[test]
+    fn negative_rotation_speed_spins_backwards() {
+        let mut bodies = orbiting_pair(0.5);
+        bodies[1].rotation_speed = -0.4;
+        bodies[1].spin(1.0);
+        assert!((bodies[1].rotation.y + 0.4).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tidal_lock_keeps_near_side_toward_parent() {
+        for orbital_speed in [0.7, -0.7] {
+            let mut bodies = orbiting_pair(orbital_speed);
+            bodies[1].tidally_locked = true;
+            bodies[1].rotation.z = 0.3; // tilt must not change the spin angle
+            for _ in 0..50 {
+                bodies[1].update(0.37);
+                let facing = Transform::from_euler(Vec3::zeros(), Vec3::new(0.0, bodies[1].rotation.y, 0.0), 1.0).matrix()
+                    * nalgebra_glm::Vec4::new(1.0, 0.0, 0.0, 0.0);
+                let toward_parent = -bodies[1].local_position.normalize();
+                assert!((facing.xyz() - toward_parent).magnitude() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn kepler_speed_keeps_retrograde_sign() {
+        let mut bodies = orbiting_pair(-1.0);
+        bodies[1].derive_speed = true;
+        apply_kepler_speeds(&mut bodies, 0.0);
+        let expected = kepler_angular_speed(1000.0, 10.0);
+        assert!((bodies[1].orbital_speed + expected).abs() < 1e-6);
+        assert!((bodies[1].orbital_period().unwrap() - 2.0 * PI / expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn nbody_velocity_follows_orbit_direction() {
+        let mut prograde = orbiting_pair(1.0);
+        let mut retrograde = orbiting_pair(-1.0);
+        for bodies in [&mut prograde, &mut retrograde] {
+            bodies[1].update(0.0);
+            bodies[1].position = bodies[1].local_position;
+            init_circular_velocities(bodies, &[0, 1]);
+        }
+        assert!(prograde[1].velocity.z > 0.0);
+        assert!(retrograde[1].velocity.z < 0.0);
+        assert!((prograde[1].velocity.magnitude() - retrograde[1].velocity.magnitude()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fast_forward_lands_where_small_steps_do() {
+        let eccentric = || {
+            let mut planet = orbiting_pair(0.5).remove(1);
+            planet.eccentricity = 0.3;
+            planet.rotation_speed = 0.2;
+            planet
+        };
+        // A bit over three orbits
+        let (mut stepped, mut jumped) = (eccentric(), eccentric());
+        for _ in 0..40_000 {
+            stepped.update(0.001);
+        }
+        jumped.fast_forward(40.0);
+        assert!((stepped.local_position - jumped.local_position).magnitude() < 0.01);
+        assert!((stepped.orbital_angle - jumped.orbital_angle).abs() < 1e-3);
+        assert!((jumped.rotation.y - 8.0).abs() < 1e-4);
+        // Mean anomaly runs at the mean speed
+        assert!((wrap_angle(jumped.mean_anomaly() - 20.0)).abs() < 1e-3);
+    }
+}
@@ -1,5 +1,10 @@
-use nalgebra_glm::{Vec3, Mat4, normalize, cross, dot};
+use nalgebra_glm::{Vec3, Mat4, cross, dot};
+use crate::geometry::normalize_or;
 
+pub const DEFAULT_MIN_DISTANCE: f32 = 100.0;
+const MAX_DISTANCE: f32 = 2000.0;
+
+#[derive(Clone)]
 pub struct Camera {
     pub position: Vec3,
     pub target: Vec3,
@@ -8,6 +13,8 @@ pub struct Camera {
     pub distance: f32,
     pub theta: f32,    // Horizontal angle (azimuth)
     pub phi: f32,      // Vertical angle (elevation)
+    // Closest the camera may get to its target, so it can't clip into it
+    pub min_distance: f32,
 }
 
 impl Camera {
@@ -19,6 +26,7 @@ impl Camera {
             distance,
             theta: 0.0,
             phi: 0.0,
+            min_distance: DEFAULT_MIN_DISTANCE,
         }
     }
 
@@ -35,6 +43,22 @@ impl Camera {
         self.position = self.target + Vec3::new(x, y, z);
     }
 
+    // Moves the point the camera orbits around, keeping distance and angles
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+        self.update_position();
+    }
+
+    // Puts the camera at a world position, still looking at its target
+    pub fn move_to(&mut self, position: Vec3) {
+        let offset = position - self.target;
+        self.distance = offset.magnitude().max(f32::EPSILON);
+        self.theta = offset.z.atan2(offset.x);
+        // Same limits as orbit(), straight above or below the view flips
+        self.phi = (offset.y / self.distance).acos().clamp(0.1, std::f32::consts::PI - 0.1);
+        self.update_position();
+    }
+
     pub fn look_at(&self) -> Mat4 {
         look_at_matrix(self.position, self.target, self.up)
     }
@@ -52,17 +76,24 @@ impl Camera {
     pub fn zoom(&mut self, delta_distance: f32) {
         self.distance += delta_distance;
         // Set reasonable distance bounds to prevent getting too close or too far
-        self.distance = self.distance.clamp(100.0, 2000.0); // Minimum 100, maximum 2000
+        self.distance = self.distance.clamp(self.min_distance, MAX_DISTANCE);
         self.update_position();
     }
+
+    // Changes the collision distance, backing off right away if already closer
+    pub fn set_min_distance(&mut self, min_distance: f32) {
+        self.min_distance = min_distance;
+        self.zoom(0.0);
+    }
 }
 
 /// Implementation of the LookAt function from OpenGL
 /// Creates a view matrix that transforms from world space to camera space
 pub fn look_at_matrix(eye: Vec3, at: Vec3, up: Vec3) -> Mat4 {
-    // Create the camera coordinate system
-    let mut zaxis = normalize(&(at - eye));    // Forward vector (toward target)
-    let xaxis = normalize(&cross(&zaxis, &up)); // Right vector
+    // Create the camera coordinate system. With the eye on the target, or
+    // looking straight along `up`, fall back to an axis instead of NaN
+    let mut zaxis = normalize_or(at - eye, -Vec3::z());    // Forward vector (toward target)
+    let xaxis = normalize_or(cross(&zaxis, &up), Vec3::x()); // Right vector
     let yaxis = cross(&xaxis, &zaxis);         // Up vector
     
     // Negate z-axis to create right-handed coordinate system
@@ -0,0 +1,270 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+
+// How long the "saved to ..." note stays on screen
+const MESSAGE_SECONDS: f32 = 4.0;
+// NeuQuant sampling factor for GIF palettes: 1 is best, 30 is fastest
+const GIF_QUANTIZE_SPEED: i32 = 10;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureFormat {
+    // One animated GIF per recording
+    #[default]
+    Gif,
+    // A folder of numbered PNG frames
+    Png,
+}
+
+// [recording] section of the scene file (F9 starts and stops a capture)
+#[derive(Deserialize, Clone)]
+pub struct RecordingSettings {
+    #[serde(default)]
+    pub format: CaptureFormat,
+    // Keep every Nth presented frame
+    #[serde(default = "default_every_nth")]
+    pub every_nth: usize,
+    // Frames stop being queued once they'd take more memory than this
+    #[serde(default = "default_max_megabytes")]
+    pub max_megabytes: usize,
+    #[serde(default = "default_directory")]
+    pub directory: String,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            format: CaptureFormat::default(),
+            every_nth: default_every_nth(),
+            max_megabytes: default_max_megabytes(),
+            directory: default_directory(),
+        }
+    }
+}
+
+fn default_every_nth() -> usize {
+    2
+}
+
+fn default_max_megabytes() -> usize {
+    512
+}
+
+fn default_directory() -> String {
+    "captures".to_string()
+}
+
+// Queues presented frames while recording and writes them out on a
+// background thread once recording stops, so the render loop never waits
+// on the encoder
+pub struct Recorder {
+    settings: RecordingSettings,
+    width: usize,
+    height: usize,
+    frames: Vec<Vec<u32>>,
+    recording: bool,
+    frames_seen: usize,
+    // Real time the recording has been running, for GIF frame delays
+    recorded_seconds: f32,
+    // Set when the memory cap was hit; frames are dropped until F9
+    pub full: bool,
+    jobs: Vec<JoinHandle<Result<String, String>>>,
+    message: Option<(String, f32)>,
+}
+
+impl Recorder {
+    pub fn new(settings: RecordingSettings, width: usize, height: usize) -> Self {
+        Self {
+            settings,
+            width,
+            height,
+            frames: Vec::new(),
+            recording: false,
+            frames_seen: 0,
+            recorded_seconds: 0.0,
+            full: false,
+            jobs: Vec::new(),
+            message: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    // Most frames one recording may queue under the memory cap
+    pub fn max_frames(&self) -> usize {
+        let frame_bytes = self.width * self.height * std::mem::size_of::<u32>();
+        (self.settings.max_megabytes * 1024 * 1024 / frame_bytes.max(1)).max(1)
+    }
+
+    // Starts a recording, or stops the current one and hands its frames to
+    // an encoder thread
+    pub fn toggle(&mut self) {
+        if !self.recording {
+            self.recording = true;
+            self.frames_seen = 0;
+            self.recorded_seconds = 0.0;
+            self.full = false;
+            return;
+        }
+        self.recording = false;
+        self.full = false;
+        let frames = std::mem::take(&mut self.frames);
+        if frames.is_empty() {
+            return;
+        }
+        let (width, height, format) = (self.width, self.height, self.settings.format);
+        let path = capture_path(&self.settings.directory, format);
+        // GIF delays are in hundredths of a second; frames are played back
+        // at the average rate they were captured at
+        let delay = (self.recorded_seconds / frames.len() as f32 * 100.0).round().max(1.0) as u16;
+        self.jobs.push(std::thread::spawn(move || {
+            let written = match format {
+                CaptureFormat::Gif => write_gif(&path, &frames, width, height, delay),
+                CaptureFormat::Png => write_pngs(&path, &frames, width, height),
+            };
+            written.map(|_| path.display().to_string())
+        }));
+    }
+
+    // Called once per presented frame, `seconds` after the previous one
+    pub fn capture(&mut self, buffer: &[u32], seconds: f32) {
+        if !self.recording || self.full {
+            return;
+        }
+        self.recorded_seconds += seconds;
+        let keep = self.frames_seen.is_multiple_of(self.settings.every_nth.max(1));
+        self.frames_seen += 1;
+        if !keep {
+            return;
+        }
+        if self.frames.len() >= self.max_frames() {
+            self.full = true;
+            return;
+        }
+        self.frames.push(buffer.to_vec());
+    }
+
+    // Collects finished encoder threads and remembers how they went
+    pub fn update(&mut self, time: f32) {
+        let (finished, running): (Vec<_>, Vec<_>) = self.jobs.drain(..).partition(|job| job.is_finished());
+        self.jobs = running;
+        for job in finished {
+            let text = match job.join() {
+                Ok(Ok(path)) => format!("Saved {}", path),
+                Ok(Err(err)) => format!("Capture failed: {}", err),
+                Err(_) => "Capture failed: encoder crashed".to_string(),
+            };
+            self.message = Some((text, time));
+        }
+        if matches!(&self.message, Some((_, since)) if time - since > MESSAGE_SECONDS) {
+            self.message = None;
+        }
+    }
+
+    pub fn is_saving(&self) -> bool {
+        !self.jobs.is_empty()
+    }
+
+    pub fn directory(&self) -> &str {
+        &self.settings.directory
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_ref().map(|(text, _)| text.as_str())
+    }
+}
+
+// captures/capture_<unix time>.gif, or a capture_<unix time>/ folder for PNGs
+fn capture_path(directory: &str, format: CaptureFormat) -> PathBuf {
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let name = match format {
+        CaptureFormat::Gif => format!("capture_{}.gif", stamp),
+        CaptureFormat::Png => format!("capture_{}", stamp),
+    };
+    Path::new(directory).join(name)
+}
+
+// Writes one still (photo mode's F12) next to the clips, returning its path
+pub fn save_photo(directory: &str, pixels: &[u32], width: usize, height: usize) -> Result<String, String> {
+    let path = capture_path(directory, CaptureFormat::Png).with_extension("png");
+    fs::create_dir_all(directory).map_err(|e| format!("{}: {}", directory, e))?;
+    write_png(&path, pixels, width, height)?;
+    Ok(path.display().to_string())
+}
+
+fn to_rgb(pixels: &[u32]) -> Vec<u8> {
+    pixels.iter().flat_map(|&p| [(p >> 16) as u8, (p >> 8) as u8, p as u8]).collect()
+}
+
+fn write_gif(path: &Path, frames: &[Vec<u32>], width: usize, height: usize, delay: u16) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+    }
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut encoder = gif::Encoder::new(BufWriter::new(file), width as u16, height as u16, &[])
+        .map_err(|e| e.to_string())?;
+    encoder.set_repeat(gif::Repeat::Infinite).map_err(|e| e.to_string())?;
+    for pixels in frames {
+        // Each frame gets its own quantized palette
+        let mut frame = gif::Frame::from_rgb_speed(width as u16, height as u16, &to_rgb(pixels), GIF_QUANTIZE_SPEED);
+        frame.delay = delay;
+        encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn write_pngs(directory: &Path, frames: &[Vec<u32>], width: usize, height: usize) -> Result<(), String> {
+    fs::create_dir_all(directory).map_err(|e| format!("{}: {}", directory.display(), e))?;
+    for (i, pixels) in frames.iter().enumerate() {
+        write_png(&directory.join(format!("frame_{:04}.png", i)), pixels, width, height)?;
+    }
+    Ok(())
+}
+
+pub fn write_png(path: &Path, pixels: &[u32], width: usize, height: usize) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(&to_rgb(pixels)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_every_nth_frame_up_to_the_memory_cap() {
+        let settings = RecordingSettings { every_nth: 3, max_megabytes: 1, ..RecordingSettings::default() };
+        // 256 KiB frames, so four fit in a megabyte
+        let mut recorder = Recorder::new(settings, 256, 256);
+        let frame = vec![0u32; 256 * 256];
+
+        recorder.capture(&frame, 0.016);
+        assert_eq!(recorder.frame_count(), 0);
+
+        recorder.toggle();
+        for _ in 0..7 {
+            recorder.capture(&frame, 0.016);
+        }
+        // Frames 1, 4 and 7
+        assert_eq!(recorder.frame_count(), 3);
+
+        for _ in 0..6 {
+            recorder.capture(&frame, 0.016);
+        }
+        assert_eq!(recorder.frame_count(), recorder.max_frames());
+        assert!(recorder.full);
+    }
+}
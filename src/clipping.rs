@@ -0,0 +1,114 @@
+use nalgebra_glm::Vec4;
+use crate::color::Color;
+use crate::vertex::Vertex;
+
+// Triangles are cut where they leave this many times the view's width or
+// height, well off screen. Whatever is left stays small enough for the
+// rasterizer's fixed point edge functions, and the viewport clamp there
+// trims the rest
+const GUARD_BAND: f32 = 16.0;
+
+// Clip space planes a point is inside of when its dot product with them is
+// at least 0: the near plane, then the guard band on each side. There's no
+// far plane, the sky sphere reaches past it
+const PLANES: [Vec4; 5] = [
+    Vec4::new(0.0, 0.0, 1.0, 1.0),
+    Vec4::new(1.0, 0.0, 0.0, GUARD_BAND),
+    Vec4::new(-1.0, 0.0, 0.0, GUARD_BAND),
+    Vec4::new(0.0, 1.0, 0.0, GUARD_BAND),
+    Vec4::new(0.0, -1.0, 0.0, GUARD_BAND),
+];
+
+// The parts of a triangle in clip space (see vertex_shader) that are in
+// front of the near plane, before the perspective divide: none when all of
+// it is behind, the triangle itself when none is, else a fan. The near plane
+// alone makes at most two triangles, the guard band a few more
+pub fn clip_triangle(triangle: [Vertex; 3]) -> Vec<[Vertex; 3]> {
+    let inside = |vertex: &Vertex| PLANES.iter().all(|plane| plane.dot(&vertex.clip_position) >= 0.0);
+    if triangle.iter().all(inside) {
+        return vec![triangle];
+    }
+
+    let mut polygon = triangle.to_vec();
+    for plane in &PLANES {
+        polygon = clip_polygon(&polygon, plane);
+        if polygon.len() < 3 {
+            return Vec::new();
+        }
+    }
+    (1..polygon.len() - 1)
+        .map(|i| [polygon[0].clone(), polygon[i].clone(), polygon[i + 1].clone()])
+        .collect()
+}
+
+// One Sutherland-Hodgman pass: the polygon's corners inside the plane, plus
+// a new one wherever an edge crosses it
+fn clip_polygon(polygon: &[Vertex], plane: &Vec4) -> Vec<Vertex> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, current) in polygon.iter().enumerate() {
+        let next = &polygon[(i + 1) % polygon.len()];
+        let (from, to) = (plane.dot(&current.clip_position), plane.dot(&next.clip_position));
+        if from >= 0.0 {
+            clipped.push(current.clone());
+        }
+        if (from >= 0.0) != (to >= 0.0) {
+            clipped.push(lerp(current, next, from / (from - to)));
+        }
+    }
+    clipped
+}
+
+// Everything the vertex shader worked out is linear in clip space, so a
+// point along an edge gets the same share of each
+fn lerp(a: &Vertex, b: &Vertex, t: f32) -> Vertex {
+    Vertex {
+        position: a.position.lerp(&b.position, t),
+        normal: a.normal.lerp(&b.normal, t),
+        tex_coords: a.tex_coords.lerp(&b.tex_coords, t),
+        color: Color::lerp(a.color, b.color, t),
+        emission: a.emission + (b.emission - a.emission) * t,
+        transformed_position: a.transformed_position.lerp(&b.transformed_position, t),
+        transformed_normal: a.transformed_normal.lerp(&b.transformed_normal, t),
+        world_position: a.world_position.lerp(&b.world_position, t),
+        clip_position: a.clip_position.lerp(&b.clip_position, t),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::{Vec2, Vec3};
+
+    fn at(x: f32, y: f32, z: f32, w: f32) -> Vertex {
+        Vertex { clip_position: Vec4::new(x, y, z, w), world_position: Vec3::new(x, y, z), ..Vertex::new(Vec3::zeros(), Vec3::y(), Vec2::zeros()) }
+    }
+
+    #[test]
+    fn cuts_triangles_at_the_near_plane() {
+        let near = |triangle: &[Vertex; 3]| triangle.iter().all(|vertex| vertex.clip_position.z + vertex.clip_position.w >= -1e-5);
+
+        // In front is kept as it was, behind is dropped
+        let kept = clip_triangle([at(0.0, 0.0, 0.5, 1.0), at(1.0, 0.0, 0.5, 1.0), at(0.0, 1.0, 0.5, 1.0)]);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0][1].clip_position, Vec4::new(1.0, 0.0, 0.5, 1.0));
+        assert!(clip_triangle([at(0.0, 0.0, -2.0, 1.0), at(1.0, 0.0, -2.0, 1.0), at(0.0, 1.0, -2.0, 1.0)]).is_empty());
+
+        // One corner behind leaves a quad, two triangles; two behind leave
+        // one smaller triangle. New corners sit on the plane with the other
+        // attributes carried along
+        let one_behind = clip_triangle([at(0.0, 0.0, -3.0, 1.0), at(1.0, 0.0, 1.0, 1.0), at(0.0, 1.0, 1.0, 1.0)]);
+        assert_eq!(one_behind.len(), 2);
+        assert!(one_behind.iter().all(near));
+        let two_behind = clip_triangle([at(0.0, 0.0, 1.0, 1.0), at(1.0, 0.0, -3.0, 1.0), at(0.0, 1.0, -3.0, 1.0)]);
+        assert_eq!(two_behind.len(), 1);
+        assert!(near(&two_behind[0]));
+        let cut = &two_behind[0][1];
+        assert!((cut.clip_position.x - 0.5).abs() < 1e-5 && (cut.world_position.x - 0.5).abs() < 1e-5);
+
+        // Far off to the side is trimmed to the guard band, never dropped
+        // while some of it might be on screen
+        let wide = clip_triangle([at(-100.0, 0.0, 0.5, 1.0), at(100.0, 0.0, 0.5, 1.0), at(0.0, 1.0, 0.5, 1.0)]);
+        assert!(!wide.is_empty());
+        assert!(wide.iter().flatten().all(|vertex| vertex.clip_position.x.abs() <= GUARD_BAND + 1e-3));
+    }
+}
@@ -1,42 +1,126 @@
 use std::fmt;
+use nalgebra_glm::{Vec3, Mat3};
+use serde::Deserialize;
 
-#[derive(Debug, Clone, Copy)]
+// Pixels in the framebuffer (and minifb) are 0RGB u32s; colors carry an
+// alpha on top of that, which to_hex keeps in the top byte
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
   pub r: u8,
   pub g: u8,
   pub b: u8,
+  // 255 is opaque
+  pub a: u8,
 }
 
 impl Color {
   // Constructor to initialize the color using r, g, b values as u8
-  pub fn new(r: u8, g: u8, b: u8) -> Self {
-    Color { r, g, b }
+  pub const fn new(r: u8, g: u8, b: u8) -> Self {
+    Color { r, g, b, a: 255 }
+  }
+
+  pub const fn with_alpha(self, a: u8) -> Self {
+    Color { a, ..self }
   }
 
   // default color
-  pub fn black() -> Self {
-    Color { r: 0, g: 0, b: 0 }
+  pub const fn black() -> Self {
+    Color::new(0, 0, 0)
   }
 
   // New constructor to initialize the color using r, g, b values as f32 (0.0 to 1.0)
   pub fn from_float(r: f32, g: f32, b: f32) -> Self {
-    Color {
-      r: (r.clamp(0.0, 1.0) * 255.0) as u8,
-      g: (g.clamp(0.0, 1.0) * 255.0) as u8,
-      b: (b.clamp(0.0, 1.0) * 255.0) as u8,
-    }
+    Self::from_vec3(Vec3::new(r, g, b))
   }
 
-  // Function to create a color from a hex value
-  pub fn from_hex(hex: u32) -> Self {
-    let r = ((hex >> 16) & 0xFF) as u8;
-    let g = ((hex >> 8) & 0xFF) as u8;
-    let b = (hex & 0xFF) as u8;
-    Color { r, g, b }
+  // From 0-1 channels as used in the shading math, clamped and rounded so
+  // that to_vec3 and back gives the same color
+  pub fn from_vec3(v: Vec3) -> Self {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Color::new(channel(v.x), channel(v.y), channel(v.z))
   }
 
-  // Function to return the color as a hex value
-  pub fn to_hex(&self) -> u32 {
+  pub fn to_vec3(self) -> Vec3 {
+    Vec3::new(self.r as f32, self.g as f32, self.b as f32) / 255.0
+  }
+
+  // Each channel scaled by its own factor (colored light), clamped to 0-255
+  pub fn scale_rgb(self, factors: Vec3) -> Color {
+    let channel = |c: u8, k: f32| (c as f32 * k).clamp(0.0, 255.0) as u8;
+    Color::new(channel(self.r, factors.x), channel(self.g, factors.y), channel(self.b, factors.z)).with_alpha(self.a)
+  }
+
+  // `a` at t = 0, `b` at t = 1; t is clamped to that range
+  pub fn lerp(a: Color, b: Color, t: f32) -> Color {
+    let t = t.clamp(0.0, 1.0);
+    let alpha = (a.a as f32 + (b.a as f32 - a.a as f32) * t).round() as u8;
+    Self::from_vec3(a.to_vec3().lerp(&b.to_vec3(), t)).with_alpha(alpha)
+  }
+
+  // Hue in degrees (any value, it wraps), saturation and value in 0-1
+  pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+    let (s, v) = (saturation.clamp(0.0, 1.0), value.clamp(0.0, 1.0));
+    let sector = hue.rem_euclid(360.0) / 60.0;
+    let chroma = v * s;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+      0 => (chroma, x, 0.0),
+      1 => (x, chroma, 0.0),
+      2 => (0.0, chroma, x),
+      3 => (0.0, x, chroma),
+      4 => (x, 0.0, chroma),
+      _ => (chroma, 0.0, x),
+    };
+    let m = v - chroma;
+    Self::from_vec3(Vec3::new(r + m, g + m, b + m))
+  }
+
+  // (hue in degrees 0-360, saturation 0-1, value 0-1); grays have hue 0
+  pub fn to_hsv(self) -> (f32, f32, f32) {
+    let c = self.to_vec3();
+    let max = c.max();
+    let chroma = max - c.min();
+    let hue = if chroma == 0.0 {
+      0.0
+    } else if max == c.x {
+      60.0 * ((c.y - c.z) / chroma).rem_euclid(6.0)
+    } else if max == c.y {
+      60.0 * ((c.z - c.x) / chroma + 2.0)
+    } else {
+      60.0 * ((c.x - c.y) / chroma + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { chroma / max };
+    (hue, saturation, max)
+  }
+
+  pub fn shift_hue(self, degrees: f32) -> Color {
+    let (h, s, v) = self.to_hsv();
+    Self::from_hsv(h + degrees, s, v).with_alpha(self.a)
+  }
+
+  pub fn with_saturation(self, saturation: f32) -> Color {
+    let (h, _, v) = self.to_hsv();
+    Self::from_hsv(h, saturation, v).with_alpha(self.a)
+  }
+
+  pub fn with_value(self, value: f32) -> Color {
+    let (h, s, _) = self.to_hsv();
+    Self::from_hsv(h, s, value).with_alpha(self.a)
+  }
+
+  // Opaque color from a 0xRRGGBB value, the way colors are written in code
+  // and scene files; the top byte is ignored like minifb does
+  pub const fn from_hex(hex: u32) -> Self {
+    Color::new((hex >> 16) as u8, (hex >> 8) as u8, hex as u8)
+  }
+
+  // 0xAARRGGBB
+  pub fn to_hex(self) -> u32 {
+    ((self.a as u32) << 24) | self.to_pixel()
+  }
+
+  // 0x00RRGGBB, what goes into the framebuffer
+  pub fn to_pixel(self) -> u32 {
     ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
   }
 }
@@ -52,6 +136,7 @@ impl Add for Color {
       r: self.r.saturating_add(other.r),
       g: self.g.saturating_add(other.g),
       b: self.b.saturating_add(other.b),
+      a: self.a.saturating_add(other.a),
     }
   }
 }
@@ -63,17 +148,199 @@ impl Mul<f32> for Color {
   type Output = Color;
 
   fn mul(self, scalar: f32) -> Color {
-    Color {
-      r: (self.r as f32 * scalar).clamp(0.0, 255.0) as u8,
-      g: (self.g as f32 * scalar).clamp(0.0, 255.0) as u8,
-      b: (self.b as f32 * scalar).clamp(0.0, 255.0) as u8,
-    }
+    self.scale_rgb(Vec3::new(scalar, scalar, scalar))
   }
 }
 
 // Implement display formatting for Color
 impl fmt::Display for Color {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
+    write!(f, "Color(r: {}, g: {}, b: {}, a: {})", self.r, self.g, self.b, self.a)
+  }
+}
+
+// What an overlay or HUD color is for. Drawing code asks the palette for
+// a role instead of naming a color, so the whole UI can be switched
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UiColor {
+  Background,
+  Orbit,
+  OrbitArrow,
+  Label,
+  Lagrange,
+  Measure,
+  Shield,
+  Panel,
+  PanelBorder,
+  Title,
+  Text,
+  Warning,
+  WarpBar,
+  Trajectory,
+  Selection,
+  // Constellation figures and star names on the sky
+  Constellation,
+}
+
+// C cycles through these, the scene file picks the first one
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PaletteMode {
+  #[default]
+  Normal,
+  // Roles told apart by blue/yellow and lightness instead of red/green
+  Deuteranopia,
+  // Pure colors on black
+  HighContrast,
+}
+
+// One row per PaletteMode, one column per UiColor
+const PALETTES: [[u32; 16]; 3] = [
+  // Bg        Orbit     Arrow     Label     Lagrange  Measure   Shield    Panel     Border    Title     Text      Warning   WarpBar   Trajectory Selection Constellation
+  [0x4A0E4E, 0x00CED1, 0xFFFFFF, 0xFFFFFF, 0xFF69B4, 0xFFFF66, 0x66CCFF, 0x1A0A2E, 0x8A6FD1, 0xFFD700, 0xE0E0E0, 0xFF3030, 0x66CCFF, 0x7CFC00, 0x7FFFD4, 0x6A8CD8],
+  [0x2A1840, 0x56B4E9, 0xFFFFFF, 0xFFFFFF, 0xE69F00, 0xF0E442, 0x56B4E9, 0x101028, 0x8C8CC8, 0xF0E442, 0xE8E8E8, 0xD55E00, 0x56B4E9, 0x009E73, 0xCC79A7, 0x8C8CC8],
+  [0x000000, 0xFFFFFF, 0xFFFF00, 0xFFFF00, 0xFF00FF, 0x00FF00, 0x00FFFF, 0x000000, 0xFFFFFF, 0xFFFF00, 0xFFFFFF, 0xFF0000, 0x00FFFF, 0x00FF00, 0x00FFFF, 0x0000FF],
+];
+
+impl PaletteMode {
+  pub fn color(self, role: UiColor) -> u32 {
+    PALETTES[self as usize][role as usize]
+  }
+
+  pub fn next(self) -> Self {
+    match self {
+      PaletteMode::Normal => PaletteMode::Deuteranopia,
+      PaletteMode::Deuteranopia => PaletteMode::HighContrast,
+      PaletteMode::HighContrast => PaletteMode::Normal,
+    }
+  }
+
+  pub fn label(self) -> &'static str {
+    match self {
+      PaletteMode::Normal => "Normal",
+      PaletteMode::Deuteranopia => "Deuteranopia",
+      PaletteMode::HighContrast => "High contrast",
+    }
+  }
+}
+
+// [accessibility] section of the scene file
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct AccessibilitySettings {
+  #[serde(default)]
+  pub palette: PaletteMode,
+  // Run every finished frame through the daltonization filter below
+  #[serde(default)]
+  pub daltonize: bool,
+}
+
+// Daltonization for deuteranopia (Fidaner, Lin and Ozguven): simulate what a
+// deuteranope sees in LMS space, then move the lost red/green difference
+// into lightness and blue. Every step is linear, so it folds into one matrix
+fn daltonize_matrix() -> Mat3 {
+  let rgb_to_lms = Mat3::new(
+    17.8824, 43.5161, 4.11935,
+    3.45565, 27.1554, 3.86714,
+    0.0299566, 0.184309, 1.46709,
+  );
+  let deuteranope = Mat3::new(
+    1.0, 0.0, 0.0,
+    0.494207, 0.0, 1.24827,
+    0.0, 0.0, 1.0,
+  );
+  let lms_to_rgb = rgb_to_lms.try_inverse().unwrap();
+  let simulate = lms_to_rgb * deuteranope * rgb_to_lms;
+  let shift_error = Mat3::new(
+    0.0, 0.0, 0.0,
+    0.7, 1.0, 0.0,
+    0.7, 0.0, 1.0,
+  );
+  Mat3::identity() + shift_error * (Mat3::identity() - simulate)
+}
+
+// Applies the filter to a buffer of 0RGB pixels in place
+pub fn daltonize(pixels: &mut [u32]) {
+  let matrix = daltonize_matrix();
+  for pixel in pixels.iter_mut() {
+    let color = Color::from_hex(*pixel);
+    *pixel = Color::from_vec3(matrix * color.to_vec3()).to_pixel();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn arithmetic_saturates_instead_of_wrapping() {
+    let white = Color::new(255, 255, 255);
+    assert_eq!((white + Color::new(1, 100, 255)).to_pixel(), 0xFFFFFF);
+    assert_eq!((Color::new(200, 100, 0) * 2.0).to_pixel(), 0xFFC800);
+    assert_eq!((white * -1.0).to_pixel(), 0x000000);
+    assert_eq!(white.scale_rgb(Vec3::new(3.0, 0.5, f32::NAN)).to_pixel(), 0xFF7F00);
+    assert_eq!(Color::lerp(Color::black(), white, 2.0).to_pixel(), 0xFFFFFF);
+    assert_eq!(Color::lerp(Color::new(0, 100, 200), Color::new(100, 0, 200), 0.5).to_pixel(), 0x3232C8);
+  }
+
+  #[test]
+  fn hsv_matches_reference_values() {
+    assert_eq!(Color::from_hsv(0.0, 1.0, 1.0).to_pixel(), 0xFF0000);
+    assert_eq!(Color::from_hsv(60.0, 1.0, 1.0).to_pixel(), 0xFFFF00);
+    assert_eq!(Color::from_hsv(210.0, 0.5, 0.8).to_pixel(), 0x6699CC);
+    assert_eq!(Color::from_hsv(-90.0, 1.0, 1.0), Color::from_hsv(270.0, 1.0, 1.0));
+    assert_eq!(Color::from_hsv(123.0, 0.0, 0.5).to_pixel(), 0x808080);
+
+    let (h, s, v) = Color::from_hex(0x6699CC).to_hsv();
+    assert!((h - 210.0).abs() < 0.01 && (s - 0.5).abs() < 0.01 && (v - 0.8).abs() < 0.01);
+    assert_eq!(Color::from_hex(0x0000FF).to_hsv(), (240.0, 1.0, 1.0));
+    assert_eq!(Color::black().to_hsv(), (0.0, 0.0, 0.0));
+
+    let red = Color::new(255, 0, 0);
+    assert_eq!(red.shift_hue(120.0).to_pixel(), 0x00FF00);
+    assert_eq!(red.shift_hue(-120.0).to_pixel(), 0x0000FF);
+    assert_eq!(red.with_saturation(0.0).to_pixel(), 0xFFFFFF);
+    assert_eq!(red.with_value(0.5).to_pixel(), 0x800000);
+  }
+
+  #[test]
+  fn vec3_round_trip_is_exact() {
+    for value in 0..=255u8 {
+      let color = Color::new(value, 255 - value, value / 3);
+      assert_eq!(Color::from_vec3(color.to_vec3()), color);
+    }
+    assert_eq!(Color::from_vec3(Vec3::new(-1.0, 0.5, 2.0)).to_pixel(), 0x0080FF);
+  }
+
+  #[test]
+  fn hex_round_trips_with_alpha() {
+    let color = Color::new(0x12, 0x34, 0x56).with_alpha(0x78);
+    assert_eq!(color.to_hex(), 0x78123456);
+    assert_eq!(color.to_pixel(), 0x123456);
+    assert_eq!(Color::from_hex(color.to_hex()).with_alpha(0x78), color);
+    // Plain 0xRRGGBB values are opaque
+    assert_eq!(Color::from_hex(0x123456), Color::new(0x12, 0x34, 0x56));
+    assert_eq!(Color::from_hex(0x123456).to_hex(), 0xFF123456);
+    for argb in [0x00000000u32, 0xFFFFFFFF, 0x80FF0000, 0x0100FF01] {
+      assert_eq!(Color::from_hex(argb).with_alpha((argb >> 24) as u8).to_hex(), argb);
+    }
+    assert_eq!(Color::from_hex(0x123456).with_value(0.5).a, 255);
+    assert_eq!(color.shift_hue(30.0).a, 0x78);
+  }
+
+  #[test]
+  fn palettes_cover_every_role_and_daltonizing_keeps_grays() {
+    let mut mode = PaletteMode::Normal;
+    for _ in 0..3 {
+      assert!(mode.color(UiColor::WarpBar) <= 0xFFFFFF);
+      mode = mode.next();
+    }
+    assert_eq!(mode, PaletteMode::Normal);
+    assert_ne!(PaletteMode::HighContrast.color(UiColor::Orbit), PaletteMode::Normal.color(UiColor::Orbit));
+
+    // Grays have no red/green difference to lose; pure red gains blue
+    let mut pixels = [0x000000, 0x808080, 0xFFFFFF, 0xFF0000];
+    daltonize(&mut pixels);
+    assert_eq!(&pixels[..3], &[0x000000, 0x808080, 0xFFFFFF]);
+    assert!(Color::from_hex(pixels[3]).b > 0x40);
   }
 }
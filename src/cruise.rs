@@ -0,0 +1,207 @@
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use crate::body::CelestialBody;
+use crate::camera::Camera;
+
+// Braking for something ahead sheds speed this many times faster than
+// letting go of the key
+const BRAKE_FACTOR: f32 = 4.0;
+
+// [cruise] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct CruiseSettings {
+    // Top speed, units a second along the view
+    #[serde(default = "default_max_speed")]
+    pub max_speed: f32,
+    // Seconds from standing still to top speed and back
+    #[serde(default = "default_ramp_time")]
+    pub ramp_time: f32,
+    // Degrees the view widens by at top speed
+    #[serde(default = "default_fov_widening")]
+    pub fov_widening: f32,
+    // Degrees of sky each star smears across at top speed
+    #[serde(default = "default_streak_length")]
+    pub streak_length: f32,
+    // Seconds of the path ahead kept clear of bodies
+    #[serde(default = "default_look_ahead")]
+    pub look_ahead: f32,
+}
+
+impl Default for CruiseSettings {
+    fn default() -> Self {
+        Self {
+            max_speed: default_max_speed(),
+            ramp_time: default_ramp_time(),
+            fov_widening: default_fov_widening(),
+            streak_length: default_streak_length(),
+            look_ahead: default_look_ahead(),
+        }
+    }
+}
+
+fn default_max_speed() -> f32 {
+    1500.0
+}
+
+fn default_ramp_time() -> f32 {
+    2.0
+}
+
+fn default_fov_widening() -> f32 {
+    10.0
+}
+
+fn default_streak_length() -> f32 {
+    1.5
+}
+
+fn default_look_ahead() -> f32 {
+    3.0
+}
+
+impl CruiseSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.max_speed > 0.0 && self.ramp_time > 0.0 && self.look_ahead > 0.0) {
+            return Err("cruise needs a positive max_speed, ramp_time and look_ahead".to_string());
+        }
+        if !(0.0..60.0).contains(&self.fov_widening) || !(0.0..=45.0).contains(&self.streak_length) {
+            return Err("cruise needs 0 <= fov_widening < 60 and 0 <= streak_length <= 45".to_string());
+        }
+        Ok(())
+    }
+}
+
+// Holding the cruise key in free flight builds up speed along the view;
+// the camera carries its target along, so it keeps looking the same way
+pub struct Cruise {
+    pub settings: CruiseSettings,
+    speed: f32,
+    // Braked for a body ahead: stays off until the key is let go
+    braking: bool,
+}
+
+impl Cruise {
+    pub fn new(settings: CruiseSettings) -> Self {
+        Self { settings, speed: 0.0, braking: false }
+    }
+
+    // Returns the body it braked for, on the frame it happens. `clearance`
+    // is how close the path may pass each body
+    pub fn update(&mut self, held: bool, camera: &mut Camera, bodies: &[CelestialBody], delta_time: f32, clearance: impl Fn(&CelestialBody) -> f32) -> Option<usize> {
+        self.braking &= held;
+        let ahead = (camera.target - camera.position).try_normalize(f32::EPSILON)?;
+        let mut braked_for = None;
+        if held && !self.braking {
+            braked_for = body_ahead(bodies, camera.position, ahead, self.speed * self.settings.look_ahead, clearance);
+            self.braking = braked_for.is_some();
+        }
+
+        let rate = self.settings.max_speed / self.settings.ramp_time * delta_time;
+        self.speed = if held && !self.braking {
+            (self.speed + rate).min(self.settings.max_speed)
+        } else {
+            (self.speed - rate * if self.braking { BRAKE_FACTOR } else { 1.0 }).max(0.0)
+        };
+        if self.speed > 0.0 {
+            camera.set_target(camera.target + ahead * self.speed * delta_time);
+        }
+        braked_for
+    }
+
+    // Drops out of cruise at once, for when something else takes the camera
+    pub fn stop(&mut self) {
+        self.speed = 0.0;
+        self.braking = false;
+    }
+
+    // 0 standing still, 1 at top speed
+    pub fn fraction(&self) -> f32 {
+        self.speed / self.settings.max_speed
+    }
+
+    pub fn fov(&self, base: f32) -> f32 {
+        base + self.settings.fov_widening.to_radians() * self.fraction()
+    }
+
+    // Way the camera is heading, as long as the stars should streak there
+    // (see Sky::draw_streaks); None when still
+    pub fn streak(&self, camera: &Camera) -> Option<Vec3> {
+        let ahead = (camera.target - camera.position).try_normalize(f32::EPSILON)?;
+        (self.speed > 0.0).then(|| ahead * self.settings.streak_length.to_radians() * self.fraction())
+    }
+}
+
+// Nearest body the path from `origin` along `ahead` runs into within
+// `reach`, counting each by its clearance
+fn body_ahead(bodies: &[CelestialBody], origin: Vec3, ahead: Vec3, reach: f32, clearance: impl Fn(&CelestialBody) -> f32) -> Option<usize> {
+    bodies.iter().enumerate()
+        .filter(|(_, body)| !body.is_virtual() && !body.is_portal())
+        .filter_map(|(i, body)| {
+            let offset = body.position - origin;
+            let along = offset.dot(&ahead);
+            let radius = clearance(body);
+            let discriminant = along * along - (offset.magnitude_squared() - radius * radius);
+            // Already inside the clearance is for the collisions to sort out
+            let near = along - discriminant.max(0.0).sqrt();
+            (discriminant >= 0.0 && near >= 0.0 && near < reach).then_some((i, near))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec2;
+
+    #[test]
+    fn ramps_up_along_the_view_and_brakes_for_bodies_ahead() {
+        let settings = CruiseSettings { max_speed: 100.0, ramp_time: 2.0, look_ahead: 3.0, ..Default::default() };
+        let mut cruise = Cruise::new(settings);
+        // Looking down -Z from the origin
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, -10.0), 10.0);
+        camera.move_to(Vec3::zeros());
+        let clearance = |body: &CelestialBody| body.bounding_radius() * 2.0;
+
+        // Two seconds to top speed, 5 faster every tenth of a second,
+        // straight along the view and still looking the same way
+        for _ in 0..20 {
+            assert_eq!(cruise.update(true, &mut camera, &[], 0.1, clearance), None);
+        }
+        assert!((cruise.fraction() - 1.0).abs() < 1e-4);
+        assert!((camera.position.z + 105.0).abs() < 1e-3 && camera.position.x.abs() < 1e-3, "{:?}", camera.position);
+        assert!((camera.target - camera.position).normalize().z < -0.999);
+        assert!(cruise.fov(1.0) > 1.0 && cruise.streak(&camera).is_some_and(|streak| streak.z < 0.0));
+
+        // Letting go takes as long to come to a stop
+        for _ in 0..10 {
+            cruise.update(false, &mut camera, &[], 0.1, clearance);
+        }
+        assert!((cruise.fraction() - 0.5).abs() < 1e-4);
+        cruise.stop();
+        assert_eq!((cruise.fraction(), cruise.fov(1.0), cruise.streak(&camera)), (0.0, 1.0, None));
+
+        // A planet 250 ahead is inside three seconds at top speed; one off
+        // to the side isn't
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let planet = |x: f32, z: f32| {
+            let mut body = CelestialBody::new_planet("Planet".to_string(), vec![rim.clone()], Vec3::zeros(), 0.0, 0.0, 10.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+            body.position = camera.position + Vec3::new(x, 0.0, z);
+            body
+        };
+        let bodies = vec![planet(100.0, -250.0), planet(5.0, -250.0)];
+        let mut braked = None;
+        for _ in 0..30 {
+            braked = braked.or(cruise.update(true, &mut camera, &bodies, 0.1, clearance));
+        }
+        assert_eq!(braked, Some(1));
+        // It stays braked with the key still down, until it's let go
+        assert_eq!(cruise.fraction(), 0.0);
+        cruise.update(false, &mut camera, &bodies, 0.1, clearance);
+        cruise.update(true, &mut camera, &bodies, 0.1, clearance);
+        assert!(cruise.fraction() > 0.0);
+    }
+}
@@ -0,0 +1,133 @@
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::overlay::ScreenProjection;
+
+// Poisson disk in the unit circle, the blur gathers from these offsets
+// scaled by each pixel's blur radius
+const KERNEL: [(f32, f32); 12] = [
+    (-0.326, -0.406), (-0.840, -0.074), (-0.696, 0.457), (-0.203, 0.621),
+    (0.962, -0.195), (0.473, -0.480), (0.519, 0.767), (0.185, -0.893),
+    (0.507, 0.064), (0.896, 0.412), (-0.322, -0.933), (-0.792, -0.598),
+];
+// Things in front of the focus blur up to this many times the aperture
+const NEAR_BLUR_LIMIT: f32 = 3.0;
+// Pixels blurred by less than this stay as rendered
+const SHARP_RADIUS: f32 = 0.5;
+// The focus plane overlay marks everything within this fraction of the
+// focus distance
+const FOCUS_BAND: f32 = 0.05;
+const FOCUS_TINT: Color = Color::new(0, 255, 120);
+const FOCUS_TINT_AMOUNT: f32 = 0.35;
+
+// Blur radius in pixels for something `distance` ahead: none at the focus
+// distance, `aperture` pixels at infinity and more toward the camera
+pub fn blur_radius(distance: f32, focus: f32, aperture: f32) -> f32 {
+    (aperture * (1.0 - focus / distance).abs()).min(aperture * NEAR_BLUR_LIMIT)
+}
+
+// Screen to camera space, for turning depth buffer values into distances
+fn unprojection(projection: &ScreenProjection) -> Option<Mat4> {
+    (projection.viewport_matrix * projection.projection_matrix).try_inverse()
+}
+
+// Distance along the view direction of a pixel, infinite for the background
+fn view_distance(unproject: &Mat4, x: usize, y: usize, depth: f32) -> f32 {
+    if !depth.is_finite() {
+        return f32::INFINITY;
+    }
+    let view = unproject * Vec4::new(x as f32, y as f32, depth, 1.0);
+    -view.z / view.w
+}
+
+fn distances(framebuffer: &Framebuffer, projection: &ScreenProjection) -> Option<Vec<f32>> {
+    let unproject = unprojection(projection)?;
+    let width = framebuffer.width;
+    Some(framebuffer.zbuffer.iter().enumerate().map(|(index, &depth)| view_distance(&unproject, index % width, index / width, depth)).collect())
+}
+
+// Distance of whatever is drawn at the pixel, None over the background
+pub fn focus_at(framebuffer: &Framebuffer, projection: &ScreenProjection, x: usize, y: usize) -> Option<f32> {
+    let distance = view_distance(&unprojection(projection)?, x, y, framebuffer.zbuffer[y * framebuffer.width + x]);
+    (distance.is_finite() && distance > 0.0).then_some(distance)
+}
+
+// Blurs the frame by each pixel's distance from the focus. A sample only
+// counts when its own blur reaches the pixel being gathered, so sharp
+// things don't bleed out into the blur around them
+pub fn depth_of_field(framebuffer: &mut Framebuffer, projection: &ScreenProjection, focus: f32, aperture: f32) {
+    if aperture <= 0.0 {
+        return;
+    }
+    let Some(distances) = distances(framebuffer, projection) else { return };
+    let radii: Vec<f32> = distances.iter().map(|&distance| blur_radius(distance, focus, aperture)).collect();
+    let source = framebuffer.buffer.clone();
+    let (width, height) = (framebuffer.width, framebuffer.height);
+
+    for (index, &radius) in radii.iter().enumerate() {
+        if radius < SHARP_RADIUS {
+            continue;
+        }
+        let (x, y) = ((index % width) as f32, (index / width) as f32);
+        let mut sum = Color::from_hex(source[index]).to_vec3();
+        let mut weight = 1.0;
+        for (dx, dy) in KERNEL {
+            let sample_x = (x + dx * radius).round().clamp(0.0, (width - 1) as f32) as usize;
+            let sample_y = (y + dy * radius).round().clamp(0.0, (height - 1) as f32) as usize;
+            let sample = sample_y * width + sample_x;
+            let reach = (radii[sample] - (dx * dx + dy * dy).sqrt() * radius + 1.0).clamp(0.0, 1.0);
+            sum += Color::from_hex(source[sample]).to_vec3() * reach;
+            weight += reach;
+        }
+        framebuffer.buffer[index] = Color::from_vec3(sum / weight).to_pixel();
+    }
+}
+
+// Tints what lies at the focus distance, to see where the focus plane cuts
+// through the scene while framing
+pub fn draw_focus_plane(framebuffer: &mut Framebuffer, projection: &ScreenProjection, focus: f32) {
+    let Some(distances) = distances(framebuffer, projection) else { return };
+    let tint: Vec3 = FOCUS_TINT.to_vec3();
+    for (pixel, distance) in framebuffer.buffer.iter_mut().zip(distances) {
+        if (distance - focus).abs() <= focus * FOCUS_BAND {
+            *pixel = Color::from_vec3(Color::from_hex(*pixel).to_vec3().lerp(&tint, FOCUS_TINT_AMOUNT)).to_pixel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::look_at_matrix;
+    use crate::framebuffer::Viewport;
+
+    #[test]
+    fn only_what_is_out_of_focus_blurs() {
+        assert_eq!(blur_radius(100.0, 100.0, 4.0), 0.0);
+        assert_eq!(blur_radius(f32::INFINITY, 100.0, 4.0), 4.0);
+        assert_eq!(blur_radius(1.0, 100.0, 4.0), 4.0 * NEAR_BLUR_LIMIT);
+
+        // A checkerboard 100 units ahead, half of it in focus
+        let viewport = Viewport { x: 0, y: 0, width: 40, height: 30 };
+        let view = look_at_matrix(Vec3::new(0.0, 0.0, 100.0), Vec3::zeros(), Vec3::y());
+        let projection = crate::scene_projection(view, 1.0, viewport);
+        let mut framebuffer = Framebuffer::new(40, 30);
+        let depth = projection.project(Vec3::zeros()).unwrap().z;
+        for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+            *pixel = if (index % 40 + index / 40) % 2 == 0 { 0xFFFFFF } else { 0x000000 };
+        }
+        for (index, value) in framebuffer.zbuffer.iter_mut().enumerate() {
+            // The right half is background
+            if index % 40 < 20 {
+                *value = depth;
+            }
+        }
+        let distance = focus_at(&framebuffer, &projection, 10, 15).unwrap();
+        assert!((distance - 100.0).abs() < 0.5, "focused at {}", distance);
+
+        depth_of_field(&mut framebuffer, &projection, distance, 4.0);
+        assert_eq!(framebuffer.buffer[15 * 40 + 11], 0xFFFFFF, "the focused half stays sharp");
+        let far = Color::from_hex(framebuffer.buffer[15 * 40 + 30]);
+        assert!(far.r > 40 && far.r < 215, "background should average out, got {}", far);
+    }
+}
@@ -0,0 +1,132 @@
+use nalgebra_glm::{Vec2, Vec3};
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Viewport};
+
+// At full strength, red and blue are sampled this fraction of the distance
+// to the center farther out and farther in than green
+const ABERRATION: f32 = 0.012;
+// Barrel distortion at full strength, at the corners
+const BARREL: f32 = 0.06;
+// Heat shimmer rolls at this many radians a second sideways, a bit faster
+// and at half the height up and down
+const SHIMMER_SPEED: f32 = 6.0;
+
+// Warp smear: the color channels split radially and the image bows out a
+// little, both scaled by `strength` (0 to 1). At 0 the frame is left as is
+pub fn chromatic_aberration(framebuffer: &mut Framebuffer, viewport: Viewport, strength: f32) {
+    if strength <= 0.0 || viewport.width == 0 || viewport.height == 0 {
+        return;
+    }
+    let source = framebuffer.buffer.clone();
+    let width = framebuffer.width;
+    let origin = Vec2::new(viewport.x as f32, viewport.y as f32);
+    let center = origin + Vec2::new(viewport.width as f32 - 1.0, viewport.height as f32 - 1.0) / 2.0;
+    let corner = (origin - center).magnitude().max(1.0);
+    let split = ABERRATION * strength;
+    // The barrel only shows near the peak
+    let barrel = BARREL * strength * strength;
+
+    for y in viewport.y..viewport.y + viewport.height {
+        for x in viewport.x..viewport.x + viewport.width {
+            let offset = Vec2::new(x as f32, y as f32) - center;
+            let reach = offset.magnitude() / corner;
+            let bowed = offset * (1.0 + barrel * reach * reach);
+            let sample = |scale: f32| sample_bilinear(&source, width, viewport, center + bowed * scale);
+            let color = Vec3::new(sample(1.0 + split).x, sample(1.0).y, sample(1.0 - split).z);
+            framebuffer.buffer[y * width + x] = Color::from_vec3(color).to_pixel();
+        }
+    }
+}
+
+// Gravitational lensing hint around a portal: pixels within `radius` of
+// `center` (screen pixels) are pulled around it, turned by up to `twist`
+// radians at the middle and not at all at the edge. Only what lies behind
+// `depth` bends, anything in front of the portal stays put
+pub fn swirl(framebuffer: &mut Framebuffer, viewport: Viewport, center: Vec2, radius: f32, depth: f32, twist: f32) {
+    if radius < 1.0 || twist == 0.0 || viewport.width == 0 || viewport.height == 0 {
+        return;
+    }
+    let left = (center.x - radius).max(viewport.x as f32) as usize;
+    let top = (center.y - radius).max(viewport.y as f32) as usize;
+    let right = ((center.x + radius).ceil() as usize).min(viewport.x + viewport.width);
+    let bottom = ((center.y + radius).ceil() as usize).min(viewport.y + viewport.height);
+    if left >= right || top >= bottom {
+        return;
+    }
+    let source = framebuffer.buffer.clone();
+    let width = framebuffer.width;
+    for y in top..bottom {
+        for x in left..right {
+            let offset = Vec2::new(x as f32, y as f32) - center;
+            let reach = offset.magnitude() / radius;
+            if reach >= 1.0 || framebuffer.zbuffer[y * width + x] < depth {
+                continue;
+            }
+            let falloff = 1.0 - reach;
+            let (sin, cos) = (twist * falloff * falloff).sin_cos();
+            let turned = Vec2::new(offset.x * cos - offset.y * sin, offset.x * sin + offset.y * cos);
+            let color = sample_bilinear(&source, width, viewport, center + turned);
+            framebuffer.buffer[y * width + x] = Color::from_vec3(color).to_pixel();
+        }
+    }
+}
+
+// Heat shimmer: rows slide sideways and columns up and down in waves
+// `wavelength` pixels long that roll with `time`, by up to `amplitude`
+// pixels. At 0 the frame is left as is
+pub fn heat_wobble(framebuffer: &mut Framebuffer, viewport: Viewport, amplitude: f32, wavelength: f32, time: f32) {
+    if amplitude <= 0.0 || viewport.width == 0 || viewport.height == 0 {
+        return;
+    }
+    let source = framebuffer.buffer.clone();
+    let width = framebuffer.width;
+    let k = std::f32::consts::TAU / wavelength.max(1.0);
+    for y in viewport.y..viewport.y + viewport.height {
+        let dx = amplitude * (y as f32 * k + time * SHIMMER_SPEED).sin();
+        for x in viewport.x..viewport.x + viewport.width {
+            let dy = amplitude * 0.5 * (x as f32 * k * 0.7 + time * SHIMMER_SPEED * 1.3).cos();
+            let color = sample_bilinear(&source, width, viewport, Vec2::new(x as f32 + dx, y as f32 + dy));
+            framebuffer.buffer[y * width + x] = Color::from_vec3(color).to_pixel();
+        }
+    }
+}
+
+// Color at a fractional pixel position, blended from the four pixels around
+// it and clamped to the viewport's edges
+fn sample_bilinear(pixels: &[u32], width: usize, viewport: Viewport, position: Vec2) -> Vec3 {
+    let x = position.x.clamp(viewport.x as f32, (viewport.x + viewport.width - 1) as f32);
+    let y = position.y.clamp(viewport.y as f32, (viewport.y + viewport.height - 1) as f32);
+    let (left, top) = (x.floor() as usize, y.floor() as usize);
+    let right = (left + 1).min(viewport.x + viewport.width - 1);
+    let bottom = (top + 1).min(viewport.y + viewport.height - 1);
+    let (fx, fy) = (x - left as f32, y - top as f32);
+    let pixel = |px: usize, py: usize| Color::from_hex(pixels[py * width + px]).to_vec3();
+    let upper = pixel(left, top).lerp(&pixel(right, top), fx);
+    let lower = pixel(left, bottom).lerp(&pixel(right, bottom), fx);
+    upper.lerp(&lower, fy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channels_split_only_while_the_effect_is_on() {
+        let mut framebuffer = Framebuffer::new(41, 31);
+        let viewport = framebuffer.full_viewport();
+        // A white dot on every other column
+        for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+            *pixel = if index % 41 % 2 == 0 { 0xFFFFFF } else { 0x202020 };
+        }
+        let original = framebuffer.buffer.clone();
+
+        chromatic_aberration(&mut framebuffer, viewport, 0.0);
+        assert_eq!(framebuffer.buffer, original);
+
+        chromatic_aberration(&mut framebuffer, viewport, 1.0);
+        // The center doesn't move, the edges pull apart
+        assert_eq!(framebuffer.buffer[15 * 41 + 20], original[15 * 41 + 20]);
+        let edge = Color::from_hex(framebuffer.buffer[15 * 41 + 36]);
+        assert!(edge.r != edge.b, "red and blue should separate at the edge, got {}", edge);
+    }
+}
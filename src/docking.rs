@@ -0,0 +1,211 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::camera::Camera;
+use crate::geometry::normalize_or;
+use crate::spaceship::{Spaceship, wrap_angle};
+
+// Z is offered within this many station radii of the port, slower than
+// this many station radii a second relative to the station
+const DOCKING_RANGE: f32 = 4.0;
+const MAX_DOCKING_SPEED: f32 = 2.0;
+// Seconds the ship takes to glide into the port, and to back out of it
+// UNDOCK_DISTANCE station radii when leaving
+const DOCKING_SECONDS: f32 = 3.0;
+const UNDOCK_SECONDS: f32 = 2.0;
+const UNDOCK_DISTANCE: f32 = 1.5;
+
+// Docked menu entries, top to bottom
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DockedEntry {
+    Repair,
+    Undock,
+}
+
+impl DockedEntry {
+    pub const ALL: [DockedEntry; 2] = [DockedEntry::Repair, DockedEntry::Undock];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DockedEntry::Repair => "Repair hull",
+            DockedEntry::Undock => "Undock",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Phase {
+    // Easing from where the ship was, kept relative to the port, onto it
+    Docking { from: Vec3, attitude: (f32, f32, f32), elapsed: f32 },
+    Docked { selected: usize },
+    Undocking { elapsed: f32 },
+}
+
+// A ship docking at, sitting in or leaving a station's port. The ship is
+// flown by the sequence the whole time and the camera is carried along
+// with it, so nothing jumps when control is handed back
+pub struct Docking {
+    pub station: usize,
+    phase: Phase,
+}
+
+impl Docking {
+    pub fn begin(station: usize, ship: &Spaceship, bodies: &[CelestialBody]) -> Option<Self> {
+        let port = bodies[station].docking_port_position()?;
+        let from = ship.position - port;
+        Some(Self { station, phase: Phase::Docking { from, attitude: (ship.yaw, ship.pitch, ship.roll), elapsed: 0.0 } })
+    }
+
+    pub fn is_docked(&self) -> bool {
+        matches!(self.phase, Phase::Docked { .. })
+    }
+
+    pub fn selected(&self) -> Option<DockedEntry> {
+        match self.phase {
+            Phase::Docked { selected } => Some(DockedEntry::ALL[selected]),
+            _ => None,
+        }
+    }
+
+    // Moves the docked menu's highlight up (-1) or down (+1), wrapping around
+    pub fn move_selection(&mut self, step: i32) {
+        if let Phase::Docked { selected } = &mut self.phase {
+            let count = DockedEntry::ALL.len() as i32;
+            *selected = (*selected as i32 + step).rem_euclid(count) as usize;
+        }
+    }
+
+    pub fn undock(&mut self) {
+        if self.is_docked() {
+            self.phase = Phase::Undocking { elapsed: 0.0 };
+        }
+    }
+
+    // Puts the ship where the sequence has it this frame and moves the
+    // camera by as much. False once the ship has backed out and is the
+    // player's again
+    pub fn update(&mut self, ship: &mut Spaceship, camera: &mut Camera, bodies: &[CelestialBody], delta_time: f32) -> bool {
+        let station = &bodies[self.station];
+        let Some(port) = station.docking_port_position() else { return false };
+        // Out of the port, away from the station's center; the ship docks
+        // nose first
+        let facing = normalize_or(port - station.position, Vec3::y());
+        let docked_attitude = attitude_toward(-facing);
+        let (position, attitude) = match &mut self.phase {
+            Phase::Docking { from, attitude, elapsed } => {
+                *elapsed += delta_time;
+                let t = smoothstep(*elapsed / DOCKING_SECONDS);
+                let position = port + *from * (1.0 - t);
+                let (yaw, pitch, roll) = *attitude;
+                let eased = (
+                    wrap_angle(yaw + wrap_angle(docked_attitude.0 - yaw) * t),
+                    pitch + (docked_attitude.1 - pitch) * t,
+                    roll * (1.0 - t),
+                );
+                if *elapsed >= DOCKING_SECONDS {
+                    self.phase = Phase::Docked { selected: 0 };
+                }
+                (position, eased)
+            }
+            Phase::Docked { .. } => (port, docked_attitude),
+            Phase::Undocking { elapsed } => {
+                *elapsed += delta_time;
+                // Gentle push that fades out, like a spring letting go
+                let t = 1.0 - (1.0 - (*elapsed / UNDOCK_SECONDS).min(1.0)).powi(2);
+                if *elapsed >= UNDOCK_SECONDS {
+                    return false;
+                }
+                (port + facing * station.bounding_radius() * UNDOCK_DISTANCE * t, docked_attitude)
+            }
+        };
+        let moved = position - ship.position;
+        ship.hold(position, attitude, delta_time);
+        camera.set_target(camera.target + moved);
+        true
+    }
+}
+
+// Station the ship is close and slow enough to dock with. `previous` holds
+// every body's position last frame, for the ship's speed relative to them
+pub fn dockable_station(ship: &Spaceship, bodies: &[CelestialBody], previous: &[Vec3], delta_time: f32) -> Option<usize> {
+    bodies.iter().enumerate().position(|(i, body)| {
+        let Some(port) = body.docking_port_position() else { return false };
+        let radius = body.bounding_radius();
+        let station_velocity = previous.get(i).map_or(Vec3::zeros(), |&before| (body.position - before) / delta_time);
+        (ship.position - port).magnitude() < radius * DOCKING_RANGE
+            && (ship.velocity - station_velocity).magnitude() < radius * MAX_DOCKING_SPEED
+    })
+}
+
+// Heading and nose-up angle that point a ship's nose along `direction`
+fn attitude_toward(direction: Vec3) -> (f32, f32, f32) {
+    (direction.x.atan2(direction.z), direction.y.clamp(-1.0, 1.0).asin(), 0.0)
+}
+
+fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::scene_graph::SceneGraph;
+    use crate::spaceship::ShipDesign;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+
+    fn station_and_ship() -> (Vec<CelestialBody>, Spaceship) {
+        let vertex = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), nalgebra_glm::Vec2::zeros());
+        let mut station = CelestialBody::new_planet(
+            "Station".to_string(), vec![vertex.clone()], Vec3::zeros(), 50.0, 1.0, 2.0, Color::from_hex(0xFFFFFF), ShaderType::Station,
+        );
+        station.docking_port = Some(Vec3::new(-1.5, 0.0, 0.0));
+        let design = ShipDesign::new("Test".to_string(), vec![vertex], 1.0, Vec3::new(0.0, -2.0, 9.0), Vec::new(), 0, 0);
+        (vec![station], Spaceship::new(vec![design]))
+    }
+
+    // Moves the station along its orbit and spins it, then has the scene
+    // graph place it
+    fn advance(bodies: &mut [CelestialBody], graph: &mut SceneGraph, angle: f32) {
+        bodies[0].local_position = Vec3::new(angle.cos(), 0.0, angle.sin()) * 50.0;
+        bodies[0].rotation.y = -angle;
+        graph.update(bodies);
+    }
+
+    #[test]
+    fn ship_ends_up_on_the_port_of_a_moving_station() {
+        let (mut bodies, mut ship) = station_and_ship();
+        let mut graph = SceneGraph::from_bodies(&bodies).unwrap();
+        advance(&mut bodies, &mut graph, 0.0);
+        let mut camera = Camera::new(Vec3::zeros(), 100.0);
+        camera.phi = 0.5;
+        camera.update_position();
+        ship.update_position(&camera, 0.1);
+        ship.position = bodies[0].position + Vec3::new(-6.0, 1.0, 0.0);
+        let camera_offset = camera.position - ship.position;
+
+        let mut docking = Docking::begin(0, &ship, &bodies).unwrap();
+        let mut angle = 0.0;
+        while !docking.is_docked() {
+            angle += 0.05;
+            advance(&mut bodies, &mut graph, angle);
+            assert!(docking.update(&mut ship, &mut camera, &bodies, 0.1));
+            graph.place_ship(&mut ship);
+        }
+        // On the port as it is now, nose toward the station, camera carried along
+        let port = bodies[0].docking_port_position().unwrap();
+        assert!((ship.position - port).magnitude() < 1e-3);
+        let nose = (ship.model_matrix * nalgebra_glm::Vec4::new(0.0, 0.0, 1.0, 0.0)).xyz().normalize();
+        assert!((nose - (bodies[0].position - port).normalize()).magnitude() < 1e-3);
+        assert!((camera.position - ship.position - camera_offset).magnitude() < 1e-2);
+
+        docking.undock();
+        let mut steps = 0;
+        while docking.update(&mut ship, &mut camera, &bodies, 0.1) {
+            steps += 1;
+            assert!(steps <= 25, "never let go of the ship");
+        }
+        assert!((ship.position - bodies[0].position).magnitude() > (port - bodies[0].position).magnitude());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::{ShaderType, Uniforms};
+
+// Sanity checks around every render() call, only compiled into debug builds.
+// A failing check panics with the label of the draw that broke it
+
+// Every this-many-th pixel outside a draw's scissor is compared before and
+// after it. Prime, so the samples don't line up in columns
+const SAMPLE_STRIDE: usize = 31;
+
+// What a draw may not change, taken before it runs
+pub struct DrawCheck<'a> {
+    label: &'a str,
+    // Index, color and depth bits of the sampled pixels
+    samples: Vec<(usize, u32, u32)>,
+}
+
+impl<'a> DrawCheck<'a> {
+    pub fn begin(framebuffer: &Framebuffer, uniforms: &Uniforms<'a>) -> Self {
+        let label = uniforms.label;
+        if let Err(problem) = check_setup(framebuffer, uniforms).and_then(|_| check_order(framebuffer, uniforms)) {
+            panic!("draw \"{}\": {}", label, problem);
+        }
+        let (viewport, clip) = (uniforms.frame.viewport, framebuffer.clip());
+        let samples = (0..framebuffer.buffer.len())
+            .step_by(SAMPLE_STRIDE)
+            .filter(|&index| {
+                let (x, y) = (index % framebuffer.width, index / framebuffer.width);
+                !(viewport.contains(x, y) && clip.contains(x, y))
+            })
+            .map(|index| (index, framebuffer.buffer[index], framebuffer.zbuffer[index].to_bits()))
+            .collect();
+        DrawCheck { label, samples }
+    }
+
+    pub fn finish(self, framebuffer: &Framebuffer) {
+        if let Err(problem) = self.check_samples(framebuffer) {
+            panic!("draw \"{}\": {}", self.label, problem);
+        }
+    }
+
+    fn check_samples(&self, framebuffer: &Framebuffer) -> Result<(), String> {
+        for &(index, color, depth) in &self.samples {
+            if framebuffer.buffer[index] != color || framebuffer.zbuffer[index].to_bits() != depth {
+                let (x, y) = (index % framebuffer.width, index / framebuffer.width);
+                return Err(format!("wrote pixel ({}, {}) outside its scissor", x, y));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn inside(rect: Viewport, framebuffer: &Framebuffer) -> bool {
+    rect.x + rect.width <= framebuffer.width && rect.y + rect.height <= framebuffer.height
+}
+
+// The draw's parameters and rectangles
+fn check_setup(framebuffer: &Framebuffer, uniforms: &Uniforms) -> Result<(), String> {
+    if !uniforms.params.suits(uniforms.shader_type) {
+        return Err(format!("shader params {:?} don't go with its shader", uniforms.params));
+    }
+    if !inside(uniforms.frame.viewport, framebuffer) {
+        return Err(format!("viewport {:?} is outside the {}x{} framebuffer", uniforms.frame.viewport, framebuffer.width, framebuffer.height));
+    }
+    if !inside(framebuffer.clip(), framebuffer) {
+        return Err(format!("clip {:?} is outside the {}x{} framebuffer", framebuffer.clip(), framebuffer.width, framebuffer.height));
+    }
+    Ok(())
+}
+
+// Blending mixes with what's already there, so anything written to the
+// depth buffer afterwards would cover it or show through it wrongly. The
+// sky writes no depth and may come after
+fn check_order(framebuffer: &Framebuffer, uniforms: &Uniforms) -> Result<(), String> {
+    let scissor = uniforms.frame.viewport;
+    if !matches!(uniforms.shader_type, ShaderType::Skybox) && framebuffer.blended_over(scissor) {
+        return Err("writes depth after something was blended over its viewport".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameUniforms, Lighting, ScreenProjection};
+    use crate::low_spec::Detail;
+    use crate::light::ShadingModel;
+    use crate::shader_params::ShaderParams;
+    use nalgebra_glm::{Mat4, Vec3};
+
+    #[test]
+    fn bad_draws_are_caught() {
+        let projection = ScreenProjection {
+            view_matrix: Mat4::identity(),
+            projection_matrix: Mat4::identity(),
+            viewport_matrix: Mat4::identity(),
+            snap_to_pixels: false,
+            affine: false,
+        };
+        let lighting = Lighting { lights: &[], ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let mut framebuffer = Framebuffer::new(20, 10);
+        let (left, right) = framebuffer.full_viewport().split(2);
+        let frame = FrameUniforms::new(&projection, lighting, 0.0, left);
+        let rocky = Uniforms::new(&frame, Mat4::identity(), ShaderType::RockyPlanet).with_label("Rock");
+        assert!(check_setup(&framebuffer, &rocky).is_ok());
+
+        // Another shader's parameters, except in the heat view
+        let star_params = ShaderParams::defaults(ShaderType::Star);
+        assert!(check_setup(&framebuffer, &Uniforms { params: star_params, ..rocky }).is_err());
+        let heat = Uniforms::new(&frame, Mat4::identity(), ShaderType::Heat { color: 0 }).with_params(star_params);
+        assert!(check_setup(&framebuffer, &heat).is_ok());
+
+        // A viewport hanging off the edge
+        let off_edge = FrameUniforms::new(&projection, lighting, 0.0, Viewport { x: 15, ..left });
+        assert!(check_setup(&framebuffer, &Uniforms::new(&off_edge, Mat4::identity(), ShaderType::Star)).is_err());
+
+        // Blending into one half only holds up later depth writes there
+        framebuffer.set_clip(right);
+        framebuffer.blend_rect(0, 0, 20, 10, 0xFFFFFF, 0.5);
+        assert!(check_order(&framebuffer, &rocky).is_ok());
+        framebuffer.set_clip(left);
+        framebuffer.blend_point(1, 1, 0.0, 0xFFFFFF, 0.5);
+        assert!(check_order(&framebuffer, &rocky).is_err());
+        assert!(check_order(&framebuffer, &Uniforms::new(&frame, Mat4::identity(), ShaderType::Skybox)).is_ok());
+        framebuffer.clear();
+        assert!(check_order(&framebuffer, &rocky).is_ok());
+
+        // Pixels outside the scissor must come through untouched
+        let check = DrawCheck::begin(&framebuffer, &rocky);
+        framebuffer.buffer[0] = 0x123456;
+        assert!(check.check_samples(&framebuffer).is_ok());
+        // (11, 1), in the right half
+        framebuffer.buffer[SAMPLE_STRIDE] = 0x123456;
+        assert!(check.check_samples(&framebuffer).is_err());
+    }
+}
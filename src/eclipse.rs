@@ -0,0 +1,171 @@
+use std::f32::consts::PI;
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::light::{Light, LightKind};
+
+// A body standing between a star and someone else's surface: a moon
+// crossing its planet, a planet over its moon or one moon over another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Occluder {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+// Bodies whose shadow, penumbra included, can reach `receiver` from any of
+// the lights. Usually none, which keeps the per-pixel work off. Stars
+// outshine any shadow they'd cast and portals are see-through
+pub fn occluders(bodies: &[CelestialBody], receiver: usize, lights: &[Light]) -> Vec<Occluder> {
+    let target = &bodies[receiver];
+    bodies.iter().enumerate()
+        .filter(|&(i, body)| i != receiver && !body.is_star() && !body.is_virtual() && !body.is_portal())
+        .map(|(_, body)| Occluder { center: body.position, radius: body.shadow_radius() })
+        .filter(|occluder| lights.iter().any(|light| reaches(light, occluder, target.position, target.bounding_radius())))
+        .collect()
+}
+
+// Whether the shadow cone of `occluder` touches the sphere at `center`
+fn reaches(light: &Light, occluder: &Occluder, center: Vec3, radius: f32) -> bool {
+    let (axis, spread) = match light.kind {
+        LightKind::Point { .. } => {
+            let from_light = occluder.center - light.position;
+            let distance = from_light.magnitude();
+            if distance <= f32::EPSILON {
+                return false;
+            }
+            // The penumbra widens by the star's and the occluder's radii over
+            // the distance between them
+            (from_light / distance, (light.radius + occluder.radius) / distance)
+        }
+        LightKind::Directional { direction } => (direction, 0.0),
+    };
+    let offset = center - occluder.center;
+    let along = offset.dot(&axis);
+    if along < -(radius + occluder.radius) {
+        return false;
+    }
+    let across = (offset - axis * along).magnitude();
+    across < occluder.radius + along.max(0.0) * spread + radius
+}
+
+// Per channel share of the light at `point` that gets past the occluders:
+// 1 in full light, 0 in a total eclipse
+pub fn light_passing(lights: &[Light], occluders: &[Occluder], point: Vec3) -> Vec3 {
+    let (all, passing) = lights.iter().fold((Vec3::zeros(), Vec3::zeros()), |(all, passing), light| {
+        let Some((_, attenuation)) = light.incoming(point) else { return (all, passing) };
+        let strength = light.color * (attenuation * light.intensity);
+        (all + strength, passing + strength * unblocked(light, occluders, point))
+    });
+    all.zip_map(&passing, |all, passing| if all > f32::EPSILON { passing / all } else { 1.0 })
+}
+
+// Share of one star's disc seen from `point` that no occluder covers.
+// Directional lights have no disc and cast hard shadows
+fn unblocked(light: &Light, occluders: &[Occluder], point: Vec3) -> f32 {
+    let (to_light, distance) = match light.kind {
+        LightKind::Point { .. } => {
+            let offset = light.position - point;
+            (offset.normalize(), offset.magnitude())
+        }
+        LightKind::Directional { direction } => (-direction, f32::INFINITY),
+    };
+    let disc = (light.radius / distance).min(1.0).asin();
+    occluders.iter().fold(1.0, |share, occluder| {
+        let offset = occluder.center - point;
+        let reach = offset.magnitude();
+        // Nearer than the star and in front of the point
+        if reach <= occluder.radius || reach - occluder.radius >= distance || offset.dot(&to_light) <= 0.0 {
+            return share;
+        }
+        let size = (occluder.radius / reach).asin();
+        let apart = (offset.dot(&to_light) / reach).clamp(-1.0, 1.0).acos();
+        let covered = if disc > 0.0 {
+            overlap(disc, size, apart) / (PI * disc * disc)
+        } else if apart < size {
+            1.0
+        } else {
+            0.0
+        };
+        share * (1.0 - covered.clamp(0.0, 1.0))
+    })
+}
+
+// Area two discs of radii `a` and `b` share with their centers `apart`
+fn overlap(a: f32, b: f32, apart: f32) -> f32 {
+    if apart >= a + b {
+        return 0.0;
+    }
+    if apart <= (a - b).abs() {
+        return PI * a.min(b).powi(2);
+    }
+    let lens = |r: f32, other: f32| r * r * ((apart * apart + r * r - other * other) / (2.0 * apart * r)).clamp(-1.0, 1.0).acos();
+    let kite = ((-apart + a + b) * (apart + a - b) * (apart - a + b) * (apart + a + b)).max(0.0).sqrt();
+    lens(a, b) + lens(b, a) - 0.5 * kite
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::light::collect_lights;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec2;
+
+    fn ball(x: f32, y: f32, radius: f32, shader_type: ShaderType) -> CelestialBody {
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let mut body = CelestialBody::new_planet("Ball".to_string(), vec![rim], Vec3::zeros(), 0.0, 0.0, radius, Color::from_hex(0xFFFFFF), shader_type);
+        body.position = Vec3::new(x, y, 0.0);
+        body
+    }
+
+    #[test]
+    fn discs_in_front_of_the_star_dim_what_is_behind_them() {
+        // A star of radius 10 seen from 1000 away along +X
+        let sun = Light { position: Vec3::new(1000.0, 0.0, 0.0), color: Vec3::new(1.0, 0.5, 0.5), intensity: 1.0, radius: 10.0, kind: LightKind::default() };
+        let at = |occluder: Occluder| light_passing(&[sun], &[occluder], Vec3::zeros());
+        assert_eq!(light_passing(&[sun], &[], Vec3::zeros()), Vec3::repeat(1.0));
+
+        // Bigger than the disc halfway there: a total eclipse
+        assert_eq!(at(Occluder { center: Vec3::new(500.0, 0.0, 0.0), radius: 6.0 }), Vec3::zeros());
+        // Half the star's angular radius, centered: a quarter of the disc
+        let annular = at(Occluder { center: Vec3::new(500.0, 0.0, 0.0), radius: 2.5 });
+        assert!((annular.x - 0.75).abs() < 1e-3 && annular.x == annular.y, "{:?}", annular);
+        // Just touching the disc's edge from the side, part of it
+        let partial = at(Occluder { center: Vec3::new(500.0, 5.0, 0.0), radius: 2.5 }).x;
+        assert!(partial > 0.75 && partial < 1.0, "{}", partial);
+        // Off to the side, behind the point or beyond the star: nothing
+        for center in [Vec3::new(500.0, 20.0, 0.0), Vec3::new(-500.0, 0.0, 0.0), Vec3::new(1500.0, 0.0, 0.0)] {
+            assert_eq!(at(Occluder { center, radius: 6.0 }), Vec3::repeat(1.0));
+        }
+
+        // Directional light has hard edges
+        let beam = Light { kind: LightKind::Directional { direction: -Vec3::x() }, ..sun };
+        assert_eq!(light_passing(&[beam], &[Occluder { center: Vec3::new(50.0, 0.9, 0.0), radius: 1.0 }], Vec3::zeros()), Vec3::zeros());
+        assert_eq!(light_passing(&[beam], &[Occluder { center: Vec3::new(50.0, 1.1, 0.0), radius: 1.0 }], Vec3::zeros()), Vec3::repeat(1.0));
+
+        // Only bodies in the shadow cone are handed to the shaders. Its axis
+        // runs from the star through the occluder, twice as far off the X
+        // axis at the origin, where the penumbra is (10 + 6) / 500 * 500 =
+        // 16 wider than the occluder
+        assert!(reaches(&sun, &Occluder { center: Vec3::new(500.0, 10.0, 0.0), radius: 6.0 }, Vec3::zeros(), 1.0));
+        assert!(!reaches(&sun, &Occluder { center: Vec3::new(500.0, 15.0, 0.0), radius: 6.0 }, Vec3::zeros(), 1.0));
+        assert!(!reaches(&sun, &Occluder { center: Vec3::new(-500.0, 0.0, 0.0), radius: 6.0 }, Vec3::zeros(), 1.0));
+
+        // A planet with two moons lined up sunward of it, and one off to
+        // the side: the planet gets both shadows, the outer moon the inner
+        // one's, the inner moon none
+        let bodies = vec![
+            ball(1000.0, 0.0, 10.0, ShaderType::Star),
+            ball(0.0, 0.0, 5.0, ShaderType::GasGiant),
+            ball(20.0, 0.5, 1.0, ShaderType::RockyPlanet),
+            ball(40.0, 0.0, 1.0, ShaderType::RockyPlanet),
+            ball(0.0, 40.0, 1.0, ShaderType::RockyPlanet),
+        ];
+        let lights = collect_lights(&bodies);
+        let centers = |receiver: usize| occluders(&bodies, receiver, &lights).iter().map(|occluder| occluder.center.x).collect::<Vec<_>>();
+        assert_eq!(centers(1), vec![20.0, 40.0]);
+        assert_eq!(centers(2), vec![40.0]);
+        assert!(centers(3).is_empty() && centers(4).is_empty());
+        assert!(light_passing(&lights, &occluders(&bodies, 1, &lights), Vec3::new(5.0, 0.0, 0.0)).x < 1.0);
+    }
+}
@@ -0,0 +1,133 @@
+use std::f64::consts::PI;
+use serde::Deserialize;
+
+// Days in a Julian century, the unit the element rates are given per
+const DAYS_PER_CENTURY: f64 = 36525.0;
+// Julian day number of the J2000 epoch (2000-01-01 12:00)
+const J2000: f64 = 2451545.0;
+const DAYS_PER_YEAR: f32 = 365.25;
+
+// [ephemeris] section of the scene file
+#[derive(Deserialize, Clone)]
+pub struct EphemerisSettings {
+    // Date the planets start at, YYYY-MM-DD; --epoch overrides it
+    pub epoch: String,
+    // Simulated seconds per Earth year, which sets every ephemeris body's
+    // orbital speed and, unless [units] says otherwise, the HUD's hours
+    #[serde(default = "default_year_seconds")]
+    pub year_seconds: f32,
+}
+
+fn default_year_seconds() -> f32 {
+    120.0
+}
+
+impl EphemerisSettings {
+    pub fn hour_seconds(&self) -> f32 {
+        self.year_seconds / (DAYS_PER_YEAR * 24.0)
+    }
+}
+
+// Mean elements at J2000 and their rates per Julian century, from Standish's
+// "Keplerian Elements for Approximate Positions of the Major Planets"
+// (the 1800-2050 fit). Angles in degrees, a in AU
+struct Elements {
+    name: &'static str,
+    semi_major_axis: f64,
+    eccentricity: f64,
+    mean_longitude: f64,
+    mean_longitude_rate: f64,
+    perihelion: f64,
+    perihelion_rate: f64,
+}
+
+const PLANETS: [Elements; 8] = [
+    Elements { name: "mercury", semi_major_axis: 0.38709927, eccentricity: 0.20563593, mean_longitude: 252.25032350, mean_longitude_rate: 149472.67411175, perihelion: 77.45779628, perihelion_rate: 0.16047689 },
+    Elements { name: "venus", semi_major_axis: 0.72333566, eccentricity: 0.00677672, mean_longitude: 181.97909950, mean_longitude_rate: 58517.81538729, perihelion: 131.60246718, perihelion_rate: 0.00268329 },
+    Elements { name: "earth", semi_major_axis: 1.00000261, eccentricity: 0.01671123, mean_longitude: 100.46457166, mean_longitude_rate: 35999.37244981, perihelion: 102.93768193, perihelion_rate: 0.32327364 },
+    Elements { name: "mars", semi_major_axis: 1.52371034, eccentricity: 0.09339410, mean_longitude: -4.55343205, mean_longitude_rate: 19140.30268499, perihelion: -23.94362959, perihelion_rate: 0.44441088 },
+    Elements { name: "jupiter", semi_major_axis: 5.20288700, eccentricity: 0.04838624, mean_longitude: 34.39644051, mean_longitude_rate: 3034.74612775, perihelion: 14.72847983, perihelion_rate: 0.21252668 },
+    Elements { name: "saturn", semi_major_axis: 9.53667594, eccentricity: 0.05386179, mean_longitude: 49.95424423, mean_longitude_rate: 1222.49362201, perihelion: 92.59887831, perihelion_rate: -0.41897216 },
+    Elements { name: "uranus", semi_major_axis: 19.18916464, eccentricity: 0.04725744, mean_longitude: 313.23810451, mean_longitude_rate: 428.48202785, perihelion: 170.95427630, perihelion_rate: 0.40805281 },
+    Elements { name: "neptune", semi_major_axis: 30.06992276, eccentricity: 0.00859048, mean_longitude: -55.12002969, mean_longitude_rate: 218.45945325, perihelion: 44.96476227, perihelion_rate: -0.32241464 },
+];
+
+// Where a planet is along its orbit on some date
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    // Angle past perihelion, which is what a body's orbital_angle holds
+    pub true_anomaly: f32,
+    // Longitude of perihelion, measured from the vernal equinox (+X here)
+    pub perihelion: f32,
+    // Kepler's third law in AU and years: T = a^1.5
+    pub period_years: f32,
+}
+
+// Days since J2000 of midnight at the start of a YYYY-MM-DD date
+pub fn parse_date(text: &str) -> Result<f64, String> {
+    let error = || format!("Bad date {}, expected YYYY-MM-DD", text);
+    let parts: Vec<i64> = text.trim().split('-').map(str::parse).collect::<Result<_, _>>().map_err(|_| error())?;
+    let [year, month, day] = parts[..] else { return Err(error()) };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(error());
+    }
+    // Gregorian calendar to Julian day number (Fliegel and Van Flandern)
+    let a = (14 - month) / 12;
+    let (y, m) = (year + 4800 - a, month + 12 * a - 3);
+    let day_number = day + (153 * m + 2) / 5 + 365 * y + y / 4 - y / 100 + y / 400 - 32045;
+    // The day number counts from noon, the date starts half a day earlier
+    Ok(day_number as f64 - 0.5 - J2000)
+}
+
+// Position of the named planet (any case) `days` after J2000
+pub fn place(planet: &str, days: f64) -> Result<Placement, String> {
+    let elements = PLANETS.iter()
+        .find(|elements| elements.name.eq_ignore_ascii_case(planet))
+        .ok_or_else(|| format!("No ephemeris for {}", planet))?;
+    let centuries = days / DAYS_PER_CENTURY;
+    let mean_longitude = (elements.mean_longitude + elements.mean_longitude_rate * centuries).to_radians();
+    let perihelion = (elements.perihelion + elements.perihelion_rate * centuries).to_radians();
+    let true_anomaly = true_anomaly((mean_longitude - perihelion).rem_euclid(2.0 * PI), elements.eccentricity);
+    Ok(Placement {
+        true_anomaly: true_anomaly as f32,
+        perihelion: perihelion.rem_euclid(2.0 * PI) as f32,
+        period_years: elements.semi_major_axis.powf(1.5) as f32,
+    })
+}
+
+// Solves Kepler's equation M = E - e sin E by Newton's method, then turns
+// the eccentric anomaly into the true one
+pub fn true_anomaly(mean_anomaly: f64, eccentricity: f64) -> f64 {
+    let mut eccentric = mean_anomaly;
+    for _ in 0..10 {
+        eccentric -= (eccentric - eccentricity * eccentric.sin() - mean_anomaly) / (1.0 - eccentricity * eccentric.cos());
+    }
+    let half = eccentric / 2.0;
+    2.0 * ((1.0 + eccentricity).sqrt() * half.sin()).atan2((1.0 - eccentricity).sqrt() * half.cos())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn planets_land_where_the_almanac_has_them() {
+        assert_eq!(parse_date("2000-01-01").unwrap(), -0.5);
+        assert_eq!(parse_date("2025-06-01").unwrap(), 9282.5);
+        assert!(parse_date("2025-13-01").is_err() && parse_date("June 1").is_err());
+
+        // Heliocentric longitudes on 2025-06-01, worked out from that year's
+        // conjunctions and oppositions (Mercury was just past superior
+        // conjunction, Venus at greatest western elongation, Jupiter three
+        // weeks from conjunction), within the few degrees mean elements allow
+        let days = parse_date("2025-06-01").unwrap();
+        for (planet, expected) in [("Mercury", 81.0), ("Venus", 294.0), ("Earth", 250.5), ("Mars", 177.0), ("Jupiter", 91.0), ("Saturn", 354.5), ("Uranus", 57.0), ("Neptune", 0.0)] {
+            let placement = place(planet, days).unwrap();
+            let longitude = (placement.perihelion + placement.true_anomaly).to_degrees();
+            let error = (longitude - expected + 180.0).rem_euclid(360.0) - 180.0;
+            assert!(error.abs() < 3.0, "{} at {:.1}, expected {}", planet, longitude, expected);
+        }
+        assert!((place("mars", 0.0).unwrap().period_years - 1.881).abs() < 0.001);
+        assert!(place("Pluto", 0.0).is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use nalgebra_glm::{Vec3, Vec4};
+use serde::Deserialize;
+use crate::body::CelestialBody;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use crate::geometry::normalize_or;
+use crate::overlay::{ScreenProjection, draw_glow};
+use crate::rng::{Rng, Seed};
+use crate::terrain::value_noise;
+
+// An eruption lasts this many simulated seconds, throwing
+// PARTICLES_PER_SECOND particles a second at intensity 1
+const ERUPTION_SECONDS: f32 = 4.0;
+const PARTICLES_PER_SECOND: f32 = 30.0;
+const MAX_INTENSITY: f32 = 4.0;
+// The lava around a vent takes this long to brighten
+const GLOW_RISE_SECONDS: f32 = 0.3;
+// Launch speed in body radii per second at intensity 1, and the pull back
+// down in radii per second squared: straight up, a particle climbs half a
+// radius and lands two seconds later
+const LAUNCH_SPEED: f32 = 1.0;
+const SURFACE_GRAVITY: f32 = 1.0;
+// How far launches stray from the vent's normal
+const SPREAD: f32 = 0.35;
+// Anything still flying after this long is dropped
+const MAX_PARTICLE_SECONDS: f32 = 6.0;
+// Spots tried for each new vent. The one where the noise runs highest wins,
+// so eruptions keep coming back to the same hot provinces
+const VENT_CANDIDATES: usize = 8;
+const VENT_NOISE_SCALE: f32 = 2.0;
+// A body with this many ash fields loses its oldest one to the next
+const MAX_ASH: usize = 16;
+// Vents show from orbit as a glowing point up to this many pixels across
+const VENT_GLOW_SIZE: f32 = 6.0;
+const VENT_COLOR: u32 = 0xFFD080;
+// Particles cool from the first color to the second over COOLING_SECONDS
+const COOLING_SECONDS: f32 = 2.0;
+const HOT_PARTICLE: u32 = 0xFFE060;
+const COOL_PARTICLE: u32 = 0xB02010;
+
+// [bodies.eruptions] table, rocky bodies only
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct EruptionSettings {
+    // Simulated seconds between eruptions on average
+    #[serde(default = "default_interval")]
+    pub interval: f32,
+    // 1 is an ordinary eruption; higher throws more, higher and brighter
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+}
+
+impl EruptionSettings {
+    pub fn validate(&self, owner: &str) -> Result<(), String> {
+        if self.interval <= 0.0 {
+            return Err(format!("{}: eruption interval must be positive", owner));
+        }
+        if self.intensity <= 0.0 || self.intensity > MAX_INTENSITY {
+            return Err(format!("{}: eruption intensity must be in (0, {}]", owner, MAX_INTENSITY));
+        }
+        Ok(())
+    }
+}
+
+fn default_interval() -> f32 {
+    30.0
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+// When a body erupts next, drawn from its own stream so bodies don't
+// change each other's timing
+struct Schedule {
+    rng: Rng,
+    countdown: f32,
+}
+
+struct Eruption {
+    body: usize,
+    // Unit direction in model space, so the vent turns with the surface
+    vent: Vec3,
+    intensity: f32,
+    age: f32,
+    // Particles owed to the emission rate but not thrown yet
+    owed: f32,
+}
+
+// Relative to the body's center but not turned with it: once thrown, a
+// particle flies free while the surface spins on underneath
+struct Particle {
+    body: usize,
+    offset: Vec3,
+    velocity: Vec3,
+    age: f32,
+}
+
+pub struct Eruptions {
+    seed: Seed,
+    // By body index, None for bodies that don't erupt
+    schedules: Vec<Option<Schedule>>,
+    active: Vec<Eruption>,
+    particles: Vec<Particle>,
+}
+
+impl Eruptions {
+    pub fn new(seed: Seed) -> Self {
+        Self { seed: seed.derive("eruptions"), schedules: Vec::new(), active: Vec::new(), particles: Vec::new() }
+    }
+
+    // Starts eruptions when they're due, throws and moves their particles
+    // and hands each body its glowing vents. Run after the scene graph, on
+    // simulated time like the impacts
+    pub fn update(&mut self, bodies: &mut [CelestialBody], sim_delta: f32) {
+        // Bodies may start or stop erupting when the scene is reloaded
+        self.schedules.resize_with(bodies.len(), || None);
+        for (i, body) in bodies.iter().enumerate() {
+            let Some(settings) = body.eruptions else {
+                self.schedules[i] = None;
+                continue;
+            };
+            let schedule = self.schedules[i].get_or_insert_with(|| {
+                let mut rng = self.seed.derive(i).rng();
+                Schedule { countdown: wait(&mut rng, settings.interval), rng }
+            });
+            schedule.countdown -= sim_delta;
+            while schedule.countdown <= 0.0 {
+                let vent = pick_vent(&mut schedule.rng);
+                self.active.push(Eruption { body: i, vent, intensity: settings.intensity, age: 0.0, owed: 0.0 });
+                schedule.countdown += wait(&mut schedule.rng, settings.interval);
+            }
+        }
+
+        for eruption in &mut self.active {
+            eruption.age += sim_delta;
+            eruption.owed += PARTICLES_PER_SECOND * eruption.intensity * sim_delta;
+            let body = &bodies[eruption.body];
+            let Some(rng) = self.schedules[eruption.body].as_mut().map(|schedule| &mut schedule.rng) else { continue };
+            // Thrown from where the vent is now, riding the spinning surface
+            let radius = body.bounding_radius();
+            let normal = normalize_or((body.model_matrix * Vec4::new(eruption.vent.x, eruption.vent.y, eruption.vent.z, 0.0)).xyz(), Vec3::y());
+            let speed = LAUNCH_SPEED * eruption.intensity.sqrt() * radius;
+            while eruption.owed >= 1.0 {
+                eruption.owed -= 1.0;
+                let direction = normalize_or(normal + rng.unit_vector() * SPREAD, normal);
+                self.particles.push(Particle { body: eruption.body, offset: normal * radius, velocity: direction * speed * rng.range(0.6, 1.0), age: 0.0 });
+            }
+        }
+
+        // Finished eruptions leave their ash behind
+        for eruption in self.active.iter().filter(|eruption| eruption.age >= ERUPTION_SECONDS) {
+            let ash = &mut bodies[eruption.body].ash;
+            if ash.len() >= MAX_ASH {
+                ash.remove(0);
+            }
+            ash.push(eruption.vent);
+        }
+        self.active.retain(|eruption| eruption.age < ERUPTION_SECONDS);
+
+        self.particles.retain_mut(|particle| {
+            let radius = bodies[particle.body].bounding_radius();
+            let down = -normalize_or(particle.offset, Vec3::y());
+            particle.velocity += down * SURFACE_GRAVITY * radius * sim_delta;
+            particle.offset += particle.velocity * sim_delta;
+            particle.age += sim_delta;
+            particle.offset.magnitude() > radius && particle.age < MAX_PARTICLE_SECONDS
+        });
+
+        for body in bodies.iter_mut() {
+            body.vents.clear();
+        }
+        for eruption in &self.active {
+            let rise = (eruption.age / GLOW_RISE_SECONDS).min(1.0);
+            let fall = (1.0 - eruption.age / ERUPTION_SECONDS).max(0.0).sqrt();
+            let glow = (rise * fall * (0.5 + 0.5 * eruption.intensity)).min(1.0);
+            bodies[eruption.body].vents.push((eruption.vent, glow));
+        }
+    }
+
+    // Particles as small glowing dots and each vent as a bright point, which
+    // is what shows of an eruption from far out, even on the night side.
+    // Depth tested, so the far side stays hidden
+    pub fn draw(&self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, bodies: &[CelestialBody]) {
+        for particle in &self.particles {
+            if let Some(screen) = projection.project(bodies[particle.body].position + particle.offset) {
+                let cooled = particle.age / COOLING_SECONDS;
+                let color = Color::lerp(Color::from_hex(HOT_PARTICLE), Color::from_hex(COOL_PARTICLE), cooled).to_pixel();
+                draw_glow(framebuffer, screen, 2, color, 1.0);
+            }
+        }
+        for body in bodies {
+            for &(vent, glow) in &body.vents {
+                // Just above the surface, so the ground around it doesn't hide it
+                let point = vent * body.mesh_radius * 1.02;
+                let world = (body.model_matrix * Vec4::new(point.x, point.y, point.z, 1.0)).xyz();
+                if let Some(screen) = projection.project(world) {
+                    draw_glow(framebuffer, screen, (VENT_GLOW_SIZE * glow).ceil() as i32, VENT_COLOR, glow);
+                }
+            }
+        }
+    }
+}
+
+// Simulated seconds to the next eruption, exponentially distributed so
+// eruptions come at random but `interval` apart on average
+fn wait(rng: &mut Rng, interval: f32) -> f32 {
+    -interval * (1.0 - rng.next_f32()).ln()
+}
+
+fn pick_vent(rng: &mut Rng) -> Vec3 {
+    (0..VENT_CANDIDATES)
+        .map(|_| rng.unit_vector())
+        .max_by(|a, b| value_noise(a * VENT_NOISE_SCALE).total_cmp(&value_noise(b * VENT_NOISE_SCALE)))
+        .unwrap_or(Vec3::y())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_graph::SceneGraph;
+    use crate::ShaderType;
+
+    fn volcano() -> CelestialBody {
+        let vertices = vec![crate::vertex::Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), nalgebra_glm::Vec2::zeros())];
+        let mut body = CelestialBody::new_planet("Volcano".to_string(), vertices, Vec3::zeros(), 0.0, 0.0, 10.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+        body.eruptions = Some(EruptionSettings { interval: 5.0, intensity: 1.0 });
+        body.rotation_speed = 0.5;
+        body
+    }
+
+    #[test]
+    fn eruptions_repeat_with_the_seed_and_throw_from_the_turning_surface() {
+        let run = || {
+            let mut bodies = vec![volcano()];
+            let mut graph = SceneGraph::from_bodies(&bodies).unwrap();
+            let mut eruptions = Eruptions::new(Seed(3));
+            let mut launches = Vec::new();
+            for _ in 0..400 {
+                bodies[0].spin(0.1);
+                graph.update(&mut bodies);
+                let thrown = eruptions.particles.len();
+                eruptions.update(&mut bodies, 0.1);
+                // Every particle starts at its vent, wherever the spin has
+                // taken it. One that just ended has turned to ash already
+                let vents: Vec<Vec3> = bodies[0].vents.iter().map(|&(vent, _)| vent).chain(bodies[0].ash.iter().copied())
+                    .map(|vent| (bodies[0].model_matrix * Vec4::new(vent.x, vent.y, vent.z, 0.0)).xyz().normalize() * 10.0)
+                    .collect();
+                for particle in eruptions.particles.iter().filter(|particle| particle.age <= 0.1) {
+                    let start = particle.offset - particle.velocity * 0.1;
+                    assert!(vents.iter().any(|vent| (start - vent).magnitude() < 1e-3));
+                }
+                launches.push(eruptions.particles.len() as i64 - thrown as i64);
+                for particle in &eruptions.particles {
+                    assert!(particle.offset.magnitude() <= 10.0 * 1.6, "particles fall back down");
+                }
+            }
+            (bodies.remove(0).ash, launches)
+        };
+        let (ash, launches) = run();
+        assert!(ash.len() >= 3, "40 seconds at one eruption every 5 should leave ash, got {}", ash.len());
+        assert!(ash.iter().all(|field| (field.magnitude() - 1.0).abs() < 1e-4));
+        assert_eq!((ash, launches), run());
+    }
+}
@@ -0,0 +1,192 @@
+use serde::Deserialize;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// Stars are this many times brighter than the brightest color the screen
+// shows, so they stay white until the exposure drops a long way
+pub const STAR_RADIANCE: f32 = 8.0;
+// Manual exposure keys step by this many EV
+const MANUAL_STEP: f32 = 0.5;
+// Keeps black pixels out of the logarithm
+const LOG_EPSILON: f32 = 0.02;
+// Only every Nth pixel is measured, the average barely moves for it
+const SAMPLE_STRIDE: usize = 4;
+
+// [exposure] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct ExposureSettings {
+    // Adapt to the frame; when off the exposure stays at 0 EV
+    #[serde(default = "default_auto")]
+    pub auto: bool,
+    #[serde(default = "default_min_ev")]
+    pub min_ev: f32,
+    #[serde(default = "default_max_ev")]
+    pub max_ev: f32,
+    // Log-average luminance the adaptation aims the lit geometry at
+    #[serde(default = "default_key")]
+    pub key: f32,
+    // Time constants in seconds for getting brighter (adjusting to the dark,
+    // slow like real eyes) and darker (glancing at the sun, fast)
+    #[serde(default = "default_brighten_seconds")]
+    pub brighten_seconds: f32,
+    #[serde(default = "default_darken_seconds")]
+    pub darken_seconds: f32,
+    // Stops darker to aim for with a star's whole disc on screen and in
+    // sight; the frame tops out at white and can't show how bright it is
+    #[serde(default = "default_sun_glare")]
+    pub sun_glare: f32,
+}
+
+impl Default for ExposureSettings {
+    fn default() -> Self {
+        Self {
+            auto: default_auto(),
+            min_ev: default_min_ev(),
+            max_ev: default_max_ev(),
+            key: default_key(),
+            brighten_seconds: default_brighten_seconds(),
+            darken_seconds: default_darken_seconds(),
+            sun_glare: default_sun_glare(),
+        }
+    }
+}
+
+fn default_auto() -> bool {
+    true
+}
+
+fn default_min_ev() -> f32 {
+    -2.0
+}
+
+fn default_max_ev() -> f32 {
+    1.0
+}
+
+fn default_key() -> f32 {
+    0.3
+}
+
+fn default_brighten_seconds() -> f32 {
+    1.5
+}
+
+fn default_darken_seconds() -> f32 {
+    0.4
+}
+
+fn default_sun_glare() -> f32 {
+    0.5
+}
+
+// Exposure of the scene in EV (stops; 0 leaves colors alone), adapted to
+// what was rendered unless set by hand
+pub struct Exposure {
+    settings: ExposureSettings,
+    pub ev: f32,
+    // Set by the manual keys, adaptation waits until reset()
+    pub manual: bool,
+}
+
+impl Exposure {
+    pub fn new(settings: ExposureSettings) -> Self {
+        Self { settings, ev: 0.0, manual: false }
+    }
+
+    // Limits and speeds from a reloaded scene, the current exposure stays
+    pub fn set_settings(&mut self, settings: ExposureSettings) {
+        self.settings = settings;
+        self.ev = self.ev.clamp(settings.min_ev, settings.max_ev);
+    }
+
+    // Factor the scene's colors are multiplied by
+    pub fn multiplier(&self) -> f32 {
+        self.ev.exp2()
+    }
+
+    // Moves toward the exposure that puts `luminance` (log-average of the
+    // last frame, rendered at the current exposure) at the key value, less
+    // the glare of a star in view. `glare` is how much of it can be seen,
+    // see SunVisibility
+    pub fn update(&mut self, luminance: Option<f32>, glare: f32, delta_time: f32) {
+        let Some(luminance) = luminance else { return };
+        if self.manual || !self.settings.auto {
+            return;
+        }
+        let target = (self.ev + (self.settings.key / luminance).log2() - self.settings.sun_glare * glare)
+            .clamp(self.settings.min_ev, self.settings.max_ev);
+        let seconds = if target > self.ev { self.settings.brighten_seconds } else { self.settings.darken_seconds };
+        // Exponential approach, independent of the frame rate
+        self.ev += (target - self.ev) * (1.0 - (-delta_time / seconds.max(1e-3)).exp());
+    }
+
+    // Manual override, `steps` of MANUAL_STEP EV up or down
+    pub fn adjust(&mut self, steps: f32) {
+        self.manual = true;
+        self.ev = (self.ev + steps * MANUAL_STEP).clamp(self.settings.min_ev, self.settings.max_ev);
+    }
+
+    // Back to automatic (or to 0 EV when adaptation is off)
+    pub fn reset(&mut self) {
+        self.manual = false;
+        if !self.settings.auto {
+            self.ev = 0.0;
+        }
+    }
+}
+
+// Log-average luminance of the rendered geometry, None when nothing but
+// background is on screen
+pub fn log_average_luminance(framebuffer: &Framebuffer) -> Option<f32> {
+    let (mut sum, mut count) = (0.0f32, 0usize);
+    for index in (0..framebuffer.buffer.len()).step_by(SAMPLE_STRIDE) {
+        if framebuffer.zbuffer[index].is_finite() {
+            let rgb = Color::from_hex(framebuffer.buffer[index]).to_vec3();
+            let luminance = 0.2126 * rgb.x + 0.7152 * rgb.y + 0.0722 * rgb.z;
+            sum += (LOG_EPSILON + luminance).ln();
+            count += 1;
+        }
+    }
+    (count > 0).then(|| (sum / count as f32).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adapts_faster_to_glare_than_to_the_dark() {
+        let settings = ExposureSettings::default();
+        let mut exposure = Exposure::new(settings);
+
+        // Staring at something four times too bright
+        exposure.update(Some(settings.key * 4.0), 0.0, settings.darken_seconds);
+        let darkened = -exposure.ev;
+        assert!((darkened - 2.0 * (1.0 - (-1.0f32).exp())).abs() < 1e-4);
+
+        // The same change the other way takes longer
+        let mut other = Exposure::new(settings);
+        other.ev = -2.0;
+        other.update(Some(settings.key / 4.0), 0.0, settings.darken_seconds);
+        assert!(other.ev + 2.0 < darkened);
+
+        // Never past the limits, and held while set by hand
+        for _ in 0..100 {
+            exposure.update(Some(settings.key * 1000.0), 0.0, 1.0);
+        }
+        assert_eq!(exposure.ev, settings.min_ev);
+        exposure.adjust(1.0);
+        exposure.update(Some(settings.key * 1000.0), 0.0, 1.0);
+        assert_eq!(exposure.ev, settings.min_ev + MANUAL_STEP);
+        exposure.reset();
+        exposure.update(Some(settings.key * 1000.0), 0.0, 10.0);
+        assert!(exposure.ev < settings.min_ev + 0.01);
+
+        // A star in full view takes sun_glare more off, a hidden one nothing
+        let (mut seen, mut hidden) = (Exposure::new(settings), Exposure::new(settings));
+        seen.update(Some(settings.key), 1.0, 100.0);
+        hidden.update(Some(settings.key), 0.0, 100.0);
+        assert!((seen.ev + settings.sun_glare).abs() < 1e-4);
+        assert!(hidden.ev.abs() < 1e-4);
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+// How often the file's modification time is looked at
+const POLL_SECONDS: f32 = 1.0;
+
+// Notices when a file is saved again, by polling its modification time
+pub struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    since_poll: f32,
+}
+
+impl FileWatcher {
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        let modified = modified_time(&path);
+        Self { path, modified, since_poll: 0.0 }
+    }
+
+    // True once after each change. A file that can't be read (an editor
+    // halfway through saving it) counts as unchanged until it's back
+    pub fn poll(&mut self, delta_time: f32) -> bool {
+        self.since_poll += delta_time;
+        if self.since_poll < POLL_SECONDS {
+            return false;
+        }
+        self.since_poll = 0.0;
+        match modified_time(&self.path) {
+            Some(modified) if self.modified != Some(modified) => {
+                self.modified = Some(modified);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn modified_time(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+
+    #[test]
+    fn reports_each_save_once_per_poll_interval() {
+        let path = std::env::temp_dir().join(format!("file_watch_{}.toml", std::process::id()));
+        fs::write(&path, "a = 1").unwrap();
+        let mut watcher = FileWatcher::new(path.to_str().unwrap());
+        assert!(!watcher.poll(2.0));
+
+        let touch = |seconds: u64| {
+            let file = File::options().write(true).open(&path).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+        };
+        touch(1_000);
+        // Not looked at again until a second has gone by
+        assert!(!watcher.poll(0.5));
+        assert!(watcher.poll(0.5));
+        assert!(!watcher.poll(1.0));
+
+        touch(2_000);
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.poll(1.0));
+    }
+}
@@ -0,0 +1,152 @@
+use serde::Deserialize;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+// [filters] section of the scene file: stylistic filters run on the finished
+// scene, in this order, before the HUD goes on top. Each one is off unless
+// turned on here or with its key
+#[derive(Deserialize, Clone, Copy)]
+pub struct FilterSettings {
+    // Animated film grain (F2), strongest in the shadows
+    #[serde(default)]
+    pub grain: bool,
+    #[serde(default = "default_grain_strength")]
+    pub grain_strength: f32,
+    // Darkening toward the corners (F4); higher falloff keeps more of the
+    // middle untouched
+    #[serde(default)]
+    pub vignette: bool,
+    #[serde(default = "default_vignette_strength")]
+    pub vignette_strength: f32,
+    #[serde(default = "default_vignette_falloff")]
+    pub vignette_falloff: f32,
+    // Retro CRT look (F10): every other row darker, colors bleeding sideways
+    #[serde(default)]
+    pub crt: bool,
+    #[serde(default = "default_scanline_darkness")]
+    pub scanline_darkness: f32,
+    #[serde(default = "default_crt_bleed")]
+    pub crt_bleed: f32,
+}
+
+impl Default for FilterSettings {
+    fn default() -> Self {
+        Self {
+            grain: false,
+            grain_strength: default_grain_strength(),
+            vignette: false,
+            vignette_strength: default_vignette_strength(),
+            vignette_falloff: default_vignette_falloff(),
+            crt: false,
+            scanline_darkness: default_scanline_darkness(),
+            crt_bleed: default_crt_bleed(),
+        }
+    }
+}
+
+fn default_grain_strength() -> f32 {
+    0.08
+}
+
+fn default_vignette_strength() -> f32 {
+    0.5
+}
+
+fn default_vignette_falloff() -> f32 {
+    2.0
+}
+
+fn default_scanline_darkness() -> f32 {
+    0.35
+}
+
+fn default_crt_bleed() -> f32 {
+    0.3
+}
+
+impl FilterSettings {
+    // `frame` seeds the grain, so replays come out the same every time
+    pub fn apply(&self, framebuffer: &mut Framebuffer, frame: usize) {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        if self.grain {
+            film_grain(&mut framebuffer.buffer, width, frame, self.grain_strength);
+        }
+        if self.vignette {
+            vignette(&mut framebuffer.buffer, width, height, self.vignette_strength, self.vignette_falloff);
+        }
+        if self.crt {
+            scanlines(&mut framebuffer.buffer, width, self.scanline_darkness, self.crt_bleed);
+        }
+    }
+}
+
+// Noise in -1..1 for a pixel on a frame, the same for the same inputs
+fn grain_noise(x: usize, y: usize, frame: usize) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6b343) ^ (y as u32).wrapping_mul(0xd8163841) ^ (frame as u32).wrapping_mul(0xcb1ab31f);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b3c6d);
+    hash ^= hash >> 12;
+    (hash & 0xFFFF) as f32 / 32767.5 - 1.0
+}
+
+pub fn film_grain(pixels: &mut [u32], width: usize, frame: usize, strength: f32) {
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let rgb = Color::from_hex(*pixel).to_vec3();
+        let luminance = 0.2126 * rgb.x + 0.7152 * rgb.y + 0.0722 * rgb.z;
+        // Highlights hide grain, shadows show it
+        let amount = grain_noise(index % width, index / width, frame) * strength * (1.0 - luminance).sqrt();
+        *pixel = Color::from_vec3(rgb.add_scalar(amount)).to_pixel();
+    }
+}
+
+// Darkens by `strength` at the corners, following the distance from the
+// center to the power of `falloff`
+pub fn vignette(pixels: &mut [u32], width: usize, height: usize, strength: f32, falloff: f32) {
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner = (center_x * center_x + center_y * center_y).sqrt();
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let dx = (index % width) as f32 + 0.5 - center_x;
+        let dy = (index / width) as f32 + 0.5 - center_y;
+        let reach = ((dx * dx + dy * dy).sqrt() / corner).powf(falloff);
+        *pixel = (Color::from_hex(*pixel) * (1.0 - strength * reach)).to_pixel();
+    }
+}
+
+// Odd rows darkened, each pixel blended with the one to its left
+pub fn scanlines(pixels: &mut [u32], width: usize, darkness: f32, bleed: f32) {
+    let source = pixels.to_vec();
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let left = if index % width == 0 { index } else { index - 1 };
+        let color = Color::from_hex(source[index]).to_vec3().lerp(&Color::from_hex(source[left]).to_vec3(), bleed);
+        let shade = if (index / width) % 2 == 1 { 1.0 - darkness } else { 1.0 };
+        *pixel = Color::from_vec3(color * shade).to_pixel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_are_repeatable_and_compose() {
+        let gray = vec![0x808080; 16 * 8];
+
+        // The same frame always gets the same grain, the next one doesn't
+        let (mut a, mut b, mut c) = (gray.clone(), gray.clone(), gray.clone());
+        film_grain(&mut a, 16, 7, 0.1);
+        film_grain(&mut b, 16, 7, 0.1);
+        film_grain(&mut c, 16, 8, 0.1);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, gray);
+
+        let mut framebuffer = Framebuffer::new(16, 8);
+        framebuffer.buffer = gray.clone();
+        let settings = FilterSettings { vignette: true, crt: true, ..FilterSettings::default() };
+        settings.apply(&mut framebuffer, 0);
+        let at = |x: usize, y: usize| Color::from_hex(framebuffer.buffer[y * 16 + x]).r;
+        // Scanlines on odd rows, and the vignette darker at the corner
+        assert!(at(8, 3) < at(8, 4));
+        assert!(at(0, 0) < at(8, 4));
+    }
+}
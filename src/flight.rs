@@ -0,0 +1,39 @@
+use nalgebra_glm::Vec3;
+use crate::body::{CelestialBody, nearest_body};
+
+// Time constant (seconds) of the readout smoothing
+const SMOOTHING: f32 = 0.3;
+
+// Smoothed speed and altitude of the camera (or ship) for the flight HUD
+#[derive(Default)]
+pub struct FlightTelemetry {
+    last_position: Option<Vec3>,
+    pub speed: f32,
+    pub altitude: f32,
+    // Body the altitude is measured from
+    pub nearest: Option<usize>,
+}
+
+impl FlightTelemetry {
+    pub fn update(&mut self, position: Vec3, bodies: &[CelestialBody], delta_time: f32) {
+        let blend = 1.0 - (-delta_time / SMOOTHING).exp();
+        if let Some(last) = self.last_position {
+            let speed = (position - last).magnitude() / delta_time;
+            self.speed += (speed - self.speed) * blend;
+        }
+        self.last_position = Some(position);
+
+        match nearest_body(bodies, position) {
+            // A new reference body starts from its own altitude instead of
+            // sliding over from the old one
+            Some((index, altitude)) if self.nearest == Some(index) => {
+                self.altitude += (altitude - self.altitude) * blend;
+            }
+            Some((index, altitude)) => {
+                self.nearest = Some(index);
+                self.altitude = altitude;
+            }
+            None => self.nearest = None,
+        }
+    }
+}
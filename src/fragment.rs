@@ -1,11 +1,16 @@
-use nalgebra_glm::Vec2;
+use nalgebra_glm::{Vec2, Vec3};
 use crate::color::Color;
 
 pub struct Fragment {
     pub position: Vec2,
     pub color: Color,
     pub depth: f32,
-    pub intensity: f32,  // For lighting calculations
+    pub intensity: Vec3,  // Per-channel light reaching the fragment
+    pub emissive: Color,  // Light it gives off itself, added unlit on top
+    // Where on the surface the fragment lies, interpolated perspective
+    // correctly; the normal isn't normalized
+    pub world_position: Vec3,
+    pub world_normal: Vec3,
 }
 
 impl Fragment {
@@ -14,16 +19,27 @@ impl Fragment {
             position: Vec2::new(x, y),
             color,
             depth,
-            intensity: 1.0,  // Default full intensity
+            intensity: Vec3::new(1.0, 1.0, 1.0),  // Default full intensity
+            emissive: Color::black(),
+            world_position: Vec3::zeros(),
+            world_normal: Vec3::zeros(),
         }
     }
     
-    pub fn new_with_intensity(x: f32, y: f32, color: Color, depth: f32, intensity: f32) -> Self {
+    pub fn new_with_intensity(x: f32, y: f32, color: Color, depth: f32, intensity: Vec3, emissive: Color) -> Self {
+        debug_assert!(x.is_finite() && y.is_finite() && !depth.is_nan(), "degenerate fragment at ({}, {}, {})", x, y, depth);
         Fragment {
             position: Vec2::new(x, y),
             color,
             depth,
             intensity,
+            emissive,
+            world_position: Vec3::zeros(),
+            world_normal: Vec3::zeros(),
         }
     }
+
+    pub fn with_surface(self, world_position: Vec3, world_normal: Vec3) -> Self {
+        Fragment { world_position, world_normal, ..self }
+    }
 }
@@ -1,12 +1,60 @@
 // framebuffer.rs
 
+// A rectangle of the framebuffer that one camera draws into
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn overlaps(&self, other: &Viewport) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    // Left and right halves, with a `gap` pixels wide column left between them
+    pub fn split(&self, gap: usize) -> (Viewport, Viewport) {
+        let left = (self.width.saturating_sub(gap)) / 2;
+        let right_x = self.x + left + gap;
+        (
+            Viewport { width: left, ..*self },
+            Viewport { x: right_x, width: self.x + self.width - right_x, ..*self },
+        )
+    }
+}
+
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
     pub zbuffer: Vec<f32>,
+    // Which object each pixel shows, written along with the depth; 0 where
+    // nothing with an ID was drawn. Only kept while track_ids() asks for it
+    pub ids: Option<Vec<u16>>,
     background_color: u32,
     current_color: u32,
+    current_id: u16,
+    // Drawing outside this rectangle is dropped; the whole buffer unless a
+    // viewport is being drawn
+    clip: Viewport,
+    // Off while drawing the sky, see set_depth_test()
+    depth_test: bool,
+    // Clip rectangles something was blended into since the last clear(),
+    // for the draw order checks in draw_checks.rs
+    #[cfg(debug_assertions)]
+    blended: Vec<Viewport>,
 }
 
 impl Framebuffer {
@@ -16,11 +64,31 @@ impl Framebuffer {
             height,
             buffer: vec![0; width * height],
             zbuffer: vec![f32::INFINITY; width * height],
+            ids: None,
             background_color: 0x000000,
             current_color: 0xFFFFFF,
+            current_id: 0,
+            clip: Viewport { x: 0, y: 0, width, height },
+            depth_test: true,
+            #[cfg(debug_assertions)]
+            blended: Vec::new(),
         }
     }
 
+    pub fn full_viewport(&self) -> Viewport {
+        Viewport { x: 0, y: 0, width: self.width, height: self.height }
+    }
+
+    pub fn clip(&self) -> Viewport {
+        self.clip
+    }
+
+    // Confines drawing to one viewport, or to the whole buffer again with
+    // full_viewport()
+    pub fn set_clip(&mut self, viewport: Viewport) {
+        self.clip = viewport;
+    }
+
     pub fn clear(&mut self) {
         for pixel in self.buffer.iter_mut() {
             *pixel = self.background_color;
@@ -28,23 +96,415 @@ impl Framebuffer {
         for depth in self.zbuffer.iter_mut() {
             *depth = f32::INFINITY;
         }
+        if let Some(ids) = &mut self.ids {
+            ids.fill(0);
+        }
+        #[cfg(debug_assertions)]
+        self.blended.clear();
+    }
+
+    // Starts or stops keeping the object ID of every pixel
+    pub fn track_ids(&mut self, enabled: bool) {
+        match (enabled, &self.ids) {
+            (true, None) => self.ids = Some(vec![0; self.width * self.height]),
+            (false, Some(_)) => self.ids = None,
+            _ => {}
+        }
+    }
+
+    // With the depth test off, point() still only draws in front of what's
+    // there but leaves the depth and IDs as they were, so nothing drawn
+    // later is hidden by it. The sky is drawn this way at f32::MAX: it only
+    // lands where nothing else has been drawn, can go before or after the
+    // bodies and never shows through them
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.depth_test = enabled;
     }
 
     pub fn point(&mut self, x: usize, y: usize, depth: f32) {
-        if x < self.width && y < self.height {
+        if self.clip.contains(x, y) {
+            let index = y * self.width + x;
+            let ids = self.ids.as_deref_mut().filter(|_| self.depth_test).map(|ids| (ids, self.current_id));
+            write_point(&mut self.buffer, &mut self.zbuffer, ids, index, depth, self.current_color, self.depth_test);
+        }
+    }
+
+    // Depth already stored at a pixel, None outside the clip rectangle
+    pub fn depth(&self, x: usize, y: usize) -> Option<f32> {
+        self.clip.contains(x, y).then(|| self.zbuffer[y * self.width + x])
+    }
+
+    // Depth-tested like point(), but mixes the color over what's already
+    // there and leaves the depth buffer alone (for translucent overlays)
+    pub fn blend_point(&mut self, x: usize, y: usize, depth: f32, color: u32, alpha: f32) {
+        self.note_blend();
+        if self.clip.contains(x, y) {
             let index = y * self.width + x;
             if self.zbuffer[index] > depth {
-                self.buffer[index] = self.current_color;
-                self.zbuffer[index] = depth;
+                self.buffer[index] = blend(self.buffer[index], color, alpha);
+            }
+        }
+    }
+
+    // Writes a pixel directly, bypassing the depth buffer (used for overlays)
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        if x >= 0 && y >= 0 && self.clip.contains(x as usize, y as usize) {
+            let index = y as usize * self.width + x as usize;
+            self.buffer[index] = color;
+        }
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: usize, height: usize, color: u32) {
+        for dy in 0..height as i32 {
+            for dx in 0..width as i32 {
+                self.set_pixel(x + dx, y + dy, color);
             }
         }
     }
 
+    // fill_rect() for translucent panels, the scene shows through
+    pub fn blend_rect(&mut self, x: i32, y: i32, width: usize, height: usize, color: u32, alpha: f32) {
+        self.note_blend();
+        let clip = self.clip;
+        for py in y.max(clip.y as i32)..(y + height as i32).min((clip.y + clip.height) as i32) {
+            for px in x.max(clip.x as i32)..(x + width as i32).min((clip.x + clip.width) as i32) {
+                let index = py as usize * self.width + px as usize;
+                self.buffer[index] = blend(self.buffer[index], color, alpha);
+            }
+        }
+    }
+
+    fn note_blend(&mut self) {
+        #[cfg(debug_assertions)]
+        if !self.blended.contains(&self.clip) {
+            self.blended.push(self.clip);
+        }
+    }
+
+    // Whether anything was blended over part of `viewport` since the last
+    // clear(), debug builds only
+    #[cfg(debug_assertions)]
+    pub fn blended_over(&self, viewport: Viewport) -> bool {
+        self.blended.iter().any(|blended| blended.overlaps(&viewport))
+    }
+
     pub fn set_background_color(&mut self, color: u32) {
         self.background_color = color;
     }
 
+    pub fn background_color(&self) -> u32 {
+        self.background_color
+    }
+
     pub fn set_current_color(&mut self, color: u32) {
         self.current_color = color;
     }
+
+    // ID written by point() from now on
+    pub fn set_current_id(&mut self, id: u16) {
+        self.current_id = id;
+    }
+
+    // Rows of squares of `size` pixels, from the top, narrower along the
+    // right and bottom edges. Each row takes its lines of the buffers as one
+    // slice, so nothing is laid out per frame, and rows can be drawn into at
+    // the same time from different threads. They keep the clip, the
+    // current ID and the depth test
+    pub fn tile_rows(&mut self, size: usize) -> Vec<TileRow<'_>> {
+        let (width, size) = (self.width.max(1), size.max(1));
+        let (clip, id, depth_test) = (self.clip, self.current_id, self.depth_test);
+        let mut ids = self.ids.as_mut().map(|ids| ids.chunks_mut(width * size));
+        self.buffer
+            .chunks_mut(width * size)
+            .zip(self.zbuffer.chunks_mut(width * size))
+            .enumerate()
+            .map(|(row, (buffer, zbuffer))| TileRow {
+                top: row * size,
+                width,
+                size,
+                clip,
+                id,
+                depth_test,
+                ids: ids.as_mut().and_then(|ids| ids.next()),
+                buffer,
+                zbuffer,
+            })
+            .collect()
+    }
+
+    // A cleared framebuffer of any size on the same background, keeping IDs
+    // if this one does, for drawing the scene again off screen
+    pub fn blank(&self, width: usize, height: usize) -> Framebuffer {
+        let mut framebuffer = Framebuffer::new(width, height);
+        framebuffer.set_background_color(self.background_color);
+        framebuffer.track_ids(self.ids.is_some());
+        framebuffer.clear();
+        framebuffer
+    }
+
+    // Stretches another framebuffer over `target`, nearest pixel, within
+    // the clip rectangle
+    pub fn blit_scaled(&mut self, source: &Framebuffer, target: Viewport) {
+        for y in 0..target.height {
+            let source_y = y * source.height / target.height.max(1);
+            for x in 0..target.width {
+                let source_x = x * source.width / target.width.max(1);
+                let color = source.buffer[source_y * source.width + source_x];
+                self.set_pixel((target.x + x) as i32, (target.y + y) as i32, color);
+            }
+        }
+    }
+
+    // Like blit_scaled but depth and IDs come along too, for a scene
+    // rendered at a lower resolution that the post-processing still has to see
+    pub fn upscale(&mut self, source: &Framebuffer, target: Viewport) {
+        for y in 0..target.height {
+            let source_y = y * source.height / target.height.max(1);
+            for x in 0..target.width {
+                let source_x = x * source.width / target.width.max(1);
+                let (px, py) = (target.x + x, target.y + y);
+                if self.clip.contains(px, py) {
+                    self.buffer[py * self.width + px] = source.buffer[source_y * source.width + source_x];
+                    self.zbuffer[py * self.width + px] = source.zbuffer[source_y * source.width + source_x];
+                    if let (Some(ids), Some(source_ids)) = (&mut self.ids, &source.ids) {
+                        ids[py * self.width + px] = source_ids[source_y * source.width + source_x];
+                    }
+                }
+            }
+        }
+    }
+
+    // Averages each `factor` x `factor` block into one pixel, for frames
+    // rendered at a multiple of the output size
+    pub fn downsample(&self, factor: usize) -> Vec<u32> {
+        let factor = factor.max(1);
+        let (width, height) = (self.width / factor, self.height / factor);
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0u32; 3];
+                for sy in y * factor..(y + 1) * factor {
+                    for sx in x * factor..(x + 1) * factor {
+                        let pixel = self.buffer[sy * self.width + sx];
+                        sum[0] += (pixel >> 16) & 0xFF;
+                        sum[1] += (pixel >> 8) & 0xFF;
+                        sum[2] += pixel & 0xFF;
+                    }
+                }
+                let samples = (factor * factor) as u32;
+                let channel = |total: u32| (total + samples / 2) / samples;
+                pixels.push(channel(sum[0]) << 16 | channel(sum[1]) << 8 | channel(sum[2]));
+            }
+        }
+        pixels
+    }
+}
+
+// A row of tiles of a framebuffer, see Framebuffer::tile_rows
+pub struct TileRow<'a> {
+    top: usize,
+    width: usize,
+    size: usize,
+    clip: Viewport,
+    id: u16,
+    depth_test: bool,
+    buffer: &'a mut [u32],
+    zbuffer: &'a mut [f32],
+    ids: Option<&'a mut [u16]>,
+}
+
+impl TileRow<'_> {
+    // The tile `column` squares from the left
+    pub fn tile(&mut self, column: usize) -> Tile<'_> {
+        let (x, height) = (column * self.size, self.buffer.len() / self.width);
+        Tile {
+            area: Viewport { x, y: self.top, width: self.size.min(self.width.saturating_sub(x)), height },
+            top: self.top,
+            width: self.width,
+            clip: self.clip,
+            id: self.id,
+            depth_test: self.depth_test,
+            buffer: self.buffer,
+            zbuffer: self.zbuffer,
+            ids: self.ids.as_deref_mut(),
+        }
+    }
+}
+
+// One square of a framebuffer. Drawing takes framebuffer coordinates and
+// writes like the framebuffer's own point(), with the color
+// passed along, but only inside the square
+pub struct Tile<'a> {
+    area: Viewport,
+    // First line and width of the row's slices
+    top: usize,
+    width: usize,
+    clip: Viewport,
+    id: u16,
+    depth_test: bool,
+    buffer: &'a mut [u32],
+    zbuffer: &'a mut [f32],
+    ids: Option<&'a mut [u16]>,
+}
+
+impl Tile<'_> {
+    // Where the tile is in the framebuffer
+    pub fn area(&self) -> Viewport {
+        self.area
+    }
+
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        (self.clip.contains(x, y) && self.area.contains(x, y)).then(|| (y - self.top) * self.width + x)
+    }
+
+    pub fn point(&mut self, x: usize, y: usize, depth: f32, color: u32) {
+        if let Some(index) = self.index(x, y) {
+            let ids = self.ids.as_deref_mut().filter(|_| self.depth_test).map(|ids| (ids, self.id));
+            write_point(self.buffer, self.zbuffer, ids, index, depth, color, self.depth_test);
+        }
+    }
+}
+
+// Depth-tested write of the pixel at `index`, for the framebuffer and its
+// tiles alike; the depth is only kept with the depth test on
+fn write_point(buffer: &mut [u32], zbuffer: &mut [f32], ids: Option<(&mut [u16], u16)>, index: usize, depth: f32, color: u32, depth_test: bool) {
+    if zbuffer[index] > depth {
+        buffer[index] = color;
+        if depth_test {
+            zbuffer[index] = depth;
+        }
+        if let Some((ids, id)) = ids {
+            ids[index] = id;
+        }
+    }
+}
+
+// Linear mix of two 0xRRGGBB colors, alpha = 1 gives `top`
+pub fn blend(bottom: u32, top: u32, alpha: f32) -> u32 {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let channel = |shift: u32| {
+        let b = ((bottom >> shift) & 0xFF) as f32;
+        let t = ((top >> shift) & 0xFF) as f32;
+        ((b + (t - b) * alpha).round() as u32) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn pixels_are_stored_as_0rgb() {
+        let mut framebuffer = Framebuffer::new(4, 3);
+        let color = Color::new(0x12, 0x34, 0x56).with_alpha(0x80);
+        framebuffer.set_current_color(color.to_pixel());
+        framebuffer.point(2, 1, 0.5);
+
+        let pixel = framebuffer.buffer[4 + 2];
+        assert_eq!(pixel, 0x00123456);
+        assert_eq!(Color::from_hex(pixel), color.with_alpha(255));
+        assert_eq!(framebuffer.zbuffer[4 + 2], 0.5);
+        assert_eq!(framebuffer.buffer.iter().filter(|&&p| p != 0).count(), 1);
+    }
+
+    #[test]
+    fn nearer_pixels_win_whatever_the_order() {
+        // A near and a far body over the same pixel, drawn both ways round,
+        // then the sky, which only fills what's left
+        for near_first in [true, false] {
+            let mut framebuffer = Framebuffer::new(2, 1);
+            let mut draws = [(0x111111, 0.2), (0x222222, 0.8)];
+            if !near_first {
+                draws.reverse();
+            }
+            for (color, depth) in draws {
+                framebuffer.set_current_color(color);
+                framebuffer.point(0, 0, depth);
+            }
+            framebuffer.set_current_color(0x333333);
+            framebuffer.set_depth_test(false);
+            framebuffer.point(0, 0, f32::MAX);
+            framebuffer.point(1, 0, f32::MAX);
+            framebuffer.set_depth_test(true);
+            assert_eq!(framebuffer.buffer, vec![0x111111, 0x333333]);
+            assert_eq!(framebuffer.zbuffer, vec![0.2, f32::INFINITY]);
+
+            framebuffer.clear();
+            assert!(framebuffer.zbuffer.iter().all(|&depth| depth == f32::INFINITY));
+        }
+    }
+
+    #[test]
+    fn drawing_stays_inside_the_clip() {
+        let mut framebuffer = Framebuffer::new(9, 2);
+        let (left, right) = framebuffer.full_viewport().split(1);
+        assert_eq!(left, Viewport { x: 0, y: 0, width: 4, height: 2 });
+        assert_eq!(right, Viewport { x: 5, y: 0, width: 4, height: 2 });
+
+        framebuffer.set_clip(right);
+        framebuffer.fill_rect(-5, 0, 20, 1, 0xFFFFFF);
+        framebuffer.blend_rect(0, 1, 20, 1, 0xFFFFFF, 1.0);
+        framebuffer.point(2, 1, 0.0);
+        let lit: Vec<usize> = (0..18).filter(|&i| framebuffer.buffer[i] != 0).collect();
+        assert_eq!(lit, vec![5, 6, 7, 8, 14, 15, 16, 17]);
+    }
+
+    #[test]
+    fn tiles_split_the_frame_and_draw_like_the_framebuffer() {
+        let mut framebuffer = Framebuffer::new(5, 3);
+        framebuffer.track_ids(true);
+        framebuffer.set_current_id(7);
+        framebuffer.set_clip(Viewport { x: 1, y: 0, width: 4, height: 3 });
+        let mut rows = framebuffer.tile_rows(2);
+        let areas: Vec<(usize, usize, usize, usize)> = rows
+            .iter_mut()
+            .flat_map(|row| (0..3).map(|column| row.tile(column).area()).collect::<Vec<_>>())
+            .map(|a| (a.x, a.y, a.width, a.height))
+            .collect();
+        assert_eq!(areas, vec![(0, 0, 2, 2), (2, 0, 2, 2), (4, 0, 1, 2), (0, 2, 2, 1), (2, 2, 2, 1), (4, 2, 1, 1)]);
+        // Outside the clip, or another tile's pixels, is left alone
+        let mut first = rows[0].tile(0);
+        first.point(0, 1, 0.5, 0x111111);
+        first.point(2, 1, 0.5, 0x111111);
+        first.point(1, 1, 0.5, 0x222222);
+        first.point(1, 1, 0.8, 0x333333);
+        drop(rows);
+        framebuffer.set_depth_test(false);
+        let mut rows = framebuffer.tile_rows(2);
+        rows[0].tile(0).point(1, 1, f32::MAX, 0x444444);
+        rows[1].tile(2).point(4, 2, f32::MAX, 0x444444);
+        drop(rows);
+
+        let lit: Vec<(usize, u32)> = (0..15).filter(|&i| framebuffer.buffer[i] != 0).map(|i| (i, framebuffer.buffer[i])).collect();
+        assert_eq!(lit, vec![(6, 0x222222), (14, 0x444444)]);
+        assert_eq!(framebuffer.zbuffer[6], 0.5);
+        assert_eq!(framebuffer.ids.as_ref().unwrap()[6], 7);
+        // The depth test was off for the far pixel
+        assert_eq!((framebuffer.zbuffer[14], framebuffer.ids.as_ref().unwrap()[14]), (f32::INFINITY, 0));
+    }
+
+    #[test]
+    fn blitting_stretches_onto_the_target() {
+        let mut small = Framebuffer::new(2, 1);
+        small.buffer = vec![0x111111, 0x222222];
+        let mut framebuffer = Framebuffer::new(6, 3);
+        framebuffer.blit_scaled(&small, Viewport { x: 1, y: 1, width: 4, height: 2 });
+
+        assert_eq!(&framebuffer.buffer[6..12], &[0, 0x111111, 0x111111, 0x222222, 0x222222, 0]);
+        assert_eq!(framebuffer.buffer[12..18], framebuffer.buffer[6..12]);
+        assert!(framebuffer.buffer[..6].iter().all(|&p| p == 0));
+    }
+
+    #[test]
+    fn downsampling_averages_blocks() {
+        let mut framebuffer = Framebuffer::new(4, 2);
+        framebuffer.fill_rect(0, 0, 1, 1, 0xFF0000);
+        framebuffer.fill_rect(1, 0, 1, 2, 0x0000FF);
+        framebuffer.fill_rect(2, 0, 2, 2, 0x336699);
+
+        assert_eq!(framebuffer.downsample(2), vec![0x400080, 0x336699]);
+        assert_eq!(framebuffer.downsample(1), framebuffer.buffer);
+    }
 }
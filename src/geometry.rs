@@ -0,0 +1,31 @@
+use nalgebra_glm::Vec3;
+
+// Shorter vectors have no usable direction; normalizing them (coincident
+// vertices, a camera sitting on its target) gives NaN
+const MIN_LENGTH: f32 = 1e-6;
+
+// Unit vector along `v`, or `fallback` when `v` is too short or not finite
+pub fn normalize_or(v: Vec3, fallback: Vec3) -> Vec3 {
+    v.try_normalize(MIN_LENGTH)
+        .filter(is_finite)
+        .unwrap_or(fallback)
+}
+
+pub fn is_finite(v: &Vec3) -> bool {
+    v.iter().all(|c| c.is_finite())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_and_broken_vectors_fall_back() {
+        let fallback = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(normalize_or(Vec3::new(0.0, 3.0, 4.0), fallback), Vec3::new(0.0, 0.6, 0.8));
+        assert_eq!(normalize_or(Vec3::zeros(), fallback), fallback);
+        assert_eq!(normalize_or(Vec3::new(1e-9, 0.0, 0.0), fallback), fallback);
+        assert_eq!(normalize_or(Vec3::new(f32::NAN, 1.0, 0.0), fallback), fallback);
+        assert_eq!(normalize_or(Vec3::new(f32::INFINITY, 1.0, 0.0), fallback), fallback);
+    }
+}
@@ -0,0 +1,421 @@
+// Golden-image tests for the renderer. Tiny scenes are rendered headlessly
+// and compared with the reference PNGs in tests/golden. After an intended
+// change to the look of a scene, regenerate them with
+//     GOLDEN_BLESS=1 cargo test golden
+// and check the new images in. A failing comparison writes the rendered frame
+// and a diff image to target/golden.
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use nalgebra_glm::{Vec2, Vec3, Mat4};
+use crate::{render, create_projection_matrix, create_viewport_matrix, FrameUniforms, Lighting, ShaderType, Uniforms};
+use crate::low_spec::Detail;
+use crate::body::CelestialBody;
+use crate::camera::look_at_matrix;
+use crate::capture::write_png;
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::light::{Light, LightKind, ShadingModel};
+use crate::obj::{Obj, smooth_normals};
+use crate::overlay::{LineDepth, ScreenProjection, draw_orbit};
+use crate::scene_graph::Transform;
+use crate::shader_params::{ShaderParams, RockyParams};
+use crate::skybox::Skybox;
+use crate::triangle::CullMode;
+use crate::vertex::Vertex;
+
+const WIDTH: usize = 200;
+const HEIGHT: usize = 150;
+// Shader animations are frozen at this time
+const TIME: f32 = 1.0;
+// Largest difference in any channel that still counts as the same pixel
+const CHANNEL_TOLERANCE: u8 = 8;
+// Pixels allowed past that tolerance, for rounding along triangle edges
+const MAX_BAD_PIXELS: usize = 30;
+const DIFF_COLOR: u32 = 0xFF00FF;
+
+fn path_in(directory: &str, name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(directory).join(name)
+}
+
+// Small frame looking from `eye` at the origin, lit by one white light
+struct Shot {
+    framebuffer: Framebuffer,
+    projection: ScreenProjection,
+    lights: Vec<Light>,
+}
+
+impl Shot {
+    fn new(eye: Vec3) -> Self {
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        framebuffer.set_background_color(0x000000);
+        framebuffer.clear();
+        Self {
+            projection: projection_into(framebuffer.full_viewport(), eye),
+            framebuffer,
+            lights: vec![Light { position: Vec3::new(20.0, 10.0, 20.0), color: Vec3::new(1.0, 1.0, 1.0), intensity: 1.0, radius: 0.0, kind: LightKind::default() }],
+        }
+    }
+
+    fn draw(&mut self, vertices: &[Vertex], model_matrix: Mat4, shader_type: ShaderType) {
+        let lighting = Lighting { lights: &self.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let frame = FrameUniforms::new(&self.projection, lighting, TIME, self.framebuffer.full_viewport());
+        render(&mut self.framebuffer, &Uniforms::new(&frame, model_matrix, shader_type), vertices);
+    }
+}
+
+fn projection_into(viewport: Viewport, eye: Vec3) -> ScreenProjection {
+    ScreenProjection {
+        view_matrix: look_at_matrix(eye, Vec3::zeros(), Vec3::y()),
+        projection_matrix: create_projection_matrix(std::f32::consts::PI / 3.0, viewport.aspect_ratio(), 0.1, 200.0),
+        viewport_matrix: create_viewport_matrix(viewport),
+        snap_to_pixels: false,
+        affine: false,
+    }
+}
+
+fn sphere() -> Vec<Vertex> {
+    Obj::load(path_in("assets/models", "Planet.obj").to_str().unwrap())
+        .expect("Failed to load Planet.obj")
+        .get_vertex_array()
+}
+
+fn read_png(path: &Path) -> Result<Vec<u32>, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut reader = png::Decoder::new(BufReader::new(file)).read_info().map_err(|e| e.to_string())?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+    if info.color_type != png::ColorType::Rgb || info.width as usize != WIDTH || info.height as usize != HEIGHT {
+        return Err(format!("{} is not a {}x{} RGB image", path.display(), WIDTH, HEIGHT));
+    }
+    Ok(data[..info.buffer_size()]
+        .chunks(3)
+        .map(|p| (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32)
+        .collect())
+}
+
+fn channels_close(a: u32, b: u32) -> bool {
+    [16, 8, 0].iter().all(|&shift| ((a >> shift) as u8).abs_diff((b >> shift) as u8) <= CHANNEL_TOLERANCE)
+}
+
+// Compares a frame with its reference, or replaces the reference when blessing
+fn check(name: &str, frame: &Framebuffer) -> Result<(), String> {
+    let golden = path_in("tests/golden", &format!("{}.png", name));
+    if std::env::var_os("GOLDEN_BLESS").is_some() {
+        fs::create_dir_all(golden.parent().unwrap()).map_err(|e| e.to_string())?;
+        return write_png(&golden, &frame.buffer, WIDTH, HEIGHT);
+    }
+    let expected = read_png(&golden)?;
+    // Mismatches in magenta over a dimmed copy of the reference
+    let diff: Vec<u32> = expected.iter().zip(&frame.buffer)
+        .map(|(&want, &got)| if channels_close(want, got) { (want >> 2) & 0x3F3F3F } else { DIFF_COLOR })
+        .collect();
+    let bad = diff.iter().filter(|&&p| p == DIFF_COLOR).count();
+    if bad <= MAX_BAD_PIXELS {
+        return Ok(());
+    }
+    let out = path_in("target/golden", "");
+    fs::create_dir_all(&out).map_err(|e| e.to_string())?;
+    write_png(&out.join(format!("{}.actual.png", name)), &frame.buffer, WIDTH, HEIGHT)?;
+    write_png(&out.join(format!("{}.diff.png", name)), &diff, WIDTH, HEIGHT)?;
+    Err(format!("{}: {} pixels differ, see {}", name, bad, out.display()))
+}
+
+#[test]
+fn golden_lit_triangle() {
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+    let vertices = [
+        Vertex::new(Vec3::new(-1.5, -1.0, 0.0), normal, Vec2::new(0.0, 0.0)),
+        Vertex::new(Vec3::new(1.5, -1.0, 0.0), normal, Vec2::new(1.0, 0.0)),
+        Vertex::new(Vec3::new(0.0, 1.5, 0.0), normal, Vec2::new(0.5, 1.0)),
+    ];
+    let mut shot = Shot::new(Vec3::new(0.0, 0.0, 4.0));
+    shot.draw(&vertices, Mat4::identity(), ShaderType::RockyPlanet);
+    check("lit_triangle", &shot.framebuffer).unwrap();
+}
+
+#[test]
+fn golden_sphere_shaders() {
+    let vertices = sphere();
+    let shaders = [
+        ("star", ShaderType::Star),
+        ("rocky_planet", ShaderType::RockyPlanet),
+        ("gas_giant", ShaderType::GasGiant),
+        ("station", ShaderType::Station),
+        ("portal", ShaderType::Portal),
+        ("ship", ShaderType::Ship { hull: 0x8899AA, emissive: 0x33CCFF, damage: 0.0 }),
+    ];
+    let failures: Vec<String> = shaders.iter()
+        .filter_map(|&(name, shader_type)| {
+            let mut shot = Shot::new(Vec3::new(0.0, 0.5, 3.0));
+            shot.draw(&vertices, Mat4::identity(), shader_type);
+            check(&format!("sphere_{}", name), &shot.framebuffer).err()
+        })
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn golden_orbit_ring_crossing_a_planet() {
+    let vertices = sphere();
+    let mut moon = CelestialBody::new_planet(
+        "Moon".to_string(), Vec::new(), Vec3::zeros(), 16.0, 1.0, 2.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+    );
+    moon.orbital_angle = 1.0;
+    let mut shot = Shot::new(Vec3::new(0.0, 12.0, 40.0));
+    let model_matrix = Transform::from_euler(Vec3::zeros(), Vec3::zeros(), 10.0).matrix();
+    shot.draw(&vertices, model_matrix, ShaderType::RockyPlanet);
+    let fade = shot.projection.fade_behind(30.0);
+    draw_orbit(&mut shot.framebuffer, &shot.projection, &moon, Vec3::zeros(), 0x446688, 0xFFCC00, fade);
+    check("orbit_ring", &shot.framebuffer).unwrap();
+}
+
+// Not compared with a reference: the same ring cut off by the planet and
+// faded behind it. A vanishing fade is the hard cut again; a long one only
+// adds color where the planet covers the ring
+#[test]
+fn orbit_ring_fades_behind_a_planet() {
+    let vertices = sphere();
+    let mut moon = CelestialBody::new_planet(
+        "Moon".to_string(), Vec::new(), Vec3::zeros(), 16.0, 1.0, 2.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+    );
+    moon.orbital_angle = 1.0;
+    // One pixel wide, so the arrow never lands on the ring's own pixels
+    moon.orbit_style.width = 1;
+    let draw = |depth: Option<f32>| {
+        let mut shot = Shot::new(Vec3::new(0.0, 12.0, 40.0));
+        shot.draw(&vertices, Transform::from_euler(Vec3::zeros(), Vec3::zeros(), 10.0).matrix(), ShaderType::RockyPlanet);
+        let depth = depth.map_or(LineDepth::Test, |distance| shot.projection.fade_behind(distance));
+        draw_orbit(&mut shot.framebuffer, &shot.projection, &moon, Vec3::zeros(), 0x446688, 0xFFCC00, depth);
+        shot.framebuffer
+    };
+    let (cut, sharp, faded) = (draw(None), draw(Some(1e-3)), draw(Some(30.0)));
+    assert_eq!(cut.buffer, sharp.buffer);
+
+    let changed: Vec<usize> = (0..WIDTH * HEIGHT).filter(|&index| faded.buffer[index] != cut.buffer[index]).collect();
+    assert!(changed.len() > 10, "only {} pixels faded in", changed.len());
+    assert!(changed.iter().all(|&index| cut.zbuffer[index].is_finite()));
+}
+
+#[test]
+fn golden_skybox() {
+    let vertices = Skybox::create_sphere_vertices(50.0, 24);
+    let mut shot = Shot::new(Vec3::new(0.0, 0.0, 0.1));
+    let model_matrix = Transform::from_euler(Vec3::zeros(), Vec3::zeros(), 1.0).matrix();
+    shot.draw(&vertices, model_matrix, ShaderType::Skybox);
+    check("skybox", &shot.framebuffer).unwrap();
+}
+
+// Not compared with a reference: a planet in front of a bright sky star.
+// The sky sphere is nearer than the planet, the way a far body sits outside
+// the skybox, so only the sky's own depth rule keeps it behind. Whether the
+// sky is drawn before or after, every pixel the planet covers, the limb
+// included, must show the planet alone
+#[test]
+fn sky_stars_stay_behind_a_planet() {
+    let eye = Vec3::new(0.0, 0.0, 40.0);
+    let sky = Skybox::create_sphere_vertices(20.0, 240);
+    let planet = sphere();
+    let sky_matrix = Transform::from_euler(eye, Vec3::zeros(), 1.0).matrix();
+    let planet_matrix = Transform::from_euler(Vec3::zeros(), Vec3::zeros(), 10.0).matrix();
+    let draw = |layers: &[(&[Vertex], Mat4, ShaderType)]| {
+        let mut shot = Shot::new(eye);
+        for &(vertices, model_matrix, shader_type) in layers {
+            shot.draw(vertices, model_matrix, shader_type);
+        }
+        shot.framebuffer
+    };
+    let sky_layer = (sky.as_slice(), sky_matrix, ShaderType::Skybox);
+    let planet_layer = (planet.as_slice(), planet_matrix, ShaderType::RockyPlanet);
+    let sky_only = draw(&[sky_layer]);
+    let planet_only = draw(&[planet_layer]);
+
+    let brightness = |pixel: u32| (pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF);
+    let covered: Vec<usize> = (0..WIDTH * HEIGHT).filter(|&index| planet_only.zbuffer[index].is_finite()).collect();
+    let star = *covered.iter().max_by_key(|&&index| brightness(sky_only.buffer[index])).unwrap();
+    let mut sky_brightness: Vec<u32> = sky_only.buffer.iter().map(|&pixel| brightness(pixel)).collect();
+    sky_brightness.sort();
+    assert!(brightness(sky_only.buffer[star]) > 3 * sky_brightness[sky_brightness.len() / 2] / 2, "no star behind the planet");
+
+    for layers in [[sky_layer, planet_layer], [planet_layer, sky_layer]] {
+        let frame = draw(&layers);
+        assert_eq!(frame.buffer[star], planet_only.buffer[star]);
+        assert!(covered.iter().all(|&index| frame.buffer[index] == planet_only.buffer[index]));
+        // The sky still fills everything else
+        assert!((0..WIDTH * HEIGHT).filter(|index| !covered.contains(index)).all(|index| frame.buffer[index] == sky_only.buffer[index]));
+    }
+}
+
+// Not compared with a reference: the same rocky sphere with and without
+// ambient occlusion. Only some of it darkens, nothing gets brighter, and
+// on the whole it stays subtle
+#[test]
+fn ambient_occlusion_darkens_the_low_ground() {
+    let vertices = sphere();
+    let draw = |ambient_occlusion: f32| {
+        let mut shot = Shot::new(Vec3::new(0.0, 0.5, 3.0));
+        let lighting = Lighting { lights: &shot.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let frame = FrameUniforms::new(&shot.projection, lighting, TIME, shot.framebuffer.full_viewport());
+        let params = ShaderParams::Rocky(RockyParams { ambient_occlusion, ..RockyParams::default() });
+        render(&mut shot.framebuffer, &Uniforms::new(&frame, Mat4::identity(), ShaderType::RockyPlanet).with_params(params), &vertices);
+        shot.framebuffer.buffer
+    };
+    let (open, occluded) = (draw(0.0), draw(1.0));
+    let brightness = |pixel: u32| (pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF);
+    let lit: Vec<(u32, u32)> = open.iter().zip(&occluded)
+        .map(|(&a, &b)| (brightness(a), brightness(b)))
+        .filter(|&(a, _)| a > 0)
+        .collect();
+    assert!(lit.iter().all(|&(a, b)| b <= a));
+    let darkened = lit.iter().filter(|&&(a, b)| b + 6 < a).count();
+    assert!(darkened > lit.len() / 5 && darkened < lit.len() * 4 / 5, "{} of {} darkened", darkened, lit.len());
+    let total = |pick: fn(&(u32, u32)) -> u32| lit.iter().map(pick).sum::<u32>() as f32;
+    let ratio = total(|&(_, b)| b) / total(|&(a, _)| a);
+    assert!((0.7..0.95).contains(&ratio), "kept {}", ratio);
+}
+
+// Not compared with a reference: culling the side of a closed mesh that
+// faces away leaves the frame as it was, the planet seen from outside and
+// the sky sphere from inside. Culling the other side does not
+#[test]
+fn culling_the_hidden_side_changes_nothing() {
+    let (planet, sky) = (sphere(), Skybox::create_sphere_vertices(50.0, 24));
+    let draw = |vertices: &[Vertex], eye: Vec3, shader_type: ShaderType, cull_mode: CullMode| {
+        let mut shot = Shot::new(eye);
+        let lighting = Lighting { lights: &shot.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let frame = FrameUniforms::new(&shot.projection, lighting, TIME, shot.framebuffer.full_viewport());
+        render(&mut shot.framebuffer, &Uniforms::new(&frame, Mat4::identity(), shader_type).with_cull_mode(cull_mode), vertices);
+        shot.framebuffer
+    };
+    for (vertices, eye, shader_type, hidden, seen) in [
+        (&planet, Vec3::new(0.0, 0.5, 3.0), ShaderType::RockyPlanet, CullMode::Back, CullMode::Front),
+        (&sky, Vec3::new(0.0, 0.0, 0.1), ShaderType::Skybox, CullMode::Front, CullMode::Back),
+    ] {
+        let all = draw(vertices, eye, shader_type, CullMode::None);
+        assert!(all.buffer.iter().any(|&pixel| pixel != 0x000000));
+        let culled = draw(vertices, eye, shader_type, hidden);
+        assert!(culled.buffer == all.buffer && culled.zbuffer == all.zbuffer);
+        assert!(draw(vertices, eye, shader_type, seen).buffer != all.buffer);
+    }
+}
+
+// Not compared with a reference: the same lit sphere shaded flat, Gouraud
+// and Phong. It's painted one plain color, so what changes between
+// neighboring pixels is the light, which only jumps across triangle edges
+// when it's flat
+#[test]
+fn smooth_shading_hides_the_facets() {
+    // Smoothed as the scene does when it loads the model
+    let mut vertices = sphere();
+    smooth_normals(&mut vertices);
+    let draw = |shading: ShadingModel| {
+        let mut shot = Shot::new(Vec3::new(0.0, 0.5, 3.0));
+        let lighting = Lighting { lights: &shot.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading };
+        let frame = FrameUniforms::new(&shot.projection, lighting, TIME, shot.framebuffer.full_viewport());
+        render(&mut shot.framebuffer, &Uniforms::new(&frame, Mat4::identity(), ShaderType::Ship { hull: 0xC0C0C0, emissive: 0xC0C0C0, damage: 0.0 }).with_shading(shading), &vertices);
+        shot.framebuffer
+    };
+    let brightness = |pixel: u32| (pixel >> 16 & 0xFF) + (pixel >> 8 & 0xFF) + (pixel & 0xFF);
+    // Squared, so a few steps count for more than a smooth ramp over the
+    // same range; only neighbors both on the sphere, so the limb doesn't
+    let roughness = |frame: &Framebuffer| -> u32 {
+        (0..HEIGHT).flat_map(|y| (1..WIDTH).map(move |x| y * WIDTH + x))
+            .filter(|&index| frame.zbuffer[index].is_finite() && frame.zbuffer[index - 1].is_finite())
+            .map(|index| brightness(frame.buffer[index]).abs_diff(brightness(frame.buffer[index - 1])).pow(2))
+            .sum()
+    };
+    let flat = draw(ShadingModel::Flat);
+    for smooth in [ShadingModel::Gouraud, ShadingModel::Phong] {
+        let frame = draw(smooth);
+        assert_eq!(frame.zbuffer, flat.zbuffer);
+        assert!(roughness(&frame) * 3 < roughness(&flat), "{:?}", smooth);
+    }
+}
+
+// Not compared with a reference: sky, two overlapping planets and a
+// viewport that cuts through tiles, drawn on one thread as a single tile
+// and on four tile by tile. Colors, depths and IDs all come out the same
+#[test]
+fn threads_draw_the_same_frame() {
+    let (planet, sky) = (sphere(), Skybox::create_sphere_vertices(50.0, 24));
+    let draw = |threads: usize| {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        pool.install(|| {
+            let mut shot = Shot::new(Vec3::new(0.0, 0.5, 3.0));
+            shot.framebuffer.track_ids(true);
+            let viewport = Viewport { x: 7, y: 9, width: 170, height: 123 };
+            let projection = projection_into(viewport, Vec3::new(0.0, 0.5, 3.0));
+            let lighting = Lighting { lights: &shot.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Phong };
+            let frame = FrameUniforms::new(&projection, lighting, TIME, viewport);
+            let near = Transform::from_euler(Vec3::new(0.6, 0.2, 0.8), Vec3::zeros(), 0.5).matrix();
+            for (id, model_matrix) in [(1, Mat4::identity()), (2, near)] {
+                shot.framebuffer.set_current_id(id);
+                render(&mut shot.framebuffer, &Uniforms::new(&frame, model_matrix, ShaderType::RockyPlanet).with_shading(ShadingModel::Phong), &planet);
+            }
+            render(&mut shot.framebuffer, &Uniforms::new(&frame, Mat4::identity(), ShaderType::Skybox).with_cull_mode(CullMode::Front), &sky);
+            shot.framebuffer
+        })
+    };
+    let (serial, parallel) = (draw(1), draw(4));
+    assert!(serial.ids.as_ref().unwrap().contains(&2));
+    assert!(serial.buffer == parallel.buffer);
+    assert!(serial.zbuffer.iter().zip(&parallel.zbuffer).all(|(a, b)| a.to_bits() == b.to_bits()));
+    assert!(serial.ids == parallel.ids);
+}
+
+// Not compared with a reference: two views of a sphere too close to fit
+// either, each drawn through its own viewport. The framebuffer's clip stays
+// at the whole frame, so only the viewports keep the views apart
+#[test]
+fn rendering_stays_inside_the_viewport() {
+    let vertices = sphere();
+    let mut shot = Shot::new(Vec3::zeros());
+    let views = [
+        Viewport { x: 10, y: 20, width: 70, height: 50 },
+        Viewport { x: 100, y: 60, width: 80, height: 70 },
+    ];
+    for viewport in views {
+        let projection = projection_into(viewport, Vec3::new(0.0, 0.2, 1.1));
+        let lighting = Lighting { lights: &shot.lights, ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let frame = FrameUniforms::new(&projection, lighting, TIME, viewport);
+        render(&mut shot.framebuffer, &Uniforms::new(&frame, Mat4::identity(), ShaderType::RockyPlanet), &vertices);
+    }
+
+    let framebuffer = &shot.framebuffer;
+    let drawn = |x: usize, y: usize| framebuffer.zbuffer[y * WIDTH + x].is_finite();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            if !views.iter().any(|view| view.contains(x, y)) {
+                assert!(!drawn(x, y) && framebuffer.buffer[y * WIDTH + x] == 0x000000, "pixel ({}, {}) drawn", x, y);
+            }
+        }
+    }
+    // The sphere overflows both, so every edge of each is reached
+    for view in views {
+        let (right, bottom) = (view.x + view.width - 1, view.y + view.height - 1);
+        assert!([(view.x, view.y), (right, view.y), (view.x, bottom), (right, bottom)].iter().all(|&(x, y)| drawn(x, y)));
+    }
+}
+
+// Not compared with a reference: none of these may draw anything or panic
+#[test]
+fn degenerate_geometry_draws_nothing() {
+    let normal = Vec3::new(0.0, 0.0, 1.0);
+    let corners = [
+        // Coincident corners
+        [Vec3::new(0.0, 0.0, -5.0); 3],
+        // Collinear corners
+        [Vec3::new(-1.0, -1.0, -5.0), Vec3::new(0.0, 0.0, -5.0), Vec3::new(1.0, 1.0, -5.0)],
+        // A corner that is already NaN
+        [Vec3::new(f32::NAN, 0.0, -5.0), Vec3::new(1.0, 0.0, -5.0), Vec3::new(0.0, 1.0, -5.0)],
+        // Entirely off the left edge of the screen
+        [Vec3::new(-12.0, 0.0, -5.0), Vec3::new(-10.0, 0.0, -5.0), Vec3::new(-11.0, 1.0, -5.0)],
+    ];
+    let vertices: Vec<Vertex> = corners.iter().flatten().map(|&p| Vertex::new(p, normal, Vec2::new(0.0, 0.0))).collect();
+
+    // Eye on the target, the view falls back to looking down -Z
+    let mut shot = Shot::new(Vec3::zeros());
+    assert!(shot.projection.view_matrix.iter().all(|m| m.is_finite()));
+    shot.draw(&vertices, Mat4::identity(), ShaderType::RockyPlanet);
+    assert!(shot.framebuffer.buffer.iter().all(|&p| p == 0x000000));
+}
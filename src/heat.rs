@@ -0,0 +1,110 @@
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use crate::color::Color;
+use crate::light::Light;
+
+// Equilibrium temperature of a black body one reference distance from a
+// luminosity 1 star, which is Earth's at 1 AU from the Sun
+const REFERENCE_KELVIN: f32 = 278.6;
+// Cold to hot, spread evenly over the scale
+const GRADIENT: [u32; 4] = [0x2040FF, 0x20C040, 0xFFE030, 0xFF2020];
+
+// [heat] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct HeatSettings {
+    // Start in the temperature view (E toggles it)
+    #[serde(default)]
+    pub enabled: bool,
+    // Distance at which a star of luminosity 1 warms a body as much as the
+    // Sun warms Earth
+    #[serde(default = "default_reference_distance")]
+    pub reference_distance: f32,
+    // Ends of the color scale in kelvin, anything past them gets the end color
+    #[serde(default = "default_min_kelvin")]
+    pub min_kelvin: f32,
+    #[serde(default = "default_max_kelvin")]
+    pub max_kelvin: f32,
+}
+
+impl Default for HeatSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reference_distance: default_reference_distance(),
+            min_kelvin: default_min_kelvin(),
+            max_kelvin: default_max_kelvin(),
+        }
+    }
+}
+
+fn default_reference_distance() -> f32 {
+    250.0
+}
+
+fn default_min_kelvin() -> f32 {
+    50.0
+}
+
+fn default_max_kelvin() -> f32 {
+    450.0
+}
+
+impl HeatSettings {
+    // Temperature at `position` of a body reflecting `albedo` of the light
+    // it gets. Flux from every star adds up and T^4 goes with the absorbed
+    // flux, so T = 278.6 K * ((1 - A) * sum(L * (ref / d)^2))^(1/4)
+    pub fn temperature(&self, lights: &[Light], position: Vec3, albedo: f32) -> f32 {
+        let flux: f32 = lights.iter()
+            .map(|light| {
+                let distance = (light.position - position).magnitude().max(f32::EPSILON);
+                light.intensity * (self.reference_distance / distance).powi(2)
+            })
+            .sum();
+        REFERENCE_KELVIN * ((1.0 - albedo.clamp(0.0, 1.0)) * flux).powf(0.25)
+    }
+
+    // Where `kelvin` falls on the scale, 0 at min_kelvin and 1 at max_kelvin
+    pub fn fraction(&self, kelvin: f32) -> f32 {
+        let span = (self.max_kelvin - self.min_kelvin).max(f32::EPSILON);
+        ((kelvin - self.min_kelvin) / span).clamp(0.0, 1.0)
+    }
+
+    pub fn color(&self, kelvin: f32) -> Color {
+        gradient(self.fraction(kelvin))
+    }
+}
+
+// Blue, green, yellow, red as `t` goes from 0 to 1
+pub fn gradient(t: f32) -> Color {
+    let scaled = t.clamp(0.0, 1.0) * (GRADIENT.len() - 1) as f32;
+    let index = (scaled as usize).min(GRADIENT.len() - 2);
+    Color::lerp(Color::from_hex(GRADIENT[index]), Color::from_hex(GRADIENT[index + 1]), scaled - index as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::LightKind;
+
+    #[test]
+    fn earth_comes_out_at_its_equilibrium_temperature() {
+        let heat = HeatSettings { reference_distance: 100.0, ..Default::default() };
+        let sun = Light { position: Vec3::zeros(), color: Vec3::new(1.0, 1.0, 1.0), intensity: 1.0, radius: 0.0, kind: LightKind::default() };
+        let earth = Vec3::new(100.0, 0.0, 0.0);
+        assert!((heat.temperature(&[sun], earth, 0.0) - 278.6).abs() < 0.01);
+        // Earth's albedo of 0.3 gives the textbook 255 K
+        assert!((heat.temperature(&[sun], earth, 0.3) - 254.9).abs() < 0.1);
+        // Four times as far is half as hot
+        let far = heat.temperature(&[sun], earth * 4.0, 0.3);
+        assert!((far - 254.9 / 2.0).abs() < 0.1, "far {}", far);
+        // A second sun doubles the flux
+        let twin = Light { position: Vec3::new(200.0, 0.0, 0.0), ..sun };
+        let both = heat.temperature(&[sun, twin], earth, 0.3);
+        assert!((both - 254.9 * 2f32.powf(0.25)).abs() < 0.1, "both {}", both);
+        assert!(heat.temperature(&[], earth, 0.3) == 0.0);
+
+        assert_eq!(heat.color(0.0).to_pixel(), GRADIENT[0]);
+        assert_eq!(heat.color(1000.0).to_pixel(), GRADIENT[3]);
+        assert_eq!(gradient(2.0 / 3.0).to_pixel(), GRADIENT[2]);
+    }
+}
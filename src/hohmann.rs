@@ -0,0 +1,156 @@
+use std::f32::consts::PI;
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::overlay::{ScreenProjection, LineDepth, draw_line_strip, draw_marker};
+use crate::spaceship::wrap_angle;
+use crate::units::Units;
+
+// The launch window counts as open while the phase angle is within this
+// (about 3 degrees) of the one the transfer needs
+const WINDOW_TOLERANCE: f32 = 0.05;
+const PATH_SEGMENTS: usize = 48;
+
+// Hohmann transfer between two circular, coplanar orbits around the same
+// body: half an ellipse with the central body at a focus, touching the
+// departure orbit at one end and the arrival orbit at the other
+#[derive(Clone, Copy, Debug)]
+pub struct Transfer {
+    pub from_radius: f32,
+    pub to_radius: f32,
+    pub semi_major_axis: f32,
+    // Departure to arrival, half the ellipse's period
+    pub duration: f32,
+    // How far ahead of the departure body (radians, along the direction of
+    // travel) the target has to be at departure to be there on arrival
+    pub phase_angle: f32,
+}
+
+impl Transfer {
+    // `mu` is the central body's G * M
+    pub fn new(from_radius: f32, to_radius: f32, mu: f32) -> Option<Self> {
+        if from_radius <= 0.0 || to_radius <= 0.0 || mu <= 0.0 || from_radius == to_radius {
+            return None;
+        }
+        let semi_major_axis = (from_radius + to_radius) / 2.0;
+        let duration = PI * (semi_major_axis.powi(3) / mu).sqrt();
+        let target_rate = (mu / to_radius.powi(3)).sqrt();
+        let phase_angle = wrap_angle(PI - target_rate * duration);
+        Some(Transfer { from_radius, to_radius, semi_major_axis, duration, phase_angle })
+    }
+
+    // Distance from the central body `angle` radians past the departure
+    // point: from_radius at 0, to_radius at PI. The eccentricity is signed so
+    // the same formula covers transfers inward, which depart at apoapsis
+    pub fn radius_at(&self, angle: f32) -> f32 {
+        let e = (self.to_radius - self.from_radius) / (self.to_radius + self.from_radius);
+        self.semi_major_axis * (1.0 - e * e) / (1.0 + e * angle.cos())
+    }
+}
+
+// G * M implied by a circular orbit's radius and angular speed (n^2 r^3),
+// so the transfer matches how the scene actually moves even when speeds
+// were set by hand instead of derived from masses
+pub fn orbit_mu(body: &CelestialBody) -> f32 {
+    body.orbital_speed.powi(2) * body.orbital_radius.powi(3)
+}
+
+// Angle from `a` to `b` around `center` in the orbit plane (XZ, as
+// orbit_offset lays it out), positive along `direction` of travel
+pub fn phase_between(center: Vec3, a: Vec3, b: Vec3, direction: f32) -> f32 {
+    let angle = |point: Vec3| (point.z - center.z).atan2(point.x - center.x);
+    wrap_angle((angle(b) - angle(a)) * direction)
+}
+
+// A transfer from one body to another as it stands this frame
+pub struct TransferPlan {
+    pub transfer: Transfer,
+    // Current phase angle, to compare with transfer.phase_angle
+    pub phase: f32,
+    path: Vec<Vec3>,
+}
+
+impl TransferPlan {
+    // None unless both bodies orbit the same center in the same direction
+    // at different radii. Eccentricity and inclination are ignored, the
+    // transfer uses the circular orbits orbital_radius describes
+    pub fn between(bodies: &[CelestialBody], from: usize, to: usize) -> Option<Self> {
+        let (a, b) = (&bodies[from], &bodies[to]);
+        if from == to || a.parent_index != b.parent_index || a.orbit_direction() != b.orbit_direction() {
+            return None;
+        }
+        let (center, other_center) = (a.orbit_center(bodies)?, b.orbit_center(bodies)?);
+        if (center - other_center).magnitude() > f32::EPSILON * center.magnitude().max(1.0) {
+            return None;
+        }
+        let transfer = Transfer::new(a.orbital_radius, b.orbital_radius, orbit_mu(a))?;
+        let direction = a.orbit_direction();
+
+        // Half ellipse from where the departure body is now, swept the way
+        // it travels; orbit_offset's angle runs from +X toward +Z
+        let offset = a.position - center;
+        let outward = Vec3::new(offset.x, 0.0, offset.z).try_normalize(f32::EPSILON)?;
+        let along = outward.cross(&Vec3::y()) * direction;
+        let path = (0..=PATH_SEGMENTS)
+            .map(|i| {
+                let angle = i as f32 / PATH_SEGMENTS as f32 * PI;
+                center + (outward * angle.cos() + along * angle.sin()) * transfer.radius_at(angle)
+            })
+            .collect();
+        let phase = phase_between(center, a.position, b.position, direction);
+        Some(TransferPlan { transfer, phase, path })
+    }
+
+    // Departing now would meet the target
+    pub fn in_window(&self) -> bool {
+        wrap_angle(self.phase - self.transfer.phase_angle).abs() <= WINDOW_TOLERANCE
+    }
+
+    pub fn departure(&self) -> Vec3 {
+        self.path[0]
+    }
+
+    pub fn arrival(&self) -> Vec3 {
+        self.path[PATH_SEGMENTS]
+    }
+}
+
+// The transfer path with its ends marked, in `window_color` while the
+// launch window is open
+pub fn draw_transfer(framebuffer: &mut Framebuffer, projection: &ScreenProjection, plan: &TransferPlan, units: Units, color: u32, window_color: u32) {
+    let color = if plan.in_window() { window_color } else { color };
+    draw_line_strip(framebuffer, projection, &plan.path, color, |_| 1.0, LineDepth::Test);
+    let lead = plan.transfer.phase_angle.to_degrees();
+    draw_marker(framebuffer, projection, plan.departure(), &format!("Depart, target {:.0} deg ahead", lead), color);
+    draw_marker(framebuffer, projection, plan.arrival(), &format!("Arrive after {}", units.duration(plan.transfer.duration)), color);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earth_to_mars_matches_the_textbook() {
+        // Units of AU and years, where the sun's G * M is 4 PI^2
+        let mu = 4.0 * PI * PI;
+        let transfer = Transfer::new(1.0, 1.524, mu).unwrap();
+        // a = 1.262 AU, half of a^1.5 years = 0.709 years, about 259 days
+        assert!((transfer.semi_major_axis - 1.262).abs() < 1e-4);
+        assert!((transfer.duration - 0.7089).abs() < 1e-3, "took {}", transfer.duration);
+        // Mars covers 135.6 of its 1.881 year orbit meanwhile, so it has to
+        // start 44.4 degrees ahead
+        assert!((transfer.phase_angle.to_degrees() - 44.4).abs() < 0.2, "phase {}", transfer.phase_angle.to_degrees());
+        assert!((transfer.radius_at(0.0) - 1.0).abs() < 1e-5);
+        assert!((transfer.radius_at(PI) - 1.524).abs() < 1e-5);
+
+        // Going inward the target has to trail instead: Venus from Earth is -54 degrees
+        let inward = Transfer::new(1.0, 0.723, mu).unwrap();
+        assert!((inward.phase_angle.to_degrees() + 54.0).abs() < 1.0, "phase {}", inward.phase_angle.to_degrees());
+        assert!((inward.radius_at(PI) - 0.723).abs() < 1e-5);
+        assert!(Transfer::new(1.0, 1.0, mu).is_none());
+
+        let quarter = phase_between(Vec3::zeros(), Vec3::x(), Vec3::z(), 1.0);
+        assert!((quarter - PI / 2.0).abs() < 1e-6);
+        assert!((phase_between(Vec3::zeros(), Vec3::x(), Vec3::z(), -1.0) + PI / 2.0).abs() < 1e-6);
+    }
+}
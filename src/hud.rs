@@ -0,0 +1,555 @@
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::text::{draw_text, text_width, LINE_HEIGHT};
+use crate::body::CelestialBody;
+use crate::scene::SimulationMode;
+use crate::scale::ScaleProfile;
+use crate::units::Units;
+use crate::warp::{WarpState, WarpEnergy};
+use crate::input::{InputBindings, BindingGroup};
+use crate::menu::{PauseMenu, MenuEntry};
+use crate::docking::{Docking, DockedEntry};
+use crate::tuning::ShaderTuner;
+use crate::thumbnails::{Thumbnails, THUMBNAIL_SIZE};
+use crate::capture::Recorder;
+use crate::photo::PhotoMode;
+use crate::objectives::Objectives;
+use crate::hohmann::TransferPlan;
+use crate::alignment::Alignment;
+use crate::surface::Daylight;
+use crate::low_spec::LowSpec;
+use crate::heat::{HeatSettings, gradient};
+use crate::color::{PaletteMode, UiColor};
+use nalgebra_glm::Vec3;
+
+const PANEL_PADDING: i32 = 6;
+const RECORDING_DOT_SIZE: usize = 7;
+const WARP_BAR_WIDTH: usize = 120;
+const WARP_BAR_HEIGHT: usize = 5;
+const LEGEND_WIDTH: usize = 160;
+const LEGEND_HEIGHT: usize = 7;
+// Help overlay: panel opacity, and the window width from which the
+// bindings are split into two columns
+const HELP_ALPHA: f32 = 0.85;
+const HELP_TWO_COLUMN_WIDTH: usize = 800;
+const HELP_KEY_COLUMN: usize = 8;
+// How much the paused scene is darkened behind the menu
+const PAUSE_DIM: f32 = 0.5;
+// Blinks per second of the proximity warning
+const WARNING_BLINK_RATE: f32 = 3.0;
+// Space between the warp target thumbnails
+const THUMBNAIL_GAP: usize = 8;
+
+// Until travel has a ship to go by, travel times assume this cruise speed
+// (units per second)
+const CRUISE_SPEED: f32 = 100.0;
+
+// Width and height in pixels of a panel holding these lines
+fn panel_size(lines: &[String]) -> (usize, usize) {
+    let width = lines.iter().map(|l| text_width(l)).max().unwrap_or(0) + 2 * PANEL_PADDING as usize;
+    let height = lines.len() * LINE_HEIGHT + 2 * PANEL_PADDING as usize - 3;
+    (width, height)
+}
+
+// Draws a boxed list of text lines; the first line is used as the title
+pub fn draw_panel(framebuffer: &mut Framebuffer, palette: PaletteMode, x: i32, y: i32, lines: &[String]) {
+    let (width, height) = panel_size(lines);
+
+    framebuffer.fill_rect(x - 1, y - 1, width + 2, height + 2, palette.color(UiColor::PanelBorder));
+    framebuffer.fill_rect(x, y, width, height, palette.color(UiColor::Panel));
+
+    for (i, line) in lines.iter().enumerate() {
+        let color = palette.color(if i == 0 { UiColor::Title } else { UiColor::Text });
+        draw_text(framebuffer, x + PANEL_PADDING, y + PANEL_PADDING + (i * LINE_HEIGHT) as i32, line, color);
+    }
+}
+
+// What the info panel shows beyond the body's own settings: the temperature
+// with the heat view on, the length of its day and, standing on it, when
+// the star next rises or sets
+pub struct Conditions {
+    pub temperature: Option<f32>,
+    pub day_length: Option<f32>,
+    pub daylight: Option<Daylight>,
+}
+
+// Info panel for the selected body
+pub fn draw_info_panel(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, body: &CelestialBody, parent: Option<&CelestialBody>, show_lagrange: bool, conditions: &Conditions) {
+    let mut lines = vec![body.name.clone()];
+
+    if let Some(parent) = parent {
+        lines.push(format!("Orbits: {}", parent.name));
+    }
+    if body.orbital_radius > 0.0 {
+        if body.eccentricity > 0.0 {
+            lines.push(format!("Semi-major axis: {}", units.distance(body.orbital_radius)));
+            lines.push(format!("Eccentricity: {:.2}", body.eccentricity));
+        } else {
+            lines.push(format!("Orbit radius: {}", units.distance(body.orbital_radius)));
+        }
+        if body.inclination != 0.0 {
+            lines.push(format!("Inclination: {:.1} deg", body.inclination.to_degrees()));
+        }
+        lines.push(format!("Orbit speed: {:.3} rad/s", body.orbital_speed));
+        lines.push(format!("Orbit angle: {:.1} deg", body.orbital_angle.to_degrees().rem_euclid(360.0)));
+    }
+    match body.orbital_period() {
+        // Scenes with real time units get hours, days or years
+        Some(period) if units.seconds_per_hour.is_some() => lines.push(format!("Period: {}", units.duration(period))),
+        Some(period) => {
+            let source = if body.derive_speed { "kepler" } else { "explicit" };
+            lines.push(format!("Period: {:.1} s ({})", period, source));
+        }
+        None => lines.push("Period: -".to_string()),
+    }
+    if body.tidally_locked {
+        lines.push("Spin: tidally locked".to_string());
+    } else {
+        lines.push(format!("Spin: {:.2} rad/s", body.rotation_speed));
+    }
+    if body.spin_rate() != 0.0 {
+        lines.push(format!("Rotation period: {}", units.duration(std::f32::consts::TAU / body.spin_rate().abs())));
+    }
+    if let Some(day) = conditions.day_length {
+        lines.push(format!("Day: {}", units.duration(day)));
+    }
+    match conditions.daylight {
+        Some(Daylight::Rises(wait)) => lines.push(format!("Sunrise in {}", units.duration(wait))),
+        Some(Daylight::Sets(wait)) => lines.push(format!("Sunset in {}", units.duration(wait))),
+        Some(Daylight::AlwaysUp) => lines.push("Sun never sets here".to_string()),
+        Some(Daylight::AlwaysDown) => lines.push("Sun never rises here".to_string()),
+        None => {}
+    }
+    if body.rotation.z != 0.0 {
+        lines.push(format!("Axial tilt: {:.1} deg", body.rotation.z.to_degrees()));
+    }
+    lines.push(format!("Scale: {:.1}", body.scale));
+    if let Some(kelvin) = conditions.temperature {
+        lines.push(format!("Temperature: {:.0} K ({:.0} C)", kelvin, kelvin - 273.15));
+    }
+    if parent.is_some() {
+        let state = if show_lagrange { "on" } else { "off" };
+        lines.push(format!("Lagrange points [L]: {}", state));
+    }
+
+    draw_panel(framebuffer, palette, 10, 10, &lines);
+}
+
+// Measurement between two bodies, top-right corner. `b` is None while the
+// second body is still being picked. Returns the y just below the panel
+pub fn draw_measure_panel(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, a: &CelestialBody, b: Option<&CelestialBody>, camera_position: Vec3, transfer: Option<&TransferPlan>) -> i32 {
+    let mut lines = vec!["Measure".to_string(), format!("A: {}", a.name)];
+    match b {
+        None => lines.push("B: select a body, press M".to_string()),
+        Some(b) => {
+            let distance = (b.position - a.position).magnitude();
+            lines.push(format!("B: {}", b.name));
+            lines.push(format!("Distance: {}", units.distance(distance)));
+            lines.push(format!("Camera to A: {}", units.distance((a.position - camera_position).magnitude())));
+            lines.push(format!("Camera to B: {}", units.distance((b.position - camera_position).magnitude())));
+            lines.push(format!("Travel at {}: {}", units.speed(CRUISE_SPEED), units.duration(distance / CRUISE_SPEED)));
+            if let Some(plan) = transfer {
+                lines.push(format!("Hohmann transfer: {}", units.duration(plan.transfer.duration)));
+                lines.push(format!("Phase needed: {:.1} deg", plan.transfer.phase_angle.to_degrees()));
+                lines.push(format!("Phase now: {:.1} deg", plan.phase.to_degrees()));
+                if plan.in_window() {
+                    lines.push("LAUNCH WINDOW OPEN".to_string());
+                }
+            }
+        }
+    }
+    lines.push("M again or Esc clears".to_string());
+
+    let (width, height) = panel_size(&lines);
+    draw_panel(framebuffer, palette, framebuffer.width as i32 - width as i32 - 10, 10, &lines);
+    10 + height as i32
+}
+
+// Shader tuning panel along the right edge, from `top` down; returns where
+// it ends
+pub fn draw_tuning_panel(framebuffer: &mut Framebuffer, palette: PaletteMode, tuner: &ShaderTuner, body: &CelestialBody, top: i32) -> i32 {
+    let mut lines = tuner.lines(&body.name, &body.shader_params);
+    lines.push("Shift fine, Enter prints".to_string());
+
+    let (width, height) = panel_size(&lines);
+    draw_panel(framebuffer, palette, framebuffer.width as i32 - width as i32 - 10, top, &lines);
+    top + height as i32
+}
+
+// Objectives checklist along the right edge, from `top` down, with the
+// total time once they're all done
+pub fn draw_objectives(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, objectives: &Objectives, top: i32) {
+    let total = objectives.entries().count();
+    let mut lines = vec![format!("Objectives {}/{}", objectives.completed_count(), total)];
+    for (label, done, orbiting) in objectives.entries() {
+        let mut line = format!("[{}] {}", if done { "X" } else { " " }, label);
+        if let Some(seconds) = orbiting.filter(|_| !done) {
+            line += &format!(" ({})", units.duration(seconds));
+        }
+        lines.push(line);
+    }
+    if let Some(elapsed) = objectives.finished_at() {
+        lines.push(format!("All done in {}", units.duration(elapsed)));
+    }
+
+    let (width, _) = panel_size(&lines);
+    draw_panel(framebuffer, palette, framebuffer.width as i32 - width as i32 - 10, top, &lines);
+}
+
+// Flashing collision warning centered near the top, with the gap and how
+// fast it's closing
+pub fn draw_proximity_warning(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, body: &CelestialBody, clearance: f32, closing_speed: f32, time: f32) {
+    let center = framebuffer.width as i32 / 2;
+    let y = 40;
+    if (time * WARNING_BLINK_RATE).fract() < 0.5 {
+        let title = format!("PROXIMITY: {}", body.name);
+        draw_text(framebuffer, center - text_width(&title) as i32 / 2, y, &title, palette.color(UiColor::Warning));
+    }
+    let readout = format!("Gap {}  closing at {}", units.distance(clearance.max(0.0)), units.speed(closing_speed));
+    draw_text(framebuffer, center - text_width(&readout) as i32 / 2, y + LINE_HEIGHT as i32, &readout, palette.color(UiColor::Warning));
+}
+
+// Blinking warning that a star is too close, with the temperature a black
+// body would reach there. Goes under the proximity warning when that's up
+pub fn draw_heat_warning(framebuffer: &mut Framebuffer, palette: PaletteMode, star: &CelestialBody, kelvin: f32, below_proximity: bool, time: f32) {
+    let center = framebuffer.width as i32 / 2;
+    let y = 40 + if below_proximity { 2 * LINE_HEIGHT as i32 } else { 0 };
+    if (time * WARNING_BLINK_RATE).fract() < 0.5 {
+        let title = format!("HEAT: {}", star.name);
+        draw_text(framebuffer, center - text_width(&title) as i32 / 2, y, &title, palette.color(UiColor::Warning));
+    }
+    let readout = format!("Hull temperature {:.0} K", kelvin);
+    draw_text(framebuffer, center - text_width(&readout) as i32 / 2, y + LINE_HEIGHT as i32, &readout, palette.color(UiColor::Warning));
+}
+
+// What the flight strip shows: camera mode, speed (or orbit distance while
+// tracking a body), altitude over the nearest surface and distance to the
+// selected body
+pub struct FlightReadout<'a> {
+    pub mode: &'a str,
+    pub orbit: Option<(&'a CelestialBody, f32)>,
+    pub speed: f32,
+    pub nearest: Option<(&'a CelestialBody, f32)>,
+    pub target: (&'a CelestialBody, f32),
+}
+
+// One-line flight readout just above the status line
+pub fn draw_flight_strip(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, readout: &FlightReadout) {
+    let FlightReadout { mode, orbit, speed, nearest, target } = *readout;
+    let mut line = match orbit {
+        Some((body, distance)) => format!("{}  {}  DIST {}", mode, body.name, units.distance(distance)),
+        None => format!("{}  SPEED {}", mode, units.speed(speed)),
+    };
+    if let Some((body, altitude)) = nearest {
+        line.push_str(&format!("  ALT {} over {}", units.distance(altitude.max(0.0)), body.name));
+    }
+    line.push_str(&format!("  TARGET {} {}", target.0.name, units.distance(target.1)));
+
+    let y = framebuffer.height as i32 - 2 * LINE_HEIGHT as i32 - 10;
+    framebuffer.fill_rect(0, y - 3, framebuffer.width, LINE_HEIGHT + 2, palette.color(UiColor::Panel));
+    draw_text(framebuffer, 10, y, &line, palette.color(UiColor::Text));
+}
+
+// Warp drive status, bottom-right above the flight strip: progress while
+// warping, a charging bar during the cooldown, then a short "ready" flash.
+// With limited energy its level goes above, and the whole widget shakes
+// when a warp is refused
+pub fn draw_warp_status(framebuffer: &mut Framebuffer, palette: PaletteMode, state: WarpState, destination: Option<&str>, energy: &WarpEnergy, time: f32) {
+    let destination = destination.unwrap_or("-");
+    let (text, fill, color) = match state {
+        WarpState::Warping(progress) => (format!("WARPING TO {}", destination), progress, palette.color(UiColor::WarpBar)),
+        WarpState::Charging(charge) => ("WARP CHARGING".to_string(), charge, palette.color(UiColor::Text)),
+        WarpState::Ready { just_charged: true } => {
+            let visible = (time * WARNING_BLINK_RATE * 2.0).fract() < 0.5;
+            (if visible { "WARP READY".to_string() } else { String::new() }, 1.0, palette.color(UiColor::Title))
+        }
+        WarpState::Ready { just_charged: false } => (format!("WARP: {}", destination), 1.0, palette.color(UiColor::Text)),
+    };
+
+    let x = framebuffer.width as i32 - WARP_BAR_WIDTH as i32 - 10 + energy.shake(time);
+    let y = framebuffer.height as i32 - 4 * LINE_HEIGHT as i32 - 10;
+    draw_text(framebuffer, x, y, &text, color);
+    draw_bar(framebuffer, palette, x, y + LINE_HEIGHT as i32, fill, color);
+
+    if let Some(level) = energy.fraction() {
+        let energy_y = y - 2 * LINE_HEIGHT as i32;
+        let color = palette.color(if energy.shake(time) != 0 { UiColor::Warning } else { UiColor::Text });
+        draw_text(framebuffer, x, energy_y, &format!("ENERGY {:.0}%", level * 100.0), color);
+        draw_bar(framebuffer, palette, x, energy_y + LINE_HEIGHT as i32, level, palette.color(UiColor::WarpBar));
+    }
+}
+
+// Framed WARP_BAR_WIDTH wide bar filled to `fill`
+fn draw_bar(framebuffer: &mut Framebuffer, palette: PaletteMode, x: i32, y: i32, fill: f32, color: u32) {
+    framebuffer.fill_rect(x - 1, y - 1, WARP_BAR_WIDTH + 2, WARP_BAR_HEIGHT + 2, palette.color(UiColor::PanelBorder));
+    framebuffer.fill_rect(x, y, WARP_BAR_WIDTH, WARP_BAR_HEIGHT, palette.color(UiColor::Panel));
+    let filled = (WARP_BAR_WIDTH as f32 * fill.clamp(0.0, 1.0)).round() as usize;
+    framebuffer.fill_rect(x, y, filled, WARP_BAR_HEIGHT, color);
+}
+
+// Color scale of the temperature view, bottom center above the flight
+// strip, with its ends in kelvin on either side
+pub fn draw_heat_legend(framebuffer: &mut Framebuffer, palette: PaletteMode, heat: &HeatSettings) {
+    let center = framebuffer.width as i32 / 2;
+    let y = framebuffer.height as i32 - 4 * LINE_HEIGHT as i32 - 10;
+    let title = "TEMPERATURE";
+    draw_text(framebuffer, center - text_width(title) as i32 / 2, y, title, palette.color(UiColor::Title));
+
+    let (x, bar_y) = (center - LEGEND_WIDTH as i32 / 2, y + LINE_HEIGHT as i32);
+    framebuffer.fill_rect(x - 1, bar_y - 1, LEGEND_WIDTH + 2, LEGEND_HEIGHT + 2, palette.color(UiColor::PanelBorder));
+    for i in 0..LEGEND_WIDTH {
+        let color = gradient(i as f32 / (LEGEND_WIDTH - 1) as f32).to_pixel();
+        framebuffer.fill_rect(x + i as i32, bar_y, 1, LEGEND_HEIGHT, color);
+    }
+    let (low, high) = (format!("{:.0} K", heat.min_kelvin), format!("{:.0} K", heat.max_kelvin));
+    draw_text(framebuffer, x - text_width(&low) as i32 - 6, bar_y, &low, palette.color(UiColor::Text));
+    draw_text(framebuffer, x + LEGEND_WIDTH as i32 + 6, bar_y, &high, palette.color(UiColor::Text));
+}
+
+// Simulation speed and mode, bottom-left corner, followed by whichever of
+// the tagged names are set (e.g. ("FOLLOW", Some("Moon")))
+pub fn draw_status_line(
+    framebuffer: &mut Framebuffer,
+    palette: PaletteMode,
+    time_scale: f32,
+    mode: SimulationMode,
+    scale: ScaleProfile,
+    tags: &[(&str, Option<&str>)],
+) {
+    let mode_name = match mode {
+        SimulationMode::Kinematic => "KINEMATIC",
+        SimulationMode::NBody => "N-BODY",
+    };
+    let scale_name = match scale {
+        ScaleProfile::Stylized => "STYLIZED",
+        ScaleProfile::Realistic => "REALISTIC",
+    };
+    let mut line = format!("TIME x{}  {}  SCALE: {}", time_scale, mode_name, scale_name);
+    for (tag, name) in tags {
+        if let Some(name) = name {
+            line.push_str(&format!("  {}: {}", tag, name));
+        }
+    }
+    let y = framebuffer.height as i32 - LINE_HEIGHT as i32 - 4;
+    draw_text(framebuffer, 10, y, &line, palette.color(UiColor::Text));
+}
+
+// Every key binding, grouped under headings, centered over the scene
+pub fn draw_help(framebuffer: &mut Framebuffer, palette: PaletteMode, bindings: &InputBindings) {
+    // (text, is_heading), with a blank line between groups
+    let mut groups: Vec<Vec<(String, bool)>> = Vec::new();
+    for group in BindingGroup::ALL {
+        let mut lines = vec![(group.title().to_string(), true)];
+        for binding in bindings.in_group(group) {
+            lines.push((format!("{:<width$}{}", binding.key_label(), binding.description, width = HELP_KEY_COLUMN), false));
+        }
+        groups.push(lines);
+    }
+
+    // Whole groups go left until about half the lines are placed
+    let total: usize = groups.iter().map(|lines| lines.len() + 1).sum();
+    let mut columns: Vec<Vec<(String, bool)>> = vec![Vec::new()];
+    for lines in groups {
+        let splitting = columns.len() == 1 && framebuffer.width >= HELP_TWO_COLUMN_WIDTH;
+        let current = columns.last_mut().unwrap();
+        if !current.is_empty() {
+            current.push((String::new(), false));
+        }
+        current.extend(lines);
+        if splitting && 2 * (current.len() + 1) >= total {
+            columns.push(Vec::new());
+        }
+    }
+    columns.retain(|column| !column.is_empty());
+
+    let column_width = |column: &Vec<(String, bool)>| column.iter().map(|(text, _)| text_width(text)).max().unwrap_or(0);
+    let gap = 4 * PANEL_PADDING as usize;
+    let content_width = columns.iter().map(column_width).sum::<usize>() + gap * (columns.len() - 1);
+    let rows = columns.iter().map(|column| column.len()).max().unwrap_or(0) + 2;
+    let width = content_width + 2 * PANEL_PADDING as usize;
+    let height = rows * LINE_HEIGHT + 2 * PANEL_PADDING as usize - 3;
+    let x = (framebuffer.width as i32 - width as i32) / 2;
+    let y = (framebuffer.height as i32 - height as i32) / 2;
+
+    framebuffer.blend_rect(x - 1, y - 1, width + 2, height + 2, palette.color(UiColor::PanelBorder), HELP_ALPHA);
+    framebuffer.blend_rect(x, y, width, height, palette.color(UiColor::Panel), HELP_ALPHA);
+    draw_text(framebuffer, x + PANEL_PADDING, y + PANEL_PADDING, "CONTROLS  (H OR ESC TO CLOSE)", palette.color(UiColor::Title));
+
+    let mut column_x = x + PANEL_PADDING;
+    for column in &columns {
+        for (row, (text, heading)) in column.iter().enumerate() {
+            let color = palette.color(if *heading { UiColor::Title } else { UiColor::Text });
+            let line_y = y + PANEL_PADDING + ((row + 2) * LINE_HEIGHT) as i32;
+            draw_text(framebuffer, column_x, line_y, text, color);
+        }
+        column_x += (column_width(column) + gap) as i32;
+    }
+}
+
+// Darkens the frozen scene and lists the menu entries in the middle
+pub fn draw_pause_menu(framebuffer: &mut Framebuffer, palette: PaletteMode, menu: &PauseMenu, low_spec: &LowSpec) {
+    framebuffer.blend_rect(0, 0, framebuffer.width, framebuffer.height, 0x000000, PAUSE_DIM);
+
+    let mut lines = vec!["PAUSED".to_string(), String::new()];
+    for entry in MenuEntry::ALL {
+        let marker = if entry == menu.selected() { ">" } else { " " };
+        match entry {
+            MenuEntry::LowSpec => lines.push(format!("{} {}: {}", marker, entry.label(), if low_spec.active { "on" } else { "off" })),
+            _ => lines.push(format!("{} {}", marker, entry.label())),
+        }
+    }
+    // Draw times before and after, while it's highlighted
+    if menu.selected() == MenuEntry::LowSpec {
+        lines.push(String::new());
+        lines.push(low_spec.readout());
+    }
+    if let Some(message) = &menu.message {
+        lines.push(String::new());
+        lines.push(message.clone());
+    }
+    let (width, height) = panel_size(&lines);
+    let x = (framebuffer.width as i32 - width as i32) / 2;
+    let y = (framebuffer.height as i32 - height as i32) / 2;
+    draw_panel(framebuffer, palette, x, y, &lines);
+}
+
+// Offer to dock, centered under the ship
+pub fn draw_docking_prompt(framebuffer: &mut Framebuffer, palette: PaletteMode, station: &CelestialBody) {
+    let text = format!("Z: DOCK AT {}", station.name.to_uppercase());
+    let y = framebuffer.height as i32 * 3 / 4;
+    draw_text(framebuffer, framebuffer.width as i32 / 2 - text_width(&text) as i32 / 2, y, &text, palette.color(UiColor::Text));
+}
+
+// Asks before skipping ahead to an alignment, centered like the docking
+// prompt
+pub fn draw_alignment_prompt(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, alignment: &Alignment, bodies: &[CelestialBody]) {
+    let names: Vec<&str> = alignment.bodies.iter().map(|&i| bodies[i].name.as_str()).collect();
+    let lines = [
+        format!("{} LINE UP IN {}", names.join(", ").to_uppercase(), units.duration(alignment.wait).to_uppercase()),
+        "ENTER: SKIP AHEAD   ESC: CANCEL".to_string(),
+    ];
+    let y = framebuffer.height as i32 * 3 / 4;
+    for (row, text) in lines.iter().enumerate() {
+        draw_text(framebuffer, framebuffer.width as i32 / 2 - text_width(text) as i32 / 2, y + (row * LINE_HEIGHT) as i32, text, palette.color(UiColor::Text));
+    }
+}
+
+// Menu while docked, over the live scene so the station keeps turning
+// behind it
+pub fn draw_docked_menu(framebuffer: &mut Framebuffer, palette: PaletteMode, docking: &Docking, station: &CelestialBody, damage: f32) {
+    let Some(selected) = docking.selected() else { return };
+    let mut lines = vec![format!("DOCKED AT {}", station.name.to_uppercase()), format!("Hull {:.0}%", (1.0 - damage) * 100.0), String::new()];
+    for entry in DockedEntry::ALL {
+        let marker = if entry == selected { ">" } else { " " };
+        lines.push(format!("{} {}", marker, entry.label()));
+    }
+    let (width, height) = panel_size(&lines);
+    let x = (framebuffer.width as i32 - width as i32) / 2;
+    let y = (framebuffer.height as i32 - height as i32) / 3;
+    draw_panel(framebuffer, palette, x, y, &lines);
+}
+
+// Red REC marker at the top while a clip is being captured, then a note
+// while it is written out and where it went
+pub fn draw_recording_indicator(framebuffer: &mut Framebuffer, palette: PaletteMode, recorder: &Recorder, time: f32) {
+    let center = framebuffer.width as i32 / 2;
+    let y = 10;
+    if recorder.is_recording() {
+        let text = if recorder.full {
+            "REC FULL - F9 TO SAVE".to_string()
+        } else {
+            format!("REC {}/{}", recorder.frame_count(), recorder.max_frames())
+        };
+        let width = text_width(&text) as i32 + RECORDING_DOT_SIZE as i32 + 4;
+        let x = center - width / 2;
+        // The dot blinks once the memory cap is hit
+        if !recorder.full || (time * WARNING_BLINK_RATE).fract() < 0.5 {
+            framebuffer.fill_rect(x, y, RECORDING_DOT_SIZE, RECORDING_DOT_SIZE, palette.color(UiColor::Warning));
+        }
+        draw_text(framebuffer, x + RECORDING_DOT_SIZE as i32 + 4, y, &text, palette.color(UiColor::Warning));
+    } else if recorder.is_saving() {
+        let text = "SAVING CLIP...";
+        draw_text(framebuffer, center - text_width(text) as i32 / 2, y, text, palette.color(UiColor::Text));
+    } else if let Some(message) = recorder.message() {
+        draw_text(framebuffer, center - text_width(message) as i32 / 2, y, message, palette.color(UiColor::Text));
+    }
+}
+
+// One-line message under the top edge (saves, loads and their errors)
+pub fn draw_notice(framebuffer: &mut Framebuffer, palette: PaletteMode, text: &str) {
+    let y = 10 + LINE_HEIGHT as i32;
+    draw_text(framebuffer, framebuffer.width as i32 / 2 - text_width(text) as i32 / 2, y, text, palette.color(UiColor::Text));
+}
+
+// Photo mode's only on-screen text, bottom-left, which the photos leave out.
+// The result of the last F12 stands in for it for a moment
+pub fn draw_photo_controls(framebuffer: &mut Framebuffer, palette: PaletteMode, units: Units, photo: &PhotoMode) {
+    let line = match photo.message() {
+        Some(message) => message.to_string(),
+        None => format!(
+            "PHOTO  FOV {:.0}  FOCUS {}  {}  VIGNETTE {}  F12 SAVE  P EXIT",
+            photo.fov.to_degrees(),
+            units.distance(photo.focus),
+            if photo.aperture > 0.0 { format!("BLUR {:.0}", photo.aperture) } else { "BLUR OFF".to_string() },
+            if photo.vignette { "ON" } else { "OFF" },
+        ),
+    };
+    let y = framebuffer.height as i32 - LINE_HEIGHT as i32 - 4;
+    draw_text(framebuffer, 10, y, &line, palette.color(UiColor::Text));
+}
+
+// Split screen: fills the gaps between viewports and names each one along
+// its bottom edge; the one the camera keys move is highlighted
+pub fn draw_viewport_labels(framebuffer: &mut Framebuffer, palette: PaletteMode, viewports: &[(Viewport, &str)], active: usize) {
+    for pair in viewports.windows(2) {
+        let (left, right) = (pair[0].0, pair[1].0);
+        let gap_x = left.x + left.width;
+        framebuffer.fill_rect(gap_x as i32, left.y as i32, right.x - gap_x, left.height, palette.color(UiColor::PanelBorder));
+    }
+    for (index, (viewport, name)) in viewports.iter().enumerate() {
+        let (text, color) = if index == active {
+            (format!("[{}]", name), palette.color(UiColor::Title))
+        } else {
+            (name.to_string(), palette.color(UiColor::Text))
+        };
+        let x = (viewport.x + viewport.width / 2) as i32 - text_width(&text) as i32 / 2;
+        let y = (viewport.y + viewport.height) as i32 - 4 * LINE_HEIGHT as i32 - 10;
+        draw_text(framebuffer, x, y, &text, color);
+    }
+}
+
+// One-pixel border around the picture-in-picture inset, and its name in
+// the top-left corner
+pub fn draw_inset_frame(framebuffer: &mut Framebuffer, palette: PaletteMode, rect: Viewport, label: &str) {
+    let (x, y) = (rect.x as i32 - 1, rect.y as i32 - 1);
+    let (width, height) = (rect.width + 2, rect.height + 2);
+    let border = palette.color(UiColor::PanelBorder);
+    framebuffer.fill_rect(x, y, width, 1, border);
+    framebuffer.fill_rect(x, y + height as i32 - 1, width, 1, border);
+    framebuffer.fill_rect(x, y, 1, height, border);
+    framebuffer.fill_rect(x + width as i32 - 1, y, 1, height, border);
+    draw_text(framebuffer, rect.x as i32 + 3, rect.y as i32 + 3, label, palette.color(UiColor::Text));
+}
+
+// Row of warp target thumbnails along the bottom, above the heat legend,
+// each framed with its warp key and named underneath. The selected body's
+// name stands out
+pub fn draw_warp_thumbnails(framebuffer: &mut Framebuffer, palette: PaletteMode, thumbnails: &Thumbnails, bodies: &[CelestialBody], selected: usize) {
+    let slots = thumbnails.slots();
+    let row_width = slots.len() * (THUMBNAIL_SIZE + THUMBNAIL_GAP);
+    let left = (framebuffer.width.saturating_sub(row_width) + THUMBNAIL_GAP) / 2;
+    let name_y = framebuffer.height as i32 - 6 * LINE_HEIGHT as i32 - 10;
+    let top = name_y - THUMBNAIL_SIZE as i32 - 4;
+    for (slot, thumbnail) in slots.iter().enumerate() {
+        let rect = Viewport { x: left + slot * (THUMBNAIL_SIZE + THUMBNAIL_GAP), y: top.max(0) as usize, width: THUMBNAIL_SIZE, height: THUMBNAIL_SIZE };
+        framebuffer.blit_scaled(&thumbnail.image, rect);
+        draw_inset_frame(framebuffer, palette, rect, &(slot + 1).to_string());
+
+        let mut name = bodies[thumbnail.body].name.to_uppercase();
+        while text_width(&name) > THUMBNAIL_SIZE {
+            name.pop();
+        }
+        let color = palette.color(if thumbnail.body == selected { UiColor::Title } else { UiColor::Text });
+        let x = (rect.x + THUMBNAIL_SIZE / 2) as i32 - text_width(&name) as i32 / 2;
+        draw_text(framebuffer, x, name_y, &name, color);
+    }
+}
@@ -0,0 +1,263 @@
+use nalgebra_glm::{Vec3, Vec4};
+use serde::Deserialize;
+use crate::body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::overlay::{ScreenProjection, draw_point, draw_glow};
+use crate::geometry::normalize_or;
+use crate::rng::{Rng, Seed};
+use crate::ShaderType;
+
+// Simulated seconds between rolls for a new impact
+const ROLL_INTERVAL: f32 = 1.0;
+// Asteroids start this many radii out from their target and take about
+// this many simulated seconds to land
+const SPAWN_DISTANCE: f32 = 8.0;
+const TRAVEL_SECONDS: f32 = 3.0;
+// No new asteroids while this many are on their way
+const MAX_IMPACTORS: usize = 8;
+// A body with this many craters loses its oldest one to the next impact
+const MAX_CRATERS: usize = 32;
+// The flash lasts this long (animation seconds, so it shows at any time
+// scale); sparks fly out to this fraction of the body's radius
+const FLASH_SECONDS: f32 = 1.2;
+const FLASH_SIZE: f32 = 8.0;
+const FLASH_SPARKS: usize = 12;
+const SPARK_REACH: f32 = 0.4;
+const FLASH_COLOR: u32 = 0xFFF4D0;
+const SPARK_COLOR: u32 = 0xFF9040;
+const ASTEROID_COLOR: u32 = 0x9C9080;
+
+// [impacts] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct ImpactSettings {
+    // Random impacts; X still forces one on the selected body when off
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // Chance per simulated second that an asteroid heads for a rocky body
+    #[serde(default = "default_chance")]
+    pub chance_per_second: f32,
+    // Same seed, scene and input give the same impacts, so replays match.
+    // Taken with the scene's master seed
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+}
+
+impl Default for ImpactSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            chance_per_second: default_chance(),
+            seed: default_seed(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_chance() -> f32 {
+    0.02
+}
+
+fn default_seed() -> u64 {
+    1
+}
+
+// An asteroid on its way in, relative to its target so it keeps up with
+// the body's orbit
+#[derive(Clone, Copy)]
+struct Impactor {
+    body: usize,
+    offset: Vec3,
+    velocity: Vec3,
+}
+
+// Where an asteroid hit, in the body's model space like its craters
+struct Flash {
+    body: usize,
+    direction: Vec3,
+    sparks: Vec<Vec3>,
+    started: f32,
+}
+
+pub struct Impacts {
+    settings: ImpactSettings,
+    rng: Rng,
+    since_roll: f32,
+    impactors: Vec<Impactor>,
+    flashes: Vec<Flash>,
+}
+
+impl Impacts {
+    pub fn new(settings: ImpactSettings, seed: Seed) -> Self {
+        let rng = seed.derive("impacts").derive(settings.seed).rng();
+        Self { settings, rng, since_roll: 0.0, impactors: Vec::new(), flashes: Vec::new() }
+    }
+
+    // Asteroids in flight and the random rolls carry on, only how often
+    // new ones come changes
+    pub fn set_settings(&mut self, settings: ImpactSettings) {
+        self.settings = settings;
+    }
+
+    // Sends an asteroid at the body, false for stars and bodies without a
+    // surface
+    pub fn force(&mut self, index: usize, bodies: &[CelestialBody]) -> bool {
+        let body = &bodies[index];
+        if body.is_virtual() || body.is_star() || body.is_portal() {
+            return false;
+        }
+        self.launch(index, body);
+        true
+    }
+
+    fn launch(&mut self, index: usize, body: &CelestialBody) {
+        let radius = body.bounding_radius();
+        let from = self.rng.unit_vector();
+        // Aimed at the disc the body shows the asteroid, well inside its
+        // edge so it can't graze past
+        let across = normalize_or(from.cross(&Vec3::y()), Vec3::x());
+        let spot = self.rng.in_disc() * 0.8;
+        let aim = (across * spot.x + from.cross(&across) * spot.y) * radius;
+        let offset = from * radius * SPAWN_DISTANCE;
+        self.impactors.push(Impactor { body: index, offset, velocity: (aim - offset) / TRAVEL_SECONDS });
+    }
+
+    // Rolls for new asteroids on simulation time, moves the ones in flight
+    // and turns those that arrive into craters. Run after the scene graph,
+    // craters are placed with this frame's model matrices
+    pub fn update(&mut self, bodies: &mut [CelestialBody], sim_delta: f32, time: f32) {
+        if self.settings.enabled {
+            self.since_roll += sim_delta;
+            while self.since_roll >= ROLL_INTERVAL {
+                self.since_roll -= ROLL_INTERVAL;
+                if self.rng.next_f32() >= self.settings.chance_per_second * ROLL_INTERVAL || self.impactors.len() >= MAX_IMPACTORS {
+                    continue;
+                }
+                let targets: Vec<usize> = (0..bodies.len())
+                    .filter(|&i| matches!(bodies[i].shader_type, ShaderType::RockyPlanet) && !bodies[i].is_virtual())
+                    .collect();
+                if !targets.is_empty() {
+                    let index = targets[self.rng.below(targets.len())];
+                    self.launch(index, &bodies[index]);
+                }
+            }
+        }
+
+        let mut landed = Vec::new();
+        self.impactors.retain_mut(|impactor| {
+            let Some(body) = bodies.get(impactor.body) else { return false };
+            let step = impactor.velocity * sim_delta;
+            match entry_point(impactor.offset, step, body.bounding_radius()) {
+                Some(point) => {
+                    landed.push((impactor.body, point));
+                    false
+                }
+                None => {
+                    impactor.offset += step;
+                    true
+                }
+            }
+        });
+        for (index, point) in landed {
+            self.land(&mut bodies[index], index, point, time);
+        }
+        self.flashes.retain(|flash| time - flash.started < FLASH_SECONDS);
+    }
+
+    fn land(&mut self, body: &mut CelestialBody, index: usize, point: Vec3, time: f32) {
+        let Some(to_model) = body.model_matrix.try_inverse() else { return };
+        let local = to_model * Vec4::new(body.position.x + point.x, body.position.y + point.y, body.position.z + point.z, 1.0);
+        let direction = local.xyz().normalize();
+        if !direction.x.is_finite() {
+            return;
+        }
+        if body.craters.len() >= MAX_CRATERS {
+            body.craters.remove(0);
+        }
+        body.craters.push(direction);
+        // Sparks leave the surface, mostly outward
+        let sparks = (0..FLASH_SPARKS).map(|_| (direction + self.rng.unit_vector() * 0.8).normalize()).collect();
+        self.flashes.push(Flash { body: index, direction, sparks, started: time });
+    }
+
+    // Asteroids as small dots, flashes as a fading glow with sparks. Depth
+    // tested, so impacts on the far side stay hidden
+    pub fn draw(&self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, bodies: &[CelestialBody], time: f32) {
+        for impactor in &self.impactors {
+            if let Some(screen) = projection.project(bodies[impactor.body].position + impactor.offset) {
+                draw_point(framebuffer, screen, 2, ASTEROID_COLOR);
+            }
+        }
+        for flash in &self.flashes {
+            let body = &bodies[flash.body];
+            let fade = 1.0 - (time - flash.started) / FLASH_SECONDS;
+            // Just above the surface, so the ground around it doesn't hide it
+            let center = flash.direction * body.mesh_radius * 1.02;
+            let to_world = |local: Vec3| (body.model_matrix * Vec4::new(local.x, local.y, local.z, 1.0)).xyz();
+            for spark in &flash.sparks {
+                let position = to_world(center + spark * body.mesh_radius * SPARK_REACH * (1.0 - fade));
+                if let Some(screen) = projection.project(position) {
+                    draw_glow(framebuffer, screen, 2, SPARK_COLOR, fade);
+                }
+            }
+            if let Some(screen) = projection.project(to_world(center)) {
+                draw_glow(framebuffer, screen, (FLASH_SIZE * fade).ceil() as i32, FLASH_COLOR, fade);
+            }
+        }
+    }
+}
+
+// Where a step from `from` first touches a sphere of `radius` around the
+// origin, None when it stays outside
+fn entry_point(from: Vec3, step: Vec3, radius: f32) -> Option<Vec3> {
+    let a = step.dot(&step);
+    let b = 2.0 * from.dot(&step);
+    let c = from.dot(&from) - radius * radius;
+    if c <= 0.0 {
+        return Some(from);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if a <= 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2.0 * a);
+    (0.0..=1.0).contains(&t).then(|| from + step * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn planet() -> CelestialBody {
+        let vertices = vec![crate::vertex::Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), nalgebra_glm::Vec2::zeros())];
+        CelestialBody::new_planet("Planet".to_string(), vertices, Vec3::zeros(), 0.0, 0.0, 10.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet)
+    }
+
+    #[test]
+    fn forced_impacts_leave_the_same_craters_every_run() {
+        let run = || {
+            let mut bodies = vec![planet()];
+            let mut impacts = Impacts::new(ImpactSettings { enabled: false, ..ImpactSettings::default() }, Seed(1));
+            assert!(impacts.force(0, &bodies));
+            let mut time = 0.0;
+            // Big steps must not let the asteroid tunnel through
+            while !impacts.impactors.is_empty() {
+                assert!(time < TRAVEL_SECONDS * 2.0, "the asteroid missed");
+                time += 1.7;
+                impacts.update(&mut bodies, 1.7, time);
+            }
+            assert_eq!(impacts.flashes.len(), 1);
+            bodies.remove(0).craters
+        };
+        let craters = run();
+        assert_eq!(craters.len(), 1);
+        assert!((craters[0].magnitude() - 1.0).abs() < 1e-4);
+        assert_eq!(craters, run());
+
+        assert_eq!(entry_point(Vec3::new(-20.0, 0.0, 0.0), Vec3::new(40.0, 0.0, 0.0), 10.0), Some(Vec3::new(-10.0, 0.0, 0.0)));
+        assert_eq!(entry_point(Vec3::new(-20.0, 15.0, 0.0), Vec3::new(40.0, 0.0, 0.0), 10.0), None);
+    }
+}
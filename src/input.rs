@@ -0,0 +1,536 @@
+use minifb::Key;
+
+// Everything the keyboard can do. The main loop asks for actions rather than
+// keys, so this table is the one place bindings live (and the help overlay
+// is generated from it)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    SelectNext,
+    Follow,
+    SplitScreen,
+    SwitchViewport,
+    CycleInset,
+    StandOnSurface,
+    Warp,
+    WarpToSelected,
+    Autopilot,
+    ToggleShip,
+    NextShip,
+    RepairShip,
+    Dock,
+    ToggleOrbits,
+    ToggleTrails,
+    ToggleLagrange,
+    ToggleConstellations,
+    ToggleHeat,
+    CycleShading,
+    ToggleFlightStrip,
+    ToggleTrajectory,
+    Measure,
+    Align,
+    Cruise,
+    ForceImpact,
+    ToggleThumbnails,
+    ToggleScale,
+    SpeedUp,
+    SlowDown,
+    Help,
+    Record,
+    ToggleFrameCap,
+    QuickSave,
+    QuickLoad,
+    ToggleObjectives,
+    CyclePalette,
+    ExposureDown,
+    ExposureUp,
+    AutoExposure,
+    ToggleMotionBlur,
+    ToggleGrain,
+    ToggleVignetteFilter,
+    ToggleCrt,
+    CycleGrading,
+    ToggleRetro,
+    ToggleStereo,
+    EyesCloser,
+    EyesApart,
+    ConvergeNearer,
+    ConvergeFarther,
+    PhotoMode,
+    FlyForward,
+    FlyBack,
+    FlyLeft,
+    FlyRight,
+    TurnLeft,
+    TurnRight,
+    TurnUp,
+    TurnDown,
+    RollLeft,
+    RollRight,
+    WidenView,
+    NarrowView,
+    ToggleVignette,
+    FocusNearer,
+    FocusFarther,
+    FocusCenter,
+    NarrowerAperture,
+    WiderAperture,
+    ShowFocus,
+    PhotoCapture,
+    Back,
+    MenuUp,
+    MenuDown,
+    MenuSelect,
+    ToggleTuning,
+    TunePrevious,
+    TuneNext,
+    TuneLess,
+    TuneMore,
+    TuneFine,
+    PrintTuning,
+}
+
+impl Action {
+    // Every action, for looking them up by name in input logs
+    pub const ALL: [Action; 88] = [
+        Action::OrbitLeft,
+        Action::OrbitRight,
+        Action::OrbitUp,
+        Action::OrbitDown,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::SelectNext,
+        Action::Follow,
+        Action::SplitScreen,
+        Action::SwitchViewport,
+        Action::CycleInset,
+        Action::StandOnSurface,
+        Action::Warp,
+        Action::WarpToSelected,
+        Action::Autopilot,
+        Action::ToggleShip,
+        Action::NextShip,
+        Action::RepairShip,
+        Action::Dock,
+        Action::ToggleOrbits,
+        Action::ToggleTrails,
+        Action::ToggleLagrange,
+        Action::ToggleConstellations,
+        Action::ToggleHeat,
+        Action::CycleShading,
+        Action::ToggleFlightStrip,
+        Action::ToggleTrajectory,
+        Action::Measure,
+        Action::Align,
+        Action::Cruise,
+        Action::ForceImpact,
+        Action::ToggleThumbnails,
+        Action::ToggleScale,
+        Action::SpeedUp,
+        Action::SlowDown,
+        Action::Help,
+        Action::Record,
+        Action::ToggleFrameCap,
+        Action::QuickSave,
+        Action::QuickLoad,
+        Action::ToggleObjectives,
+        Action::CyclePalette,
+        Action::ExposureDown,
+        Action::ExposureUp,
+        Action::AutoExposure,
+        Action::ToggleMotionBlur,
+        Action::ToggleGrain,
+        Action::ToggleVignetteFilter,
+        Action::ToggleCrt,
+        Action::CycleGrading,
+        Action::ToggleRetro,
+        Action::ToggleStereo,
+        Action::EyesCloser,
+        Action::EyesApart,
+        Action::ConvergeNearer,
+        Action::ConvergeFarther,
+        Action::PhotoMode,
+        Action::FlyForward,
+        Action::FlyBack,
+        Action::FlyLeft,
+        Action::FlyRight,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::TurnUp,
+        Action::TurnDown,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::WidenView,
+        Action::NarrowView,
+        Action::ToggleVignette,
+        Action::FocusNearer,
+        Action::FocusFarther,
+        Action::FocusCenter,
+        Action::NarrowerAperture,
+        Action::WiderAperture,
+        Action::ShowFocus,
+        Action::PhotoCapture,
+        Action::Back,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuSelect,
+        Action::ToggleTuning,
+        Action::TunePrevious,
+        Action::TuneNext,
+        Action::TuneLess,
+        Action::TuneMore,
+        Action::TuneFine,
+        Action::PrintTuning,
+    ];
+
+    pub fn name(self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|action| action.name() == name)
+    }
+}
+
+// Headings the help overlay sorts bindings under
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingGroup {
+    Camera,
+    Navigation,
+    Ship,
+    Stereo,
+    Global,
+    // Exposure and the post-processing filters
+    Image,
+    // Only read while the pause menu is open, so these keys may also mean
+    // something in the other groups
+    Menu,
+    // Only read in photo mode, along with the keys that leave it
+    Photo,
+    // Only read while the shader tuning panel is open, along with the keys
+    // that close it
+    Tuning,
+}
+
+impl BindingGroup {
+    pub const ALL: [BindingGroup; 9] = [
+        BindingGroup::Camera,
+        BindingGroup::Navigation,
+        BindingGroup::Ship,
+        BindingGroup::Stereo,
+        BindingGroup::Global,
+        BindingGroup::Menu,
+        BindingGroup::Photo,
+        BindingGroup::Tuning,
+        BindingGroup::Image,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            BindingGroup::Camera => "ORBITAL CAMERA",
+            BindingGroup::Navigation => "NAVIGATION",
+            BindingGroup::Ship => "SHIP",
+            BindingGroup::Stereo => "STEREO 3D",
+            BindingGroup::Global => "GLOBAL",
+            BindingGroup::Menu => "PAUSE MENU",
+            BindingGroup::Photo => "PHOTO MODE",
+            BindingGroup::Image => "IMAGE",
+            BindingGroup::Tuning => "SHADER TUNING",
+        }
+    }
+}
+
+pub struct Binding {
+    pub action: Action,
+    pub keys: Vec<Key>,
+    pub group: BindingGroup,
+    pub description: &'static str,
+}
+
+impl Binding {
+    // Keys as shown to the user: "F", "H/F1", or "1-9" for a run of keys.
+    // Left and right modifiers show once
+    pub fn key_label(&self) -> String {
+        match self.keys.as_slice() {
+            [first, .., last] if self.keys.len() > 2 => format!("{}-{}", key_name(*first), key_name(*last)),
+            keys => {
+                let mut names: Vec<String> = keys.iter().map(|&key| key_name(key)).collect();
+                names.dedup();
+                names.join("/")
+            }
+        }
+    }
+}
+
+pub struct InputBindings {
+    bindings: Vec<Binding>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use Action::*;
+        use BindingGroup::*;
+        let bind = |action, keys: &[Key], group, description| Binding { action, keys: keys.to_vec(), group, description };
+        InputBindings {
+            bindings: vec![
+                bind(OrbitLeft, &[Key::Left], Camera, "Orbit left"),
+                bind(OrbitRight, &[Key::Right], Camera, "Orbit right"),
+                bind(OrbitUp, &[Key::Up], Camera, "Orbit up"),
+                bind(OrbitDown, &[Key::Down], Camera, "Orbit down"),
+                bind(ZoomIn, &[Key::A], Camera, "Zoom in"),
+                bind(ZoomOut, &[Key::S], Camera, "Zoom out"),
+                bind(Follow, &[Key::F], Camera, "Follow selected body"),
+                bind(SplitScreen, &[Key::F6], Camera, "Split screen"),
+                bind(SwitchViewport, &[Key::F7], Camera, "Switch camera keys"),
+                bind(CycleInset, &[Key::B], Camera, "Rear / target inset"),
+                bind(StandOnSurface, &[Key::Home], Camera, "Stand on selected body"),
+                bind(SelectNext, &[Key::Tab], Navigation, "Select next body"),
+                bind(Warp, &[
+                    Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+                    Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+                ], Navigation, "Warp to numbered body"),
+                bind(WarpToSelected, &[Key::Enter], Navigation, "Warp to selected body"),
+                bind(Autopilot, &[Key::G], Navigation, "Autopilot to selected"),
+                bind(Measure, &[Key::M], Navigation, "Measure distance"),
+                bind(Align, &[Key::Backslash], Navigation, "Skip to alignment"),
+                bind(Cruise, &[Key::Space], Navigation, "Cruise (hold)"),
+                bind(ForceImpact, &[Key::X], Navigation, "Asteroid at selected"),
+                bind(ToggleThumbnails, &[Key::W], Navigation, "Warp target previews"),
+                bind(ToggleShip, &[Key::V], Ship, "Chase view on/off"),
+                bind(NextShip, &[Key::N], Ship, "Next ship"),
+                bind(RepairShip, &[Key::D], Ship, "Repair hull"),
+                bind(Dock, &[Key::Z], Ship, "Dock at nearby station"),
+                bind(ToggleFlightStrip, &[Key::I], Ship, "Flight readouts"),
+                bind(ToggleTrajectory, &[Key::Y], Ship, "Predicted path"),
+                bind(ToggleStereo, &[Key::F3], Stereo, "Red-cyan 3D"),
+                bind(EyesCloser, &[Key::Comma], Stereo, "Eyes closer"),
+                bind(EyesApart, &[Key::Period], Stereo, "Eyes apart"),
+                bind(ConvergeNearer, &[Key::Semicolon], Stereo, "Converge nearer"),
+                bind(ConvergeFarther, &[Key::Apostrophe], Stereo, "Converge farther"),
+                bind(SpeedUp, &[Key::RightBracket], Global, "Faster time"),
+                bind(SlowDown, &[Key::LeftBracket], Global, "Slower time"),
+                bind(ToggleScale, &[Key::K], Global, "Switch scale profile"),
+                bind(ToggleOrbits, &[Key::O], Global, "Orbits"),
+                bind(ToggleTrails, &[Key::T], Global, "Trails"),
+                bind(ToggleLagrange, &[Key::L], Global, "Lagrange points"),
+                bind(ToggleConstellations, &[Key::Slash], Global, "Constellations"),
+                bind(ToggleHeat, &[Key::E], Global, "Temperature view"),
+                bind(Help, &[Key::H, Key::F1], Global, "This help"),
+                bind(Record, &[Key::F9], Global, "Record clip"),
+                bind(ToggleFrameCap, &[Key::U], Global, "Uncap frame rate"),
+                bind(QuickSave, &[Key::F5], Global, "Quick-save"),
+                bind(QuickLoad, &[Key::F8], Global, "Quick-load"),
+                bind(ToggleObjectives, &[Key::Q], Global, "Objectives"),
+                bind(CyclePalette, &[Key::C], Global, "Color palette"),
+                bind(PhotoMode, &[Key::P], Global, "Photo mode"),
+                bind(ToggleTuning, &[Key::R], Global, "Tune selected shader"),
+                bind(Back, &[Key::Escape], Global, "Close / cancel / pause"),
+                bind(ExposureDown, &[Key::Minus], Image, "Darker exposure"),
+                bind(ExposureUp, &[Key::Equal], Image, "Brighter exposure"),
+                bind(AutoExposure, &[Key::Key0], Image, "Automatic exposure"),
+                bind(ToggleMotionBlur, &[Key::J], Image, "Motion blur"),
+                bind(ToggleGrain, &[Key::F2], Image, "Film grain"),
+                bind(ToggleVignetteFilter, &[Key::F4], Image, "Vignette"),
+                bind(ToggleCrt, &[Key::F10], Image, "CRT scanlines"),
+                bind(CycleGrading, &[Key::F11], Image, "Color grade"),
+                bind(ToggleRetro, &[Key::Backquote], Image, "Retro look"),
+                bind(CycleShading, &[Key::Insert], Image, "Flat / smooth shading"),
+                bind(MenuUp, &[Key::Up], Menu, "Previous entry"),
+                bind(MenuDown, &[Key::Down], Menu, "Next entry"),
+                bind(MenuSelect, &[Key::Enter], Menu, "Choose entry"),
+                bind(FlyForward, &[Key::W], Photo, "Fly forward"),
+                bind(FlyBack, &[Key::S], Photo, "Fly back"),
+                bind(FlyLeft, &[Key::A], Photo, "Fly left"),
+                bind(FlyRight, &[Key::D], Photo, "Fly right"),
+                bind(TurnLeft, &[Key::Left], Photo, "Turn left"),
+                bind(TurnRight, &[Key::Right], Photo, "Turn right"),
+                bind(TurnUp, &[Key::Up], Photo, "Look up"),
+                bind(TurnDown, &[Key::Down], Photo, "Look down"),
+                bind(RollLeft, &[Key::Q], Photo, "Roll left"),
+                bind(RollRight, &[Key::E], Photo, "Roll right"),
+                bind(NarrowView, &[Key::Z], Photo, "Narrower view"),
+                bind(WidenView, &[Key::X], Photo, "Wider view"),
+                bind(ToggleVignette, &[Key::V], Photo, "Vignette"),
+                bind(FocusNearer, &[Key::R], Photo, "Focus nearer"),
+                bind(FocusFarther, &[Key::T], Photo, "Focus farther"),
+                bind(FocusCenter, &[Key::F], Photo, "Focus on center"),
+                bind(NarrowerAperture, &[Key::G], Photo, "Less blur"),
+                bind(WiderAperture, &[Key::H], Photo, "More blur"),
+                bind(ShowFocus, &[Key::Y], Photo, "Show focus plane"),
+                bind(PhotoCapture, &[Key::F12], Photo, "Take photo"),
+                bind(TunePrevious, &[Key::Up], Tuning, "Previous value"),
+                bind(TuneNext, &[Key::Down], Tuning, "Next value"),
+                bind(TuneLess, &[Key::Left], Tuning, "Decrease"),
+                bind(TuneMore, &[Key::Right], Tuning, "Increase"),
+                bind(TuneFine, &[Key::LeftShift, Key::RightShift], Tuning, "Fine steps (hold)"),
+                bind(PrintTuning, &[Key::Enter], Tuning, "Print as scene line"),
+            ],
+        }
+    }
+}
+
+impl InputBindings {
+    fn binding(&self, action: Action) -> Option<&Binding> {
+        self.bindings.iter().find(|binding| binding.action == action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.binding(action).map_or(&[], |binding| binding.keys.as_slice())
+    }
+
+    // Turns the keyboard into actions for this frame, given which keys are
+    // down and which went down since the last frame
+    pub fn sample(&self, is_down: impl Fn(Key) -> bool, went_down: impl Fn(Key) -> bool) -> InputFrame {
+        let mut frame = InputFrame::default();
+        for binding in &self.bindings {
+            if binding.keys.iter().any(|&key| is_down(key)) {
+                frame.held.push(binding.action);
+            }
+            if let Some(index) = binding.keys.iter().position(|&key| went_down(key)) {
+                frame.pressed.push((binding.action, index));
+            }
+        }
+        frame
+    }
+
+    pub fn in_group(&self, group: BindingGroup) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter().filter(move |binding| binding.group == group)
+    }
+}
+
+// What the keyboard did during one frame, in actions. Live input and
+// replayed input logs both come in as these
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct InputFrame {
+    // Actions with a key held down
+    held: Vec<Action>,
+    // Actions whose key went down this frame, with which of their keys
+    pressed: Vec<(Action, usize)>,
+}
+
+impl InputFrame {
+    // True on the frame one of the action's keys goes down
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed_index(action).is_some()
+    }
+
+    // Which of the action's keys went down this frame (for numbered keys)
+    pub fn pressed_index(&self, action: Action) -> Option<usize> {
+        self.pressed.iter().find(|(pressed, _)| *pressed == action).map(|&(_, index)| index)
+    }
+
+    // True for as long as one of the action's keys is held
+    pub fn held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    // One line of an input log: held actions by name, then pressed ones as
+    // +Name:key, e.g. "OrbitLeft ZoomIn +Warp:2"
+    pub fn to_line(&self) -> String {
+        let held = self.held.iter().map(|action| action.name());
+        let pressed = self.pressed.iter().map(|(action, index)| format!("+{}:{}", action.name(), index));
+        held.chain(pressed).collect::<Vec<_>>().join(" ")
+    }
+
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut frame = InputFrame::default();
+        for token in line.split_whitespace() {
+            let unknown = || format!("Unknown input '{}'", token);
+            match token.strip_prefix('+') {
+                Some(pressed) => {
+                    let (name, index) = pressed.split_once(':').ok_or_else(unknown)?;
+                    let action = Action::from_name(name).ok_or_else(unknown)?;
+                    frame.pressed.push((action, index.parse().map_err(|_| unknown())?));
+                }
+                None => frame.held.push(Action::from_name(token).ok_or_else(unknown)?),
+            }
+        }
+        Ok(frame)
+    }
+}
+
+fn key_name(key: Key) -> String {
+    match key {
+        Key::LeftBracket => "[".to_string(),
+        Key::RightBracket => "]".to_string(),
+        Key::Comma => ",".to_string(),
+        Key::Period => ".".to_string(),
+        Key::Semicolon => ";".to_string(),
+        Key::Apostrophe => "'".to_string(),
+        Key::Minus => "-".to_string(),
+        Key::Equal => "=".to_string(),
+        Key::Backquote => "`".to_string(),
+        Key::Slash => "/".to_string(),
+        Key::Backslash => "\\".to_string(),
+        Key::Escape => "ESC".to_string(),
+        Key::LeftShift | Key::RightShift => "SHIFT".to_string(),
+        _ => {
+            let name = format!("{:?}", key);
+            // Key1 -> 1
+            match name.strip_prefix("Key") {
+                Some(digit) if !digit.is_empty() => digit.to_string(),
+                _ => name.to_uppercase(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_does_one_thing() {
+        let bindings = InputBindings::default();
+        // The menu, photo mode and the tuning panel are contexts of their
+        // own, everything else is live at once. Photo mode and tuning still
+        // listen for the keys leaving them
+        let leaves_photo = |b: &Binding| matches!(b.action, Action::PhotoMode | Action::Back);
+        let leaves_tuning = |b: &Binding| matches!(b.action, Action::ToggleTuning | Action::Back);
+        let contexts: [&dyn Fn(&Binding) -> bool; 4] = [
+            &|b| !matches!(b.group, BindingGroup::Menu | BindingGroup::Photo | BindingGroup::Tuning),
+            &|b| b.group == BindingGroup::Menu,
+            &|b| b.group == BindingGroup::Photo || leaves_photo(b),
+            &|b| b.group == BindingGroup::Tuning || leaves_tuning(b),
+        ];
+        for context in contexts {
+            let mut seen = Vec::new();
+            for binding in bindings.bindings.iter().filter(|b| context(b)) {
+                for key in &binding.keys {
+                    assert!(!seen.contains(key), "{:?} is bound twice", key);
+                    seen.push(*key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_bound_action_can_be_logged() {
+        let bindings = InputBindings::default();
+        for binding in &bindings.bindings {
+            assert_eq!(Action::from_name(&binding.action.name()), Some(binding.action));
+        }
+
+        let frame = InputFrame { held: vec![Action::OrbitLeft, Action::ZoomIn], pressed: vec![(Action::Warp, 2)] };
+        assert_eq!(frame.to_line(), "OrbitLeft ZoomIn +Warp:2");
+        assert_eq!(InputFrame::parse(&frame.to_line()), Ok(frame));
+        assert_eq!(InputFrame::parse(""), Ok(InputFrame::default()));
+        assert!(InputFrame::parse("+Warp").is_err());
+    }
+
+    #[test]
+    fn key_labels_are_readable() {
+        let bindings = InputBindings::default();
+        let label = |action| bindings.binding(action).unwrap().key_label();
+        assert_eq!(label(Action::Warp), "1-9");
+        assert_eq!(label(Action::Help), "H/F1");
+        assert_eq!(label(Action::SpeedUp), "]");
+        assert_eq!(label(Action::SelectNext), "TAB");
+        assert_eq!(label(Action::TuneFine), "SHIFT");
+    }
+}
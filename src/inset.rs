@@ -0,0 +1,91 @@
+use nalgebra_glm::{Vec3, Mat4};
+use crate::body::CelestialBody;
+use crate::camera::{Camera, look_at_matrix};
+use crate::framebuffer::Viewport;
+use crate::geometry::normalize_or;
+
+// Size of the picture-in-picture inset on screen, and how much smaller it is
+// rendered before being stretched up to that
+pub const INSET_WIDTH: usize = 200;
+pub const INSET_HEIGHT: usize = 150;
+pub const INSET_DOWNSCALE: usize = 2;
+// Gap to the edge of the main view; the bottom one leaves room for the
+// warp readout
+const INSET_MARGIN: usize = 10;
+const INSET_BOTTOM_MARGIN: usize = 60;
+// The target view zooms until the body spans this share of the inset's height
+const TARGET_FILL: f32 = 0.6;
+const MIN_TARGET_FOV: f32 = 0.01;
+const MAX_TARGET_FOV: f32 = 1.2;
+// Field of view of the rear view, same as the live view
+const REAR_FOV: f32 = std::f32::consts::PI / 3.0;
+
+// What the inset in the corner of the main view shows (B cycles)
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum InsetMode {
+    #[default]
+    Off,
+    // Straight back from the camera
+    RearView,
+    // From the camera, zoomed in on the selected body
+    TargetView,
+}
+
+impl InsetMode {
+    pub fn next(self) -> Self {
+        match self {
+            InsetMode::Off => InsetMode::RearView,
+            InsetMode::RearView => InsetMode::TargetView,
+            InsetMode::TargetView => InsetMode::Off,
+        }
+    }
+
+    // View matrix, field of view and label for the inset, None while it's off
+    pub fn view(self, camera: &Camera, target: &CelestialBody) -> Option<(Mat4, f32, String)> {
+        let eye = camera.position;
+        match self {
+            InsetMode::Off => None,
+            InsetMode::RearView => {
+                let behind = -normalize_or(camera.target - eye, -Vec3::z());
+                Some((look_at_matrix(eye, eye + behind, camera.up), REAR_FOV, "REAR".to_string()))
+            }
+            InsetMode::TargetView => {
+                let distance = (target.position - eye).magnitude();
+                let fov = 2.0 * (target.bounding_radius() / TARGET_FILL).atan2(distance);
+                let fov = fov.clamp(MIN_TARGET_FOV, MAX_TARGET_FOV);
+                Some((look_at_matrix(eye, target.position, camera.up), fov, target.name.to_uppercase()))
+            }
+        }
+    }
+}
+
+// Where the inset sits: the bottom-right corner of the main view
+pub fn inset_rect(main: Viewport) -> Viewport {
+    let width = INSET_WIDTH.min(main.width.saturating_sub(2 * INSET_MARGIN));
+    let height = INSET_HEIGHT.min(main.height.saturating_sub(INSET_MARGIN + INSET_BOTTOM_MARGIN));
+    Viewport {
+        x: main.x + main.width - width - INSET_MARGIN.min(main.width - width),
+        y: main.y + main.height - height - INSET_BOTTOM_MARGIN.min(main.height - height),
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inset_fits_in_the_corner_of_any_view() {
+        let rect = inset_rect(Viewport { x: 0, y: 0, width: 800, height: 600 });
+        assert_eq!(rect, Viewport { x: 590, y: 390, width: INSET_WIDTH, height: INSET_HEIGHT });
+
+        let half = Viewport { x: 401, y: 0, width: 399, height: 600 };
+        let rect = inset_rect(half);
+        assert!(rect.x + rect.width <= half.x + half.width && rect.x >= half.x);
+
+        let tiny = Viewport { x: 0, y: 0, width: 50, height: 40 };
+        let rect = inset_rect(tiny);
+        assert!(rect.x + rect.width <= 50 && rect.y + rect.height <= 40);
+    }
+}
@@ -0,0 +1,92 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+
+// Hill radius as a fraction of the orbit radius when masses aren't set
+const FALLBACK_HILL_FRACTION: f32 = 0.1;
+
+pub struct LagrangePoint {
+    pub name: &'static str,
+    pub position: Vec3,
+}
+
+// The five Lagrange points of a body and the primary it orbits, where they
+// are this frame. L1/L2 sit a Hill radius r * (m / 3M)^(1/3) inside/outside
+// the orbit, L3 on the far side, and L4/L5 60 degrees ahead of/behind the body.
+pub fn lagrange_points(primary: &CelestialBody, secondary: &CelestialBody) -> Option<[LagrangePoint; 5]> {
+    let offset = secondary.position - primary.position;
+    let radius = offset.magnitude();
+    if radius <= f32::EPSILON {
+        return None;
+    }
+    let outward = offset / radius;
+
+    let mass_ratio = if primary.mass > 0.0 && secondary.mass > 0.0 {
+        Some(secondary.mass / primary.mass)
+    } else {
+        None
+    };
+    let hill = match mass_ratio {
+        Some(ratio) => radius * (ratio / 3.0).cbrt(),
+        None => radius * FALLBACK_HILL_FRACTION,
+    };
+    // L3 is pushed out very slightly by the secondary's pull
+    let l3_radius = radius * (1.0 + 5.0 / 12.0 * mass_ratio.unwrap_or(0.0));
+
+    // Direction of travel, kept in the orbit plane and perpendicular to the
+    // sun-planet line
+    let step = 0.01 * secondary.orbit_direction();
+    let travel = secondary.orbit_offset(secondary.orbital_angle + step) - secondary.orbit_offset(secondary.orbital_angle);
+    let ahead = (travel - outward * outward.dot(&travel)).try_normalize(f32::EPSILON)?;
+    let (sin_60, cos_60) = 60f32.to_radians().sin_cos();
+
+    Some([
+        LagrangePoint { name: "L1", position: secondary.position - outward * hill },
+        LagrangePoint { name: "L2", position: secondary.position + outward * hill },
+        LagrangePoint { name: "L3", position: primary.position - outward * l3_radius },
+        LagrangePoint { name: "L4", position: primary.position + (outward * cos_60 + ahead * sin_60) * radius },
+        LagrangePoint { name: "L5", position: primary.position + (outward * cos_60 - ahead * sin_60) * radius },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::ShaderType;
+
+    fn sun_and_planet(planet_mass: f32) -> (CelestialBody, CelestialBody) {
+        let mut sun = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 1.0, Color::from_hex(0xFFFFFF));
+        sun.mass = 1000.0;
+        let mut planet = CelestialBody::new_planet(
+            "Planet".to_string(), Vec::new(), Vec3::zeros(), 10.0, 0.5, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+        );
+        planet.mass = planet_mass;
+        planet.update(0.7);
+        // Root body with a fixed center, so its local position is the world one
+        planet.position = planet.local_position;
+        (sun, planet)
+    }
+
+    #[test]
+    fn l4_and_l5_form_equilateral_triangles() {
+        let (sun, planet) = sun_and_planet(3.0);
+        let points = lagrange_points(&sun, &planet).unwrap();
+        for point in &points[3..] {
+            assert!((point.position.magnitude() - 10.0).abs() < 1e-3);
+            assert!(((point.position - planet.position).magnitude() - 10.0).abs() < 1e-3);
+        }
+        // L4 leads the planet along its (counter-clockwise) orbit
+        let ahead = planet.orbit_offset(planet.orbital_angle + 0.1) - planet.position;
+        assert!((points[3].position - planet.position).dot(&ahead) > 0.0);
+    }
+
+    #[test]
+    fn l1_and_l2_use_the_hill_radius() {
+        let (sun, planet) = sun_and_planet(3.0);
+        let points = lagrange_points(&sun, &planet).unwrap();
+        // (3 / 3000)^(1/3) = 0.1 of the orbit radius
+        assert!((points[0].position.magnitude() - 9.0).abs() < 1e-3);
+        assert!((points[1].position.magnitude() - 11.0).abs() < 1e-3);
+        assert!((points[2].position + planet.position).magnitude() < 0.02);
+    }
+}
@@ -0,0 +1,220 @@
+use nalgebra_glm::{Mat4, Vec3, Vec4, dot};
+use serde::Deserialize;
+use crate::body::CelestialBody;
+
+// Constant, linear and quadratic falloff of point lights that don't give
+// their own
+pub const DEFAULT_ATTENUATION: [f32; 3] = [1.0, 0.0001, 0.000001];
+
+// How a star's light spreads
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    // From the star's position, dimming as 1 / (constant + linear * d +
+    // quadratic * d^2) with the distance d
+    Point { attenuation: [f32; 3] },
+    // Parallel rays going `direction` (unit length) everywhere, undimmed
+    Directional { direction: Vec3 },
+}
+
+impl Default for LightKind {
+    fn default() -> Self {
+        LightKind::Point { attenuation: DEFAULT_ATTENUATION }
+    }
+}
+
+// A light; every star in the scene emits one
+#[derive(Clone, Copy)]
+pub struct Light {
+    pub position: Vec3,
+    // Per-channel color in the 0-1 range
+    pub color: Vec3,
+    pub intensity: f32,
+    // Size of the star, which softens the edges of eclipse shadows
+    pub radius: f32,
+    pub kind: LightKind,
+}
+
+impl Light {
+    pub fn from_star(star: &CelestialBody) -> Self {
+        let hex = star.light_color;
+        Self {
+            position: star.position,
+            color: Vec3::new(
+                ((hex >> 16) & 0xFF) as f32 / 255.0,
+                ((hex >> 8) & 0xFF) as f32 / 255.0,
+                (hex & 0xFF) as f32 / 255.0,
+            ),
+            intensity: star.luminosity,
+            radius: star.bounding_radius(),
+            kind: star.light_kind,
+        }
+    }
+
+    // Unit direction from `point` toward the light and the share of its
+    // intensity that gets there. None on top of a point light
+    pub fn incoming(&self, point: Vec3) -> Option<(Vec3, f32)> {
+        match self.kind {
+            LightKind::Point { attenuation: [constant, linear, quadratic] } => {
+                let to_light = self.position - point;
+                let distance = to_light.magnitude();
+                if distance <= f32::EPSILON {
+                    return None;
+                }
+                Some((to_light / distance, 1.0 / (constant + linear * distance + quadratic * distance * distance)))
+            }
+            LightKind::Directional { direction } => Some((-direction, 1.0)),
+        }
+    }
+}
+
+// [ambient] section of the scene file: light that reaches every surface
+// from all around, night sides included. None unless the scene asks
+#[derive(Deserialize, Clone, Copy)]
+pub struct AmbientSettings {
+    #[serde(default = "default_ambient_color")]
+    pub color: u32,
+    #[serde(default)]
+    pub intensity: f32,
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self { color: default_ambient_color(), intensity: 0.0 }
+    }
+}
+
+fn default_ambient_color() -> u32 {
+    0xFFFFFF
+}
+
+impl AmbientSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.intensity) {
+            return Err("ambient intensity must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+
+    // Per-channel light added to what the lights give
+    pub fn light(&self) -> Vec3 {
+        let channel = |shift: u32| ((self.color >> shift) & 0xFF) as f32 / 255.0;
+        Vec3::new(channel(16), channel(8), channel(0)) * self.intensity
+    }
+}
+
+// Lights for this frame, one per star at its current position
+pub fn collect_lights(bodies: &[CelestialBody]) -> Vec<Light> {
+    bodies.iter()
+        .filter(|body| body.is_star())
+        .map(Light::from_star)
+        .collect()
+}
+
+// How smooth lit surfaces look: one intensity per triangle, one per corner
+// blended across it (Gouraud), or one per pixel from the blended normal
+// (Phong). Only meshes with real vertex normals are shaded smooth. Bodies
+// with bump mapping are lit per pixel in every mode, so it doesn't change
+// them
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingModel {
+    Flat,
+    Gouraud,
+    Phong,
+}
+
+impl ShadingModel {
+    pub fn next(self) -> Self {
+        match self {
+            ShadingModel::Flat => ShadingModel::Gouraud,
+            ShadingModel::Gouraud => ShadingModel::Phong,
+            ShadingModel::Phong => ShadingModel::Flat,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ShadingModel::Flat => "Flat shading",
+            ShadingModel::Gouraud => "Gouraud shading",
+            ShadingModel::Phong => "Phong shading",
+        }
+    }
+}
+
+// Diffuse light reaching a surface, summed over every light. Each star gets
+// its own terminator, so two stars at an angle give two overlapping ones.
+pub fn shade(lights: &[Light], point: Vec3, normal: Vec3) -> Vec3 {
+    shade_bumped(lights, point, normal, normal)
+}
+
+// shade() with the diffuse term taken from a `bumped` normal. The surface's
+// own normal still decides what's on the night side, so bumps can't catch
+// light past the terminator
+pub fn shade_bumped(lights: &[Light], point: Vec3, normal: Vec3, bumped: Vec3) -> Vec3 {
+    lights.iter().fold(Vec3::zeros(), |total, light| {
+        let Some((direction, attenuation)) = light.incoming(point) else {
+            return total;
+        };
+        if dot(&normal, &direction) <= 0.0 {
+            return total;
+        }
+        let diffuse = dot(&bumped, &direction).max(0.0);
+        total + light.color * (diffuse * attenuation * light.intensity)
+    })
+}
+
+// Latitude (radians) where the light a body gets most of stands overhead,
+// north of its equator when positive. The model's +Y is the spin axis, so it
+// follows from the axial tilt and where the body is along its orbit, and
+// with it the seasons. 0 with no lights
+pub fn subsolar_latitude(model_matrix: &Mat4, lights: &[Light]) -> f32 {
+    let center = (model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0)).xyz();
+    let Some(pole) = (model_matrix * Vec4::new(0.0, 1.0, 0.0, 0.0)).xyz().try_normalize(f32::EPSILON) else { return 0.0 };
+    lights.iter()
+        .filter_map(|light| light.incoming(center).map(|(to_light, attenuation)| (to_light, light.intensity * attenuation)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0.0, |(to_light, _)| dot(&pole, &to_light).clamp(-1.0, 1.0).asin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_graph::Transform;
+
+    #[test]
+    fn the_sun_stands_over_the_tropic_of_the_summer_hemisphere() {
+        let sun = Light { position: Vec3::zeros(), color: Vec3::repeat(1.0), intensity: 1.0, radius: 0.0, kind: LightKind::default() };
+        let tilt = 23.4f32.to_radians();
+        // Tilted about Z, so the north pole leans toward -X
+        let at = |position: Vec3, spin: f32| subsolar_latitude(&Transform::from_euler(position, Vec3::new(0.0, spin, tilt), 5.0).matrix(), &[sun]);
+        // Northern summer on the +X side of the orbit, winter opposite,
+        // equinoxes in between, whichever way the body has spun
+        for spin in [0.0, 1.0, 4.0] {
+            assert!((at(Vec3::new(100.0, 0.0, 0.0), spin) - tilt).abs() < 1e-4);
+            assert!((at(Vec3::new(-100.0, 0.0, 0.0), spin) + tilt).abs() < 1e-4);
+            assert!(at(Vec3::new(0.0, 0.0, 100.0), spin).abs() < 1e-4);
+        }
+        // The brightest light wins, and no light is no season
+        let far = Light { position: Vec3::new(0.0, 5000.0, 100.0), ..sun };
+        assert!((subsolar_latitude(&Transform::from_euler(Vec3::new(0.0, 0.0, 100.0), Vec3::zeros(), 1.0).matrix(), &[far, sun])).abs() < 1e-4);
+        assert_eq!(subsolar_latitude(&Mat4::identity(), &[]), 0.0);
+    }
+
+    #[test]
+    fn directional_lights_keep_their_strength_and_ambient_reaches_everywhere() {
+        let beam = Light { position: Vec3::zeros(), color: Vec3::repeat(1.0), intensity: 1.0, radius: 0.0, kind: LightKind::Directional { direction: -Vec3::x() } };
+        // Lit the same near and far, dark facing away
+        let near = shade(&[beam], Vec3::new(10.0, 0.0, 0.0), Vec3::x());
+        let far = shade(&[beam], Vec3::new(1.0e5, 0.0, 0.0), Vec3::x());
+        assert_eq!(near, Vec3::repeat(1.0));
+        assert_eq!(near, far);
+        assert_eq!(shade(&[beam], Vec3::new(10.0, 0.0, 0.0), -Vec3::x()), Vec3::zeros());
+        // Point lights still fall off
+        let point = Light { kind: LightKind::default(), ..beam };
+        assert!(shade(&[point], Vec3::new(1000.0, 0.0, 0.0), -Vec3::x()).x < 0.5);
+
+        assert_eq!(AmbientSettings::default().light(), Vec3::zeros());
+        let dim_blue = AmbientSettings { color: 0x0000FF, intensity: 0.2 };
+        assert_eq!(dim_blue.light(), Vec3::new(0.0, 0.0, 0.2));
+        assert!(AmbientSettings { intensity: 1.5, ..dim_blue }.validate().is_err());
+    }
+}
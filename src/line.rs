@@ -1,8 +1,10 @@
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::color::Color;
+use crate::framebuffer::Viewport;
 
-pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
+// Fragments along the line from a to b, the ones outside `viewport` left out
+pub fn line(a: &Vertex, b: &Vertex, viewport: &Viewport) -> Vec<Fragment> {
     let mut fragments = Vec::new();
 
     let start = a.transformed_position;
@@ -23,7 +25,9 @@ pub fn line(a: &Vertex, b: &Vertex) -> Vec<Fragment> {
 
     loop {
         let z = start.z + (end.z - start.z) * (x0 - start.x as i32) as f32 / (end.x - start.x) as f32;
-        fragments.push(Fragment::new(x0 as f32, y0 as f32, Color::new(255, 255, 255), z));
+        if x0 >= 0 && y0 >= 0 && viewport.contains(x0 as usize, y0 as usize) {
+            fragments.push(Fragment::new(x0 as f32, y0 as f32, Color::new(255, 255, 255), z));
+        }
 
         if x0 == x1 && y0 == y1 { break; }
 
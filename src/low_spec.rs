@@ -0,0 +1,198 @@
+use serde::Deserialize;
+use crate::impacts::ImpactSettings;
+use crate::light::ShadingModel;
+use crate::sky::{SkyMode, SkySettings};
+
+// Each new draw time counts this much towards the readout, so it settles
+// in a second or so instead of flickering
+const TIMING_SMOOTHING: f32 = 0.1;
+
+// [low_spec] section of the scene file: every cheap path at once, for slow
+// machines. --low-spec or `enabled` starts in it, the pause menu switches it
+#[derive(Deserialize, Clone, Copy)]
+pub struct LowSpecSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    // The scene is drawn this many times smaller each way and stretched back
+    // up, overlays and the HUD stay sharp
+    #[serde(default = "default_render_divisor")]
+    pub render_divisor: usize,
+    // Bodies turn into dots, and point bodies stay dots, until this many
+    // times bigger on screen than usual
+    #[serde(default = "default_lod_scale")]
+    pub lod_scale: f32,
+    // Bodies farther than this from the camera are shaded flat: no terrain
+    // bumps, textures or eclipses
+    #[serde(default = "default_flat_distance")]
+    pub flat_distance: f32,
+    // Share of the random asteroid impacts still rolled
+    #[serde(default = "default_impact_share")]
+    pub impact_share: f32,
+}
+
+impl Default for LowSpecSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            render_divisor: default_render_divisor(),
+            lod_scale: default_lod_scale(),
+            flat_distance: default_flat_distance(),
+            impact_share: default_impact_share(),
+        }
+    }
+}
+
+fn default_render_divisor() -> usize {
+    2
+}
+
+fn default_lod_scale() -> f32 {
+    4.0
+}
+
+fn default_flat_distance() -> f32 {
+    300.0
+}
+
+fn default_impact_share() -> f32 {
+    0.25
+}
+
+impl LowSpecSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=8).contains(&self.render_divisor) {
+            return Err("low_spec needs 1 <= render_divisor <= 8".to_string());
+        }
+        if !(self.lod_scale >= 1.0 && self.flat_distance >= 0.0) {
+            return Err("low_spec needs lod_scale >= 1 and a flat_distance of at least 0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.impact_share) {
+            return Err("low_spec needs 0 <= impact_share <= 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+// How finely draw_scene draws the bodies
+#[derive(Clone, Copy)]
+pub struct Detail {
+    // Stars and planets colored per pixel, or per vertex: cheaper, but only
+    // as detailed as the meshes
+    pub per_pixel: bool,
+    // Multiplies the level of detail thresholds
+    pub lod_scale: f32,
+    // See LowSpecSettings
+    pub flat_distance: f32,
+}
+
+impl Detail {
+    pub const FULL: Detail = Detail { per_pixel: true, lod_scale: 1.0, flat_distance: f32::INFINITY };
+}
+
+pub struct LowSpec {
+    pub settings: LowSpecSettings,
+    pub active: bool,
+    // Smoothed milliseconds to draw the scene, with it off and on, for the
+    // pause menu's before and after
+    draw_ms: [Option<f32>; 2],
+}
+
+impl LowSpec {
+    pub fn new(settings: LowSpecSettings, forced: bool) -> Self {
+        Self { settings, active: forced || settings.enabled, draw_ms: [None; 2] }
+    }
+
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+    }
+
+    pub fn detail(&self) -> Detail {
+        if !self.active {
+            return Detail::FULL;
+        }
+        Detail { per_pixel: false, lod_scale: self.settings.lod_scale, flat_distance: self.settings.flat_distance }
+    }
+
+    // None draws the scene at full size
+    pub fn render_divisor(&self) -> Option<usize> {
+        self.active.then_some(self.settings.render_divisor)
+    }
+
+    // Motion blur, lensing, heat shimmer, aberration, filters and grading
+    pub fn post_processing(&self) -> bool {
+        !self.active
+    }
+
+    // The live sky is swapped for the baked dome; no sky stays no sky
+    pub fn sky(&self, settings: SkySettings) -> SkySettings {
+        match settings.mode {
+            SkyMode::Live if self.active => settings.with_mode(Some(SkyMode::Baked)),
+            _ => settings,
+        }
+    }
+
+    // One light per triangle, whatever was picked
+    pub fn shading(&self, shading: ShadingModel) -> ShadingModel {
+        if self.active { ShadingModel::Flat } else { shading }
+    }
+
+    pub fn impacts(&self, settings: ImpactSettings) -> ImpactSettings {
+        let share = if self.active { self.settings.impact_share } else { 1.0 };
+        ImpactSettings { chance_per_second: settings.chance_per_second * share, ..settings }
+    }
+
+    pub fn record(&mut self, seconds: f32) {
+        let ms = seconds * 1000.0;
+        let slot = &mut self.draw_ms[self.active as usize];
+        *slot = Some(slot.map_or(ms, |old| old + (ms - old) * TIMING_SMOOTHING));
+    }
+
+    // Draw time now, and the other way once it has been tried
+    pub fn readout(&self) -> String {
+        let now = self.draw_ms[self.active as usize].map_or("-".to_string(), |ms| format!("{:.1} ms", ms));
+        match self.draw_ms[!self.active as usize] {
+            Some(ms) => format!("Scene drawn in {}, {:.1} ms with it {}", now, ms, if self.active { "off" } else { "on" }),
+            None => format!("Scene drawn in {}", now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switches_every_cheap_path_together() {
+        let mut low_spec = LowSpec::new(LowSpecSettings::default(), false);
+        assert!(low_spec.post_processing() && low_spec.render_divisor().is_none());
+        assert_eq!(low_spec.detail().flat_distance, f32::INFINITY);
+        let live = SkySettings { mode: SkyMode::Live, ..Default::default() };
+        assert!(low_spec.sky(live) == live);
+        assert_eq!(low_spec.shading(ShadingModel::Phong), ShadingModel::Phong);
+
+        low_spec.record(0.010);
+        assert_eq!(low_spec.readout(), "Scene drawn in 10.0 ms");
+        low_spec.toggle();
+        let detail = low_spec.detail();
+        assert!(!detail.per_pixel && detail.lod_scale == 4.0 && detail.flat_distance == 300.0);
+        assert_eq!(low_spec.render_divisor(), Some(2));
+        assert!(!low_spec.post_processing());
+        assert!(low_spec.sky(live).mode == SkyMode::Baked);
+        let off = SkySettings { mode: SkyMode::Off, ..Default::default() };
+        assert!(low_spec.sky(off).mode == SkyMode::Off);
+        assert_eq!(low_spec.shading(ShadingModel::Phong), ShadingModel::Flat);
+        let impacts = low_spec.impacts(ImpactSettings { chance_per_second: 0.02, ..Default::default() });
+        assert!((impacts.chance_per_second - 0.005).abs() < 1e-6);
+
+        // Both ways show once each has been drawn, smoothed towards new times
+        assert_eq!(low_spec.readout(), "Scene drawn in -, 10.0 ms with it off");
+        low_spec.record(0.004);
+        low_spec.record(0.014);
+        assert_eq!(low_spec.readout(), "Scene drawn in 5.0 ms, 10.0 ms with it off");
+
+        assert!(LowSpec::new(LowSpecSettings { enabled: true, ..Default::default() }, false).active);
+        assert!(LowSpecSettings { render_divisor: 0, ..Default::default() }.validate().is_err());
+        assert!(LowSpecSettings { impact_share: 1.5, ..Default::default() }.validate().is_err());
+        assert!(LowSpecSettings::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::Path;
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+
+// .cube tables larger than this per side are refused, 64 is already a lot
+const MAX_LUT_SIZE: usize = 128;
+
+// [grading] section of the scene file
+#[derive(Deserialize, Clone, Default)]
+pub struct GradingSettings {
+    // .cube files that F11 cycles through, after "none"
+    #[serde(default)]
+    pub luts: Vec<String>,
+    // Index into `luts` to start with, no grading when left out
+    #[serde(default)]
+    pub start: Option<usize>,
+}
+
+// A 3D color lookup table from an Adobe .cube file
+pub struct Lut {
+    pub name: String,
+    strides: [usize; 3],
+    // Output colors as flat r, g, b floats, red varying fastest then green
+    // then blue, as in the file
+    table: Vec<f32>,
+    // For each 8-bit channel value: the offset into the table of the entry
+    // below it along that channel, and how far toward the next one it sits
+    axes: [[(usize, f32); 256]; 3],
+}
+
+impl Lut {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("Failed to read LUT {}: {}", path, e))?;
+        let name = Path::new(path).file_stem().map_or(path.to_string(), |stem| stem.to_string_lossy().into_owned());
+        Lut::parse(&name, &source).map_err(|e| format!("Bad LUT {}: {}", path, e))
+    }
+
+    pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut domain_min = Vec3::zeros();
+        let mut domain_max = Vec3::new(1.0, 1.0, 1.0);
+        let mut table = Vec::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let triple = |words: std::str::SplitWhitespace| -> Result<Vec3, String> {
+                let values: Vec<f32> = words.map(str::parse).collect::<Result<_, _>>().map_err(|_| error("expected three numbers"))?;
+                match values[..] {
+                    [r, g, b] => Ok(Vec3::new(r, g, b)),
+                    _ => Err(error("expected three numbers")),
+                }
+            };
+            match keyword {
+                "TITLE" => {}
+                "LUT_1D_SIZE" => return Err(error("1D LUTs are not supported")),
+                "LUT_3D_SIZE" => {
+                    let value: usize = words.next().and_then(|word| word.parse().ok()).ok_or_else(|| error("bad LUT_3D_SIZE"))?;
+                    if !(2..=MAX_LUT_SIZE).contains(&value) {
+                        return Err(error(&format!("LUT_3D_SIZE must be 2-{}", MAX_LUT_SIZE)));
+                    }
+                    size = Some(value);
+                }
+                "DOMAIN_MIN" => domain_min = triple(words)?,
+                "DOMAIN_MAX" => domain_max = triple(words)?,
+                _ if keyword.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => {
+                    table.push(triple(line.split_whitespace())?);
+                }
+                _ => return Err(error(&format!("unknown keyword {}", keyword))),
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        if table.len() != size * size * size {
+            return Err(format!("expected {} entries, found {}", size * size * size, table.len()));
+        }
+        if (0..3).any(|channel| domain_max[channel] <= domain_min[channel]) {
+            return Err("DOMAIN_MAX must be above DOMAIN_MIN".to_string());
+        }
+
+        // Table entries one step apart along red, green and blue
+        let strides = [3, 3 * size, 3 * size * size];
+        let mut axes = [[(0, 0.0); 256]; 3];
+        for (channel, axis) in axes.iter_mut().enumerate() {
+            for (value, entry) in axis.iter_mut().enumerate() {
+                let input = value as f32 / 255.0;
+                let position = ((input - domain_min[channel]) / (domain_max[channel] - domain_min[channel])).clamp(0.0, 1.0) * (size - 1) as f32;
+                let index = (position.floor() as usize).min(size - 2);
+                *entry = (index * strides[channel], position - index as f32);
+            }
+        }
+        let table = table.iter().flat_map(|color: &Vec3| [color.x, color.y, color.z]).collect();
+        Ok(Lut { name: name.to_string(), strides, table, axes })
+    }
+
+    // Trilinear lookup of one 0RGB pixel. Runs for every pixel of every
+    // frame, so it works on the flat table directly
+    pub fn grade(&self, pixel: u32) -> u32 {
+        let (r, fr) = self.axes[0][(pixel >> 16 & 0xFF) as usize];
+        let (g, fg) = self.axes[1][(pixel >> 8 & 0xFF) as usize];
+        let (b, fb) = self.axes[2][(pixel & 0xFF) as usize];
+        let [step_r, step_g, step_b] = self.strides;
+        // The eight entries around the color, all within this slice
+        let cell = &self.table[r + g + b..r + g + b + step_b + step_g + step_r + 3];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let mut result = 0;
+        for channel in 0..3 {
+            let at = |offset: usize| cell[offset + channel];
+            let near = lerp(lerp(at(0), at(step_r), fr), lerp(at(step_g), at(step_g + step_r), fr), fg);
+            let far = lerp(lerp(at(step_b), at(step_b + step_r), fr), lerp(at(step_b + step_g), at(step_b + step_g + step_r), fr), fg);
+            // + 0.5 and truncating rounds without a call to round()
+            let value = (lerp(near, far, fb).clamp(0.0, 1.0) * 255.0 + 0.5) as u32;
+            result = result << 8 | value;
+        }
+        result
+    }
+
+    pub fn apply(&self, pixels: &mut [u32]) {
+        // Runs of one color (mostly empty space) are only looked up once
+        let mut last: Option<(u32, u32)> = None;
+        for pixel in pixels.iter_mut() {
+            let graded = match last {
+                Some((input, output)) if input == *pixel => output,
+                _ => self.grade(*pixel),
+            };
+            last = Some((*pixel, graded));
+            *pixel = graded;
+        }
+    }
+}
+
+// The loaded LUTs and which one is in use (F11 cycles, starting with none)
+pub struct ColorGrading {
+    luts: Vec<Lut>,
+    active: Option<usize>,
+}
+
+impl ColorGrading {
+    // Files that fail to load are left out; their errors come back to be
+    // shown instead of stopping the program
+    pub fn load(settings: &GradingSettings) -> (Self, Vec<String>) {
+        let mut luts = Vec::new();
+        let mut errors = Vec::new();
+        for path in &settings.luts {
+            match Lut::load(path) {
+                Ok(lut) => luts.push(lut),
+                Err(err) => errors.push(err),
+            }
+        }
+        let active = settings.start.filter(|&index| index < luts.len());
+        (ColorGrading { luts, active }, errors)
+    }
+
+    pub fn next(&mut self) {
+        self.active = match self.active {
+            None if !self.luts.is_empty() => Some(0),
+            Some(index) if index + 1 < self.luts.len() => Some(index + 1),
+            _ => None,
+        };
+    }
+
+    pub fn label(&self) -> &str {
+        self.active.map_or("none", |index| self.luts[index].name.as_str())
+    }
+
+    pub fn apply(&self, pixels: &mut [u32]) {
+        if let Some(index) = self.active {
+            self.luts[index].apply(pixels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn identity_cube(size: usize) -> String {
+        let mut source = format!("TITLE \"identity\"\n# neutral\nLUT_3D_SIZE {}\n", size);
+        let step = |i: usize| i as f32 / (size - 1) as f32;
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    source += &format!("{:.6} {:.6} {:.6}\n", step(r), step(g), step(b));
+                }
+            }
+        }
+        source
+    }
+
+    #[test]
+    fn identity_lut_leaves_colors_alone() {
+        let lut = Lut::parse("identity", &identity_cube(5)).unwrap();
+        for value in (0..=255).step_by(3) {
+            let color = Color::new(value as u8, (255 - value) as u8, (value * 7 % 256) as u8);
+            let graded = Color::from_hex(lut.grade(color.to_pixel()));
+            for (a, b) in [(color.r, graded.r), (color.g, graded.g), (color.b, graded.b)] {
+                assert!(a.abs_diff(b) <= 1, "{} came out as {}", color, graded);
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_luts_are_refused() {
+        let error = |source: &str| Lut::parse("bad", source).err().unwrap_or_default();
+        let short = identity_cube(3).replace("1.000000 1.000000 1.000000\n", "");
+        assert!(error(&short).contains("expected 27 entries"));
+        assert!(error("LUT_3D_SIZE 2\n0 0 zero\n").contains("line 2"));
+        assert!(error("0 0 0\n").contains("missing LUT_3D_SIZE"));
+        assert!(error("LUT_1D_SIZE 16\n").contains("not supported"));
+    }
+}
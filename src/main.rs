@@ -1,7 +1,7 @@
 use nalgebra_glm::{Vec3, Mat4, perspective, identity};
-use minifb::{Key, Window, WindowOptions};
-use std::time::Duration;
+use rayon::prelude::*;
 use std::f32::consts::PI;
+use std::time::Instant;
 
 mod framebuffer;
 mod triangle;
@@ -13,14 +13,177 @@ mod fragment;
 mod shaders;
 mod skybox;
 mod camera;
+mod body;
+mod scene;
+mod text;
+mod hud;
+mod nbody;
+mod overlay;
+mod light;
+mod lagrange;
+mod trail;
+mod units;
+mod scale;
+mod scene_graph;
+mod autopilot;
+mod spaceship;
+mod flight;
+mod warp;
+mod input;
+mod menu;
+mod capture;
+mod pacing;
+mod save;
+mod options;
+mod replay;
+mod geometry;
+mod photo;
+mod stereo;
+mod exposure;
+mod motion_blur;
+mod depth_of_field;
+mod distortion;
+mod filters;
+mod lut;
+mod retro;
+mod sky;
+mod star_catalog;
+mod impacts;
+mod eruptions;
+mod objectives;
+mod trajectory;
+mod hohmann;
+mod alignment;
+mod ephemeris;
+mod heat;
+mod sun_haze;
+mod cruise;
+mod sun_visibility;
+mod eclipse;
+mod surface;
+mod low_spec;
+mod clipping;
+mod terrain;
+mod texture;
+mod shader_params;
+mod inset;
+mod file_watch;
+mod tuning;
+mod thumbnails;
+mod outline;
+mod backend;
+mod terminal;
+mod benchmark;
+mod docking;
+mod portal;
+mod rng;
+#[cfg(debug_assertions)]
+mod draw_checks;
+#[cfg(test)]
+mod golden;
 
-use framebuffer::Framebuffer;
+use framebuffer::{Framebuffer, Tile, Viewport};
 use vertex::Vertex;
-use obj::Obj;
-use triangle::{triangle, triangle_with_uniforms};
-use shaders::{vertex_shader, fragment_shader};
-use skybox::Skybox;
-use camera::Camera;
+use triangle::{triangle, Setup, CullMode};
+use shaders::{vertex_shader, to_screen, fragment_shader};
+use clipping::clip_triangle;
+use camera::{Camera, DEFAULT_MIN_DISTANCE};
+use scene::{Scene, SimulationMode};
+use hud::{draw_info_panel, draw_heat_legend, draw_measure_panel, draw_objectives, draw_status_line, draw_proximity_warning, draw_heat_warning, draw_flight_strip, FlightReadout, draw_warp_status, draw_help, draw_pause_menu, draw_tuning_panel, draw_recording_indicator, draw_notice, draw_photo_controls, draw_viewport_labels, draw_inset_frame, draw_warp_thumbnails, draw_docking_prompt, draw_docked_menu, draw_alignment_prompt, Conditions};
+use overlay::{ScreenProjection, OrbitDisplay, draw_orbit, draw_label, draw_point, draw_marker, draw_trail, draw_measurement, draw_shield, draw_corona};
+use scale::ScaleTransition;
+use lagrange::lagrange_points;
+use trail::Trails;
+use color::{Color, UiColor, PaletteMode, daltonize};
+use body::CelestialBody;
+use light::{Light, ShadingModel, collect_lights, subsolar_latitude};
+use autopilot::Autopilot;
+use spaceship::{Spaceship, engine_flicker};
+use flight::FlightTelemetry;
+use hohmann::{TransferPlan, draw_transfer};
+use trajectory::{predict_path, draw_predicted_path};
+use heat::HeatSettings;
+use texture::SurfaceTexture;
+use shader_params::ShaderParams;
+use warp::{WarpSystem, WarpEnergy, WARP_DISTANCE_FACTOR};
+use input::{InputBindings, InputFrame, Action};
+use menu::{PauseMenu, MenuEntry};
+use capture::{Recorder, write_png, save_photo};
+use options::Options;
+use file_watch::FileWatcher;
+use tuning::ShaderTuner;
+use thumbnails::Thumbnails;
+use outline::{body_id, draw_selection_outline};
+use replay::{InputLog, InputReplay};
+use pacing::FrameClock;
+use backend::WINDOW_TITLE;
+use benchmark::{Benchmark, Stage};
+use photo::{PhotoMode, DEFAULT_FOV, PHOTO_SUPERSAMPLING};
+use stereo::{Eye, anaglyph};
+use exposure::{Exposure, log_average_luminance};
+use motion_blur::MotionBlur;
+use lut::ColorGrading;
+use impacts::Impacts;
+use eruptions::Eruptions;
+use rng::Seed;
+use sky::{Sky, SKY_DEPTH};
+use docking::{Docking, DockedEntry, dockable_station};
+use portal::{Portals, draw_lensing};
+use alignment::{Alignment, next_alignment};
+use sun_visibility::SunVisibility;
+use eclipse::{Occluder, occluders};
+use cruise::Cruise;
+use surface::{SurfaceView, day_length};
+use low_spec::{LowSpec, Detail};
+use objectives::Objectives;
+use depth_of_field::{depth_of_field, draw_focus_plane, focus_at};
+use distortion::chromatic_aberration;
+use inset::{InsetMode, inset_rect, INSET_DOWNSCALE};
+use save::{SaveState, BodyState, CameraState, ShipState, QUICKSAVE_PATH, format_hash};
+
+const MIN_TIME_SCALE: f32 = 0.125;
+const MAX_TIME_SCALE: f32 = 64.0;
+
+// Size in pixels of the glow drawn at each ship engine
+const ENGINE_GLOW_SIZE: i32 = 3;
+// Sparks off a damaged hull start this many pixels across and shrink away
+const SPARK_SIZE: i32 = 3;
+const SPARK_COLOR: u32 = 0xFFB040;
+
+// The camera stays this many bounding radii away from the body it follows,
+// and never closer than MIN_CAMERA_DISTANCE so the near plane doesn't cut in
+const COLLISION_MARGIN: f32 = 1.5;
+const MIN_CAMERA_DISTANCE: f32 = 25.0;
+
+// How long save/load messages stay up
+const NOTICE_SECONDS: f32 = 3.0;
+
+// Holding Escape this long quits even with the pause menu up
+const FORCE_QUIT_SECONDS: f32 = 2.0;
+
+// Split screen: the overview camera's distance from the star and angle from
+// straight above it, and the gap between the two views
+const OVERVIEW_DISTANCE: f32 = 1500.0;
+const OVERVIEW_ELEVATION: f32 = 0.5;
+const DIVIDER_WIDTH: usize = 2;
+
+// Near plane pushed out to prevent clipping issues, and brought right in
+// standing on a surface; far plane for the whole system
+const NEAR_PLANE: f32 = 10.0;
+const SURFACE_NEAR_PLANE: f32 = 0.05;
+const FAR_PLANE: f32 = 3000.0;
+
+// render() cuts the frame into squares this many pixels across, drawn one
+// per thread at a time
+const TILE_SIZE: usize = 32;
+
+// Meshes smaller than this on screen (radius in pixels) are drawn as a dot
+const LOD_MIN_PIXELS: f32 = 1.0;
+// Point bodies switch to their mesh once they'd be this big on screen
+const POINT_SWAP_PIXELS: f32 = 4.0;
+// Terrain bumps fade in as a body grows from the first to the second size on
+// screen (radius in pixels); any smaller and they'd only shimmer
+const BUMP_FADE_PIXELS: (f32, f32) = (20.0, 60.0);
 
 #[derive(Clone, Copy)]
 pub enum ShaderType {
@@ -28,262 +191,461 @@ pub enum ShaderType {
     Star,        // Sun shader with emission effects
     RockyPlanet, // Rocky planet with surface features
     GasGiant,    // Gas giant with atmospheric effects
+    Station,     // Artificial satellite with blinking nav lights
+    Portal,      // Swirling wormhole disc, see portal.rs
+    Ship { hull: u32, emissive: u32, damage: f32 }, // The player's ship, colors from its config
+    Heat { color: u32 }, // Flat temperature false color, see heat.rs
 }
 
-pub struct Uniforms {
-    model_matrix: Mat4,
+// What every draw of a pass shares: where it's seen from, the lights, the
+// time and how the pass is rendered. Made once per pass
+pub struct FrameUniforms<'a> {
     view_matrix: Mat4,
     projection_matrix: Mat4,
     viewport_matrix: Mat4,
-    light_position: Vec3,
-    is_light_source: bool,
-    shader_type: ShaderType,
+    lights: &'a [Light],
+    // Light every surface gets from all around, see AmbientSettings
+    ambient: Vec3,
     time: f32, // For animated effects
+    // Part of the framebuffer this pass draws into
+    viewport: Viewport,
+    // Brightness multiplier from the exposure, 1 leaves colors alone
+    exposure: f32,
+    // Copied from the projection, see ScreenProjection
+    snap_to_pixels: bool,
+    affine: bool,
+    // Stars and planets colored per pixel rather than per vertex, see Lighting
+    per_pixel: bool,
 }
 
-// Enhanced celestial body struct for multiple models
-struct CelestialBody {
-    name: String,
-    vertices: Vec<Vertex>,
-    position: Vec3,
-    rotation: Vec3,
-    scale: f32,
-    color: u32,
-    shader_type: ShaderType,  // New field for shader selection
-    // Orbital properties
-    orbital_center: Option<Vec3>,
-    orbital_radius: f32,
-    orbital_speed: f32,
-    orbital_angle: f32,
-    // Self rotation
-    rotation_speed: f32,
-    // Parent for moons
-    parent_index: Option<usize>,
+impl<'a> FrameUniforms<'a> {
+    fn new(projection: &ScreenProjection, lighting: Lighting<'a>, time: f32, viewport: Viewport) -> Self {
+        FrameUniforms {
+            view_matrix: projection.view_matrix,
+            projection_matrix: projection.projection_matrix,
+            viewport_matrix: projection.viewport_matrix,
+            lights: lighting.lights,
+            ambient: lighting.ambient,
+            time,
+            viewport,
+            exposure: lighting.exposure,
+            snap_to_pixels: projection.snap_to_pixels,
+            affine: projection.affine,
+            per_pixel: lighting.detail.per_pixel,
+        }
+    }
 }
 
-impl CelestialBody {
-    fn new_sun(name: String, vertices: Vec<Vertex>, position: Vec3, scale: f32, color: u32) -> Self {
-        Self {
-            name,
-            vertices,
-            position,
-            rotation: Vec3::new(0.0, 0.0, 0.0),
-            scale,
-            color,
-            shader_type: ShaderType::Star,  // Sun uses Star shader
-            orbital_center: None,
-            orbital_radius: 0.0,
-            orbital_speed: 0.0,
-            orbital_angle: 0.0,
-            rotation_speed: 0.1,
-            parent_index: None,
-        }
-    }
-
-    fn new_planet(
-        name: String,
-        vertices: Vec<Vertex>,
-        orbital_center: Vec3,
-        orbital_radius: f32,
-        orbital_speed: f32,
-        scale: f32,
-        color: u32,
-        shader_type: ShaderType,
-    ) -> Self {
-        Self {
-            name,
-            vertices,
-            position: Vec3::new(0.0, 0.0, 0.0), // Will be calculated
-            rotation: Vec3::new(0.0, 0.0, 0.0),
-            scale,
-            color,
-            shader_type,
-            orbital_center: Some(orbital_center),
-            orbital_radius,
-            orbital_speed,
-            orbital_angle: 0.0,
-            rotation_speed: 0.3,
-            parent_index: None,
-        }
-    }
-
-    fn new_moon(
-        name: String,
-        vertices: Vec<Vertex>,
-        parent_index: usize,
-        orbital_radius: f32,
-        orbital_speed: f32,
-        scale: f32,
-        color: u32,
-        shader_type: ShaderType,
-    ) -> Self {
-        Self {
-            name,
-            vertices,
-            position: Vec3::new(0.0, 0.0, 0.0), // Will be calculated
-            rotation: Vec3::new(0.0, 0.0, 0.0),
-            scale,
-            color,
-            shader_type,
-            orbital_center: None, // Will use parent position
-            orbital_radius,
-            orbital_speed,
-            orbital_angle: 0.0,
-            rotation_speed: 0.5,
-            parent_index: Some(parent_index),
-        }
-    }
-
-    fn update(&mut self, delta_time: f32, parent_positions: &[Vec3]) {
-        // Update orbital angle
-        self.orbital_angle += self.orbital_speed * delta_time;
-        
-        // Update rotation
-        self.rotation.y += self.rotation_speed * delta_time;
-
-        // Update position based on orbital mechanics
-        if let Some(center) = self.orbital_center {
-            // Planet orbiting the sun
-            self.position = Vec3::new(
-                center.x + self.orbital_radius * self.orbital_angle.cos(),
-                center.y,
-                center.z + self.orbital_radius * self.orbital_angle.sin(),
-            );
-        } else if let Some(parent_idx) = self.parent_index {
-            // Moon orbiting a planet
-            if parent_idx < parent_positions.len() {
-                let parent_pos = parent_positions[parent_idx];
-                self.position = Vec3::new(
-                    parent_pos.x + self.orbital_radius * self.orbital_angle.cos(),
-                    parent_pos.y,
-                    parent_pos.z + self.orbital_radius * self.orbital_angle.sin(),
-                );
-            }
+// One draw: the pass's shared state plus what's drawn and how. Starts as a
+// plain untextured mesh, the with_ methods add the rest
+pub struct Uniforms<'a> {
+    frame: &'a FrameUniforms<'a>,
+    model_matrix: Mat4,
+    shader_type: ShaderType,
+    // Impact craters, ash fields and erupting vents of the body being
+    // drawn, see CelestialBody
+    craters: &'a [Vec3],
+    ash: &'a [Vec3],
+    vents: &'a [(Vec3, f32)],
+    // Latitude the sun stands over (radians), which sets the season
+    season: f32,
+    // Strength of the terrain's bump mapping, 0 shades triangles flat
+    bump: f32,
+    // Albedo painted per pixel over the shader's colors
+    texture: Option<&'a SurfaceTexture>,
+    // Palette and pattern settings of the body's shader
+    params: ShaderParams,
+    // Bodies that may be eclipsing this one, see eclipse.rs
+    occluders: &'a [Occluder],
+    // Triangles skipped by the way they face, none unless the mesh is closed
+    cull_mode: CullMode,
+    // Flat unless the mesh has normals worth blending, see ShadingModel
+    shading: ShadingModel,
+    // Names the draw when a debug check fails, see draw_checks.rs
+    #[cfg(debug_assertions)]
+    label: &'a str,
+}
+
+impl<'a> Uniforms<'a> {
+    fn new(frame: &'a FrameUniforms<'a>, model_matrix: Mat4, shader_type: ShaderType) -> Self {
+        Uniforms {
+            frame, model_matrix, shader_type, craters: &[], ash: &[], vents: &[], season: 0.0, bump: 0.0, texture: None, params: ShaderParams::defaults(shader_type), occluders: &[], cull_mode: CullMode::None, shading: ShadingModel::Flat,
+            #[cfg(debug_assertions)]
+            label: "",
         }
     }
 
-    fn get_model_matrix(&self) -> Mat4 {
-        create_model_matrix(
-            self.position,
-            self.scale,
-            self.rotation,
-        )
+    fn with_craters(self, craters: &'a [Vec3]) -> Self {
+        Uniforms { craters, ..self }
+    }
+
+    fn with_ash(self, ash: &'a [Vec3]) -> Self {
+        Uniforms { ash, ..self }
+    }
+
+    fn with_vents(self, vents: &'a [(Vec3, f32)]) -> Self {
+        Uniforms { vents, ..self }
+    }
+
+    fn with_season(self, season: f32) -> Self {
+        Uniforms { season, ..self }
+    }
+
+    fn with_bump(self, bump: f32) -> Self {
+        Uniforms { bump, ..self }
+    }
+
+    fn with_texture(self, texture: Option<&'a SurfaceTexture>) -> Self {
+        Uniforms { texture, ..self }
+    }
+
+    fn with_params(self, params: ShaderParams) -> Self {
+        Uniforms { params, ..self }
+    }
+
+    fn with_occluders(self, occluders: &'a [Occluder]) -> Self {
+        Uniforms { occluders, ..self }
+    }
+
+    fn with_cull_mode(self, cull_mode: CullMode) -> Self {
+        Uniforms { cull_mode, ..self }
+    }
+
+    fn with_shading(self, shading: ShadingModel) -> Self {
+        Uniforms { shading, ..self }
+    }
+
+    #[cfg(debug_assertions)]
+    fn with_label(self, label: &'a str) -> Self {
+        Uniforms { label, ..self }
+    }
+
+    // Release builds don't check draws, so there's nothing to name
+    #[cfg(not(debug_assertions))]
+    fn with_label(self, _label: &'a str) -> Self {
+        self
     }
 }
 
-fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
-    let (sin_x, cos_x) = rotation.x.sin_cos();
-    let (sin_y, cos_y) = rotation.y.sin_cos();
-    let (sin_z, cos_z) = rotation.z.sin_cos();
-
-    let rotation_matrix_x = Mat4::new(
-        1.0,  0.0,    0.0,   0.0,
-        0.0,  cos_x, -sin_x, 0.0,
-        0.0,  sin_x,  cos_x, 0.0,
-        0.0,  0.0,    0.0,   1.0,
-    );
-
-    let rotation_matrix_y = Mat4::new(
-        cos_y,  0.0,  sin_y, 0.0,
-        0.0,    1.0,  0.0,   0.0,
-        -sin_y, 0.0,  cos_y, 0.0,
-        0.0,    0.0,  0.0,   1.0,
-    );
-
-    let rotation_matrix_z = Mat4::new(
-        cos_z, -sin_z, 0.0, 0.0,
-        sin_z,  cos_z, 0.0, 0.0,
-        0.0,    0.0,  1.0, 0.0,
-        0.0,    0.0,  0.0, 1.0,
-    );
-
-    let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
-
-    let transform_matrix = Mat4::new(
-        scale, 0.0,   0.0,   translation.x,
-        0.0,   scale, 0.0,   translation.y,
-        0.0,   0.0,   scale, translation.z,
-        0.0,   0.0,   0.0,   1.0,
-    );
-
-    transform_matrix * rotation_matrix
+// Lights of the scene and the exposure it's seen with, shared by every pass
+#[derive(Clone, Copy)]
+struct Lighting<'a> {
+    lights: &'a [Light],
+    ambient: Vec3,
+    exposure: f32,
+    // Temperature view, stars keep their own look
+    heat: Option<HeatSettings>,
+    // Per pixel or per vertex, and how far the level of detail goes
+    detail: Detail,
+    // For the bodies with smooth normals
+    shading: ShadingModel,
 }
 
 fn create_projection_matrix(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
-    perspective(fov_y, aspect, near, far)
+    // nalgebra-glm takes the aspect ratio first
+    perspective(aspect, fov_y, near, far)
 }
 
-fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
+// Maps normalized device coordinates onto the viewport's pixels
+fn create_viewport_matrix(viewport: Viewport) -> Mat4 {
+    let (width, height) = (viewport.width as f32, viewport.height as f32);
+    let (left, top) = (viewport.x as f32, viewport.y as f32);
     Mat4::new(
-        width / 2.0, 0.0,         0.0, width / 2.0,
-        0.0,         -height / 2.0, 0.0, height / 2.0,
+        width / 2.0, 0.0,         0.0, left + width / 2.0,
+        0.0,         -height / 2.0, 0.0, top + height / 2.0,
         0.0,         0.0,         1.0, 0.0,
         0.0,         0.0,         0.0, 1.0,
     )
 }
 
 fn render(framebuffer: &mut Framebuffer, uniforms: &Uniforms, vertex_array: &[Vertex]) {
+    #[cfg(debug_assertions)]
+    let check = draw_checks::DrawCheck::begin(framebuffer, uniforms);
+
     // Vertex Shader Stage
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
+    let transformed_vertices: Vec<Vertex> = vertex_array.par_iter().map(|vertex| vertex_shader(vertex, uniforms)).collect();
+
+    // Primitive Assembly Stage, clipped against the near plane and only
+    // then divided through and placed on screen, where the triangles facing
+    // the culled way are dropped
+    let triangles: Vec<[Vertex; 3]> = transformed_vertices
+        .par_chunks_exact(3)
+        .flat_map_iter(|corners| {
+            clip_triangle([corners[0].clone(), corners[1].clone(), corners[2].clone()])
+                .into_iter()
+                .map(|clipped| clipped.map(|vertex| to_screen(vertex, uniforms)))
+                .filter(|[v1, v2, v3]| !uniforms.cull_mode.culls(v1, v2, v3))
+        })
+        .collect();
+
+    // Rasterization and Fragment Processing Stages, a tile at a time. Each
+    // triangle is listed in every tile it covers, in order, and a tile draws
+    // its list straight into its own pixels, so a pixel gets its fragments
+    // in draw order however many threads share out the rows of tiles
+    let viewport = uniforms.frame.viewport;
+    let setups: Vec<Setup> = triangles.par_iter().filter_map(|[v1, v2, v3]| Setup::new(v1, v2, v3, &viewport, Some(uniforms))).collect();
+    if rayon::current_num_threads() > 1 {
+        // Each row of tiles bins its own triangles on whichever thread draws
+        // it, so nothing but the setups is done before the rows start
+        let columns = framebuffer.width.max(1).div_ceil(TILE_SIZE);
+        framebuffer.tile_rows(TILE_SIZE).into_par_iter().enumerate().for_each(|(row_index, mut row)| {
+            for (column, bin) in bin_row(&setups, row_index, columns).iter().enumerate().filter(|(_, bin)| !bin.is_empty()) {
+                draw_tile(&mut row.tile(column), bin.iter().map(|&index| &setups[index]), uniforms);
+            }
+        });
+    } else if let Some(row) = framebuffer.tile_rows(framebuffer.width.max(framebuffer.height)).first_mut() {
+        // Binning only pays for itself when tiles are shared out; one
+        // thread draws the whole frame as a single tile, in the same order
+        draw_tile(&mut row.tile(0), setups.iter(), uniforms);
     }
 
-    // Primitive Assembly Stage
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+    #[cfg(debug_assertions)]
+    check.finish(framebuffer);
+}
+
+// For each of the `columns` tiles in row `row`, the triangles that cover
+// some of it, by index and in order
+fn bin_row(setups: &[Setup], row: usize, columns: usize) -> Vec<Vec<usize>> {
+    let mut bins = vec![Vec::new(); columns];
+    for (index, setup) in setups.iter().enumerate() {
+        // Inside the viewport, so never negative
+        let (min_x, min_y, max_x, max_y) = setup.bounds();
+        if row < min_y as usize / TILE_SIZE || row > max_y as usize / TILE_SIZE {
+            continue;
         }
+        let spanned = min_x as usize / TILE_SIZE..=max_x as usize / TILE_SIZE;
+        // Nothing to skip when it's all in one tile
+        let single = min_y as usize / TILE_SIZE == max_y as usize / TILE_SIZE && spanned.start() == spanned.end();
+        for column in spanned {
+            let area = Viewport { x: column * TILE_SIZE, y: row * TILE_SIZE, width: TILE_SIZE, height: TILE_SIZE };
+            if single || setup.covers(&area) {
+                bins[column].push(index);
+            }
+        }
+    }
+    bins
+}
+
+// The fragments of a tile's triangles, shaded and written as they're made
+fn draw_tile<'a>(tile: &mut Tile, setups: impl Iterator<Item = &'a Setup<'a>>, uniforms: &Uniforms) {
+    let area = tile.area();
+    for setup in setups {
+        setup.rasterize(&area, |fragment| {
+            let processed_fragment = fragment_shader(fragment, uniforms);
+            // Negative and NaN floats cast to usize as 0, which would pile
+            // off-screen fragments up along the left and top edges
+            let (x, y) = (processed_fragment.position.x, processed_fragment.position.y);
+            if x >= 0.0 && y >= 0.0 && uniforms.frame.viewport.contains(x as usize, y as usize) {
+                // The sky sphere has a finite radius but sits behind everything
+                let depth = if matches!(uniforms.shader_type, ShaderType::Skybox) { SKY_DEPTH } else { processed_fragment.depth };
+                tile.point(x as usize, y as usize, depth, processed_fragment.color.to_pixel());
+            }
+        });
     }
+}
+
+// Camera, perspective and viewport matrices for drawing into one viewport
+fn scene_projection(view_matrix: Mat4, fov: f32, viewport: Viewport) -> ScreenProjection {
+    near_projection(view_matrix, fov, NEAR_PLANE, viewport)
+}
+
+// With the near plane moved to `near`, for views right over a surface
+fn near_projection(view_matrix: Mat4, fov: f32, near: f32, viewport: Viewport) -> ScreenProjection {
+    ScreenProjection {
+        view_matrix,
+        projection_matrix: create_projection_matrix(fov, viewport.aspect_ratio(), near, FAR_PLANE),
+        viewport_matrix: create_viewport_matrix(viewport),
+        snap_to_pixels: false,
+        affine: false,
+    }
+}
+
+// Bodies and the ship, without any overlays, inside the framebuffer's clip
+// rectangle. `pixel_scale` is how many
+// framebuffer pixels make up one pixel of the final image, so dots and the
+// level of detail come out the same in supersampled photos
+fn draw_scene(
+    framebuffer: &mut Framebuffer,
+    projection: &ScreenProjection,
+    lighting: Lighting,
+    bodies: &[CelestialBody],
+    ship: Option<&Spaceship>,
+    time: f32,
+    pixel_scale: usize,
+) {
+    let scale = pixel_scale as f32;
+    let frame = FrameUniforms::new(projection, lighting, time, framebuffer.clip());
+    let eye = projection.view_matrix.try_inverse().map_or(Vec3::zeros(), |inverse| inverse.column(3).xyz());
+
+    // Render each celestial body individually (following the recommendation)
+    for (index, body) in bodies.iter().enumerate() {
+        if body.is_virtual() {
+            continue;
+        }
+
+        // Level of detail: bodies too small to see, and point bodies that
+        // are still far away, are drawn as a dot instead of rasterized
+        let screen_radius = projection.projected_radius(body.position, body.bounding_radius()).map(|radius| radius / scale);
+        if let Some(radius) = screen_radius {
+            let dot_below = if body.draw_as_point { POINT_SWAP_PIXELS } else { LOD_MIN_PIXELS } * lighting.detail.lod_scale;
+            if radius < dot_below {
+                if let Some(screen) = projection.project(body.position) {
+                    // Dots are exposed like the bodies they stand in for
+                    let seen = match lighting.heat {
+                        _ if body.is_star() => body.color,
+                        Some(heat) => heat.color(heat.temperature(lighting.lights, body.position, body.albedo)),
+                        None => body.color * lighting.exposure,
+                    };
+                    let (size, color) = if body.draw_as_point {
+                        point_appearance(seen, radius)
+                    } else {
+                        (1, seen.to_pixel())
+                    };
+                    draw_point(framebuffer, screen, size * pixel_scale as i32, color);
+                }
+                continue;
+            }
+        }
+
+        // Set the shader for this specific model
+        let model_matrix = body.get_model_matrix();
+        let shader_type = match lighting.heat {
+            Some(heat) if !body.is_star() => {
+                ShaderType::Heat { color: heat.color(heat.temperature(lighting.lights, body.position, body.albedo)).to_pixel() }
+            }
+            _ => body.shader_type,
+        };
+        // Far enough away in low spec, only the plain shading is left
+        let flat = (body.position - eye).magnitude() > lighting.detail.flat_distance;
+        let (fade_start, fade_end) = BUMP_FADE_PIXELS;
+        let bump = match (shader_type, screen_radius) {
+            (ShaderType::RockyPlanet, Some(radius)) if !flat => body.bump * ((radius - fade_start) / (fade_end - fade_start)).clamp(0.0, 1.0),
+            _ => 0.0,
+        };
+
+        // Stars make their own light, and false colors ignore it
+        let eclipsing = match shader_type {
+            ShaderType::RockyPlanet | ShaderType::GasGiant | ShaderType::Station if !flat => occluders(bodies, index, lighting.lights),
+            _ => Vec::new(),
+        };
+        // Spheres are closed, so their far side never shows, and their
+        // normals are smooth; stations and portal discs can be seen from any
+        // side and are faceted on purpose
+        let (cull_mode, shading) = match body.shader_type {
+            ShaderType::Star | ShaderType::RockyPlanet | ShaderType::GasGiant => (CullMode::Back, lighting.shading),
+            _ => (CullMode::None, ShadingModel::Flat),
+        };
+        let uniforms = Uniforms::new(&frame, model_matrix, shader_type)
+            .with_craters(&body.craters)
+            .with_ash(&body.ash)
+            .with_vents(&body.vents)
+            .with_season(subsolar_latitude(&model_matrix, lighting.lights))
+            .with_bump(bump)
+            .with_texture(body.texture.as_ref().filter(|_| !flat && !matches!(shader_type, ShaderType::Heat { .. })))
+            .with_params(body.shader_params)
+            .with_occluders(&eclipsing)
+            .with_cull_mode(cull_mode)
+            .with_shading(shading)
+            .with_label(&body.name);
+
+        // Set the color and ID for this model
+        framebuffer.set_current_color(body.color.to_pixel());
+        framebuffer.set_current_id(body_id(index));
 
-    // Rasterization Stage
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle_with_uniforms(&tri[0], &tri[1], &tri[2], Some(uniforms)));
+        // Render this specific model
+        render(framebuffer, &uniforms, &body.vertices);
     }
 
-    // Fragment Processing Stage
-    for fragment in fragments {
-        let processed_fragment = fragment_shader(fragment, uniforms);
-        let x = processed_fragment.position.x as usize;
-        let y = processed_fragment.position.y as usize;
-        if x < framebuffer.width && y < framebuffer.height {
-            let color = processed_fragment.color.to_hex();
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, processed_fragment.depth);
+    framebuffer.set_current_id(0);
+    if let Some(ship) = ship {
+        let uniforms = Uniforms::new(&frame, ship.model_matrix, ship.shader_type()).with_label(&ship.design().name);
+        render(framebuffer, &uniforms, &ship.design().vertices);
+        let glow = Color::from_hex(ship.design().emissive_color) * engine_flicker(ship.damage(), time);
+        for engine in ship.engine_positions() {
+            if let Some(screen) = projection.project(engine) {
+                draw_point(framebuffer, screen, ENGINE_GLOW_SIZE * pixel_scale as i32, glow.to_hex());
+            }
+        }
+        for (spark, life) in ship.sparks() {
+            if let Some(screen) = projection.project(spark) {
+                let size = (SPARK_SIZE as f32 * life).ceil() as i32 * pixel_scale as i32;
+                draw_point(framebuffer, screen, size, SPARK_COLOR);
+            }
         }
     }
 }
 
+// Renders the photo camera's view again at PHOTO_SUPERSAMPLING times the
+// size of `live` and averages it back down. Only the saved frame pays for
+// the extra pixels; depth of field blurs by as many more of them
+fn take_photo(shot: &PhotoMode, live: &Framebuffer, lighting: Lighting, bodies: &[CelestialBody], ship: Option<&Spaceship>, sky: &Sky, time: f32) -> Vec<u32> {
+    let mut framebuffer = live.blank(live.width * PHOTO_SUPERSAMPLING, live.height * PHOTO_SUPERSAMPLING);
+    let projection = scene_projection(shot.view_matrix(), shot.fov, framebuffer.full_viewport());
+    draw_scene(&mut framebuffer, &projection, lighting, bodies, ship, time, PHOTO_SUPERSAMPLING);
+    sky.draw(&mut framebuffer, &projection, time);
+    depth_of_field(&mut framebuffer, &projection, shot.focus, shot.aperture * PHOTO_SUPERSAMPLING as f32);
+    let mut pixels = framebuffer.downsample(PHOTO_SUPERSAMPLING);
+    shot.finish(&mut pixels, live.width, live.height);
+    pixels
+}
+
 fn main() {
     let window_width = 800;
     let window_height = 800;
     let framebuffer_width = 800;
     let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
 
     let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
-    framebuffer.set_background_color(0x2D1B69); // Dark purple background
-    let mut window = Window::new(
-        "Enhanced Solar System - Multi-Model 3D Renderer",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    )
-    .unwrap();
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+    // render() shares rows of tiles out over this many threads
+    if let Some(threads) = options.threads {
+        if let Err(err) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global() {
+            eprintln!("{}", err);
+        }
+    }
 
-    window.set_position(500, 500);
-    window.update();
+    // Headless runs never open a window, they only replay and write frames
+    let mut backend = if options.headless {
+        None
+    } else {
+        Some(options.backend.open(window_width, window_height).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }))
+    };
+    let mut fullscreen = false;
 
-    framebuffer.set_background_color(0x4A0E4E); // Purple background
+    // --uncapped runs as fast as possible (U toggles it at runtime)
+    let mut clock = FrameClock::new(!options.uncapped, options.deterministic());
+    if let Some(backend) = &mut backend {
+        clock.apply(backend.as_mut());
+    }
+    let mut shown_fps = 0.0f32;
 
-    let solar_system_center = Vec3::new(400.0, 300.0, 0.0);
+    let scene_path = options.scene_path.clone();
+    let scene = Scene::load(&scene_path, options.epoch.as_deref()).expect("Failed to load scene");
+    let mut celestial_bodies = scene.bodies;
+    let mut scene_graph = scene.graph;
+    let mut trails = Trails::new(scene.trails, celestial_bodies.len());
+    let mut scale_transition = ScaleTransition::new(scene.realistic_scale, &celestial_bodies);
+    let mut simulation_mode = scene.settings.mode;
+    let mut scene_hash = scene.hash;
+    let mut scene_layout = scene.layout;
+    let mut units = scene.units;
+    // Saving the scene file applies it while running: settings in place,
+    // added or removed bodies and new models by rebuilding the scene. Not
+    // while recording or replaying, those have to match the file they started
+    // with
+    let mut scene_watcher = (!options.deterministic()).then(|| FileWatcher::new(&scene_path));
+    // A scene to switch to at the start of the next frame, and whether the
+    // camera goes back to its star
+    let mut pending_scene: Option<(Scene, bool)> = None;
+
+    // The first body in the scene is the central star
+    let mut solar_system_center = celestial_bodies[0].position;
     
     // Create LookAt camera that always looks at the center of the solar system (sun)
     let mut camera = Camera::new(solar_system_center, 600.0); // Start 600 units away
@@ -291,179 +653,1306 @@ fn main() {
     camera.phi = std::f32::consts::PI / 4.0; // Initial vertical angle (45 degrees)
     camera.update_position();
 
-    // Load models
-    let sun_obj = Obj::load("assets/models/Planet.obj").expect("Failed to load Planet.obj for sun");
-    let planet_obj = Obj::load("assets/models/Planet.obj").expect("Failed to load Planet.obj for planet");
-    let moon_obj = Obj::load("assets/models/basketmoon.obj").expect("Failed to load basketmoon.obj for moon");
-    let third_planet_obj = Obj::load("assets/models/trasureP.obj").expect("Failed to load trasureP.obj for third planet");
+    // Every cheap drawing path at once (--low-spec, [low_spec] or the pause
+    // menu). The sky and impacts remember their own settings to go back to
+    let mut low_spec = LowSpec::new(scene.low_spec, options.low_spec);
+    // Background stars, baked once or drawn live (see the scene's [sky])
+    let mut sky_settings = scene.sky.with_mode(options.sky);
+    let mut sky = Sky::new(low_spec.sky(sky_settings));
+    // / shows the constellation figures and the brightest stars' names
+    let mut show_constellations = scene.sky.constellations;
 
-    let sun_vertices = sun_obj.get_vertex_array();
-    let planet_vertices = planet_obj.get_vertex_array();
-    let moon_vertices = moon_obj.get_vertex_array();
-    let third_planet_vertices = third_planet_obj.get_vertex_array();
+    let mut time = 0.0f32;
 
-    // TODO: Skybox temporarily disabled - will work on it later
-    // let skybox_vertices = Skybox::create_sphere_vertices(2000.0, 20); // Large radius, moderate detail
+    // Simulation speed multiplier, changed with [ and ]
+    let mut time_scale = 1.0f32;
 
-    let mut time = 0.0f32;
+    let mut orbit_display = OrbitDisplay::All;
+    let mut show_trails = true;
+
+    // Distance tool: M picks body A, then body B, a third M clears it
+    let mut measurement: Option<(usize, Option<usize>)> = None;
+    // \ works out when the measured bodies (and the selected one) next line
+    // up and asks before skipping ahead to then
+    let mut alignment: Option<Alignment> = None;
+
+    // Lagrange points of the selected body and its parent (L toggles)
+    let mut show_lagrange = false;
+
+    // Body the camera is locked onto (F toggles following the selected body)
+    let mut followed_body: Option<usize> = None;
+    // Home stands the camera on the selected body's surface, turning with
+    // it; the arrow keys walk, Home again goes back
+    let mut surface: Option<SurfaceView> = None;
+
+    // Body shown in the info panel (Tab cycles through them)
+    let mut selected_body: usize = 0;
+
+    // Every key the program reacts to, looked up by action
+    let bindings = InputBindings::default();
+    // H or F1 lists them over the scene, which keeps running behind
+    let mut show_help = false;
+
+    // Number keys warp the camera to the matching body, Enter to the
+    // selected one
+    let mut warp_targets = collect_warp_targets(&celestial_bodies, bindings.keys(Action::Warp).len());
+    // W shows a small render of each of them along the bottom
+    let mut show_thumbnails = false;
+    let mut thumbnails = Thumbnails::new(&warp_targets);
+    let mut warp = WarpSystem::default();
+    // Optional energy budget: warps cost energy by distance, which recharges
+    // over time and faster near a star
+    let mut warp_energy = WarpEnergy::new(scene.warp_energy);
+
+    // Chase view (V toggles): the ship flies just ahead of the camera and
+    // bumps into bodies instead of passing through them; N switches ships
+    let mut spaceship = Spaceship::new(scene.ships);
+    let mut show_ship = false;
+
+    // Flight readouts above the status line (I toggles)
+    let mut show_flight_strip = true;
+    let mut show_trajectory = false;
+    let mut telemetry = FlightTelemetry::default();
+
+    // G flies the camera into orbit around the selected body; any manual
+    // camera input takes back control
+    let mut autopilot: Option<Autopilot> = None;
+
+    // F9 records what's on screen into a clip
+    let mut recorder = Recorder::new(scene.recording, framebuffer_width, framebuffer_height);
+
+    // Overlay and HUD colors (C cycles the palette), plus the optional
+    // full-frame filter for deuteranopia
+    let mut palette = scene.accessibility.palette;
+    let daltonize_frames = scene.accessibility.daltonize;
+
+    // F3 draws the scene once per eye and mixes them for red-cyan glasses
+    let mut stereo = scene.stereo;
+    let mut exposure = Exposure::new(scene.exposure);
+    let mut motion_blur = MotionBlur::new(scene.motion_blur);
+    let mut filters = scene.filters;
+    let mut retro = scene.retro;
+    // E recolors bodies by their estimated temperature
+    let mut heat = scene.heat;
+    // Insert cycles the planets through flat, Gouraud and Phong shading
+    let mut shading = ShadingModel::Flat;
+    // Shimmer, shake and a warning close to a star, which keeps you out
+    let mut sun_haze = scene.sun_haze;
+    // Space held in free flight speeds up along the view
+    let mut cruise = Cruise::new(scene.cruise);
+    // How much of each star the main view can see, for the exposure and
+    // the coronas
+    let mut sun_visibility = SunVisibility::default();
+    let mut ambient = scene.ambient;
+    // Orbit rings and trails fade out behind bodies
+    let mut overlays = scene.overlays;
+    // Asteroids now and then strike rocky bodies and leave craters
+    let mut impact_settings = scene.impacts;
+    let mut impacts = Impacts::new(low_spec.impacts(impact_settings), Seed(scene.settings.seed));
+    // Rocky bodies with an [bodies.eruptions] table erupt now and then
+    let mut eruptions = Eruptions::new(Seed(scene.settings.seed));
+
+    // Exploration goals from the scene, checked every tick (Q shows them)
+    let mut objectives = Objectives::new(scene.objectives);
+    let mut show_objectives = false;
+    // LUTs that fail to load are skipped, the first problem shows on screen
+    let (mut grading, grading_errors) = ColorGrading::load(&scene.grading);
+    for err in &grading_errors {
+        eprintln!("{}", err);
+    }
+
+    // F5 / F8 quick-save and quick-load; the result shows up for a moment
+    let mut notice: Option<(String, f32)> = grading_errors.first().map(|err| (err.clone(), time));
+
+    // --record logs every frame's input, --replay plays a log back in place
+    // of the keyboard. Both run on a fixed time step, so a replay renders
+    // exactly the frames of the recorded run
+    let mut input_log = options.record.as_deref().map(|path| InputLog::create(path, scene_hash).expect("Failed to start recording"));
+    let mut replay = options.replay.as_deref().map(|path| InputReplay::load(path, scene_hash).expect("Failed to load replay"));
+    if options.headless && !options.benchmark {
+        std::fs::create_dir_all(&options.out).expect("Failed to create the frames folder");
+    }
+    let mut frame_number = 0usize;
+    // --benchmark flies the camera itself and times every frame
+    let mut benchmark = options.benchmark.then(|| Benchmark::new(sky.mode()));
+
+    // Escape pauses the simulation behind a small menu
+    let mut pause_menu: Option<PauseMenu> = None;
+    let mut escape_held = 0.0f32;
+
+    // F6 splits the screen between the usual view and a second camera
+    // overlooking the whole system; F7 picks which one the camera keys move
+    let mut split_screen = false;
+    let mut overview = Camera::new(solar_system_center, OVERVIEW_DISTANCE);
+    overview.phi = OVERVIEW_ELEVATION;
+    overview.update_position();
+    let mut overview_active = false;
+
+    // B cycles a small inset in the corner of the main view: off, the view
+    // behind the camera, or a close-up of the selected body
+    let mut inset_mode = InsetMode::default();
+
+    // P freezes everything for a free-flying photo camera; F12 saves a
+    // supersampled still
+    let mut photo: Option<PhotoMode> = None;
 
-    // Create celestial bodies following the new system
-    let mut celestial_bodies = vec![
-        // Sun (index 0) - using Planet model, larger scale
-        CelestialBody::new_sun(
-            "Sun".to_string(),
-            sun_vertices,
-            solar_system_center,
-            60.0,      // Large scale for the sun
-            0xFFD700,   // Gold color for sun
-        ),
-        
-        // Rocky Planet (index 1) - using Planet model, smaller scale
-        CelestialBody::new_planet(
-            "Rocky Planet".to_string(),
-            planet_vertices,
-            solar_system_center,
-            250.0,      // Orbital radius
-            0.2,        // Orbital speed
-            7.0,       // Smaller scale for planet
-            0x8B4513,   // Brown base color for rocky planet
-            ShaderType::RockyPlanet,
-        ),
-        
-        // Gas Giant (index 2) - using trasureP model
-        CelestialBody::new_planet(
-            "Gas Giant".to_string(),
-            third_planet_vertices,
-            solar_system_center,
-            450.0,      // Larger orbital radius
-            0.4,        // Slower orbital speed
-            12.0,       // Scale
-            0xDAA520,   // Golden base color for gas giant
-            ShaderType::GasGiant,
-        ),
-        
-        // Moon (index 3) - orbiting Rocky Planet (index 1)
-        CelestialBody::new_moon(
-            "Moon".to_string(),
-            moon_vertices,
-            1,          // Parent index (Rocky Planet)
-            40.0,       // Orbital radius from planet
-            2.0,        // Fast orbital speed
-            2.0,       // Small scale for moon
-            0x8B7D6B,   // Grayish-brown color for rocky moon
-            ShaderType::RockyPlanet,  // Moon uses rocky shader too
-        ),
-    ];
-
-    while window.is_open() {
-        if window.is_key_down(Key::Escape) {
+    // R opens the selected body's shader parameters for tuning by hand; the
+    // arrow keys work the panel instead of the camera until it's closed
+    let mut tuning: Option<ShaderTuner> = None;
+
+    // Z near a station's docking port, slow enough, flies the ship in and
+    // opens the docked menu until it undocks
+    let mut docking: Option<Docking> = None;
+    let mut dockable: Option<usize> = None;
+
+    // Flying through a portal disc comes out of its partner
+    let mut portals = Portals::default();
+
+    while backend.as_ref().is_none_or(|backend| backend.is_open()) {
+        // Everything that moves is advanced by the real time since the last
+        // frame, so speeds don't depend on the frame rate
+        let frame_delta = clock.tick();
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.start_frame();
+        }
+
+        if scene_watcher.as_mut().is_some_and(|watcher| watcher.poll(frame_delta)) {
+            match Scene::load(&scene_path, options.epoch.as_deref()) {
+                Ok(scene) if scene.layout == scene_layout => {
+                    for (body, loaded) in celestial_bodies.iter_mut().zip(scene.bodies) {
+                        body.take_settings(loaded);
+                    }
+                    scale_transition.rebase(scene.realistic_scale, &mut celestial_bodies);
+                    // New radii and tilts show up even while paused
+                    if simulation_mode == SimulationMode::Kinematic {
+                        for body in celestial_bodies.iter_mut() {
+                            body.update(0.0);
+                        }
+                    }
+                    scene_graph.update(&mut celestial_bodies);
+                    scene_hash = scene.hash;
+                    units = scene.units;
+                    heat = scene.heat;
+                    sun_haze = scene.sun_haze;
+                    cruise.settings = scene.cruise;
+                    ambient = scene.ambient;
+                    overlays = scene.overlays;
+                    palette = scene.accessibility.palette;
+                    stereo = scene.stereo;
+                    exposure.set_settings(scene.exposure);
+                    motion_blur.settings = scene.motion_blur;
+                    filters = scene.filters;
+                    retro = scene.retro;
+                    low_spec.settings = scene.low_spec;
+                    sky_settings = scene.sky.with_mode(options.sky);
+                    sky.set_settings(low_spec.sky(sky_settings));
+                    notice = Some(("Scene reloaded".to_string(), time));
+                }
+                Ok(scene) => {
+                    pending_scene = Some((scene, false));
+                    notice = Some(("Scene reloaded".to_string(), time));
+                }
+                // The running scene stays as it was
+                Err(err) => {
+                    eprintln!("{}", err);
+                    notice = Some((err, time));
+                }
+            }
+        }
+        if let Some((scene, recenter)) = pending_scene.take() {
+            celestial_bodies = scene.bodies;
+            scene_graph = scene.graph;
+            trails = Trails::new(scene.trails, celestial_bodies.len());
+            scale_transition = ScaleTransition::new(scene.realistic_scale, &celestial_bodies);
+            simulation_mode = scene.settings.mode;
+            scene_hash = scene.hash;
+            scene_layout = scene.layout;
+            units = scene.units;
+            heat = scene.heat;
+            sun_haze = scene.sun_haze;
+            cruise = Cruise::new(scene.cruise);
+            ambient = scene.ambient;
+            overlays = scene.overlays;
+            spaceship = Spaceship::new(scene.ships);
+            low_spec.settings = scene.low_spec;
+            impact_settings = scene.impacts;
+            impacts = Impacts::new(low_spec.impacts(impact_settings), Seed(scene.settings.seed));
+            eruptions = Eruptions::new(Seed(scene.settings.seed));
+            warp_energy = WarpEnergy::new(scene.warp_energy);
+            objectives = Objectives::new(scene.objectives);
+            sky_settings = scene.sky.with_mode(options.sky);
+            sky.set_settings(low_spec.sky(sky_settings));
+            warp_targets = collect_warp_targets(&celestial_bodies, bindings.keys(Action::Warp).len());
+            thumbnails = Thumbnails::new(&warp_targets);
+
+            // Body indices from the old scene mean nothing now
+            tuning = None;
+            docking = None;
+            dockable = None;
+            portals = Portals::default();
+            selected_body = 0;
+            followed_body = None;
+            if let Some(view) = surface.take() {
+                camera = view.previous;
+            }
+            measurement = None;
+            alignment = None;
+            autopilot = None;
+            warp = WarpSystem::default();
+            telemetry = FlightTelemetry::default();
+            solar_system_center = celestial_bodies[0].position;
+            if recenter {
+                camera.set_target(solar_system_center);
+                camera.set_min_distance(DEFAULT_MIN_DISTANCE);
+            }
+        }
+
+        let replayed = replay.as_mut().and_then(InputReplay::next_frame);
+        let input = match (replayed, &mut backend) {
+            (Some(frame), _) => frame,
+            // The benchmark flies itself, the keyboard only gets to close it
+            (None, Some(backend)) => {
+                let keyboard = backend.poll_input(&bindings);
+                if benchmark.is_some() { InputFrame::default() } else { keyboard }
+            }
+            (None, None) if benchmark.is_some() => InputFrame::default(),
+            // Headless runs end with their replay
+            (None, None) => break,
+        };
+        if let Some(log) = &mut input_log {
+            if let Err(err) = log.write(&input) {
+                notice = Some((err, time));
+                input_log = None;
+            }
+        }
+
+        // Holding Escape quits whatever is open, in case the menu misbehaves
+        escape_held = if input.held(Action::Back) { escape_held + frame_delta } else { 0.0 };
+        if escape_held >= FORCE_QUIT_SECONDS {
             break;
         }
 
-        handle_camera_input(&window, &mut camera);
+        if let Some(menu) = &mut pause_menu {
+            if input.pressed(Action::Back) {
+                pause_menu = None;
+            } else if input.pressed(Action::MenuUp) {
+                menu.move_selection(-1);
+            } else if input.pressed(Action::MenuDown) {
+                menu.move_selection(1);
+            } else if input.pressed(Action::MenuSelect) {
+                match menu.selected() {
+                    MenuEntry::Resume => pause_menu = None,
+                    // The scene keeps drawing behind the menu, so the
+                    // difference shows straight away
+                    MenuEntry::LowSpec => {
+                        low_spec.toggle();
+                        sky.set_settings(low_spec.sky(sky_settings));
+                        impacts.set_settings(low_spec.impacts(impact_settings));
+                    }
+                    MenuEntry::ReloadScene => match Scene::load(&scene_path, options.epoch.as_deref()) {
+                        Ok(scene) => {
+                            pending_scene = Some((scene, true));
+                            pause_menu = None;
+                        }
+                        Err(err) => menu.message = Some(format!("Reload failed: {}", err)),
+                    },
+                    // Nothing to resize when replaying headless
+                    MenuEntry::ToggleFullscreen if backend.is_some() => {
+                        if let Some(backend) = &mut backend {
+                            match backend.set_fullscreen(!fullscreen) {
+                                Ok(()) => fullscreen = !fullscreen,
+                                Err(err) => menu.message = Some(format!("Fullscreen failed: {}", err)),
+                            }
+                        }
+                    }
+                    MenuEntry::ToggleFullscreen => {}
+                    MenuEntry::Quit => break,
+                }
+            }
+        } else if let Some(shot) = &mut photo {
+            if input.pressed(Action::PhotoMode) || input.pressed(Action::Back) {
+                photo = None;
+            } else {
+                shot.update(&input, frame_delta);
+            }
+        } else if let Some(tuner) = &mut tuning {
+            let body = &mut celestial_bodies[tuner.body];
+            if input.pressed(Action::ToggleTuning) || input.pressed(Action::Back) {
+                tuning = None;
+            } else if input.pressed(Action::TunePrevious) || input.pressed(Action::TuneNext) {
+                tuner.move_selection(&body.shader_params, if input.pressed(Action::TuneNext) { 1 } else { -1 });
+            } else if input.pressed(Action::TuneLess) || input.pressed(Action::TuneMore) {
+                let direction = if input.pressed(Action::TuneMore) { 1.0 } else { -1.0 };
+                tuner.adjust(&mut body.shader_params, direction, input.held(Action::TuneFine));
+            } else if input.pressed(Action::PrintTuning) {
+                if let Some(line) = body.shader_params.scene_line() {
+                    println!("# {}\n{}", body.name, line);
+                    notice = Some((format!("Printed the parameters of {}", body.name), time));
+                }
+            }
+        } else if let Some(pending) = &alignment {
+            if input.pressed(Action::MenuSelect) {
+                // Straight to the answer rather than frame by frame, and the
+                // trails would only draw the jump
+                for body in celestial_bodies.iter_mut() {
+                    body.fast_forward(pending.wait);
+                }
+                scene_graph.update(&mut celestial_bodies);
+                trails.clear();
+                notice = Some((format!("Skipped {} ahead", units.duration(pending.wait)), time));
+                alignment = None;
+            } else if input.pressed(Action::Back) {
+                alignment = None;
+            }
+        } else if let Some(sequence) = &mut docking {
+            // The sequence flies the ship; only the docked menu takes keys
+            if input.pressed(Action::Back) {
+                pause_menu = Some(PauseMenu::open());
+            } else if input.pressed(Action::MenuUp) {
+                sequence.move_selection(-1);
+            } else if input.pressed(Action::MenuDown) {
+                sequence.move_selection(1);
+            } else if input.pressed(Action::MenuSelect) {
+                match sequence.selected() {
+                    Some(DockedEntry::Repair) if spaceship.damage() > 0.0 => {
+                        spaceship.repair();
+                        notice = Some(("Hull repaired".to_string(), time));
+                    }
+                    Some(DockedEntry::Repair) => notice = Some(("The hull is intact".to_string(), time)),
+                    Some(DockedEntry::Undock) => sequence.undock(),
+                    None => {}
+                }
+            }
+        } else {
+            // Escape closes the help, cancels the autopilot or clears an active
+            // measurement first, otherwise it pauses
+            if input.pressed(Action::Back) {
+                if show_help {
+                    show_help = false;
+                } else if autopilot.is_some() {
+                    autopilot = None;
+                } else if measurement.is_some() {
+                    measurement = None;
+                } else {
+                    pause_menu = Some(PauseMenu::open());
+                }
+            }
+
+            if input.pressed(Action::Help) {
+                show_help = !show_help;
+            }
+            if input.pressed(Action::PhotoMode) {
+                photo = Some(PhotoMode::new(&camera));
+            }
+            if input.pressed(Action::ToggleTuning) {
+                let body = &celestial_bodies[selected_body];
+                tuning = ShaderTuner::open(selected_body, &body.shader_params);
+                if tuning.is_none() {
+                    notice = Some((format!("{} has no shader parameters to tune", body.name), time));
+                }
+            }
+            if input.pressed(Action::Record) {
+                recorder.toggle();
+            }
+            if input.pressed(Action::ToggleFrameCap) {
+                if let Some(backend) = &mut backend {
+                    clock.toggle_cap(backend.as_mut());
+                }
+            }
+            if input.pressed(Action::QuickSave) {
+                let state = SaveState {
+                    scene_hash: format_hash(scene_hash),
+                    time,
+                    time_scale,
+                    scale_profile: scale_transition.profile(),
+                    scale_progress: scale_transition.progress(),
+                    selected_body,
+                    followed_body,
+                    bodies: celestial_bodies.iter().map(BodyState::from_body).collect(),
+                    camera: CameraState::from_camera(&camera),
+                    ship: ShipState::from_ship(&spaceship, show_ship),
+                    objectives: objectives.progress().clone(),
+                    warp_energy: warp_energy.settings.enabled.then_some(warp_energy.level),
+                };
+                let text = match state.write(QUICKSAVE_PATH) {
+                    Ok(()) => format!("Saved {}", QUICKSAVE_PATH),
+                    Err(err) => err,
+                };
+                notice = Some((text, time));
+            }
+            if input.pressed(Action::ToggleStereo) {
+                stereo.enabled = !stereo.enabled;
+                notice = Some((format!("Stereo 3D {}", if stereo.enabled { "on" } else { "off" }), time));
+            }
+            if input.pressed(Action::EyesCloser) || input.pressed(Action::EyesApart) {
+                stereo.widen(input.pressed(Action::EyesApart));
+                notice = Some((format!("Eye separation {:.2}", stereo.eye_separation), time));
+            }
+            if input.pressed(Action::ConvergeNearer) || input.pressed(Action::ConvergeFarther) {
+                stereo.converge(input.pressed(Action::ConvergeFarther));
+                notice = Some((format!("Convergence {:.0}", stereo.convergence), time));
+            }
+            if input.pressed(Action::CyclePalette) {
+                palette = palette.next();
+                notice = Some((format!("Palette: {}", palette.label()), time));
+            }
+            if input.pressed(Action::ExposureDown) || input.pressed(Action::ExposureUp) {
+                exposure.adjust(if input.pressed(Action::ExposureUp) { 1.0 } else { -1.0 });
+                notice = Some((format!("Exposure {:+.1} EV", exposure.ev), time));
+            }
+            if input.pressed(Action::AutoExposure) {
+                exposure.reset();
+                notice = Some(("Exposure automatic".to_string(), time));
+            }
+            if input.pressed(Action::ToggleMotionBlur) {
+                let enabled = motion_blur.toggle();
+                notice = Some((format!("Motion blur {}", if enabled { "on" } else { "off" }), time));
+            }
+            if input.pressed(Action::CycleGrading) {
+                grading.next();
+                notice = Some((format!("Color grade: {}", grading.label()), time));
+            }
+            for (action, name, enabled) in [
+                (Action::ToggleGrain, "Film grain", &mut filters.grain),
+                (Action::ToggleVignetteFilter, "Vignette", &mut filters.vignette),
+                (Action::ToggleCrt, "CRT scanlines", &mut filters.crt),
+                (Action::ToggleRetro, "Retro look", &mut retro.enabled),
+            ] {
+                if input.pressed(action) {
+                    *enabled = !*enabled;
+                    notice = Some((format!("{} {}", name, if *enabled { "on" } else { "off" }), time));
+                }
+            }
+            if input.pressed(Action::QuickLoad) {
+                match SaveState::read(QUICKSAVE_PATH, scene_hash, celestial_bodies.len()) {
+                    Ok(state) => {
+                        for (body, saved) in celestial_bodies.iter_mut().zip(&state.bodies) {
+                            saved.apply(body);
+                        }
+                        state.camera.apply(&mut camera);
+                        state.ship.apply(&mut spaceship);
+                        show_ship = state.ship.shown;
+                        scale_transition.restore(state.scale_profile, state.scale_progress);
+                        time = state.time;
+                        time_scale = state.time_scale;
+                        selected_body = state.selected_body.min(celestial_bodies.len() - 1);
+                        followed_body = state.followed_body.filter(|&i| i < celestial_bodies.len());
+                        objectives.restore(state.objectives);
+                        if let Some(level) = state.warp_energy {
+                            warp_energy.level = level.min(warp_energy.settings.capacity);
+                        }
+
+                        // Flights in progress and history belong to the old timeline
+                        autopilot = None;
+                        surface = None;
+                        warp = WarpSystem::default();
+                        trails.clear();
+                        telemetry = FlightTelemetry::default();
+                        notice = Some((format!("Loaded {}", QUICKSAVE_PATH), time));
+                    }
+                    Err(err) => notice = Some((err, time)),
+                }
+            }
+
+            if input.pressed(Action::SplitScreen) {
+                split_screen = !split_screen;
+                overview_active = false;
+            }
+            if input.pressed(Action::CycleInset) {
+                inset_mode = inset_mode.next();
+            }
+            if input.pressed(Action::SwitchViewport) && split_screen {
+                overview_active = !overview_active;
+            }
+            if overview_active {
+                handle_camera_input(&input, &mut overview);
+            } else if let Some(view) = &mut surface {
+                view.walk(&input, frame_delta);
+            } else if handle_camera_input(&input, &mut camera) {
+                autopilot = None;
+            }
+
+            if input.pressed(Action::SelectNext) {
+                selected_body = (selected_body + 1) % celestial_bodies.len();
+            }
+            if input.pressed(Action::Follow) {
+                autopilot = None;
+                followed_body = match followed_body {
+                    Some(_) => {
+                        camera.set_target(solar_system_center);
+                        camera.set_min_distance(DEFAULT_MIN_DISTANCE);
+                        None
+                    }
+                    None => {
+                        camera.set_min_distance(collision_distance(&celestial_bodies[selected_body]));
+                        Some(selected_body)
+                    }
+                };
+            }
+            if input.pressed(Action::StandOnSurface) {
+                match surface.take() {
+                    Some(view) => {
+                        camera = view.previous;
+                        followed_body = view.followed;
+                        show_ship = view.chase;
+                    }
+                    None => {
+                        let body = &celestial_bodies[selected_body];
+                        if body.is_star() || body.is_virtual() || body.is_portal() {
+                            notice = Some((format!("{} has no surface to stand on", body.name), time));
+                        } else {
+                            surface = Some(SurfaceView::new(selected_body, &celestial_bodies, &collect_lights(&celestial_bodies), camera.clone(), followed_body, show_ship));
+                            notice = Some((format!("On {}: arrows walk, Home leaves", body.name), time));
+                            autopilot = None;
+                            followed_body = None;
+                            show_ship = false;
+                        }
+                    }
+                }
+            }
+            if input.pressed(Action::ToggleOrbits) {
+                orbit_display = orbit_display.next();
+                notice = Some((format!("Orbits: {}", orbit_display.label()), time));
+            }
+            if input.pressed(Action::Measure) {
+                measurement = match measurement {
+                    None => Some((selected_body, None)),
+                    Some((a, None)) if a != selected_body => Some((a, Some(selected_body))),
+                    Some((a, None)) => Some((a, None)),
+                    Some(_) => None,
+                };
+            }
+            if input.pressed(Action::Align) {
+                // The measured pair or the body picked as A, plus whatever
+                // else is selected
+                let mut chosen: Vec<usize> = match measurement {
+                    Some((a, b)) => std::iter::once(a).chain(b).collect(),
+                    None => Vec::new(),
+                };
+                if !chosen.contains(&selected_body) {
+                    chosen.push(selected_body);
+                }
+                if simulation_mode != SimulationMode::Kinematic {
+                    notice = Some(("Alignments need the scripted orbits".to_string(), time));
+                } else if chosen.len() < 2 {
+                    notice = Some(("Measure a body (M) to line the selected one up with".to_string(), time));
+                } else {
+                    match next_alignment(&celestial_bodies, &chosen) {
+                        Ok(next) => alignment = Some(next),
+                        Err(err) => notice = Some((err, time)),
+                    }
+                }
+            }
+            if input.pressed(Action::ForceImpact) {
+                let name = &celestial_bodies[selected_body].name;
+                let text = if impacts.force(selected_body, &celestial_bodies) {
+                    format!("Asteroid inbound: {}", name)
+                } else {
+                    format!("Nothing to hit on {}", name)
+                };
+                notice = Some((text, time));
+            }
+            // Scale profiles only rescale scripted orbits, n-body positions come
+            // from the integrator
+            if input.pressed(Action::ToggleScale) && simulation_mode == SimulationMode::Kinematic {
+                scale_transition.toggle();
+            }
+            if input.pressed(Action::ToggleTrails) {
+                show_trails = !show_trails;
+            }
+            if input.pressed(Action::ToggleLagrange) {
+                show_lagrange = !show_lagrange;
+            }
+            if input.pressed(Action::ToggleConstellations) {
+                show_constellations = !show_constellations;
+            }
+            if input.pressed(Action::ToggleHeat) {
+                heat.enabled = !heat.enabled;
+                notice = Some((format!("Temperature view {}", if heat.enabled { "on" } else { "off" }), time));
+            }
+            if input.pressed(Action::CycleShading) {
+                shading = shading.next();
+                notice = Some((shading.label().to_string(), time));
+            }
+            if input.pressed(Action::ToggleShip) {
+                show_ship = !show_ship;
+            }
+            if input.pressed(Action::NextShip) {
+                spaceship.cycle();
+            }
+            if input.pressed(Action::RepairShip) && spaceship.damage() > 0.0 {
+                spaceship.repair();
+                notice = Some(("Hull repaired".to_string(), time));
+            }
+            if input.pressed(Action::Dock) && show_ship {
+                match dockable.and_then(|station| Docking::begin(station, &spaceship, &celestial_bodies)) {
+                    Some(sequence) => {
+                        // The station's port is the only target from here on
+                        followed_body = None;
+                        autopilot = None;
+                        warp.cancel();
+                        docking = Some(sequence);
+                    }
+                    None => notice = Some(("No station close and slow enough to dock with".to_string(), time)),
+                }
+            }
+            if input.pressed(Action::ToggleObjectives) {
+                show_objectives = !show_objectives;
+                if objectives.is_empty() && show_objectives {
+                    notice = Some(("This scene has no objectives".to_string(), time));
+                }
+            }
+            if input.pressed(Action::ToggleThumbnails) {
+                show_thumbnails = !show_thumbnails;
+            }
+            if input.pressed(Action::ToggleFlightStrip) {
+                show_flight_strip = !show_flight_strip;
+            }
+            if input.pressed(Action::ToggleTrajectory) {
+                show_trajectory = !show_trajectory;
+            }
+            if input.pressed(Action::SpeedUp) {
+                time_scale = (time_scale * 2.0).min(MAX_TIME_SCALE);
+            }
+            if input.pressed(Action::SlowDown) {
+                time_scale = (time_scale / 2.0).max(MIN_TIME_SCALE);
+            }
+
+            let mut warp_request = input.pressed_index(Action::Warp)
+                .and_then(|slot| warp_targets.get(slot).copied());
+            if input.pressed(Action::WarpToSelected) && !celestial_bodies[selected_body].is_virtual() {
+                warp_request = Some(selected_body);
+            }
+            if let Some(target) = warp_request.filter(|_| warp.is_ready(time)) {
+                let body = &celestial_bodies[target];
+                let to_distance = arrival_distance(body);
+                let distance = ((body.position - camera.position).magnitude() - to_distance).max(0.0);
+                let cost = warp_energy.cost(distance);
+                if !warp_energy.spend(cost, time) {
+                    notice = Some((format!("Not enough warp energy for {}: needs {:.0}, have {:.0}", body.name, cost, warp_energy.level), time));
+                } else if warp.start(target, time, &camera, to_distance, warp_energy.duration(distance)) {
+                    autopilot = None;
+                    selected_body = target;
+                    followed_body = None;
+                    camera.min_distance = collision_distance(body);
+                }
+            }
+            if input.pressed(Action::Autopilot) && !celestial_bodies[selected_body].is_virtual() {
+                let body = &celestial_bodies[selected_body];
+                let orbit_radius = arrival_distance(body);
+                followed_body = None;
+                warp.cancel();
+                camera.min_distance = collision_distance(body);
+                autopilot = Some(Autopilot::new(selected_body, orbit_radius, camera.position, &celestial_bodies));
+            }
+        }
 
+        framebuffer.set_background_color(palette.color(UiColor::Background));
+        // The selection outline needs to know which body each pixel shows
+        framebuffer.track_ids(photo.is_none() && !celestial_bodies[selected_body].is_virtual());
         framebuffer.clear();
 
-        // Update time for animations
-        time += 0.016;
-
-        // Get the view matrix from the camera
-        let view_matrix = camera.look_at();
-        
-        // Create projection matrix (perspective projection)
-        let aspect_ratio = framebuffer_width as f32 / framebuffer_height as f32;
-        let projection_matrix = create_projection_matrix(
-            PI / 3.0,    // 60 degrees field of view
-            aspect_ratio,
-            10.0,        // Near plane - increased to prevent clipping issues
-            3000.0       // Far plane - increased for better range
-        );
-        
-        // Create viewport matrix (NDC to screen coordinates)
-        let viewport_matrix = create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
-
-        // TODO: Skybox temporarily disabled - will work on it later
-        /*
-        // Render skybox first (background)
-        let skybox_matrix = create_model_matrix(
-            solar_system_center, // Center the skybox
-            1.0, // No scaling needed for skybox
-            Vec3::new(0.0, 0.0, 0.0), // No rotation
-        );
-        
-        let skybox_uniforms = Uniforms {
-            model_matrix: skybox_matrix,
-            view_matrix,
-            light_position: Vec3::new(0.0, 0.0, 0.0), // Not used for skybox
-            is_light_source: false, // Not used for skybox
-            shader_type: ShaderType::Skybox,
-            time,
+        // Nothing moves behind the pause menu, in photo mode or while a
+        // skip ahead to an alignment waits for an answer
+        let frozen = pause_menu.is_some() || photo.is_some() || alignment.is_some();
+        let mut proximity = None;
+        if !frozen {
+            // Update time for animations
+            time += frame_delta;
+
+            // Scale transitions run on real time so they always take the same
+            // couple of seconds; the camera zooms along to keep its framing
+            let orbit_before = scale_transition.orbit_multiplier();
+            let followed_scale_before = followed_body.map(|i| celestial_bodies[i].scale);
+            scale_transition.update(&mut celestial_bodies, frame_delta);
+            match (followed_body, followed_scale_before) {
+                (Some(index), Some(scale_before)) if scale_before > 0.0 => {
+                    camera.distance *= celestial_bodies[index].scale / scale_before;
+                    camera.set_min_distance(collision_distance(&celestial_bodies[index]));
+                }
+                _ => {
+                    camera.distance *= scale_transition.orbit_multiplier() / orbit_before;
+                    camera.zoom(0.0);
+                }
+            }
+
+            // Update celestial bodies; where they were is kept for the ship's
+            // speed relative to stations
+            let positions_before: Vec<Vec3> = celestial_bodies.iter().map(|body| body.position).collect();
+            let sim_delta = frame_delta * time_scale;
+            match simulation_mode {
+                SimulationMode::Kinematic => {
+                    for body in celestial_bodies.iter_mut() {
+                        body.update(sim_delta);
+                    }
+                }
+                SimulationMode::NBody => nbody::advance(&mut celestial_bodies, sim_delta),
+            }
+            // World positions and model matrices for the whole tree in one pass
+            scene_graph.update(&mut celestial_bodies);
+            trails.record(&celestial_bodies, sim_delta);
+            impacts.update(&mut celestial_bodies, sim_delta, time);
+            eruptions.update(&mut celestial_bodies, sim_delta);
+
+            // Warps ease the camera onto the moving body, then follow it
+            if let Some(arrived) = warp.update(time, &mut camera, &celestial_bodies) {
+                followed_body = Some(arrived);
+            }
+
+            // The autopilot moves the camera itself and keeps it looking at the
+            // target, then hands over to the follow camera
+            if let Some(pilot) = &mut autopilot {
+                let arrived = pilot.fly(&celestial_bodies, frame_delta, collision_distance);
+                camera.target = celestial_bodies[pilot.target].position;
+                camera.move_to(pilot.position);
+                if arrived {
+                    followed_body = Some(pilot.target);
+                    autopilot = None;
+                }
+            }
+
+            // Follow camera keeps the tracked body centered
+            if let Some(index) = followed_body {
+                camera.set_target(celestial_bodies[index].position);
+            }
+
+            // Standing on a surface turns with it, until something else
+            // takes the camera
+            if let Some(view) = &surface {
+                if followed_body.is_some() || autopilot.is_some() || warp.is_warping() || docking.is_some() {
+                    camera.up = view.previous.up;
+                    surface = None;
+                } else {
+                    view.place(&mut camera, &celestial_bodies);
+                }
+            }
+
+            // Cruise only flies a camera nothing else is steering, and brakes
+            // for bodies in its path
+            let piloted = followed_body.is_some() || autopilot.is_some() || warp.is_warping() || docking.is_some() || benchmark.is_some() || surface.is_some();
+            if piloted {
+                cruise.stop();
+            } else if let Some(body) = cruise.update(input.held(Action::Cruise), &mut camera, &celestial_bodies, frame_delta, collision_distance) {
+                notice = Some((format!("Cruise stopped: {} ahead", celestial_bodies[body].name), time));
+            }
+
+            // The ship can't enter a body; when it would, it is pushed back out
+            // and drags the camera along. Docking flies it instead, carrying
+            // the camera with it
+            if show_ship {
+                let docked = docking.as_mut()
+                    .is_some_and(|sequence| sequence.update(&mut spaceship, &mut camera, &celestial_bodies, frame_delta));
+                if !docked {
+                    docking = None;
+                    spaceship.update_position(&camera, frame_delta);
+                    let correction = spaceship.resolve_collisions(&celestial_bodies);
+                    if correction != Vec3::zeros() {
+                        camera.move_to(camera.position + correction);
+                        if let Some(pilot) = &mut autopilot {
+                            pilot.position += correction;
+                        }
+                    }
+                    proximity = spaceship.proximity_warning(&celestial_bodies, frame_delta);
+                }
+            }
+            dockable = (show_ship && docking.is_none())
+                .then(|| dockable_station(&spaceship, &celestial_bodies, &positions_before, frame_delta))
+                .flatten();
+
+            // Portals only take whoever flies freely: the follow camera,
+            // warps, the autopilot, docking, the benchmark and standing on a
+            // surface all steer along paths of their own
+            let flying_free = followed_body.is_none() && autopilot.is_none() && !warp.is_warping() && docking.is_none() && benchmark.is_none() && surface.is_none();
+            let traveller = flying_free.then_some(if show_ship { spaceship.position } else { camera.position });
+            if let Some(passage) = portals.update(&celestial_bodies, &positions_before, traveller, frame_delta) {
+                // The camera goes through with the ship, keeping its place behind it
+                camera.target = passage.point(camera.target);
+                camera.move_to(passage.point(camera.position));
+                if show_ship {
+                    spaceship.pass_through(&passage);
+                }
+                notice = Some((format!("Through {} to {}", celestial_bodies[passage.from].name, celestial_bodies[passage.to].name), time));
+            }
+
+            // Stars push back whoever flies the view once it's too close,
+            // like a collision, except along paths that steer themselves
+            let steered = autopilot.is_some() || warp.is_warping() || docking.is_some() || benchmark.is_some() || surface.is_some();
+            if !steered {
+                let correction = sun_haze.push_out(&celestial_bodies, if show_ship { spaceship.position } else { camera.position });
+                if correction != Vec3::zeros() {
+                    camera.move_to(camera.position + correction);
+                }
+            }
+
+            // Checked after warps, the autopilot and the follow camera have
+            // moved things, so arriving any way counts
+            let observers = [camera.position, spaceship.position];
+            let observers = &observers[..if show_ship { 2 } else { 1 }];
+            let met = objectives.update(&celestial_bodies, observers, followed_body, sim_delta, arrival_distance);
+            for &index in &met {
+                notice = Some((format!("Objective complete: {}", objectives.label(index)), time));
+            }
+            // The last one brings up the checklist with the total time
+            if let Some(elapsed) = objectives.finished_at().filter(|_| !met.is_empty()) {
+                notice = Some((format!("All objectives complete in {}", units.duration(elapsed)), time));
+                show_objectives = true;
+            }
+        }
+
+        if let Some(benchmark) = &benchmark {
+            let (target, position) = benchmark.camera(&celestial_bodies);
+            camera.target = target;
+            camera.move_to(position);
+        }
+
+        // The ship moves last, after whatever it flies among
+        scene_graph.place_ship(&mut spaceship);
+
+        // Readouts follow the ship when it's out, the camera otherwise
+        let observer = if show_ship { spaceship.position } else { camera.position };
+        // Photo mode looks through its own camera and leaves the heat out
+        let sun_heat = sun_haze.heat(&celestial_bodies, observer).filter(|_| photo.is_none());
+        if !frozen {
+            telemetry.update(observer, &celestial_bodies, frame_delta);
+            warp_energy.recharge(observer, &celestial_bodies, frame_delta);
+        }
+
+        // Photo mode looks through its own camera. On a surface the ground
+        // is much nearer than the near plane usually allows
+        let (view_matrix, fov, near) = match &photo {
+            Some(shot) => (shot.view_matrix(), shot.fov, NEAR_PLANE),
+            None => (
+                sun_heat.map_or(identity(), |heat| heat.shake(time)) * camera.look_at(),
+                cruise.fov(DEFAULT_FOV),
+                if surface.is_some() { SURFACE_NEAR_PLANE } else { NEAR_PLANE },
+            ),
+        };
+        sun_visibility.update(&celestial_bodies, photo.as_ref().map_or(camera.position, |shot| shot.position));
+
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.lap(Stage::Update);
+        }
+
+        // The main view fills the frame unless the screen is split; photo
+        // mode always has the whole frame
+        let full = framebuffer.full_viewport();
+        let split = (split_screen && photo.is_none()).then(|| full.split(DIVIDER_WIDTH));
+        let main_viewport = split.map_or(full, |(left, _)| left);
+        if split.is_some() {
+            overview.set_target(celestial_bodies[0].position);
+        }
+        // Only the main view widens in cruise
+        let overview_view = split.map(|(_, right)| (right, overview.look_at(), DEFAULT_FOV, NEAR_PLANE));
+        let views = std::iter::once((main_viewport, view_matrix, fov, near)).chain(overview_view);
+        let projection = near_projection(view_matrix, fov, near, main_viewport);
+
+        // Every star lights the scene
+        let lights = collect_lights(&celestial_bodies);
+        let lighting = Lighting { lights: &lights, ambient: ambient.light(), exposure: exposure.multiplier(), heat: heat.enabled.then_some(heat), detail: low_spec.detail(), shading: low_spec.shading(shading) };
+        let ship = show_ship.then_some(&spaceship);
+        // Hohmann transfer between the two measured bodies; only the
+        // scripted circular orbits have the geometry it assumes
+        let transfer = match measurement {
+            Some((a, Some(b))) if simulation_mode == SimulationMode::Kinematic => TransferPlan::between(&celestial_bodies, a, b),
+            _ => None,
         };
-        
-        render(&mut framebuffer, &skybox_uniforms, &skybox_vertices);
-        */
-
-        // Update celestial bodies
-        let positions: Vec<Vec3> = celestial_bodies.iter().map(|body| body.position).collect();
-        for body in &mut celestial_bodies {
-            body.update(0.016, &positions);
-        }
-
-        // Get sun position for lighting (sun is always the first body - index 0)
-        let sun_position = celestial_bodies[0].position;
-
-        // Render each celestial body individually (following the recommendation)
-        for (index, body) in celestial_bodies.iter().enumerate() {
-            // Set the shader for this specific model
-            let model_matrix = body.get_model_matrix();
-            let is_sun = index == 0; // First body is the sun
-            
-            let uniforms = Uniforms { 
-                model_matrix,
-                view_matrix,
-                projection_matrix,
-                viewport_matrix,
-                light_position: sun_position,
-                is_light_source: is_sun,
-                shader_type: body.shader_type,  // Use the body's specific shader type
-                time,
+        let drawing = Instant::now();
+        if stereo.enabled {
+            // Overlays below are drawn once, from the camera between the eyes
+            let mut right_eye = framebuffer.blank(framebuffer_width, framebuffer_height);
+            for (viewport, view, fov, near) in views {
+                let left = near_projection(stereo.eye_view(&view, Eye::Left), fov, near, viewport);
+                let right = near_projection(stereo.eye_view(&view, Eye::Right), fov, near, viewport);
+                framebuffer.set_clip(viewport);
+                right_eye.set_clip(viewport);
+                draw_scene(&mut framebuffer, &left, lighting, &celestial_bodies, ship, time, 1);
+                draw_scene(&mut right_eye, &right, lighting, &celestial_bodies, ship, time, 1);
+                sky.draw(&mut framebuffer, &left, time);
+                sky.draw(&mut right_eye, &right, time);
+            }
+            anaglyph(&mut framebuffer.buffer, &right_eye.buffer);
+        } else if retro.enabled {
+            // Each view is drawn small, loses colors and is stretched back
+            // up, depth included so the effects below still work
+            for (viewport, view, fov, near) in views {
+                let mut low = retro.buffer_for(&framebuffer, viewport);
+                let low_projection = ScreenProjection {
+                    snap_to_pixels: retro.snap_vertices,
+                    affine: retro.affine,
+                    ..near_projection(view, fov, near, low.full_viewport())
+                };
+                draw_scene(&mut low, &low_projection, lighting, &celestial_bodies, ship, time, 1);
+                sky.draw(&mut low, &low_projection, time);
+                retro.quantize(&mut low.buffer, low.width);
+                framebuffer.set_clip(viewport);
+                framebuffer.upscale(&low, viewport);
+            }
+        } else if let Some(divisor) = low_spec.render_divisor() {
+            // Drawn small and stretched back up like retro, without its look
+            for (viewport, view, fov, near) in views {
+                let mut low = framebuffer.blank((viewport.width / divisor).max(1), (viewport.height / divisor).max(1));
+                let low_projection = near_projection(view, fov, near, low.full_viewport());
+                draw_scene(&mut low, &low_projection, lighting, &celestial_bodies, ship, time, 1);
+                sky.draw(&mut low, &low_projection, time);
+                framebuffer.set_clip(viewport);
+                framebuffer.upscale(&low, viewport);
+            }
+        } else {
+            for (viewport, view, fov, near) in views {
+                framebuffer.set_clip(viewport);
+                let projection = near_projection(view, fov, near, viewport);
+                draw_scene(&mut framebuffer, &projection, lighting, &celestial_bodies, ship, time, 1);
+                sky.draw(&mut framebuffer, &projection, time);
+            }
+        }
+        // Stars rush past the main view in cruise
+        if let Some(motion) = cruise.streak(&camera).filter(|_| photo.is_none()) {
+            framebuffer.set_clip(main_viewport);
+            sky.draw_streaks(&mut framebuffer, &projection, motion, time);
+        }
+        low_spec.record(drawing.elapsed().as_secs_f32());
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.lap(Stage::Scene);
+        }
+        // Next frame's exposure comes from this one, before any overlays
+        exposure.update(log_average_luminance(&framebuffer), sun_visibility.glare(&projection, main_viewport, &celestial_bodies), frame_delta);
+        // Blurred before anything is drawn on top, so overlays and the HUD stay sharp
+        if low_spec.post_processing() {
+            motion_blur.apply(&mut framebuffer, &projection, main_viewport);
+            chromatic_aberration(&mut framebuffer, main_viewport, warp.state(time).distortion());
+            draw_lensing(&mut framebuffer, &projection, &celestial_bodies, main_viewport);
+        }
+        // Overlays belong to the main view, the HUD to the whole frame
+        framebuffer.set_clip(main_viewport);
+        for (index, star) in celestial_bodies.iter().enumerate().filter(|(_, body)| body.is_star()) {
+            draw_corona(&mut framebuffer, &projection, star, sun_visibility.of(index));
+        }
+        if let Some(sun_heat) = sun_heat.filter(|_| low_spec.post_processing()) {
+            sun_heat.apply(&mut framebuffer, main_viewport, time);
+        }
+        // A passage through a portal whites out the view for a moment
+        if portals.flash() > 0.0 {
+            framebuffer.blend_rect(main_viewport.x as i32, main_viewport.y as i32, main_viewport.width, main_viewport.height, 0xFFFFFF, portals.flash());
+        }
+        impacts.draw(&mut framebuffer, &projection, &celestial_bodies, time);
+        eruptions.draw(&mut framebuffer, &projection, &celestial_bodies);
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.lap(Stage::Effects);
+        }
+
+        if let Some(shot) = &mut photo {
+            if input.pressed(Action::FocusCenter) {
+                if let Some(distance) = focus_at(&framebuffer, &projection, framebuffer_width / 2, framebuffer_height / 2) {
+                    shot.focus_on(distance);
+                }
+            }
+            depth_of_field(&mut framebuffer, &projection, shot.focus, shot.aperture);
+            if shot.show_focus {
+                draw_focus_plane(&mut framebuffer, &projection, shot.focus);
+            }
+            shot.finish(&mut framebuffer.buffer, framebuffer_width, framebuffer_height);
+            filters.apply(&mut framebuffer, frame_number);
+            grading.apply(&mut framebuffer.buffer);
+            if input.pressed(Action::PhotoCapture) {
+                let pixels = take_photo(shot, &framebuffer, lighting, &celestial_bodies, ship, &sky, time);
+                shot.set_message(match save_photo(recorder.directory(), &pixels, framebuffer_width, framebuffer_height) {
+                    Ok(path) => format!("Saved {}", path),
+                    Err(err) => format!("Photo failed: {}", err),
+                });
+            }
+        } else {
+            // Under every other overlay, like the sky they belong to
+            if show_constellations {
+                sky.draw_figures(&mut framebuffer, &projection, palette.color(UiColor::Constellation));
+            }
+            if framebuffer.ids.is_some() {
+                draw_selection_outline(&mut framebuffer, body_id(selected_body), palette.color(UiColor::Selection), time);
+            }
+            if show_ship && spaceship.shield_strength() > 0.0 {
+                draw_shield(&mut framebuffer, &projection, spaceship.position, spaceship.bounding_radius(), palette.color(UiColor::Shield), spaceship.shield_strength());
+            }
+            // Only free flight has a path worth predicting; following, the
+            // autopilot and warps all move the ship along their own
+            if show_ship && show_trajectory && followed_body.is_none() && autopilot.is_none() && !warp.is_warping() {
+                let gravity = simulation_mode == SimulationMode::NBody;
+                let path = predict_path(spaceship.position, spaceship.velocity, &celestial_bodies, gravity, spaceship.bounding_radius());
+                draw_predicted_path(&mut framebuffer, &projection, &path, palette.color(UiColor::Trajectory), palette.color(UiColor::Warning));
+            }
+
+            // Orbit rings only describe the scripted orbits, n-body paths aren't circles
+            let fade = projection.fade_behind(overlays.occlusion_fade);
+            if simulation_mode == SimulationMode::Kinematic {
+                for body in celestial_bodies.iter().filter(|body| orbit_display.shows(body, &celestial_bodies)) {
+                    if let Some(center) = body.orbit_center(&celestial_bodies) {
+                        // Accessible palettes keep one orbit color they can vouch for
+                        let color = if palette == PaletteMode::Normal { body.orbit_style.color } else { palette.color(UiColor::Orbit) };
+                        draw_orbit(&mut framebuffer, &projection, body, center, color, palette.color(UiColor::OrbitArrow), fade);
+                    }
+                }
+            }
+
+            // Trails show where bodies have actually been, so they matter most
+            // for eccentric orbits and n-body mode
+            if show_trails {
+                for (index, body) in celestial_bodies.iter().enumerate() {
+                    if !body.is_virtual() {
+                        draw_trail(&mut framebuffer, &projection, body, trails.points(index), body.color.to_pixel(), fade);
+                    }
+                }
+            }
+
+            // Overlays are drawn last, on top of the scene
+            if let Some((a, Some(b))) = measurement {
+                let (a, b) = (celestial_bodies[a].position, celestial_bodies[b].position);
+                draw_measurement(&mut framebuffer, &projection, a, b, &units.distance((b - a).magnitude()), palette.color(UiColor::Measure));
+            }
+            if let Some(plan) = &transfer {
+                draw_transfer(&mut framebuffer, &projection, plan, units, palette.color(UiColor::Measure), palette.color(UiColor::Title));
+            }
+            if show_lagrange {
+                let selected = &celestial_bodies[selected_body];
+                let primary = selected.parent_index.map(|i| &celestial_bodies[i]);
+                if let Some(points) = primary.and_then(|primary| lagrange_points(primary, selected)) {
+                    for point in &points {
+                        draw_marker(&mut framebuffer, &projection, point.position, point.name, palette.color(UiColor::Lagrange));
+                    }
+                }
+            }
+            for (index, body) in celestial_bodies.iter().enumerate() {
+                if body.is_virtual() {
+                    continue;
+                }
+                // Bodies with a warp key show it in front of their name
+                let label = match warp_targets.iter().position(|&target| target == index) {
+                    Some(slot) => format!("{} {}", slot + 1, body.name),
+                    None => body.name.clone(),
+                };
+                draw_label(&mut framebuffer, &projection, body, &label, palette.color(UiColor::Label));
+            }
+            // The inset goes on after the main view's overlays so none of
+            // them land on it, and is rendered at a fraction of its size
+            let inset = inset_mode.view(&camera, &celestial_bodies[selected_body]);
+            let rect = inset_rect(main_viewport);
+            if let Some((inset_view, inset_fov, label)) = inset.filter(|_| rect.width > 0 && rect.height > 0) {
+                let mut small = framebuffer.blank(rect.width / INSET_DOWNSCALE, rect.height / INSET_DOWNSCALE);
+                let inset_projection = scene_projection(inset_view, inset_fov, small.full_viewport());
+                draw_scene(&mut small, &inset_projection, lighting, &celestial_bodies, ship, time, 1);
+                framebuffer.blit_scaled(&small, rect);
+                draw_inset_frame(&mut framebuffer, palette, rect, &label);
+            }
+
+            framebuffer.set_clip(full);
+            // Filters take the finished view, the HUD stays clean on top
+            if low_spec.post_processing() {
+                filters.apply(&mut framebuffer, frame_number);
+                grading.apply(&mut framebuffer.buffer);
+            }
+            if let Some((left, right)) = split {
+                draw_viewport_labels(&mut framebuffer, palette, &[(left, "MAIN VIEW"), (right, "OVERVIEW")], overview_active as usize);
+            }
+            let selected = &celestial_bodies[selected_body];
+            let parent = selected.parent_index.map(|i| &celestial_bodies[i]);
+            let conditions = Conditions {
+                temperature: lighting.heat.filter(|_| !selected.is_star()).map(|heat| heat.temperature(&lights, selected.position, selected.albedo)),
+                day_length: day_length(&celestial_bodies, selected_body),
+                daylight: surface.as_ref().filter(|view| view.body == selected_body).and_then(|view| view.daylight(&celestial_bodies, &lights)),
             };
+            draw_info_panel(&mut framebuffer, palette, units, selected, parent, show_lagrange, &conditions);
+            if heat.enabled {
+                draw_heat_legend(&mut framebuffer, palette, &heat);
+            }
+            let mut right_top = 10;
+            if let Some((a, b)) = measurement {
+                let b = b.map(|b| &celestial_bodies[b]);
+                right_top = draw_measure_panel(&mut framebuffer, palette, units, &celestial_bodies[a], b, camera.position, transfer.as_ref()) + 8;
+            }
+            if let Some(tuner) = &tuning {
+                right_top = draw_tuning_panel(&mut framebuffer, palette, tuner, &celestial_bodies[tuner.body], right_top) + 8;
+            }
+            if show_objectives && !objectives.is_empty() {
+                draw_objectives(&mut framebuffer, palette, units, &objectives, right_top);
+            }
+            if let Some(warning) = &proximity {
+                draw_proximity_warning(&mut framebuffer, palette, units, &celestial_bodies[warning.body], warning.clearance, warning.closing_speed, time);
+            }
+            if let Some(sun_heat) = sun_heat {
+                let kelvin = heat.temperature(&lights, observer, 0.0);
+                draw_heat_warning(&mut framebuffer, palette, &celestial_bodies[sun_heat.star], kelvin, proximity.is_some(), time);
+            }
+            if let Some(pending) = &alignment {
+                draw_alignment_prompt(&mut framebuffer, palette, units, pending, &celestial_bodies);
+            }
+            if let Some(station) = dockable {
+                draw_docking_prompt(&mut framebuffer, palette, &celestial_bodies[station]);
+            }
+            if let Some(sequence) = &docking {
+                draw_docked_menu(&mut framebuffer, palette, sequence, &celestial_bodies[sequence.station], spaceship.damage());
+            }
+            if show_flight_strip {
+                let mode = if docking.as_ref().is_some_and(Docking::is_docked) {
+                    "DOCKED"
+                } else if docking.is_some() {
+                    "DOCKING"
+                } else if autopilot.is_some() {
+                    "AUTOPILOT"
+                } else if warp.is_warping() {
+                    "WARP"
+                } else if followed_body.is_some() {
+                    "ORBIT"
+                } else if surface.is_some() {
+                    "SURFACE"
+                } else {
+                    "FREE"
+                };
+                let orbit = followed_body.map(|i| (&celestial_bodies[i], camera.distance));
+                let nearest = telemetry.nearest.map(|i| (&celestial_bodies[i], telemetry.altitude));
+                let target = &celestial_bodies[selected_body];
+                let target_distance = (target.position - observer).magnitude();
+                let readout = FlightReadout { mode, orbit, speed: telemetry.speed, nearest, target: (target, target_distance) };
+                draw_flight_strip(&mut framebuffer, palette, units, &readout);
+            }
+            if show_thumbnails {
+                // At most one body is rendered again per frame, and only if
+                // it looks different from its thumbnail
+                thumbnails.refresh(&celestial_bodies, &lights, time, heat.enabled, &framebuffer, |image, body, view, fov| {
+                    let projection = scene_projection(view, fov, image.full_viewport());
+                    draw_scene(image, &projection, lighting, std::slice::from_ref(body), None, time, 1);
+                });
+                draw_warp_thumbnails(&mut framebuffer, palette, &thumbnails, &celestial_bodies, selected_body);
+            }
+            let warp_destination = warp.destination().map(|i| celestial_bodies[i].name.as_str());
+            draw_warp_status(&mut framebuffer, palette, warp.state(time), warp_destination, &warp_energy, time);
+            let following = followed_body.map(|i| celestial_bodies[i].name.as_str());
+            let flying_to = autopilot.as_ref().map(|pilot| celestial_bodies[pilot.target].name.as_str());
+            // Hull integrity next to the name once the ship has taken a hit
+            let ship_status = show_ship.then(|| match spaceship.damage() {
+                damage if damage > 0.0 => format!("{} HULL {:.0}%", spaceship.design().name, (1.0 - damage) * 100.0),
+                _ => spaceship.design().name.clone(),
+            });
+            draw_status_line(
+                &mut framebuffer,
+                palette,
+                time_scale,
+                simulation_mode,
+                scale_transition.profile(),
+                &[("FOLLOW", following), ("AUTOPILOT", flying_to), ("SHIP", ship_status.as_deref())],
+            );
+            if show_help {
+                draw_help(&mut framebuffer, palette, &bindings);
+            }
+            if let Some(menu) = &pause_menu {
+                draw_pause_menu(&mut framebuffer, palette, menu, &low_spec);
+            }
+        }
+
+        if daltonize_frames {
+            daltonize(&mut framebuffer.buffer);
+        }
 
-            // Set the color for this model
-            framebuffer.set_current_color(body.color);
+        // Clips get everything on screen except their own indicator
+        recorder.capture(&framebuffer.buffer, frame_delta);
+        recorder.update(time);
+        draw_recording_indicator(&mut framebuffer, palette, &recorder, time);
+        if let Some((text, since)) = &notice {
+            if time - since < NOTICE_SECONDS {
+                draw_notice(&mut framebuffer, palette, text);
+            }
+        }
+        if let Some(shot) = &photo {
+            draw_photo_controls(&mut framebuffer, palette, units, shot);
+        }
 
-            // Render this specific model
-            render(&mut framebuffer, &uniforms, &body.vertices);
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.lap(Stage::Overlays);
         }
+        match &mut backend {
+            Some(backend) => {
+                backend.present(&framebuffer.buffer, framebuffer_width, framebuffer_height).unwrap();
 
-        window
-            .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
-            .unwrap();
+                // Uncapped runs show the measured frame rate in the title bar
+                let fps = if clock.capped { 0.0 } else { clock.fps.round() };
+                if fps != shown_fps {
+                    let title = if clock.capped { WINDOW_TITLE.to_string() } else { format!("{} - {} FPS", WINDOW_TITLE, fps) };
+                    backend.set_title(&title);
+                    shown_fps = fps;
+                }
+            }
+            // Headless benchmarks only time the drawing
+            None if benchmark.is_some() => {}
+            None => {
+                let path = std::path::Path::new(&options.out).join(format!("frame_{:05}.png", frame_number));
+                write_png(&path, &framebuffer.buffer, framebuffer_width, framebuffer_height).expect("Failed to write frame");
+            }
+        }
+        frame_number += 1;
+        if let Some(benchmark) = &mut benchmark {
+            benchmark.lap(Stage::Present);
+            if benchmark.end_frame() {
+                break;
+            }
+        }
+    }
 
-        std::thread::sleep(frame_delay);
+    // Printed once the terminal backend has given the screen back
+    drop(backend);
+    if let Some(benchmark) = &benchmark {
+        print!("{}", benchmark.report());
     }
 }
 
-fn handle_camera_input(window: &Window, camera: &mut Camera) {
+// Every drawn body is a warp target, in scene order, one per number key
+fn collect_warp_targets(bodies: &[CelestialBody], keys: usize) -> Vec<usize> {
+    (0..bodies.len())
+        .filter(|&i| !bodies[i].is_virtual())
+        .take(keys)
+        .collect()
+}
+
+// Dot size (1-3 pixels) and color for a body drawn as a point; both grow as
+// it gets bigger on screen, so bigger and closer bodies look brighter
+fn point_appearance(color: Color, projected_radius: f32) -> (i32, u32) {
+    let fraction = (projected_radius / POINT_SWAP_PIXELS).clamp(0.0, 1.0);
+    let size = 1 + (fraction * 2.0).round() as i32;
+    let brightness = 0.35 + 0.65 * fraction;
+    (size, (color * brightness).to_pixel())
+}
+
+// Closest the camera may get to a body, from its real mesh extent
+fn collision_distance(body: &CelestialBody) -> f32 {
+    (body.bounding_radius() * COLLISION_MARGIN).max(MIN_CAMERA_DISTANCE)
+}
+
+// Where warps and the autopilot leave the camera: a few radii out, never
+// inside the collision distance
+fn arrival_distance(body: &CelestialBody) -> f32 {
+    (body.bounding_radius() * WARP_DISTANCE_FACTOR).max(collision_distance(body))
+}
+
+// Returns true when any camera key was held this frame
+fn handle_camera_input(input: &InputFrame, camera: &mut Camera) -> bool {
     // Camera orbital movement around the sun
-    if window.is_key_down(Key::Right) {
+    if input.held(Action::OrbitRight) {
         camera.orbit(PI / 50.0, 0.0); // Rotate around Y axis
     }
-    if window.is_key_down(Key::Left) {
+    if input.held(Action::OrbitLeft) {
         camera.orbit(-PI / 50.0, 0.0); // Rotate around Y axis
     }
-    if window.is_key_down(Key::Up) {
+    if input.held(Action::OrbitUp) {
         camera.orbit(0.0, -PI / 50.0); // Rotate around X axis (elevation)
     }
-    if window.is_key_down(Key::Down) {
+    if input.held(Action::OrbitDown) {
         camera.orbit(0.0, PI / 50.0); // Rotate around X axis (elevation)
     }
     
     // Zoom in/out (change distance to target)
-    if window.is_key_down(Key::S) {
+    if input.held(Action::ZoomOut) {
         camera.zoom(20.0); // Move away from sun
     }
-    if window.is_key_down(Key::A) {
+    if input.held(Action::ZoomIn) {
         camera.zoom(-20.0); // Move closer to sun
     }
+
+    [Action::OrbitRight, Action::OrbitLeft, Action::OrbitUp, Action::OrbitDown, Action::ZoomOut, Action::ZoomIn]
+        .iter()
+        .any(|&action| input.held(action))
 }
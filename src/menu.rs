@@ -0,0 +1,70 @@
+// Pause menu entries, top to bottom
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MenuEntry {
+    Resume,
+    ReloadScene,
+    LowSpec,
+    ToggleFullscreen,
+    Quit,
+}
+
+impl MenuEntry {
+    pub const ALL: [MenuEntry; 5] = [
+        MenuEntry::Resume,
+        MenuEntry::ReloadScene,
+        MenuEntry::LowSpec,
+        MenuEntry::ToggleFullscreen,
+        MenuEntry::Quit,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuEntry::Resume => "Resume",
+            MenuEntry::ReloadScene => "Reload scene",
+            MenuEntry::LowSpec => "Low spec",
+            MenuEntry::ToggleFullscreen => "Toggle fullscreen",
+            MenuEntry::Quit => "Quit",
+        }
+    }
+}
+
+// Escape opens this instead of quitting. The simulation is frozen while it
+// is up, but the scene is still drawn behind it
+pub struct PauseMenu {
+    selected: usize,
+    // Shown under the entries, e.g. why a reload failed
+    pub message: Option<String>,
+}
+
+impl PauseMenu {
+    // Opens with Quit highlighted, so Escape then Enter still quits
+    pub fn open() -> Self {
+        PauseMenu { selected: MenuEntry::ALL.len() - 1, message: None }
+    }
+
+    pub fn selected(&self) -> MenuEntry {
+        MenuEntry::ALL[self.selected]
+    }
+
+    // Moves the highlight up (-1) or down (+1), wrapping around
+    pub fn move_selection(&mut self, step: i32) {
+        let count = MenuEntry::ALL.len() as i32;
+        self.selected = (self.selected as i32 + step).rem_euclid(count) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_on_quit_and_wraps() {
+        let mut menu = PauseMenu::open();
+        assert_eq!(menu.selected(), MenuEntry::Quit);
+        menu.move_selection(1);
+        assert_eq!(menu.selected(), MenuEntry::Resume);
+        menu.move_selection(-1);
+        menu.move_selection(-1);
+        assert_eq!(menu.selected(), MenuEntry::ToggleFullscreen);
+    }
+}
@@ -0,0 +1,153 @@
+use nalgebra_glm::{Vec2, Vec4, Mat4};
+use serde::Deserialize;
+use crate::color::Color;
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::overlay::ScreenProjection;
+
+// Colors averaged along each pixel's streak
+const SAMPLES: usize = 6;
+// Pixels that moved less than this are left sharp
+const MIN_STREAK: f32 = 0.5;
+
+// [motion_blur] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct MotionBlurSettings {
+    // Off unless turned on here or with J
+    #[serde(default)]
+    pub enabled: bool,
+    // Share of a frame's motion each streak covers, like a shutter angle
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+    // Longest streak in pixels, however fast the camera turns
+    #[serde(default = "default_max_pixels")]
+    pub max_pixels: f32,
+}
+
+impl Default for MotionBlurSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: default_strength(),
+            max_pixels: default_max_pixels(),
+        }
+    }
+}
+
+fn default_strength() -> f32 {
+    0.5
+}
+
+fn default_max_pixels() -> f32 {
+    24.0
+}
+
+// Camera motion blur. Every pixel's world position is rebuilt from its
+// depth, projected with last frame's matrices, and the pixel is smeared along
+// the difference. Only the camera's motion counts, a body moving on its own
+// stays sharp unless the camera follows it
+pub struct MotionBlur {
+    pub settings: MotionBlurSettings,
+    // Last frame's world-to-screen matrix, and the viewport it drew into
+    previous: Option<(Mat4, Viewport)>,
+}
+
+impl MotionBlur {
+    pub fn new(settings: MotionBlurSettings) -> Self {
+        Self { settings, previous: None }
+    }
+
+    pub fn toggle(&mut self) -> bool {
+        self.settings.enabled = !self.settings.enabled;
+        // Motion from before it was switched off doesn't count
+        self.previous = None;
+        self.settings.enabled
+    }
+
+    // Blurs `viewport` of the framebuffer, which `projection` was just
+    // rendered into. Run before any overlays so those stay sharp
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, viewport: Viewport) {
+        if !self.settings.enabled {
+            return;
+        }
+        let current = projection.viewport_matrix * projection.projection_matrix * projection.view_matrix;
+        // Nothing to compare with on the first frame, or after the view was resized
+        let Some((previous, _)) = self.previous.replace((current, viewport)).filter(|(_, last)| *last == viewport) else { return };
+        let Some(unproject) = current.try_inverse() else { return };
+        // Screen position now to screen position last frame
+        let reproject = previous * unproject;
+
+        let source = framebuffer.buffer.clone();
+        let width = framebuffer.width;
+        let (min_x, max_x) = (viewport.x as f32, (viewport.x + viewport.width - 1) as f32);
+        let (min_y, max_y) = (viewport.y as f32, (viewport.y + viewport.height - 1) as f32);
+        for y in viewport.y..viewport.y + viewport.height {
+            for x in viewport.x..viewport.x + viewport.width {
+                let index = y * width + x;
+                // The background counts as infinitely far, so it still
+                // streaks when the camera turns
+                let depth = framebuffer.zbuffer[index];
+                let depth = if depth.is_finite() { depth } else { 1.0 };
+                let then = reproject * Vec4::new(x as f32, y as f32, depth, 1.0);
+                if then.w <= 0.001 {
+                    continue;
+                }
+                let velocity = (Vec2::new(x as f32, y as f32) - then.xy() / then.w) * self.settings.strength;
+                let length = velocity.magnitude();
+                if !length.is_finite() || length < MIN_STREAK {
+                    continue;
+                }
+                let streak = velocity * (length.min(self.settings.max_pixels) / length);
+
+                let mut sum = Color::from_hex(source[index]).to_vec3();
+                for sample in 1..SAMPLES {
+                    // Spread evenly from half a streak behind to half ahead
+                    let t = sample as f32 / (SAMPLES - 1) as f32 - 0.5;
+                    let sample_x = (x as f32 + streak.x * t).round().clamp(min_x, max_x) as usize;
+                    let sample_y = (y as f32 + streak.y * t).round().clamp(min_y, max_y) as usize;
+                    sum += Color::from_hex(source[sample_y * width + sample_x]).to_vec3();
+                }
+                framebuffer.buffer[index] = Color::from_vec3(sum / SAMPLES as f32).to_pixel();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec3;
+    use crate::camera::look_at_matrix;
+
+    #[test]
+    fn only_a_moving_camera_blurs() {
+        let viewport = Viewport { x: 0, y: 0, width: 40, height: 30 };
+        let mut framebuffer = Framebuffer::new(40, 30);
+        let mut blur = MotionBlur::new(MotionBlurSettings { enabled: true, ..MotionBlurSettings::default() });
+        let view = |x: f32| look_at_matrix(Vec3::new(x, 0.0, 100.0), Vec3::new(x, 0.0, 0.0), Vec3::y());
+
+        // A hard edge down the middle of a wall
+        let draw = |framebuffer: &mut Framebuffer| {
+            framebuffer.clear();
+            for y in 0..30 {
+                for x in 0..20 {
+                    framebuffer.buffer[y * 40 + x] = 0xFFFFFF;
+                }
+            }
+            for depth in framebuffer.zbuffer.iter_mut() {
+                *depth = 0.9;
+            }
+        };
+
+        draw(&mut framebuffer);
+        blur.apply(&mut framebuffer, &crate::scene_projection(view(0.0), 1.0, viewport), viewport);
+        draw(&mut framebuffer);
+        blur.apply(&mut framebuffer, &crate::scene_projection(view(0.0), 1.0, viewport), viewport);
+        assert_eq!(framebuffer.buffer[15 * 40 + 20], 0x000000, "a still camera leaves the frame alone");
+
+        draw(&mut framebuffer);
+        blur.apply(&mut framebuffer, &crate::scene_projection(view(40.0), 1.0, viewport), viewport);
+        let edge = framebuffer.buffer[15 * 40 + 20];
+        assert!(edge != 0x000000 && edge != 0xFFFFFF, "the edge smears when panning, got {:06X}", edge);
+        assert_eq!(framebuffer.buffer[15 * 40], 0xFFFFFF);
+    }
+}
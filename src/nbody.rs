@@ -0,0 +1,164 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+
+// Largest step the integrator takes. Leapfrog stays stable while a step is a
+// small fraction of the shortest orbital period in the scene (roughly 1/100 or
+// less); the default scene's fastest orbit (the Moon, about 20 s) gets over a
+// thousand steps per revolution. Higher time scales take more steps per frame
+// instead of longer ones, so 64x costs 64 steps but is just as stable as 1x.
+pub const MAX_STEP: f32 = 0.016;
+
+// Softening length, keeps close encounters from producing huge accelerations
+const SOFTENING: f32 = 1.0;
+
+// Gives every orbiting body the velocity of a circular orbit around its parent,
+// on top of the parent's own velocity, then takes the velocity of the center
+// of mass off every body so the system as a whole stays put instead of
+// drifting away with the planets' momentum. Bodies must already be at their
+// starting positions; `order` is the hierarchy order so parents get their
+// velocity first.
+pub fn init_circular_velocities(bodies: &mut [CelestialBody], order: &[usize]) {
+    for &i in order {
+        let Some(parent_idx) = bodies[i].parent_index else { continue };
+        let parent_position = bodies[parent_idx].position;
+        let parent_velocity = bodies[parent_idx].velocity;
+        let parent_mass = bodies[parent_idx].mass;
+
+        let offset = bodies[i].position - parent_position;
+        let radius = offset.magnitude();
+        if radius <= f32::EPSILON {
+            continue;
+        }
+        // Counter-clockwise in the XZ plane like the kinematic orbits, or
+        // clockwise for retrograde bodies
+        let tangent = Vec3::new(-offset.z, 0.0, offset.x) * (bodies[i].orbit_direction() / radius);
+        let speed = (parent_mass / radius).sqrt();
+        bodies[i].velocity = parent_velocity + tangent * speed;
+    }
+
+    let total_mass: f32 = bodies.iter().map(|body| body.mass).sum();
+    if total_mass > 0.0 {
+        let momentum = bodies.iter().fold(Vec3::zeros(), |sum, body| sum + body.velocity * body.mass);
+        let drift = momentum / total_mass;
+        for body in bodies.iter_mut() {
+            body.velocity -= drift;
+        }
+    }
+}
+
+fn accelerations(bodies: &[CelestialBody]) -> Vec<Vec3> {
+    let mut result = vec![Vec3::new(0.0, 0.0, 0.0); bodies.len()];
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let offset = bodies[j].position - bodies[i].position;
+            let distance_sq = offset.magnitude_squared() + SOFTENING * SOFTENING;
+            let inv_distance_cubed = 1.0 / (distance_sq * distance_sq.sqrt());
+            // mass holds G * M, so the gravitational constant is already folded in
+            result[i] += offset * (bodies[j].mass * inv_distance_cubed);
+            result[j] -= offset * (bodies[i].mass * inv_distance_cubed);
+        }
+    }
+    result
+}
+
+// One kick-drift-kick leapfrog step
+fn step(bodies: &mut [CelestialBody], dt: f32) {
+    let acc = accelerations(bodies);
+    for (body, a) in bodies.iter_mut().zip(&acc) {
+        body.velocity += a * (dt * 0.5);
+        body.position += body.velocity * dt;
+    }
+    let acc = accelerations(bodies);
+    for (body, a) in bodies.iter_mut().zip(&acc) {
+        body.velocity += a * (dt * 0.5);
+    }
+}
+
+// Advances the simulation by delta_time, split into steps of at most MAX_STEP
+pub fn advance(bodies: &mut [CelestialBody], delta_time: f32) {
+    let steps = (delta_time / MAX_STEP).ceil().max(1.0) as usize;
+    let dt = delta_time / steps as f32;
+    for _ in 0..steps {
+        step(bodies, dt);
+    }
+    for body in bodies.iter_mut() {
+        body.spin(delta_time);
+    }
+    for i in 0..bodies.len() {
+        // Keep the local positions in step so the scene graph reproduces the
+        // integrated world positions
+        let offset = match bodies[i].parent_index {
+            Some(parent_idx) => bodies[i].position - bodies[parent_idx].position,
+            None => bodies[i].position,
+        };
+        bodies[i].local_position = offset;
+        if bodies[i].tidally_locked && bodies[i].parent_index.is_some() {
+            bodies[i].apply_tidal_lock(offset);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec2;
+
+    // The default scene's sun and rocky planet, with a moon close enough to
+    // stay bound to the planet
+    fn system() -> Vec<CelestialBody> {
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let body = |name: &str, x: f32, mass: f32, parent: Option<usize>| {
+            let mut body = CelestialBody::new_planet(name.to_string(), vec![rim.clone()], Vec3::zeros(), 0.0, 0.0, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+            body.position = Vec3::new(x, 0.0, 0.0);
+            (body.mass, body.parent_index) = (mass, parent);
+            body
+        };
+        let mut bodies = vec![body("Sun", 0.0, 625000.0, None), body("Planet", 250.0, 6250.0, Some(0)), body("Moon", 262.0, 60.0, Some(1))];
+        init_circular_velocities(&mut bodies, &[0, 1, 2]);
+        bodies
+    }
+
+    fn momentum(bodies: &[CelestialBody]) -> Vec3 {
+        bodies.iter().fold(Vec3::zeros(), |sum, body| sum + body.velocity * body.mass)
+    }
+
+    // Kinetic plus potential, with the same softening as the accelerations
+    fn energy(bodies: &[CelestialBody]) -> f32 {
+        let mut energy: f32 = bodies.iter().map(|body| 0.5 * body.mass * body.velocity.magnitude_squared()).sum();
+        for i in 0..bodies.len() {
+            for j in (i + 1)..bodies.len() {
+                let distance_sq = (bodies[j].position - bodies[i].position).magnitude_squared() + SOFTENING * SOFTENING;
+                energy -= bodies[i].mass * bodies[j].mass / distance_sq.sqrt();
+            }
+        }
+        energy
+    }
+
+    #[test]
+    fn system_starts_at_rest_and_holds_together_at_64x() {
+        let mut bodies = system();
+        // Orbiting bodies still go round their parents
+        assert!(bodies[1].velocity.z > 0.0 && (bodies[2].velocity - bodies[1].velocity).z > 0.0);
+        let scale = bodies[1].mass * bodies[1].velocity.magnitude();
+        assert!(momentum(&bodies).magnitude() < scale * 1e-5);
+
+        // Ten seconds at 60 frames a second and 64x: about twenty orbits of
+        // the planet and 190 of the moon
+        let start = energy(&bodies);
+        let total_mass: f32 = bodies.iter().map(|body| body.mass).sum();
+        let center = |bodies: &[CelestialBody]| bodies.iter().fold(Vec3::zeros(), |sum, body| sum + body.position * body.mass) / total_mass;
+        let start_center = center(&bodies);
+        for _ in 0..600 {
+            advance(&mut bodies, 64.0 / 60.0);
+        }
+        assert!(((energy(&bodies) - start) / start).abs() < 1e-4, "energy {} -> {}", start, energy(&bodies));
+        assert!(momentum(&bodies).magnitude() < scale * 1e-4);
+        assert!((center(&bodies) - start_center).magnitude() < 0.05);
+        // Neither the planet nor its moon has wandered off
+        assert!(((bodies[1].position - bodies[0].position).magnitude() - 250.0).abs() < 2.0);
+        assert!(((bodies[2].position - bodies[1].position).magnitude() - 12.0).abs() < 0.5);
+    }
+}
@@ -1,7 +1,22 @@
 use tobj;
 use nalgebra_glm::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use crate::vertex::Vertex;
 
+// Faces meeting at a sharper angle than this keep a hard edge between them
+// when normals are smoothed
+const CREASE_ANGLE: f32 = 60.0;
+
+fn load_options() -> tobj::LoadOptions {
+    tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    }
+}
+
 pub struct Obj {
     meshes: Vec<Mesh>,
 }
@@ -15,15 +30,25 @@ struct Mesh {
 
 impl Obj {
     pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(filename, &tobj::LoadOptions {
-            single_index: true,
-            triangulate: true,
-            ..Default::default()
-        })?;
+        let file = File::open(filename).map_err(|_| tobj::LoadError::OpenFileFailed)?;
+        Self::parse(&mut BufReader::new(file))
+    }
 
+    // Material libraries are ignored, nothing here uses them
+    pub fn parse(reader: &mut impl BufRead) -> Result<Self, tobj::LoadError> {
+        let (models, _) = tobj::load_obj_buf(reader, &load_options(), |_| Err(tobj::LoadError::OpenFileFailed))?;
+        Self::from_models(models)
+    }
+
+    fn from_models(models: Vec<tobj::Model>) -> Result<Self, tobj::LoadError> {
         let meshes = models.into_iter().map(|model| {
             let mesh = model.mesh;
-            Mesh {
+            // Every index has to name a position, get_vertex_array relies on it
+            let positions = mesh.positions.len() / 3;
+            if mesh.indices.iter().any(|&index| index as usize >= positions) {
+                return Err(tobj::LoadError::FaceVertexOutOfBounds);
+            }
+            Ok(Mesh {
                 vertices: mesh.positions.chunks(3)
                     .map(|v| Vec3::new(v[0], -v[1], -v[2]))
                     .collect(),
@@ -34,12 +59,17 @@ impl Obj {
                     .map(|t| Vec2::new(t[0], 1.0 - t[1]))
                     .collect(),
                 indices: mesh.indices,
-            }
-        }).collect();
+            })
+        }).collect::<Result<Vec<_>, _>>()?;
 
         Ok(Obj { meshes })
     }
 
+    // Whether every mesh in the file came with normals
+    pub fn has_normals(&self) -> bool {
+        self.meshes.iter().all(|mesh| !mesh.normals.is_empty())
+    }
+
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
         let mut vertices = Vec::new();
 
@@ -60,3 +90,129 @@ impl Obj {
         vertices
     }
 }
+
+// Meshes exported without smoothing have a normal per face, which smooth
+// shading would light facet by facet. Each corner takes the faces around
+// its position instead, bigger ones counting for more, leaving out those
+// across a crease (the two sides of a ring, the panels of a hull). The old
+// normals only decide which side is out, so only meshes that have them
+// should be smoothed
+pub fn smooth_normals(vertices: &mut [Vertex]) {
+    let key = |position: Vec3| [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()];
+    let faces: Vec<Vec3> = vertices
+        .chunks_exact(3)
+        .map(|triangle| {
+            let (a, b, c) = (triangle[0].position, triangle[1].position, triangle[2].position);
+            let face = (b - a).cross(&(c - a));
+            if face.dot(&(triangle[0].normal + triangle[1].normal + triangle[2].normal)) < 0.0 { -face } else { face }
+        })
+        .collect();
+    let mut around: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (index, vertex) in vertices.iter().enumerate().take(faces.len() * 3) {
+        around.entry(key(vertex.position)).or_default().push(index / 3);
+    }
+
+    let crease = CREASE_ANGLE.to_radians().cos();
+    let normals: Vec<Option<Vec3>> = vertices
+        .iter()
+        .enumerate()
+        .map(|(index, vertex)| {
+            // Zero-area faces have no direction and keep their normals
+            let own = faces.get(index / 3)?.try_normalize(f32::EPSILON)?;
+            around[&key(vertex.position)]
+                .iter()
+                .map(|&face| faces[face])
+                .filter(|face| face.try_normalize(f32::EPSILON).is_some_and(|unit| unit.dot(&own) >= crease))
+                .fold(Vec3::zeros(), |sum, face| sum + face)
+                .try_normalize(f32::EPSILON)
+        })
+        .collect();
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        if let Some(normal) = normal {
+            vertex.normal = normal;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+    use proptest::prelude::*;
+
+    fn parse(bytes: &[u8]) -> Result<Obj, tobj::LoadError> {
+        Obj::parse(&mut std::io::Cursor::new(bytes))
+    }
+
+    #[test]
+    fn smoothing_rounds_spheres_and_keeps_creases() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/models/Planet.obj");
+        let obj = Obj::load(path).unwrap();
+        assert!(obj.has_normals());
+        // Copies of a corner with different normals, one per face
+        let seams = |vertices: &[Vertex]| vertices.iter().filter(|a| vertices.iter().any(|b| a.position == b.position && a.normal != b.normal)).count();
+        let mut sphere = obj.get_vertex_array();
+        assert!(seams(&sphere) > 0);
+        smooth_normals(&mut sphere);
+        assert_eq!(seams(&sphere), 0);
+        assert!(sphere.iter().all(|v| v.normal.dot(&v.position.normalize()) > 0.99));
+
+        // Both sides of a flat ring keep facing their own way, and the
+        // side of a box stays square to it
+        let ring = "v 0 0 0\nv 1 0 0\nv 0 0 1\nvn 0 1 0\nvn 0 -1 0\nf 1//1 3//1 2//1\nf 1//2 2//2 3//2\n";
+        let box_corner = "v 0 0 0\nv 1 0 0\nv 0 0 1\nv 0 1 0\nvn 0 1 0\nvn 1 0 0\nf 1//1 3//1 2//1\nf 1//2 4//2 3//2\n";
+        for (text, normals) in [(ring, [-Vec3::y(), Vec3::y()]), (box_corner, [-Vec3::y(), Vec3::x()])] {
+            let mut vertices = parse(text.as_bytes()).unwrap().get_vertex_array();
+            smooth_normals(&mut vertices);
+            for (triangle, normal) in vertices.chunks(3).zip(normals) {
+                assert!(triangle.iter().all(|v| (v.normal - normal).magnitude() < 1e-5), "{:?}", triangle);
+            }
+        }
+        assert!(!parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap().has_normals());
+    }
+
+    // Positions plus triangles indexing into them
+    fn mesh() -> impl Strategy<Value = (Vec<[f32; 3]>, Vec<[usize; 3]>)> {
+        prop::collection::vec(prop::array::uniform3(-1000.0f32..1000.0), 3..40).prop_flat_map(|positions| {
+            let triangles = prop::collection::vec(prop::array::uniform3(0..positions.len()), 1..60);
+            (Just(positions), triangles)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+            if let Ok(obj) = parse(&bytes) {
+                obj.get_vertex_array();
+            }
+        }
+
+        // Random bytes rarely get past the first line, these look enough like
+        // OBJ to reach the face and index handling
+        #[test]
+        fn obj_like_text_never_panics(lines in prop::collection::vec("(v|vn|vt|f|o|g|s|l|p) [-0-9./ ]{0,24}", 0..24)) {
+            if let Ok(obj) = parse(lines.join("\n").as_bytes()) {
+                obj.get_vertex_array();
+            }
+        }
+
+        #[test]
+        fn generated_meshes_round_trip((positions, triangles) in mesh()) {
+            let mut text = String::new();
+            for p in &positions {
+                writeln!(text, "v {} {} {}", p[0], p[1], p[2]).unwrap();
+            }
+            for t in &triangles {
+                writeln!(text, "f {} {} {}", t[0] + 1, t[1] + 1, t[2] + 1).unwrap();
+            }
+
+            let vertices = parse(text.as_bytes()).unwrap().get_vertex_array();
+            prop_assert_eq!(vertices.len(), triangles.len() * 3);
+            for (vertex, &index) in vertices.iter().zip(triangles.iter().flatten()) {
+                let p = positions[index];
+                // Y and Z are flipped on load
+                prop_assert_eq!(vertex.position, Vec3::new(p[0], -p[1], -p[2]));
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::body::CelestialBody;
+use crate::units::Units;
+
+// Warps and the autopilot stop right at the arrival distance, this much
+// slack keeps them from missing it by a rounding error
+const VISIT_SLACK: f32 = 1.05;
+// Orbiting counts out to this many arrival distances, and at any distance
+// while the camera follows the body
+const ORBIT_RANGE: f32 = 3.0;
+
+// One [[objectives]] entry of the scene file, e.g.
+//     kind = "approach"
+//     body = "Comet"
+//     distance = 50.0
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ObjectiveConfig {
+    // Come as close as a warp to the body would
+    Visit { body: String },
+    // Get within `distance` units of the body's surface
+    Approach { body: String, distance: f32 },
+    // Stay around the body for `seconds` of simulation time in a row
+    Orbit { body: String, seconds: f32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Goal {
+    Visit,
+    Approach(f32),
+    Orbit(f32),
+}
+
+pub struct Objective {
+    pub label: String,
+    body: usize,
+    goal: Goal,
+}
+
+// Turns the scene's objectives into checks on body indices
+pub fn resolve(configs: &[ObjectiveConfig], name_to_index: &HashMap<&str, usize>, units: Units) -> Result<Vec<Objective>, String> {
+    configs.iter()
+        .map(|config| {
+            let (name, goal) = match config {
+                ObjectiveConfig::Visit { body } => (body, Goal::Visit),
+                ObjectiveConfig::Approach { body, distance } => (body, Goal::Approach(*distance)),
+                ObjectiveConfig::Orbit { body, seconds } => (body, Goal::Orbit(*seconds)),
+            };
+            let body = *name_to_index.get(name.as_str()).ok_or_else(|| format!("Objective: unknown body {}", name))?;
+            let label = match goal {
+                Goal::Visit => format!("Visit {}", name),
+                Goal::Approach(distance) => format!("Get within {} of {}", units.distance(distance), name),
+                Goal::Orbit(seconds) => format!("Orbit {} for {}", name, units.duration(seconds)),
+            };
+            Ok(Objective { label, body, goal })
+        })
+        .collect()
+}
+
+// What the quick-save keeps, one entry per objective in scene order
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct ObjectiveProgress {
+    pub completed: Vec<bool>,
+    // Time spent orbiting so far, reset on leaving
+    pub orbit_seconds: Vec<f32>,
+    // Simulation time since the start, and when the last objective was met
+    pub elapsed: f32,
+    pub finished_at: Option<f32>,
+}
+
+pub struct Objectives {
+    objectives: Vec<Objective>,
+    progress: ObjectiveProgress,
+}
+
+impl Objectives {
+    pub fn new(objectives: Vec<Objective>) -> Self {
+        let progress = ObjectiveProgress {
+            completed: vec![false; objectives.len()],
+            orbit_seconds: vec![0.0; objectives.len()],
+            ..ObjectiveProgress::default()
+        };
+        Self { objectives, progress }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objectives.is_empty()
+    }
+
+    pub fn progress(&self) -> &ObjectiveProgress {
+        &self.progress
+    }
+
+    // Progress from a save; one that doesn't match the objectives (saved
+    // before they were added) starts them over
+    pub fn restore(&mut self, progress: ObjectiveProgress) {
+        if progress.completed.len() == self.objectives.len() && progress.orbit_seconds.len() == self.objectives.len() {
+            self.progress = progress;
+        } else {
+            *self = Objectives::new(std::mem::take(&mut self.objectives));
+        }
+    }
+
+    // Each objective with whether it's done and, for orbits under way, the
+    // time spent so far
+    pub fn entries(&self) -> impl Iterator<Item = (&str, bool, Option<f32>)> {
+        self.objectives.iter().enumerate().map(|(i, objective)| {
+            let orbiting = self.progress.orbit_seconds[i];
+            let partial = (matches!(objective.goal, Goal::Orbit(_)) && orbiting > 0.0).then_some(orbiting);
+            (objective.label.as_str(), self.progress.completed[i], partial)
+        })
+    }
+
+    pub fn completed_count(&self) -> usize {
+        self.progress.completed.iter().filter(|&&done| done).count()
+    }
+
+    // Simulation time it took to meet all of them
+    pub fn finished_at(&self) -> Option<f32> {
+        self.progress.finished_at
+    }
+
+    // Checks the objectives against this tick's positions; `observers` are
+    // the camera and, when it's out, the ship. Arrivals by warp or autopilot
+    // end at `arrival_distance`, so they count like flying there. Returns
+    // the objectives met this tick
+    pub fn update(
+        &mut self,
+        bodies: &[CelestialBody],
+        observers: &[Vec3],
+        followed: Option<usize>,
+        sim_delta: f32,
+        arrival_distance: impl Fn(&CelestialBody) -> f32,
+    ) -> Vec<usize> {
+        if self.progress.finished_at.is_some() {
+            return Vec::new();
+        }
+        self.progress.elapsed += sim_delta;
+        let mut met = Vec::new();
+        for (i, objective) in self.objectives.iter().enumerate() {
+            if self.progress.completed[i] {
+                continue;
+            }
+            let body = &bodies[objective.body];
+            let closest = observers.iter().map(|&observer| (observer - body.position).magnitude()).fold(f32::INFINITY, f32::min);
+            let done = match objective.goal {
+                Goal::Visit => closest <= arrival_distance(body) * VISIT_SLACK,
+                Goal::Approach(distance) => closest - body.bounding_radius() <= distance,
+                Goal::Orbit(seconds) => {
+                    let orbiting = followed == Some(objective.body) || closest <= arrival_distance(body) * ORBIT_RANGE;
+                    let spent = &mut self.progress.orbit_seconds[i];
+                    *spent = if orbiting { *spent + sim_delta } else { 0.0 };
+                    *spent >= seconds
+                }
+            };
+            if done {
+                self.progress.completed[i] = true;
+                met.push(i);
+            }
+        }
+        if !met.is_empty() && self.progress.completed.iter().all(|&done| done) {
+            self.progress.finished_at = Some(self.progress.elapsed);
+        }
+        met
+    }
+
+    pub fn label(&self, index: usize) -> &str {
+        &self.objectives[index].label
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+
+    #[test]
+    fn objectives_complete_from_where_the_camera_ends_up() {
+        let vertices = vec![Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), nalgebra_glm::Vec2::zeros())];
+        let planet = CelestialBody::new_planet("Moon".to_string(), vertices, Vec3::zeros(), 0.0, 0.0, 10.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+        let bodies = vec![planet];
+        let names = HashMap::from([("Moon", 0)]);
+        let configs: Vec<ObjectiveConfig> = toml::from_str::<HashMap<String, Vec<ObjectiveConfig>>>(
+            "objectives = [{ kind = \"visit\", body = \"Moon\" }, { kind = \"orbit\", body = \"Moon\", seconds = 2.0 }]",
+        ).unwrap().remove("objectives").unwrap();
+        assert!(resolve(&[ObjectiveConfig::Visit { body: "Sun".to_string() }], &names, Units::default()).is_err());
+        let mut objectives = Objectives::new(resolve(&configs, &names, Units::default()).unwrap());
+        let arrival = |body: &CelestialBody| body.bounding_radius() * 4.0;
+
+        // Far away, but following the body still counts as orbiting
+        assert!(objectives.update(&bodies, &[Vec3::new(500.0, 0.0, 0.0)], Some(0), 1.0, arrival).is_empty());
+        // A warp leaves the camera exactly at the arrival distance
+        assert_eq!(objectives.update(&bodies, &[Vec3::new(0.0, 0.0, 40.0)], None, 0.5, arrival), vec![0]);
+        assert_eq!(objectives.finished_at(), None);
+        assert_eq!(objectives.update(&bodies, &[Vec3::new(0.0, 0.0, 40.0)], None, 0.5, arrival), vec![1]);
+        assert_eq!(objectives.finished_at(), Some(2.0));
+
+        // Saves from another set of objectives start over
+        let mut other = Objectives::new(resolve(&configs[..1], &names, Units::default()).unwrap());
+        other.restore(objectives.progress().clone());
+        assert_eq!(other.completed_count(), 0);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::backend::BackendKind;
+use crate::scene::DEFAULT_SCENE;
+use crate::sky::SkyMode;
+
+// Default folder for --headless frames
+const DEFAULT_FRAMES_DIR: &str = "frames";
+
+// Command line: [scene.toml] [--uncapped] [--low-spec] [--epoch YYYY-MM-DD]
+// [--backend window|terminal] [--record log | --replay log [--headless [--out dir]]]
+// [--benchmark [--headless]] [--sky off|baked|live] [--threads n]
+pub struct Options {
+    pub scene_path: String,
+    // Run without the frame rate limit (U toggles it at runtime)
+    pub uncapped: bool,
+    // Start with every cheap drawing path on, for slow machines (see
+    // LowSpecSettings)
+    pub low_spec: bool,
+    // Date for scenes with an [ephemeris], instead of the one they name
+    pub epoch: Option<String>,
+    // What frames are shown in and keys read from
+    pub backend: BackendKind,
+    // Log every frame's input here
+    pub record: Option<String>,
+    // Feed input from this log instead of the keyboard
+    pub replay: Option<String>,
+    // No window: replay, write every frame as a PNG, then exit
+    pub headless: bool,
+    pub out: String,
+    // Fly the built-in path through the default scene uncapped, print frame
+    // times and exit
+    pub benchmark: bool,
+    // Sky mode instead of the scene's [sky] one, e.g. to compare them
+    // under --benchmark
+    pub sky: Option<SkyMode>,
+    // Threads drawing the scene, one for the direct path without tiles;
+    // every core unless given. The frames come out the same for any count, so
+    // --benchmark runs with --threads 1 and more show what they gain
+    pub threads: Option<usize>,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            scene_path: DEFAULT_SCENE.to_string(),
+            uncapped: false,
+            low_spec: false,
+            epoch: None,
+            backend: BackendKind::default(),
+            record: None,
+            replay: None,
+            headless: false,
+            out: DEFAULT_FRAMES_DIR.to_string(),
+            benchmark: false,
+            sky: None,
+            threads: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |flag: &str| args.next().ok_or_else(|| format!("{} needs a value", flag));
+            match arg.as_str() {
+                "--uncapped" => options.uncapped = true,
+                // --low-end was the per vertex shading alone, now part of it
+                "--low-spec" | "--low-end" => options.low_spec = true,
+                "--epoch" => options.epoch = Some(value("--epoch")?),
+                "--backend" => options.backend = BackendKind::parse(&value("--backend")?)?,
+                "--record" => options.record = Some(value("--record")?),
+                "--replay" => options.replay = Some(value("--replay")?),
+                "--headless" => options.headless = true,
+                "--out" => options.out = value("--out")?,
+                "--benchmark" => options.benchmark = true,
+                "--sky" => options.sky = Some(SkyMode::parse(&value("--sky")?)?),
+                "--threads" => match value("--threads")?.parse() {
+                    Ok(threads) if threads >= 1 => options.threads = Some(threads),
+                    _ => return Err("--threads needs a count of at least 1".to_string()),
+                },
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                _ => options.scene_path = arg,
+            }
+        }
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay can't be used together".to_string());
+        }
+        if options.benchmark && (options.record.is_some() || options.replay.is_some()) {
+            return Err("--benchmark flies itself, it can't be recorded or replayed".to_string());
+        }
+        if options.headless && options.replay.is_none() && !options.benchmark {
+            return Err("--headless needs --replay or --benchmark".to_string());
+        }
+        if options.headless && options.backend != BackendKind::default() {
+            return Err("--headless shows nothing, it can't take a --backend".to_string());
+        }
+        // Numbers are only comparable on the scene the path was made for
+        if options.benchmark {
+            options.scene_path = DEFAULT_SCENE.to_string();
+            options.uncapped = true;
+        }
+        Ok(options)
+    }
+
+    // Recorded, replayed and benchmark runs advance by a fixed step every
+    // frame, so the same input always gives the same frames
+    pub fn deterministic(&self) -> bool {
+        self.record.is_some() || self.replay.is_some() || self.benchmark
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn reads_flags_and_scene_in_any_order() {
+        let options = parse(&["--replay", "session.log", "scenes/other.toml", "--headless", "--out", "shots", "--epoch", "2025-06-01"]).unwrap();
+        assert_eq!(options.scene_path, "scenes/other.toml");
+        assert_eq!(options.replay.as_deref(), Some("session.log"));
+        assert!(options.headless && options.deterministic());
+        assert_eq!(options.out, "shots");
+        assert_eq!(options.epoch.as_deref(), Some("2025-06-01"));
+
+        assert!(!options.low_spec && parse(&["--low-spec"]).unwrap().low_spec && parse(&["--low-end"]).unwrap().low_spec);
+
+        assert_eq!(parse(&[]).unwrap().scene_path, DEFAULT_SCENE);
+        assert_eq!(options.backend, BackendKind::Window);
+        assert_eq!(parse(&["--backend", "terminal"]).unwrap().backend, BackendKind::Terminal);
+        assert!(parse(&["--backend", "tty"]).is_err());
+        assert!(parse(&["--replay", "b.log", "--headless", "--backend", "terminal"]).is_err());
+
+        let benchmark = parse(&["scenes/sol.toml", "--benchmark", "--headless"]).unwrap();
+        assert!(benchmark.uncapped && benchmark.deterministic());
+        assert_eq!(benchmark.scene_path, DEFAULT_SCENE);
+        assert!(parse(&["--benchmark", "--replay", "b.log"]).is_err());
+        assert_eq!(parse(&["--benchmark", "--sky", "live"]).unwrap().sky, Some(SkyMode::Live));
+        assert!(parse(&["--sky", "cube"]).is_err());
+        assert_eq!(parse(&["--threads", "4"]).unwrap().threads, Some(4));
+        assert_eq!(parse(&[]).unwrap().threads, None);
+        assert!(parse(&["--threads", "0"]).is_err() && parse(&["--threads", "all"]).is_err());
+
+        assert!(parse(&["--headless"]).is_err());
+        assert!(parse(&["--record"]).is_err());
+        assert!(parse(&["--record", "a.log", "--replay", "b.log"]).is_err());
+    }
+}
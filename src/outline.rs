@@ -0,0 +1,88 @@
+use crate::framebuffer::{Framebuffer, blend};
+
+// Pulses per second of the selection outline, and how faint it gets
+// between them
+const PULSE_RATE: f32 = 1.5;
+const MIN_ALPHA: f32 = 0.35;
+
+// ID the renderer writes for a body; 0 is left for everything else
+pub fn body_id(index: usize) -> u16 {
+    index as u16 + 1
+}
+
+// Pixels inside the clip next to a visible pixel of object `id` without
+// being part of it. Bodies in front count as outside, so the ring follows
+// only what can be seen of the object
+fn outline_pixels(framebuffer: &Framebuffer, id: u16) -> Vec<usize> {
+    let Some(ids) = &framebuffer.ids else {
+        return Vec::new();
+    };
+    let clip = framebuffer.clip();
+    let mut pixels = Vec::new();
+    for y in clip.y..clip.y + clip.height {
+        for x in clip.x..clip.x + clip.width {
+            let index = y * framebuffer.width + x;
+            if ids[index] == id {
+                continue;
+            }
+            let touches = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))).any(|(dx, dy)| {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                nx >= 0 && ny >= 0 && clip.contains(nx as usize, ny as usize) && ids[ny as usize * framebuffer.width + nx as usize] == id
+            });
+            if touches {
+                pixels.push(index);
+            }
+        }
+    }
+    pixels
+}
+
+// Pulsing one-pixel ring around the selected body's silhouette, from the
+// IDs left by the last render
+pub fn draw_selection_outline(framebuffer: &mut Framebuffer, id: u16, color: u32, time: f32) {
+    let pulse = 0.5 + 0.5 * (time * PULSE_RATE * std::f32::consts::TAU).sin();
+    let alpha = MIN_ALPHA + (1.0 - MIN_ALPHA) * pulse;
+    for index in outline_pixels(framebuffer, id) {
+        framebuffer.buffer[index] = blend(framebuffer.buffer[index], color, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framebuffer::Viewport;
+
+    fn fill(framebuffer: &mut Framebuffer, id: u16, depth: f32, x: usize, y: usize, size: usize) {
+        framebuffer.set_current_id(id);
+        for py in y..y + size {
+            for px in x..x + size {
+                framebuffer.point(px, py, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn outline_follows_the_visible_silhouette() {
+        let mut framebuffer = Framebuffer::new(12, 12);
+        framebuffer.track_ids(true);
+        // The selected square, with a nearer one covering its right half
+        fill(&mut framebuffer, 1, 5.0, 2, 2, 6);
+        fill(&mut framebuffer, 2, 1.0, 5, 3, 5);
+
+        let ring = outline_pixels(&framebuffer, 1);
+        let on_ring = |x: usize, y: usize| ring.contains(&(y * 12 + x));
+        // Around the uncovered left edge and corner
+        assert!(on_ring(1, 4) && on_ring(1, 1));
+        // Along the near square where it starts to cover it
+        assert!(on_ring(5, 4));
+        // Not over the covered part, nor inside what's still visible
+        assert!(!on_ring(7, 5) && !on_ring(8, 5) && !on_ring(3, 4));
+
+        // Nothing past the clip edge
+        framebuffer.set_clip(Viewport { x: 3, y: 0, width: 9, height: 12 });
+        assert!(outline_pixels(&framebuffer, 1).iter().all(|&index| index % 12 >= 3));
+
+        framebuffer.track_ids(false);
+        assert!(outline_pixels(&framebuffer, 1).is_empty());
+    }
+}
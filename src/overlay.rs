@@ -0,0 +1,424 @@
+use std::collections::VecDeque;
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use std::f32::consts::PI;
+use serde::Deserialize;
+use crate::framebuffer::Framebuffer;
+use crate::body::CelestialBody;
+use crate::text::{draw_text, text_width, LINE_HEIGHT};
+
+const ORBIT_SEGMENTS: usize = 96;
+// Each dash of a dashed ring needs at least one segment, and so does each gap
+pub const MAX_ORBIT_DASHES: usize = ORBIT_SEGMENTS / 2;
+// A star's corona reaches this many radii out from its center and starts
+// this opaque at the rim
+const CORONA_REACH: f32 = 2.5;
+const CORONA_ALPHA: f32 = 0.45;
+
+// [overlays] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct OverlaySettings {
+    // Orbit rings and trails fade out over this many units behind whatever
+    // covers them instead of stopping at its edge; 0 cuts them off there
+    #[serde(default = "default_occlusion_fade")]
+    pub occlusion_fade: f32,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self { occlusion_fade: default_occlusion_fade() }
+    }
+}
+
+fn default_occlusion_fade() -> f32 {
+    30.0
+}
+
+// How a line meets the scene already drawn
+#[derive(Clone, Copy)]
+pub enum LineDepth {
+    // Hidden wherever something is in front of it
+    Test,
+    // Dimmed by how far behind the scene it is, see ScreenProjection::fade_behind
+    Fade(OcclusionFade),
+}
+
+// Turns stored depths back into distances from the camera, so a line can
+// fade over a distance in world units behind what covers it
+#[derive(Clone, Copy)]
+pub struct OcclusionFade {
+    // Third row of the projection matrix, which made the depths
+    depth_scale: f32,
+    depth_offset: f32,
+    distance: f32,
+}
+
+impl OcclusionFade {
+    fn camera_distance(&self, depth: f32) -> f32 {
+        self.depth_offset / (depth + self.depth_scale)
+    }
+
+    // Opacity left of a line at `depth` where the scene is at `scene_depth`
+    fn visibility(&self, depth: f32, scene_depth: f32) -> f32 {
+        if depth <= scene_depth {
+            return 1.0;
+        }
+        let behind = self.camera_distance(depth) - self.camera_distance(scene_depth);
+        (1.0 - behind / self.distance).clamp(0.0, 1.0)
+    }
+}
+
+// Matrices needed to place world-space overlay geometry on the screen
+pub struct ScreenProjection {
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+    pub viewport_matrix: Mat4,
+    // Round projected vertices to whole pixels, for the retro look's wobble
+    pub snap_to_pixels: bool,
+    // Spread colors and textures with the plain screen weights, without
+    // perspective correction, for the retro look's warping
+    pub affine: bool,
+}
+
+impl ScreenProjection {
+    // Screen-space position (x, y in pixels, z for depth), or None when the
+    // point is behind the camera
+    pub fn project(&self, world: Vec3) -> Option<Vec3> {
+        let clip = self.projection_matrix * self.view_matrix * Vec4::new(world.x, world.y, world.z, 1.0);
+        if clip.w <= 0.001 {
+            return None;
+        }
+        let ndc = Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+        let screen = self.viewport_matrix * ndc;
+        Some(Vec3::new(screen.x, screen.y, screen.z))
+    }
+
+    // Approximate on-screen radius in pixels of a sphere, None when its center
+    // is behind the camera
+    pub fn projected_radius(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let clip = self.projection_matrix * self.view_matrix * Vec4::new(center.x, center.y, center.z, 1.0);
+        if clip.w <= 0.001 {
+            return None;
+        }
+        // projection[(1, 1)] scales view-space units to NDC, the viewport maps
+        // one NDC unit to half the screen height
+        Some(radius * self.projection_matrix[(1, 1)] * self.viewport_matrix[(1, 1)].abs() / clip.w)
+    }
+
+    // Lines that fade out over `distance` world units behind the scene, or
+    // plain depth-tested ones when it's 0
+    pub fn fade_behind(&self, distance: f32) -> LineDepth {
+        if distance <= 0.0 {
+            return LineDepth::Test;
+        }
+        // A perspective projection stores -(A + B / z) for a view depth z,
+        // with A and B from its third row
+        LineDepth::Fade(OcclusionFade {
+            depth_scale: self.projection_matrix[(2, 2)],
+            depth_offset: self.projection_matrix[(2, 3)],
+            distance,
+        })
+    }
+}
+
+// Clips the segment a-b to the framebuffer's clip rectangle (Liang-Barsky),
+// returns the parameter range that stays on screen
+fn clip_to_screen(framebuffer: &Framebuffer, a: &Vec3, b: &Vec3) -> Option<(f32, f32)> {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let clip = framebuffer.clip();
+    let (min_x, min_y) = (clip.x as f32, clip.y as f32);
+    let max_x = (clip.x + clip.width) as f32 - 1.0;
+    let max_y = (clip.y + clip.height) as f32 - 1.0;
+    let mut t0 = 0.0f32;
+    let mut t1 = 1.0f32;
+
+    for (p, q) in [(-dx, a.x - min_x), (dx, max_x - a.x), (-dy, a.y - min_y), (dy, max_y - a.y)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+
+    if t0 <= t1 { Some((t0, t1)) } else { None }
+}
+
+// Depth-tested line between two screen-space points
+pub fn draw_line(framebuffer: &mut Framebuffer, a: Vec3, b: Vec3, color: u32) {
+    draw_blended_line(framebuffer, a, b, color, 1.0);
+}
+
+// Same as draw_line, mixed over the scene with the given opacity
+pub fn draw_blended_line(framebuffer: &mut Framebuffer, a: Vec3, b: Vec3, color: u32, alpha: f32) {
+    raster_line(framebuffer, a, b, color, alpha, None, LineDepth::Test);
+}
+
+// Translucent line that meets the scene as `depth` says
+pub fn draw_overlay_line(framebuffer: &mut Framebuffer, a: Vec3, b: Vec3, color: u32, alpha: f32, depth: LineDepth) {
+    raster_line(framebuffer, a, b, color, alpha, None, depth);
+}
+
+// Depth-tested line drawn as dashes of `dash` pixels with equal gaps
+pub fn draw_dashed_line(framebuffer: &mut Framebuffer, a: Vec3, b: Vec3, color: u32, dash: usize) {
+    raster_line(framebuffer, a, b, color, 1.0, Some(dash.max(1)), LineDepth::Test);
+}
+
+fn raster_line(framebuffer: &mut Framebuffer, a: Vec3, b: Vec3, color: u32, alpha: f32, dash: Option<usize>, depth: LineDepth) {
+    let Some((t0, t1)) = clip_to_screen(framebuffer, &a, &b) else { return };
+    let start = a + (b - a) * t0;
+    let end = a + (b - a) * t1;
+
+    let steps = (end.x - start.x).abs().max((end.y - start.y).abs()).ceil().max(1.0) as usize;
+    framebuffer.set_current_color(color);
+    for i in 0..=steps {
+        if dash.is_some_and(|dash| (i / dash) % 2 == 1) {
+            continue;
+        }
+        let t = i as f32 / steps as f32;
+        let p = start + (end - start) * t;
+        let (x, y) = (p.x.round() as usize, p.y.round() as usize);
+        match depth {
+            LineDepth::Test if alpha >= 1.0 => framebuffer.point(x, y, p.z),
+            LineDepth::Test => framebuffer.blend_point(x, y, p.z, color, alpha),
+            // Drawn over everything, only as opaque as the fade leaves it
+            LineDepth::Fade(fade) => {
+                let Some(scene_depth) = framebuffer.depth(x, y) else { continue };
+                let visible = alpha * fade.visibility(p.z, scene_depth);
+                if visible > 0.0 {
+                    framebuffer.blend_point(x, y, f32::NEG_INFINITY, color, visible);
+                }
+            }
+        }
+    }
+}
+
+// Line between two world points, `width` pixels wide
+pub fn draw_world_line(framebuffer: &mut Framebuffer, projection: &ScreenProjection, a: Vec3, b: Vec3, color: u32, width: usize, depth: LineDepth) {
+    let (Some(sa), Some(sb)) = (projection.project(a), projection.project(b)) else { return };
+    // Copies shifted a pixel at a time across the line's minor axis, so
+    // they touch without gaps whatever the slope
+    let across = if (sb.x - sa.x).abs() >= (sb.y - sa.y).abs() { Vec3::y() } else { Vec3::x() };
+    let width = width.max(1);
+    for k in 0..width {
+        let shift = across * (k as f32 - (width - 1) as f32 / 2.0);
+        draw_overlay_line(framebuffer, sa + shift, sb + shift, color, 1.0, depth);
+    }
+}
+
+// Dashed line between two bodies with a text tag at its midpoint
+pub fn draw_measurement(framebuffer: &mut Framebuffer, projection: &ScreenProjection, a: Vec3, b: Vec3, text: &str, color: u32) {
+    const DASH: usize = 6;
+    if let (Some(sa), Some(sb)) = (projection.project(a), projection.project(b)) {
+        draw_dashed_line(framebuffer, sa, sb, color, DASH);
+    }
+    if let Some(mid) = projection.project((a + b) * 0.5) {
+        let x = mid.x as i32 - text_width(text) as i32 / 2;
+        draw_text(framebuffer, x, mid.y as i32 - LINE_HEIGHT as i32, text, color);
+    }
+}
+
+// Connected segments through world-space points (a line strip), each one
+// faded by alpha(segment index); points behind the camera break the strip
+pub fn draw_line_strip<'a>(
+    framebuffer: &mut Framebuffer,
+    projection: &ScreenProjection,
+    points: impl IntoIterator<Item = &'a Vec3>,
+    color: u32,
+    alpha: impl Fn(usize) -> f32,
+    depth: LineDepth,
+) {
+    let mut previous: Option<Vec3> = None;
+    for (i, point) in points.into_iter().enumerate() {
+        let screen = projection.project(*point);
+        if let (Some(a), Some(b)) = (previous, screen) {
+            draw_overlay_line(framebuffer, a, b, color, alpha(i - 1), depth);
+        }
+        previous = screen;
+    }
+}
+
+// Fading polyline through a body's recent positions, ending at the body
+pub fn draw_trail(framebuffer: &mut Framebuffer, projection: &ScreenProjection, body: &CelestialBody, trail: &VecDeque<Vec3>, color: u32, depth: LineDepth) {
+    // Oldest segment is almost transparent, the newest one is solid
+    let segments = trail.len().max(1) as f32;
+    let fade = |i: usize| (i + 1) as f32 / segments;
+    draw_line_strip(framebuffer, projection, trail.iter().chain(std::iter::once(&body.position)), color, fade, depth);
+}
+
+// Orbit path for a body in its orbit style, plus an arrowhead just ahead of
+// the body pointing in its direction of travel
+pub fn draw_orbit(
+    framebuffer: &mut Framebuffer,
+    projection: &ScreenProjection,
+    body: &CelestialBody,
+    center: Vec3,
+    color: u32,
+    arrow_color: u32,
+    depth: LineDepth,
+) {
+    if body.orbital_radius <= 0.0 {
+        return;
+    }
+
+    let style = body.orbit_style;
+    for i in 0..ORBIT_SEGMENTS {
+        // Dashes are counted around the ring, so they keep their length
+        // however the ring is seen
+        if style.dashes.is_some_and(|dashes| i * dashes * 2 / ORBIT_SEGMENTS % 2 == 1) {
+            continue;
+        }
+        let a0 = i as f32 / ORBIT_SEGMENTS as f32 * 2.0 * PI;
+        let a1 = (i + 1) as f32 / ORBIT_SEGMENTS as f32 * 2.0 * PI;
+        draw_world_line(framebuffer, projection, center + body.orbit_offset(a0), center + body.orbit_offset(a1), color, style.width, depth);
+    }
+
+    // Arrow sits a fixed arc length ahead of the body so it rides along with it
+    let direction = body.orbit_direction();
+    let arc = (body.scale * 3.0 / body.orbital_radius).min(0.5);
+    let tip_angle = body.orbital_angle + direction * arc;
+    let back_angle = tip_angle - direction * arc * 0.5;
+    let width = body.scale.max(2.0);
+
+    let tip = body.orbit_offset(tip_angle);
+    let back = body.orbit_offset(back_angle);
+    let outward = back.normalize() * width;
+    draw_world_line(framebuffer, projection, center + back - outward, center + tip, arrow_color, style.width, depth);
+    draw_world_line(framebuffer, projection, center + back + outward, center + tip, arrow_color, style.width, depth);
+}
+
+// Which orbit rings are shown, O cycles through them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OrbitDisplay {
+    All,
+    PlanetsOnly,
+    Hidden,
+}
+
+impl OrbitDisplay {
+    pub fn next(self) -> Self {
+        match self {
+            OrbitDisplay::All => OrbitDisplay::PlanetsOnly,
+            OrbitDisplay::PlanetsOnly => OrbitDisplay::Hidden,
+            OrbitDisplay::Hidden => OrbitDisplay::All,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OrbitDisplay::All => "all",
+            OrbitDisplay::PlanetsOnly => "planets only",
+            OrbitDisplay::Hidden => "off",
+        }
+    }
+
+    // Whether the ring of `body` is drawn; the scene can hide single ones
+    pub fn shows(self, body: &CelestialBody, bodies: &[CelestialBody]) -> bool {
+        body.orbit_style.visible && match self {
+            OrbitDisplay::All => true,
+            OrbitDisplay::PlanetsOnly => !body.is_moon(bodies),
+            OrbitDisplay::Hidden => false,
+        }
+    }
+}
+
+// Small depth-tested square, size x size pixels, centered on a screen point
+pub fn draw_point(framebuffer: &mut Framebuffer, screen: Vec3, size: i32, color: u32) {
+    framebuffer.set_current_color(color);
+    let (x0, y0) = (screen.x as i32 - size / 2, screen.y as i32 - size / 2);
+    for y in y0..y0 + size {
+        for x in x0..x0 + size {
+            if x >= 0 && y >= 0 {
+                framebuffer.point(x as usize, y as usize, screen.z);
+            }
+        }
+    }
+}
+
+// Same square mixed over the scene, for glows that fade
+pub fn draw_glow(framebuffer: &mut Framebuffer, screen: Vec3, size: i32, color: u32, alpha: f32) {
+    let (x0, y0) = (screen.x as i32 - size / 2, screen.y as i32 - size / 2);
+    for y in y0.max(0)..y0 + size {
+        for x in x0.max(0)..x0 + size {
+            framebuffer.blend_point(x as usize, y as usize, screen.z, color, alpha);
+        }
+    }
+}
+
+// Name tag centered just above a body, hidden when it's behind the camera
+pub fn draw_label(framebuffer: &mut Framebuffer, projection: &ScreenProjection, body: &CelestialBody, text: &str, color: u32) {
+    let top = body.position + Vec3::new(0.0, body.bounding_radius(), 0.0);
+    if let Some(screen) = projection.project(top) {
+        let x = screen.x as i32 - text_width(text) as i32 / 2;
+        let y = screen.y as i32 - LINE_HEIGHT as i32;
+        draw_text(framebuffer, x, y, text, color);
+    }
+}
+
+// Diamond marker with a name next to it, for points in space (not bodies)
+pub fn draw_marker(framebuffer: &mut Framebuffer, projection: &ScreenProjection, position: Vec3, text: &str, color: u32) {
+    const SIZE: f32 = 4.0;
+    let Some(center) = projection.project(position) else { return };
+    let corners = [
+        center + Vec3::new(0.0, -SIZE, 0.0),
+        center + Vec3::new(SIZE, 0.0, 0.0),
+        center + Vec3::new(0.0, SIZE, 0.0),
+        center + Vec3::new(-SIZE, 0.0, 0.0),
+    ];
+    for i in 0..corners.len() {
+        draw_line(framebuffer, corners[i], corners[(i + 1) % corners.len()], color);
+    }
+    draw_text(framebuffer, center.x as i32 + SIZE as i32 + 3, center.y as i32 - 3, text, color);
+}
+
+// Glow around a star's disc, fading out to CORONA_REACH radii. Depth tested
+// against the star's center, so bodies in front of it cover it; `strength`
+// dims all of it as the star itself is hidden
+pub fn draw_corona(framebuffer: &mut Framebuffer, projection: &ScreenProjection, star: &CelestialBody, strength: f32) {
+    let (Some(screen), Some(pixels)) = (projection.project(star.position), projection.projected_radius(star.position, star.bounding_radius())) else { return };
+    if strength <= 0.0 || pixels <= 0.0 {
+        return;
+    }
+    // Only the part of the glow inside the clip is walked
+    let (reach, clip) = (pixels * CORONA_REACH, framebuffer.clip());
+    let span = |center: f32, start: usize, length: usize| {
+        ((center - reach).max(start as f32) as usize, ((center + reach).ceil().max(0.0) as usize).min(start + length))
+    };
+    let ((left, right), (top, bottom)) = (span(screen.x, clip.x, clip.width), span(screen.y, clip.y, clip.height));
+    let color = star.color.to_pixel();
+    for y in top..bottom {
+        for x in left..right {
+            let distance = (x as f32 - screen.x).hypot(y as f32 - screen.y) / pixels;
+            if distance <= 1.0 || distance >= CORONA_REACH {
+                continue;
+            }
+            let fade = 1.0 - (distance - 1.0) / (CORONA_REACH - 1.0);
+            framebuffer.blend_point(x, y, screen.z, color, strength * CORONA_ALPHA * fade * fade);
+        }
+    }
+}
+
+// Translucent bubble around the ship, brightest at its rim. The ship is
+// always the closest thing to the camera, so this skips the depth test.
+pub fn draw_shield(framebuffer: &mut Framebuffer, projection: &ScreenProjection, center: Vec3, radius: f32, color: u32, strength: f32) {
+    let (Some(screen), Some(pixels)) = (projection.project(center), projection.projected_radius(center, radius)) else { return };
+    let r = pixels.ceil() as i32;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt() / pixels;
+            if distance > 1.0 {
+                continue;
+            }
+            let (x, y) = (screen.x as i32 + dx, screen.y as i32 + dy);
+            if x >= 0 && y >= 0 {
+                let alpha = strength * (0.15 + 0.6 * distance.powi(4));
+                framebuffer.blend_point(x as usize, y as usize, f32::NEG_INFINITY, color, alpha);
+            }
+        }
+    }
+}
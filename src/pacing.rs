@@ -0,0 +1,83 @@
+use std::time::Instant;
+use crate::backend::Backend;
+
+// Frame rate the display is held to unless uncapped (U or --uncapped)
+pub const TARGET_FPS: usize = 60;
+// Longer frames (a stall, dragging the window) are counted as this long, so
+// nothing jumps across the scene afterwards. Frames are never counted as
+// zero either, several updates divide by the frame time
+const MAX_FRAME_SECONDS: f32 = 0.1;
+const MIN_FRAME_SECONDS: f32 = 0.0001;
+// How often the frame rate readout is refreshed
+const FPS_WINDOW: f32 = 1.0;
+
+// Real time between frames. The waiting itself is left to the backend,
+// which only sleeps for whatever is left of the frame budget
+pub struct FrameClock {
+    last_tick: Instant,
+    pub capped: bool,
+    // Recorded and replayed runs pretend every frame took exactly this long
+    fixed_step: Option<f32>,
+    // Frames and seconds counted towards the next fps readout
+    frames: u32,
+    elapsed: f32,
+    pub fps: f32,
+}
+
+impl FrameClock {
+    pub fn new(capped: bool, fixed_step: bool) -> Self {
+        Self {
+            last_tick: Instant::now(),
+            capped,
+            fixed_step: fixed_step.then(|| 1.0 / TARGET_FPS as f32),
+            frames: 0,
+            elapsed: 0.0,
+            fps: 0.0,
+        }
+    }
+
+    // Tells the backend how fast to go
+    pub fn apply(&self, backend: &mut dyn Backend) {
+        backend.set_target_fps(if self.capped { TARGET_FPS } else { 0 });
+    }
+
+    pub fn toggle_cap(&mut self, backend: &mut dyn Backend) {
+        self.capped = !self.capped;
+        self.apply(backend);
+    }
+
+    // Seconds since the previous tick, called once at the top of each frame
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let seconds = (now - self.last_tick).as_secs_f32();
+        self.last_tick = now;
+        self.count(seconds);
+        self.fixed_step.unwrap_or(seconds.clamp(MIN_FRAME_SECONDS, MAX_FRAME_SECONDS))
+    }
+
+    fn count(&mut self, seconds: f32) {
+        self.frames += 1;
+        self.elapsed += seconds;
+        if self.elapsed >= FPS_WINDOW {
+            self.fps = self.frames as f32 / self.elapsed;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_is_averaged_over_a_second() {
+        let mut clock = FrameClock::new(true, false);
+        for _ in 0..63 {
+            clock.count(0.015625);
+        }
+        assert_eq!(clock.fps, 0.0);
+        clock.count(0.015625);
+        assert_eq!(clock.fps, 64.0);
+    }
+}
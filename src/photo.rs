@@ -0,0 +1,171 @@
+use std::f32::consts::PI;
+use nalgebra_glm::{Vec3, Mat4, rotate_vec3};
+use crate::camera::{Camera, look_at_matrix};
+use crate::filters::vignette;
+use crate::geometry::normalize_or;
+use crate::input::{InputFrame, Action};
+
+// Field of view of the live view, which photo mode starts from
+pub const DEFAULT_FOV: f32 = PI / 3.0;
+const MIN_FOV: f32 = PI / 18.0;
+const MAX_FOV: f32 = PI * 5.0 / 9.0;
+// Photo mode moves slowly so shots can be framed precisely: units and
+// radians per second, and FOV change per second
+const FLY_SPEED: f32 = 40.0;
+const TURN_SPEED: f32 = 0.4;
+const ROLL_SPEED: f32 = 0.5;
+const ZOOM_SPEED: f32 = PI / 9.0;
+// How long the result of a capture replaces the controls line
+const MESSAGE_SECONDS: f32 = 3.0;
+// How dark the corners get with the vignette on
+const VIGNETTE_STRENGTH: f32 = 0.6;
+const VIGNETTE_FALLOFF: f32 = 2.0;
+// Focus distance changes by this factor per second, and stays in the range
+// the camera can draw
+const FOCUS_SPEED: f32 = 1.0;
+const MIN_FOCUS: f32 = 10.0;
+const MAX_FOCUS: f32 = 3000.0;
+// Depth of field blur at infinity in pixels, 0 turns it off
+const MAX_APERTURE: f32 = 8.0;
+// Photos are rendered at this many times the framebuffer size and averaged
+// back down, whatever the live view is doing
+pub const PHOTO_SUPERSAMPLING: usize = 2;
+
+// Photo mode (P): the simulation stands still and the HUD is hidden while
+// a free camera, separate from the orbit camera, frames the shot. Nothing
+// else is touched, so leaving it puts the view back exactly as it was
+pub struct PhotoMode {
+    pub position: Vec3,
+    forward: Vec3,
+    up: Vec3,
+    pub fov: f32,
+    pub vignette: bool,
+    // Distance that stays sharp, and how strongly everything else blurs
+    pub focus: f32,
+    pub aperture: f32,
+    // Tint what's in focus while framing
+    pub show_focus: bool,
+    // Real seconds spent in photo mode, since the simulation clock stops
+    elapsed: f32,
+    // Result of the last capture, and when it happened
+    message: Option<String>,
+    message_since: f32,
+}
+
+impl PhotoMode {
+    // Starts where the orbit camera is, looking the same way
+    pub fn new(camera: &Camera) -> Self {
+        let forward = normalize_or(camera.target - camera.position, -Vec3::z());
+        let right = normalize_or(forward.cross(&camera.up), Vec3::x());
+        Self {
+            position: camera.position,
+            forward,
+            up: right.cross(&forward),
+            fov: DEFAULT_FOV,
+            vignette: false,
+            // Focused on whatever the orbit camera was looking at
+            focus: (camera.target - camera.position).magnitude().clamp(MIN_FOCUS, MAX_FOCUS),
+            aperture: 0.0,
+            show_focus: false,
+            elapsed: 0.0,
+            message: None,
+            message_since: 0.0,
+        }
+    }
+
+    fn right(&self) -> Vec3 {
+        self.forward.cross(&self.up)
+    }
+
+    pub fn update(&mut self, input: &InputFrame, delta_time: f32) {
+        self.elapsed += delta_time;
+        let axis = |positive, negative| {
+            (input.held(positive) as i32 - input.held(negative) as i32) as f32 * delta_time
+        };
+
+        let yaw = axis(Action::TurnLeft, Action::TurnRight) * TURN_SPEED;
+        let pitch = axis(Action::TurnUp, Action::TurnDown) * TURN_SPEED;
+        let roll = axis(Action::RollRight, Action::RollLeft) * ROLL_SPEED;
+        let right = self.right();
+        self.forward = rotate_vec3(&rotate_vec3(&self.forward, yaw, &self.up), pitch, &right);
+        self.up = rotate_vec3(&rotate_vec3(&self.up, pitch, &right), roll, &self.forward);
+        // Keep the basis orthonormal as the small rotations add up
+        self.forward = normalize_or(self.forward, -Vec3::z());
+        self.up = normalize_or(self.right().cross(&self.forward), Vec3::y());
+
+        let ahead = axis(Action::FlyForward, Action::FlyBack);
+        let sideways = axis(Action::FlyRight, Action::FlyLeft);
+        self.position += (self.forward * ahead + self.right() * sideways) * FLY_SPEED;
+
+        let zoom = axis(Action::WidenView, Action::NarrowView);
+        self.fov = (self.fov + zoom * ZOOM_SPEED).clamp(MIN_FOV, MAX_FOV);
+
+        if input.pressed(Action::ToggleVignette) {
+            self.vignette = !self.vignette;
+        }
+
+        let focusing = axis(Action::FocusFarther, Action::FocusNearer);
+        self.focus = (self.focus * (focusing * FOCUS_SPEED).exp()).clamp(MIN_FOCUS, MAX_FOCUS);
+        if input.pressed(Action::WiderAperture) {
+            self.aperture = (self.aperture + 1.0).min(MAX_APERTURE);
+        }
+        if input.pressed(Action::NarrowerAperture) {
+            self.aperture = (self.aperture - 1.0).max(0.0);
+        }
+        if input.pressed(Action::ShowFocus) {
+            self.show_focus = !self.show_focus;
+        }
+    }
+
+    pub fn focus_on(&mut self, distance: f32) {
+        self.focus = distance.clamp(MIN_FOCUS, MAX_FOCUS);
+    }
+
+    pub fn set_message(&mut self, text: String) {
+        self.message = Some(text);
+        self.message_since = self.elapsed;
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref().filter(|_| self.elapsed - self.message_since < MESSAGE_SECONDS)
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        look_at_matrix(self.position, self.position + self.forward, self.up)
+    }
+
+    // Darkens the frame toward its corners when the vignette is on
+    pub fn finish(&self, pixels: &mut [u32], width: usize, height: usize) {
+        if self.vignette {
+            vignette(pixels, width, height, VIGNETTE_STRENGTH, VIGNETTE_FALLOFF);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flying_keeps_the_view_upright_until_rolled() {
+        let mut camera = Camera::new(Vec3::zeros(), 100.0);
+        camera.phi = PI / 2.0;
+        camera.update_position();
+        let mut photo = PhotoMode::new(&camera);
+        assert!((photo.forward - Vec3::new(-1.0, 0.0, 0.0)).magnitude() < 1e-5);
+
+        let turn = InputFrame::parse("TurnLeft FlyForward").unwrap();
+        for _ in 0..60 {
+            photo.update(&turn, 1.0 / 60.0);
+        }
+        assert!((photo.forward.magnitude() - 1.0).abs() < 1e-4);
+        assert!(photo.up.y > 0.9999);
+        assert!((photo.position - camera.position).magnitude() > 0.9 * FLY_SPEED);
+
+        photo.update(&InputFrame::parse("RollLeft").unwrap(), 1.0);
+        assert!(photo.up.y < 0.9 && photo.forward.dot(&photo.up).abs() < 1e-4);
+
+        photo.update(&InputFrame::parse("WidenView").unwrap(), 100.0);
+        assert_eq!(photo.fov, MAX_FOV);
+    }
+}
@@ -0,0 +1,191 @@
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+use crate::body::CelestialBody;
+use crate::distortion::swirl;
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::overlay::ScreenProjection;
+
+// Seconds both ends of a wormhole stay shut after a passage, so coming out
+// of one doesn't send the ship straight back in
+const COOLDOWN_SECONDS: f32 = 1.5;
+// How long the white flash of a passage takes to fade
+const FLASH_SECONDS: f32 = 0.4;
+// Background out to this many disc radii bends around a portal, turned by
+// up to LENS_TWIST radians next to it
+const LENS_REACH: f32 = 1.8;
+const LENS_TWIST: f32 = 0.8;
+// Portals smaller than this on screen (radius in pixels) aren't worth bending
+const LENS_MIN_PIXELS: f32 = 4.0;
+
+// One trip through a wormhole: what was at `from` comes out of `to`
+pub struct Passage {
+    pub from: usize,
+    pub to: usize,
+    // Takes world space around the entrance to world space around the exit
+    transform: Mat4,
+    from_velocity: Vec3,
+    to_velocity: Vec3,
+}
+
+impl Passage {
+    fn new(bodies: &[CelestialBody], previous: &[Vec3], from: usize, to: usize, delta_time: f32) -> Self {
+        // Half a turn about the disc's vertical: going into one disc is
+        // coming out of the other, moving away from it
+        let turn = Mat4::from_diagonal(&Vec4::new(-1.0, 1.0, -1.0, 1.0));
+        let entrance = pose(&bodies[from]).try_inverse().unwrap_or(Mat4::identity());
+        let velocity = |i: usize| previous.get(i).map_or(Vec3::zeros(), |&before| (bodies[i].position - before) / delta_time);
+        Self {
+            from,
+            to,
+            transform: pose(&bodies[to]) * turn * entrance,
+            from_velocity: velocity(from),
+            to_velocity: velocity(to),
+        }
+    }
+
+    pub fn point(&self, point: Vec3) -> Vec3 {
+        (self.transform * Vec4::new(point.x, point.y, point.z, 1.0)).xyz()
+    }
+
+    pub fn direction(&self, direction: Vec3) -> Vec3 {
+        (self.transform * Vec4::new(direction.x, direction.y, direction.z, 0.0)).xyz()
+    }
+
+    // Speed relative to the entrance becomes speed relative to the exit
+    pub fn velocity(&self, velocity: Vec3) -> Vec3 {
+        self.direction(velocity - self.from_velocity) + self.to_velocity
+    }
+}
+
+// Wormhole traversal for whatever flies the view: the ship when it's out,
+// the camera otherwise
+#[derive(Default)]
+pub struct Portals {
+    // Seconds each body's portal stays shut, by body index
+    cooldowns: Vec<f32>,
+    // Where the traveller was last frame
+    traveller: Option<Vec3>,
+    // Seconds of white flash left
+    flash: f32,
+}
+
+impl Portals {
+    // Checks whether the traveller, now at `traveller`, went through an open
+    // portal since last frame. `previous` holds where the bodies were, so a
+    // moving portal can't slip past it. None while something else flies the
+    // view (following a body, warps, docking), which also forgets the last
+    // position so nothing fires when free flight picks up somewhere else
+    pub fn update(&mut self, bodies: &[CelestialBody], previous: &[Vec3], traveller: Option<Vec3>, delta_time: f32) -> Option<Passage> {
+        self.flash = (self.flash - delta_time).max(0.0);
+        self.cooldowns.resize(bodies.len(), 0.0);
+        for cooldown in &mut self.cooldowns {
+            *cooldown = (*cooldown - delta_time).max(0.0);
+        }
+
+        let Some(after) = traveller else {
+            self.traveller = None;
+            return None;
+        };
+        let before = self.traveller.replace(after)?;
+        let passage = bodies.iter().enumerate().find_map(|(i, body)| {
+            let partner = body.portal_partner.filter(|_| self.cooldowns[i] <= 0.0)?;
+            // Measured against the portal where it is now
+            let moved = previous.get(i).map_or(Vec3::zeros(), |&position| body.position - position);
+            crosses(body, before + moved, after).then(|| Passage::new(bodies, previous, i, partner, delta_time))
+        })?;
+
+        self.cooldowns[passage.from] = COOLDOWN_SECONDS;
+        self.cooldowns[passage.to] = COOLDOWN_SECONDS;
+        self.flash = FLASH_SECONDS;
+        self.traveller = Some(passage.point(after));
+        Some(passage)
+    }
+
+    // 1 right after a passage, fading to 0
+    pub fn flash(&self) -> f32 {
+        self.flash / FLASH_SECONDS
+    }
+}
+
+// Where the disc is and which way it faces, without its size
+fn pose(body: &CelestialBody) -> Mat4 {
+    body.get_model_matrix() * nalgebra_glm::scaling(&Vec3::repeat(1.0 / body.scale.max(f32::EPSILON)))
+}
+
+// Whether the segment from `before` to `after` goes through the disc
+fn crosses(body: &CelestialBody, before: Vec3, after: Vec3) -> bool {
+    let Some(to_disc) = pose(body).try_inverse() else { return false };
+    let local = |point: Vec3| (to_disc * Vec4::new(point.x, point.y, point.z, 1.0)).xyz();
+    let (a, b) = (local(before), local(after));
+    if (a.z > 0.0) == (b.z > 0.0) || a.z == b.z {
+        return false;
+    }
+    let hit = a + (b - a) * (a.z / (a.z - b.z));
+    hit.xy().magnitude() <= body.bounding_radius()
+}
+
+// Bends the background around every portal in view, a hint of the space
+// folded up behind the disc
+pub fn draw_lensing(framebuffer: &mut Framebuffer, projection: &ScreenProjection, bodies: &[CelestialBody], viewport: Viewport) {
+    for body in bodies.iter().filter(|body| body.is_portal()) {
+        let reach = body.bounding_radius() * LENS_REACH;
+        let (Some(center), Some(radius)) = (projection.project(body.position), projection.projected_radius(body.position, reach)) else {
+            continue;
+        };
+        // Depth of the lens's near side; with the camera inside it there's
+        // no telling what's behind
+        let view = projection.view_matrix * Vec4::new(body.position.x, body.position.y, body.position.z, 1.0);
+        let near = projection.projection_matrix * Vec4::new(view.x, view.y, view.z + reach, 1.0);
+        if radius < LENS_MIN_PIXELS || view.z + reach >= 0.0 {
+            continue;
+        }
+        swirl(framebuffer, viewport, Vec2::new(center.x, center.y), radius, near.z / near.w, LENS_TWIST);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::scene_graph::SceneGraph;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+
+    fn portal(position: Vec3, facing_yaw: f32, partner: usize) -> CelestialBody {
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::z(), Vec2::zeros());
+        let mut body = CelestialBody::new_sun("Portal".to_string(), vec![rim], position, 10.0, Color::from_hex(0xFFFFFF));
+        body.shader_type = ShaderType::Portal;
+        body.portal_partner = Some(partner);
+        body.rotation.y = facing_yaw;
+        body
+    }
+
+    #[test]
+    fn passages_leave_the_exit_and_close_it_behind_them() {
+        // Entrance facing +Z at the origin, exit facing +X far away
+        let mut bodies = vec![portal(Vec3::zeros(), 0.0, 1), portal(Vec3::new(500.0, 0.0, 0.0), std::f32::consts::FRAC_PI_2, 0)];
+        SceneGraph::from_bodies(&bodies).unwrap().update(&mut bodies);
+        let previous: Vec<Vec3> = bodies.iter().map(|body| body.position).collect();
+        let mut portals = Portals::default();
+
+        // Missing the disc does nothing
+        assert!(portals.update(&bodies, &previous, Some(Vec3::new(20.0, 0.0, 5.0)), 0.1).is_none());
+        assert!(portals.update(&bodies, &previous, Some(Vec3::new(20.0, 0.0, -5.0)), 0.1).is_none());
+
+        // Flying in along -Z comes out heading along +X, just off the exit
+        assert!(portals.update(&bodies, &previous, Some(Vec3::new(2.0, 0.0, 5.0)), 0.1).is_none());
+        let passage = portals.update(&bodies, &previous, Some(Vec3::new(2.0, 0.0, -5.0)), 0.1).unwrap();
+        assert_eq!((passage.from, passage.to), (0, 1));
+        assert!((passage.point(Vec3::new(2.0, 0.0, -5.0)) - Vec3::new(505.0, 0.0, 2.0)).magnitude() < 1e-3);
+        assert!((passage.velocity(Vec3::new(0.0, 0.0, -10.0)) - Vec3::new(10.0, 0.0, 0.0)).magnitude() < 1e-3);
+        assert_eq!(portals.flash(), 1.0);
+
+        // Turning straight back through the exit does nothing until it opens again
+        assert!(portals.update(&bodies, &previous, Some(Vec3::new(495.0, 0.0, 2.0)), 0.1).is_none());
+        for _ in 0..20 {
+            portals.update(&bodies, &previous, Some(Vec3::new(495.0, 0.0, 2.0)), 0.1);
+        }
+        assert_eq!(portals.flash(), 0.0);
+        let back = portals.update(&bodies, &previous, Some(Vec3::new(505.0, 0.0, 2.0)), 0.1).unwrap();
+        assert_eq!((back.from, back.to), (1, 0));
+    }
+}
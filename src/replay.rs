@@ -0,0 +1,96 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use crate::input::InputFrame;
+use crate::save::format_hash;
+
+// First line of every input log, followed by "scene <hash>" and then one
+// line of input per frame
+const LOG_HEADER: &str = "# space_travel input log v1";
+
+// Writes the input of every frame of a --record run
+pub struct InputLog {
+    out: BufWriter<File>,
+}
+
+impl InputLog {
+    pub fn create(path: &str, scene_hash: u64) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut log = InputLog { out: BufWriter::new(file) };
+        log.write_line(LOG_HEADER)?;
+        log.write_line(&format!("scene {}", format_hash(scene_hash)))?;
+        Ok(log)
+    }
+
+    pub fn write(&mut self, frame: &InputFrame) -> Result<(), String> {
+        self.write_line(&frame.to_line())
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), String> {
+        writeln!(self.out, "{}", line).map_err(|e| format!("Failed to write input log: {}", e))
+    }
+}
+
+// Input of a recorded run, handed out one frame at a time
+pub struct InputReplay {
+    frames: Vec<InputFrame>,
+    next: usize,
+}
+
+impl InputReplay {
+    // Reads a log, refusing one that was recorded over another scene
+    pub fn load(path: &str, scene_hash: u64) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut lines = text.lines();
+        if lines.next() != Some(LOG_HEADER) {
+            return Err(format!("{} is not an input log", path));
+        }
+        if lines.next() != Some(format!("scene {}", format_hash(scene_hash)).as_str()) {
+            return Err(format!("{} was recorded with a different scene", path));
+        }
+        let frames = lines
+            .enumerate()
+            .map(|(i, line)| InputFrame::parse(line).map_err(|e| format!("{} line {}: {}", path, i + 3, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(InputReplay { frames, next: 0 })
+    }
+
+    // None once the recording has run out
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let frame = self.frames.get(self.next).cloned();
+        self.next += 1;
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Action;
+
+    #[test]
+    fn replays_what_was_recorded() {
+        let path = std::env::temp_dir().join("space_travel_input_test.log");
+        let path = path.to_str().unwrap();
+        let frames = vec![
+            InputFrame::parse("OrbitLeft").unwrap(),
+            InputFrame::default(),
+            InputFrame::parse("ZoomIn +Warp:3").unwrap(),
+        ];
+        {
+            let mut log = InputLog::create(path, 42).unwrap();
+            for frame in &frames {
+                log.write(frame).unwrap();
+            }
+        }
+
+        assert!(InputReplay::load(path, 43).is_err());
+        let mut replay = InputReplay::load(path, 42).unwrap();
+        let _ = fs::remove_file(path);
+        assert_eq!(replay.next_frame().as_ref(), Some(&frames[0]));
+        assert_eq!(replay.next_frame().as_ref(), Some(&frames[1]));
+        let last = replay.next_frame().unwrap();
+        assert_eq!(last.pressed_index(Action::Warp), Some(3));
+        assert!(last.held(Action::ZoomIn));
+        assert_eq!(replay.next_frame(), None);
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use crate::framebuffer::{Framebuffer, Viewport};
+
+// 4x4 ordered dither thresholds, in sixteenths
+const BAYER: [[u32; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+// [retro] section of the scene file: the "PS1 in space" look (` toggles)
+#[derive(Deserialize, Clone, Copy)]
+pub struct RetroSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    // Internal resolution for the whole frame, stretched up with nearest
+    // pixels; split views get their share of it
+    #[serde(default = "default_width")]
+    pub width: usize,
+    #[serde(default = "default_height")]
+    pub height: usize,
+    // Bits kept per color channel, 5 is the 15/16-bit look
+    #[serde(default = "default_color_bits")]
+    pub color_bits: u32,
+    // Ordered dithering hides the banding from the lost bits
+    #[serde(default = "default_true")]
+    pub dither: bool,
+    // Whole-pixel vertices, so geometry wobbles as it moves
+    #[serde(default = "default_true")]
+    pub snap_vertices: bool,
+    // Colors and textures spread across triangles without perspective
+    // correction, so they swim and bend on big faces up close
+    #[serde(default = "default_true")]
+    pub affine: bool,
+}
+
+impl Default for RetroSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            width: default_width(),
+            height: default_height(),
+            color_bits: default_color_bits(),
+            dither: true,
+            snap_vertices: true,
+            affine: true,
+        }
+    }
+}
+
+fn default_width() -> usize {
+    320
+}
+
+fn default_height() -> usize {
+    240
+}
+
+fn default_color_bits() -> u32 {
+    5
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl RetroSettings {
+    // Off-screen framebuffer to render `viewport` of `frame` into
+    pub fn buffer_for(&self, frame: &Framebuffer, viewport: Viewport) -> Framebuffer {
+        let width = (viewport.width * self.width / frame.width.max(1)).max(1);
+        let height = (viewport.height * self.height / frame.height.max(1)).max(1);
+        frame.blank(width, height)
+    }
+
+    // Drops each channel to `color_bits`, dithered if enabled
+    pub fn quantize(&self, pixels: &mut [u32], width: usize) {
+        let bits = self.color_bits.clamp(1, 8);
+        if bits == 8 {
+            return;
+        }
+        let step = 1u32 << (8 - bits);
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+            // Offset within one step, so flat areas mix the two nearest levels
+            let offset = if self.dither { BAYER[index / width % 4][index % width % 4] * step / 16 } else { step / 2 };
+            let mut result = 0;
+            for shift in [16, 8, 0] {
+                let channel = *pixel >> shift & 0xFF;
+                let level = ((channel + offset) / step * step).min(255);
+                // Spread the kept levels over the full 0-255 range
+                result |= (level * 255 / (256 - step)).min(255) << shift;
+            }
+            *pixel = result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dithering_keeps_the_average_brightness() {
+        let retro = RetroSettings::default();
+        let mut pixels = vec![0x646464; 16 * 16];
+        retro.quantize(&mut pixels, 16);
+        let levels: Vec<u32> = pixels.iter().map(|pixel| pixel & 0xFF).collect();
+        let average = levels.iter().sum::<u32>() as f32 / levels.len() as f32;
+        assert!((average - 100.0).abs() < 4.0, "average came out as {}", average);
+        assert!(levels.iter().any(|&level| level != levels[0]), "a flat area should be dithered");
+
+        // White and black survive, and 8 bits changes nothing
+        let mut extremes = vec![0xFFFFFF, 0x000000];
+        retro.quantize(&mut extremes, 2);
+        assert_eq!(extremes, vec![0xFFFFFF, 0x000000]);
+        let full = RetroSettings { color_bits: 8, ..retro };
+        let mut same = vec![0x123456];
+        full.quantize(&mut same, 1);
+        assert_eq!(same, vec![0x123456]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::f32::consts::PI;
+use nalgebra_glm::{Vec2, Vec3};
+
+// Randomness for everything procedural. A scene has one master seed; each
+// system takes its own stream from it by name and then by index
+// (seed.derive("impacts").derive(body)), so drawing more numbers in one
+// system never moves what another one gets. Replays and golden images rely
+// on every run producing the same numbers
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Seed(pub u64);
+
+impl Seed {
+    // Independent seed for a part of whatever this one seeds
+    pub fn derive(self, key: impl SeedKey) -> Seed {
+        Seed(mix(self.0 ^ mix(key.seed_key())))
+    }
+
+    pub fn rng(self) -> Rng {
+        Rng::pcg32(self.0, mix(self.0))
+    }
+}
+
+// What a seed can be derived by
+pub trait SeedKey {
+    fn seed_key(&self) -> u64;
+}
+
+impl SeedKey for &str {
+    // FNV-1a, so names give the same key on every platform
+    fn seed_key(&self) -> u64 {
+        self.bytes().fold(0xCBF29CE484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001B3))
+    }
+}
+
+impl SeedKey for u64 {
+    fn seed_key(&self) -> u64 {
+        *self
+    }
+}
+
+impl SeedKey for u32 {
+    fn seed_key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+impl SeedKey for usize {
+    fn seed_key(&self) -> u64 {
+        *self as u64
+    }
+}
+
+// SplitMix64's finalizer, nudged so zero doesn't map to zero
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// PCG32 (XSH RR), small, fast and the same on every platform
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+impl Rng {
+    const MULTIPLIER: u64 = 6364136223846793005;
+
+    // The reference generator's seeding, so its published outputs can be checked
+    fn pcg32(state: u64, sequence: u64) -> Self {
+        let mut rng = Self { state: 0, increment: (sequence << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(state);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(Self::MULTIPLIER).wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // Uniform index below `count`, which must not be zero
+    pub fn below(&mut self, count: usize) -> usize {
+        ((self.next_u32() as u64 * count as u64) >> 32) as usize
+    }
+
+    // Uniform on the unit sphere
+    pub fn unit_vector(&mut self) -> Vec3 {
+        let z = self.next_f32() * 2.0 - 1.0;
+        let angle = self.next_f32() * 2.0 * PI;
+        let ring = (1.0 - z * z).sqrt();
+        Vec3::new(ring * angle.cos(), z, ring * angle.sin())
+    }
+
+    // Uniform inside the unit circle
+    pub fn in_disc(&mut self) -> Vec2 {
+        let angle = self.next_f32() * 2.0 * PI;
+        Vec2::new(angle.cos(), angle.sin()) * self.next_f32().sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_are_fixed_by_their_seed_alone() {
+        // First outputs of the reference pcg32 demo (state 42, sequence 54)
+        let mut reference = Rng::pcg32(42, 54);
+        let outputs: Vec<u32> = (0..6).map(|_| reference.next_u32()).collect();
+        assert_eq!(outputs, [0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]);
+
+        let master = Seed(7);
+        let draw = |seed: Seed| {
+            let mut rng = seed.rng();
+            (0..16).map(|_| rng.next_u32()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(master.derive("asteroids").derive(3usize)), draw(Seed(7).derive("asteroids").derive(3usize)));
+        // Siblings and other names don't share a stream
+        let streams = [
+            master.derive("asteroids").derive(3usize),
+            master.derive("asteroids").derive(4usize),
+            master.derive("stars").derive(3usize),
+            Seed(8).derive("asteroids").derive(3usize),
+        ];
+        for (i, a) in streams.iter().enumerate() {
+            for b in &streams[i + 1..] {
+                assert_ne!(draw(*a), draw(*b));
+            }
+        }
+
+        let mut rng = master.rng();
+        for _ in 0..1000 {
+            assert!((rng.unit_vector().magnitude() - 1.0).abs() < 1e-5);
+            assert!(rng.in_disc().magnitude() <= 1.0 + 1e-5);
+            assert!((2.0..5.0).contains(&rng.range(2.0, 5.0)));
+            assert!(rng.below(3) < 3);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::fs;
+use nalgebra_glm::Vec3;
+use serde::{Deserialize, Serialize};
+use crate::body::CelestialBody;
+use crate::camera::Camera;
+use crate::objectives::ObjectiveProgress;
+use crate::scale::ScaleProfile;
+use crate::spaceship::Spaceship;
+
+// F5 writes it, F8 reads it back
+pub const QUICKSAVE_PATH: &str = "quicksave.toml";
+
+// Everything that changes while the simulation runs. The scene file itself
+// isn't stored, only a hash of it, so a save only loads over the scene it
+// was made from
+#[derive(Serialize, Deserialize)]
+pub struct SaveState {
+    // 64-bit hash of the scene file, as hex (TOML integers are signed)
+    pub scene_hash: String,
+    pub time: f32,
+    pub time_scale: f32,
+    pub scale_profile: ScaleProfile,
+    pub scale_progress: f32,
+    pub selected_body: usize,
+    pub followed_body: Option<usize>,
+    pub bodies: Vec<BodyState>,
+    pub camera: CameraState,
+    pub ship: ShipState,
+    // Missing from saves made before the scene had objectives
+    #[serde(default)]
+    pub objectives: ObjectiveProgress,
+    // Only kept when the scene limits warp energy
+    #[serde(default)]
+    pub warp_energy: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BodyState {
+    // Only used to make the file readable
+    pub name: String,
+    pub local_position: [f32; 3],
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub orbital_angle: f32,
+    pub orbital_radius: f32,
+    pub scale: f32,
+    // Only integrated in n-body mode, zero otherwise
+    pub velocity: [f32; 3],
+    // Saves from before impacts existed have no craters
+    #[serde(default)]
+    pub craters: Vec<[f32; 3]>,
+    // Nor ash from eruptions
+    #[serde(default)]
+    pub ash: Vec<[f32; 3]>,
+}
+
+impl BodyState {
+    pub fn from_body(body: &CelestialBody) -> Self {
+        Self {
+            name: body.name.clone(),
+            local_position: body.local_position.into(),
+            position: body.position.into(),
+            rotation: body.rotation.into(),
+            orbital_angle: body.orbital_angle,
+            orbital_radius: body.orbital_radius,
+            scale: body.scale,
+            velocity: body.velocity.into(),
+            craters: body.craters.iter().map(|&crater| crater.into()).collect(),
+            ash: body.ash.iter().map(|&field| field.into()).collect(),
+        }
+    }
+
+    pub fn apply(&self, body: &mut CelestialBody) {
+        body.local_position = Vec3::from(self.local_position);
+        body.position = Vec3::from(self.position);
+        body.rotation = Vec3::from(self.rotation);
+        body.orbital_angle = self.orbital_angle;
+        body.orbital_radius = self.orbital_radius;
+        body.scale = self.scale;
+        body.velocity = Vec3::from(self.velocity);
+        body.craters = self.craters.iter().map(|&crater| Vec3::from(crater)).collect();
+        body.ash = self.ash.iter().map(|&field| Vec3::from(field)).collect();
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub distance: f32,
+    pub theta: f32,
+    pub phi: f32,
+    pub min_distance: f32,
+}
+
+impl CameraState {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            position: camera.position.into(),
+            target: camera.target.into(),
+            up: camera.up.into(),
+            distance: camera.distance,
+            theta: camera.theta,
+            phi: camera.phi,
+            min_distance: camera.min_distance,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = Vec3::from(self.position);
+        camera.target = Vec3::from(self.target);
+        camera.up = Vec3::from(self.up);
+        camera.distance = self.distance;
+        camera.theta = self.theta;
+        camera.phi = self.phi;
+        camera.min_distance = self.min_distance;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ShipState {
+    pub shown: bool,
+    pub design: usize,
+    pub position: [f32; 3],
+    pub velocity: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    // Saves from before ships took damage have a fresh hull
+    #[serde(default)]
+    pub damage: f32,
+}
+
+impl ShipState {
+    pub fn from_ship(ship: &Spaceship, shown: bool) -> Self {
+        Self {
+            shown,
+            design: ship.current_design(),
+            position: ship.position.into(),
+            velocity: ship.velocity.into(),
+            yaw: ship.yaw,
+            pitch: ship.pitch,
+            roll: ship.roll,
+            damage: ship.damage(),
+        }
+    }
+
+    pub fn apply(&self, ship: &mut Spaceship) {
+        ship.restore(
+            self.design,
+            Vec3::from(self.position),
+            Vec3::from(self.velocity),
+            (self.yaw, self.pitch, self.roll),
+            self.damage,
+        );
+    }
+}
+
+impl SaveState {
+    pub fn write(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| format!("Failed to save {}: {}", path, e))?;
+        fs::write(path, text).map_err(|e| format!("Failed to save {}: {}", path, e))
+    }
+
+    // Reads a save, refusing it when it was made with another scene
+    pub fn read(path: &str, scene_hash: u64, body_count: usize) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let state: SaveState = toml::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path, e))?;
+        if state.scene_hash != format_hash(scene_hash) || state.bodies.len() != body_count {
+            return Err(format!("{} was saved with a different scene", path));
+        }
+        Ok(state)
+    }
+}
+
+pub fn format_hash(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::ShaderType;
+
+    #[test]
+    fn round_trips_exactly_and_checks_the_scene() {
+        let mut planet = CelestialBody::new_planet(
+            "Planet".to_string(), Vec::new(), Vec3::zeros(), 123.456, 0.37, 1.3, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+        );
+        planet.update(17.3);
+        planet.position = planet.local_position;
+        let mut camera = Camera::new(Vec3::new(0.1, 0.2, 0.3), 654.321);
+        camera.phi = 0.9;
+        camera.update_position();
+
+        let state = SaveState {
+            scene_hash: format_hash(0xDEADBEEF12345678),
+            time: 12.345,
+            time_scale: 8.0,
+            scale_profile: ScaleProfile::Realistic,
+            scale_progress: 0.7,
+            selected_body: 0,
+            followed_body: Some(0),
+            bodies: vec![BodyState::from_body(&planet)],
+            camera: CameraState::from_camera(&camera),
+            ship: ShipState { shown: false, design: 0, position: [0.0; 3], velocity: [0.0; 3], yaw: 0.0, pitch: 0.0, roll: 0.0, damage: 0.35 },
+            objectives: ObjectiveProgress::default(),
+            warp_energy: None,
+        };
+        let path = std::env::temp_dir().join("space_travel_save_test.toml");
+        let path = path.to_str().unwrap();
+        state.write(path).unwrap();
+
+        assert!(SaveState::read(path, 0x1234, 1).is_err());
+        let loaded = SaveState::read(path, 0xDEADBEEF12345678, 1).unwrap();
+        let mut restored = CelestialBody::new_planet(
+            "Planet".to_string(), Vec::new(), Vec3::zeros(), 123.456, 0.37, 1.3, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+        );
+        loaded.bodies[0].apply(&mut restored);
+        let mut restored_camera = Camera::new(Vec3::zeros(), 1.0);
+        loaded.camera.apply(&mut restored_camera);
+        let _ = fs::remove_file(path);
+
+        assert_eq!(restored.position, planet.position);
+        assert_eq!(restored.orbital_angle.to_bits(), planet.orbital_angle.to_bits());
+        assert_eq!(restored.rotation, planet.rotation);
+        assert_eq!(restored_camera.position, camera.position);
+        assert_eq!(loaded.time.to_bits(), 12.345f32.to_bits());
+        assert_eq!(loaded.ship.damage, 0.35);
+    }
+}
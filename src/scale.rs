@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use crate::body::CelestialBody;
+
+// Seconds a switch between scale profiles takes
+const TRANSITION_SECONDS: f32 = 2.0;
+
+// How the "realistic" profile differs from the scene's own (stylized)
+// numbers: orbits spread out while bodies shrink, so proportions get closer
+// to the real thing without being literal
+#[derive(Deserialize, Clone, Copy)]
+pub struct ScaleFactors {
+    #[serde(default = "default_orbit_factor")]
+    pub orbit_factor: f32,
+    #[serde(default = "default_size_factor")]
+    pub size_factor: f32,
+    #[serde(default = "default_star_size_factor")]
+    pub star_size_factor: f32,
+}
+
+impl Default for ScaleFactors {
+    fn default() -> Self {
+        Self {
+            orbit_factor: default_orbit_factor(),
+            size_factor: default_size_factor(),
+            star_size_factor: default_star_size_factor(),
+        }
+    }
+}
+
+fn default_orbit_factor() -> f32 {
+    1.6
+}
+
+fn default_size_factor() -> f32 {
+    0.35
+}
+
+fn default_star_size_factor() -> f32 {
+    0.4
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ScaleProfile {
+    Stylized,
+    Realistic,
+}
+
+// Blends every body's orbit radius and size between the two profiles
+pub struct ScaleTransition {
+    factors: ScaleFactors,
+    // Stylized (orbital_radius, scale) of every body, as loaded
+    base: Vec<(f32, f32)>,
+    target: ScaleProfile,
+    // 0 = stylized, 1 = realistic
+    progress: f32,
+}
+
+impl ScaleTransition {
+    pub fn new(factors: ScaleFactors, bodies: &[CelestialBody]) -> Self {
+        Self {
+            factors,
+            base: bodies.iter().map(|body| (body.orbital_radius, body.scale)).collect(),
+            target: ScaleProfile::Stylized,
+            progress: 0.0,
+        }
+    }
+
+    pub fn profile(&self) -> ScaleProfile {
+        self.target
+    }
+
+    // How far the blend has got, 0 = stylized, 1 = realistic
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    // Puts the blend back where a save left it; bodies carry their own
+    // radii and sizes, so they aren't touched
+    pub fn restore(&mut self, target: ScaleProfile, progress: f32) {
+        self.target = target;
+        self.progress = progress.clamp(0.0, 1.0);
+    }
+
+    pub fn toggle(&mut self) {
+        self.target = match self.target {
+            ScaleProfile::Stylized => ScaleProfile::Realistic,
+            ScaleProfile::Realistic => ScaleProfile::Stylized,
+        };
+    }
+
+    // Current multiplier on orbit radii
+    pub fn orbit_multiplier(&self) -> f32 {
+        lerp(1.0, self.factors.orbit_factor, self.eased())
+    }
+
+    // Moves toward the target profile and rewrites orbit radii and sizes.
+    // Angular speeds are left alone, so periods stay the same and the system
+    // stays watchable at either scale.
+    pub fn update(&mut self, bodies: &mut [CelestialBody], delta_time: f32) {
+        let goal = match self.target {
+            ScaleProfile::Stylized => 0.0,
+            ScaleProfile::Realistic => 1.0,
+        };
+        if self.progress == goal {
+            return;
+        }
+        let step = delta_time / TRANSITION_SECONDS;
+        self.progress = if goal > self.progress {
+            (self.progress + step).min(goal)
+        } else {
+            (self.progress - step).max(goal)
+        };
+        self.apply(bodies);
+    }
+
+    // Takes new stylized radii and sizes (and factors) from a reloaded scene
+    // and puts the bodies at the current point of the blend
+    pub fn rebase(&mut self, factors: ScaleFactors, bodies: &mut [CelestialBody]) {
+        self.factors = factors;
+        self.base = bodies.iter().map(|body| (body.orbital_radius, body.scale)).collect();
+        self.apply(bodies);
+    }
+
+    fn apply(&self, bodies: &mut [CelestialBody]) {
+        let t = self.eased();
+        let orbit = lerp(1.0, self.factors.orbit_factor, t);
+        for (body, &(radius, scale)) in bodies.iter_mut().zip(&self.base) {
+            let size_factor = if body.is_star() { self.factors.star_size_factor } else { self.factors.size_factor };
+            body.orbital_radius = radius * orbit;
+            body.scale = scale * lerp(1.0, size_factor, t);
+        }
+    }
+
+    fn eased(&self) -> f32 {
+        let t = self.progress;
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use nalgebra_glm::Vec3;
+    use crate::ShaderType;
+
+    #[test]
+    fn toggle_blends_to_realistic_and_back() {
+        let sun = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 10.0, Color::from_hex(0xFFFFFF));
+        let planet = CelestialBody::new_planet(
+            "Planet".to_string(), Vec::new(), Vec3::zeros(), 100.0, 0.5, 2.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet,
+        );
+        let mut bodies = vec![sun, planet];
+        let factors = ScaleFactors { orbit_factor: 2.0, size_factor: 0.5, star_size_factor: 0.25 };
+        let mut transition = ScaleTransition::new(factors, &bodies);
+
+        transition.toggle();
+        transition.update(&mut bodies, TRANSITION_SECONDS / 2.0);
+        // Halfway through, smoothstep is exactly in the middle too
+        assert!((bodies[1].orbital_radius - 150.0).abs() < 1e-3);
+
+        transition.update(&mut bodies, TRANSITION_SECONDS);
+        assert_eq!(transition.profile(), ScaleProfile::Realistic);
+        assert!((bodies[0].scale - 2.5).abs() < 1e-5);
+        assert!((bodies[1].scale - 1.0).abs() < 1e-5);
+        assert!((bodies[1].orbital_radius - 200.0).abs() < 1e-3);
+
+        transition.toggle();
+        transition.update(&mut bodies, TRANSITION_SECONDS);
+        assert!((bodies[1].orbital_radius - 100.0).abs() < 1e-3);
+        assert!((bodies[0].scale - 10.0).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,937 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::f32::consts::PI;
+use std::fs;
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use crate::obj::{Obj, smooth_normals};
+use crate::vertex::Vertex;
+use crate::body::{CelestialBody, OrbitStyle, apply_kepler_speeds, hierarchy_order};
+use crate::scene_graph::SceneGraph;
+use crate::nbody::init_circular_velocities;
+use crate::trail::TrailSettings;
+use crate::overlay::{OverlaySettings, MAX_ORBIT_DASHES};
+use crate::scale::ScaleFactors;
+use crate::spaceship::ShipDesign;
+use crate::capture::RecordingSettings;
+use crate::color::{Color, AccessibilitySettings};
+use crate::stereo::StereoSettings;
+use crate::exposure::ExposureSettings;
+use crate::motion_blur::MotionBlurSettings;
+use crate::filters::FilterSettings;
+use crate::lut::GradingSettings;
+use crate::retro::RetroSettings;
+use crate::sky::SkySettings;
+use crate::impacts::ImpactSettings;
+use crate::eruptions::EruptionSettings;
+use crate::objectives::{self, Objective, ObjectiveConfig};
+use crate::warp::WarpEnergySettings;
+use crate::heat::HeatSettings;
+use crate::sun_haze::SunHazeSettings;
+use crate::cruise::CruiseSettings;
+use crate::low_spec::LowSpecSettings;
+use crate::light::{AmbientSettings, LightKind};
+use crate::ephemeris::{self, EphemerisSettings};
+use crate::units::Units;
+use crate::terrain;
+use crate::rng::Seed;
+use crate::texture::{Albedo, SurfaceTexture, TextureConfig};
+use crate::shader_params::{ShaderParams, StarParams, RockyParams, GasGiantParams, SkyboxParams};
+use crate::ShaderType;
+
+pub const DEFAULT_SCENE: &str = "scenes/default.toml";
+
+// How body positions are advanced each frame
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulationMode {
+    // Scripted circular orbits (orbital_radius / orbital_speed)
+    #[default]
+    Kinematic,
+    // Orbits emerge from gravity between every pair of bodies
+    NBody,
+}
+
+// Scene-wide simulation settings
+#[derive(Deserialize, Clone)]
+pub struct SimulationSettings {
+    #[serde(default)]
+    pub mode: SimulationMode,
+    // Derive every orbital speed from its radius unless a body overrides it
+    #[serde(default)]
+    pub derive_from_radius: bool,
+    // Gravitational parameter (G * M) of the central star
+    #[serde(default = "default_central_mass")]
+    pub central_mass: f32,
+    // Master seed every random system takes its own stream from
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+}
+
+impl Default for SimulationSettings {
+    fn default() -> Self {
+        Self {
+            mode: SimulationMode::Kinematic,
+            derive_from_radius: false,
+            central_mass: default_central_mass(),
+            seed: default_seed(),
+        }
+    }
+}
+
+fn default_seed() -> u64 {
+    1
+}
+
+fn default_central_mass() -> f32 {
+    625000.0
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_color() -> u32 {
+    0xFFFFFF
+}
+
+#[derive(Deserialize)]
+struct SceneFile {
+    #[serde(default)]
+    simulation: SimulationSettings,
+    #[serde(default)]
+    bodies: Vec<BodyConfig>,
+    #[serde(default)]
+    dwarf_planets: Option<DwarfPlanetBelt>,
+    #[serde(default)]
+    trails: TrailSettings,
+    #[serde(default)]
+    overlays: OverlaySettings,
+    #[serde(default)]
+    realistic_scale: ScaleFactors,
+    #[serde(default = "default_ships")]
+    ships: Vec<ShipConfig>,
+    #[serde(default)]
+    recording: RecordingSettings,
+    #[serde(default)]
+    accessibility: AccessibilitySettings,
+    #[serde(default)]
+    stereo: StereoSettings,
+    #[serde(default)]
+    exposure: ExposureSettings,
+    #[serde(default)]
+    motion_blur: MotionBlurSettings,
+    #[serde(default)]
+    filters: FilterSettings,
+    #[serde(default)]
+    grading: GradingSettings,
+    #[serde(default)]
+    retro: RetroSettings,
+    #[serde(default)]
+    sky: SkySettings,
+    #[serde(default)]
+    impacts: ImpactSettings,
+    #[serde(default)]
+    objectives: Vec<ObjectiveConfig>,
+    #[serde(default)]
+    warp_energy: WarpEnergySettings,
+    #[serde(default)]
+    ephemeris: Option<EphemerisSettings>,
+    #[serde(default)]
+    units: Units,
+    #[serde(default)]
+    heat: HeatSettings,
+    #[serde(default)]
+    sun_haze: SunHazeSettings,
+    #[serde(default)]
+    ambient: AmbientSettings,
+    #[serde(default)]
+    cruise: CruiseSettings,
+    #[serde(default)]
+    low_spec: LowSpecSettings,
+}
+
+// A ship the player can fly in chase view (N cycles through them)
+#[derive(Deserialize)]
+struct ShipConfig {
+    name: String,
+    model: String,
+    #[serde(default = "default_ship_scale")]
+    scale: f32,
+    // Place relative to the camera in ship radii: right, up, ahead
+    #[serde(default = "default_follow_offset")]
+    follow_offset: [f32; 3],
+    // Engine exhaust positions in model space
+    #[serde(default)]
+    engines: Vec<[f32; 3]>,
+    #[serde(default = "default_hull_color")]
+    hull_color: u32,
+    #[serde(default = "default_emissive_color")]
+    emissive_color: u32,
+}
+
+fn default_ship_scale() -> f32 {
+    1.5
+}
+
+fn default_follow_offset() -> [f32; 3] {
+    [0.0, -2.0, 9.0]
+}
+
+fn default_hull_color() -> u32 {
+    0xC8CDD7
+}
+
+fn default_emissive_color() -> u32 {
+    0xFF9632
+}
+
+// Scenes without [[ships]] get the small default ship
+fn default_ships() -> Vec<ShipConfig> {
+    vec![ShipConfig {
+        name: "Scout".to_string(),
+        model: "assets/models/ship.obj".to_string(),
+        scale: default_ship_scale(),
+        follow_offset: default_follow_offset(),
+        engines: vec![[-0.23, 0.0, -0.72], [0.23, 0.0, -0.72]],
+        hull_color: default_hull_color(),
+        emissive_color: default_emissive_color(),
+    }]
+}
+
+// A procedurally generated scattering of distant dwarf planets
+#[derive(Deserialize)]
+struct DwarfPlanetBelt {
+    parent: String,
+    model: String,
+    count: usize,
+    #[serde(default)]
+    seed: u32,
+    min_radius: f32,
+    max_radius: f32,
+    #[serde(default = "default_max_eccentricity")]
+    max_eccentricity: f32,
+    // Degrees
+    #[serde(default = "default_max_inclination")]
+    max_inclination: f32,
+}
+
+fn default_max_eccentricity() -> f32 {
+    0.25
+}
+
+fn default_max_inclination() -> f32 {
+    17.0
+}
+
+#[derive(Deserialize, Default)]
+struct BodyConfig {
+    name: String,
+    // Bodies without a model are virtual nodes (e.g. a barycenter two stars
+    // orbit); they are never drawn
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    shader: Option<String>,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    #[serde(default = "default_color")]
+    color: u32,
+    // Bodies without a parent stay fixed at `position`
+    #[serde(default)]
+    position: Option<[f32; 3]>,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    orbital_radius: f32,
+    // Explicit angular speed in radians per second, negative for retrograde
+    #[serde(default)]
+    orbital_speed: Option<f32>,
+    // Orbit clockwise (seen from above); also applies to derived speeds
+    #[serde(default)]
+    retrograde: bool,
+    // Per-body override of the scene-wide derive_from_radius flag
+    #[serde(default)]
+    derive_from_radius: Option<bool>,
+    #[serde(default)]
+    mass: Option<f32>,
+    // Radians per second, negative spins backwards
+    #[serde(default)]
+    rotation_speed: Option<f32>,
+    // Tilt of the spin axis in degrees
+    #[serde(default)]
+    axial_tilt: f32,
+    // Keep the same face toward the parent; lock_offset (degrees) turns it
+    #[serde(default)]
+    tidally_locked: bool,
+    #[serde(default)]
+    lock_offset: f32,
+    // Starting angle along the orbit in degrees
+    #[serde(default)]
+    phase: f32,
+    // Elliptical orbit, orbital_radius is then the semi-major axis
+    #[serde(default)]
+    eccentricity: f32,
+    // Orbit plane tilt and the direction of its line of nodes, in degrees
+    #[serde(default)]
+    inclination: f32,
+    #[serde(default)]
+    ascending_node: f32,
+    // Draw as a dot until the camera is close enough to resolve the mesh
+    #[serde(default)]
+    point: bool,
+    // Rocky bodies only: terrain raised into the mesh itself, the highest
+    // peaks this share of the radius above it (0.05 is already rugged)
+    #[serde(default)]
+    displacement: f32,
+    // Rocky bodies only: terrain faked in the lighting instead, cheaper than
+    // displacement and finer than the mesh. Same scale as displacement
+    #[serde(default)]
+    bump: f32,
+    // Albedo texture (or a procedural rock pattern) painted over the mesh,
+    // mapped by its UVs or triplanar
+    #[serde(default)]
+    texture: Option<TextureConfig>,
+    // Colors and patterns of the body's shader, one table named after it
+    #[serde(default)]
+    star: Option<StarParams>,
+    #[serde(default)]
+    rocky: Option<RockyParams>,
+    #[serde(default)]
+    gas_giant: Option<GasGiantParams>,
+    // Light emitted by stars
+    #[serde(default)]
+    light_color: Option<u32>,
+    #[serde(default)]
+    luminosity: Option<f32>,
+    // Stars only: point (the default) or directional light
+    #[serde(default)]
+    light: Option<LightConfig>,
+    // Share of starlight reflected (0-1), for the temperature view
+    #[serde(default)]
+    albedo: Option<f32>,
+    // Starting velocity of a parentless body, only used in n-body mode
+    #[serde(default)]
+    velocity: Option<[f32; 3]>,
+    // Real planet ("mars") whose place on the [ephemeris] date sets the
+    // starting angle and perihelion direction, and whose real period sets
+    // the orbital speed; phase, ascending_node and orbital_speed are ignored
+    #[serde(default)]
+    ephemeris: Option<String>,
+    // Stations only: where ships dock (Z when close and slow), in model space
+    #[serde(default)]
+    docking_port: Option<[f32; 3]>,
+    // Portals only: where the wormhole leads and which way the disc faces
+    #[serde(default)]
+    portal: Option<PortalConfig>,
+    // Rocky bodies only: volcanic eruptions now and then
+    #[serde(default)]
+    eruptions: Option<EruptionSettings>,
+    // Look of the orbit ring, anything left out keeps the derived style
+    #[serde(default)]
+    orbit: Option<OrbitConfig>,
+}
+
+// [bodies.orbit] table
+#[derive(Deserialize, Default)]
+struct OrbitConfig {
+    #[serde(default)]
+    color: Option<u32>,
+    #[serde(default)]
+    width: Option<usize>,
+    #[serde(default)]
+    dashes: Option<usize>,
+    #[serde(default)]
+    visible: Option<bool>,
+}
+
+// [bodies.light] table, `type = "point"` with an optional `attenuation`
+// (constant, linear, quadratic) or `type = "directional"` with the
+// `direction` the light travels
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum LightConfig {
+    Point {
+        #[serde(default = "default_attenuation")]
+        attenuation: [f32; 3],
+    },
+    Directional {
+        direction: [f32; 3],
+    },
+}
+
+fn default_attenuation() -> [f32; 3] {
+    crate::light::DEFAULT_ATTENUATION
+}
+
+impl LightConfig {
+    fn kind(&self, owner: &str) -> Result<LightKind, String> {
+        match *self {
+            LightConfig::Point { attenuation } => {
+                let [constant, linear, quadratic] = attenuation;
+                if constant <= 0.0 || linear < 0.0 || quadratic < 0.0 {
+                    return Err(format!("{}: light attenuation needs a positive constant and no negative terms", owner));
+                }
+                Ok(LightKind::Point { attenuation })
+            }
+            LightConfig::Directional { direction } => Vec3::from(direction).try_normalize(f32::EPSILON)
+                .map(|direction| LightKind::Directional { direction })
+                .ok_or_else(|| format!("{}: light direction can't be zero", owner)),
+        }
+    }
+}
+
+// [bodies.portal] table. Portals come in pairs, each naming the other
+#[derive(Deserialize)]
+struct PortalConfig {
+    partner: String,
+    // Normal of the disc in world space (before the parent's orbit turns it)
+    #[serde(default = "default_facing")]
+    facing: [f32; 3],
+}
+
+fn default_facing() -> [f32; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+// Widest orbit ring a scene may ask for, in pixels
+const MAX_ORBIT_WIDTH: usize = 8;
+
+impl OrbitConfig {
+    fn apply(&self, style: &mut OrbitStyle, owner: &str) -> Result<(), String> {
+        if let Some(color) = self.color {
+            style.color = color;
+        }
+        if let Some(width) = self.width {
+            if !(1..=MAX_ORBIT_WIDTH).contains(&width) {
+                return Err(format!("{}: orbit width must be 1 to {}", owner, MAX_ORBIT_WIDTH));
+            }
+            style.width = width;
+        }
+        if let Some(dashes) = self.dashes {
+            if !(1..=MAX_ORBIT_DASHES).contains(&dashes) {
+                return Err(format!("{}: orbit dashes must be 1 to {}", owner, MAX_ORBIT_DASHES));
+            }
+            style.dashes = Some(dashes);
+        }
+        if let Some(visible) = self.visible {
+            style.visible = visible;
+        }
+        Ok(())
+    }
+}
+
+pub struct Scene {
+    pub settings: SimulationSettings,
+    pub bodies: Vec<CelestialBody>,
+    // Transform hierarchy that turns local positions into world ones
+    pub graph: SceneGraph,
+    pub trails: TrailSettings,
+    pub overlays: OverlaySettings,
+    // Factors for the realistic scale profile (K toggles)
+    pub realistic_scale: ScaleFactors,
+    // Selectable ships, meshes already loaded
+    pub ships: Vec<ShipDesign>,
+    // Clip capture (F9)
+    pub recording: RecordingSettings,
+    // UI palette (C cycles it) and the daltonization filter
+    pub accessibility: AccessibilitySettings,
+    // Anaglyph 3D (F3) and its eye setup
+    pub stereo: StereoSettings,
+    // Auto-exposure limits and adaptation speeds
+    pub exposure: ExposureSettings,
+    // Camera motion blur (J toggles)
+    pub motion_blur: MotionBlurSettings,
+    // Film grain, vignette and CRT filters (F2, F4, F10)
+    pub filters: FilterSettings,
+    // Color grading LUTs (F11 cycles)
+    pub grading: GradingSettings,
+    // Low-resolution, few-colors render mode (` toggles)
+    pub retro: RetroSettings,
+    // Background starfield, live or baked into a texture
+    pub sky: SkySettings,
+    // Random asteroid impacts on rocky bodies (X forces one)
+    pub impacts: ImpactSettings,
+    // Exploration checklist (Q shows it), bodies already looked up
+    pub objectives: Vec<Objective>,
+    // Energy budget for warps, off unless the scene turns it on
+    pub warp_energy: WarpEnergySettings,
+    // Real units for the HUD, hours default to the [ephemeris] year
+    pub units: Units,
+    // Temperature view (E toggles) and its color scale
+    pub heat: HeatSettings,
+    // Heat shimmer and shake close to a star, and how close it lets you get
+    pub sun_haze: SunHazeSettings,
+    // Light reaching every surface on top of the stars'
+    pub ambient: AmbientSettings,
+    // Speeding along the view in free flight (space, held)
+    pub cruise: CruiseSettings,
+    // The cheap way to draw everything (--low-spec, or the pause menu)
+    pub low_spec: LowSpecSettings,
+    // Hash of the scene file, saves made from another file are refused
+    pub hash: u64,
+    // Hash of what can't change without rebuilding the scene: the bodies and
+    // ships, their parents and models, and the simulation mode
+    pub layout: u64,
+}
+
+impl Scene {
+    // `epoch` (YYYY-MM-DD) replaces the date in the scene's [ephemeris]
+    pub fn load(path: &str, epoch: Option<&str>) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scene {}: {}", path, e))?;
+        let mut file: SceneFile = toml::from_str(&source)
+            .map_err(|e| format!("Failed to parse scene {}: {}", path, e))?;
+        if file.sky.random {
+            file.sky.starfield = SkyboxParams::random(Seed(file.simulation.seed).derive("sky"));
+        }
+        if let Some(belt) = &file.dwarf_planets {
+            let generated = generate_dwarf_planets(belt, Seed(file.simulation.seed).derive("dwarf planets").derive(belt.seed));
+            file.bodies.extend(generated);
+        }
+
+        let layout = content_hash(&file.bodies.iter()
+            .map(|config| format!("{}|{:?}|{:?}|{}\n", config.name, config.parent, config.model, config.displacement))
+            .chain(file.ships.iter().map(|config| format!("{}|{}\n", config.name, config.model)))
+            .chain(std::iter::once(format!("{:?}", file.simulation.mode)))
+            .collect::<String>());
+
+        // Parents are looked up by name and may be declared in any order
+        let name_to_index: HashMap<&str, usize> = file.bodies.iter()
+            .enumerate()
+            .map(|(i, config)| (config.name.as_str(), i))
+            .collect();
+
+        let days_since_j2000 = file.ephemeris.as_ref()
+            .map(|settings| ephemeris::parse_date(epoch.unwrap_or(&settings.epoch)))
+            .transpose()?;
+
+        let mut models: HashMap<(String, u32), Vec<Vertex>> = HashMap::new();
+        let mut textures: HashMap<String, Arc<Albedo>> = HashMap::new();
+        let mut bodies: Vec<CelestialBody> = Vec::with_capacity(file.bodies.len());
+
+        for config in &file.bodies {
+            let shader_type = match (&config.shader, &config.model) {
+                (Some(shader), _) => parse_shader_type(shader)?,
+                (None, None) => ShaderType::RockyPlanet, // never drawn
+                (None, Some(_)) => return Err(format!("{}: a body with a model needs a shader", config.name)),
+            };
+            if (config.displacement != 0.0 || config.bump != 0.0) && !matches!(shader_type, ShaderType::RockyPlanet) {
+                return Err(format!("{}: displacement and bump need the rocky shader", config.name));
+            }
+            if !(0.0..1.0).contains(&config.displacement) {
+                return Err(format!("{}: displacement must be in [0, 1)", config.name));
+            }
+            if config.bump < 0.0 {
+                return Err(format!("{}: bump can't be negative", config.name));
+            }
+            let vertices = match &config.model {
+                Some(model) => load_model(&mut models, model, &config.name, config.displacement)?,
+                None => Vec::new(),
+            };
+
+            let mut body = match &config.parent {
+                None => {
+                    let position = config.position.map(Vec3::from).unwrap_or(Vec3::zeros());
+                    let mut sun = CelestialBody::new_sun(config.name.clone(), vertices, position, config.scale, Color::from_hex(config.color));
+                    sun.shader_type = shader_type;
+                    sun
+                }
+                Some(parent_name) => {
+                    let parent_index = *name_to_index.get(parent_name.as_str())
+                        .ok_or_else(|| format!("{}: unknown parent {}", config.name, parent_name))?;
+                    let orbital_speed = config.orbital_speed.unwrap_or(0.0);
+                    if file.bodies[parent_index].parent.is_none() {
+                        // Orbiting a root body (a star or barycenter)
+                        let mut planet = CelestialBody::new_planet(
+                            config.name.clone(),
+                            vertices,
+                            Vec3::zeros(),
+                            config.orbital_radius,
+                            orbital_speed,
+                            config.scale,
+                            Color::from_hex(config.color),
+                            shader_type,
+                        );
+                        // The parent's live position is used as the orbit center
+                        planet.orbital_center = None;
+                        planet.parent_index = Some(parent_index);
+                        planet
+                    } else {
+                        CelestialBody::new_moon(
+                            config.name.clone(),
+                            vertices,
+                            parent_index,
+                            config.orbital_radius,
+                            orbital_speed,
+                            config.scale,
+                            Color::from_hex(config.color),
+                            shader_type,
+                        )
+                    }
+                }
+            };
+
+            if let Some(velocity) = config.velocity {
+                body.velocity = Vec3::from(velocity);
+            }
+            if let Some(rotation_speed) = config.rotation_speed {
+                body.rotation_speed = rotation_speed;
+            }
+            if let Some(light_color) = config.light_color {
+                body.light_color = light_color;
+            }
+            if let Some(luminosity) = config.luminosity {
+                body.luminosity = luminosity;
+            }
+            if let Some(light) = &config.light {
+                if !matches!(shader_type, ShaderType::Star) {
+                    return Err(format!("{}: only stars have a light table", config.name));
+                }
+                body.light_kind = light.kind(&config.name)?;
+            }
+            if let Some(albedo) = config.albedo {
+                if !(0.0..=1.0).contains(&albedo) {
+                    return Err(format!("{}: albedo must be in [0, 1]", config.name));
+                }
+                body.albedo = albedo;
+            }
+            body.orbital_angle = config.phase.to_radians();
+            if !(0.0..1.0).contains(&config.eccentricity) {
+                return Err(format!("{}: eccentricity must be in [0, 1)", config.name));
+            }
+            body.eccentricity = config.eccentricity;
+            body.inclination = config.inclination.to_radians();
+            body.ascending_node = config.ascending_node.to_radians();
+            body.draw_as_point = config.point;
+            body.bump = config.bump;
+            if let Some(texture) = &config.texture {
+                body.texture = Some(load_texture(&mut textures, texture, &config.name)?);
+            }
+            body.shader_params = match (shader_type, config.star, config.rocky, config.gas_giant) {
+                (_, None, None, None) => ShaderParams::defaults(shader_type),
+                (ShaderType::Star, Some(star), None, None) => ShaderParams::Star(star),
+                (ShaderType::RockyPlanet, None, Some(rocky), None) => ShaderParams::Rocky(rocky),
+                (ShaderType::GasGiant, None, None, Some(gas)) => ShaderParams::GasGiant(gas),
+                _ => return Err(format!("{}: only the table of its own shader (star, rocky or gas_giant) can be given", config.name)),
+            };
+            body.shader_params.validate(&config.name)?;
+            if config.docking_port.is_some() && !matches!(shader_type, ShaderType::Station) {
+                return Err(format!("{}: only stations have a docking port", config.name));
+            }
+            body.docking_port = config.docking_port.map(Vec3::from);
+            if let Some(eruptions) = &config.eruptions {
+                if !matches!(shader_type, ShaderType::RockyPlanet) {
+                    return Err(format!("{}: only rocky bodies have eruptions", config.name));
+                }
+                eruptions.validate(&config.name)?;
+            }
+            body.eruptions = config.eruptions;
+            if let Some(orbit) = &config.orbit {
+                orbit.apply(&mut body.orbit_style, &config.name)?;
+            }
+            body.rotation.z = config.axial_tilt.to_radians();
+            body.tidally_locked = config.tidally_locked && config.parent.is_some();
+            body.lock_offset = config.lock_offset.to_radians();
+            match (&config.portal, shader_type) {
+                (Some(portal), ShaderType::Portal) => {
+                    let partner = *name_to_index.get(portal.partner.as_str())
+                        .ok_or_else(|| format!("{}: unknown portal partner {}", config.name, portal.partner))?;
+                    let named_back = file.bodies[partner].portal.as_ref().is_some_and(|other| other.partner == config.name);
+                    if partner == bodies.len() || !named_back {
+                        return Err(format!("{}: portal partner {} must be another portal that names it back", config.name, portal.partner));
+                    }
+                    let facing = Vec3::from(portal.facing).try_normalize(f32::EPSILON)
+                        .ok_or_else(|| format!("{}: portal facing can't be zero", config.name))?;
+                    // The disc's normal is the model's +Z; portals hold still
+                    // so they keep facing that way
+                    body.rotation = Vec3::new(-facing.y.asin(), facing.x.atan2(facing.z), 0.0);
+                    body.rotation_speed = 0.0;
+                    body.tidally_locked = false;
+                    body.portal_partner = Some(partner);
+                }
+                (None, ShaderType::Portal) => return Err(format!("{}: the portal shader needs a [bodies.portal] table", config.name)),
+                (Some(_), _) => return Err(format!("{}: only portals have a portal table", config.name)),
+                (None, _) => {}
+            }
+            body.mass = match config.mass {
+                Some(mass) => mass,
+                // A lone portal isn't a star, it has nothing to pull with
+                None if config.parent.is_none() && !matches!(shader_type, ShaderType::Portal) => file.simulation.central_mass,
+                None => 0.0,
+            };
+            if config.parent.is_some() {
+                // A per-body flag wins over the scene-wide one
+                body.derive_speed = config.derive_from_radius.unwrap_or(file.simulation.derive_from_radius);
+                if !body.derive_speed && config.orbital_speed.is_none() && config.ephemeris.is_none() {
+                    return Err(format!("{}: needs orbital_speed or derive_from_radius = true", config.name));
+                }
+                if config.retrograde {
+                    // Derived speeds keep this sign, so -1 is enough until Kepler fills it in
+                    body.orbital_speed = if body.derive_speed { -1.0 } else { -body.orbital_speed.abs() };
+                }
+            }
+            if let Some(planet) = &config.ephemeris {
+                let (Some(days), Some(settings)) = (days_since_j2000, &file.ephemeris) else {
+                    return Err(format!("{}: ephemeris bodies need an [ephemeris] section", config.name));
+                };
+                let placement = ephemeris::place(planet, days).map_err(|e| format!("{}: {}", config.name, e))?;
+                // Periapsis sits on the line of nodes here, so turning the
+                // orbit by the perihelion longitude puts it where it belongs
+                body.orbital_angle = placement.true_anomaly;
+                body.ascending_node = -placement.perihelion;
+                body.orbital_speed = body.orbit_direction() * 2.0 * PI / (placement.period_years * settings.year_seconds);
+                body.derive_speed = false;
+            }
+
+            bodies.push(body);
+        }
+
+        if bodies.is_empty() {
+            return Err(format!("Scene {} has no bodies", path));
+        }
+        let units = Units {
+            seconds_per_hour: file.units.seconds_per_hour.or(file.ephemeris.as_ref().map(EphemerisSettings::hour_seconds)),
+            ..file.units
+        };
+        let objectives = objectives::resolve(&file.objectives, &name_to_index, units)?;
+
+        apply_kepler_speeds(&mut bodies, file.simulation.central_mass);
+
+        // Place every body at its starting point
+        let mut graph = SceneGraph::from_bodies(&bodies)?;
+        for body in bodies.iter_mut() {
+            body.update(0.0);
+        }
+        graph.update(&mut bodies);
+
+        if file.simulation.mode == SimulationMode::NBody {
+            if let Some(node) = bodies.iter().find(|body| body.is_virtual()) {
+                return Err(format!("{}: barycenters are only supported in kinematic mode", node.name));
+            }
+            let order = hierarchy_order(&bodies)?;
+            init_circular_velocities(&mut bodies, &order);
+        }
+
+        if file.ships.is_empty() {
+            return Err(format!("Scene {} has an empty ship list", path));
+        }
+        file.sky.validate()?;
+        file.sun_haze.validate()?;
+        file.ambient.validate()?;
+        file.cruise.validate()?;
+        file.low_spec.validate()?;
+        let mut ships = Vec::with_capacity(file.ships.len());
+        for config in &file.ships {
+            let vertices = load_model(&mut models, &config.model, &config.name, 0.0)?;
+            ships.push(ShipDesign::new(
+                config.name.clone(),
+                vertices,
+                config.scale,
+                Vec3::from(config.follow_offset),
+                config.engines.iter().copied().map(Vec3::from).collect(),
+                config.hull_color,
+                config.emissive_color,
+            ));
+        }
+
+        Ok(Scene {
+            settings: file.simulation,
+            bodies,
+            graph,
+            trails: file.trails,
+            overlays: file.overlays,
+            realistic_scale: file.realistic_scale,
+            ships,
+            recording: file.recording,
+            accessibility: file.accessibility,
+            stereo: file.stereo,
+            exposure: file.exposure,
+            motion_blur: file.motion_blur,
+            filters: file.filters,
+            grading: file.grading,
+            retro: file.retro,
+            sky: file.sky,
+            impacts: file.impacts,
+            objectives,
+            warp_energy: file.warp_energy,
+            units,
+            heat: file.heat,
+            sun_haze: file.sun_haze,
+            ambient: file.ambient,
+            cruise: file.cruise,
+            low_spec: file.low_spec,
+            hash: content_hash(&source),
+            layout,
+        })
+    }
+}
+
+// FNV-1a, stable across builds unlike std's DefaultHasher
+fn content_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+// Loads each model file once; bodies and ships sharing a model share the mesh,
+// with its normals smoothed for Gouraud and Phong shading.
+// Displaced meshes never change either, so each amplitude of a model is
+// worked out once as well
+fn load_model(models: &mut HashMap<(String, u32), Vec<Vertex>>, model: &str, owner: &str, displacement: f32) -> Result<Vec<Vertex>, String> {
+    let plain = (model.to_string(), 0.0f32.to_bits());
+    if !models.contains_key(&plain) {
+        let obj = Obj::load(model)
+            .map_err(|e| format!("Failed to load {} for {}: {}", model, owner, e))?;
+        let mut vertices = obj.get_vertex_array();
+        if obj.has_normals() {
+            smooth_normals(&mut vertices);
+        }
+        models.insert(plain.clone(), vertices);
+    }
+    let key = (model.to_string(), displacement.to_bits());
+    if !models.contains_key(&key) {
+        let mut vertices = models[&plain].clone();
+        terrain::displace(&mut vertices, displacement);
+        models.insert(key.clone(), vertices);
+    }
+    Ok(models[&key].clone())
+}
+
+// Loads each image once, bodies with the same texture share it
+fn load_texture(textures: &mut HashMap<String, Arc<Albedo>>, config: &TextureConfig, owner: &str) -> Result<SurfaceTexture, String> {
+    let albedo = match &config.path {
+        Some(path) => match textures.get(path) {
+            Some(albedo) => albedo.clone(),
+            None => {
+                let albedo = Arc::new(Albedo::load(path).map_err(|e| format!("{}: {}", owner, e))?);
+                textures.insert(path.clone(), albedo.clone());
+                albedo
+            }
+        },
+        None => Arc::new(Albedo::Procedural),
+    };
+    Ok(SurfaceTexture { albedo, mapping: config.mapping, scale: config.scale, sharpness: config.sharpness, filter: config.filter, wrap: config.wrap })
+}
+
+// Dwarf planets are muted, icy to rusty; only the hue varies much
+const DWARF_PLANET_SATURATION: (f32, f32) = (0.15, 0.45);
+const DWARF_PLANET_VALUE: (f32, f32) = (0.6, 0.85);
+// Up close their lowlands are this much darker than their color, and their
+// iron patches are turned this many degrees around the color wheel and
+// saturated this much, like the default rocky palette
+const DWARF_PLANET_LOWLAND_VALUE: f32 = 0.65;
+const DWARF_PLANET_IRON_HUE_SHIFT: f32 = -20.0;
+const DWARF_PLANET_IRON_SATURATION: f32 = 0.55;
+
+// One hue per body, each drawn from its own equal slice of the color wheel
+// so a belt never ends up all one color. A stream apart from the orbits,
+// so colors don't move the bodies
+fn dwarf_planet_colors(count: usize, seed: Seed) -> Vec<u32> {
+    let mut rng = seed.derive("colors").rng();
+    let slice = 360.0 / count.max(1) as f32;
+    (0..count)
+        .map(|i| {
+            let hue = (i as f32 + rng.next_f32()) * slice;
+            let saturation = rng.range(DWARF_PLANET_SATURATION.0, DWARF_PLANET_SATURATION.1);
+            let value = rng.range(DWARF_PLANET_VALUE.0, DWARF_PLANET_VALUE.1);
+            Color::from_hsv(hue, saturation, value).to_pixel()
+        })
+        .collect()
+}
+
+// Rocky surface colors from a dwarf planet's own color, so it doesn't turn
+// the default brown when the camera gets close enough to draw it whole
+fn dwarf_planet_surface(color: u32) -> RockyParams {
+    let base = Color::from_hex(color);
+    let (_, _, value) = base.to_hsv();
+    RockyParams {
+        highland: color,
+        lowland: base.with_value(value * DWARF_PLANET_LOWLAND_VALUE).to_pixel(),
+        iron: base.shift_hue(DWARF_PLANET_IRON_HUE_SHIFT).with_saturation(DWARF_PLANET_IRON_SATURATION).to_pixel(),
+        ..RockyParams::default()
+    }
+}
+
+// Dwarf planets on inclined, eccentric orbits with Kepler speeds, drawn as
+// points until the camera gets close
+fn generate_dwarf_planets(belt: &DwarfPlanetBelt, seed: Seed) -> Vec<BodyConfig> {
+    let colors = dwarf_planet_colors(belt.count, seed);
+    (0..belt.count)
+        .map(|i| (i, seed.derive("orbits").derive(i).rng()))
+        .map(|(i, mut rng)| BodyConfig {
+            name: format!("KBO {}", 101 + i),
+            model: Some(belt.model.clone()),
+            shader: Some("rocky".to_string()),
+            scale: rng.range(1.5, 4.0),
+            color: colors[i],
+            rocky: Some(dwarf_planet_surface(colors[i])),
+            parent: Some(belt.parent.clone()),
+            orbital_radius: rng.range(belt.min_radius, belt.max_radius),
+            derive_from_radius: Some(true),
+            rotation_speed: Some(rng.range(0.1, 0.6)),
+            phase: rng.range(0.0, 360.0),
+            eccentricity: rng.range(0.0, belt.max_eccentricity),
+            inclination: rng.range(-belt.max_inclination, belt.max_inclination),
+            ascending_node: rng.range(0.0, 360.0),
+            point: true,
+            // Hairlines, there are a lot of them
+            orbit: Some(OrbitConfig { width: Some(1), ..Default::default() }),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn parse_shader_type(name: &str) -> Result<ShaderType, String> {
+    match name {
+        "star" => Ok(ShaderType::Star),
+        "rocky" => Ok(ShaderType::RockyPlanet),
+        "gas_giant" => Ok(ShaderType::GasGiant),
+        "station" => Ok(ShaderType::Station),
+        "portal" => Ok(ShaderType::Portal),
+        other => Err(format!("Unknown shader type: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dwarf_planet_hues_cover_the_color_wheel() {
+        let colors = dwarf_planet_colors(6, Seed(7));
+        for (i, &color) in colors.iter().enumerate() {
+            // Hues are rounded through 8-bit channels, allow a few degrees
+            let (hue, _, _) = Color::from_hex(color).to_hsv();
+            let slice = i as f32 * 60.0;
+            let distance = (hue - slice - 30.0 + 180.0).rem_euclid(360.0) - 180.0;
+            assert!(distance.abs() <= 35.0, "color {} has hue {}", i, hue);
+        }
+        assert_eq!(dwarf_planet_colors(6, Seed(7)), colors);
+    }
+
+    #[test]
+    fn dwarf_planet_surfaces_take_the_body_color() {
+        let surface = dwarf_planet_surface(0x99B3CC);
+        let (hue, saturation, value) = Color::from_hex(0x99B3CC).to_hsv();
+        let (lowland, iron) = (Color::from_hex(surface.lowland).to_hsv(), Color::from_hex(surface.iron).to_hsv());
+        assert_eq!(surface.highland, 0x99B3CC);
+        assert!((lowland.0 - hue).abs() < 2.0 && (lowland.1 - saturation).abs() < 0.02);
+        assert!((lowland.2 - value * DWARF_PLANET_LOWLAND_VALUE).abs() < 0.01);
+        assert!((iron.0 - (hue + DWARF_PLANET_IRON_HUE_SHIFT)).abs() < 2.0);
+        assert!((iron.1 - DWARF_PLANET_IRON_SATURATION).abs() < 0.02 && (iron.2 - value).abs() < 0.01);
+    }
+
+    #[test]
+    fn orbit_overrides_keep_the_rest_of_the_derived_style() {
+        let color = Color::from_hex(0x3366CC);
+        let (planet, moon) = (OrbitStyle::derived(color, false), OrbitStyle::derived(color, true));
+        assert!(moon.width < planet.width);
+        assert!(Color::from_hex(moon.color).to_hsv().2 < Color::from_hex(planet.color).to_hsv().2);
+
+        let mut style = planet;
+        let config: OrbitConfig = toml::from_str("dashes = 12\nvisible = false").unwrap();
+        config.apply(&mut style, "Planet").unwrap();
+        assert_eq!(style, OrbitStyle { dashes: Some(12), visible: false, ..planet });
+        let too_wide: OrbitConfig = toml::from_str("width = 20").unwrap();
+        assert!(too_wide.apply(&mut style, "Planet").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use nalgebra_glm::{Vec3, Mat4, Quat};
+use crate::body::{CelestialBody, hierarchy_order};
+use crate::spaceship::Spaceship;
+
+pub type NodeId = usize;
+
+// Local placement of a node relative to its parent
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: f32,
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            translation: Vec3::zeros(),
+            rotation: nalgebra_glm::quat_identity(),
+            scale: 1.0,
+        }
+    }
+
+    // Euler angles applied as Rz * Ry * Rx: spin around Y happens inside the
+    // axial tilt around Z
+    pub fn from_euler(translation: Vec3, rotation: Vec3, scale: f32) -> Self {
+        let x = nalgebra_glm::quat_angle_axis(rotation.x, &Vec3::x());
+        let y = nalgebra_glm::quat_angle_axis(rotation.y, &Vec3::y());
+        let z = nalgebra_glm::quat_angle_axis(rotation.z, &Vec3::z());
+        Transform { translation, rotation: z * y * x, scale }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        nalgebra_glm::translation(&self.translation)
+            * nalgebra_glm::quat_to_mat4(&self.rotation)
+            * nalgebra_glm::scaling(&Vec3::repeat(self.scale))
+    }
+}
+
+struct Node {
+    local: Transform,
+    parent: Option<NodeId>,
+    world: Mat4,
+}
+
+// Transform hierarchy for the whole scene. Every body gets two nodes: a frame
+// that only carries its position (children orbit inside it) and a mesh node
+// under the frame with its spin, tilt and scale. Attachments hang off either
+// one depending on whether they should turn with the body. The ship has a
+// node of its own at the root.
+pub struct SceneGraph {
+    // Parents always have a smaller id than their children, so one pass in
+    // id order updates the whole tree
+    nodes: Vec<Node>,
+    // (frame, mesh) node of every body
+    body_nodes: Vec<(NodeId, NodeId)>,
+    ship: NodeId,
+}
+
+impl SceneGraph {
+    pub fn from_bodies(bodies: &[CelestialBody]) -> Result<Self, String> {
+        let mut graph = SceneGraph { nodes: Vec::new(), body_nodes: vec![(0, 0); bodies.len()], ship: 0 };
+        // Bodies may be declared child-first, so add them parents-first
+        for i in hierarchy_order(bodies)? {
+            let parent_frame = bodies[i].parent_index.map(|parent_idx| graph.body_frame(parent_idx));
+            let frame = graph.attach(parent_frame, Transform::identity());
+            let mesh = graph.attach(Some(frame), Transform::identity());
+            graph.body_nodes[i] = (frame, mesh);
+        }
+        graph.ship = graph.attach(None, Transform::identity());
+        Ok(graph)
+    }
+
+    // New node under an existing one, or at the root (rings, cloud shells,
+    // ship mounts ...)
+    pub fn attach(&mut self, parent: Option<NodeId>, local: Transform) -> NodeId {
+        self.nodes.push(Node { local, parent, world: Mat4::identity() });
+        self.nodes.len() - 1
+    }
+
+    // World matrix as of the last update
+    pub fn world_matrix(&self, node: NodeId) -> &Mat4 {
+        &self.nodes[node].world
+    }
+
+    // Node that moves with a body but doesn't spin or scale with it
+    pub fn body_frame(&self, body: usize) -> NodeId {
+        self.body_nodes[body].0
+    }
+
+    fn refresh(&mut self, id: NodeId) {
+        let local = self.nodes[id].local.matrix();
+        self.nodes[id].world = match self.nodes[id].parent {
+            Some(parent) => self.nodes[parent].world * local,
+            None => local,
+        };
+    }
+
+    // Copies the bodies' local placement into their nodes, recomputes every
+    // world matrix in one parents-first pass and writes the results back as
+    // world positions and model matrices
+    pub fn update(&mut self, bodies: &mut [CelestialBody]) {
+        for (i, body) in bodies.iter().enumerate() {
+            let (frame, mesh) = self.body_nodes[i];
+            self.nodes[frame].local = Transform { translation: body.local_position, ..Transform::identity() };
+            self.nodes[mesh].local = Transform::from_euler(Vec3::zeros(), body.rotation, body.scale);
+        }
+
+        for id in 0..self.nodes.len() {
+            self.refresh(id);
+        }
+
+        for (i, body) in bodies.iter_mut().enumerate() {
+            let (frame, mesh) = self.body_nodes[i];
+            let frame_world = &self.nodes[frame].world;
+            body.position = Vec3::new(frame_world[(0, 3)], frame_world[(1, 3)], frame_world[(2, 3)]);
+            body.model_matrix = self.nodes[mesh].world;
+        }
+    }
+
+    // Puts the ship's node where the ship has got to and hands the ship its
+    // model matrix. The ship flies among the bodies, so it only moves after
+    // update() has placed them; nothing hangs off its node, so nothing else
+    // needs recomputing
+    pub fn place_ship(&mut self, ship: &mut Spaceship) {
+        self.nodes[self.ship].local = ship.transform();
+        self.refresh(self.ship);
+        ship.model_matrix = *self.world_matrix(self.ship);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn apply(transform: &Transform, v: Vec3) -> Vec3 {
+        (transform.matrix() * nalgebra_glm::Vec4::new(v.x, v.y, v.z, 1.0)).xyz()
+    }
+
+    #[test]
+    fn euler_angles_apply_x_then_y_then_z() {
+        // Rx takes +Y to +Z, then Ry takes +Z to +X
+        let transform = Transform::from_euler(Vec3::zeros(), Vec3::new(FRAC_PI_2, FRAC_PI_2, 0.0), 1.0);
+        assert!((apply(&transform, Vec3::y()) - Vec3::x()).magnitude() < 1e-5);
+
+        // Scale before rotation, translation last
+        let transform = Transform::from_euler(Vec3::new(1.0, 2.0, 3.0), Vec3::new(0.0, 0.0, FRAC_PI_2), 2.0);
+        assert!((apply(&transform, Vec3::x()) - Vec3::new(1.0, 4.0, 3.0)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn attachments_follow_their_parent() {
+        let mut graph = SceneGraph::from_bodies(&[]).unwrap();
+        let root = graph.attach(None, Transform { translation: Vec3::new(10.0, 0.0, 0.0), ..Transform::identity() });
+        let spun = Transform::from_euler(Vec3::zeros(), Vec3::new(0.0, FRAC_PI_2, 0.0), 2.0);
+        let mesh = graph.attach(Some(root), spun);
+        let ring = graph.attach(Some(mesh), Transform { translation: Vec3::new(1.0, 0.0, 0.0), ..Transform::identity() });
+        graph.update(&mut []);
+
+        // The ring is scaled and turned with the mesh: +X becomes -Z, times 2
+        let world = graph.world_matrix(ring);
+        let position = Vec3::new(world[(0, 3)], world[(1, 3)], world[(2, 3)]);
+        assert!((position - Vec3::new(10.0, 0.0, -2.0)).magnitude() < 1e-5);
+    }
+}
@@ -0,0 +1,520 @@
+use serde::Deserialize;
+use crate::color::Color;
+use crate::rng::Seed;
+use crate::ShaderType;
+
+// Per-body look of the star, rocky and gas giant shaders. Each comes from a
+// table named after the shader in the body's scene entry (`rocky = { ... }`),
+// and anything left out keeps the value the shader always had
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShaderParams {
+    Star(StarParams),
+    Rocky(RockyParams),
+    GasGiant(GasGiantParams),
+    // The sky's, from the scene's [sky] section rather than a body
+    Skybox(SkyboxParams),
+    // Stations, ships and the rest have nothing to tune
+    Fixed,
+}
+
+impl ShaderParams {
+    // Defaults for a shader, used when the body has no table for it
+    pub fn defaults(shader_type: ShaderType) -> Self {
+        match shader_type {
+            ShaderType::Star => ShaderParams::Star(StarParams::default()),
+            ShaderType::RockyPlanet => ShaderParams::Rocky(RockyParams::default()),
+            ShaderType::GasGiant => ShaderParams::GasGiant(GasGiantParams::default()),
+            ShaderType::Skybox => ShaderParams::Skybox(SkyboxParams::default()),
+            _ => ShaderParams::Fixed,
+        }
+    }
+
+    // Whether these are the parameters `shader_type` reads. The heat view
+    // reads none, so anything goes with it
+    #[cfg(debug_assertions)]
+    pub fn suits(&self, shader_type: ShaderType) -> bool {
+        matches!(shader_type, ShaderType::Heat { .. })
+            || std::mem::discriminant(self) == std::mem::discriminant(&ShaderParams::defaults(shader_type))
+    }
+
+    // The parameters of one shader, or its defaults when these are another's
+    pub fn star(&self) -> StarParams {
+        match self {
+            ShaderParams::Star(star) => *star,
+            _ => StarParams::default(),
+        }
+    }
+
+    pub fn rocky(&self) -> RockyParams {
+        match self {
+            ShaderParams::Rocky(rocky) => *rocky,
+            _ => RockyParams::default(),
+        }
+    }
+
+    pub fn gas_giant(&self) -> GasGiantParams {
+        match self {
+            ShaderParams::GasGiant(gas) => *gas,
+            _ => GasGiantParams::default(),
+        }
+    }
+
+    pub fn skybox(&self) -> SkyboxParams {
+        match self {
+            ShaderParams::Skybox(sky) => *sky,
+            _ => SkyboxParams::default(),
+        }
+    }
+}
+
+// `star` table
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct StarParams {
+    // Multiplies the white-yellow-orange-red of the surface; white keeps it
+    #[serde(default = "default_white")]
+    pub tint: u32,
+    // Share of the brightness the pulse takes away at its lowest
+    #[serde(default = "default_pulse_amount")]
+    pub pulse_amount: f32,
+    // Scale of the flare pattern, higher gives smaller flares
+    #[serde(default = "default_one")]
+    pub flare_frequency: f32,
+    // Speeds up (or slows down) the pulse and flares
+    #[serde(default = "default_one")]
+    pub animation_speed: f32,
+}
+
+impl Default for StarParams {
+    fn default() -> Self {
+        Self {
+            tint: default_white(),
+            pulse_amount: default_pulse_amount(),
+            flare_frequency: default_one(),
+            animation_speed: default_one(),
+        }
+    }
+}
+
+// `rocky` table
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RockyParams {
+    // Surface colors at full height: the highlands, the lowlands and the
+    // iron-rich patches on high ground
+    #[serde(default = "default_highland")]
+    pub highland: u32,
+    #[serde(default = "default_lowland")]
+    pub lowland: u32,
+    #[serde(default = "default_iron")]
+    pub iron: u32,
+    // Scale of the mineral and dark patch patterns, higher is finer
+    #[serde(default = "default_one")]
+    pub noise_frequency: f32,
+    // Share of the high ground the iron-rich patches cover, 0 for none and
+    // 1 for all of it
+    #[serde(default = "default_iron_coverage")]
+    pub iron_coverage: f32,
+    // How much less light valleys and crater floors catch than the ridges
+    // around them, 0 for none and 1 for the most
+    #[serde(default = "default_ambient_occlusion")]
+    pub ambient_occlusion: f32,
+    // Degrees of latitude the polar ice caps reach down from the poles at
+    // the equinoxes, 0 for none
+    #[serde(default)]
+    pub ice_cap: f32,
+    // How far the caps' edges follow the sun: a cap grows by this many
+    // degrees for each degree the sun stands over the other hemisphere, and
+    // shrinks as much in summer
+    #[serde(default = "default_one")]
+    pub cap_growth: f32,
+    // How strongly the temperate bands green up in summer and brown in
+    // winter, 0 for not at all and 1 for the most
+    #[serde(default)]
+    pub season_shift: f32,
+}
+
+impl Default for RockyParams {
+    fn default() -> Self {
+        Self {
+            highland: default_highland(),
+            lowland: default_lowland(),
+            iron: default_iron(),
+            noise_frequency: default_one(),
+            iron_coverage: default_iron_coverage(),
+            ambient_occlusion: default_ambient_occlusion(),
+            ice_cap: 0.0,
+            cap_growth: default_one(),
+            season_shift: 0.0,
+        }
+    }
+}
+
+// `gas_giant` table
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct GasGiantParams {
+    // Hue (degrees) shared by every band; zones, belts and storms only
+    // differ in saturation and value
+    #[serde(default = "default_hue")]
+    pub hue: f32,
+    // Scale of the bands, higher gives more of them
+    #[serde(default = "default_one")]
+    pub band_frequency: f32,
+    // How much darker the storms make the bands they cross, 0 to 1
+    #[serde(default = "default_storm_strength")]
+    pub storm_strength: f32,
+    // Speeds up (or slows down) the drifting bands and storms
+    #[serde(default = "default_one")]
+    pub animation_speed: f32,
+}
+
+impl Default for GasGiantParams {
+    fn default() -> Self {
+        Self {
+            hue: default_hue(),
+            band_frequency: default_one(),
+            storm_strength: default_storm_strength(),
+            animation_speed: default_one(),
+        }
+    }
+}
+
+// `starfield` table of the [sky] section
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct SkyboxParams {
+    // Where the starfield comes from; another seed scatters the stars
+    // somewhere else entirely
+    #[serde(default = "default_sky_seed")]
+    pub seed: u64,
+    // Share of the sky's points that are stars
+    #[serde(default = "default_star_density")]
+    pub star_density: f32,
+    // Colors of the brightest stars at the cool and the hot end; each star
+    // falls somewhere between. Fainter stars lose the channels their color
+    // lacks first, so dim yellow ones turn orange
+    #[serde(default = "default_star_colors")]
+    pub star_colors: [u32; 2],
+    // Space between the stars, brightened by up to `background_variation`
+    // from point to point
+    #[serde(default = "default_sky_background")]
+    pub background: u32,
+    #[serde(default = "default_sky_variation")]
+    pub background_variation: u32,
+    // Radians a second the twinkle goes through
+    #[serde(default = "default_twinkle_speed")]
+    pub twinkle_speed: f32,
+}
+
+impl Default for SkyboxParams {
+    fn default() -> Self {
+        Self {
+            seed: default_sky_seed(),
+            star_density: default_star_density(),
+            star_colors: default_star_colors(),
+            background: default_sky_background(),
+            background_variation: default_sky_variation(),
+            twinkle_speed: default_twinkle_speed(),
+        }
+    }
+}
+
+impl SkyboxParams {
+    // A sky of its own for every seed: where the stars are, how many, their
+    // colors and the tint of the space between them
+    pub fn random(seed: Seed) -> Self {
+        let mut rng = seed.rng();
+        let seed = ((rng.next_u32() as u64) << 32) | rng.next_u32() as u64;
+        let cool = Color::from_hsv(rng.range(190.0, 240.0), rng.range(0.0, 0.3), 1.0);
+        let hot = Color::from_hsv(rng.range(20.0, 60.0), rng.range(0.1, 0.4), 1.0);
+        let hue = rng.range(0.0, 360.0);
+        Self {
+            seed,
+            star_density: rng.range(0.002, 0.01),
+            star_colors: [cool.to_pixel(), hot.to_pixel()],
+            background: Color::from_hsv(hue, 0.8, rng.range(0.04, 0.15)).to_pixel(),
+            background_variation: Color::from_hsv(hue, 0.6, rng.range(0.05, 0.12)).to_pixel(),
+            twinkle_speed: rng.range(1.0, 4.0),
+        }
+    }
+
+    // Sky noise above this is a star
+    pub fn star_threshold(&self) -> f32 {
+        1.0 - self.star_density
+    }
+}
+
+impl RockyParams {
+    pub fn colors(&self) -> (Color, Color, Color) {
+        (Color::from_hex(self.highland), Color::from_hex(self.lowland), Color::from_hex(self.iron))
+    }
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_white() -> u32 {
+    0xFFFFFF
+}
+
+fn default_pulse_amount() -> f32 {
+    0.3
+}
+
+fn default_highland() -> u32 {
+    0x8C7864
+}
+
+fn default_lowland() -> u32 {
+    0x5A5046
+}
+
+fn default_iron() -> u32 {
+    0xB46450
+}
+
+fn default_iron_coverage() -> f32 {
+    0.4
+}
+
+fn default_ambient_occlusion() -> f32 {
+    0.5
+}
+
+fn default_hue() -> f32 {
+    30.0
+}
+
+fn default_storm_strength() -> f32 {
+    0.4
+}
+
+fn default_sky_seed() -> u64 {
+    0x5EED
+}
+
+fn default_star_density() -> f32 {
+    0.005
+}
+
+fn default_star_colors() -> [u32; 2] {
+    [0xFFFFCD; 2]
+}
+
+fn default_sky_background() -> u32 {
+    0x00001E
+}
+
+fn default_sky_variation() -> u32 {
+    0x0A0F19
+}
+
+fn default_twinkle_speed() -> f32 {
+    2.0
+}
+
+// One number of a shader's parameters that can be nudged at runtime (the
+// tuning panel), with the range validate() accepts
+pub struct Tunable {
+    pub name: &'static str,
+    // Change per key press, a tenth of it for fine steps
+    pub step: f32,
+    min: f32,
+    max: f32,
+    get: fn(&ShaderParams) -> f32,
+    set: fn(&mut ShaderParams, f32),
+}
+
+impl Tunable {
+    pub fn value(&self, params: &ShaderParams) -> f32 {
+        (self.get)(params)
+    }
+
+    // Moves the value by `steps` steps, kept in range and rounded so it
+    // prints cleanly
+    pub fn nudge(&self, params: &mut ShaderParams, steps: f32) {
+        let value = (self.value(params) + self.step * steps).clamp(self.min, self.max);
+        (self.set)(params, (value * 1000.0).round() / 1000.0);
+    }
+}
+
+const STAR_TUNABLES: [Tunable; 3] = [
+    Tunable {
+        name: "pulse_amount", step: 0.05, min: 0.0, max: 1.0,
+        get: |params| params.star().pulse_amount,
+        set: |params, value| if let ShaderParams::Star(star) = params { star.pulse_amount = value },
+    },
+    Tunable {
+        name: "flare_frequency", step: 0.1, min: 0.01, max: 10.0,
+        get: |params| params.star().flare_frequency,
+        set: |params, value| if let ShaderParams::Star(star) = params { star.flare_frequency = value },
+    },
+    Tunable {
+        name: "animation_speed", step: 0.1, min: 0.0, max: 10.0,
+        get: |params| params.star().animation_speed,
+        set: |params, value| if let ShaderParams::Star(star) = params { star.animation_speed = value },
+    },
+];
+
+const ROCKY_TUNABLES: [Tunable; 3] = [
+    Tunable {
+        name: "noise_frequency", step: 0.1, min: 0.01, max: 10.0,
+        get: |params| params.rocky().noise_frequency,
+        set: |params, value| if let ShaderParams::Rocky(rocky) = params { rocky.noise_frequency = value },
+    },
+    Tunable {
+        name: "iron_coverage", step: 0.05, min: 0.0, max: 1.0,
+        get: |params| params.rocky().iron_coverage,
+        set: |params, value| if let ShaderParams::Rocky(rocky) = params { rocky.iron_coverage = value },
+    },
+    Tunable {
+        name: "ambient_occlusion", step: 0.1, min: 0.0, max: 1.0,
+        get: |params| params.rocky().ambient_occlusion,
+        set: |params, value| if let ShaderParams::Rocky(rocky) = params { rocky.ambient_occlusion = value },
+    },
+];
+
+const GAS_GIANT_TUNABLES: [Tunable; 4] = [
+    Tunable {
+        name: "hue", step: 5.0, min: 0.0, max: 360.0,
+        get: |params| params.gas_giant().hue,
+        set: |params, value| if let ShaderParams::GasGiant(gas) = params { gas.hue = value },
+    },
+    Tunable {
+        name: "band_frequency", step: 0.1, min: 0.01, max: 10.0,
+        get: |params| params.gas_giant().band_frequency,
+        set: |params, value| if let ShaderParams::GasGiant(gas) = params { gas.band_frequency = value },
+    },
+    Tunable {
+        name: "storm_strength", step: 0.05, min: 0.0, max: 1.0,
+        get: |params| params.gas_giant().storm_strength,
+        set: |params, value| if let ShaderParams::GasGiant(gas) = params { gas.storm_strength = value },
+    },
+    Tunable {
+        name: "animation_speed", step: 0.1, min: 0.0, max: 10.0,
+        get: |params| params.gas_giant().animation_speed,
+        set: |params, value| if let ShaderParams::GasGiant(gas) = params { gas.animation_speed = value },
+    },
+];
+
+impl ShaderParams {
+    // The numbers the tuning panel can change, none for fixed shaders
+    pub fn tunables(&self) -> &'static [Tunable] {
+        match self {
+            ShaderParams::Star(_) => &STAR_TUNABLES,
+            ShaderParams::Rocky(_) => &ROCKY_TUNABLES,
+            ShaderParams::GasGiant(_) => &GAS_GIANT_TUNABLES,
+            ShaderParams::Skybox(_) | ShaderParams::Fixed => &[],
+        }
+    }
+
+    // The line a scene file takes for these, e.g. `rocky = { ... }`
+    pub fn scene_line(&self) -> Option<String> {
+        match self {
+            ShaderParams::Star(star) => Some(format!(
+                "star = {{ tint = 0x{:06X}, pulse_amount = {:?}, flare_frequency = {:?}, animation_speed = {:?} }}",
+                star.tint, star.pulse_amount, star.flare_frequency, star.animation_speed,
+            )),
+            ShaderParams::Rocky(rocky) => Some(format!(
+                "rocky = {{ highland = 0x{:06X}, lowland = 0x{:06X}, iron = 0x{:06X}, noise_frequency = {:?}, iron_coverage = {:?}, ice_cap = {:?}, cap_growth = {:?}, season_shift = {:?} }}",
+                rocky.highland, rocky.lowland, rocky.iron, rocky.noise_frequency, rocky.iron_coverage, rocky.ice_cap, rocky.cap_growth, rocky.season_shift,
+            )),
+            ShaderParams::GasGiant(gas) => Some(format!(
+                "gas_giant = {{ hue = {:?}, band_frequency = {:?}, storm_strength = {:?}, animation_speed = {:?} }}",
+                gas.hue, gas.band_frequency, gas.storm_strength, gas.animation_speed,
+            )),
+            ShaderParams::Skybox(sky) => Some(format!(
+                "starfield = {{ seed = {}, star_density = {:?}, star_colors = [0x{:06X}, 0x{:06X}], background = 0x{:06X}, background_variation = 0x{:06X}, twinkle_speed = {:?} }}",
+                sky.seed, sky.star_density, sky.star_colors[0], sky.star_colors[1], sky.background, sky.background_variation, sky.twinkle_speed,
+            )),
+            ShaderParams::Fixed => None,
+        }
+    }
+
+    // Checks the ranges the shaders rely on, `owner` names the body in errors
+    pub fn validate(&self, owner: &str) -> Result<(), String> {
+        let check = |ok: bool, what: &str| if ok { Ok(()) } else { Err(format!("{}: {}", owner, what)) };
+        match self {
+            ShaderParams::Star(star) => {
+                check((0.0..=1.0).contains(&star.pulse_amount), "pulse_amount must be between 0 and 1")?;
+                check(star.flare_frequency > 0.0, "flare_frequency must be positive")
+            }
+            ShaderParams::Rocky(rocky) => {
+                check(rocky.noise_frequency > 0.0, "noise_frequency must be positive")?;
+                check((0.0..=1.0).contains(&rocky.iron_coverage), "iron_coverage must be between 0 and 1")?;
+                check((0.0..=1.0).contains(&rocky.ambient_occlusion), "ambient_occlusion must be between 0 and 1")?;
+                check((0.0..=90.0).contains(&rocky.ice_cap), "ice_cap must be between 0 and 90 degrees")?;
+                check((0.0..=4.0).contains(&rocky.cap_growth), "cap_growth must be between 0 and 4")?;
+                check((0.0..=1.0).contains(&rocky.season_shift), "season_shift must be between 0 and 1")
+            }
+            ShaderParams::GasGiant(gas) => {
+                check(gas.band_frequency > 0.0, "band_frequency must be positive")?;
+                check((0.0..=1.0).contains(&gas.storm_strength), "storm_strength must be between 0 and 1")
+            }
+            ShaderParams::Skybox(sky) => {
+                check(sky.star_density > 0.0 && sky.star_density < 1.0, "star_density must be between 0 and 1")?;
+                check(sky.twinkle_speed >= 0.0, "twinkle_speed can't be negative")
+            }
+            ShaderParams::Fixed => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_tables_keep_the_defaults_and_bad_values_are_rejected() {
+        let rocky: RockyParams = toml::from_str("highland = 0xA0A0A0\niron_coverage = 0.0").unwrap();
+        assert_eq!(rocky, RockyParams { highland: 0xA0A0A0, iron_coverage: 0.0, ..RockyParams::default() });
+        assert!(ShaderParams::Rocky(rocky).validate("Mercury").is_ok());
+
+        // Another shader's parameters read as the defaults
+        assert_eq!(ShaderParams::Rocky(rocky).gas_giant(), GasGiantParams::default());
+        assert_eq!(ShaderParams::defaults(ShaderType::Station), ShaderParams::Fixed);
+
+        let pulsing = StarParams { pulse_amount: 1.5, ..StarParams::default() };
+        assert_eq!(ShaderParams::Star(pulsing).validate("Sun"), Err("Sun: pulse_amount must be between 0 and 1".to_string()));
+        let glowing = RockyParams { ambient_occlusion: -0.2, ..RockyParams::default() };
+        assert!(ShaderParams::Rocky(glowing).validate("Io").is_err());
+        let flat = GasGiantParams { band_frequency: 0.0, ..GasGiantParams::default() };
+        assert!(ShaderParams::GasGiant(flat).validate("Jupiter").is_err());
+    }
+
+    #[test]
+    fn nudged_values_print_as_a_scene_line_that_reads_back() {
+        #[derive(Deserialize)]
+        struct Entry {
+            gas_giant: GasGiantParams,
+        }
+        let mut params = ShaderParams::defaults(ShaderType::GasGiant);
+        let storm = &params.tunables()[2];
+        assert_eq!(storm.name, "storm_strength");
+        storm.nudge(&mut params, 1.0);
+        storm.nudge(&mut params, 0.1);
+        assert_eq!(storm.value(&params), 0.455);
+        // Kept in the range validate() accepts
+        storm.nudge(&mut params, 100.0);
+        assert_eq!(storm.value(&params), 1.0);
+
+        let line = params.scene_line().unwrap();
+        let entry: Entry = toml::from_str(&line).unwrap();
+        assert_eq!(ShaderParams::GasGiant(entry.gas_giant), params);
+        assert!(ShaderParams::Fixed.tunables().is_empty());
+    }
+
+    #[test]
+    fn random_skies_differ_by_seed_and_stay_valid() {
+        let [a, b] = [1, 2].map(|seed| SkyboxParams::random(Seed(seed)));
+        assert_eq!(a, SkyboxParams::random(Seed(1)));
+        assert_ne!(a.seed, b.seed);
+        assert_ne!(a.background, b.background);
+        for sky in [a, b] {
+            assert!(ShaderParams::Skybox(sky).validate("sky").is_ok());
+            assert!(sky.star_colors.iter().chain([&sky.background]).all(|&color| color <= 0xFFFFFF));
+        }
+        // The old constant threshold, to the bit
+        assert_eq!(SkyboxParams::default().star_threshold(), 0.995);
+        let crowded = SkyboxParams { star_density: 1.0, ..SkyboxParams::default() };
+        assert!(ShaderParams::Skybox(crowded).validate("sky").is_err());
+    }
+}
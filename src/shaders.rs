@@ -1,7 +1,19 @@
-use nalgebra_glm::{Vec3, Vec4, Mat3};
+use nalgebra_glm::{Vec3, Vec4, Mat3, mat4_to_mat3};
 use crate::vertex::Vertex;
 use crate::{Uniforms, ShaderType};
 use crate::color::Color;
+use crate::exposure::STAR_RADIANCE;
+use crate::geometry::normalize_or;
+use crate::eclipse::light_passing;
+use crate::light::shade_bumped;
+use crate::shader_params::{StarParams, RockyParams, GasGiantParams, SkyboxParams};
+use crate::spaceship::engine_flicker;
+use crate::terrain;
+use crate::rng::Seed;
+
+// Share of a star's brightness lost toward its limb, where the light comes
+// from its cooler upper layers (about right for the Sun)
+const LIMB_DARKENING: f32 = 0.6;
 
 pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
   // Transform position through the complete graphics pipeline
@@ -14,32 +26,13 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
   
   // Model -> World -> View -> Clip space
   let world_position = uniforms.model_matrix * position;
-  let view_position = uniforms.view_matrix * world_position;
-  let clip_position = uniforms.projection_matrix * view_position;
-
-  // Perform perspective division to get normalized device coordinates (-1 to 1)
-  let w = clip_position.w.max(0.001); // Prevent division by zero or very small values
-  let ndc_position = Vec4::new(
-    (clip_position.x / w).clamp(-10.0, 10.0), // Clamp extreme values
-    (clip_position.y / w).clamp(-10.0, 10.0),
-    (clip_position.z / w).clamp(-10.0, 10.0),
-    1.0
-  );
-  
-  // Transform to screen coordinates
-  let screen_position = uniforms.viewport_matrix * ndc_position;
-  let transformed_position = Vec3::new(
-    screen_position.x,
-    screen_position.y,
-    screen_position.z,
-  );
+  let view_position = uniforms.frame.view_matrix * world_position;
+  // Left in clip space: triangles are clipped against the near plane
+  // before the perspective divide (see to_screen)
+  let clip_position = uniforms.frame.projection_matrix * view_position;
 
   // Transform normal
-  let model_mat3 = Mat3::new(
-    uniforms.model_matrix[0], uniforms.model_matrix[1], uniforms.model_matrix[2],
-    uniforms.model_matrix[4], uniforms.model_matrix[5], uniforms.model_matrix[6],
-    uniforms.model_matrix[8], uniforms.model_matrix[9], uniforms.model_matrix[10]
-  );
+  let model_mat3 = mat4_to_mat3(&uniforms.model_matrix);
   let normal_matrix = model_mat3.transpose().try_inverse().unwrap_or(Mat3::identity());
   let transformed_normal = normal_matrix * vertex.normal;
 
@@ -47,121 +40,240 @@ pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
   let final_color = match uniforms.shader_type {
     ShaderType::Skybox => {
       // Skybox uses fragment-based star generation
-      skybox_shader(vertex.position, uniforms.time)
+      skybox_shader(vertex.position, uniforms.frame.time, &uniforms.params.skybox())
     }
-    ShaderType::Star => {
-      // Star shader with pulsing and emission effects
-      star_shader(vertex.position, uniforms.time)
+    // Left to the fragment stage, which fills in every pixel
+    _ if shades_per_pixel(uniforms) => Color::black(),
+    ShaderType::Star | ShaderType::RockyPlanet | ShaderType::GasGiant => {
+      // Stars are spheres, so the direction from the center is a smooth
+      // normal even where the mesh's own are faceted
+      let view_normal = (uniforms.frame.view_matrix * uniforms.model_matrix * Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 0.0)).xyz();
+      surface_color(uniforms, vertex.position, transformed_normal, view_normal, view_position.xyz())
     }
-    ShaderType::RockyPlanet => {
-      // Rocky planet with surface features
-      rocky_planet_shader(vertex.position, transformed_normal, uniforms.time)
+    ShaderType::Station => {
+      // Metal hull, solar panels and blinking navigation lights
+      station_shader(vertex.position, uniforms.frame.time)
+    }
+    ShaderType::Portal => {
+      // Swirling arms around a dark throat, brightest at the rim
+      portal_shader(vertex.position, uniforms.frame.time)
+    }
+    ShaderType::Ship { hull, emissive, .. } => {
+      // Painted hull with glowing engine exhausts
+      ship_shader(vertex.position, hull, emissive)
     }
-    ShaderType::GasGiant => {
-      // Gas giant with atmospheric bands
-      gas_giant_shader(vertex.position, transformed_normal, uniforms.time)
+    ShaderType::Heat { color } => {
+      // One flat color for the whole body, picked from its temperature
+      Color::from_hex(color)
     }
   };
 
+  // Stars, portals, the sky and false colors glow all over, navigation
+  // lights and exhausts glow on their own even on the night side
+  let emission = match uniforms.shader_type {
+    ShaderType::Star | ShaderType::Portal | ShaderType::Skybox | ShaderType::Heat { .. } => 1.0,
+    ShaderType::Station if nav_light_on(vertex.position, uniforms.frame.time) => 1.0,
+    ShaderType::Ship { damage, .. } if is_exhaust(vertex.position) => engine_flicker(damage, uniforms.frame.time),
+    _ => 0.0,
+  };
+
   // Create a new Vertex with transformed attributes and lighting
   Vertex {
     position: vertex.position,
     normal: vertex.normal,
     tex_coords: vertex.tex_coords,
     color: final_color,
-    transformed_position,
+    emission,
+    // Not on screen yet, see Vertex::transformed_position
+    transformed_position: Vec3::zeros(),
     transformed_normal,
+    world_position: world_position.xyz(),
+    clip_position,
   }
 }
 
-fn skybox_shader(vertex_pos: Vec3, time: f32) -> Color {
-  // Create a starfield effect based on vertex position
-  let x = vertex_pos.x;
-  let y = vertex_pos.y;
-  let z = vertex_pos.z;
-  
-  // Use position to generate pseudo-random stars
-  let seed = (x * 12.9898 + y * 78.233 + z * 43.758).sin() * 43758.5453;
-  let noise = (seed - seed.floor()).abs();
-  
+// Perspective divide and viewport transform, for the vertices clip_triangle
+// leaves. All of them are in front of the near plane, so w is positive
+pub fn to_screen(mut vertex: Vertex, uniforms: &Uniforms) -> Vertex {
+  let clip = vertex.clip_position;
+  let ndc_position = Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+  let mut screen_position = uniforms.frame.viewport_matrix * ndc_position;
+  // Whole-pixel vertices make geometry jitter as it moves, like old consoles
+  if uniforms.frame.snap_to_pixels {
+    screen_position.x = screen_position.x.round();
+    screen_position.y = screen_position.y.round();
+  }
+  vertex.transformed_position = screen_position.xyz();
+  vertex
+}
+
+fn skybox_shader(vertex_pos: Vec3, time: f32, params: &SkyboxParams) -> Color {
+  sky_color(sky_noise(vertex_pos, params), time, params)
+}
+
+// Each point of the sky seeds its own draw, so a star stays put and looks
+// the same on every machine
+pub fn sky_noise(position: Vec3, params: &SkyboxParams) -> f32 {
+  Seed(params.seed).derive(position.x.to_bits()).derive(position.y.to_bits()).derive(position.z.to_bits()).rng().next_f32()
+}
+
+// Starfield color for a point's noise
+pub fn sky_color(noise: f32, time: f32, params: &SkyboxParams) -> Color {
   // Create twinkling effect with time
-  let twinkle = ((time * 2.0 + noise * 10.0).sin() * 0.5 + 0.5).max(0.0);
-  
-  // Create stars at specific noise thresholds
-  let star_threshold = 0.995; // Higher value = fewer stars
-  
-  if noise > star_threshold {
-    // This is a star - make it bright and white/yellow
-    let star_intensity = ((noise - star_threshold) / (1.0 - star_threshold)) * twinkle;
-    let brightness = (star_intensity * 255.0) as u8;
-    Color::new(brightness, brightness, brightness.saturating_sub(50)) // Slightly yellow
+  let twinkle = ((time * params.twinkle_speed + noise * 10.0).sin() * 0.5 + 0.5).max(0.0);
+  let threshold = params.star_threshold();
+
+  if noise > threshold {
+    // This is a star, bright and somewhere in the scene's range of colors
+    let rank = (noise - threshold) / (1.0 - threshold);
+    let [cool, hot] = params.star_colors.map(Color::from_hex);
+    let color = Color::lerp(cool, hot, (rank * 97.0).fract());
+    let brightness = (rank * twinkle * 255.0) as u8;
+    let channel = |full: u8| brightness.saturating_sub(255 - full);
+    Color::new(channel(color.r), channel(color.g), channel(color.b))
   } else {
     // Dark space background with subtle color variation
-    let r = (noise * 10.0) as u8;
-    let g = (noise * 15.0) as u8;
-    let b = (noise * 25.0 + 30.0) as u8; // Slightly blue tint
-    Color::new(r, g, b)
+    let (base, variation) = (Color::from_hex(params.background), Color::from_hex(params.background_variation));
+    let channel = |base: u8, variation: u8| (noise * variation as f32 + base as f32) as u8;
+    Color::new(channel(base.r, variation.r), channel(base.g, variation.g), channel(base.b, variation.b))
   }
 }
 
-fn calculate_lighting(vertex_pos: Vec3, normal: Vec3, light_pos: Vec3, base_color: Color) -> Color {
-  // Calculate light direction
-  let light_dir = (light_pos - vertex_pos).normalize();
-  
-  // Calculate diffuse lighting (Lambert)
-  let diffuse = normal.dot(&light_dir).max(0.0);
-  
-  // Calculate distance attenuation
-  let distance = (light_pos - vertex_pos).magnitude();
-  let attenuation = 1.0 / (1.0 + 0.0001 * distance + 0.000001 * distance * distance);
-  
-  // Ambient lighting component
-  let ambient = 0.1;
-  
-  // Combine lighting
-  let intensity = (ambient + diffuse * attenuation).min(1.0);
-  
-  Color::new(
-    (base_color.r as f32 * intensity) as u8,
-    (base_color.g as f32 * intensity) as u8,
-    (base_color.b as f32 * intensity) as u8,
-  )
+// Stars, planets and portals have detail finer than their meshes, so
+// they're colored per pixel unless the cheaper per-vertex path was asked
+// for. Stations, ships and false colors are flat per part either way
+fn shades_per_pixel(uniforms: &Uniforms) -> bool {
+  uniforms.frame.per_pixel && matches!(uniforms.shader_type, ShaderType::Star | ShaderType::RockyPlanet | ShaderType::GasGiant | ShaderType::Portal)
+}
+
+// Color of a star or planet at a point given in model space, with its world
+// normal; the view space normal and position are only used by stars
+fn surface_color(uniforms: &Uniforms, position: Vec3, normal: Vec3, view_normal: Vec3, view_position: Vec3) -> Color {
+  match uniforms.shader_type {
+    ShaderType::Star => {
+      // Pulsing and emission effects, dimmer toward the edge of the disc as
+      // seen from the camera
+      let facing = match (view_normal.try_normalize(f32::EPSILON), (-view_position).try_normalize(f32::EPSILON)) {
+        (Some(normal), Some(to_camera)) => normal.dot(&to_camera).clamp(0.0, 1.0),
+        _ => 1.0,
+      };
+      star_shader(position, uniforms.frame.time, &uniforms.params.star()) * (1.0 - LIMB_DARKENING * (1.0 - facing))
+    }
+    // Rocky planet with surface features
+    ShaderType::RockyPlanet => {
+      let params = uniforms.params.rocky();
+      seasonal(rocky_planet_shader(position, normal, uniforms.craters, uniforms.ash, &params), position, uniforms.season, &params)
+    }
+    // Gas giant with atmospheric bands
+    ShaderType::GasGiant => gas_giant_shader(position, normal, uniforms.frame.time, &uniforms.params.gas_giant()),
+    ShaderType::Portal => portal_shader(position, uniforms.frame.time),
+    _ => Color::black(),
+  }
 }
 
 // Fragment shader - applies lighting intensity as described in the reference
 pub fn fragment_shader(fragment: crate::fragment::Fragment, uniforms: &Uniforms) -> crate::fragment::Fragment {
   let mut processed_fragment = fragment;
+
+  // Per pixel work is done in model space, where the surface is fixed
+  let world_position = processed_fragment.world_position;
+  let scorched = matches!(uniforms.shader_type, ShaderType::Ship { damage, .. } if damage > 0.0);
+  let erupting = !uniforms.vents.is_empty();
+  let to_model = (shades_per_pixel(uniforms) || uniforms.bump > 0.0 || scorched || erupting).then(|| uniforms.model_matrix.try_inverse()).flatten();
+  let model_position = to_model.map(|to_model| (to_model * Vec4::new(world_position.x, world_position.y, world_position.z, 1.0)).xyz());
+
+  if let Some(position) = model_position.filter(|_| shades_per_pixel(uniforms)) {
+    let to_view = |v: Vec4| (uniforms.frame.view_matrix * v).xyz();
+    let view_position = to_view(Vec4::new(world_position.x, world_position.y, world_position.z, 1.0));
+    let view_center = to_view(uniforms.model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0));
+    let color = surface_color(uniforms, position, processed_fragment.world_normal, view_position - view_center, view_position);
+    // Stars and portals glow all over; a texture, where there is one, is kept
+    match uniforms.shader_type {
+      ShaderType::Star | ShaderType::Portal => processed_fragment.emissive = color,
+      _ if uniforms.texture.is_none() => processed_fragment.color = color,
+      _ => {}
+    }
+  }
+  // Lava showing through the cracks around an erupting vent, lit or not
+  if let Some(position) = model_position.filter(|_| erupting) {
+    processed_fragment.emissive = processed_fragment.emissive + LAVA * vent_glow(position, uniforms.vents);
+  }
   
   // Apply lighting intensity to fragment color (as described in reference)
-  let intensity_factor = processed_fragment.intensity;
-  processed_fragment.color = Color::new(
-    (processed_fragment.color.r as f32 * intensity_factor) as u8,
-    (processed_fragment.color.g as f32 * intensity_factor) as u8,
-    (processed_fragment.color.b as f32 * intensity_factor) as u8,
-  );
+  // Light is colored, so each channel is scaled separately. Emission is
+  // added after, unlit. Exposure goes on both; stars are far brighter than
+  // white, so they only dim once it drops below 1 / STAR_RADIANCE
+  let (exposure, emissive_exposure) = match uniforms.shader_type {
+    // Temperature colors are a scale, they have to read the same at any exposure
+    ShaderType::Heat { .. } => (1.0, 1.0),
+    ShaderType::Star => (uniforms.frame.exposure, (uniforms.frame.exposure * STAR_RADIANCE).min(1.0)),
+    _ => (uniforms.frame.exposure, uniforms.frame.exposure),
+  };
+  // Bump-mapped bodies are lit here, per pixel, rather than per triangle,
+  // whichever ShadingModel the rest are drawn with
+  if let Some(position) = model_position.filter(|_| uniforms.bump > 0.0) {
+    processed_fragment.intensity = bumped_intensity(uniforms, position, world_position, processed_fragment.world_normal);
+  }
+  // Eclipse shadows are worked out per pixel, even on flat-shaded
+  // triangles, so their edges stay round. The ambient light isn't blocked,
+  // and glowing triangles reflect nothing to block
+  if !uniforms.occluders.is_empty() && processed_fragment.intensity != Vec3::zeros() {
+    let direct = processed_fragment.intensity - uniforms.frame.ambient;
+    processed_fragment.intensity = direct.component_mul(&light_passing(uniforms.frame.lights, uniforms.occluders, world_position)) + uniforms.frame.ambient;
+  }
+  // Valleys and crater floors of rocky bodies catch less of the light, and
+  // so does soot on a damaged hull. Only what's reflected, glowing parts
+  // shine just as bright under it
+  let darkening = match (uniforms.shader_type, model_position) {
+    (ShaderType::RockyPlanet, Some(position)) => ambient_occlusion(position, uniforms.craters, uniforms.params.rocky().ambient_occlusion),
+    (ShaderType::Ship { damage, .. }, Some(position)) if !is_exhaust(position) => scorch_marks(position, damage),
+    _ => 1.0,
+  };
+  let reflected = processed_fragment.color.scale_rgb(processed_fragment.intensity * exposure * darkening);
+  processed_fragment.color = reflected + processed_fragment.emissive * emissive_exposure;
   
   processed_fragment
 }
 
+// Light at a point of a bump-mapped surface, from its model and world
+// positions and smooth world normal. Bumps deepen the shading but the smooth
+// normal still sets the terminator
+fn bumped_intensity(uniforms: &Uniforms, position: Vec3, world_position: Vec3, world_normal: Vec3) -> Vec3 {
+  let normal = normalize_or(world_normal, Vec3::zeros());
+  // Bodies are scaled evenly, so past its scale the model matrix only turns
+  // the slope, which is per unit of radius
+  let to_world = |v: Vec3| (uniforms.model_matrix * Vec4::new(v.x, v.y, v.z, 0.0)).xyz();
+  let scale = to_world(Vec3::x()).magnitude().max(f32::EPSILON);
+  let slope = to_world(terrain::gradient(position)) / scale;
+  // Height h raises the surface by bump * (2h - 1) of the radius, so its
+  // slope is twice the gradient's
+  let bumped = normalize_or(normal - slope * (2.0 * uniforms.bump), normal);
+  shade_bumped(uniforms.frame.lights, world_position, normal, bumped) + uniforms.frame.ambient
+}
+
 // Star shader - creates a bright, pulsing sun with corona effects
-fn star_shader(position: Vec3, time: f32) -> Color {
+fn star_shader(position: Vec3, time: f32, params: &StarParams) -> Color {
+  let time = time * params.animation_speed;
+  let flare_scale = 0.1 * params.flare_frequency;
+
   // Layer 1: Core temperature gradient
   let distance_from_center = (position.x * position.x + position.y * position.y + position.z * position.z).sqrt();
   let normalized_distance = (distance_from_center * 0.1).min(1.0);
   
   // Layer 2: Pulsing effect
-  let pulse = (time * 3.0).sin() * 0.15 + 0.85; // Pulsing between 0.7 and 1.0
+  // Dips to 1 - pulse_amount (0.7 by default) and back up to 1
+  let pulse = 1.0 - params.pulse_amount * 0.5 + (time * 3.0).sin() * params.pulse_amount * 0.5;
   
   // Layer 3: Temperature zones (hot core to cooler surface)
   let temp_factor = (1.0 - normalized_distance) * pulse;
   
   // Layer 4: Solar flares and activity
-  let flare_noise = ((position.x * 0.1 + time).sin() * (position.y * 0.1 + time).cos() + (position.z * 0.1).sin()) * 0.2;
+  let flare_noise = ((position.x * flare_scale + time).sin() * (position.y * flare_scale + time).cos() + (position.z * flare_scale).sin()) * 0.2;
   
   // Combine layers for realistic sun coloring
   let final_intensity = (temp_factor + flare_noise).max(0.0).min(1.0);
   
-  if final_intensity > 0.8 {
+  let color = if final_intensity > 0.8 {
     // Hot core - white/yellow
     Color::new(255, 255, (200.0 * final_intensity) as u8)
   } else if final_intensity > 0.5 {
@@ -170,46 +282,193 @@ fn star_shader(position: Vec3, time: f32) -> Color {
   } else {
     // Outer layer - red
     Color::new((255.0 * final_intensity) as u8, (150.0 * final_intensity) as u8, 50)
+  };
+  color.scale_rgb(Color::from_hex(params.tint).to_vec3())
+}
+
+// Impact craters: angular radius (radians, wide enough to cover a few
+// vertices of the planet mesh), how dark the floor gets, and the brighter
+// ejecta rim around it
+const CRATER_RADIUS: f32 = 0.3;
+const CRATER_FLOOR: f32 = 0.35;
+const CRATER_RIM: f32 = 1.25;
+
+// How far into the bowl of the deepest crater a point is, 1 at the center
+// of a floor and 0 from its rim outward
+fn crater_depth(position: Vec3, craters: &[Vec3]) -> f32 {
+  let direction = position.normalize();
+  craters.iter().fold(0.0, |depth, crater| {
+    let t = direction.dot(crater).clamp(-1.0, 1.0).acos() / CRATER_RADIUS;
+    depth.max(1.0 - t / 0.8)
+  })
+}
+
+// Ambient occlusion from the terrain height: below OPEN_HEIGHT the ground
+// sits in a valley and gets darker toward FLOOR_HEIGHT, where at full
+// strength only VALLEY_LIGHT of the light reaches it. Crater floors count
+// as valleys too
+const OPEN_HEIGHT: f32 = 0.55;
+const FLOOR_HEIGHT: f32 = 0.25;
+const VALLEY_LIGHT: f32 = 0.5;
+
+// Share of the light reaching a point of a rocky body, in model space
+fn ambient_occlusion(position: Vec3, craters: &[Vec3], strength: f32) -> f32 {
+  if strength <= 0.0 {
+    return 1.0;
   }
+  let valley = ((OPEN_HEIGHT - terrain::height(position)) / (OPEN_HEIGHT - FLOOR_HEIGHT)).clamp(0.0, 1.0);
+  let depth = valley.max(crater_depth(position, craters));
+  // Eased in, so the shadow gathers at the bottom rather than on the slopes
+  1.0 - strength * (1.0 - VALLEY_LIGHT) * depth * depth * (3.0 - 2.0 * depth)
+}
+
+// Shading factor from the recorded impacts, 1 away from all of them
+fn crater_shade(position: Vec3, craters: &[Vec3]) -> f32 {
+  let direction = position.normalize();
+  craters.iter().fold(1.0, |shade, crater| {
+    let t = direction.dot(crater).clamp(-1.0, 1.0).acos() / CRATER_RADIUS;
+    let factor = if t < 0.8 {
+      // Dark bowl, deepest in the middle
+      CRATER_FLOOR + (1.0 - CRATER_FLOOR) * 0.5 * (t / 0.8).powi(2)
+    } else if t < 1.0 {
+      CRATER_RIM
+    } else {
+      1.0
+    };
+    shade * factor
+  })
+}
+
+// Ash fields left by eruptions: angular radius and how much of the ground's
+// color is lost at the middle of one. Overlapping fields don't darken further
+const ASH_RADIUS: f32 = 0.4;
+const ASH_DARKNESS: f32 = 0.6;
+
+// Shading factor from the ash fields, 1 away from all of them
+fn ash_shade(position: Vec3, ash: &[Vec3]) -> f32 {
+  let direction = position.normalize();
+  let cover = ash.iter().fold(0.0f32, |cover, field| {
+    let t = direction.dot(field).clamp(-1.0, 1.0).acos() / ASH_RADIUS;
+    cover.max(1.0 - t)
+  });
+  1.0 - ASH_DARKNESS * cover * cover * (3.0 - 2.0 * cover)
+}
+
+// Erupting vents glow through cracks of noise out to VENT_RADIUS radians,
+// thinning toward the edge
+const VENT_RADIUS: f32 = 0.25;
+const VENT_CRACK_SCALE: f32 = 12.0;
+const VENT_CRACK_WIDTH: f32 = 0.12;
+const LAVA: Color = Color::new(255, 110, 20);
+
+// How brightly lava shows at a point of a rocky body, 0 to 1, from its
+// vents and how hard each is glowing
+fn vent_glow(position: Vec3, vents: &[(Vec3, f32)]) -> f32 {
+  let direction = position.normalize();
+  // Cracks are where the noise crosses its middle
+  let crack = (1.0 - (terrain::value_noise(position * VENT_CRACK_SCALE) - 0.5).abs() / VENT_CRACK_WIDTH).max(0.0);
+  vents.iter().fold(0.0f32, |glow, &(vent, strength)| {
+    let t = direction.dot(&vent).clamp(-1.0, 1.0).acos() / VENT_RADIUS;
+    // The vent itself is all lava, the cracks take over around it
+    let near = (1.0 - t).max(0.0);
+    glow.max(strength * near * crack.max(near * near))
+  })
 }
 
 // Rocky planet shader - creates terrain-like features with multiple color layers
-fn rocky_planet_shader(position: Vec3, normal: Vec3, time: f32) -> Color {
-  // Layer 1: Base terrain height using position as noise
-  let terrain_noise = (position.x * 0.05).sin() * (position.y * 0.05).cos() + (position.z * 0.03).sin();
-  let height_factor = (terrain_noise + 1.0) * 0.5; // Normalize to 0-1
+fn rocky_planet_shader(position: Vec3, normal: Vec3, craters: &[Vec3], ash: &[Vec3], params: &RockyParams) -> Color {
+  let (highland, lowland, iron_rich) = params.colors();
+  let frequency = params.noise_frequency;
+
+  // Layer 1: Terrain height, the same one displaced meshes are shaped by
+  let height_factor = terrain::height(position);
   
   // Layer 2: Crater patterns
-  let crater_pattern = ((position.x * 0.2).sin() * (position.y * 0.15).cos() * (position.z * 0.18).sin()).abs();
+  let crater_pattern = ((position.x * 0.2 * frequency).sin() * (position.y * 0.15 * frequency).cos() * (position.z * 0.18 * frequency).sin()).abs();
   let crater_factor = if crater_pattern > 0.7 { 0.3 } else { 1.0 };
   
   // Layer 3: Mineral veins and variation
-  let mineral_noise = ((position.x * 0.8 + position.y * 0.6).sin() + (position.z * 0.4).cos()) * 0.5 + 0.5;
+  let mineral_noise = (((position.x * 0.8 + position.y * 0.6) * frequency).sin() + (position.z * 0.4 * frequency).cos()) * 0.5 + 0.5;
   
   // Layer 4: Surface roughness based on normal
   let surface_roughness = (normal.x + normal.y + normal.z).abs() * 0.1 + 0.9;
   
+  // Layer 5: Craters left by impacts during this run
+  let impact_factor = crater_shade(position, craters);
+
+  // Layer 6: Ash from eruptions during this run
+  let ash_factor = ash_shade(position, ash);
+
   // Combine layers for rocky appearance
-  let base_factor = height_factor * crater_factor * surface_roughness;
+  let base_factor = height_factor * crater_factor * surface_roughness * impact_factor * ash_factor;
   
   // Color based on height and mineral content
-  if mineral_noise > 0.7 && height_factor > 0.6 {
+  // The mineral noise runs from -0.5 to 1.5, iron takes the top of it
+  if mineral_noise > 1.5 - 2.0 * params.iron_coverage && height_factor > 0.6 {
     // Iron-rich areas (reddish)
-    Color::new((180.0 * base_factor) as u8, (100.0 * base_factor) as u8, (80.0 * base_factor) as u8)
+    iron_rich * base_factor
   } else if height_factor > 0.4 {
     // Highland terrain (grayish-brown)
-    Color::new((140.0 * base_factor) as u8, (120.0 * base_factor) as u8, (100.0 * base_factor) as u8)
+    highland * base_factor
   } else {
     // Lowland/impact areas (darker)
-    Color::new((90.0 * base_factor) as u8, (80.0 * base_factor) as u8, (70.0 * base_factor) as u8)
+    lowland * base_factor
+  }
+}
+
+// Seasons of a rocky body. Temperate bands run between these latitudes
+// (degrees), fading in and out over TEMPERATE_FADE, and have shifted their
+// colors all the way once the sun stands FULL_SEASON degrees over their
+// hemisphere (or the other one). Cap edges are a few degrees ragged and soft
+const TEMPERATE_BAND: (f32, f32) = (25.0, 60.0);
+const TEMPERATE_FADE: f32 = 10.0;
+const FULL_SEASON: f32 = 25.0;
+const SUMMER_TINT: [f32; 3] = [0.8, 1.2, 0.7];
+const WINTER_TINT: [f32; 3] = [1.15, 1.0, 0.8];
+const CAP_RAGGEDNESS: f32 = 6.0;
+const CAP_SOFTNESS: f32 = 2.0;
+const ICE: Color = Color::new(232, 240, 248);
+
+// Turns a rocky body's color to the season at a point given in model
+// space. `season` is the latitude the sun stands over, in radians
+fn seasonal(color: Color, position: Vec3, season: f32, params: &RockyParams) -> Color {
+  let direction = position.normalize();
+  let latitude = direction.y.clamp(-1.0, 1.0).asin().to_degrees();
+  // How far the sun is over this hemisphere, negative in its winter
+  let summer = season.to_degrees() * latitude.signum();
+
+  let (band_start, band_end) = TEMPERATE_BAND;
+  let band = ((latitude.abs() - band_start + TEMPERATE_FADE) / TEMPERATE_FADE)
+    .min((band_end + TEMPERATE_FADE - latitude.abs()) / TEMPERATE_FADE)
+    .clamp(0.0, 1.0);
+  let shift = params.season_shift * band * (summer / FULL_SEASON).clamp(-1.0, 1.0);
+  let tint = Vec3::from(if shift > 0.0 { SUMMER_TINT } else { WINTER_TINT });
+  let color = color.scale_rgb(Vec3::repeat(1.0).lerp(&tint, shift.abs()));
+
+  if params.ice_cap <= 0.0 {
+    return color;
   }
+  let reach = (params.ice_cap - params.cap_growth * summer).clamp(0.0, 90.0);
+  // Never more ragged than the cap is big, so a vanishing cap doesn't
+  // leave specks at the pole
+  let ragged = (terrain::value_noise(position * 4.0) - 0.5) * CAP_RAGGEDNESS.min(reach);
+  let ice = ((latitude.abs() + ragged - (90.0 - reach)) / CAP_SOFTNESS).clamp(0.0, 1.0);
+  Color::lerp(color, ICE, ice)
 }
 
+// Gas giant bands share the body's hue and differ in (saturation, value)
+const LIGHT_ZONE: (f32, f32) = (0.25, 0.86);
+const DARK_BELT: (f32, f32) = (0.5, 0.63);
+const STORM: (f32, f32) = (0.5, 0.78);
+
 // Gas giant shader - creates atmospheric bands and swirling patterns
-fn gas_giant_shader(position: Vec3, normal: Vec3, time: f32) -> Color {
+fn gas_giant_shader(position: Vec3, normal: Vec3, time: f32, params: &GasGiantParams) -> Color {
+  let time = time * params.animation_speed;
+  let band_y = position.y * params.band_frequency;
+
   // Layer 1: Atmospheric bands based on latitude (y-coordinate)
-  let latitude = (position.y * 0.02).sin() * 0.5 + 0.5;
-  let band_pattern = (position.y * 0.1 + time * 0.1).sin() * 0.5 + 0.5;
+  let latitude = (band_y * 0.02).sin() * 0.5 + 0.5;
+  let band_pattern = (band_y * 0.1 + time * 0.1).sin() * 0.5 + 0.5;
   
   // Layer 2: Storm systems and turbulence
   let storm_x = (position.x * 0.03 + time * 0.2).sin();
@@ -224,20 +483,123 @@ fn gas_giant_shader(position: Vec3, normal: Vec3, time: f32) -> Color {
   
   // Combine layers for gas giant appearance
   let band_intensity = (latitude + band_pattern * 0.3) * composition_noise * depth_factor;
-  let storm_intensity = storm_factor * 0.4 + 0.6;
+  let storm_intensity = 1.0 - params.storm_strength * (1.0 - storm_factor);
   
   // Create Jupiter-like coloring with bands
   let final_factor = band_intensity * storm_intensity;
   
-  if band_pattern > 0.6 {
+  let (saturation, value) = if band_pattern > 0.6 {
     // Light bands (cream/white zones)
-    Color::new((220.0 * final_factor) as u8, (200.0 * final_factor) as u8, (170.0 * final_factor) as u8)
+    LIGHT_ZONE
   } else if band_pattern > 0.3 {
     // Dark bands (brown belts)
-    Color::new((160.0 * final_factor) as u8, (120.0 * final_factor) as u8, (80.0 * final_factor) as u8)
+    DARK_BELT
   } else {
     // Storm regions (reddish spots)
-    Color::new((200.0 * final_factor) as u8, (140.0 * final_factor) as u8, (100.0 * final_factor) as u8)
+    STORM
+  };
+  Color::from_hsv(params.hue, saturation, value) * final_factor
+}
+
+
+// Station nav lights sit at the tips of the truss (|x| >= 0.94 in the model)
+const NAV_LIGHT_START: f32 = 0.935;
+// Blinks per second, and the fraction of each blink the light stays on
+const NAV_LIGHT_RATE: f32 = 1.5;
+const NAV_LIGHT_DUTY: f32 = 0.3;
+
+const NAV_LIGHT_PORT: Color = Color::new(255, 40, 40);
+const NAV_LIGHT_STARBOARD: Color = Color::new(40, 255, 40);
+const NAV_LIGHT_OFF: Color = Color::new(40, 40, 40);
+const SOLAR_PANEL: Color = Color::new(60, 90, 170);
+const STATION_HULL: Color = Color::new(180, 180, 190);
+
+fn nav_light_on(position: Vec3, time: f32) -> bool {
+  position.x.abs() >= NAV_LIGHT_START && (time * NAV_LIGHT_RATE).fract() < NAV_LIGHT_DUTY
+}
+
+// Space station shader - metal modules, blue solar panels and nav lights
+fn station_shader(position: Vec3, time: f32) -> Color {
+  if position.x.abs() >= NAV_LIGHT_START {
+    // Red on the port side, green on starboard, dark between blinks
+    let on = nav_light_on(position, time);
+    return match (position.x < 0.0, on) {
+      (true, true) => NAV_LIGHT_PORT,
+      (false, true) => NAV_LIGHT_STARBOARD,
+      _ => NAV_LIGHT_OFF,
+    };
+  }
+
+  // Colors are per vertex, so each box of the model gets a flat color
+  let is_panel = position.x.abs() >= 0.5 && position.z.abs() < 0.02;
+  if is_panel {
+    SOLAR_PANEL
+  } else {
+    STATION_HULL
   }
 }
 
+// Portal discs lie in the model's XY plane with radius 1. Arms wind this
+// many times around, the spiral turns PORTAL_SPIN radians a second and the
+// rim glows over the outer PORTAL_RIM_WIDTH of the radius
+const PORTAL_ARMS: f32 = 3.0;
+const PORTAL_TWIST: f32 = 4.0;
+const PORTAL_SPIN: f32 = 2.0;
+const PORTAL_RIM_WIDTH: f32 = 0.15;
+
+const PORTAL_THROAT: Color = Color::new(10, 0, 30);
+const PORTAL_ARM: Color = Color::new(150, 60, 255);
+const PORTAL_RIM: Color = Color::new(200, 240, 255);
+
+// Wormhole portal shader - spiral arms of noise drawn into a dark center
+fn portal_shader(position: Vec3, time: f32) -> Color {
+  let radius = (position.x * position.x + position.y * position.y).sqrt().min(1.0);
+  let angle = position.y.atan2(position.x);
+
+  // Log spiral, so the arms tighten toward the throat
+  let spiral = angle * PORTAL_ARMS + (radius + 0.05).ln() * PORTAL_TWIST - time * PORTAL_SPIN;
+  let arms = spiral.sin() * 0.5 + 0.5;
+  // Noise turned faster near the middle breaks the arms up as they wind in
+  let turn = time * PORTAL_SPIN * (1.0 - radius);
+  let (sin, cos) = turn.sin_cos();
+  let swirled = Vec3::new(position.x * cos - position.y * sin, position.x * sin + position.y * cos, time * 0.3);
+  let streaks = terrain::value_noise(swirled * 6.0);
+
+  let glow = (arms * 0.7 + streaks * 0.3) * radius.sqrt();
+  let body = Color::lerp(PORTAL_THROAT, PORTAL_ARM, glow);
+  let rim = ((radius - (1.0 - PORTAL_RIM_WIDTH)) / PORTAL_RIM_WIDTH).clamp(0.0, 1.0);
+  Color::lerp(body, PORTAL_RIM, rim * rim)
+}
+
+// Engine exhausts are the rearmost faces of a ship model; ship meshes
+// keep their engines at the back, behind z = -0.69
+const EXHAUST_Z: f32 = -0.69;
+
+fn is_exhaust(position: Vec3) -> bool {
+  position.z <= EXHAUST_Z
+}
+
+// Scorch patches of a damaged hull: blotches of noise this many to a model
+// unit, covering up to SCORCH_COVERAGE of the noise's range on a wreck and
+// taking SCORCH_DARKNESS of the light off where they're thickest
+const SCORCH_SCALE: f32 = 5.0;
+const SCORCH_COVERAGE: f32 = 0.6;
+const SCORCH_EDGE: f32 = 0.08;
+const SCORCH_DARKNESS: f32 = 0.85;
+
+// Light kept at a point of a hull with `damage` (0 to 1). Patches start at
+// the noise's peaks and spread as the damage grows
+fn scorch_marks(position: Vec3, damage: f32) -> f32 {
+  let threshold = 1.0 - damage * SCORCH_COVERAGE;
+  let soot = ((terrain::value_noise(position * SCORCH_SCALE) - threshold) / SCORCH_EDGE).clamp(0.0, 1.0);
+  1.0 - SCORCH_DARKNESS * soot
+}
+
+// Ship shader - hull color from the ship's config, exhausts glow
+fn ship_shader(position: Vec3, hull: u32, emissive: u32) -> Color {
+  if is_exhaust(position) {
+    Color::from_hex(emissive)
+  } else {
+    Color::from_hex(hull)
+  }
+}
@@ -0,0 +1,452 @@
+use std::f32::consts::PI;
+use nalgebra_glm::{Vec3, Vec4, Mat4};
+use serde::Deserialize;
+use crate::{create_projection_matrix, create_viewport_matrix, render, FrameUniforms, Lighting, ShaderType, Uniforms};
+use crate::low_spec::Detail;
+use crate::light::ShadingModel;
+use crate::camera::look_at_matrix;
+use crate::framebuffer::{Framebuffer, Viewport};
+use crate::overlay::{draw_blended_line, ScreenProjection};
+use crate::scene_graph::Transform;
+use crate::shader_params::{ShaderParams, SkyboxParams};
+use crate::shaders::{sky_color, sky_noise};
+use crate::skybox::Skybox;
+use crate::star_catalog::{CatalogStar, FIGURES, STARS};
+use crate::text::draw_text;
+use crate::triangle::CullMode;
+use crate::vertex::Vertex;
+
+// The live sky sphere sits around the camera this far out, well inside the
+// far plane; the sky's own depth rule keeps it behind everything anyway
+const LIVE_RADIUS: f32 = 1000.0;
+// Twinkling stars are drawn this many pixels across over the baked dome
+const TWINKLE_SIZE: usize = 2;
+// Depth that anything drawn is in front of; points drawn at it with the
+// depth test off only land where the sky shows
+pub const SKY_DEPTH: f32 = f32::MAX;
+const CATALOG_STAR_COLOR: u32 = 0xFFF4E0;
+// Catalog stars brighter than this are drawn as small crosses
+const CROSS_MAGNITUDE: f32 = 2.0;
+const FIGURE_ALPHA: f32 = 0.5;
+// Background stars streaked in cruise, over the catalog ones, and how
+// strongly
+const STREAKING_STARS: usize = 400;
+const STREAK_ALPHA: f32 = 0.6;
+// Star names sit this many pixels right of and above their star
+const LABEL_OFFSET: i32 = 4;
+
+// How the background sky is drawn
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SkyMode {
+    // Flat background color only
+    Off,
+    // Rendered once into a texture that every frame samples; only the
+    // brightest stars still twinkle
+    Baked,
+    // The starfield shader on a sphere every frame, every star twinkling
+    Live,
+}
+
+impl SkyMode {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "off" => Ok(SkyMode::Off),
+            "baked" => Ok(SkyMode::Baked),
+            "live" => Ok(SkyMode::Live),
+            other => Err(format!("Unknown sky mode {} (off, baked or live)", other)),
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SkyMode::Off => "off",
+            SkyMode::Baked => "baked",
+            SkyMode::Live => "live",
+        }
+    }
+}
+
+// [sky] section of the scene file
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct SkySettings {
+    #[serde(default = "default_mode")]
+    pub mode: SkyMode,
+    // Texels along each side of the baked cube map's six faces
+    #[serde(default = "default_resolution")]
+    pub resolution: usize,
+    // Brightest stars drawn over the baked texture so they keep twinkling
+    #[serde(default = "default_twinkling")]
+    pub twinkling: usize,
+    // Detail of the live sphere
+    #[serde(default = "default_subdivisions")]
+    pub subdivisions: u32,
+    // Turns the whole sky, catalog stars included: degrees about X, Y and Z
+    #[serde(default)]
+    pub orientation: [f32; 3],
+    // Constellation figures and star names shown from the start (/ toggles)
+    #[serde(default)]
+    pub constellations: bool,
+    // Rolls the starfield from the scene's master seed, over whatever the
+    // starfield table says
+    #[serde(default)]
+    pub random: bool,
+    // Stars and the space between them ([sky.starfield])
+    #[serde(default)]
+    pub starfield: SkyboxParams,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            mode: default_mode(),
+            resolution: default_resolution(),
+            twinkling: default_twinkling(),
+            subdivisions: default_subdivisions(),
+            orientation: [0.0; 3],
+            constellations: false,
+            random: false,
+            starfield: SkyboxParams::default(),
+        }
+    }
+}
+
+fn default_mode() -> SkyMode {
+    SkyMode::Off
+}
+
+fn default_resolution() -> usize {
+    1024
+}
+
+fn default_twinkling() -> usize {
+    64
+}
+
+fn default_subdivisions() -> u32 {
+    30
+}
+
+impl SkySettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(16..=4096).contains(&self.resolution) {
+            return Err("sky resolution must be 16 to 4096".to_string());
+        }
+        if !(4..=200).contains(&self.subdivisions) {
+            return Err("sky subdivisions must be 4 to 200".to_string());
+        }
+        ShaderParams::Skybox(self.starfield).validate("sky starfield")
+    }
+
+    // The same settings in `mode` when one is given (--sky)
+    pub fn with_mode(self, mode: Option<SkyMode>) -> Self {
+        Self { mode: mode.unwrap_or(self.mode), ..self }
+    }
+
+    fn rotation(&self) -> Mat4 {
+        Transform::from_euler(Vec3::zeros(), Vec3::from(self.orientation).map(f32::to_radians), 1.0).matrix()
+    }
+}
+
+// The live sky rendered once into a cube map: six square faces, one each
+// side of every axis, so a direction finds its texel without any trig
+struct Dome {
+    size: usize,
+    // Face after face, row after row, as framebuffer pixels
+    texels: Vec<u32>,
+    // Brightest stars of the sphere: direction and sky noise
+    twinkling: Vec<(Vec3, f32)>,
+}
+
+impl Dome {
+    fn bake(sphere: &[Vertex], params: &SkyboxParams, size: usize, twinkling: usize) -> Self {
+        let viewport = Viewport { x: 0, y: 0, width: size, height: size };
+        let mut texels = Vec::with_capacity(6 * size * size);
+        for face in 0..6 {
+            // A quarter turn across each face, looking straight down its axis
+            let forward = face_direction(face, 0.0, 0.0);
+            let projection = ScreenProjection {
+                view_matrix: look_at_matrix(Vec3::zeros(), forward, face_direction(face, 0.0, 1.0) - forward),
+                projection_matrix: create_projection_matrix(PI / 2.0, 1.0, 1.0, LIVE_RADIUS * 2.0),
+                viewport_matrix: create_viewport_matrix(viewport),
+                snap_to_pixels: false,
+                affine: false,
+            };
+            let mut image = Framebuffer::new(size, size);
+            draw_sphere(&mut image, &projection, Mat4::identity(), sphere, params, 0.0);
+            // Whichever way the camera has the face turned, every texel takes
+            // the pixel its own direction lands on
+            let to_screen = rotation_only(&projection);
+            for row in 0..size {
+                for column in 0..size {
+                    let direction = face_direction(face, texel_center(column, size), texel_center(row, size));
+                    let screen = to_screen * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+                    let pixel = |coordinate: f32| ((coordinate / screen.w).max(0.0) as usize).min(size - 1);
+                    texels.push(image.buffer[pixel(screen.y) * size + pixel(screen.x)]);
+                }
+            }
+        }
+
+        Self { size, texels, twinkling: brightest_stars(sphere, params, twinkling) }
+    }
+
+    // Nearest texel, any length of direction but zero
+    fn sample(&self, direction: Vec3) -> u32 {
+        let (face, u, v) = face_coordinates(direction);
+        let texel = |coordinate: f32| (((coordinate + 1.0) * 0.5 * self.size as f32) as usize).min(self.size - 1);
+        self.texels[(face * self.size + texel(v)) * self.size + texel(u)]
+    }
+}
+
+// The `count` brightest stars of the sphere: direction and sky noise
+fn brightest_stars(sphere: &[Vertex], params: &SkyboxParams, count: usize) -> Vec<(Vec3, f32)> {
+    let mut stars: Vec<(Vec3, f32)> = sphere.iter()
+        .map(|vertex| (vertex.position.normalize(), sky_noise(vertex.position, params)))
+        .filter(|&(_, noise)| noise > params.star_threshold())
+        .collect();
+    stars.sort_by(|a, b| b.1.total_cmp(&a.1));
+    // Triangles share their corners
+    stars.dedup_by(|a, b| a.1 == b.1);
+    stars.truncate(count);
+    stars
+}
+
+// -1 to 1 across a face's `size` texels
+fn texel_center(texel: usize, size: usize) -> f32 {
+    (texel as f32 + 0.5) / size as f32 * 2.0 - 1.0
+}
+
+// Face 2a looks down axis a, face 2a + 1 up it; u and v run along the next
+// two axes
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let axis = face / 2;
+    let mut direction = Vec3::zeros();
+    direction[axis] = [1.0, -1.0][face % 2];
+    direction[(axis + 1) % 3] = u;
+    direction[(axis + 2) % 3] = v;
+    direction.normalize()
+}
+
+fn face_coordinates(direction: Vec3) -> (usize, f32, f32) {
+    let size = direction.abs();
+    let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+    let face = axis * 2 + usize::from(direction[axis] < 0.0);
+    (face, direction[(axis + 1) % 3] / size[axis], direction[(axis + 2) % 3] / size[axis])
+}
+
+// Viewport, projection and view without the camera's position: the sky is
+// infinitely far away, only where the camera looks matters
+fn rotation_only(projection: &ScreenProjection) -> Mat4 {
+    let mut view = projection.view_matrix;
+    view[(0, 3)] = 0.0;
+    view[(1, 3)] = 0.0;
+    view[(2, 3)] = 0.0;
+    projection.viewport_matrix * projection.projection_matrix * view
+}
+
+// Background behind the bodies, drawn after them into whatever they left
+// empty (see Framebuffer::set_depth_test)
+pub struct Sky {
+    settings: SkySettings,
+    dome: Option<Dome>,
+    sphere: Vec<Vertex>,
+    // Where each catalog star is before the orientation turns the sky
+    stars: Vec<Vec3>,
+    // Brightest background stars, the ones that streak in cruise
+    streaking: Vec<(Vec3, f32)>,
+}
+
+impl Sky {
+    pub fn new(settings: SkySettings) -> Self {
+        let sphere = match settings.mode {
+            SkyMode::Off => Vec::new(),
+            _ => Skybox::create_sphere_vertices(LIVE_RADIUS, settings.subdivisions),
+        };
+        let dome = (settings.mode == SkyMode::Baked).then(|| Dome::bake(&sphere, &settings.starfield, settings.resolution, settings.twinkling));
+        let stars = STARS.iter().map(CatalogStar::direction).collect();
+        let streaking = brightest_stars(&sphere, &settings.starfield, STREAKING_STARS);
+        Self { settings, dome, sphere, stars, streaking }
+    }
+
+    // Bakes again only when something changed, the starfield included
+    pub fn set_settings(&mut self, settings: SkySettings) {
+        if settings != self.settings {
+            *self = Sky::new(settings);
+        }
+    }
+
+    pub fn mode(&self) -> SkyMode {
+        self.settings.mode
+    }
+
+    // Fills the framebuffer's clip rectangle as seen through `projection`,
+    // catalog stars over the background ones
+    pub fn draw(&self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, time: f32) {
+        if matches!(self.settings.mode, SkyMode::Off) {
+            return;
+        }
+        let to_screen = rotation_only(projection) * self.settings.rotation();
+        framebuffer.set_depth_test(false);
+        match &self.dome {
+            Some(dome) => draw_dome(framebuffer, &to_screen, dome, &self.settings.starfield, time),
+            None => {
+                if let Some(camera) = projection.view_matrix.try_inverse().map(|inverse| inverse.column(3).xyz()) {
+                    let centered = nalgebra_glm::translation(&camera) * self.settings.rotation();
+                    draw_sphere(framebuffer, projection, centered, &self.sphere, &self.settings.starfield, time);
+                }
+            }
+        }
+        framebuffer.set_depth_test(true);
+        for (star, &direction) in STARS.iter().zip(&self.stars) {
+            let Some((x, y)) = sky_to_screen(&to_screen, direction) else { continue };
+            let brightness = star.brightness();
+            framebuffer.blend_point(x, y, SKY_DEPTH, CATALOG_STAR_COLOR, brightness);
+            // The brightest get a small cross, fainter at its arms
+            if star.magnitude < CROSS_MAGNITUDE {
+                for (dx, dy) in [(1, 0), (0, 1), (-1, 0), (0, -1)] {
+                    framebuffer.blend_point(x.wrapping_add_signed(dx), y.wrapping_add_signed(dy), SKY_DEPTH, CATALOG_STAR_COLOR, brightness * 0.5);
+                }
+            }
+        }
+    }
+
+    // Stars smeared back towards where the camera is heading, as if they
+    // rushed past; `motion` points there and is as long as the streaks, in
+    // radians (see Cruise::streak)
+    pub fn draw_streaks(&self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, motion: Vec3, time: f32) {
+        if self.settings.mode == SkyMode::Off {
+            return;
+        }
+        let rotation = self.settings.rotation();
+        let to_screen = rotation_only(projection) * rotation;
+        // Into the sky before its orientation
+        let motion = (rotation.transpose() * Vec4::new(motion.x, motion.y, motion.z, 0.0)).xyz();
+        let catalog = STARS.iter().zip(&self.stars).map(|(star, &direction)| (direction, CATALOG_STAR_COLOR, star.brightness()));
+        let background = self.streaking.iter().map(|&(direction, noise)| (direction, sky_color(noise, time, &self.settings.starfield).to_pixel(), 1.0));
+        for (direction, color, brightness) in catalog.chain(background) {
+            // Stars drift away from the point ahead, so the trail leads back to it
+            let tail = direction + motion - direction * motion.dot(&direction);
+            if let (Some(head), Some(tail)) = (sky_point(&to_screen, direction), sky_point(&to_screen, tail)) {
+                draw_blended_line(framebuffer, tail, head, color, brightness * STREAK_ALPHA);
+            }
+        }
+    }
+
+    // Constellation lines and the famous stars' names, over the sky only
+    pub fn draw_figures(&self, framebuffer: &mut Framebuffer, projection: &ScreenProjection, color: u32) {
+        if self.settings.mode == SkyMode::Off {
+            return;
+        }
+        let to_screen = rotation_only(projection) * self.settings.rotation();
+        let screen = |index: usize| sky_point(&to_screen, self.stars[index]);
+        for lines in FIGURES {
+            for &(a, b) in lines {
+                if let (Some(a), Some(b)) = (screen(a), screen(b)) {
+                    draw_blended_line(framebuffer, a, b, color, FIGURE_ALPHA);
+                }
+            }
+        }
+        for (index, star) in STARS.iter().enumerate().filter(|(_, star)| star.labeled) {
+            let Some((x, y)) = sky_to_screen(&to_screen, self.stars[index]) else { continue };
+            // Hidden with the star behind a body
+            if framebuffer.depth(x, y) == Some(f32::INFINITY) {
+                draw_text(framebuffer, x as i32 + LABEL_OFFSET, y as i32 - LABEL_OFFSET, star.name, color);
+            }
+        }
+    }
+}
+
+// Screen position of a sky direction at the sky's depth, None behind the
+// camera
+fn sky_point(to_screen: &Mat4, direction: Vec3) -> Option<Vec3> {
+    let point = to_screen * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+    (point.w > 0.0).then(|| Vec3::new(point.x / point.w, point.y / point.w, SKY_DEPTH))
+}
+
+// Pixel a sky direction lands on, None behind the camera or off the top
+// or left edge
+fn sky_to_screen(to_screen: &Mat4, direction: Vec3) -> Option<(usize, usize)> {
+    let screen = to_screen * Vec4::new(direction.x, direction.y, direction.z, 0.0);
+    let (x, y) = (screen.x / screen.w, screen.y / screen.w);
+    (screen.w > 0.0 && x >= 0.0 && y >= 0.0).then_some((x as usize, y as usize))
+}
+
+// The starfield shader on the sky sphere, placed by `model_matrix`
+fn draw_sphere(framebuffer: &mut Framebuffer, projection: &ScreenProjection, model_matrix: Mat4, sphere: &[Vertex], params: &SkyboxParams, time: f32) {
+    let lighting = Lighting { lights: &[], ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail { per_pixel: false, ..Detail::FULL }, shading: ShadingModel::Flat };
+    let frame = FrameUniforms::new(projection, lighting, time, framebuffer.clip());
+    render(framebuffer, &Uniforms::new(&frame, model_matrix, ShaderType::Skybox).with_params(ShaderParams::Skybox(*params)).with_cull_mode(CullMode::Front).with_label("skybox"), sphere);
+}
+
+// `to_screen` takes the dome's directions to pixels
+fn draw_dome(framebuffer: &mut Framebuffer, to_screen: &Mat4, dome: &Dome, params: &SkyboxParams, time: f32) {
+    let Some(to_sky) = to_screen.try_inverse() else { return };
+    // A pixel's far plane point, back in view space, is linear in its screen
+    // position before the divide, so each pixel only adds a column
+    let (step_x, step_y) = (to_sky.column(0).into_owned(), to_sky.column(1).into_owned());
+    let corner = to_sky * Vec4::new(0.5, 0.5, 1.0, 1.0);
+    let clip = framebuffer.clip();
+    for y in clip.y..clip.y + clip.height {
+        for x in clip.x..clip.x + clip.width {
+            if framebuffer.depth(x, y) != Some(f32::INFINITY) {
+                continue;
+            }
+            let point = corner + step_x * x as f32 + step_y * y as f32;
+            framebuffer.set_current_color(dome.sample(point.xyz() / point.w));
+            framebuffer.point(x, y, SKY_DEPTH);
+        }
+    }
+
+    for &(direction, noise) in &dome.twinkling {
+        let Some((x, y)) = sky_to_screen(to_screen, direction) else { continue };
+        framebuffer.set_current_color(sky_color(noise, time, params).to_pixel());
+        for dy in 0..TWINKLE_SIZE {
+            for dx in 0..TWINKLE_SIZE {
+                framebuffer.point(x + dx, y + dy, SKY_DEPTH);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baked_dome_looks_like_the_live_sky() {
+        let sphere = Skybox::create_sphere_vertices(LIVE_RADIUS, 30);
+        let params = SkyboxParams { seed: 7, star_colors: [0xC0D0FF, 0xFFC080], ..SkyboxParams::default() };
+        let dome = Dome::bake(&sphere, &params, 256, 2);
+        assert_eq!(dome.twinkling.len(), 2);
+        assert!(dome.twinkling.windows(2).all(|pair| pair[0].1 > pair[1].1));
+        for face in 0..6 {
+            assert_eq!(face_coordinates(face_direction(face, 0.3, -0.6) * 3.0).0, face);
+        }
+
+        // The same view drawn live and from the dome, a planet in front
+        let viewport = Viewport { x: 0, y: 0, width: 64, height: 48 };
+        let projection = ScreenProjection {
+            view_matrix: look_at_matrix(Vec3::new(5.0, 1.0, 3.0), Vec3::new(6.0, 1.5, 3.5), Vec3::y()),
+            projection_matrix: create_projection_matrix(PI / 3.0, viewport.aspect_ratio(), 0.1, 2000.0),
+            viewport_matrix: create_viewport_matrix(viewport),
+            snap_to_pixels: false,
+            affine: false,
+        };
+        let [mut expected, mut baked] = [(); 2].map(|_| {
+            let mut framebuffer = Framebuffer::new(64, 48);
+            framebuffer.set_background_color(0xFF00FF);
+            framebuffer.clear();
+            framebuffer.set_current_color(0x00FF00);
+            framebuffer.point(10, 10, 0.5);
+            framebuffer
+        });
+        draw_sphere(&mut expected, &projection, nalgebra_glm::translation(&Vec3::new(5.0, 1.0, 3.0)), &sphere, &params, 0.0);
+        draw_dome(&mut baked, &rotation_only(&projection), &Dome { twinkling: Vec::new(), ..dome }, &params, 0.0);
+        assert_eq!(baked.buffer[10 * 64 + 10], 0x00FF00);
+        assert!(baked.buffer.iter().all(|&pixel| pixel != 0xFF00FF));
+        let difference = expected.buffer.iter().zip(&baked.buffer)
+            .map(|(&a, &b)| (0..3).map(|channel| ((a >> (channel * 8)) as u8).abs_diff((b >> (channel * 8)) as u8) as u32).max().unwrap())
+            .sum::<u32>() as f32 / expected.buffer.len() as f32;
+        assert!(difference < 2.0, "baked sky is {} levels off the live one", difference);
+    }
+}
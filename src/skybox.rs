@@ -28,7 +28,8 @@ impl Skybox {
             }
         }
         
-        // Generate triangles for the sphere
+        // Generate triangles for the sphere, counterclockwise seen from
+        // outside like the model files
         let mut triangle_vertices = Vec::new();
         for i in 0..subdivisions {
             for j in 0..subdivisions {
@@ -37,13 +38,13 @@ impl Skybox {
                 
                 // First triangle
                 triangle_vertices.push(vertices[current as usize].clone());
-                triangle_vertices.push(vertices[(next + 1) as usize].clone());
                 triangle_vertices.push(vertices[(current + 1) as usize].clone());
+                triangle_vertices.push(vertices[(next + 1) as usize].clone());
                 
                 // Second triangle
                 triangle_vertices.push(vertices[current as usize].clone());
-                triangle_vertices.push(vertices[next as usize].clone());
                 triangle_vertices.push(vertices[(next + 1) as usize].clone());
+                triangle_vertices.push(vertices[next as usize].clone());
             }
         }
         
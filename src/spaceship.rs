@@ -0,0 +1,506 @@
+use nalgebra_glm::{Vec3, Mat4, Quat};
+use std::f32::consts::PI;
+use crate::body::{CelestialBody, mesh_radius};
+use crate::camera::Camera;
+use crate::geometry::normalize_or;
+use crate::portal::Passage;
+use crate::rng::{Rng, Seed};
+use crate::scene_graph::Transform;
+use crate::terrain::value_noise;
+use crate::vertex::Vertex;
+use crate::ShaderType;
+
+// Keeps big ships from reaching into the camera's near plane
+const MIN_CHASE_DISTANCE: f32 = 12.0;
+
+// How long the shield stays lit after a hit
+const SHIELD_FLASH_SECONDS: f32 = 0.6;
+// Warn when the ship would reach a surface in less than this many seconds
+const WARNING_TIME: f32 = 3.0;
+// Damage from hitting a surface, per unit of speed into it; ramming at 200
+// units a second wrecks the ship
+const DAMAGE_PER_SPEED: f32 = 0.005;
+// Sparks a second from the hull of a wrecked ship, fewer with less damage.
+// Each flies off at SPARK_SPEED ship radii a second for SPARK_SECONDS
+const SPARKS_PER_SECOND: f32 = 12.0;
+const SPARK_SECONDS: f32 = 0.5;
+const SPARK_SPEED: f32 = 1.5;
+// Engine glow changes brightness this many times a second when damaged
+const FLICKER_RATE: f32 = 20.0;
+
+// Closest body the ship is heading into
+pub struct ProximityWarning {
+    pub body: usize,
+    // Gap between the ship's and the body's bounding spheres
+    pub clearance: f32,
+    pub closing_speed: f32,
+}
+
+// Thrown off a damaged hull, kept relative to the ship so it stays in view
+struct Spark {
+    offset: Vec3,
+    velocity: Vec3,
+    age: f32,
+}
+
+// One selectable ship, from the scene's [[ships]] entries
+pub struct ShipDesign {
+    pub name: String,
+    pub vertices: Vec<Vertex>,
+    pub scale: f32,
+    // Where the ship sits relative to the camera, in ship radii: x right, y
+    // up, z along the view. Radii come from the mesh, so ships of any size
+    // sit the same way in frame.
+    pub follow_offset: Vec3,
+    // Engine exhausts in model space, drawn as glowing points
+    pub engines: Vec<Vec3>,
+    pub hull_color: u32,
+    pub emissive_color: u32,
+    mesh_radius: f32,
+}
+
+impl ShipDesign {
+    pub fn new(
+        name: String,
+        vertices: Vec<Vertex>,
+        scale: f32,
+        follow_offset: Vec3,
+        engines: Vec<Vec3>,
+        hull_color: u32,
+        emissive_color: u32,
+    ) -> Self {
+        let mesh_radius = mesh_radius(&vertices);
+        Self { name, vertices, scale, follow_offset, engines, hull_color, emissive_color, mesh_radius }
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        self.mesh_radius * self.scale
+    }
+}
+
+pub struct Spaceship {
+    designs: Vec<ShipDesign>,
+    current: usize,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    // Smoothed attitude in radians: heading around the vertical axis (the
+    // model's nose points along +Z), nose up, and bank (positive raises the
+    // +X wing)
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    // Seconds of shield flash left
+    shield: f32,
+    // 0 for a fresh hull up to 1 for a wreck. Only new contacts add to it,
+    // scraping along a surface doesn't
+    damage: f32,
+    // Where the last hit landed, a direction in model space
+    damage_point: Vec3,
+    touching: bool,
+    sparks: Vec<Spark>,
+    // Sparks owed to the emission rate but not thrown yet
+    spark_debt: f32,
+    rng: Rng,
+    // Last frame's clearance to every body, for closing speeds
+    clearances: Vec<f32>,
+    placed: bool,
+    // World matrix of the mesh, cached by the scene graph
+    pub model_matrix: Mat4,
+}
+
+impl Spaceship {
+    // Time constant (seconds) of the attitude smoothing, larger lags more
+    pub const ORIENTATION_LAG: f32 = 0.25;
+    // Bank (radians) per radian per second of turn rate, and its limit
+    pub const BANK_PER_YAW_RATE: f32 = 0.35;
+    pub const MAX_BANK: f32 = 0.6;
+
+    // `designs` must not be empty
+    pub fn new(designs: Vec<ShipDesign>) -> Self {
+        Self {
+            designs,
+            current: 0,
+            position: Vec3::zeros(),
+            velocity: Vec3::zeros(),
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            shield: 0.0,
+            damage: 0.0,
+            damage_point: -Vec3::z(),
+            touching: false,
+            sparks: Vec::new(),
+            spark_debt: 0.0,
+            rng: Seed(1).derive("sparks").rng(),
+            clearances: Vec::new(),
+            placed: false,
+            model_matrix: Mat4::identity(),
+        }
+    }
+
+    pub fn design(&self) -> &ShipDesign {
+        &self.designs[self.current]
+    }
+
+    pub fn current_design(&self) -> usize {
+        self.current
+    }
+
+    // Puts the ship back where a save left it
+    pub fn restore(&mut self, design: usize, position: Vec3, velocity: Vec3, (yaw, pitch, roll): (f32, f32, f32), damage: f32) {
+        self.current = design.min(self.designs.len() - 1);
+        self.position = position;
+        self.velocity = velocity;
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+        self.shield = 0.0;
+        self.damage = damage.clamp(0.0, 1.0);
+        self.touching = false;
+        self.sparks.clear();
+        self.clearances.clear();
+        self.placed = true;
+    }
+
+    // Switches to the next ship; meshes are all loaded with the scene
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.designs.len();
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        self.design().bounding_radius()
+    }
+
+    pub fn shader_type(&self) -> ShaderType {
+        let design = self.design();
+        ShaderType::Ship { hull: design.hull_color, emissive: design.emissive_color, damage: self.damage }
+    }
+
+    pub fn damage(&self) -> f32 {
+        self.damage
+    }
+
+    pub fn repair(&mut self) {
+        self.damage = 0.0;
+        self.sparks.clear();
+    }
+
+    // Puts the ship in front of the camera and eases its attitude toward
+    // where the camera looks, banking into turns like an aircraft
+    pub fn update_position(&mut self, camera: &Camera, delta_time: f32) {
+        // Same basis as the view matrix, so the offset turns with pitch too
+        let forward = normalize_or(camera.target - camera.position, -Vec3::z());
+        let right = normalize_or(forward.cross(&camera.up), Vec3::x());
+        let up = right.cross(&forward);
+
+        let offset = self.design().follow_offset * self.bounding_radius();
+        let ahead = offset.z.max(MIN_CHASE_DISTANCE + self.bounding_radius());
+        let position = camera.position + right * offset.x + up * offset.y + forward * ahead;
+        let target_yaw = forward.x.atan2(forward.z);
+        let target_pitch = forward.y.clamp(-1.0, 1.0).asin();
+
+        if self.placed {
+            self.velocity = (position - self.position) / delta_time;
+            // Exponential smoothing, independent of the frame rate
+            let blend = 1.0 - (-delta_time / Self::ORIENTATION_LAG).exp();
+            let previous_yaw = self.yaw;
+            self.yaw = wrap_angle(self.yaw + wrap_angle(target_yaw - self.yaw) * blend);
+            self.pitch += (target_pitch - self.pitch) * blend;
+
+            // Turning toward +X (the ship's left) lowers the +X wing
+            let yaw_rate = wrap_angle(self.yaw - previous_yaw) / delta_time;
+            let target_roll = (-yaw_rate * Self::BANK_PER_YAW_RATE).clamp(-Self::MAX_BANK, Self::MAX_BANK);
+            self.roll += (target_roll - self.roll) * blend;
+        } else {
+            self.velocity = Vec3::zeros();
+            self.yaw = target_yaw;
+            self.pitch = target_pitch;
+            self.roll = 0.0;
+        }
+        self.position = position;
+        self.placed = true;
+        self.shield = (self.shield - delta_time).max(0.0);
+        self.update_sparks(delta_time);
+    }
+
+    // Places the ship directly, for sequences that fly it themselves like
+    // docking; the velocity follows from the move
+    pub fn hold(&mut self, position: Vec3, (yaw, pitch, roll): (f32, f32, f32), delta_time: f32) {
+        self.velocity = (position - self.position) / delta_time;
+        self.position = position;
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self.roll = roll;
+        self.touching = false;
+        self.clearances.clear();
+        self.shield = (self.shield - delta_time).max(0.0);
+        self.update_sparks(delta_time);
+    }
+
+    // Carries the ship through a wormhole, turned the way the passage turns
+    // space; the bank is kept
+    pub fn pass_through(&mut self, passage: &Passage) {
+        let nose = normalize_or(passage.direction(nalgebra_glm::quat_rotate_vec3(&self.attitude(), &Vec3::z())), Vec3::z());
+        self.position = passage.point(self.position);
+        self.velocity = passage.velocity(self.velocity);
+        self.yaw = nose.x.atan2(nose.z);
+        self.pitch = nose.y.clamp(-1.0, 1.0).asin();
+        self.clearances.clear();
+    }
+
+    // Ages the sparks and throws new ones from where the last hit landed
+    fn update_sparks(&mut self, delta_time: f32) {
+        for spark in &mut self.sparks {
+            spark.offset += spark.velocity * delta_time;
+            spark.age += delta_time;
+        }
+        self.sparks.retain(|spark| spark.age < SPARK_SECONDS);
+
+        self.spark_debt += SPARKS_PER_SECOND * self.damage * delta_time;
+        let radius = self.bounding_radius();
+        let outward = normalize_or(nalgebra_glm::quat_rotate_vec3(&self.attitude(), &self.damage_point), Vec3::y());
+        while self.spark_debt >= 1.0 {
+            self.spark_debt -= 1.0;
+            let direction = normalize_or(outward + self.rng.unit_vector() * 0.7, outward);
+            self.sparks.push(Spark { offset: outward * radius * 0.8, velocity: direction * radius * SPARK_SPEED, age: 0.0 });
+        }
+    }
+
+    // World positions of the sparks and how much of their life is left
+    pub fn sparks(&self) -> impl Iterator<Item = (Vec3, f32)> + '_ {
+        self.sparks.iter().map(|spark| (self.position + spark.offset, 1.0 - spark.age / SPARK_SECONDS))
+    }
+
+    // Pushes the ship out of every body it overlaps and drops the part of its
+    // velocity that went into the surface, which damages it when the contact
+    // is new. Returns how far it was moved, so the camera can be moved along
+    // with it.
+    pub fn resolve_collisions(&mut self, bodies: &[CelestialBody]) -> Vec3 {
+        let mut correction = Vec3::zeros();
+        let mut touching = false;
+        for body in bodies {
+            if body.is_virtual() || body.is_portal() {
+                continue;
+            }
+            let offset = self.position - body.position;
+            let distance = offset.magnitude();
+            let min_distance = body.bounding_radius() + self.bounding_radius();
+            if distance >= min_distance {
+                continue;
+            }
+
+            let normal = if distance > f32::EPSILON { offset / distance } else { Vec3::y() };
+            let push = normal * (min_distance - distance);
+            self.position += push;
+            correction += push;
+            let into_surface = self.velocity.dot(&normal);
+            if into_surface < 0.0 {
+                self.velocity -= normal * into_surface;
+                if !self.touching {
+                    self.hit(-normal, -into_surface);
+                }
+            }
+            self.shield = SHIELD_FLASH_SECONDS;
+            touching = true;
+        }
+        self.touching = touching;
+        correction
+    }
+
+    // Damage from hitting something in `direction` (world space) at `speed`
+    fn hit(&mut self, direction: Vec3, speed: f32) {
+        self.damage = (self.damage + speed * DAMAGE_PER_SPEED).min(1.0);
+        let local = nalgebra_glm::quat_rotate_vec3(&nalgebra_glm::quat_inverse(&self.attitude()), &direction);
+        self.damage_point = normalize_or(local, self.damage_point);
+    }
+
+    // Body the ship will hit soonest at its current velocity, if that's less
+    // than WARNING_TIME away. Closing speed is how fast the gap shrinks, so
+    // it only counts motion toward the body: skimming past or orbiting close
+    // to a surface doesn't raise the alarm.
+    pub fn proximity_warning(&mut self, bodies: &[CelestialBody], delta_time: f32) -> Option<ProximityWarning> {
+        let clearances: Vec<f32> = bodies.iter()
+            .map(|body| (self.position - body.position).magnitude() - body.bounding_radius() - self.bounding_radius())
+            .collect();
+
+        let mut warning: Option<ProximityWarning> = None;
+        if self.clearances.len() == clearances.len() {
+            for (i, body) in bodies.iter().enumerate() {
+                let closing_speed = (self.clearances[i] - clearances[i]) / delta_time;
+                if body.is_virtual() || body.is_portal() || closing_speed <= 0.0 {
+                    continue;
+                }
+                let time_to_impact = clearances[i].max(0.0) / closing_speed;
+                let sooner = warning.as_ref()
+                    .is_none_or(|current| time_to_impact < current.clearance.max(0.0) / current.closing_speed);
+                if time_to_impact < WARNING_TIME && sooner {
+                    warning = Some(ProximityWarning { body: i, clearance: clearances[i], closing_speed });
+                }
+            }
+        }
+        self.clearances = clearances;
+        warning
+    }
+
+    // 1 right after a hit, fading to 0
+    pub fn shield_strength(&self) -> f32 {
+        self.shield / SHIELD_FLASH_SECONDS
+    }
+
+    // Bank around the nose first, then pitch, then heading
+    fn attitude(&self) -> Quat {
+        let yaw = nalgebra_glm::quat_angle_axis(self.yaw, &Vec3::y());
+        let pitch = nalgebra_glm::quat_angle_axis(-self.pitch, &Vec3::x());
+        let roll = nalgebra_glm::quat_angle_axis(self.roll, &Vec3::z());
+        yaw * pitch * roll
+    }
+
+    // Local placement of the ship's node in the scene graph, which sits at
+    // the root
+    pub fn transform(&self) -> Transform {
+        Transform { translation: self.position, rotation: self.attitude(), scale: self.design().scale }
+    }
+
+    // World positions of the engine exhausts, as of the last time the scene
+    // graph placed the ship
+    pub fn engine_positions(&self) -> Vec<Vec3> {
+        self.design().engines.iter()
+            .map(|engine| (self.model_matrix * nalgebra_glm::Vec4::new(engine.x, engine.y, engine.z, 1.0)).xyz())
+            .collect()
+    }
+}
+
+// Brightness of a ship's engines at `time`, 1 on an undamaged ship. Damage
+// makes them sputter, dimming at random by up to all of it
+pub fn engine_flicker(damage: f32, time: f32) -> f32 {
+    1.0 - damage * value_noise(Vec3::new(time * FLICKER_RATE, 0.0, 0.0))
+}
+
+// Same angle in (-PI, PI]
+pub fn wrap_angle(angle: f32) -> f32 {
+    PI - (PI - angle).rem_euclid(2.0 * PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn ship_near_sun() -> (Spaceship, Vec<CelestialBody>) {
+        let vertex = Vertex::new(Vec3::new(0.0, 0.0, 1.0), Vec3::y(), nalgebra_glm::Vec2::zeros());
+        let design = ShipDesign::new("Test".to_string(), vec![vertex.clone()], 1.5, Vec3::new(0.0, -2.0, 9.0), Vec::new(), 0, 0);
+        let ship = Spaceship::new(vec![design]);
+        let sun = CelestialBody::new_sun("Sun".to_string(), vec![vertex], Vec3::zeros(), 10.0, Color::from_hex(0xFFFFFF));
+        (ship, vec![sun])
+    }
+
+    fn nose_direction(ship: &Spaceship) -> Vec3 {
+        nalgebra_glm::quat_rotate_vec3(&ship.transform().rotation, &Vec3::z())
+    }
+
+    #[test]
+    fn attitude_settles_on_the_view_and_banks_in_turns() {
+        let (mut ship, _) = ship_near_sun();
+        let mut camera = Camera::new(Vec3::zeros(), 100.0);
+        camera.phi = 0.5;
+        camera.update_position();
+        for _ in 0..200 {
+            ship.update_position(&camera, 0.016);
+        }
+        let forward = (camera.target - camera.position).normalize();
+        assert!((nose_direction(&ship) - forward).magnitude() < 1e-3);
+        assert!(ship.roll.abs() < 1e-3);
+
+        // Quick turn: the nose lags behind and the bank stays within limits
+        let mut max_roll: f32 = 0.0;
+        for _ in 0..30 {
+            camera.orbit(0.1, 0.0);
+            ship.update_position(&camera, 0.016);
+            max_roll = max_roll.max(ship.roll.abs());
+        }
+        let forward = (camera.target - camera.position).normalize();
+        assert!((nose_direction(&ship) - forward).magnitude() > 0.05);
+        assert!(max_roll > 0.1 && max_roll <= Spaceship::MAX_BANK);
+    }
+
+    #[test]
+    fn placement_scales_with_the_ship() {
+        let (mut ship, _) = ship_near_sun();
+        let vertex = ship.design().vertices[0].clone();
+        ship.designs.push(ShipDesign::new("Big".to_string(), vec![vertex], 6.0, Vec3::new(0.0, -2.0, 9.0), Vec::new(), 0, 0));
+        let mut camera = Camera::new(Vec3::zeros(), 100.0);
+        camera.phi = 0.5;
+        camera.update_position();
+
+        ship.update_position(&camera, 0.016);
+        let small = (ship.position - camera.position).magnitude() / ship.bounding_radius();
+        ship.cycle();
+        ship.update_position(&camera, 0.016);
+        let big = (ship.position - camera.position).magnitude() / ship.bounding_radius();
+        assert_eq!(ship.design().name, "Big");
+        assert!((small - big).abs() < 1e-3);
+    }
+
+    #[test]
+    fn collisions_push_the_ship_out_and_flash_the_shield() {
+        let (mut ship, bodies) = ship_near_sun();
+        ship.position = Vec3::new(5.0, 0.0, 0.0);
+        ship.velocity = Vec3::new(-3.0, 0.0, 2.0);
+        let correction = ship.resolve_collisions(&bodies);
+
+        assert!((ship.position.x - (10.0 + ship.bounding_radius())).abs() < 1e-4);
+        assert!((correction.x - (ship.position.x - 5.0)).abs() < 1e-4);
+        assert_eq!(ship.velocity, Vec3::new(0.0, 0.0, 2.0));
+        assert_eq!(ship.shield_strength(), 1.0);
+    }
+
+    #[test]
+    fn new_contacts_damage_the_hull_by_their_speed() {
+        let (mut ship, bodies) = ship_near_sun();
+        ship.position = Vec3::new(5.0, 0.0, 0.0);
+        ship.velocity = Vec3::new(-40.0, 0.0, 0.0);
+        ship.resolve_collisions(&bodies);
+        assert!((ship.damage() - 40.0 * DAMAGE_PER_SPEED).abs() < 1e-6);
+
+        // Still pressed against the surface: no new hit
+        ship.position = Vec3::new(10.0, 0.0, 0.0);
+        ship.velocity = Vec3::new(-40.0, 0.0, 0.0);
+        ship.resolve_collisions(&bodies);
+        assert!((ship.damage() - 40.0 * DAMAGE_PER_SPEED).abs() < 1e-6);
+
+        // A damaged ship throws sparks, a repaired one doesn't
+        let camera = Camera::new(Vec3::zeros(), 100.0);
+        ship.update_position(&camera, 1.0);
+        assert!(ship.sparks().count() > 0);
+        ship.repair();
+        ship.update_position(&camera, 1.0);
+        assert_eq!((ship.damage(), ship.sparks().count()), (0.0, 0));
+        assert_eq!(engine_flicker(0.0, 1.234), 1.0);
+    }
+
+    #[test]
+    fn only_closing_motion_raises_a_warning() {
+        let (mut ship, bodies) = ship_near_sun();
+        let dt = 0.1;
+
+        // Circling at a constant distance: the gap never shrinks
+        for step in 0..5 {
+            let angle = step as f32 * 0.2;
+            ship.position = Vec3::new(angle.cos(), 0.0, angle.sin()) * 30.0;
+            assert!(ship.proximity_warning(&bodies, dt).is_none());
+        }
+
+        // Heading straight in at 10 units per second, 15.5 units out
+        ship.clearances.clear();
+        ship.position = Vec3::new(28.0, 0.0, 0.0);
+        ship.proximity_warning(&bodies, dt);
+        ship.position = Vec3::new(27.0, 0.0, 0.0);
+        let warning = ship.proximity_warning(&bodies, dt).unwrap();
+        assert_eq!(warning.body, 0);
+        assert!((warning.closing_speed - 10.0).abs() < 1e-3);
+
+        // Same distance, but backing away
+        ship.position = Vec3::new(28.0, 0.0, 0.0);
+        assert!(ship.proximity_warning(&bodies, dt).is_none());
+    }
+}
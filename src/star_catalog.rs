@@ -0,0 +1,474 @@
+use nalgebra_glm::Vec3;
+
+// Tilt of Earth's equator against the ecliptic, at J2000
+const OBLIQUITY_DEGREES: f32 = 23.439;
+// Magnitudes drawn at full and at least brightness
+const BRIGHTEST: f32 = -1.5;
+const FAINTEST: f32 = 6.0;
+
+// A bright star at its J2000 place
+pub struct CatalogStar {
+    pub name: &'static str,
+    // Hours east of the vernal equinox, degrees north of the celestial equator
+    pub right_ascension: f32,
+    pub declination: f32,
+    // Visual magnitude: lower is brighter, five steps are a hundred times
+    pub magnitude: f32,
+    // One of the famous few drawn with their name
+    pub labeled: bool,
+}
+
+const fn star(name: &'static str, right_ascension: f32, declination: f32, magnitude: f32) -> CatalogStar {
+    CatalogStar { name, right_ascension, declination, magnitude, labeled: false }
+}
+
+const fn named(name: &'static str, right_ascension: f32, declination: f32, magnitude: f32) -> CatalogStar {
+    CatalogStar { labeled: true, ..star(name, right_ascension, declination, magnitude) }
+}
+
+impl CatalogStar {
+    // Unit direction in the scene. Orbits lie in the ecliptic, which is the
+    // XZ plane here with the vernal equinox along +X (as the ephemeris has
+    // it), so the celestial north pole tilts off -Y by the obliquity
+    pub fn direction(&self) -> Vec3 {
+        let (sin_ra, cos_ra) = (self.right_ascension * 15.0).to_radians().sin_cos();
+        let (sin_dec, cos_dec) = self.declination.to_radians().sin_cos();
+        let (x, y, z) = (cos_dec * cos_ra, cos_dec * sin_ra, sin_dec);
+        let (sin_e, cos_e) = OBLIQUITY_DEGREES.to_radians().sin_cos();
+        let (ecliptic_y, ecliptic_z) = (y * cos_e + z * sin_e, z * cos_e - y * sin_e);
+        Vec3::new(x, -ecliptic_z, ecliptic_y)
+    }
+
+    // 1 for the brightest stars down to 0 at FAINTEST, evenly per magnitude
+    // since the eye sees them that way
+    pub fn brightness(&self) -> f32 {
+        ((FAINTEST - self.magnitude) / (FAINTEST - BRIGHTEST)).clamp(0.0, 1.0)
+    }
+}
+
+// The brightest stars and those the figures below need, brightest first.
+// Positions are rounded to about a hundredth of a degree
+pub const STARS: [CatalogStar; 289] = [
+    named("Sirius", 6.752, -16.716, -1.46),
+    named("Canopus", 6.399, -52.696, -0.74),
+    star("Rigil Kentaurus", 14.660, -60.835, -0.27),
+    named("Arcturus", 14.261, 19.182, -0.05),
+    named("Vega", 18.616, 38.784, 0.03),
+    named("Capella", 5.278, 45.998, 0.08),
+    named("Rigel", 5.242, -8.202, 0.13),
+    named("Procyon", 7.655, 5.225, 0.34),
+    named("Betelgeuse", 5.919, 7.407, 0.45),
+    star("Achernar", 1.629, -57.237, 0.46),
+    star("Hadar", 14.064, -60.373, 0.61),
+    named("Altair", 19.846, 8.868, 0.77),
+    star("Acrux", 12.443, -63.099, 0.77),
+    named("Aldebaran", 4.599, 16.509, 0.87),
+    star("Spica", 13.420, -11.161, 0.97),
+    named("Antares", 16.490, -26.432, 1.06),
+    star("Pollux", 7.755, 28.026, 1.14),
+    star("Fomalhaut", 22.961, -29.622, 1.16),
+    star("Deneb", 20.690, 45.280, 1.25),
+    star("Mimosa", 12.795, -59.689, 1.25),
+    star("Regulus", 10.140, 11.967, 1.36),
+    star("Adhara", 6.977, -28.972, 1.50),
+    star("Castor", 7.577, 31.888, 1.58),
+    star("Shaula", 17.560, -37.104, 1.62),
+    star("Gacrux", 12.519, -57.113, 1.63),
+    star("Bellatrix", 5.419, 6.350, 1.64),
+    star("Elnath", 5.438, 28.608, 1.65),
+    star("Miaplacidus", 9.220, -69.717, 1.67),
+    star("Alnilam", 5.604, -1.202, 1.69),
+    star("Alnair", 22.137, -46.961, 1.74),
+    star("Alnitak", 5.679, -1.943, 1.77),
+    star("Alioth", 12.900, 55.960, 1.77),
+    star("Dubhe", 11.062, 61.751, 1.79),
+    star("Mirfak", 3.405, 49.861, 1.79),
+    star("Regor", 8.159, -47.337, 1.83),
+    star("Wezen", 7.140, -26.393, 1.84),
+    star("Kaus Australis", 18.403, -34.385, 1.85),
+    star("Alkaid", 13.792, 49.313, 1.86),
+    star("Sargas", 17.622, -42.998, 1.86),
+    star("Avior", 8.375, -59.510, 1.86),
+    star("Menkalinan", 5.992, 44.948, 1.90),
+    star("Atria", 16.811, -69.028, 1.91),
+    star("Alhena", 6.629, 16.399, 1.93),
+    star("Peacock", 20.427, -56.735, 1.94),
+    star("Alsephina", 8.745, -54.709, 1.96),
+    named("Polaris", 2.530, 89.264, 1.98),
+    star("Mirzam", 6.378, -17.956, 1.98),
+    star("Alphard", 9.460, -8.659, 1.99),
+    star("Hamal", 2.120, 23.463, 2.01),
+    star("Diphda", 0.727, -17.987, 2.04),
+    star("Nunki", 18.921, -26.297, 2.05),
+    star("Mirach", 1.162, 35.621, 2.05),
+    star("Menkent", 14.111, -36.370, 2.06),
+    star("Alpheratz", 0.140, 29.091, 2.06),
+    star("Saiph", 5.796, -9.670, 2.07),
+    star("Rasalhague", 17.582, 12.560, 2.07),
+    star("Tiaki", 22.711, -46.885, 2.07),
+    star("Kochab", 14.845, 74.156, 2.08),
+    star("Algieba", 10.333, 19.842, 2.08),
+    star("Almach", 2.065, 42.330, 2.10),
+    star("Algol", 3.136, 40.956, 2.12),
+    star("Denebola", 11.818, 14.572, 2.14),
+    star("Muhlifain", 12.692, -48.960, 2.17),
+    star("Naos", 8.060, -40.003, 2.21),
+    star("Suhail", 9.133, -43.433, 2.21),
+    star("Mintaka", 5.533, -0.299, 2.23),
+    star("Sadr", 20.370, 40.257, 2.23),
+    star("Alphecca", 15.578, 26.715, 2.23),
+    star("Eltanin", 17.943, 51.489, 2.23),
+    star("Schedar", 0.675, 56.537, 2.24),
+    star("Aspidiske", 9.285, -59.275, 2.25),
+    star("Mizar", 13.399, 54.925, 2.27),
+    star("Caph", 0.153, 59.150, 2.27),
+    star("Dschubba", 16.006, -22.622, 2.29),
+    star("Larawag", 16.836, -34.293, 2.29),
+    star("Epsilon Centauri", 13.665, -53.466, 2.30),
+    star("Alpha Lupi", 14.699, -47.388, 2.30),
+    star("Eta Centauri", 14.592, -42.158, 2.31),
+    star("Merak", 11.031, 56.382, 2.37),
+    star("Izar", 14.750, 27.074, 2.37),
+    star("Girtab", 17.708, -39.030, 2.39),
+    star("Enif", 21.736, 9.875, 2.39),
+    star("Ankaa", 0.438, -42.306, 2.40),
+    star("Scheat", 23.063, 28.083, 2.42),
+    star("Sabik", 17.173, -15.725, 2.43),
+    star("Phecda", 11.897, 53.695, 2.44),
+    star("Alderamin", 21.310, 62.585, 2.45),
+    star("Aludra", 7.401, -29.303, 2.45),
+    star("Gamma Cassiopeiae", 0.945, 60.717, 2.47),
+    star("Markeb", 9.368, -55.011, 2.47),
+    star("Aljanah", 20.770, 33.970, 2.48),
+    star("Markab", 23.079, 15.205, 2.49),
+    star("Delta Centauri", 12.139, -50.722, 2.52),
+    star("Menkar", 3.038, 4.090, 2.54),
+    star("Zeta Centauri", 13.926, -47.288, 2.55),
+    star("Zosma", 11.235, 20.524, 2.56),
+    star("Zeta Ophiuchi", 16.619, -10.567, 2.56),
+    star("Arneb", 5.546, -17.822, 2.58),
+    star("Gienah", 12.263, -17.542, 2.59),
+    star("Ascella", 19.044, -29.880, 2.60),
+    star("Zubeneschamali", 15.283, -9.383, 2.61),
+    star("Acrab", 16.091, -19.806, 2.62),
+    star("Mahasim", 5.995, 37.213, 2.62),
+    star("Unukalhai", 15.738, 6.426, 2.63),
+    star("Sheratan", 1.911, 20.808, 2.64),
+    star("Phact", 5.661, -34.074, 2.65),
+    star("Kraz", 12.573, -23.397, 2.65),
+    star("Ruchbah", 1.430, 60.235, 2.68),
+    star("Beta Lupi", 14.976, -43.134, 2.68),
+    star("Muphrid", 13.911, 18.398, 2.68),
+    star("Lesath", 17.513, -37.296, 2.69),
+    star("Hassaleh", 4.950, 33.166, 2.69),
+    star("Mu Velorum", 10.779, -49.420, 2.69),
+    star("Kaus Media", 18.350, -29.828, 2.70),
+    star("Pi Puppis", 7.286, -37.097, 2.70),
+    star("Tarazed", 19.771, 10.613, 2.72),
+    star("Aldibain", 16.400, 61.514, 2.74),
+    star("Porrima", 12.694, -1.449, 2.74),
+    star("Yed Prior", 16.239, -3.694, 2.74),
+    star("Zubenelgenubi", 14.848, -16.042, 2.75),
+    star("Theta Carinae", 10.716, -64.394, 2.76),
+    star("Hatysa", 5.590, -5.910, 2.77),
+    star("Kornephoros", 16.504, 21.490, 2.77),
+    star("Cebalrai", 17.725, 4.567, 2.77),
+    star("Gamma Lupi", 15.586, -41.167, 2.78),
+    star("Imai", 12.252, -58.749, 2.79),
+    star("Rastaban", 17.507, 52.301, 2.79),
+    star("Cursa", 5.131, -5.086, 2.79),
+    star("Zeta Herculis", 16.688, 31.603, 2.81),
+    star("Kaus Borealis", 18.466, -25.422, 2.81),
+    star("Tureis", 8.126, -24.304, 2.81),
+    star("Paikauhale", 16.598, -28.216, 2.82),
+    star("Algenib", 0.220, 15.184, 2.83),
+    star("Nihal", 5.471, -20.759, 2.84),
+    star("Vindemiatrix", 13.036, 10.959, 2.85),
+    star("Deneb Algedi", 21.784, -16.127, 2.85),
+    star("Zeta Persei", 3.902, 31.884, 2.85),
+    star("Beta Arae", 17.422, -55.530, 2.85),
+    star("Beta Trianguli Australis", 15.919, -63.430, 2.85),
+    star("Delta Cygni", 19.750, 45.131, 2.87),
+    star("Tejat", 6.383, 22.514, 2.87),
+    star("Alcyone", 3.791, 24.105, 2.87),
+    star("Sadalsuud", 21.526, -5.571, 2.87),
+    star("Gamma Trianguli Australis", 15.315, -68.679, 2.87),
+    star("Fang", 15.981, -26.114, 2.89),
+    star("Gomeisa", 7.453, 8.289, 2.89),
+    star("Epsilon Persei", 3.964, 40.010, 2.89),
+    star("Cor Caroli", 12.934, 38.318, 2.89),
+    star("Alniyat", 16.353, -25.593, 2.90),
+    star("Gamma Persei", 3.080, 53.506, 2.93),
+    star("Algorab", 12.498, -16.515, 2.94),
+    star("Sadalmelik", 22.096, -0.320, 2.94),
+    star("Matar", 22.717, 30.221, 2.94),
+    star("Alpha Arae", 17.531, -49.876, 2.95),
+    star("Zaurak", 3.967, -13.509, 2.95),
+    star("Epsilon Leonis", 9.764, 23.774, 2.98),
+    star("Mebsuta", 6.732, 25.131, 2.98),
+    star("Okab", 19.090, 13.863, 2.99),
+    star("Almaaz", 5.033, 43.823, 2.99),
+    star("Alnasl", 18.097, -30.424, 2.99),
+    star("Mu Scorpii", 16.864, -38.048, 3.00),
+    star("Tianguan", 5.627, 21.143, 3.00),
+    star("Minkar", 12.169, -22.620, 3.00),
+    star("Beta Trianguli", 2.159, 34.987, 3.00),
+    star("Delta Persei", 3.715, 47.788, 3.01),
+    star("Furud", 6.338, -30.063, 3.02),
+    star("Omicron2 Canis Majoris", 7.050, -23.833, 3.02),
+    star("Iota Scorpii", 17.793, -40.127, 3.03),
+    star("Seginus", 14.535, 38.308, 3.03),
+    star("Mira", 2.322, -2.978, 3.04),
+    star("Pherkad", 15.345, 71.834, 3.05),
+    star("Albireo", 19.512, 27.960, 3.05),
+    star("Dabih", 20.350, -14.781, 3.05),
+    star("Altais", 19.209, 67.662, 3.07),
+    star("Zeta Hydrae", 8.923, 5.946, 3.11),
+    star("Wazn", 5.849, -35.768, 3.12),
+    star("Sarin", 17.250, 24.839, 3.13),
+    star("Pi Herculis", 17.251, 36.809, 3.16),
+    star("Zeta Draconis", 17.146, 65.715, 3.17),
+    star("Phi Sagittarii", 18.761, -26.991, 3.17),
+    star("Epsilon Leporis", 5.091, -22.371, 3.19),
+    star("Zeta Cygni", 21.216, 30.227, 3.20),
+    star("Kappa Ophiuchi", 16.961, 9.375, 3.20),
+    star("Errai", 23.656, 77.632, 3.21),
+    star("Alfirk", 21.478, 70.561, 3.23),
+    star("Theta Aquilae", 20.188, -0.821, 3.23),
+    star("Yed Posterior", 16.305, -4.693, 3.24),
+    star("Sulafat", 18.982, 32.690, 3.25),
+    star("Skat", 22.911, -15.821, 3.27),
+    star("Delta Andromedae", 0.656, 30.861, 3.27),
+    star("Propus", 6.248, 22.507, 3.28),
+    star("Edasich", 15.415, 58.966, 3.29),
+    star("Megrez", 12.257, 57.033, 3.31),
+    star("Mu Leporis", 5.216, -16.206, 3.31),
+    star("Tau Sagittarii", 19.116, -27.671, 3.32),
+    star("Eta Scorpii", 17.203, -43.239, 3.33),
+    star("Chertan", 11.237, 15.430, 3.33),
+    star("Zeta Cephei", 22.181, 58.201, 3.35),
+    star("Delta Aquilae", 19.425, 3.115, 3.36),
+    star("Segin", 1.907, 63.670, 3.37),
+    star("Rasalgethi", 17.244, 14.390, 3.37),
+    star("Heze", 13.578, -0.596, 3.37),
+    star("Minelauva", 12.927, 3.398, 3.38),
+    star("Epsilon Hydrae", 8.780, 6.419, 3.38),
+    star("Meissa", 5.585, 9.934, 3.39),
+    star("Chamukuy", 4.478, 15.871, 3.40),
+    star("Homam", 22.691, 10.831, 3.41),
+    star("Mothallah", 1.885, 29.579, 3.41),
+    star("Lambda Aquilae", 19.104, -4.882, 3.43),
+    star("Adhafera", 10.278, 23.417, 3.43),
+    star("Eta Ceti", 1.143, -10.182, 3.45),
+    star("Lambda Tauri", 4.011, 12.490, 3.47),
+    star("Delta Bootis", 15.258, 33.315, 3.47),
+    star("Kaffaljidhma", 2.722, 3.236, 3.47),
+    star("Eta Herculis", 16.715, 38.922, 3.48),
+    star("Eta Leonis", 10.122, 16.763, 3.49),
+    star("Nekkar", 15.032, 40.391, 3.50),
+    star("Tau Ceti", 1.734, -15.938, 3.50),
+    star("Iota Cephei", 22.828, 66.201, 3.52),
+    star("Sheliak", 18.835, 33.363, 3.52),
+    star("Tarf", 8.275, 9.186, 3.52),
+    star("Rana", 3.721, -9.763, 3.52),
+    star("Wasat", 7.335, 21.982, 3.53),
+    star("Ain", 4.477, 19.180, 3.53),
+    star("Biham", 22.170, 6.198, 3.53),
+    star("Iota Ceti", 0.324, -8.824, 3.56),
+    star("Algedi", 20.300, -12.545, 3.57),
+    star("Rho Bootis", 14.530, 30.371, 3.58),
+    star("Gamma Leporis", 5.741, -22.448, 3.60),
+    star("Theta Ceti", 1.400, -8.183, 3.60),
+    star("Zavijava", 11.845, 1.765, 3.61),
+    star("Zeta Scorpii", 16.910, -42.362, 3.62),
+    star("Rotanev", 20.626, 14.595, 3.63),
+    star("Prima Hyadum", 4.330, 15.628, 3.65),
+    star("Thuban", 14.073, 64.376, 3.65),
+    star("Zeta Aquarii", 22.481, -0.020, 3.65),
+    star("Beta Serpentis", 15.770, 15.422, 3.67),
+    star("Nashira", 21.668, -16.662, 3.67),
+    star("Nusakan", 15.464, 29.106, 3.68),
+    star("Alshain", 19.922, 6.407, 3.71),
+    star("Epsilon Serpentis", 15.847, 4.478, 3.71),
+    star("Lambda Aquarii", 22.877, -7.580, 3.73),
+    star("Baten Kaitos", 1.858, -10.335, 3.73),
+    star("Ran", 3.549, -9.458, 3.73),
+    star("Zeta Capricorni", 21.445, -22.411, 3.74),
+    star("Grumium", 17.892, 56.873, 3.75),
+    star("Secunda Hyadum", 4.382, 17.543, 3.76),
+    star("Sualocin", 20.661, 15.912, 3.77),
+    star("Mekbuda", 7.068, 20.570, 3.79),
+    star("Delta Leporis", 5.940, -20.879, 3.81),
+    star("Epsilon Draconis", 19.803, 70.268, 3.83),
+    star("Gamma Coronae Borealis", 15.713, 26.296, 3.84),
+    star("Giausar", 11.523, 69.331, 3.84),
+    star("Sadachbia", 22.361, -1.387, 3.84),
+    star("Kappa Draconis", 12.558, 69.788, 3.87),
+    star("Rasalas", 9.879, 26.007, 3.88),
+    star("Theta Hydrae", 9.239, 2.314, 3.88),
+    star("Mesarthim", 1.892, 19.294, 3.88),
+    star("Eta Cygni", 19.938, 35.083, 3.89),
+    star("Zaniah", 12.332, -0.667, 3.89),
+    star("Epsilon Herculis", 17.005, 30.926, 3.92),
+    star("Asellus Australis", 8.745, 18.154, 3.94),
+    star("Theta Draconis", 16.031, 58.565, 4.01),
+    star("Gamma Trianguli", 2.289, 33.847, 4.01),
+    star("Iota Cancri", 8.778, 28.760, 4.02),
+    star("Aldulfin", 20.554, 11.303, 4.03),
+    star("Eta Aquarii", 22.589, -0.118, 4.04),
+    star("Theta Capricorni", 21.099, -17.233, 4.07),
+    star("Delta Ceti", 2.658, 0.329, 4.07),
+    star("Omega Capricorni", 20.864, -26.919, 4.11),
+    star("Theta Coronae Borealis", 15.549, 31.359, 4.14),
+    star("Delta Hydrae", 8.628, 5.704, 4.14),
+    star("Epsilon Coronae Borealis", 15.960, 26.878, 4.15),
+    star("Epsilon Ursae Minoris", 16.766, 82.037, 4.21),
+    star("Delta Lyrae", 18.908, 36.899, 4.22),
+    star("Acubens", 8.975, 11.858, 4.25),
+    star("Chara", 12.562, 41.357, 4.26),
+    star("Gamma2 Delphini", 20.777, 16.124, 4.27),
+    star("Zeta Ursae Minoris", 15.734, 77.795, 4.29),
+    star("Eta Hydrae", 8.720, 3.399, 4.30),
+    star("Yildun", 17.537, 86.586, 4.35),
+    star("Zeta Lyrae", 18.746, 37.605, 4.36),
+    star("Delta Delphini", 20.724, 15.075, 4.43),
+    star("Sigma Hydrae", 8.646, 3.341, 4.45),
+    star("Delta Coronae Borealis", 15.827, 26.068, 4.63),
+    star("Asellus Borealis", 8.721, 21.469, 4.66),
+    star("Epsilon Lyrae", 18.739, 39.670, 4.67),
+    star("Kuma", 17.536, 55.184, 4.88),
+    star("Eta Ursae Minoris", 16.292, 75.755, 4.95),
+];
+
+// Constellation figures, pairs of STARS indices joined by a line
+pub const FIGURES: [&[(usize, usize)]; 45] = [
+    // Orion
+    &[(204, 8), (204, 25), (8, 25), (8, 30), (25, 65), (65, 28), (28, 30), (30, 54), (65, 6)],
+    // Ursa Major
+    &[(32, 78), (78, 85), (85, 192), (192, 32), (192, 31), (31, 71), (71, 37)],
+    // Ursa Minor
+    &[(45, 280), (280, 273), (273, 278), (278, 57), (57, 170), (170, 288), (288, 278)],
+    // Cassiopeia
+    &[(72, 69), (69, 88), (88, 107), (107, 199)],
+    // Cepheus
+    &[(86, 184), (184, 183), (183, 218), (218, 197), (197, 86), (184, 218)],
+    // Cygnus
+    &[(18, 66), (66, 258), (258, 171), (66, 139), (66, 90), (90, 181)],
+    // Lyra
+    &[(4, 286), (4, 281), (281, 219), (219, 187), (187, 274), (274, 281)],
+    // Aquila
+    &[(115, 11), (11, 239), (239, 185), (11, 198), (198, 208), (198, 157)],
+    // Scorpius
+    &[(101, 73), (73, 144), (73, 148), (148, 15), (15, 131), (131, 74), (74, 160), (160, 231), (231, 195), (195, 38), (38, 167), (167, 80), (80, 23), (23, 110)],
+    // Crux
+    &[(12, 24), (19, 125)],
+    // Centaurus
+    &[(2, 10), (10, 75), (75, 94), (94, 77), (77, 52), (75, 62), (62, 92)],
+    // Lupus
+    &[(76, 108), (108, 124)],
+    // Leo
+    &[(20, 215), (215, 58), (58, 209), (209, 255), (255, 155), (58, 95), (95, 61), (61, 196), (196, 20)],
+    // Gemini
+    &[(22, 16), (22, 156), (156, 140), (140, 190), (16, 222), (222, 248), (248, 42)],
+    // Cancer
+    &[(220, 261), (261, 285), (285, 264), (261, 275)],
+    // Taurus
+    &[(13, 205), (205, 233), (233, 246), (246, 223), (223, 26), (13, 161), (233, 211)],
+    // Auriga
+    &[(5, 40), (40, 102), (102, 26), (26, 111), (111, 158), (158, 5)],
+    // Canis Major
+    &[(46, 0), (0, 166), (166, 35), (35, 87), (35, 21), (21, 165)],
+    // Canis Minor
+    &[(7, 145)],
+    // Lepus
+    &[(193, 97), (97, 133), (133, 180), (133, 228), (228, 249), (249, 97)],
+    // Columba
+    &[(105, 175)],
+    // Bootes
+    &[(3, 79), (79, 212), (212, 216), (216, 168), (168, 227), (227, 3), (3, 109)],
+    // Corona Borealis
+    &[(270, 238), (238, 67), (67, 251), (251, 284), (284, 272)],
+    // Hercules
+    &[(128, 214), (214, 177), (177, 260), (260, 128), (128, 122), (260, 176), (176, 200)],
+    // Draco
+    &[(68, 126), (126, 287), (287, 245), (245, 68), (245, 173), (173, 250), (173, 178), (178, 116), (116, 262), (262, 191), (191, 234), (234, 254), (254, 252)],
+    // Virgo
+    &[(230, 259), (259, 117), (117, 202), (202, 134), (117, 14), (202, 201), (201, 14)],
+    // Libra
+    &[(119, 100)],
+    // Corvus
+    &[(98, 150), (150, 106), (106, 162), (162, 98)],
+    // Hydra
+    &[(271, 283), (283, 279), (279, 174), (174, 203), (203, 271), (174, 256), (256, 47)],
+    // Ophiuchus
+    &[(55, 123), (123, 84), (84, 96), (96, 186), (186, 118), (118, 182), (182, 55)],
+    // Serpens
+    &[(236, 103), (103, 240)],
+    // Sagittarius
+    &[(159, 113), (113, 36), (36, 99), (99, 179), (179, 113), (113, 129), (129, 179), (179, 50), (50, 194), (194, 99), (159, 36)],
+    // Capricornus
+    &[(226, 172), (172, 269), (269, 244), (244, 135), (135, 237), (237, 267), (267, 172)],
+    // Aquarius
+    &[(142, 151), (151, 253), (253, 235), (235, 266), (253, 241), (241, 188)],
+    // Pegasus
+    &[(91, 83), (83, 53), (53, 132), (132, 91), (91, 206), (206, 224), (224, 81), (83, 152)],
+    // Andromeda
+    &[(53, 189), (189, 51), (51, 59)],
+    // Perseus
+    &[(149, 33), (33, 164), (164, 146), (146, 136), (33, 60)],
+    // Aries
+    &[(48, 104), (104, 257)],
+    // Triangulum
+    &[(207, 163), (163, 263), (263, 207)],
+    // Cetus
+    &[(49, 225), (49, 210), (210, 229), (229, 242), (242, 217), (217, 49), (242, 169), (169, 268), (268, 213), (213, 93)],
+    // Delphinus
+    &[(265, 232), (232, 247), (247, 277), (277, 282), (282, 232)],
+    // Canes Venatici
+    &[(147, 276)],
+    // Grus
+    &[(29, 56)],
+    // Ara
+    &[(137, 153)],
+    // Triangulum Australe
+    &[(41, 138), (138, 143), (143, 41)],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn angle(a: usize, b: usize) -> f32 {
+        STARS[a].direction().dot(&STARS[b].direction()).clamp(-1.0, 1.0).acos().to_degrees()
+    }
+
+    fn index(name: &str) -> usize {
+        STARS.iter().position(|star| star.name == name).unwrap()
+    }
+
+    #[test]
+    fn figures_join_nearby_stars_on_a_sky_that_matches_the_orbits() {
+        assert!(STARS.windows(2).all(|pair| pair[0].magnitude <= pair[1].magnitude));
+        assert_eq!(STARS.iter().filter(|star| star.labeled).count(), 12);
+        // A line across more of the sky than any figure spans is a typo
+        for lines in FIGURES {
+            for &(a, b) in lines {
+                assert!(a != b && a < STARS.len() && b < STARS.len());
+                assert!(angle(a, b) < 25.0, "{} to {} is {} degrees", STARS[a].name, STARS[b].name, angle(a, b));
+            }
+        }
+        // Orion's shoulders to its knees
+        assert!((angle(index("Betelgeuse"), index("Rigel")) - 18.6).abs() < 0.1);
+
+        // The pole star sits the obliquity off the ecliptic pole, and the
+        // equinox is on the orbits' +X
+        let polaris = STARS[index("Polaris")].direction();
+        assert!((polaris.dot(&-Vec3::y()).acos().to_degrees() - OBLIQUITY_DEGREES).abs() < 1.0);
+        let equinox = star("", 0.0, 0.0, 0.0).direction();
+        assert!((equinox - Vec3::x()).magnitude() < 1e-6);
+        // The June solstice point, a quarter of the way along the ecliptic,
+        // is where the planets head from +X
+        let solstice = star("", 6.0, OBLIQUITY_DEGREES, 0.0).direction();
+        assert!((solstice - Vec3::z()).magnitude() < 1e-5);
+    }
+}
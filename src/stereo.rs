@@ -0,0 +1,112 @@
+use nalgebra_glm::{Vec3, Mat4, rotation, translation};
+use serde::Deserialize;
+
+// Runtime adjustments multiply or divide by this per key press
+const ADJUST_STEP: f32 = 1.25;
+const MIN_EYE_SEPARATION: f32 = 0.05;
+const MAX_EYE_SEPARATION: f32 = 200.0;
+const MIN_CONVERGENCE: f32 = 10.0;
+const MAX_CONVERGENCE: f32 = 5000.0;
+
+// [stereo] section of the scene file. Eye separation and convergence are in
+// world units, so comfortable values depend on the scene's scale
+#[derive(Deserialize, Clone, Copy)]
+pub struct StereoSettings {
+    // Start in red-cyan anaglyph mode (F3 toggles)
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_eye_separation")]
+    pub eye_separation: f32,
+    // Distance in front of the camera where both eyes' views cross; things
+    // there sit at screen depth
+    #[serde(default = "default_convergence")]
+    pub convergence: f32,
+    // Keep both eyes looking straight ahead instead of toeing them in
+    #[serde(default)]
+    pub parallel: bool,
+}
+
+impl Default for StereoSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            eye_separation: default_eye_separation(),
+            convergence: default_convergence(),
+            parallel: false,
+        }
+    }
+}
+
+fn default_eye_separation() -> f32 {
+    8.0
+}
+
+fn default_convergence() -> f32 {
+    600.0
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl StereoSettings {
+    pub fn widen(&mut self, wider: bool) {
+        let factor = if wider { ADJUST_STEP } else { 1.0 / ADJUST_STEP };
+        self.eye_separation = (self.eye_separation * factor).clamp(MIN_EYE_SEPARATION, MAX_EYE_SEPARATION);
+    }
+
+    pub fn converge(&mut self, farther: bool) {
+        let factor = if farther { ADJUST_STEP } else { 1.0 / ADJUST_STEP };
+        self.convergence = (self.convergence * factor).clamp(MIN_CONVERGENCE, MAX_CONVERGENCE);
+    }
+
+    // View matrix of one eye, from the view matrix of the camera between
+    // them. Each eye sits half the separation along the camera's right
+    // vector and, unless parallel, turns in toward the convergence point
+    pub fn eye_view(&self, view_matrix: &Mat4, eye: Eye) -> Mat4 {
+        // Camera-space offset that moves the scene for this eye
+        let shift = match eye {
+            Eye::Left => self.eye_separation / 2.0,
+            Eye::Right => -self.eye_separation / 2.0,
+        };
+        let toe_in = if self.parallel { 0.0 } else { (shift / self.convergence).atan() };
+        rotation(toe_in, &Vec3::y()) * translation(&Vec3::new(shift, 0.0, 0.0)) * view_matrix
+    }
+}
+
+// Red from the left eye, green and blue from the right, for red-cyan glasses
+pub fn anaglyph(left: &mut [u32], right: &[u32]) {
+    for (pixel, &other) in left.iter_mut().zip(right) {
+        *pixel = (*pixel & 0xFF0000) | (other & 0x00FFFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+    use crate::camera::look_at_matrix;
+
+    #[test]
+    fn eyes_converge_on_the_convergence_point() {
+        let settings = StereoSettings { eye_separation: 10.0, convergence: 100.0, ..StereoSettings::default() };
+        let view = look_at_matrix(Vec3::new(0.0, 0.0, 50.0), Vec3::zeros(), Vec3::y());
+        // 100 units ahead of the camera
+        let point = Vec4::new(0.0, 0.0, -50.0, 1.0);
+        for eye in [Eye::Left, Eye::Right] {
+            let seen = settings.eye_view(&view, eye) * point;
+            assert!(seen.x.abs() < 1e-3 && seen.z < 0.0, "{:?} sees it at {:?}", eye, seen);
+        }
+
+        // Parallel eyes see it off to opposite sides
+        let parallel = StereoSettings { parallel: true, ..settings };
+        assert!((parallel.eye_view(&view, Eye::Left) * point).x > 4.9);
+        assert!((parallel.eye_view(&view, Eye::Right) * point).x < -4.9);
+
+        let mut left = vec![0x112233, 0xFFFFFF];
+        anaglyph(&mut left, &[0x445566, 0x000000]);
+        assert_eq!(left, vec![0x115566, 0xFF0000]);
+    }
+}
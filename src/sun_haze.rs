@@ -0,0 +1,161 @@
+use nalgebra_glm::{Mat4, Vec3};
+use serde::Deserialize;
+use crate::body::CelestialBody;
+use crate::distortion::heat_wobble;
+use crate::framebuffer::{Framebuffer, Viewport};
+
+// At full heat the frame shimmers by up to WOBBLE_PIXELS in waves
+// WOBBLE_WAVELENGTH pixels long and is tinted up to MAX_CAST orange
+const WOBBLE_PIXELS: f32 = 3.0;
+const WOBBLE_WAVELENGTH: f32 = 48.0;
+const CAST_COLOR: u32 = 0xFF7A20;
+const MAX_CAST: f32 = 0.35;
+// The view shakes by up to this many radians at full trauma
+const MAX_SHAKE: f32 = 0.015;
+
+// [sun_haze] section of the scene file
+#[derive(Deserialize, Clone, Copy)]
+pub struct SunHazeSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // In radii of the star, from its center: the heat starts to show at
+    // `threshold` and is at its worst by `critical`, closer than which
+    // whoever flies the view is pushed back out
+    #[serde(default = "default_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_critical")]
+    pub critical: f32,
+}
+
+impl Default for SunHazeSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            threshold: default_threshold(),
+            critical: default_critical(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_threshold() -> f32 {
+    3.0
+}
+
+fn default_critical() -> f32 {
+    1.6
+}
+
+// How close the observer is to burning up, near the star it's deepest in
+// range of
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SunHeat {
+    pub star: usize,
+    // 0 at the threshold, 1 from the critical distance in, easing both ways
+    pub intensity: f32,
+}
+
+impl SunHazeSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.critical > 1.0 && self.critical < self.threshold) {
+            return Err("sun_haze needs 1 < critical < threshold".to_string());
+        }
+        Ok(())
+    }
+
+    // None when off or out of every star's threshold
+    pub fn heat(&self, bodies: &[CelestialBody], observer: Vec3) -> Option<SunHeat> {
+        if !self.enabled {
+            return None;
+        }
+        bodies.iter().enumerate()
+            .filter(|(_, body)| body.is_star() && body.bounding_radius() > 0.0)
+            .filter_map(|(star, body)| {
+                let radii = (observer - body.position).magnitude() / body.bounding_radius();
+                let depth = ((self.threshold - radii) / (self.threshold - self.critical)).min(1.0);
+                (depth > 0.0).then_some(SunHeat { star, intensity: depth * depth * (3.0 - 2.0 * depth) })
+            })
+            .max_by(|a, b| a.intensity.total_cmp(&b.intensity))
+    }
+
+    // How far `position` has to move to stay the critical distance from
+    // every star, like the ship's collisions. Zero when off
+    pub fn push_out(&self, bodies: &[CelestialBody], position: Vec3) -> Vec3 {
+        if !self.enabled {
+            return Vec3::zeros();
+        }
+        bodies.iter().filter(|body| body.is_star()).fold(Vec3::zeros(), |correction, body| {
+            let offset = position + correction - body.position;
+            let distance = offset.magnitude();
+            let min_distance = body.bounding_radius() * self.critical;
+            if distance >= min_distance {
+                return correction;
+            }
+            let normal = if distance > f32::EPSILON { offset / distance } else { Vec3::y() };
+            correction + normal * (min_distance - distance)
+        })
+    }
+}
+
+impl SunHeat {
+    // Camera trauma: squared, so the shake stays slight until it's close
+    fn trauma(&self) -> f32 {
+        self.intensity * self.intensity
+    }
+
+    // A small turn of the view, from simulation time so replays shake alike.
+    // Goes in front of the view matrix
+    pub fn shake(&self, time: f32) -> Mat4 {
+        let amount = MAX_SHAKE * self.trauma();
+        // Uneven sums of sines, so the shake doesn't visibly repeat
+        let wave = |phase: f32| ((time * 17.0 + phase).sin() + 0.5 * (time * 29.3 + phase * 2.1).sin()) / 1.5;
+        nalgebra_glm::rotation(amount * wave(0.0), &Vec3::y())
+            * nalgebra_glm::rotation(amount * wave(1.9), &Vec3::x())
+            * nalgebra_glm::rotation(0.5 * amount * wave(3.7), &Vec3::z())
+    }
+
+    // Shimmer and orange cast over the viewport, rolling with simulation time
+    pub fn apply(&self, framebuffer: &mut Framebuffer, viewport: Viewport, time: f32) {
+        heat_wobble(framebuffer, viewport, WOBBLE_PIXELS * self.intensity, WOBBLE_WAVELENGTH, time);
+        framebuffer.blend_rect(viewport.x as i32, viewport.y as i32, viewport.width, viewport.height, CAST_COLOR, MAX_CAST * self.intensity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use nalgebra_glm::Vec2;
+
+    #[test]
+    fn heat_ramps_up_inside_the_threshold_and_the_critical_distance_holds() {
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let bodies = vec![CelestialBody::new_sun("Sun".to_string(), vec![rim], Vec3::zeros(), 10.0, Color::from_hex(0xFFFFFF))];
+        let settings = SunHazeSettings::default();
+        let at = |x: f32| settings.heat(&bodies, Vec3::new(x, 0.0, 0.0)).map_or(0.0, |heat| heat.intensity);
+
+        // Nothing at all from the threshold out, then rising smoothly
+        assert_eq!(at(30.0), 0.0);
+        assert_eq!(at(100.0), 0.0);
+        let ramp: Vec<f32> = (0..=14).map(|step| at(30.0 - step as f32)).collect();
+        assert!(ramp.windows(2).all(|pair| pair[1] > pair[0]));
+        assert!(at(29.9) < 0.001);
+        assert_eq!(at(16.0), 1.0);
+        assert_eq!(at(12.0), 1.0);
+        assert!(SunHazeSettings { enabled: false, ..settings }.heat(&bodies, Vec3::new(12.0, 0.0, 0.0)).is_none());
+
+        // Pushed straight back out to the critical distance
+        assert_eq!(settings.push_out(&bodies, Vec3::new(0.0, 20.0, 0.0)), Vec3::zeros());
+        assert!((settings.push_out(&bodies, Vec3::new(0.0, 10.0, 0.0)) - Vec3::new(0.0, 6.0, 0.0)).magnitude() < 1e-4);
+
+        // The same time shakes the same way, and no heat doesn't shake
+        let hot = SunHeat { star: 0, intensity: 1.0 };
+        assert_eq!(hot.shake(3.25), hot.shake(3.25));
+        assert_ne!(hot.shake(3.25), Mat4::identity());
+        assert_eq!(SunHeat { intensity: 0.0, ..hot }.shake(3.25), Mat4::identity());
+    }
+}
@@ -0,0 +1,140 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::framebuffer::Viewport;
+use crate::geometry::normalize_or;
+use crate::overlay::ScreenProjection;
+
+// A star is looked at along its center and RING_SAMPLES rays spread around
+// a ring RING_FRACTION of the way out to its rim, which splits the disc
+// into halves of equal area. Every ray counts the same
+const RING_SAMPLES: usize = 12;
+const RING_FRACTION: f32 = 0.7;
+
+// How much of each star the observer can see, worked out once a frame for
+// everything that dims with it (the exposure's glare, the coronas)
+#[derive(Default)]
+pub struct SunVisibility {
+    // By body index, 0 for anything that isn't a star
+    fractions: Vec<f32>,
+}
+
+impl SunVisibility {
+    pub fn update(&mut self, bodies: &[CelestialBody], observer: Vec3) {
+        self.fractions = (0..bodies.len())
+            .map(|i| if bodies[i].is_star() { visible_fraction(bodies, i, observer) } else { 0.0 })
+            .collect();
+    }
+
+    // 0 when the star is hidden, 1 with all of it in sight
+    pub fn of(&self, star: usize) -> f32 {
+        self.fractions.get(star).copied().unwrap_or(0.0)
+    }
+
+    // Visible fraction of the most visible star whose center is on screen
+    pub fn glare(&self, projection: &ScreenProjection, viewport: Viewport, bodies: &[CelestialBody]) -> f32 {
+        bodies.iter().enumerate()
+            .filter(|(_, body)| body.is_star())
+            .filter(|(_, body)| {
+                projection.project(body.position)
+                    .is_some_and(|screen| screen.x >= 0.0 && screen.y >= 0.0 && viewport.contains(screen.x as usize, screen.y as usize))
+            })
+            .map(|(star, _)| self.of(star))
+            .fold(0.0, f32::max)
+    }
+}
+
+// Share of the rays from `observer` to the star that no other body's
+// bounding sphere is in the way of. Portals are flat and see-through, so
+// they never count; from inside the star all of it is in sight
+pub fn visible_fraction(bodies: &[CelestialBody], star: usize, observer: Vec3) -> f32 {
+    let sun = &bodies[star];
+    let to_sun = sun.position - observer;
+    let distance = to_sun.magnitude();
+    let radius = sun.bounding_radius();
+    if distance <= radius {
+        return 1.0;
+    }
+    let center = to_sun / distance;
+    // Any two directions square with the center one, for the ring
+    let side = normalize_or(center.cross(&Vec3::y()), Vec3::x());
+    let up = center.cross(&side);
+    let ring = RING_FRACTION * (radius / distance).asin();
+
+    let rays = std::iter::once(center).chain((0..RING_SAMPLES).map(|i| {
+        // Half a step round, so no ray lies along `side` or `up`
+        let azimuth = (i as f32 + 0.5) * std::f32::consts::TAU / RING_SAMPLES as f32;
+        center * ring.cos() + (side * azimuth.cos() + up * azimuth.sin()) * ring.sin()
+    }));
+    let clear = rays
+        .filter(|&ray| {
+            // Every ray meets the star; only what's nearer than that blocks it
+            let Some((surface, _)) = hit(observer, ray, sun.position, radius) else { return true };
+            !bodies.iter().enumerate().any(|(i, body)| {
+                i != star && !body.is_virtual() && !body.is_portal()
+                    && hit(observer, ray, body.position, body.bounding_radius()).is_some_and(|(near, far)| far > 0.0 && near < surface)
+            })
+        })
+        .count();
+    clear as f32 / (RING_SAMPLES + 1) as f32
+}
+
+// Distances along the unit `ray` from `origin` to where it enters and
+// leaves the sphere, None if it misses
+fn hit(origin: Vec3, ray: Vec3, center: Vec3, radius: f32) -> Option<(f32, f32)> {
+    let offset = center - origin;
+    let along = ray.dot(&offset);
+    let discriminant = along * along - (offset.magnitude_squared() - radius * radius);
+    (discriminant >= 0.0).then(|| (along - discriminant.sqrt(), along + discriminant.sqrt()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec2;
+
+    fn ball(position: Vec3, radius: f32, shader_type: ShaderType) -> CelestialBody {
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let mut body = CelestialBody::new_planet("Ball".to_string(), vec![rim], Vec3::zeros(), 0.0, 0.0, radius, Color::from_hex(0xFFFFFF), shader_type);
+        body.position = position;
+        body
+    }
+
+    #[test]
+    fn blocked_rays_are_counted_against_the_disc() {
+        // A sun of radius 100 seen from 1000 away along -Z: the ring sits
+        // 0.7 * asin(0.1) off the center, about 70 units out at the sun
+        let sun = Vec3::new(0.0, 0.0, -1000.0);
+        let with = |blocker: Option<CelestialBody>| {
+            let bodies: Vec<CelestialBody> = std::iter::once(ball(sun, 100.0, ShaderType::Star)).chain(blocker).collect();
+            visible_fraction(&bodies, 0, Vec3::zeros())
+        };
+        assert_eq!(with(None), 1.0);
+
+        // Covering the whole disc halfway there: an eclipse
+        assert_eq!(with(Some(ball(Vec3::new(0.0, 0.0, -500.0), 60.0, ShaderType::RockyPlanet))), 0.0);
+        // A small moon crossing the center only, the ring is 35 units out there
+        assert_eq!(with(Some(ball(Vec3::new(0.0, 0.0, -500.0), 10.0, ShaderType::RockyPlanet))), 12.0 / 13.0);
+        // A huge body whose edge stops just short of the center line covers
+        // the six ring rays on its side and leaves the center
+        let edge = ball(Vec3::new(10_005.0, 0.0, -500.0), 10_000.0, ShaderType::RockyPlanet);
+        assert_eq!(with(Some(edge)), 7.0 / 13.0);
+
+        // Behind the sun, behind the observer or off the line, nothing
+        assert_eq!(with(Some(ball(Vec3::new(0.0, 0.0, -1500.0), 300.0, ShaderType::RockyPlanet))), 1.0);
+        assert_eq!(with(Some(ball(Vec3::new(0.0, 0.0, 500.0), 300.0, ShaderType::RockyPlanet))), 1.0);
+        assert_eq!(with(Some(ball(Vec3::new(300.0, 0.0, -500.0), 100.0, ShaderType::RockyPlanet))), 1.0);
+        // Portals are looked through
+        let mut portal = ball(Vec3::new(0.0, 0.0, -500.0), 60.0, ShaderType::Portal);
+        portal.portal_partner = Some(0);
+        assert_eq!(with(Some(portal)), 1.0);
+
+        // Cached by body index, stars only
+        let bodies = vec![ball(sun, 100.0, ShaderType::Star), ball(Vec3::new(0.0, 0.0, -500.0), 10.0, ShaderType::RockyPlanet)];
+        let mut visibility = SunVisibility::default();
+        visibility.update(&bodies, Vec3::zeros());
+        assert_eq!((visibility.of(0), visibility.of(1), visibility.of(7)), (12.0 / 13.0, 0.0, 0.0));
+    }
+}
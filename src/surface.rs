@@ -0,0 +1,218 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+use nalgebra_glm::{Vec3, Vec4};
+use crate::body::CelestialBody;
+use crate::camera::Camera;
+use crate::geometry::normalize_or;
+use crate::input::{Action, InputFrame};
+use crate::light::Light;
+
+// The camera floats this share of the body's radius over the ground, which
+// drops the horizon into the lower part of a level view
+const ALTITUDE: f32 = 0.03;
+// Radians of latitude or longitude walked a second with the arrow keys
+const WALK_SPEED: f32 = 0.5;
+// Walking stops short of the poles, where east and west run together
+const MAX_LATITUDE: f32 = 1.4;
+// Radians of the body's turn left before sunrise on arrival
+const BEFORE_DAWN: f32 = 0.3;
+
+// When the star next rises or sets over the anchor, in seconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Daylight {
+    Rises(f32),
+    Sets(f32),
+    // Polar day and night
+    AlwaysUp,
+    AlwaysDown,
+}
+
+// The camera parked over a latitude and longitude of a body, turning with
+// it and looking level to the east, where the star comes up
+pub struct SurfaceView {
+    pub body: usize,
+    // Radians, north of the equator and around the spin from the model's +X
+    pub latitude: f32,
+    pub longitude: f32,
+    // What to go back to on leaving
+    pub previous: Camera,
+    pub followed: Option<usize>,
+    pub chase: bool,
+}
+
+impl SurfaceView {
+    // Arrives on the equator a little before sunrise
+    pub fn new(body: usize, bodies: &[CelestialBody], lights: &[Light], previous: Camera, followed: Option<usize>, chase: bool) -> Self {
+        let target = &bodies[body];
+        let pole = spin_axis(target);
+        let rate = solar_rate(bodies, body);
+        let longitude = strongest_light(lights, target.position)
+            .and_then(|to_light| (to_light - pole * pole.dot(&to_light)).try_normalize(f32::EPSILON))
+            .and_then(|noon| {
+                // A quarter turn back from noon is where it rises
+                let dawn = nalgebra_glm::rotate_vec3(&noon, -(FRAC_PI_2 + BEFORE_DAWN) * rate.signum(), &pole);
+                let local = target.model_matrix.try_inverse()? * Vec4::new(dawn.x, dawn.y, dawn.z, 0.0);
+                Some((-local.z).atan2(local.x))
+            })
+            .unwrap_or(0.0);
+        Self { body, latitude: 0.0, longitude, previous, followed, chase }
+    }
+
+    // The arrow keys walk over the surface
+    pub fn walk(&mut self, input: &InputFrame, delta_time: f32) {
+        let axis = |positive, negative| (input.held(positive) as i32 - input.held(negative) as i32) as f32 * WALK_SPEED * delta_time;
+        self.longitude = (self.longitude + axis(Action::OrbitRight, Action::OrbitLeft)).rem_euclid(TAU);
+        self.latitude = (self.latitude + axis(Action::OrbitUp, Action::OrbitDown)).clamp(-MAX_LATITUDE, MAX_LATITUDE);
+    }
+
+    // Puts the camera over the anchor as the body stands now. The camera's
+    // own angles can't look along the world's Y axis, which a tilted body's
+    // horizon may need, so its position, target and up are set directly
+    pub fn place(&self, camera: &mut Camera, bodies: &[CelestialBody]) {
+        let body = &bodies[self.body];
+        let up = self.up(body);
+        let position = body.position + up * body.shadow_radius() * (1.0 + ALTITUDE);
+        let east = normalize_or(spin_axis(body).cross(&up) * solar_rate(bodies, self.body).signum(), up.cross(&Vec3::y()));
+        camera.position = position;
+        camera.target = position + east * camera.distance.max(1.0);
+        camera.up = up;
+    }
+
+    // Straight up from the anchor, in world space
+    pub fn up(&self, body: &CelestialBody) -> Vec3 {
+        let local = Vec3::new(self.latitude.cos() * self.longitude.cos(), self.latitude.sin(), -self.latitude.cos() * self.longitude.sin());
+        normalize_or((body.model_matrix * Vec4::new(local.x, local.y, local.z, 0.0)).xyz(), Vec3::y())
+    }
+
+    // When the brightest light next crosses the anchor's horizon, as the
+    // body turns under it. None without any light or turning
+    pub fn daylight(&self, bodies: &[CelestialBody], lights: &[Light]) -> Option<Daylight> {
+        let body = &bodies[self.body];
+        let to_light = strongest_light(lights, body.position)?;
+        Some(daylight(spin_axis(body), self.up(body), to_light, solar_rate(bodies, self.body)))
+    }
+}
+
+// How fast the star crosses a body's sky, rad/s about its spin axis the
+// way the spin counts: its own spin less how fast the star's direction turns
+// as the body, or whichever parent of it orbits the star, goes round. Orbits
+// count angles the other way round the Y axis from spins
+pub fn solar_rate(bodies: &[CelestialBody], index: usize) -> f32 {
+    let mut orbiter = &bodies[index];
+    while let Some(parent) = orbiter.parent_index.map(|i| &bodies[i]).filter(|parent| !parent.is_star()) {
+        orbiter = parent;
+    }
+    bodies[index].spin_rate() + orbiter.angular_rate()
+}
+
+// Time from one noon to the next, None for stars and bodies that keep the
+// same face to theirs
+pub fn day_length(bodies: &[CelestialBody], index: usize) -> Option<f32> {
+    let rate = solar_rate(bodies, index);
+    (!bodies[index].is_star() && rate.abs() > f32::EPSILON).then(|| TAU / rate.abs())
+}
+
+fn spin_axis(body: &CelestialBody) -> Vec3 {
+    normalize_or((body.model_matrix * Vec4::new(0.0, 1.0, 0.0, 0.0)).xyz(), Vec3::y())
+}
+
+fn strongest_light(lights: &[Light], point: Vec3) -> Option<Vec3> {
+    lights.iter()
+        .filter_map(|light| light.incoming(point).map(|(to_light, attenuation)| (to_light, light.intensity * attenuation)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(to_light, _)| to_light)
+}
+
+// `up` turns about `pole` at `rate` under a light that stays put: it's up
+// while their hour angle is inside the half-day arc for this latitude and
+// declination, over the horizon at the body's center
+fn daylight(pole: Vec3, up: Vec3, to_light: Vec3, rate: f32) -> Daylight {
+    let latitude = pole.dot(&up).clamp(-1.0, 1.0).asin();
+    let declination = pole.dot(&to_light).clamp(-1.0, 1.0).asin();
+    let up_now = up.dot(&to_light) > 0.0;
+    let half_day = -latitude.tan() * declination.tan();
+    if rate.abs() <= f32::EPSILON || half_day >= 1.0 || half_day <= -1.0 {
+        let always_up = if rate.abs() <= f32::EPSILON { up_now } else { half_day <= -1.0 };
+        return if always_up { Daylight::AlwaysUp } else { Daylight::AlwaysDown };
+    }
+    let half_day = half_day.acos();
+    // Hour angle from noon, growing with time
+    let hour = pole.dot(&to_light.cross(&up)).atan2(to_light.dot(&up) - latitude.sin() * declination.sin()) * rate.signum();
+    if hour.abs() < half_day {
+        Daylight::Sets((half_day - hour) / rate.abs())
+    } else {
+        Daylight::Rises((-half_day - hour).rem_euclid(TAU) / rate.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use crate::color::Color;
+    use crate::light::collect_lights;
+    use crate::scene_graph::SceneGraph;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+    use nalgebra_glm::Vec2;
+
+    #[test]
+    fn the_star_rises_and_sets_as_the_anchor_turns_under_it() {
+        // Turning at 1 rad/s about +Y under a star along +X: the hour angle
+        // is the anchor's angle past the star, rising at -pi/2
+        let sun = Vec3::x();
+        let at = |angle: f32| Vec3::new(angle.cos(), 0.0, -angle.sin());
+        let near = |daylight: Daylight, expected: Daylight| match (daylight, expected) {
+            (Daylight::Rises(a), Daylight::Rises(b)) | (Daylight::Sets(a), Daylight::Sets(b)) => (a - b).abs() < 1e-4,
+            (a, b) => a == b,
+        };
+        assert!(near(daylight(Vec3::y(), at(0.0), sun, 1.0), Daylight::Sets(FRAC_PI_2)));
+        assert!(near(daylight(Vec3::y(), at(-1.0), sun, 1.0), Daylight::Sets(FRAC_PI_2 + 1.0)));
+        assert!(near(daylight(Vec3::y(), at(PI), sun, 1.0), Daylight::Rises(FRAC_PI_2)));
+        assert!(near(daylight(Vec3::y(), at(-2.0), sun, 1.0), Daylight::Rises(2.0 - FRAC_PI_2)));
+        // Turning the other way, or twice as fast
+        assert!(near(daylight(Vec3::y(), at(-1.0), sun, -1.0), Daylight::Sets(FRAC_PI_2 - 1.0)));
+        assert!(near(daylight(Vec3::y(), at(PI), sun, 2.0), Daylight::Rises(FRAC_PI_2 / 2.0)));
+        // The star a little north: days are longer in the north, the far
+        // north never sees it set and the far south never sees it rise
+        let north = Vec3::new(0.9, 0.3, 0.0).normalize();
+        let noon = |latitude: f32| daylight(Vec3::y(), Vec3::new(latitude.cos(), latitude.sin(), 0.0), north, 1.0);
+        assert!(matches!(noon(0.5), Daylight::Sets(left) if left > FRAC_PI_2));
+        assert!(matches!(noon(-0.5), Daylight::Sets(left) if left < FRAC_PI_2));
+        assert_eq!(daylight(Vec3::y(), Vec3::new(0.1, 0.99, 0.0).normalize(), north, 1.0), Daylight::AlwaysUp);
+        assert_eq!(daylight(Vec3::y(), Vec3::new(0.1, -0.99, 0.0).normalize(), north, 1.0), Daylight::AlwaysDown);
+
+        // A planet spinning at 0.5 rad/s and going round the star at 0.1;
+        // a locked moon of it only sees the planet's orbit turn the star
+        let rim = Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), Vec2::zeros());
+        let mut bodies = vec![
+            CelestialBody::new_sun("Sun".to_string(), vec![rim.clone()], Vec3::zeros(), 10.0, Color::from_hex(0xFFFFFF)),
+            CelestialBody::new_planet("Planet".to_string(), vec![rim.clone()], Vec3::zeros(), 100.0, 0.1, 2.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet),
+            CelestialBody::new_planet("Moon".to_string(), vec![rim], Vec3::zeros(), 10.0, 0.5, 1.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet),
+        ];
+        bodies[1].rotation_speed = 0.5;
+        bodies[2].parent_index = Some(1);
+        bodies[2].tidally_locked = true;
+        assert!((solar_rate(&bodies, 1) - 0.6).abs() < 1e-5);
+        assert!((solar_rate(&bodies, 2) - (0.1 - 0.5)).abs() < 1e-5);
+        assert!((day_length(&bodies, 1).unwrap() - TAU / 0.6).abs() < 1e-3);
+        assert_eq!(day_length(&bodies, 0), None);
+
+        // Arriving puts the star just under the eastern horizon, the
+        // camera looking that way over the ground
+        for body in bodies.iter_mut() {
+            body.update(3.0);
+        }
+        SceneGraph::from_bodies(&bodies).unwrap().update(&mut bodies);
+        let lights = collect_lights(&bodies[..1]);
+        let view = SurfaceView::new(1, &bodies, &lights, Camera::new(Vec3::zeros(), 500.0), None, false);
+        let Some(Daylight::Rises(wait)) = view.daylight(&bodies, &lights) else { panic!("{:?}", view.daylight(&bodies, &lights)) };
+        assert!((wait - BEFORE_DAWN / 0.6).abs() < 1e-3, "{}", wait);
+        let mut camera = Camera::new(Vec3::zeros(), 500.0);
+        view.place(&mut camera, &bodies);
+        let planet = &bodies[1];
+        assert!(((camera.position - planet.position).magnitude() - planet.shadow_radius() * (1.0 + ALTITUDE)).abs() < 1e-3);
+        let toward_sun = (bodies[0].position - camera.position).normalize();
+        let looking = (camera.target - camera.position).normalize();
+        assert!(looking.dot(&toward_sun) > 0.9 && camera.up.dot(&toward_sun) < 0.0, "{:?} {:?}", looking, toward_sun);
+    }
+}
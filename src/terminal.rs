@@ -0,0 +1,279 @@
+use std::fmt::Write as _;
+use std::io::{self, Stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+use crossterm::{cursor, execute, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags};
+use minifb::Key;
+use crate::backend::Backend;
+use crate::input::{InputBindings, InputFrame};
+
+// Most terminals never say when a key comes back up, they only repeat it
+// while it's held. Such a key counts as held until no repeat has come for
+// this long, which has to cover the pause before repeating starts
+const HOLD_SECONDS: f32 = 0.6;
+// Top half block: the foreground color paints the upper pixel of a cell
+// and the background the lower one
+const HALF_BLOCK: char = '\u{2580}';
+
+// Frames drawn with 24-bit colored half blocks, two pixels to a character
+// cell, scaled to whatever size the terminal is. Keys come from raw
+// terminal events
+pub struct TerminalBackend {
+    stdout: Stdout,
+    open: bool,
+    // Whether the terminal reports key releases
+    releases: bool,
+    // Keys down, with when they were last seen down
+    held: Vec<(Key, Instant)>,
+    // Keys that went down since the last frame
+    pressed: Vec<Key>,
+    frame_time: Option<Duration>,
+    last_present: Instant,
+    // Size in cells of the last frame; the screen is cleared when it changes
+    size: (u16, u16),
+}
+
+impl TerminalBackend {
+    pub fn new() -> Result<Self, String> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide).map_err(|err| err.to_string())?;
+        if releases {
+            execute!(stdout, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)).map_err(|err| err.to_string())?;
+        }
+        Ok(Self {
+            stdout,
+            open: true,
+            releases,
+            held: Vec::new(),
+            pressed: Vec::new(),
+            frame_time: None,
+            last_present: Instant::now(),
+            size: (0, 0),
+        })
+    }
+
+    fn key_event(&mut self, event: KeyEvent) {
+        // Raw mode swallows the interrupt, so Ctrl+C closes it like a window
+        if event.code == KeyCode::Char('c') && event.modifiers.contains(KeyModifiers::CONTROL) {
+            self.open = false;
+            return;
+        }
+        // Shift only ever arrives along with another key
+        let shift = event.modifiers.contains(KeyModifiers::SHIFT).then_some(Key::LeftShift);
+        let now = Instant::now();
+        for key in key_for(event.code).into_iter().chain(shift) {
+            self.held.retain(|&(held, _)| held != key);
+            match event.kind {
+                KeyEventKind::Release => {}
+                KeyEventKind::Press => {
+                    self.held.push((key, now));
+                    self.pressed.push(key);
+                }
+                KeyEventKind::Repeat => self.held.push((key, now)),
+            }
+        }
+    }
+}
+
+impl Backend for TerminalBackend {
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn present(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), String> {
+        let (columns, rows) = terminal::size().map_err(|err| err.to_string())?;
+        let mut out = String::new();
+        if (columns, rows) != self.size {
+            out.push_str("\x1b[2J");
+            self.size = (columns, rows);
+        }
+        out.push_str(&half_blocks(buffer, width, height, columns as usize, rows as usize));
+        self.stdout.write_all(out.as_bytes()).and_then(|_| self.stdout.flush()).map_err(|err| err.to_string())?;
+
+        // Nothing else waits for the next frame here
+        if let Some(frame_time) = self.frame_time {
+            let elapsed = self.last_present.elapsed();
+            if elapsed < frame_time {
+                thread::sleep(frame_time - elapsed);
+            }
+        }
+        self.last_present = Instant::now();
+        Ok(())
+    }
+
+    fn poll_input(&mut self, bindings: &InputBindings) -> InputFrame {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            match event::read() {
+                Ok(Event::Key(key)) => self.key_event(key),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        // Shift has no release of its own even where other keys do
+        let releases = self.releases;
+        self.held.retain(|&(key, seen)| (releases && key != Key::LeftShift) || seen.elapsed().as_secs_f32() < HOLD_SECONDS);
+
+        let pressed = std::mem::take(&mut self.pressed);
+        let held = &self.held;
+        bindings.sample(|key| held.iter().any(|&(down, _)| down == key), |key| pressed.contains(&key))
+    }
+
+    fn set_target_fps(&mut self, fps: usize) {
+        self.frame_time = (fps > 0).then(|| Duration::from_secs_f32(1.0 / fps as f32));
+    }
+
+    // A terminal is as big as it is
+    fn set_fullscreen(&mut self, _fullscreen: bool) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+impl Drop for TerminalBackend {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, terminal::LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// The minifb key a terminal key stands for, so the bindings work unchanged.
+// Shifted symbols count as their unshifted key
+fn key_for(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::Char(c) => match c.to_ascii_lowercase() {
+            'a' => Key::A, 'b' => Key::B, 'c' => Key::C, 'd' => Key::D, 'e' => Key::E, 'f' => Key::F,
+            'g' => Key::G, 'h' => Key::H, 'i' => Key::I, 'j' => Key::J, 'k' => Key::K, 'l' => Key::L,
+            'm' => Key::M, 'n' => Key::N, 'o' => Key::O, 'p' => Key::P, 'q' => Key::Q, 'r' => Key::R,
+            's' => Key::S, 't' => Key::T, 'u' => Key::U, 'v' => Key::V, 'w' => Key::W, 'x' => Key::X,
+            'y' => Key::Y, 'z' => Key::Z,
+            '0' | ')' => Key::Key0, '1' | '!' => Key::Key1, '2' | '@' => Key::Key2, '3' | '#' => Key::Key3,
+            '4' | '$' => Key::Key4, '5' | '%' => Key::Key5, '6' | '^' => Key::Key6, '7' | '&' => Key::Key7,
+            '8' | '*' => Key::Key8, '9' | '(' => Key::Key9,
+            ' ' => Key::Space,
+            '[' | '{' => Key::LeftBracket,
+            ']' | '}' => Key::RightBracket,
+            '-' | '_' => Key::Minus,
+            '=' | '+' => Key::Equal,
+            ',' | '<' => Key::Comma,
+            '.' | '>' => Key::Period,
+            ';' | ':' => Key::Semicolon,
+            '\'' | '"' => Key::Apostrophe,
+            '`' | '~' => Key::Backquote,
+            '/' | '?' => Key::Slash,
+            '\\' | '|' => Key::Backslash,
+            _ => return None,
+        },
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Escape,
+        KeyCode::Tab | KeyCode::BackTab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::Left => Key::Left,
+        KeyCode::Right => Key::Right,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::F(n) => match n {
+            1 => Key::F1, 2 => Key::F2, 3 => Key::F3, 4 => Key::F4, 5 => Key::F5, 6 => Key::F6,
+            7 => Key::F7, 8 => Key::F8, 9 => Key::F9, 10 => Key::F10, 11 => Key::F11, 12 => Key::F12,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Escape codes drawing the frame as large as it fits into `columns` by
+// `rows` cells, centered. Each pixel there is the average of the block of
+// the frame it covers. Colors are only sent when they change
+fn half_blocks(buffer: &[u32], width: usize, height: usize, columns: usize, rows: usize) -> String {
+    let scale = (columns as f32 / width as f32).min((2 * rows) as f32 / height as f32);
+    let (out_width, out_height) = (((width as f32 * scale) as usize).max(1), ((height as f32 * scale) as usize).max(1));
+    let cells = out_height.div_ceil(2);
+    let (left, top) = (columns.saturating_sub(out_width) / 2, rows.saturating_sub(cells) / 2);
+
+    let pixel = |x: usize, y: usize| {
+        if y >= out_height {
+            return 0;
+        }
+        average(buffer, width, x * width / out_width..((x + 1) * width / out_width).max(x * width / out_width + 1), y * height / out_height..((y + 1) * height / out_height).max(y * height / out_height + 1))
+    };
+    let mut out = String::new();
+    for row in 0..cells {
+        let _ = write!(out, "\x1b[{};{}H", top + row + 1, left + 1);
+        let (mut foreground, mut background) = (None, None);
+        for x in 0..out_width {
+            let (upper, lower) = (pixel(x, 2 * row), pixel(x, 2 * row + 1));
+            if foreground != Some(upper) {
+                let _ = write!(out, "\x1b[38;2;{};{};{}m", upper >> 16 & 0xFF, upper >> 8 & 0xFF, upper & 0xFF);
+                foreground = Some(upper);
+            }
+            if background != Some(lower) {
+                let _ = write!(out, "\x1b[48;2;{};{};{}m", lower >> 16 & 0xFF, lower >> 8 & 0xFF, lower & 0xFF);
+                background = Some(lower);
+            }
+            out.push(HALF_BLOCK);
+        }
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+// Mean color of a block of pixels
+fn average(buffer: &[u32], width: usize, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) -> u32 {
+    let mut sum = [0usize; 3];
+    let mut count = 0;
+    for y in ys {
+        for x in xs.clone() {
+            let pixel = buffer[y * width + x] as usize;
+            sum[0] += pixel >> 16 & 0xFF;
+            sum[1] += pixel >> 8 & 0xFF;
+            sum[2] += pixel & 0xFF;
+            count += 1;
+        }
+    }
+    let channel = |total: usize| ((total + count / 2) / count.max(1)) as u32;
+    channel(sum[0]) << 16 | channel(sum[1]) << 8 | channel(sum[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_become_centered_half_blocks() {
+        // Red over blue on the left, green over white on the right
+        let buffer = [0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF];
+        let out = half_blocks(&buffer, 2, 2, 4, 1);
+        assert_eq!(
+            out,
+            "\x1b[1;2H\x1b[38;2;255;0;0m\x1b[48;2;0;0;255m\u{2580}\x1b[38;2;0;255;0m\x1b[48;2;255;255;255m\u{2580}\x1b[0m",
+        );
+
+        // Four pixels to each one in the terminal, colors sent once per run
+        let buffer = [0x204060; 8 * 4];
+        let out = half_blocks(&buffer, 8, 4, 4, 1);
+        assert_eq!(out.matches(HALF_BLOCK).count(), 4);
+        assert_eq!(out.matches("\x1b[38;2;32;64;96m").count(), 1);
+    }
+
+    #[test]
+    fn terminal_keys_stand_for_window_keys() {
+        assert_eq!(key_for(KeyCode::Char('w')), Some(Key::W));
+        assert_eq!(key_for(KeyCode::Char('W')), Some(Key::W));
+        assert_eq!(key_for(KeyCode::Char('+')), Some(Key::Equal));
+        assert_eq!(key_for(KeyCode::Char('7')), Some(Key::Key7));
+        assert_eq!(key_for(KeyCode::F(12)), Some(Key::F12));
+        assert_eq!(key_for(KeyCode::Esc), Some(Key::Escape));
+        assert_eq!(key_for(KeyCode::Char('é')), None);
+    }
+}
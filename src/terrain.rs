@@ -0,0 +1,126 @@
+use nalgebra_glm::Vec3;
+use crate::obj::smooth_normals;
+use crate::vertex::Vertex;
+
+// Rocky terrain, shared by the rocky shader's coloring and the mesh
+// displacement, so the mountains standing out on the limb are the ones
+// shaded as highlands. It's sampled on the unit sphere and doesn't depend on
+// a mesh's size.
+const OCTAVES: usize = 5;
+// Hills per unit of radius in the first octave
+const BASE_FREQUENCY: f32 = 1.5;
+// Each octave has twice the detail at half the height
+const LACUNARITY: f32 = 2.0;
+const GAIN: f32 = 0.5;
+// Moves each octave off the others' lattice, so they don't all line up at
+// the origin
+const OCTAVE_OFFSET: f32 = 17.3;
+// Finite difference step for gradient(), a share of the radius; well under
+// the finest octave's features
+const GRADIENT_STEP: f32 = 0.002;
+
+// Fractal (fBm) value noise in the direction of `position`: 0 in the
+// deepest lowland, 1 on the highest peak
+pub fn height(position: Vec3) -> f32 {
+    let Some(direction) = position.try_normalize(f32::EPSILON) else { return 0.5 };
+    let (mut total, mut range) = (0.0, 0.0);
+    let (mut amplitude, mut frequency) = (1.0, BASE_FREQUENCY);
+    for octave in 0..OCTAVES {
+        total += value_noise(direction * frequency + Vec3::repeat(octave as f32 * OCTAVE_OFFSET)) * amplitude;
+        range += amplitude;
+        amplitude *= GAIN;
+        frequency *= LACUNARITY;
+    }
+    total / range
+}
+
+// Uphill direction and steepness of height() at `position`, per unit of
+// radius, by forward differences
+pub fn gradient(position: Vec3) -> Vec3 {
+    let step = GRADIENT_STEP * position.magnitude();
+    if step <= 0.0 {
+        return Vec3::zeros();
+    }
+    let here = height(position);
+    let slope = |axis: Vec3| (height(position + axis * step) - here) / GRADIENT_STEP;
+    Vec3::new(slope(Vec3::x()), slope(Vec3::y()), slope(Vec3::z()))
+}
+
+// Random values on the integer lattice, smoothly blended in between
+pub fn value_noise(point: Vec3) -> f32 {
+    let cell = point.map(f32::floor);
+    let t = (point - cell).map(|f| f * f * (3.0 - 2.0 * f));
+    let (x, y, z) = (cell.x as i32, cell.y as i32, cell.z as i32);
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let edge = |dy, dz| lerp(lattice(x, y + dy, z + dz), lattice(x + 1, y + dy, z + dz), t.x);
+    let face = |dz| lerp(edge(0, dz), edge(1, dz), t.y);
+    lerp(face(0), face(1), t.z)
+}
+
+// Same integer hash as the film grain, in [0, 1]
+fn lattice(x: i32, y: i32, z: i32) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x8da6b343) ^ (y as u32).wrapping_mul(0xd8163841) ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2c1b3c6d);
+    hash ^= hash >> 12;
+    (hash & 0xFFFF) as f32 / 65535.0
+}
+
+// Raises or sinks each vertex of a mesh centered on its origin by the
+// terrain's relief, at most `amplitude` times its distance from the center,
+// then smooths the normals again over the new faces. Vertices move straight
+// out from the center, so height() reads the same at the new position as
+// at the old one, and copies of a vertex move together so triangles stay
+// joined.
+pub fn displace(vertices: &mut [Vertex], amplitude: f32) {
+    for vertex in vertices.iter_mut() {
+        let relief = height(vertex.position) * 2.0 - 1.0;
+        vertex.position *= 1.0 + amplitude * relief;
+    }
+
+    smooth_normals(vertices);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::Obj;
+
+    #[test]
+    fn displaced_sphere_matches_the_shaded_terrain() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/models/Planet.obj");
+        let sphere = Obj::load(path).unwrap().get_vertex_array();
+        let mut lumpy = sphere.clone();
+        displace(&mut lumpy, 0.3);
+
+        let radii: Vec<f32> = lumpy.iter().map(|vertex| vertex.position.magnitude()).collect();
+        let (low, high) = radii.iter().fold((f32::MAX, f32::MIN), |(low, high), &r| (low.min(r), high.max(r)));
+        assert!(high - low > 0.1, "relief only {}", high - low);
+        for (before, after) in sphere.iter().zip(&lumpy) {
+            // Straight out from the center, by the height the shader will see
+            assert!(before.position.normalize().dot(&after.position.normalize()) > 0.9999);
+            let expected = before.position.magnitude() * (1.0 + 0.3 * (height(after.position) * 2.0 - 1.0));
+            assert!((after.position.magnitude() - expected).abs() < 1e-4);
+            // Normals face out and stay unit length
+            assert!((after.normal.magnitude() - 1.0).abs() < 1e-4);
+            assert!(after.normal.dot(&after.position) > 0.0);
+        }
+        // Copies of a vertex end up together, so no cracks open
+        for (a, b) in sphere.iter().zip(&lumpy) {
+            for (c, d) in sphere.iter().zip(&lumpy) {
+                if a.position == c.position {
+                    assert_eq!(b.position, d.position);
+                }
+            }
+        }
+        assert!((0.0..=1.0).contains(&height(Vec3::new(0.3, -2.0, 5.0))));
+
+        // Height only depends on direction, so the slope runs along the
+        // surface, and it's the same per unit of radius at any size
+        let point = Vec3::new(0.3, -0.5, 0.8).normalize();
+        let slope = gradient(point);
+        assert!(slope.magnitude() > 0.0);
+        assert!(slope.normalize().dot(&point).abs() < 0.05);
+        assert!((gradient(point * 10.0) - slope).magnitude() < 0.01 * slope.magnitude().max(1.0));
+    }
+}
@@ -0,0 +1,389 @@
+use std::sync::Arc;
+use image::ImageReader;
+use nalgebra_glm::{Vec2, Vec3};
+use serde::Deserialize;
+use crate::color::Color;
+use crate::terrain::value_noise;
+
+// Images larger than this per side are refused, they'd only eat memory
+const MAX_TEXTURE_SIZE: usize = 4096;
+// The procedural pattern: two shades of rock mottled by a few octaves of
+// noise
+const ROCK_DARK: Vec3 = Vec3::new(0.32, 0.29, 0.26);
+const ROCK_LIGHT: Vec3 = Vec3::new(0.72, 0.68, 0.62);
+const ROCK_OCTAVES: usize = 4;
+const ROCK_FREQUENCY: f32 = 4.0;
+
+// How a texture is laid onto a mesh
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Mapping {
+    // The mesh's own texture coordinates
+    Uv,
+    // Projected along the three model axes and blended by the normal; needs
+    // no coordinates and leaves no seams
+    Triplanar,
+}
+
+// How colors between texel centers are found
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    // Closest texel, blocky up close; suits the retro look
+    Nearest,
+    // The four around the point, weighted by distance
+    Bilinear,
+}
+
+// What lies past the 0-1 texture coordinates
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap {
+    // The image tiles, its right edge blending into its left
+    Repeat,
+    // The edge texels stretch on, e.g. for ring bands
+    Clamp,
+}
+
+// `texture` table of a body in the scene file
+#[derive(Deserialize, Clone)]
+pub struct TextureConfig {
+    // PNG or JPEG to use as the albedo, a procedural rock pattern without one
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default = "default_mapping")]
+    pub mapping: Mapping,
+    // Repeats of the texture per unit of model space (the radius of the
+    // sphere meshes)
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    // Triplanar only: higher keeps each projection to the faces looking
+    // down its axis, lower blends them over a wider band
+    #[serde(default = "default_sharpness")]
+    pub sharpness: f32,
+    #[serde(default = "default_filter")]
+    pub filter: Filter,
+    #[serde(default = "default_wrap")]
+    pub wrap: Wrap,
+}
+
+fn default_mapping() -> Mapping {
+    Mapping::Triplanar
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_sharpness() -> f32 {
+    4.0
+}
+
+fn default_filter() -> Filter {
+    Filter::Bilinear
+}
+
+fn default_wrap() -> Wrap {
+    Wrap::Repeat
+}
+
+// One level of a mip chain, 0-1 colors row by row from the top
+pub struct MipLevel {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+impl MipLevel {
+    fn texel(&self, column: i32, row: i32, wrap: Wrap) -> Vec3 {
+        let index = |i: i32, size: usize| match wrap {
+            Wrap::Repeat => i.rem_euclid(size as i32) as usize,
+            Wrap::Clamp => i.clamp(0, size as i32 - 1) as usize,
+        };
+        self.pixels[index(row, self.height) * self.width + index(column, self.width)]
+    }
+
+    fn sample(&self, uv: Vec2, filter: Filter, wrap: Wrap) -> Vec3 {
+        let (x, y) = (uv.x * self.width as f32, uv.y * self.height as f32);
+        match filter {
+            Filter::Nearest => self.texel(x.floor() as i32, y.floor() as i32, wrap),
+            Filter::Bilinear => {
+                // Measured from texel centers, so a texel's own color is
+                // exact at its middle
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (tx, ty) = (x - x0, y - y0);
+                let (column, row) = (x0 as i32, y0 as i32);
+                let top = self.texel(column, row, wrap).lerp(&self.texel(column + 1, row, wrap), tx);
+                let bottom = self.texel(column, row + 1, wrap).lerp(&self.texel(column + 1, row + 1, wrap), tx);
+                top.lerp(&bottom, ty)
+            }
+        }
+    }
+
+    // Half the size each way, every texel the average of the 2x2 block it
+    // covers
+    fn downsample(&self) -> Self {
+        let (width, height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for column in 0..width {
+                // The last block runs to the edge, taking in an odd texel
+                let block = |i: usize, size: usize, source: usize| (i * 2)..if i + 1 == size { source } else { i * 2 + 2 };
+                let (rows, columns) = (block(row, height, self.height), block(column, width, self.width));
+                let mut sum = Vec3::zeros();
+                for y in rows.clone() {
+                    for x in columns.clone() {
+                        sum += self.pixels[y * self.width + x];
+                    }
+                }
+                pixels.push(sum / (rows.len() * columns.len()) as f32);
+            }
+        }
+        MipLevel { width, height, pixels }
+    }
+}
+
+// Colors a surface is painted with
+pub enum Albedo {
+    // Mip chain from the full image down to 1x1
+    Image { levels: Vec<MipLevel> },
+    Procedural,
+}
+
+impl Albedo {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("Failed to load texture {}: {}", path, e);
+        // PNG or JPEG, told apart by their contents rather than the extension
+        let open = || ImageReader::open(path).and_then(|reader| reader.with_guessed_format()).map_err(|e| error(&e));
+        // The size is in the header, read before anything huge is decoded
+        let (width, height) = open()?.into_dimensions().map_err(|e| error(&e))?;
+        if width as usize > MAX_TEXTURE_SIZE || height as usize > MAX_TEXTURE_SIZE {
+            return Err(error(&format!("larger than {0}x{0}", MAX_TEXTURE_SIZE)));
+        }
+        // Gray images come out gray in all three channels, alpha is dropped
+        let image = open()?.decode().map_err(|e| error(&e))?.into_rgb8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image.pixels().map(|p| Vec3::new(p[0] as f32, p[1] as f32, p[2] as f32) / 255.0).collect();
+        Ok(Albedo::image(width, height, pixels))
+    }
+
+    // An image and its mip chain
+    pub fn image(width: usize, height: usize, pixels: Vec<Vec3>) -> Self {
+        let mut levels = vec![MipLevel { width, height, pixels }];
+        while let Some(last) = levels.last().filter(|level| level.width > 1 || level.height > 1) {
+            levels.push(last.downsample());
+        }
+        Albedo::Image { levels }
+    }
+
+    // Texels in the full size image, none for the pattern
+    pub fn texel_count(&self) -> Option<f32> {
+        match self {
+            Albedo::Image { levels } => Some((levels[0].width * levels[0].height) as f32),
+            Albedo::Procedural => None,
+        }
+    }
+
+    // Color at texture coordinates `uv`, (0, 0) being the top-left corner,
+    // from mip level `lod` (0 is the full image, each step halves it).
+    // Bilinear blends the two nearest levels as well, nearest picks one.
+    // The pattern has no texels, the other arguments only matter for images
+    pub fn sample(&self, uv: Vec2, lod: f32, filter: Filter, wrap: Wrap) -> Vec3 {
+        match self {
+            Albedo::Image { levels } => {
+                // Repeating coordinates are brought into 0-1 first so large
+                // ones don't lose precision
+                let uv = match wrap {
+                    Wrap::Repeat => uv.map(|c| c.rem_euclid(1.0)),
+                    Wrap::Clamp => uv,
+                };
+                let lod = lod.clamp(0.0, (levels.len() - 1) as f32);
+                match filter {
+                    Filter::Nearest => levels[lod.round() as usize].sample(uv, filter, wrap),
+                    Filter::Bilinear => {
+                        let level = lod.floor() as usize;
+                        let finer = levels[level].sample(uv, filter, wrap);
+                        match levels.get(level + 1) {
+                            Some(coarser) if lod > level as f32 => finer.lerp(&coarser.sample(uv, filter, wrap), lod - level as f32),
+                            _ => finer,
+                        }
+                    }
+                }
+            }
+            Albedo::Procedural => {
+                let (mut total, mut range, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, ROCK_FREQUENCY);
+                for _ in 0..ROCK_OCTAVES {
+                    total += value_noise(Vec3::new(uv.x * frequency, uv.y * frequency, 0.0)) * amplitude;
+                    range += amplitude;
+                    amplitude *= 0.5;
+                    frequency *= 2.0;
+                }
+                ROCK_DARK.lerp(&ROCK_LIGHT, total / range)
+            }
+        }
+    }
+}
+
+// A body's albedo and how it's mapped
+pub struct SurfaceTexture {
+    // Shared by every body using the same image
+    pub albedo: Arc<Albedo>,
+    pub mapping: Mapping,
+    pub scale: f32,
+    pub sharpness: f32,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl SurfaceTexture {
+    // Color at a point of the mesh, from its model space position and
+    // normal or its texture coordinates, depending on the mapping, at mip
+    // level `lod`
+    pub fn color(&self, position: Vec3, normal: Vec3, tex_coords: Vec2, lod: f32) -> Color {
+        let sample = |uv: Vec2| self.albedo.sample(uv, lod, self.filter, self.wrap);
+        let color = match self.mapping {
+            Mapping::Uv => sample(tex_coords * self.scale),
+            Mapping::Triplanar => {
+                let p = position * self.scale;
+                let weights = triplanar_weights(normal, self.sharpness);
+                sample(Vec2::new(p.z, p.y)) * weights.x + sample(Vec2::new(p.x, p.z)) * weights.y + sample(Vec2::new(p.x, p.y)) * weights.z
+            }
+        };
+        Color::from_vec3(color)
+    }
+
+    // Mip level for a triangle with these model space corners and texture
+    // coordinates covering `screen_area` pixels: the one where a texel is
+    // about a pixel across. Per triangle, from how many texels it spans.
+    // Triplanar takes the model space area as it projects nearly flat onto
+    // the axis it's mostly facing
+    pub fn level_of_detail(&self, positions: [Vec3; 3], tex_coords: [Vec2; 3], screen_area: f32) -> f32 {
+        let Some(texels) = self.albedo.texel_count() else { return 0.0 };
+        let texture_area = match self.mapping {
+            Mapping::Uv => {
+                let (u, v) = (tex_coords[1] - tex_coords[0], tex_coords[2] - tex_coords[0]);
+                (u.x * v.y - u.y * v.x).abs() / 2.0
+            }
+            Mapping::Triplanar => (positions[1] - positions[0]).cross(&(positions[2] - positions[0])).magnitude() / 2.0,
+        };
+        let texel_area = texture_area * self.scale * self.scale * texels;
+        if screen_area <= 0.0 || texel_area <= 0.0 {
+            return 0.0;
+        }
+        // Each level has a quarter of the texels
+        (0.5 * (texel_area / screen_area).log2()).max(0.0)
+    }
+}
+
+// Share of the YZ, XZ and XY projections at a point with this normal; they
+// add up to 1
+pub fn triplanar_weights(normal: Vec3, sharpness: f32) -> Vec3 {
+    let weights = normal.map(|component| component.abs().powf(sharpness));
+    let total = weights.x + weights.y + weights.z;
+    if total > f32::EPSILON {
+        weights / total
+    } else {
+        Vec3::repeat(1.0 / 3.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_and_jpeg_images_load() {
+        // A flat orange, which JPEG keeps close to exact
+        let image = image::RgbImage::from_pixel(8, 4, image::Rgb([255, 128, 0]));
+        for extension in ["png", "jpg"] {
+            let path = std::env::temp_dir().join(format!("space_travel_texture_test.{}", extension));
+            image.save(&path).unwrap();
+            let albedo = Albedo::load(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(albedo.texel_count(), Some(32.0));
+            let color = albedo.sample(Vec2::new(0.5, 0.5), 0.0, Filter::Nearest, Wrap::Clamp);
+            assert!((color - Vec3::new(1.0, 128.0 / 255.0, 0.0)).abs().max() < 0.02, "{}: {:?}", extension, color);
+        }
+        assert!(Albedo::load("assets/missing.png").is_err());
+
+        // An 8192x8192 PNG with no pixel data at all, refused on its header
+        // before the decoder would notice
+        let mut header = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        header.extend([0, 0, 0x20, 0, 0, 0, 0x20, 0, 8, 2, 0, 0, 0, 0xFD, 0xC8, 0x5D, 0x0E]);
+        header.extend(b"\0\0\0\0IDAT\x35\xAF\x06\x1E");
+        let path = std::env::temp_dir().join("space_travel_texture_test_huge.png");
+        std::fs::write(&path, header).unwrap();
+        let huge = Albedo::load(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert!(huge.as_ref().is_err_and(|e| e.ends_with("larger than 4096x4096")), "{:?}", huge.err());
+    }
+
+    #[test]
+    fn projections_blend_by_the_normal_and_repeat() {
+        let up = triplanar_weights(Vec3::y(), 4.0);
+        assert!((up - Vec3::y()).magnitude() < 1e-6);
+        let diagonal = triplanar_weights(Vec3::new(1.0, 1.0, 0.0).normalize(), 4.0);
+        assert!((diagonal - Vec3::new(0.5, 0.5, 0.0)).magnitude() < 1e-6);
+        // Sharper keeps more of the dominant projection
+        let tilted = Vec3::new(0.8, 0.6, 0.0);
+        assert!(triplanar_weights(tilted, 8.0).x > triplanar_weights(tilted, 2.0).x);
+        let sum = triplanar_weights(Vec3::new(-0.3, 0.5, -0.8), 3.0);
+        assert!((sum.x + sum.y + sum.z - 1.0).abs() < 1e-6);
+
+        // 2x1 image: black then white
+        let image = Albedo::image(2, 1, vec![Vec3::zeros(), Vec3::repeat(1.0)]);
+        let bilinear = |uv: Vec2, wrap| image.sample(uv, 0.0, Filter::Bilinear, wrap);
+        assert_eq!(bilinear(Vec2::new(0.25, 0.5), Wrap::Repeat), Vec3::zeros());
+        assert_eq!(bilinear(Vec2::new(0.75, 0.5), Wrap::Repeat), Vec3::repeat(1.0));
+        assert_eq!(bilinear(Vec2::new(1.75, -3.5), Wrap::Repeat), Vec3::repeat(1.0));
+        assert!((bilinear(Vec2::new(0.5, 0.5), Wrap::Repeat) - Vec3::repeat(0.5)).magnitude() < 1e-6);
+        // Across the 1 -> 0 edge repeating blends the last column into the
+        // first, the same from either side, so no seam shows
+        assert!((bilinear(Vec2::new(0.0, 0.5), Wrap::Repeat) - Vec3::repeat(0.5)).magnitude() < 1e-6);
+        assert_eq!(bilinear(Vec2::new(1.0, 0.5), Wrap::Repeat), bilinear(Vec2::new(0.0, 0.5), Wrap::Repeat));
+        // Clamping holds the edge texel instead
+        assert_eq!(bilinear(Vec2::new(0.0, 0.5), Wrap::Clamp), Vec3::zeros());
+        assert_eq!(bilinear(Vec2::new(1.5, 0.5), Wrap::Clamp), Vec3::repeat(1.0));
+        assert_eq!(bilinear(Vec2::new(-2.0, 0.5), Wrap::Clamp), Vec3::zeros());
+        // Nearest never blends
+        assert_eq!(image.sample(Vec2::new(0.49, 0.5), 0.0, Filter::Nearest, Wrap::Repeat), Vec3::zeros());
+        assert_eq!(image.sample(Vec2::new(0.51, 0.5), 0.0, Filter::Nearest, Wrap::Repeat), Vec3::repeat(1.0));
+        assert_eq!(image.sample(Vec2::new(-0.25, 0.5), 0.0, Filter::Nearest, Wrap::Repeat), Vec3::repeat(1.0));
+
+        // On a face looking straight down X only the YZ projection counts
+        let surface = SurfaceTexture { albedo: Arc::new(image), mapping: Mapping::Triplanar, scale: 1.0, sharpness: 4.0, filter: Filter::Bilinear, wrap: Wrap::Repeat };
+        let a = surface.color(Vec3::new(0.3, 0.5, 0.75), Vec3::x(), Vec2::zeros(), 0.0);
+        let b = surface.color(Vec3::new(-0.9, 0.5, 0.75), Vec3::x(), Vec2::zeros(), 0.0);
+        assert_eq!(a, b);
+        assert_eq!(a, Color::from_vec3(Vec3::repeat(1.0)));
+    }
+
+    #[test]
+    fn far_triangles_sample_smaller_mip_levels() {
+        // 4x4 checkerboard: every level above the first averages to gray
+        let pixels = (0..16).map(|i| Vec3::repeat(((i % 4 + i / 4) % 2) as f32)).collect();
+        let checker = Albedo::image(4, 4, pixels);
+        let Albedo::Image { levels } = &checker else { panic!("not an image") };
+        assert_eq!(levels.iter().map(|level| (level.width, level.height)).collect::<Vec<_>>(), vec![(4, 4), (2, 2), (1, 1)]);
+        assert!(levels[1].pixels.iter().all(|&texel| (texel - Vec3::repeat(0.5)).magnitude() < 1e-6));
+        let uv = Vec2::new(0.125, 0.125);
+        assert_eq!(checker.sample(uv, 0.0, Filter::Bilinear, Wrap::Repeat), Vec3::zeros());
+        assert!((checker.sample(uv, 0.5, Filter::Bilinear, Wrap::Repeat) - Vec3::repeat(0.25)).magnitude() < 1e-6);
+        assert_eq!(checker.sample(uv, 9.0, Filter::Nearest, Wrap::Repeat), Vec3::repeat(0.5));
+        // Odd sizes still end at 1x1, the odd texels counted in the last block
+        let Albedo::Image { levels } = Albedo::image(5, 3, (0..15).map(|i| Vec3::repeat(i as f32)).collect()) else { panic!("not an image") };
+        assert_eq!(levels.iter().map(|level| (level.width, level.height)).collect::<Vec<_>>(), vec![(5, 3), (2, 1), (1, 1)]);
+        assert!((levels[1].pixels[1] - Vec3::repeat(8.0)).magnitude() < 1e-5);
+        assert!((levels[2].pixels[0] - Vec3::repeat(6.75)).magnitude() < 1e-5);
+
+        // A unit UV square over 16 texels drawn 4 pixels big is at full
+        // size, 1 pixel big two levels down
+        let surface = SurfaceTexture { albedo: Arc::new(checker), mapping: Mapping::Uv, scale: 1.0, sharpness: 4.0, filter: Filter::Bilinear, wrap: Wrap::Repeat };
+        let corners = [Vec3::zeros(); 3];
+        let uvs = [Vec2::zeros(), Vec2::x(), Vec2::y()];
+        assert_eq!(surface.level_of_detail(corners, uvs, 8.0), 0.0);
+        assert!((surface.level_of_detail(corners, uvs, 0.5) - 2.0).abs() < 1e-6);
+        assert_eq!(surface.level_of_detail(corners, uvs, 0.0), 0.0);
+    }
+}
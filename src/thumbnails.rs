@@ -0,0 +1,174 @@
+use nalgebra_glm::{Vec3, Mat4};
+use crate::body::CelestialBody;
+use crate::camera::look_at_matrix;
+use crate::framebuffer::Framebuffer;
+use crate::geometry::normalize_or;
+use crate::light::Light;
+use crate::shader_params::ShaderParams;
+use crate::ShaderType;
+
+// Side of a thumbnail in pixels, rendered and shown at this size
+pub const THUMBNAIL_SIZE: usize = 64;
+// Share of the thumbnail's height the body spans
+const FILL: f32 = 0.8;
+// The camera stays this many radii away, and never closer than
+// MIN_DISTANCE so the near plane can't cut into a small moon
+const DISTANCE_RADII: f32 = 6.0;
+const MIN_DISTANCE: f32 = 30.0;
+// Angle at the body between its star and the camera; the lit side faces
+// the camera with a sliver of night along one edge
+const VIEW_ANGLE: f32 = 0.6;
+// What counts as a change: turning by more than ANGLE_STEP radians, and for
+// animated shaders ANIMATION_STEP seconds passing
+const ANGLE_STEP: f32 = 0.02;
+const ANIMATION_STEP: f32 = 0.25;
+
+// Everything a thumbnail's picture depends on, rounded so that small
+// movements don't redraw it every frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Appearance {
+    color: u32,
+    background: u32,
+    params: ShaderParams,
+    craters: usize,
+    ash: usize,
+    spin: [i32; 3],
+    light: [i32; 3],
+    clock: i32,
+    heat: bool,
+}
+
+impl Appearance {
+    fn of(body: &CelestialBody, to_light: Vec3, time: f32, heat: bool, background: u32) -> Self {
+        let step = |angle: f32| (angle.rem_euclid(std::f32::consts::TAU) / ANGLE_STEP).round() as i32;
+        // Erupting vents flicker as they brighten and fade
+        let animated = matches!(body.shader_type, ShaderType::Star | ShaderType::GasGiant | ShaderType::Station | ShaderType::Portal)
+            || !body.vents.is_empty();
+        Self {
+            color: body.color.to_pixel(),
+            background,
+            params: body.shader_params,
+            craters: body.craters.len(),
+            ash: body.ash.len(),
+            spin: [step(body.rotation.x), step(body.rotation.y), step(body.rotation.z)],
+            light: [to_light.x, to_light.y, to_light.z].map(|c| (c / ANGLE_STEP).round() as i32),
+            clock: if animated { (time / ANIMATION_STEP).floor() as i32 } else { 0 },
+            heat,
+        }
+    }
+}
+
+// One warp target's picture
+pub struct Thumbnail {
+    pub body: usize,
+    pub image: Framebuffer,
+    appearance: Option<Appearance>,
+}
+
+// Small renders of the warp targets for the HUD, redrawn one per frame and
+// only when the body looks different
+pub struct Thumbnails {
+    slots: Vec<Thumbnail>,
+    next: usize,
+}
+
+impl Thumbnails {
+    pub fn new(targets: &[usize]) -> Self {
+        let slots = targets.iter()
+            .map(|&body| Thumbnail { body, image: Framebuffer::new(THUMBNAIL_SIZE, THUMBNAIL_SIZE), appearance: None })
+            .collect();
+        Self { slots, next: 0 }
+    }
+
+    pub fn slots(&self) -> &[Thumbnail] {
+        &self.slots
+    }
+
+    // Redraws the first out-of-date thumbnail after the last one redrawn,
+    // so all of them get a turn. `draw` renders the body into a blank image
+    // through the given view matrix and field of view. Returns the slot
+    // redrawn, None when every thumbnail is current
+    pub fn refresh(
+        &mut self,
+        bodies: &[CelestialBody],
+        lights: &[Light],
+        time: f32,
+        heat: bool,
+        template: &Framebuffer,
+        mut draw: impl FnMut(&mut Framebuffer, &CelestialBody, Mat4, f32),
+    ) -> Option<usize> {
+        for offset in 0..self.slots.len() {
+            let index = (self.next + offset) % self.slots.len();
+            let body = &bodies[self.slots[index].body];
+            let to_light = light_direction(body, lights);
+            let appearance = Appearance::of(body, to_light, time, heat, template.background_color());
+            if self.slots[index].appearance == Some(appearance) {
+                continue;
+            }
+            let (view, fov) = framing(body, to_light);
+            let slot = &mut self.slots[index];
+            slot.image = template.blank(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+            draw(&mut slot.image, body, view, fov);
+            slot.appearance = Some(appearance);
+            self.next = index + 1;
+            return Some(index);
+        }
+        None
+    }
+}
+
+// Toward the other star whose light reaches it least dimmed (for equal
+// falloffs the nearest), or down +Z for a lone star
+fn light_direction(body: &CelestialBody, lights: &[Light]) -> Vec3 {
+    lights.iter()
+        .filter_map(|light| light.incoming(body.position))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(Vec3::z(), |(to_light, _)| to_light)
+}
+
+// View matrix and field of view that fit the body in a thumbnail, seen
+// from its day side
+fn framing(body: &CelestialBody, to_light: Vec3) -> (Mat4, f32) {
+    let side = normalize_or(Vec3::y().cross(&to_light), Vec3::x());
+    let direction = to_light * VIEW_ANGLE.cos() + side * VIEW_ANGLE.sin();
+    let radius = body.bounding_radius();
+    let distance = (radius * DISTANCE_RADII).max(MIN_DISTANCE);
+    let fov = 2.0 * (radius / FILL).atan2(distance);
+    (look_at_matrix(body.position + direction * distance, body.position, Vec3::y()), fov)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::light::LightKind;
+
+    #[test]
+    fn redraws_one_stale_thumbnail_per_frame() {
+        let planet = |name: &str, distance: f32| CelestialBody::new_planet(
+            name.to_string(), Vec::new(), Vec3::zeros(), distance, 1.0, 2.0, Color::from_hex(0x3366CC), ShaderType::RockyPlanet,
+        );
+        let mut bodies = vec![planet("Near", 50.0), planet("Far", 90.0)];
+        let lights = [Light { position: Vec3::zeros(), color: Vec3::new(1.0, 1.0, 1.0), intensity: 1.0, radius: 0.0, kind: LightKind::default() }];
+        let template = Framebuffer::new(8, 8);
+        let mut thumbnails = Thumbnails::new(&[0, 1]);
+        let mut refresh = |bodies: &[CelestialBody], time: f32| {
+            thumbnails.refresh(bodies, &lights, time, false, &template, |_, _, view, fov| {
+                assert!(view.iter().all(|m| m.is_finite()) && fov.is_finite());
+            })
+        };
+
+        assert_eq!(refresh(&bodies, 0.0), Some(0));
+        assert_eq!(refresh(&bodies, 0.1), Some(1));
+        // Nothing changed that shows at this size
+        bodies[1].rotation.y += ANGLE_STEP / 4.0;
+        assert_eq!(refresh(&bodies, 5.0), None);
+
+        bodies[1].color = Color::from_hex(0xCC6633);
+        bodies[0].rotation.y += 1.0;
+        // Its turn comes after the last one redrawn
+        assert_eq!(refresh(&bodies, 5.0), Some(0));
+        assert_eq!(refresh(&bodies, 5.0), Some(1));
+        assert_eq!(refresh(&bodies, 5.0), None);
+    }
+}
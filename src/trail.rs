@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use crate::body::CelestialBody;
+
+// A sample this far from the previous one means the body was teleported
+// rather than flown there, so its old trail is dropped instead of drawing a
+// line across the scene
+const TELEPORT_DISTANCE: f32 = 500.0;
+
+// How much recent history each body keeps
+#[derive(Deserialize, Clone, Copy)]
+pub struct TrailSettings {
+    // Number of samples kept per body
+    #[serde(default = "default_length")]
+    pub length: usize,
+    // Simulated seconds between samples
+    #[serde(default = "default_sample_interval")]
+    pub sample_interval: f32,
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        Self {
+            length: default_length(),
+            sample_interval: default_sample_interval(),
+        }
+    }
+}
+
+fn default_length() -> usize {
+    120
+}
+
+fn default_sample_interval() -> f32 {
+    0.1
+}
+
+// Ring buffer of recent world positions for every body, oldest first
+pub struct Trails {
+    settings: TrailSettings,
+    points: Vec<VecDeque<Vec3>>,
+    since_sample: f32,
+}
+
+impl Trails {
+    pub fn new(settings: TrailSettings, body_count: usize) -> Self {
+        Self {
+            settings,
+            points: (0..body_count).map(|_| VecDeque::with_capacity(settings.length)).collect(),
+            since_sample: 0.0,
+        }
+    }
+
+    // Samples on simulated time, not frames, so trail length in orbit terms
+    // doesn't depend on the frame rate; at high time scales this is at most
+    // one sample per frame
+    pub fn record(&mut self, bodies: &[CelestialBody], sim_delta: f32) {
+        self.since_sample += sim_delta;
+        if self.since_sample < self.settings.sample_interval {
+            return;
+        }
+        self.since_sample %= self.settings.sample_interval.max(f32::EPSILON);
+
+        for (trail, body) in self.points.iter_mut().zip(bodies) {
+            if trail.back().is_some_and(|last| (body.position - last).magnitude() > TELEPORT_DISTANCE) {
+                trail.clear();
+            }
+            if trail.len() == self.settings.length {
+                trail.pop_front();
+            }
+            if self.settings.length > 0 {
+                trail.push_back(body.position);
+            }
+        }
+    }
+
+    // Forgets all history, e.g. after the bodies were moved by a load
+    pub fn clear(&mut self) {
+        for trail in self.points.iter_mut() {
+            trail.clear();
+        }
+        self.since_sample = 0.0;
+    }
+
+    pub fn points(&self, body_index: usize) -> &VecDeque<Vec3> {
+        &self.points[body_index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn samples_on_sim_time_and_drops_oldest() {
+        let mut body = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 1.0, Color::from_hex(0xFFFFFF));
+        let settings = TrailSettings { length: 3, sample_interval: 0.5 };
+        let mut trails = Trails::new(settings, 1);
+
+        for step in 0..20 {
+            body.position.x = step as f32;
+            trails.record(std::slice::from_ref(&body), 0.25);
+        }
+        // One sample every other step, only the last three kept
+        let xs: Vec<f32> = trails.points(0).iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![15.0, 17.0, 19.0]);
+
+        body.position.x = 1000.0;
+        trails.record(std::slice::from_ref(&body), 0.5);
+        assert_eq!(trails.points(0).len(), 1);
+    }
+}
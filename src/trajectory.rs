@@ -0,0 +1,103 @@
+use nalgebra_glm::Vec3;
+use crate::body::CelestialBody;
+use crate::framebuffer::Framebuffer;
+use crate::overlay::{ScreenProjection, draw_blended_line};
+
+// How far ahead the path is predicted, in steps much coarser than the
+// simulation's; the line only has to show where the ship is headed
+const PREDICTION_SECONDS: f32 = 20.0;
+const PREDICTION_STEP: f32 = 0.25;
+// Slower than this the ship is as good as parked, there's no path to show
+const MIN_SPEED: f32 = 0.5;
+// Same softening as the n-body integrator, so a pass close to a body's
+// center doesn't fling the prediction off to infinity
+const SOFTENING: f32 = 1.0;
+// The far end of the line fades down to this
+const END_ALPHA: f32 = 0.1;
+
+// One sample of the predicted path, and whether the ship would be inside a
+// body there
+pub struct PathPoint {
+    pub position: Vec3,
+    pub colliding: bool,
+}
+
+// Where the ship coasts from `position` at `velocity` over the next
+// PREDICTION_SECONDS. With `gravity` (n-body mode) every body pulls on it,
+// held where it is now; otherwise the ship flies straight
+pub fn predict_path(position: Vec3, velocity: Vec3, bodies: &[CelestialBody], gravity: bool, ship_radius: f32) -> Vec<PathPoint> {
+    if velocity.magnitude() < MIN_SPEED {
+        return Vec::new();
+    }
+    let colliding = |point: Vec3| {
+        bodies.iter().any(|body| !body.is_virtual() && !body.is_portal() && (point - body.position).magnitude() < body.bounding_radius() + ship_radius)
+    };
+    let acceleration = |point: Vec3| {
+        bodies.iter().filter(|body| body.mass > 0.0).fold(Vec3::zeros(), |sum, body| {
+            let offset = body.position - point;
+            let distance_sq = offset.magnitude_squared() + SOFTENING * SOFTENING;
+            sum + offset * (body.mass / (distance_sq * distance_sq.sqrt()))
+        })
+    };
+
+    let steps = (PREDICTION_SECONDS / PREDICTION_STEP) as usize;
+    let (mut position, mut velocity) = (position, velocity);
+    let mut path = Vec::with_capacity(steps + 1);
+    path.push(PathPoint { position, colliding: colliding(position) });
+    for _ in 0..steps {
+        // Semi-implicit Euler, plenty for a preview this coarse
+        if gravity {
+            velocity += acceleration(position) * PREDICTION_STEP;
+        }
+        position += velocity * PREDICTION_STEP;
+        path.push(PathPoint { position, colliding: colliding(position) });
+    }
+    path
+}
+
+// The path as a line fading out toward its end, in `warning_color` wherever
+// either end of a segment is inside a body
+pub fn draw_predicted_path(framebuffer: &mut Framebuffer, projection: &ScreenProjection, path: &[PathPoint], color: u32, warning_color: u32) {
+    let segments = path.len().saturating_sub(1).max(1) as f32;
+    for (i, pair) in path.windows(2).enumerate() {
+        let (Some(a), Some(b)) = (projection.project(pair[0].position), projection.project(pair[1].position)) else { continue };
+        let alpha = 1.0 - (1.0 - END_ALPHA) * i as f32 / segments;
+        let color = if pair[0].colliding || pair[1].colliding { warning_color } else { color };
+        draw_blended_line(framebuffer, a, b, color, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::vertex::Vertex;
+    use crate::ShaderType;
+
+    #[test]
+    fn path_bends_toward_mass_and_flags_collisions() {
+        let vertices = vec![Vertex::new(Vec3::new(1.0, 0.0, 0.0), Vec3::x(), nalgebra_glm::Vec2::zeros())];
+        let mut planet = CelestialBody::new_planet("Planet".to_string(), vertices, Vec3::zeros(), 0.0, 0.0, 10.0, Color::from_hex(0xFFFFFF), ShaderType::RockyPlanet);
+        planet.position = Vec3::new(0.0, 0.0, -100.0);
+        planet.mass = 5000.0;
+        let bodies = [planet];
+        let start = Vec3::new(20.0, 0.0, 0.0);
+        let velocity = Vec3::new(0.0, 0.0, -10.0);
+
+        // Straight flight passes beside the planet without touching it
+        let straight = predict_path(start, velocity, &bodies, false, 1.0);
+        assert_eq!(straight.len(), (PREDICTION_SECONDS / PREDICTION_STEP) as usize + 1);
+        assert!(straight.iter().all(|point| point.position.x == 20.0 && !point.colliding));
+
+        // Under gravity the same start curves in toward it
+        let bent = predict_path(start, velocity, &bodies, true, 1.0);
+        assert!(bent.last().unwrap().position.x < 20.0);
+
+        // Aimed straight at it, the path runs through it
+        let head_on = predict_path(Vec3::zeros(), velocity, &bodies, false, 1.0);
+        assert!(head_on.iter().any(|point| point.colliding));
+        assert!(!head_on[0].colliding);
+
+        assert!(predict_path(start, Vec3::zeros(), &bodies, true, 1.0).is_empty());
+    }
+}
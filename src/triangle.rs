@@ -1,98 +1,206 @@
-use nalgebra_glm::{Vec3, dot, cross, normalize};
+use std::cmp::Ordering;
+use nalgebra_glm::{Vec3, Vec4, cross};
 use crate::fragment::Fragment;
 use crate::vertex::Vertex;
 use crate::line::line;
 use crate::color::Color;
 use crate::Uniforms;
+use crate::light::{shade, ShadingModel};
+use crate::geometry::{normalize_or, is_finite};
+use crate::framebuffer::Viewport;
+use crate::texture::SurfaceTexture;
 
-pub fn _triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
+// Screen positions are snapped to 1/2^SUBPIXEL_BITS of a pixel so the edge
+// functions come out as exact integers. Two triangles sharing an edge then
+// get exactly opposite values along it, and the fill rule can give every
+// pixel on it to one of them
+const SUBPIXEL_BITS: u32 = 8;
+
+// A screen position in subpixel steps
+type Point = (i64, i64);
+
+pub fn _triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, viewport: &Viewport) -> Vec<Fragment> {
   let mut fragments = Vec::new();
 
   // Draw the three sides of the triangle
-  fragments.extend(line(v1, v2));
-  fragments.extend(line(v2, v3));
-  fragments.extend(line(v3, v1));
+  fragments.extend(line(v1, v2, viewport));
+  fragments.extend(line(v2, v3, viewport));
+  fragments.extend(line(v3, v1, viewport));
 
   fragments
 }
 
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
-  triangle_with_uniforms(v1, v2, v3, None)
-}
-
-pub fn triangle_with_uniforms(v1: &Vertex, v2: &Vertex, v3: &Vertex, uniforms: Option<&Uniforms>) -> Vec<Fragment> {
+// Fragments of the triangle inside `viewport`, unlit
+pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex, viewport: &Viewport) -> Vec<Fragment> {
   let mut fragments = Vec::new();
-  let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
-
-  let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
-
-  // Performance protection: Limit triangle size to prevent excessive fragment generation
-  let triangle_width = (max_x - min_x) as usize;
-  let triangle_height = (max_y - min_y) as usize;
-  let max_triangle_size = 300; // Maximum triangle dimension in pixels
-  
-  if triangle_width > max_triangle_size || triangle_height > max_triangle_size {
-    // Skip rendering triangles that are too large (probably very close objects)
-    return fragments;
+  if let Some(setup) = Setup::new(v1, v2, v3, viewport, None) {
+    setup.rasterize(viewport, |fragment| fragments.push(fragment));
   }
+  fragments
+}
 
-  // Calculate flat shading normal as described in the reference
-  // Using world positions for proper lighting calculation
-  let world_a = Vec3::new(v1.position.x, v1.position.y, v1.position.z);
-  let world_b = Vec3::new(v2.position.x, v2.position.y, v2.position.z);
-  let world_c = Vec3::new(v3.position.x, v3.position.y, v3.position.z);
-  
-  let edge1 = world_b - world_a;
-  let edge2 = world_c - world_a;
-  let triangle_normal = normalize(&cross(&edge1, &edge2));
-  
-  // Calculate triangle center for light direction calculation
-  let triangle_center = (world_a + world_b + world_c) / 3.0;
-  
-  // Calculate lighting intensity based on uniforms
-  let intensity = if let Some(uniforms) = uniforms {
-    if uniforms.is_light_source {
-      1.0 // Light sources are always at full intensity
-    } else {
-      // Calculate light direction from light position to triangle center
-      let light_direction = normalize(&(uniforms.light_position - triangle_center));
-      dot(&triangle_normal, &light_direction).max(0.0)
-    }
-  } else {
-    0.5 // Default intensity if no uniforms provided
-  };
-
-  let triangle_area = edge_function(&a, &b, &c);
-
-  // Iterate over each pixel in the bounding box
-  for y in min_y..=max_y {
-    for x in min_x..=max_x {
-      let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-
-      // Calculate barycentric coordinates
-      let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
-
-      // Check if the point is inside the triangle
-      if w1 >= 0.0 && w1 <= 1.0 && 
-         w2 >= 0.0 && w2 <= 1.0 &&
-         w3 >= 0.0 && w3 <= 1.0 {
-        
-        // Interpolate color from vertices
-        let color = Color::new(
-          (v1.color.r as f32 * w1 + v2.color.r as f32 * w2 + v3.color.r as f32 * w3) as u8,
-          (v1.color.g as f32 * w1 + v2.color.g as f32 * w2 + v3.color.g as f32 * w3) as u8,
-          (v1.color.b as f32 * w1 + v2.color.b as f32 * w2 + v3.color.b as f32 * w3) as u8,
-        );
-
-        // Interpolate depth
-        let depth = a.z * w1 + b.z * w2 + c.z * w3;
-
-        fragments.push(Fragment::new_with_intensity(x as f32, y as f32, color, depth, intensity));
+// What a triangle needs worked out once before any of its pixels, however
+// many parts of the frame it's then drawn in
+pub struct Setup<'a> {
+  corners: [&'a Vertex; 3],
+  edges: Edges,
+  // Pixels of the viewport its bounding box covers
+  bounds: (i32, i32, i32, i32),
+  lit: Lit<'a>,
+  // Emissive parts (e.g. station nav lights) glow with their own color
+  emission: f32,
+  texture: Option<&'a SurfaceTexture>,
+  lod: f32,
+  // Screen space weights are skewed by perspective; weighting each corner
+  // by 1 / w as well undoes that for what lies on the surface
+  inverse_w: [f32; 3],
+  // Colors, textures and glow keep the skewed weights (see
+  // ScreenProjection::affine); lighting stays perspective correct
+  affine: bool,
+}
+
+impl<'a> Setup<'a> {
+  // None for a triangle that can't cover any pixel of `viewport`
+  pub fn new(v1: &'a Vertex, v2: &'a Vertex, v3: &'a Vertex, viewport: &Viewport, uniforms: Option<&'a Uniforms<'a>>) -> Option<Self> {
+    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+
+    // NaN or infinite corners would turn into garbage pixel coordinates
+    if !(is_finite(&a) && is_finite(&b) && is_finite(&c)) {
+      return None;
+    }
+
+    // Only the part of the bounding box inside the viewport being drawn gets
+    // rasterized, and nothing needs working out for a triangle outside it
+    let bounds = clamp_to_viewport(calculate_bounding_box(&a, &b, &c), viewport);
+    if bounds.0 > bounds.2 || bounds.1 > bounds.3 {
+      return None;
+    }
+
+    // A zero-area triangle covers no pixels
+    let edges = Edges::new(&a, &b, &c)?;
+
+    // Calculate lighting intensity based on uniforms
+    let lit = if let Some(uniforms) = uniforms {
+      let corner = |v: &Vertex| shade(uniforms.frame.lights, v.world_position, normalize_or(v.transformed_normal, Vec3::zeros())) + uniforms.frame.ambient;
+      if v1.emission >= 1.0 && v2.emission >= 1.0 && v3.emission >= 1.0 {
+        Lit::Flat(Vec3::zeros()) // Glows all over (e.g. a star), reflects no light at all
+      } else if uniforms.shading == ShadingModel::Gouraud {
+        Lit::Corners([corner(v1), corner(v2), corner(v3)])
+      } else if uniforms.shading == ShadingModel::Phong {
+        Lit::Pixels(uniforms)
+      } else {
+        // Flat shading normal, computed in world space so it lines up with the
+        // positions of the lights
+        let to_world = |v: &Vertex| (uniforms.model_matrix * Vec4::new(v.position.x, v.position.y, v.position.z, 1.0)).xyz();
+        let world_a = to_world(v1);
+        let world_b = to_world(v2);
+        let world_c = to_world(v3);
+
+        let edge1 = world_b - world_a;
+        let edge2 = world_c - world_a;
+        // Coincident corners have no normal; such a triangle just gets no light
+        let triangle_normal = normalize_or(cross(&edge1, &edge2), Vec3::zeros());
+
+        // Light direction is taken from the triangle center
+        let triangle_center = (world_a + world_b + world_c) / 3.0;
+        Lit::Flat(shade(uniforms.frame.lights, triangle_center, triangle_normal) + uniforms.frame.ambient)
       }
+    } else {
+      Lit::Flat(Vec3::new(0.5, 0.5, 0.5)) // Default intensity if no uniforms provided
+    };
+
+    let texture = uniforms.and_then(|uniforms| uniforms.texture);
+    // One mip level for the whole triangle, from the texels it spans over the
+    // pixels it covers
+    let lod = texture.map_or(0.0, |texture| texture.level_of_detail(
+      [v1.position, v2.position, v3.position],
+      [v1.tex_coords, v2.tex_coords, v3.tex_coords],
+      edge_function(&a, &b, &c).abs() / 2.0,
+    ));
+
+    Some(Setup {
+      corners: [v1, v2, v3],
+      edges,
+      bounds,
+      lit,
+      emission: (v1.emission + v2.emission + v3.emission) / 3.0,
+      texture,
+      lod,
+      inverse_w: [1.0 / v1.clip_position.w, 1.0 / v2.clip_position.w, 1.0 / v3.clip_position.w],
+      affine: uniforms.is_some_and(|uniforms| uniforms.frame.affine),
+    })
+  }
+
+  pub fn bounds(&self) -> (i32, i32, i32, i32) {
+    self.bounds
+  }
+
+  // Whether any of its pixels are in `area`, so a thin triangle across
+  // the frame isn't drawn in every tile its bounds take in
+  pub fn covers(&self, area: &Viewport) -> bool {
+    let (min_x, min_y, max_x, max_y) = clamp_to_viewport(self.bounds, area);
+    min_x <= max_x && min_y <= max_y && self.edges.reaches((min_x, min_y, max_x, max_y))
+  }
+
+  // Hands `emit` the fragments that are inside `area` as well, one at a
+  // time as they're made
+  pub fn rasterize(&self, area: &Viewport, mut emit: impl FnMut(Fragment)) {
+    let (min_x, min_y, max_x, max_y) = clamp_to_viewport(self.bounds, area);
+    if min_x > max_x || min_y > max_y {
+      return;
     }
+    let [v1, v2, v3] = self.corners;
+    let (a, b, c) = (v1.transformed_position, v2.transformed_position, v3.transformed_position);
+    let [inverse_w1, inverse_w2, inverse_w3] = self.inverse_w;
+
+    self.edges.traverse((min_x, min_y, max_x, max_y), |x, y, (w1, w2, w3)| {
+      // Everything on the surface is interpolated perspective correctly:
+      // attribute / w and 1 / w are both linear on screen, so the weights
+      // times 1 / w, divided by their sum, give each corner its true share
+      let (p1, p2, p3) = (w1 * inverse_w1, w2 * inverse_w2, w3 * inverse_w3);
+      let scale = 1.0 / (p1 + p2 + p3);
+      let (p1, p2, p3) = (p1 * scale, p2 * scale, p3 * scale);
+      let (s1, s2, s3) = if self.affine { (w1, w2, w3) } else { (p1, p2, p3) };
+
+      // Interpolate color from vertices, or paint the texture on, except
+      // over glowing parts which keep the shader's color
+      let color = match self.texture {
+        Some(texture) if self.emission <= 0.0 => texture.color(
+          v1.position * s1 + v2.position * s2 + v3.position * s3,
+          v1.normal * s1 + v2.normal * s2 + v3.normal * s3,
+          v1.tex_coords * s1 + v2.tex_coords * s2 + v3.tex_coords * s3,
+          self.lod,
+        ),
+        _ => Color::from_vec3(v1.color.to_vec3() * s1 + v2.color.to_vec3() * s2 + v3.color.to_vec3() * s3),
+      };
+
+      // Depth is z / w already, which is itself linear on screen, so it keeps
+      // the plain weights
+      let depth = a.z * w1 + b.z * w2 + c.z * w3;
+
+      // The glowing share of the color is given off rather than reflected
+      let glow = (v1.emission * s1 + v2.emission * s2 + v3.emission * s3).clamp(0.0, 1.0);
+      let (color, emissive) = if glow > 0.0 { (color * (1.0 - glow), color * glow) } else { (color, Color::black()) };
+
+      let world_position = v1.world_position * p1 + v2.world_position * p2 + v3.world_position * p3;
+      let world_normal = v1.transformed_normal * p1 + v2.transformed_normal * p2 + v3.transformed_normal * p3;
+      let intensity = match self.lit {
+        Lit::Flat(intensity) => intensity,
+        Lit::Corners([i1, i2, i3]) => i1 * p1 + i2 * p2 + i3 * p3,
+        Lit::Pixels(uniforms) => shade(uniforms.frame.lights, world_position, normalize_or(world_normal, Vec3::zeros())) + uniforms.frame.ambient,
+      };
+
+      emit(Fragment::new_with_intensity(x as f32, y as f32, color, depth, intensity, emissive).with_surface(world_position, world_normal));
+    });
   }
+}
 
-  fragments
+// Light reaching a triangle, by ShadingModel: the same all over, blended
+// from its corners, or worked out again at every pixel
+enum Lit<'a> {
+  Flat(Vec3),
+  Corners([Vec3; 3]),
+  Pixels(&'a Uniforms<'a>),
 }
 
 fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
@@ -101,25 +209,449 @@ fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i3
     let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
     let max_y = v1.y.max(v2.y).max(v3.y).ceil() as i32;
 
-    // Clamp to reasonable screen bounds to prevent coordinate overflow
-    let min_x = min_x.max(-1000).min(2000);
-    let min_y = min_y.max(-1000).min(2000);
-    let max_x = max_x.max(-1000).min(2000);
-    let max_y = max_y.max(-1000).min(2000);
-
     (min_x, min_y, max_x, max_y)
 }
 
-fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) -> (f32, f32, f32) {
-    let w1 = edge_function(b, c, p) / area;
-    let w2 = edge_function(c, a, p) / area;
-    let w3 = edge_function(a, b, p) / area;
+fn clamp_to_viewport((min_x, min_y, max_x, max_y): (i32, i32, i32, i32), viewport: &Viewport) -> (i32, i32, i32, i32) {
+    let (left, top) = (viewport.x as i32, viewport.y as i32);
+    let right = left + viewport.width as i32 - 1;
+    let bottom = top + viewport.height as i32 - 1;
+    (min_x.max(left), min_y.max(top), max_x.min(right), max_y.min(bottom))
+}
+
+const HALF_PIXEL: i64 = 1 << (SUBPIXEL_BITS - 1);
+
+fn pixel_center(x: i32, y: i32) -> Point {
+    (((x as i64) << SUBPIXEL_BITS) | HALF_PIXEL, ((y as i64) << SUBPIXEL_BITS) | HALF_PIXEL)
+}
+
+fn snap(v: &Vec3) -> Point {
+    let steps = (1 << SUBPIXEL_BITS) as f32;
+    ((v.x * steps).round() as i64, (v.y * steps).round() as i64)
+}
+
+fn edge(a: Point, b: Point, p: Point) -> i64 {
+    (p.0 - a.0) * (b.1 - a.1) - (p.1 - a.1) * (b.0 - a.0)
+}
 
-    (w1, w2, w3)
+// Top-left fill rule: a pixel center right on an edge belongs to the
+// triangle only if that's a top edge (flat, running left with the winding
+// of positive area) or a left edge (running down). Of two triangles sharing
+// the edge exactly one sees it that way
+fn is_top_left(from: Point, to: Point) -> bool {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    dy > 0 || (dy == 0 && dx < 0)
+}
+
+// The fixed point edge functions of one triangle, either winding. Their
+// values are kept with the sign that makes them positive inside
+struct Edges {
+    corners: [Point; 3],
+    // Twice the area, negative for the other winding
+    area: i64,
+    inverse_area: f32,
+    // Smallest value of each edge function (the one opposite the corner
+    // with the same index) still inside: 0 where the edge owns the pixels
+    // right on it, 1 where it doesn't
+    thresholds: [i64; 3],
+    // How much each changes one pixel to the right and one row down
+    per_pixel: [i64; 3],
+    per_row: [i64; 3],
+}
+
+impl Edges {
+    // None for a zero-area triangle, which has no barycentric coordinates
+    fn new(a: &Vec3, b: &Vec3, c: &Vec3) -> Option<Self> {
+        let corners = [snap(a), snap(b), snap(c)];
+        let area = edge(corners[0], corners[1], corners[2]);
+        if area == 0 {
+            return None;
+        }
+        let sign = area.signum();
+        let edges = [(1, 2), (2, 0), (0, 1)].map(|(from, to)| (corners[from], corners[to]));
+        // Edges are judged as if wound the positive way round
+        let thresholds = edges.map(|(from, to)| {
+            let (from, to) = if area > 0 { (from, to) } else { (to, from) };
+            if is_top_left(from, to) { 0 } else { 1 }
+        });
+        Some(Self {
+            corners,
+            area,
+            inverse_area: 1.0 / area.abs() as f32,
+            thresholds,
+            per_pixel: edges.map(|(from, to)| (sign * (to.1 - from.1)) << SUBPIXEL_BITS),
+            per_row: edges.map(|(from, to)| (-sign * (to.0 - from.0)) << SUBPIXEL_BITS),
+        })
+    }
+
+    fn values(&self, p: Point) -> [i64; 3] {
+        let [a, b, c] = self.corners;
+        let sign = self.area.signum();
+        [edge(b, c, p) * sign, edge(c, a, p) * sign, edge(a, b, p) * sign]
+    }
+
+    // Whether any pixel center within the bounds could be inside: false
+    // when they're all on the wrong side of one edge. Each edge function is
+    // largest at one corner of the bounds, found from the signs of its steps
+    fn reaches(&self, (min_x, min_y, max_x, max_y): (i32, i32, i32, i32)) -> bool {
+        let corner = self.values(pixel_center(min_x, min_y));
+        let (columns, rows) = ((max_x - min_x) as i64, (max_y - min_y) as i64);
+        (0..3).all(|i| corner[i] + self.per_pixel[i].max(0) * columns + self.per_row[i].max(0) * rows >= self.thresholds[i])
+    }
+
+    // Whether the fill rule puts a point with these values in the triangle
+    fn inside(&self, values: [i64; 3]) -> bool {
+        values.iter().zip(self.thresholds).all(|(&value, threshold)| value >= threshold)
+    }
+
+    fn weights(&self, values: [i64; 3]) -> (f32, f32, f32) {
+        let [w1, w2, w3] = values.map(|value| value as f32 * self.inverse_area);
+        (w1, w2, w3)
+    }
+
+    fn step_x(&self, values: [i64; 3], pixels: i32) -> [i64; 3] {
+        [0, 1, 2].map(|i| values[i] + self.per_pixel[i] * pixels as i64)
+    }
+
+    fn step_y(&self, values: [i64; 3]) -> [i64; 3] {
+        [0, 1, 2].map(|i| values[i] + self.per_row[i])
+    }
+
+    // First and last pixel from the start of a row, no further than `last`,
+    // that are inside, given the values at that start. None when the row
+    // misses the triangle
+    fn span(&self, row: [i64; 3], last: i32) -> Option<(i32, i32)> {
+        let (mut first, mut last) = (0, last as i64);
+        for (value, (step, threshold)) in row.into_iter().zip(self.per_pixel.into_iter().zip(self.thresholds)) {
+            // Pixel k is inside this edge if value + k * step >= threshold
+            match step.cmp(&0) {
+                Ordering::Greater => first = first.max((threshold - value + step - 1).div_euclid(step)),
+                Ordering::Less => last = last.min((value - threshold).div_euclid(-step)),
+                Ordering::Equal if value < threshold => return None,
+                Ordering::Equal => {}
+            }
+        }
+        (first <= last).then_some((first as i32, last as i32))
+    }
+
+    // Calls `visit` with the weights of every pixel inside, row by row
+    // within the bounds. The edge functions are affine, so they're worked
+    // out once at the first pixel center and stepped from there; only the
+    // pixels inside are visited, and rows the triangle misses not at all
+    fn traverse(&self, (min_x, min_y, max_x, max_y): (i32, i32, i32, i32), mut visit: impl FnMut(i32, i32, (f32, f32, f32))) {
+        if min_x > max_x || min_y > max_y {
+            return;
+        }
+        let mut row = self.values(pixel_center(min_x, min_y));
+        for y in min_y..=max_y {
+            if let Some((first, last)) = self.span(row, max_x - min_x) {
+                let mut values = self.step_x(row, first);
+                for x in min_x + first..=min_x + last {
+                    debug_assert!(self.inside(values));
+                    visit(x, y, self.weights(values));
+                    values = self.step_x(values, 1);
+                }
+            }
+            row = self.step_y(row);
+        }
+    }
 }
 
 fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
     (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
 }
 
+// Which triangles a draw skips before rasterizing, by the way they face
+// the camera. Meshes wind their corners counterclockwise seen from outside,
+// which the viewport's flipped y turns into a positive edge function
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CullMode {
+  None,
+  Back,  // Closed meshes seen from outside, whose far side is always hidden
+  Front, // Closed meshes seen from inside, like the sky sphere
+}
+
+impl CullMode {
+  // Whether the triangle, on screen already, faces the culled way. Edge-on
+  // ones are left to the rasterizer, which draws nothing for them
+  pub fn culls(self, v1: &Vertex, v2: &Vertex, v3: &Vertex) -> bool {
+    let area = edge_function(&v1.transformed_position, &v2.transformed_position, &v3.transformed_position);
+    match self {
+      CullMode::None => false,
+      CullMode::Back => area < 0.0,
+      CullMode::Front => area > 0.0,
+    }
+  }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FrameUniforms, Lighting, ScreenProjection, ShaderType};
+    use crate::low_spec::Detail;
+    use nalgebra_glm::{Vec2, Mat2, Mat4};
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+    use std::hint::black_box;
+    use criterion::Criterion;
+
+    const SCREEN: Viewport = Viewport { x: 0, y: 0, width: 400, height: 400 };
+
+    fn point() -> impl Strategy<Value = Vec3> {
+        (0.0f32..200.0, 0.0f32..200.0).prop_map(|(x, y)| Vec3::new(x, y, 0.0))
+    }
+
+    // Right on a subpixel step, so snapping leaves it where it is
+    fn on_grid(v: Vec3) -> Vec3 {
+        let steps = (1 << SUBPIXEL_BITS) as f32;
+        Vec3::new((v.x * steps).round() / steps, (v.y * steps).round() / steps, v.z)
+    }
+
+    // What the rasterizer did before stepping: every edge function at every
+    // pixel of the bounds
+    fn every_pixel(edges: &Edges, (min_x, min_y, max_x, max_y): (i32, i32, i32, i32), mut visit: impl FnMut(i32, i32, (f32, f32, f32))) {
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let values = edges.values(pixel_center(x, y));
+                if edges.inside(values) {
+                    visit(x, y, edges.weights(values));
+                }
+            }
+        }
+    }
+
+    // How many fragments land on each pixel
+    fn coverage(triangles: &[[Vec3; 3]]) -> HashMap<(i32, i32), usize> {
+        let mut counts = HashMap::new();
+        for [a, b, c] in triangles {
+            for fragment in triangle(&vertex(*a), &vertex(*b), &vertex(*c), &SCREEN) {
+                *counts.entry((fragment.position.x as i32, fragment.position.y as i32)).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    fn vertex(position: Vec3) -> Vertex {
+        Vertex::new(position, Vec3::new(0.0, 0.0, 1.0), Vec2::new(0.0, 0.0))
+    }
+
+    proptest! {
+        #[test]
+        fn barycentric_weights_of_inside_points_sum_to_one(
+            a in point(), b in point(), c in point(), weights in (0.01f32..1.0, 0.01f32..1.0, 0.01f32..1.0),
+        ) {
+            let (a, b, c) = (on_grid(a), on_grid(b), on_grid(c));
+            prop_assume!(edge_function(&a, &b, &c).abs() > 1.0);
+            let total = weights.0 + weights.1 + weights.2;
+            let p = (a * weights.0 + b * weights.1 + c * weights.2) / total;
+            // Snapping moves p by up to half a subpixel step each way, which
+            // can take it out of a sliver of a triangle
+            let step = 1.0 / (1 << SUBPIXEL_BITS) as f32;
+            let clear = |from: &Vec3, to: &Vec3| edge_function(from, to, &p).abs() / (to - from).xy().magnitude() > step;
+            prop_assume!(clear(&a, &b) && clear(&b, &c) && clear(&c, &a));
+
+            let edges = Edges::new(&a, &b, &c).unwrap();
+            let values = edges.values(snap(&p));
+            prop_assert!(edges.inside(values));
+            let (w1, w2, w3) = edges.weights(values);
+            prop_assert!((w1 + w2 + w3 - 1.0).abs() < 1e-3);
+            prop_assert!(w1 > -1e-3 && w2 > -1e-3 && w3 > -1e-3);
+        }
+
+        // Never rules out bounds with a pixel inside
+        #[test]
+        fn reaching_misses_no_pixels(a in point(), b in point(), c in point(), from in point(), size in (0i32..40, 0i32..40)) {
+            let (a, b, c) = (on_grid(a), on_grid(b), on_grid(c));
+            prop_assume!(edge_function(&a, &b, &c).abs() > 1.0);
+            let edges = Edges::new(&a, &b, &c).unwrap();
+            let bounds = (from.x as i32, from.y as i32, from.x as i32 + size.0, from.y as i32 + size.1);
+            let mut inside = 0;
+            every_pixel(&edges, bounds, |_, _, _| inside += 1);
+            prop_assert!(inside == 0 || edges.reaches(bounds));
+        }
+
+        // Solves p = a + s(b - a) + t(c - a) directly instead of going
+        // through edge functions
+        #[test]
+        fn edge_functions_agree_with_a_direct_inside_test(a in point(), b in point(), c in point(), p in point()) {
+            let (a, b, c, p) = (on_grid(a), on_grid(b), on_grid(c), on_grid(p));
+            prop_assume!(edge_function(&a, &b, &c).abs() > 1.0);
+            let basis = Mat2::new(b.x - a.x, c.x - a.x, b.y - a.y, c.y - a.y);
+            let st = basis.try_inverse().unwrap() * Vec2::new(p.x - a.x, p.y - a.y);
+            let (s, t) = (st.x, st.y);
+            // Points right on an edge could go either way
+            prop_assume!(s.abs() > 1e-3 && t.abs() > 1e-3 && (1.0 - s - t).abs() > 1e-3);
+
+            let covers = |edges: Edges| edges.inside(edges.values(snap(&p)));
+            let inside = covers(Edges::new(&a, &b, &c).unwrap());
+            prop_assert_eq!(inside, s >= 0.0 && t >= 0.0 && s + t <= 1.0);
+            // The other winding covers the same points
+            prop_assert_eq!(inside, covers(Edges::new(&a, &c, &b).unwrap()));
+        }
+
+        // Integer coordinates keep the three points exactly on one line
+        #[test]
+        fn zero_area_triangles_give_no_fragments(
+            x in 0i32..100, y in 0i32..100, dx in -5i32..5, dy in -5i32..5, k1 in -10i32..10, k2 in -10i32..10,
+        ) {
+            let at = |k: i32| Vec3::new((x + dx * k) as f32, (y + dy * k) as f32, 1.0);
+            let (a, b, c) = (at(0), at(k1), at(k2));
+            prop_assert_eq!(edge_function(&a, &b, &c), 0.0);
+            prop_assert!(Edges::new(&a, &b, &c).is_none());
+            prop_assert!(triangle(&vertex(a), &vertex(b), &vertex(c), &SCREEN).is_empty());
+        }
+
+        // Stepping the edge functions visits the same pixels with the same
+        // weights as working them out at every pixel of the bounds
+        #[test]
+        fn traversal_matches_testing_every_pixel(a in point(), b in point(), c in point(), bounds in (0i32..200, 0i32..200, 0i32..200, 0i32..200)) {
+            let Some(edges) = Edges::new(&a, &b, &c) else {
+                return Ok(());
+            };
+            let bounds = (bounds.0.min(bounds.2), bounds.1.min(bounds.3), bounds.0.max(bounds.2), bounds.1.max(bounds.3));
+            let mut stepped = Vec::new();
+            edges.traverse(bounds, |x, y, weights| stepped.push((x, y, weights)));
+            let mut tested = Vec::new();
+            every_pixel(&edges, bounds, |x, y, weights| tested.push((x, y, weights)));
+            prop_assert_eq!(stepped, tested);
+        }
+
+        // Any quad split along a diagonal, either triangle wound either way
+        #[test]
+        fn a_shared_edge_is_drawn_once(a in point(), b in point(), c in point(), d in point(), flip in (any::<bool>(), any::<bool>())) {
+            let (a, b, c, d) = (on_grid(a), on_grid(b), on_grid(c), on_grid(d));
+            // c and d on opposite sides of the shared edge a-b
+            prop_assume!(edge_function(&a, &b, &c) * edge_function(&a, &b, &d) < -1.0);
+            let first = if flip.0 { [a, b, c] } else { [b, a, c] };
+            let second = if flip.1 { [a, b, d] } else { [b, a, d] };
+            prop_assert!(coverage(&[first, second]).values().all(|&count| count == 1));
+        }
+    }
+
+    // A fan around a center point, like the triangles at a sphere's pole,
+    // with its edges running right through pixel centers: every pixel inside
+    // the outline is drawn exactly once, whichever way each triangle winds
+    #[test]
+    fn a_triangle_fan_leaves_no_gaps_or_overlaps() {
+        let center = Vec3::new(100.5, 100.5, 0.0);
+        let rim: Vec<Vec3> = [(40.0, 0.0), (30.0, -30.0), (0.0, -40.0), (-30.0, -30.0), (-40.0, 0.0), (-30.0, 30.0), (0.0, 40.0), (30.0, 30.0)]
+            .iter()
+            .map(|&(dx, dy)| center + Vec3::new(dx, dy, 0.0))
+            .collect();
+        let fan: Vec<[Vec3; 3]> = (0..rim.len())
+            .map(|i| {
+                let (from, to) = (rim[i], rim[(i + 1) % rim.len()]);
+                if i % 2 == 0 { [center, from, to] } else { [center, to, from] }
+            })
+            .collect();
+        let counts = coverage(&fan);
+        assert!(counts.values().all(|&count| count == 1));
+
+        // The outline is convex, so inside means inside every rim edge
+        let mut inside_pixels = 0;
+        for y in 50..150 {
+            for x in 50..150 {
+                let p = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+                let inside = (0..rim.len()).all(|i| edge_function(&rim[i], &rim[(i + 1) % rim.len()], &p) > 0.0);
+                if inside {
+                    inside_pixels += 1;
+                    assert_eq!(counts.get(&(x, y)), Some(&1), "gap at ({}, {})", x, y);
+                }
+            }
+        }
+        assert!(inside_pixels > 4000);
+    }
+
+    // Timing rather than correctness, so only run when asked for:
+    //     cargo test --release rasterizer_speed -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn rasterizer_speed() {
+        let edges = Edges::new(&Vec3::new(0.0, 0.0, 0.0), &Vec3::new(300.0, 0.0, 0.0), &Vec3::new(0.0, 300.0, 0.0)).unwrap();
+        let bounds = (0, 0, 300, 300);
+        let mut criterion = Criterion::default();
+        let mut group = criterion.benchmark_group("300x300 triangle");
+        group.bench_function("every pixel", |bencher| bencher.iter(|| {
+            let mut total = 0.0;
+            every_pixel(&edges, black_box(bounds), |_, _, (w1, _, _)| total += w1);
+            black_box(total)
+        }));
+        group.bench_function("stepped", |bencher| bencher.iter(|| {
+            let mut total = 0.0;
+            edges.traverse(black_box(bounds), |_, _, (w1, _, _)| total += w1);
+            black_box(total)
+        }));
+        group.finish();
+    }
+
+    // Corners at w 1, 3 and 1 with the screen centroid, (120, 110), on a
+    // pixel center: there the plain weights are a third each, the true ones
+    // 3/7, 1/7 and 3/7
+    fn skewed_corners() -> [Vertex; 3] {
+        let corner = |x: f32, y: f32, z: f32, w: f32, color: Color| Vertex {
+            transformed_position: Vec3::new(x, y, z),
+            clip_position: Vec4::new(0.0, 0.0, 0.0, w),
+            color,
+            ..vertex(Vec3::zeros())
+        };
+        [
+            corner(100.5, 100.5, 0.2, 1.0, Color::new(210, 0, 0)),
+            corner(160.5, 100.5, 0.5, 3.0, Color::new(0, 210, 0)),
+            corner(100.5, 130.5, 0.8, 1.0, Color::new(0, 0, 210)),
+        ]
+    }
+
+    // Depth, being z / w, stays the plain average
+    #[test]
+    fn attributes_at_the_centroid_are_perspective_correct() {
+        let corners = skewed_corners();
+        let fragments = triangle(&corners[0], &corners[1], &corners[2], &SCREEN);
+        let centroid = fragments.iter().find(|fragment| fragment.position == Vec2::new(120.0, 110.0)).unwrap();
+        assert_eq!(centroid.color, Color::new(90, 30, 90));
+        assert!((centroid.depth - 0.5).abs() < 1e-5);
+    }
+
+    // The retro look turns the correction off for colors
+    #[test]
+    fn affine_frames_keep_the_plain_weights() {
+        let projection = ScreenProjection {
+            view_matrix: Mat4::identity(),
+            projection_matrix: Mat4::identity(),
+            viewport_matrix: Mat4::identity(),
+            snap_to_pixels: false,
+            affine: true,
+        };
+        let lighting = Lighting { lights: &[], ambient: Vec3::zeros(), exposure: 1.0, heat: None, detail: Detail::FULL, shading: ShadingModel::Flat };
+        let frame = FrameUniforms::new(&projection, lighting, 0.0, SCREEN);
+        let uniforms = Uniforms::new(&frame, Mat4::identity(), ShaderType::RockyPlanet);
+        let corners = skewed_corners();
+        let setup = Setup::new(&corners[0], &corners[1], &corners[2], &SCREEN, Some(&uniforms)).unwrap();
+        let mut centroid = None;
+        setup.rasterize(&SCREEN, |fragment| {
+            if fragment.position == Vec2::new(120.0, 110.0) {
+                centroid = Some(fragment);
+            }
+        });
+        assert_eq!(centroid.unwrap().color, Color::new(70, 70, 70));
+    }
+
+    // A triangle slanting away from a pinhole camera: projecting each
+    // fragment's world position again has to land on that fragment
+    #[test]
+    fn world_positions_are_interpolated_perspective_correctly() {
+        let project = |world: Vec3| Vec3::new(100.0 + 100.0 * world.x / world.z, 100.0 + 100.0 * world.y / world.z, 0.0);
+        let corner = |world: Vec3| Vertex {
+            transformed_position: project(world),
+            world_position: world,
+            clip_position: Vec4::new(0.0, 0.0, 0.0, world.z),
+            ..vertex(world)
+        };
+        let corners = [Vec3::new(0.0, 0.0, 1.0), Vec3::new(3.0, 0.0, 3.0), Vec3::new(0.0, 1.0, 1.0)].map(corner);
+        let fragments = triangle(&corners[0], &corners[1], &corners[2], &SCREEN);
+        assert!(fragments.len() > 1000);
+        for fragment in &fragments {
+            let seen = project(fragment.world_position);
+            assert!((seen.x - fragment.position.x - 0.5).abs() < 1e-2 && (seen.y - fragment.position.y - 0.5).abs() < 1e-2, "{} at {}", seen, fragment.position);
+        }
+    }
+}
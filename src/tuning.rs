@@ -0,0 +1,63 @@
+use crate::shader_params::ShaderParams;
+
+// Shader tuning panel (R): lists the parameters of one body's shader, the
+// arrow keys pick one and nudge it, and the body is redrawn with it at once
+pub struct ShaderTuner {
+    // Index of the body being tuned
+    pub body: usize,
+    selected: usize,
+}
+
+impl ShaderTuner {
+    // None for shaders without anything to tune
+    pub fn open(body: usize, params: &ShaderParams) -> Option<Self> {
+        (!params.tunables().is_empty()).then_some(ShaderTuner { body, selected: 0 })
+    }
+
+    // Moves the highlight up (-1) or down (+1), wrapping around
+    pub fn move_selection(&mut self, params: &ShaderParams, step: i32) {
+        let count = params.tunables().len() as i32;
+        self.selected = (self.selected as i32 + step).rem_euclid(count) as usize;
+    }
+
+    // Raises (+1) or lowers (-1) the highlighted value by a step, or by a
+    // tenth of one when `fine`
+    pub fn adjust(&self, params: &mut ShaderParams, direction: f32, fine: bool) {
+        if let Some(tunable) = params.tunables().get(self.selected) {
+            tunable.nudge(params, if fine { direction * 0.1 } else { direction });
+        }
+    }
+
+    // Panel text: the body, then one line per parameter
+    pub fn lines(&self, name: &str, params: &ShaderParams) -> Vec<String> {
+        let mut lines = vec![format!("Tuning {}", name)];
+        for (i, tunable) in params.tunables().iter().enumerate() {
+            let marker = if i == self.selected { ">" } else { " " };
+            lines.push(format!("{} {} {}", marker, tunable.name, tunable.value(params)));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ShaderType;
+
+    #[test]
+    fn arrows_pick_and_nudge_the_highlighted_value() {
+        assert!(ShaderTuner::open(0, &ShaderParams::Fixed).is_none());
+        let mut params = ShaderParams::defaults(ShaderType::RockyPlanet);
+        let mut tuner = ShaderTuner::open(3, &params).unwrap();
+        tuner.move_selection(&params, -1);
+        assert_eq!(tuner.selected, 2);
+        tuner.move_selection(&params, -1);
+        assert_eq!(tuner.selected, 1);
+
+        tuner.adjust(&mut params, 1.0, false);
+        tuner.adjust(&mut params, -1.0, true);
+        assert_eq!(params.rocky().iron_coverage, 0.445);
+        assert_eq!(params.rocky().noise_frequency, 1.0);
+        assert_eq!(tuner.lines("Mars", &params), vec!["Tuning Mars", "  noise_frequency 1", "> iron_coverage 0.445", "  ambient_occlusion 0.5"]);
+    }
+}
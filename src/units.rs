@@ -0,0 +1,156 @@
+use serde::Deserialize;
+
+// Formatting for values shown on the HUD. Scene distances are in abstract
+// units, shown as "u", unless the scene says how they map to real ones.
+
+// IAU 2012 definition, exact
+pub const KM_PER_AU: f64 = 149_597_870.7;
+// Below this many AU distances read better in kilometers
+const KM_BELOW_AU: f64 = 0.01;
+const HOURS_PER_DAY: f64 = 24.0;
+// Julian year
+const DAYS_PER_YEAR: f64 = 365.25;
+// Durations shorter than this many years are given in days
+const MIN_YEARS: f64 = 2.0;
+
+pub fn format_distance(distance: f32) -> String {
+    if distance >= 1000.0 {
+        format!("{:.2}k u", distance / 1000.0)
+    } else {
+        format!("{:.1} u", distance)
+    }
+}
+
+pub fn format_duration(seconds: f32) -> String {
+    if !seconds.is_finite() {
+        return "-".to_string();
+    }
+    let total = seconds.max(0.0);
+    if total < 60.0 {
+        format!("{:.1} s", total)
+    } else if total < 3600.0 {
+        let whole = total as u32;
+        format!("{}m {:02}s", whole / 60, whole % 60)
+    } else {
+        let minutes = total as u32 / 60;
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
+
+// [units] section of the scene file. Either can be left out, that quantity
+// then stays in world units and simulated seconds
+#[derive(Deserialize, Clone, Copy, Default)]
+pub struct Units {
+    // World units in one astronomical unit
+    #[serde(default)]
+    pub units_per_au: Option<f32>,
+    // Simulated seconds that stand for one hour
+    #[serde(default)]
+    pub seconds_per_hour: Option<f32>,
+}
+
+impl Units {
+    // Conversions are done in f64, kilometers across a solar system are
+    // past what f32 holds exactly
+    pub fn kilometers(self, distance: f32) -> Option<f64> {
+        self.units_per_au.map(|per_au| distance as f64 / per_au as f64 * KM_PER_AU)
+    }
+
+    pub fn hours(self, seconds: f32) -> Option<f64> {
+        self.seconds_per_hour.map(|per_hour| seconds as f64 / per_hour as f64)
+    }
+
+    // Kilometers up close, AU further out
+    pub fn distance(self, distance: f32) -> String {
+        let Some(km) = self.kilometers(distance) else { return format_distance(distance) };
+        let au = km / KM_PER_AU;
+        if au.abs() < KM_BELOW_AU {
+            if km.abs() < 1000.0 { format!("{:.1} km", km) } else { format!("{} km", group_thousands(km)) }
+        } else if au.abs() < 10.0 {
+            format!("{:.3} AU", au)
+        } else if au.abs() < 100.0 {
+            format!("{:.2} AU", au)
+        } else {
+            format!("{:.1} AU", au)
+        }
+    }
+
+    // Minutes, hours, days or years, whichever reads best
+    pub fn duration(self, seconds: f32) -> String {
+        let Some(hours) = self.hours(seconds).filter(|hours| hours.is_finite()) else { return format_duration(seconds) };
+        let hours = hours.max(0.0);
+        let days = hours / HOURS_PER_DAY;
+        if hours < 1.0 {
+            format!("{:.0} min", hours * 60.0)
+        } else if days < 2.0 {
+            format!("{:.1} h", hours)
+        } else if days < MIN_YEARS * DAYS_PER_YEAR {
+            format!("{:.1} days", days)
+        } else {
+            format!("{:.2} years", days / DAYS_PER_YEAR)
+        }
+    }
+
+    // Needs both conversions, otherwise world units per second
+    pub fn speed(self, units_per_second: f32) -> String {
+        match (self.kilometers(units_per_second), self.hours(1.0)) {
+            (Some(km), Some(hours)) => {
+                let km_per_second = km / (hours * 3600.0);
+                if km_per_second.abs() < 1000.0 { format!("{:.1} km/s", km_per_second) } else { format!("{} km/s", group_thousands(km_per_second)) }
+            }
+            _ => format!("{:.1} u/s", units_per_second),
+        }
+    }
+}
+
+// 384400 -> "384,400"
+fn group_thousands(value: f64) -> String {
+    let digits = (value.abs().round() as u64).to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    if value < 0.0 { format!("-{}", grouped) } else { grouped }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_distances_and_durations() {
+        assert_eq!(format_distance(250.0), "250.0 u");
+        assert_eq!(format_distance(1520.0), "1.52k u");
+        assert_eq!(format_duration(12.34), "12.3 s");
+        assert_eq!(format_duration(125.0), "2m 05s");
+        assert_eq!(format_duration(3725.0), "1h 02m");
+        assert_eq!(format_duration(f32::INFINITY), "-");
+    }
+
+    #[test]
+    fn converts_exactly_and_falls_back_to_world_units() {
+        let plain = Units::default();
+        assert_eq!(plain.distance(250.0), format_distance(250.0));
+        assert_eq!(plain.duration(125.0), format_duration(125.0));
+        assert_eq!(plain.speed(12.0), "12.0 u/s");
+
+        // 100 units to the AU, and a simulated second for every hour
+        let units = Units { units_per_au: Some(100.0), seconds_per_hour: Some(1.0) };
+        assert_eq!(units.kilometers(100.0), Some(KM_PER_AU));
+        assert_eq!(units.kilometers(50.0), Some(74_798_935.35));
+        assert_eq!(units.hours(36.0), Some(36.0));
+        assert_eq!(units.distance(100.0), "1.000 AU");
+        assert_eq!(units.distance(3007.0), "30.07 AU");
+        assert_eq!(units.distance(0.5), "747,989 km");
+        assert_eq!(units.distance(0.0001), "149.6 km");
+        assert_eq!(units.duration(0.5), "30 min");
+        assert_eq!(units.duration(30.0), "30.0 h");
+        assert_eq!(units.duration(24.0 * 687.0), "687.0 days");
+        assert_eq!(units.duration(24.0 * 365.25 * 11.86), "11.86 years");
+        // An AU an hour
+        assert_eq!(units.speed(100.0), "41,555 km/s");
+    }
+}
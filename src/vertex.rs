@@ -1,4 +1,4 @@
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 use crate::color::Color;
 
 #[derive(Clone, Debug)]
@@ -7,8 +7,18 @@ pub struct Vertex {
   pub normal: Vec3,
   pub tex_coords: Vec2,
   pub color: Color,
+  pub emission: f32, // Self-lit share of the color, 0 = only reflects light, 1 = only glows
+  // Screen position: x and y in pixels, z the depth. Only set by to_screen,
+  // after clipping; what comes straight out of the vertex shader has zeros
+  // here and must not be read as a position
   pub transformed_position: Vec3,
   pub transformed_normal: Vec3,
+  // Set by the vertex shader: where the vertex is in the world
+  pub world_position: Vec3,
+  // Also from the vertex shader, before clipping and the perspective
+  // divide turn it into transformed_position. Its w is kept for
+  // perspective-correct interpolation
+  pub clip_position: Vec4,
 }
 
 impl Vertex {
@@ -18,8 +28,11 @@ impl Vertex {
       normal,
       tex_coords,
       color: Color::black(),
+      emission: 0.0,
       transformed_position: position,
       transformed_normal: normal,
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      clip_position: Vec4::new(position.x, position.y, position.z, 1.0),
     }
   }
 
@@ -29,8 +42,11 @@ impl Vertex {
       normal: Vec3::new(0.0, 0.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
       color,
+      emission: 0.0,
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 0.0, 0.0),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
     }
   }
 
@@ -47,8 +63,11 @@ impl Default for Vertex {
       normal: Vec3::new(0.0, 1.0, 0.0),
       tex_coords: Vec2::new(0.0, 0.0),
       color: Color::black(),
+      emission: 0.0,
       transformed_position: Vec3::new(0.0, 0.0, 0.0),
       transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+      world_position: Vec3::new(0.0, 0.0, 0.0),
+      clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
     }
   }
 }
@@ -0,0 +1,315 @@
+use nalgebra_glm::Vec3;
+use serde::Deserialize;
+use crate::body::CelestialBody;
+use crate::camera::Camera;
+
+pub const WARP_COOLDOWN: f32 = 1.0;
+const WARP_DURATION: f32 = 1.0;
+// Warps stop this many bounding radii away from the body
+pub const WARP_DISTANCE_FACTOR: f32 = 6.0;
+// How long "ready" is announced once the cooldown is over
+const READY_FLASH_SECONDS: f32 = 0.8;
+// Solar charging is capped at this many times its rate at solar_distance,
+// so grazing the star doesn't refill instantly
+const MAX_SOLAR_BOOST: f32 = 4.0;
+// A refused warp shakes the warp widget this long
+const DENIED_SECONDS: f32 = 0.5;
+
+// [warp_energy] section of the scene file. Off by default, warps are then
+// free and always take WARP_DURATION
+#[derive(Deserialize, Clone, Copy)]
+pub struct WarpEnergySettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_capacity")]
+    pub capacity: f32,
+    // Energy per unit of distance a warp covers
+    #[serde(default = "default_cost_per_unit")]
+    pub cost_per_unit: f32,
+    // Energy per second anywhere, plus solar charging that is
+    // `solar_recharge` at `solar_distance` from a star and falls off with
+    // the square of the distance
+    #[serde(default = "default_recharge")]
+    pub recharge: f32,
+    #[serde(default = "default_solar_recharge")]
+    pub solar_recharge: f32,
+    #[serde(default = "default_solar_distance")]
+    pub solar_distance: f32,
+    // Warps take one more WARP_DURATION for every this many units
+    #[serde(default = "default_duration_distance")]
+    pub duration_distance: f32,
+}
+
+impl Default for WarpEnergySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: default_capacity(),
+            cost_per_unit: default_cost_per_unit(),
+            recharge: default_recharge(),
+            solar_recharge: default_solar_recharge(),
+            solar_distance: default_solar_distance(),
+            duration_distance: default_duration_distance(),
+        }
+    }
+}
+
+fn default_capacity() -> f32 {
+    100.0
+}
+
+fn default_cost_per_unit() -> f32 {
+    0.05
+}
+
+fn default_recharge() -> f32 {
+    1.0
+}
+
+fn default_solar_recharge() -> f32 {
+    4.0
+}
+
+fn default_solar_distance() -> f32 {
+    300.0
+}
+
+fn default_duration_distance() -> f32 {
+    1000.0
+}
+
+// Camera flight from wherever it was looking to a body
+struct WarpAnimation {
+    body: usize,
+    start_time: f32,
+    duration: f32,
+    from_target: Vec3,
+    from_distance: f32,
+    to_distance: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WarpState {
+    // Fraction of the way to the destination
+    Warping(f32),
+    // Fraction of the cooldown that has passed
+    Charging(f32),
+    // `just_charged` is set for a moment after the cooldown ends
+    Ready { just_charged: bool },
+}
+
+impl WarpState {
+    // Strength of the screen distortion: swells and fades over the flight,
+    // none otherwise
+    pub fn distortion(self) -> f32 {
+        match self {
+            WarpState::Warping(progress) => (progress * std::f32::consts::PI).sin().max(0.0),
+            _ => 0.0,
+        }
+    }
+}
+
+// Number-key warps: the cooldown between them and the camera flight itself
+#[derive(Default)]
+pub struct WarpSystem {
+    last_warp_time: Option<f32>,
+    animation: Option<WarpAnimation>,
+    // Body of the last warp
+    destination: Option<usize>,
+}
+
+impl WarpSystem {
+    pub fn state(&self, time: f32) -> WarpState {
+        if let Some(animation) = &self.animation {
+            return WarpState::Warping(animation_progress(animation, time));
+        }
+        match self.last_warp_time {
+            Some(last) if time - last < WARP_COOLDOWN => WarpState::Charging((time - last) / WARP_COOLDOWN),
+            Some(last) => WarpState::Ready { just_charged: time - last - WARP_COOLDOWN < READY_FLASH_SECONDS },
+            None => WarpState::Ready { just_charged: false },
+        }
+    }
+
+    pub fn is_warping(&self) -> bool {
+        self.animation.is_some()
+    }
+
+    pub fn destination(&self) -> Option<usize> {
+        self.destination
+    }
+
+    pub fn is_ready(&self, time: f32) -> bool {
+        matches!(self.state(time), WarpState::Ready { .. })
+    }
+
+    // Starts a `duration` second warp to `body`, ending `to_distance` away
+    // from it. Returns false (and does nothing) while the drive is still
+    // charging.
+    pub fn start(&mut self, body: usize, time: f32, camera: &Camera, to_distance: f32, duration: f32) -> bool {
+        if !self.is_ready(time) {
+            return false;
+        }
+        self.last_warp_time = Some(time);
+        self.destination = Some(body);
+        self.animation = Some(WarpAnimation {
+            body,
+            start_time: time,
+            duration,
+            from_target: camera.target,
+            from_distance: camera.distance,
+            to_distance,
+        });
+        true
+    }
+
+    // Drops the camera flight, the cooldown keeps running
+    pub fn cancel(&mut self) {
+        self.animation = None;
+    }
+
+    // Eases the camera onto the moving body. Returns the body once the warp
+    // has arrived, so the caller can start following it.
+    pub fn update(&mut self, time: f32, camera: &mut Camera, bodies: &[CelestialBody]) -> Option<usize> {
+        let animation = self.animation.as_ref()?;
+        let t = animation_progress(animation, time);
+        let eased = t * t * (3.0 - 2.0 * t);
+        let destination = bodies[animation.body].position;
+        camera.distance = animation.from_distance + (animation.to_distance - animation.from_distance) * eased;
+        camera.set_target(animation.from_target + (destination - animation.from_target) * eased);
+        if t >= 1.0 {
+            let body = animation.body;
+            self.animation = None;
+            Some(body)
+        } else {
+            None
+        }
+    }
+}
+
+fn animation_progress(animation: &WarpAnimation, time: f32) -> f32 {
+    ((time - animation.start_time) / animation.duration).clamp(0.0, 1.0)
+}
+
+// Optional energy budget for warps. Kept apart from WarpSystem, which is
+// reset on every reload, so the tank survives those
+pub struct WarpEnergy {
+    pub settings: WarpEnergySettings,
+    pub level: f32,
+    // When the last warp was refused, for the widget's shake
+    denied_at: Option<f32>,
+}
+
+impl WarpEnergy {
+    pub fn new(settings: WarpEnergySettings) -> Self {
+        Self { settings, level: settings.capacity, denied_at: None }
+    }
+
+    pub fn cost(&self, distance: f32) -> f32 {
+        if self.settings.enabled { distance * self.settings.cost_per_unit } else { 0.0 }
+    }
+
+    // Long jumps take longer when energy is on, all of them WARP_DURATION
+    // otherwise
+    pub fn duration(&self, distance: f32) -> f32 {
+        if self.settings.enabled {
+            WARP_DURATION * (1.0 + distance / self.settings.duration_distance.max(1.0))
+        } else {
+            WARP_DURATION
+        }
+    }
+
+    // Takes the energy for a warp, or remembers the refusal and returns
+    // false when there isn't enough
+    pub fn spend(&mut self, cost: f32, time: f32) -> bool {
+        if !self.settings.enabled {
+            return true;
+        }
+        if cost > self.level {
+            self.denied_at = Some(time);
+            return false;
+        }
+        self.level -= cost;
+        true
+    }
+
+    // Refills over `delta_time` real seconds, faster near a star
+    pub fn recharge(&mut self, observer: Vec3, bodies: &[CelestialBody], delta_time: f32) {
+        if !self.settings.enabled {
+            return;
+        }
+        let nearest_star = bodies.iter()
+            .filter(|body| body.is_star())
+            .map(|star| (star.position - observer).magnitude())
+            .fold(f32::INFINITY, f32::min);
+        let sunlight = (self.settings.solar_distance / nearest_star.max(1.0)).powi(2).min(MAX_SOLAR_BOOST);
+        let rate = self.settings.recharge + self.settings.solar_recharge * sunlight;
+        self.level = (self.level + rate * delta_time).min(self.settings.capacity);
+    }
+
+    // Share of a full tank, None when energy is off
+    pub fn fraction(&self) -> Option<f32> {
+        self.settings.enabled.then(|| self.level / self.settings.capacity.max(f32::EPSILON))
+    }
+
+    // Horizontal shake in pixels for the warp widget after a refusal
+    pub fn shake(&self, time: f32) -> i32 {
+        match self.denied_at {
+            Some(denied) if time - denied < DENIED_SECONDS => ((time - denied) * 60.0).sin().signum() as i32 * 3,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    #[test]
+    fn warp_goes_through_flight_charge_and_ready() {
+        let sun = CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::new(100.0, 0.0, 0.0), 1.0, Color::from_hex(0xFFFFFF));
+        let bodies = vec![sun];
+        let mut camera = Camera::new(Vec3::zeros(), 500.0);
+        let mut warp = WarpSystem::default();
+        assert_eq!(warp.state(0.0), WarpState::Ready { just_charged: false });
+
+        assert!(warp.start(0, 0.0, &camera, 50.0, WARP_DURATION));
+        assert!(!warp.start(0, 0.5, &camera, 50.0, WARP_DURATION));
+        assert_eq!(warp.update(0.5, &mut camera, &bodies), None);
+        assert_eq!(warp.state(0.5), WarpState::Warping(0.5));
+
+        assert_eq!(warp.update(WARP_DURATION, &mut camera, &bodies), Some(0));
+        assert_eq!(camera.target, bodies[0].position);
+        assert_eq!(camera.distance, 50.0);
+
+        let charged = WARP_COOLDOWN + 0.1;
+        assert_eq!(warp.state(charged), WarpState::Ready { just_charged: true });
+        assert_eq!(warp.state(charged + READY_FLASH_SECONDS), WarpState::Ready { just_charged: false });
+        assert_eq!(warp.destination(), Some(0));
+    }
+
+    #[test]
+    fn warps_cost_energy_only_when_enabled() {
+        let bodies = vec![CelestialBody::new_sun("Sun".to_string(), Vec::new(), Vec3::zeros(), 1.0, Color::from_hex(0xFFFFFF))];
+        let mut unlimited = WarpEnergy::new(WarpEnergySettings::default());
+        assert_eq!(unlimited.cost(1e6), 0.0);
+        assert!(unlimited.spend(unlimited.cost(1e6), 0.0));
+        assert_eq!(unlimited.duration(1e6), WARP_DURATION);
+        assert_eq!(unlimited.fraction(), None);
+
+        let settings = WarpEnergySettings { enabled: true, ..WarpEnergySettings::default() };
+        let mut energy = WarpEnergy::new(settings);
+        assert!(energy.spend(energy.cost(1500.0), 0.0));
+        assert!(!energy.spend(energy.cost(1500.0), 1.0));
+        assert_ne!(energy.shake(1.1), 0);
+        assert_eq!(energy.shake(2.0), 0);
+        assert!(energy.duration(2000.0) > energy.duration(100.0));
+
+        // Charging is faster close to the star
+        let level = energy.level;
+        energy.recharge(Vec3::new(settings.solar_distance * 4.0, 0.0, 0.0), &bodies, 1.0);
+        let far = energy.level - level;
+        energy.recharge(Vec3::new(settings.solar_distance, 0.0, 0.0), &bodies, 1.0);
+        assert!(energy.level - level - far > far * 2.0);
+    }
+}